@@ -0,0 +1,61 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use shard_lib::vector_index::{brute_force_search, DenseIndex};
+use std::time::Duration;
+
+const DIMS: usize = 256;
+
+fn sample_vectors(n: usize, seed: u64) -> Vec<(String, Vec<f32>)> {
+    // Deterministic xorshift vectors; real embeddings cluster more, which favours HNSW further
+    let mut state = seed;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % 2000) as f32 / 1000.0 - 1.0
+    };
+    (0..n)
+        .map(|i| (format!("doc_{i}"), (0..DIMS).map(|_| next()).collect()))
+        .collect()
+}
+
+fn bench_dense_search(c: &mut Criterion) {
+    let query = sample_vectors(1, 99).remove(0).1;
+    let mut group = c.benchmark_group("dense_search");
+
+    for size in [1_000, 10_000] {
+        let entries = sample_vectors(size, 42);
+        let index = DenseIndex::build("bench", DIMS, entries.clone());
+
+        group.bench_function(format!("linear_{size}"), |b| {
+            b.iter(|| brute_force_search(black_box(&entries), black_box(&query), 50))
+        });
+        group.bench_function(format!("hnsw_{size}"), |b| {
+            b.iter(|| index.search(black_box(&query), 50))
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_dense_build(c: &mut Criterion) {
+    let entries = sample_vectors(1_000, 42);
+    let mut group = c.benchmark_group("dense_build");
+    group.sample_size(10);
+    group.bench_function("hnsw_1000", |b| {
+        b.iter(|| DenseIndex::build("bench", DIMS, black_box(entries.clone())))
+    });
+    group.finish();
+}
+
+fn configure_criterion() -> Criterion {
+    Criterion::default()
+        .noise_threshold(0.05)
+        .measurement_time(Duration::from_secs(5))
+}
+
+criterion_group! {
+    name = benches;
+    config = configure_criterion();
+    targets = bench_dense_search, bench_dense_build
+}
+criterion_main!(benches);
@@ -0,0 +1,207 @@
+// Anthropic Messages API utilities - message mapping and streaming event parsing
+// The system prompt lives outside `messages`, and tool calls/results travel as
+// `tool_use` / `tool_result` content blocks instead of separate fields.
+
+use super::types::*;
+use crate::config::AppConfig;
+use serde_json::Value;
+
+pub const ANTHROPIC_MESSAGES_URL: &str = "https://api.anthropic.com/v1/messages";
+pub const ANTHROPIC_VERSION: &str = "2023-06-01";
+/// Anthropic requires an explicit output cap
+pub const DEFAULT_ANTHROPIC_MAX_TOKENS: u32 = 8192;
+/// Model name suffix that routes to the Anthropic API directly
+pub const ANTHROPIC_MODEL_SUFFIX: &str = "(Anthropic)";
+
+/// Whether a model should go to Anthropic: a bare `claude-*` name or the "(Anthropic)"
+/// suffix, or an OpenRouter-style `anthropic/...` id once an Anthropic key is configured
+pub fn is_anthropic_model(model: &str, config: &AppConfig) -> bool {
+    let model = model.trim();
+    model.contains(ANTHROPIC_MODEL_SUFFIX)
+        || model.starts_with("claude-")
+        || (model.starts_with("anthropic/") && config.anthropic_api_key.is_some())
+}
+
+/// Model id as Anthropic expects it ("anthropic/claude-sonnet-4.5" -> "claude-sonnet-4-5")
+pub fn anthropic_model_id(model: &str) -> String {
+    let model = model.replace(ANTHROPIC_MODEL_SUFFIX, "");
+    let model = model.trim();
+    model.strip_prefix("anthropic/").unwrap_or(model).replace('.', "-")
+}
+
+/// Convert tool definitions to Anthropic's flat `input_schema` format
+pub fn to_anthropic_tools(tools: &[ToolDefinition]) -> Vec<AnthropicTool> {
+    tools
+        .iter()
+        .map(|t| AnthropicTool {
+            name: t.function.name.clone(),
+            description: t.function.description.clone(),
+            input_schema: t.function.parameters.clone(),
+        })
+        .collect()
+}
+
+/// Convert chat history to Anthropic messages. System messages are dropped (the prompt
+/// goes in `system`), tool results become user `tool_result` blocks, and consecutive
+/// messages with the same role are merged since the API expects alternating turns.
+pub fn construct_anthropic_messages(history: &[ChatMessage]) -> Vec<AnthropicMessage> {
+    let mut messages: Vec<AnthropicMessage> = Vec::new();
+
+    for msg in history {
+        let (role, content) = match msg.role.as_str() {
+            "system" => continue,
+            "tool" => (
+                "user",
+                vec![AnthropicContent::ToolResult {
+                    tool_use_id: msg.tool_call_id.clone().unwrap_or_default(),
+                    content: msg.content.clone().unwrap_or_default(),
+                }],
+            ),
+            "assistant" | "model" => {
+                let mut blocks = Vec::new();
+                if let Some(text) = msg.content.as_ref().filter(|t| !t.trim().is_empty()) {
+                    blocks.push(AnthropicContent::Text { text: text.clone() });
+                }
+                for call in msg.tool_calls.iter().flatten() {
+                    blocks.push(AnthropicContent::ToolUse {
+                        id: call.id.clone(),
+                        name: call.function.name.clone(),
+                        input: serde_json::from_str(&call.function.arguments)
+                            .unwrap_or_else(|_| Value::Object(Default::default())),
+                    });
+                }
+                ("assistant", blocks)
+            }
+            _ => {
+                let mut blocks = Vec::new();
+                for img in msg.images.iter().flatten() {
+                    blocks.push(AnthropicContent::Image {
+                        source: AnthropicImageSource {
+                            source_type: "base64".to_string(),
+                            media_type: img.mime_type.clone(),
+                            data: img.base64.clone(),
+                        },
+                    });
+                }
+                if let Some(text) = msg.content.as_ref().filter(|t| !t.trim().is_empty()) {
+                    blocks.push(AnthropicContent::Text { text: text.clone() });
+                }
+                ("user", blocks)
+            }
+        };
+        if content.is_empty() {
+            continue;
+        }
+
+        match messages.last_mut() {
+            Some(last) if last.role == role => last.content.extend(content),
+            _ => messages.push(AnthropicMessage {
+                role: role.to_string(),
+                content,
+            }),
+        }
+    }
+
+    messages
+}
+
+/// What a single streamed `data:` payload means for the turn
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnthropicStreamEvent {
+    Text(String),
+    Thinking(String),
+    ToolUseStart { index: usize, id: String, name: String },
+    ToolInputDelta { index: usize, partial_json: String },
+    Stop(String), // stop_reason, e.g. "end_turn" / "tool_use" / "max_tokens"
+    Error(String),
+}
+
+/// Parse one SSE `data:` payload. Bookkeeping events (message_start, ping,
+/// content_block_stop, ...) return None.
+pub fn parse_anthropic_event(data: &str) -> Option<AnthropicStreamEvent> {
+    let json: Value = serde_json::from_str(data).ok()?;
+    let index = json["index"].as_u64().unwrap_or(0) as usize;
+
+    match json["type"].as_str()? {
+        "content_block_start" => {
+            let block = &json["content_block"];
+            match block["type"].as_str()? {
+                "tool_use" => Some(AnthropicStreamEvent::ToolUseStart {
+                    index,
+                    id: block["id"].as_str().unwrap_or_default().to_string(),
+                    name: block["name"].as_str().unwrap_or_default().to_string(),
+                }),
+                "text" => block["text"]
+                    .as_str()
+                    .filter(|t| !t.is_empty())
+                    .map(|t| AnthropicStreamEvent::Text(t.to_string())),
+                _ => None,
+            }
+        }
+        "content_block_delta" => {
+            let delta = &json["delta"];
+            match delta["type"].as_str()? {
+                "text_delta" => Some(AnthropicStreamEvent::Text(delta["text"].as_str()?.to_string())),
+                "thinking_delta" => Some(AnthropicStreamEvent::Thinking(delta["thinking"].as_str()?.to_string())),
+                "input_json_delta" => Some(AnthropicStreamEvent::ToolInputDelta {
+                    index,
+                    partial_json: delta["partial_json"].as_str()?.to_string(),
+                }),
+                _ => None,
+            }
+        }
+        "message_delta" => json["delta"]["stop_reason"]
+            .as_str()
+            .map(|reason| AnthropicStreamEvent::Stop(reason.to_string())),
+        "error" => Some(AnthropicStreamEvent::Error(
+            json["error"]["message"].as_str().unwrap_or("Unknown Anthropic stream error").to_string(),
+        )),
+        _ => None,
+    }
+}
+
+/// Collects streamed `tool_use` blocks (keyed by content block index) into ToolCalls
+#[derive(Debug, Default)]
+pub struct ToolUseAccumulator {
+    calls: Vec<(usize, ToolCall)>,
+}
+
+impl ToolUseAccumulator {
+    pub fn start(&mut self, index: usize, id: String, name: String) {
+        self.calls.push((
+            index,
+            ToolCall {
+                id,
+                tool_type: "function".to_string(),
+                function: FunctionCall {
+                    name,
+                    arguments: String::new(),
+                },
+                thought_signature: None,
+            },
+        ));
+    }
+
+    pub fn push_input(&mut self, index: usize, partial_json: &str) {
+        if let Some((_, call)) = self.calls.iter_mut().find(|(i, _)| *i == index) {
+            call.function.arguments.push_str(partial_json);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+
+    /// Finished calls in stream order; a tool with no input gets "{}"
+    pub fn finish(self) -> Vec<ToolCall> {
+        self.calls
+            .into_iter()
+            .map(|(_, mut call)| {
+                if call.function.arguments.trim().is_empty() {
+                    call.function.arguments = "{}".to_string();
+                }
+                call
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,103 @@
+// Streaming chunk coalescing - batches tiny model deltas into fewer
+// `agent-response-chunk` events so the UI isn't flooded during a response.
+
+use futures_util::{Stream, StreamExt};
+use std::time::{Duration, Instant};
+
+/// Default time window between emits
+pub const DEFAULT_COALESCE_MS: u64 = 50;
+/// Default buffered size that forces an emit before the window elapses
+pub const DEFAULT_COALESCE_CHARS: usize = 64;
+
+/// Buffers streamed text, releasing it once `window` has passed since the last emit
+/// or `max_chars` have piled up. A zero window disables coalescing.
+#[derive(Debug)]
+pub struct ChunkCoalescer {
+    pending: String,
+    window: Duration,
+    max_chars: usize,
+    last_emit: Option<Instant>,
+}
+
+impl ChunkCoalescer {
+    pub fn new(window: Duration, max_chars: usize) -> Self {
+        Self {
+            pending: String::new(),
+            window,
+            max_chars: max_chars.max(1),
+            last_emit: None,
+        }
+    }
+
+    /// Window from `stream_coalesce_ms` / `stream_coalesce_chars`
+    pub fn from_config(config: &crate::config::AppConfig) -> Self {
+        Self::new(
+            Duration::from_millis(config.stream_coalesce_ms.unwrap_or(DEFAULT_COALESCE_MS)),
+            config.stream_coalesce_chars.unwrap_or(DEFAULT_COALESCE_CHARS),
+        )
+    }
+
+    /// Add streamed text; returns what should be emitted now, if anything.
+    /// The first chunk after a quiet spell goes straight out so slow streams don't lag.
+    pub fn push(&mut self, text: &str, now: Instant) -> Option<String> {
+        if text.is_empty() {
+            return None;
+        }
+        self.pending.push_str(text);
+
+        let window_elapsed = match self.last_emit {
+            Some(last) => now.saturating_duration_since(last) >= self.window,
+            None => true,
+        };
+        if window_elapsed || self.pending.chars().count() >= self.max_chars {
+            self.last_emit = Some(now);
+            return Some(std::mem::take(&mut self.pending));
+        }
+        None
+    }
+
+    /// When held text is due out if no further chunk arrives
+    pub fn deadline(&self) -> Option<Instant> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        Some(self.last_emit.map_or_else(Instant::now, |last| last + self.window))
+    }
+
+    /// Release held text once its window has passed (stream stalled mid-window)
+    pub fn flush_due(&mut self, now: Instant) -> Option<String> {
+        if self.deadline().is_none_or(|deadline| now < deadline) {
+            return None;
+        }
+        self.last_emit = Some(now);
+        self.flush()
+    }
+
+    /// Release everything buffered (stream end, tool-call boundary)
+    pub fn flush(&mut self) -> Option<String> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        Some(std::mem::take(&mut self.pending))
+    }
+}
+
+/// Wait for the next stream item, handing held text to `emit` if its window
+/// passes first so a stalled stream doesn't hide the tail of the response
+pub async fn next_or_flush<S: Stream + Unpin>(
+    stream: &mut S,
+    coalescer: &mut ChunkCoalescer,
+    mut emit: impl FnMut(String),
+) -> Option<S::Item> {
+    loop {
+        let Some(deadline) = coalescer.deadline() else {
+            return stream.next().await;
+        };
+        if let Ok(item) = tokio::time::timeout_at(deadline.into(), stream.next()).await {
+            return item;
+        }
+        if let Some(text) = coalescer.flush_due(Instant::now()) {
+            emit(text);
+        }
+    }
+}
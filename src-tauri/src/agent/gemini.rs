@@ -9,6 +9,42 @@ pub enum AgentEvent {
     ReasoningChunk(String),
 }
 
+/// Largest decoded image sent inline; bigger images must go through the Files API
+pub const MAX_INLINE_IMAGE_BYTES: usize = 4 * 1024 * 1024;
+
+/// Decoded size of a base64 payload (without decoding it)
+fn base64_decoded_len(data: &str) -> usize {
+    let padding = data.bytes().rev().take_while(|&b| b == b'=').count();
+    (data.len() / 4 * 3).saturating_sub(padding)
+}
+
+/// Thinking budget used when the config doesn't set one
+pub const DEFAULT_THINKING_BUDGET: i32 = 1024;
+
+/// Whether a Gemini model accepts a thinkingConfig
+pub fn supports_thinking(model: &str) -> bool {
+    model.contains("2.5") || model.contains("gemini-3") || model.contains("thinking")
+}
+
+/// Build the thinkingConfig for a model, with config overrides taking precedence
+/// over the defaults. A budget of 0 disables thinking (and thought output).
+pub fn build_thinking_config(
+    model: &str,
+    config: &crate::config::AppConfig,
+) -> Option<ThinkingConfig> {
+    if !supports_thinking(model) {
+        return None;
+    }
+
+    let thinking_budget = config.thinking_budget.unwrap_or(DEFAULT_THINKING_BUDGET);
+    let include_thoughts = thinking_budget != 0 && config.include_thoughts.unwrap_or(true);
+
+    Some(ThinkingConfig {
+        include_thoughts,
+        thinking_budget: Some(thinking_budget),
+    })
+}
+
 /// Convert chat history to Gemini API format
 pub fn construct_gemini_messages(history: &[ChatMessage]) -> Vec<GeminiContent> {
     let mut contents: Vec<GeminiContent> = Vec::new();
@@ -86,6 +122,22 @@ pub fn construct_gemini_messages(history: &[ChatMessage]) -> Vec<GeminiContent>
                                 file_uri: uri.clone(),
                             },
                         });
+                    } else if img.base64.is_empty() {
+                        continue;
+                    } else if base64_decoded_len(&img.base64) <= MAX_INLINE_IMAGE_BYTES {
+                        // Upload failed or was skipped: small images can still go inline
+                        parts.push(GeminiPart::InlineData {
+                            inline_data: GeminiInlineData {
+                                mime_type: img.mime_type.clone(),
+                                data: img.base64.clone(),
+                            },
+                        });
+                    } else {
+                        log::warn!(
+                            "[Gemini] Dropping {} image over the {}MB inline limit (upload required)",
+                            img.mime_type,
+                            MAX_INLINE_IMAGE_BYTES / (1024 * 1024)
+                        );
                     }
                 }
             }
@@ -116,6 +168,157 @@ pub fn construct_gemini_messages(history: &[ChatMessage]) -> Vec<GeminiContent>
     contents
 }
 
+/// Why Gemini stopped generating
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FinishReason {
+    Stop,
+    MaxTokens,
+    /// SAFETY, RECITATION, BLOCKLIST, PROHIBITED_CONTENT, ... (or a blocked prompt)
+    Blocked(String),
+    Other(String),
+}
+
+impl FinishReason {
+    pub fn parse(reason: &str) -> Self {
+        match reason {
+            "STOP" => FinishReason::Stop,
+            "MAX_TOKENS" => FinishReason::MaxTokens,
+            "SAFETY" | "RECITATION" | "BLOCKLIST" | "PROHIBITED_CONTENT" | "SPII"
+            | "IMAGE_SAFETY" => FinishReason::Blocked(reason.to_string()),
+            other => FinishReason::Other(other.to_string()),
+        }
+    }
+
+    /// Event name and message to emit for an abnormal finish (None for STOP).
+    /// Abnormal finishes must not trigger the empty-response retry.
+    pub fn abnormal_event(&self) -> Option<(&'static str, String)> {
+        match self {
+            FinishReason::Stop => None,
+            FinishReason::MaxTokens => Some((
+                "agent-truncated",
+                "Response was cut off: the model hit its output token limit.".to_string(),
+            )),
+            FinishReason::Blocked(reason) => Some((
+                "agent-error",
+                format!("Gemini blocked the response ({}). Try rephrasing the request.", reason),
+            )),
+            FinishReason::Other(reason) => Some((
+                "agent-error",
+                format!("Gemini stopped unexpectedly ({}).", reason),
+            )),
+        }
+    }
+}
+
+/// Finish reason carried by a streamed chunk, if any (a blocked prompt counts as Blocked)
+pub fn chunk_finish_reason(response: &GenerateContentResponse) -> Option<FinishReason> {
+    let candidate_reason = response
+        .candidates
+        .iter()
+        .flatten()
+        .find_map(|c| c.finish_reason.as_deref())
+        .map(FinishReason::parse);
+
+    candidate_reason.or_else(|| {
+        response
+            .prompt_feedback
+            .as_ref()
+            .and_then(|f| f.block_reason.as_ref())
+            .map(|reason| FinishReason::Blocked(reason.clone()))
+    })
+}
+
+/// Incremental reader for `streamGenerateContent`: a JSON array whose elements arrive
+/// split across arbitrary byte chunks. Complete objects are returned as soon as they
+/// close and the unfinished remainder is kept for the next chunk.
+#[derive(Debug, Default)]
+pub struct GeminiStreamReader {
+    buffer: Vec<u8>,
+    dropped_bytes: usize,
+}
+
+/// Start of the next object at or after `from` (or the buffer end)
+fn next_object_start(buffer: &[u8], from: usize) -> usize {
+    buffer[from.min(buffer.len())..]
+        .iter()
+        .position(|&b| b == b'{')
+        .map_or(buffer.len(), |offset| from + offset)
+}
+
+impl GeminiStreamReader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a network chunk and return every response object it completed
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<GenerateContentResponse> {
+        self.buffer.extend_from_slice(chunk);
+        let mut responses = Vec::new();
+        let mut pos = 0;
+
+        loop {
+            // Array brackets, separators and whitespace between elements
+            while pos < self.buffer.len()
+                && (matches!(self.buffer[pos], b'[' | b']' | b',') || self.buffer[pos].is_ascii_whitespace())
+            {
+                pos += 1;
+            }
+            if pos >= self.buffer.len() {
+                break;
+            }
+
+            let skip_from = if self.buffer[pos] == b'{' {
+                let mut objects = serde_json::Deserializer::from_slice(&self.buffer[pos..]).into_iter::<Value>();
+                match objects.next() {
+                    Some(Ok(value)) => {
+                        let end = pos + objects.byte_offset();
+                        match serde_json::from_value::<GenerateContentResponse>(value) {
+                            Ok(response) => responses.push(response),
+                            Err(e) => {
+                                log::warn!("[Gemini] Dropped unrecognized stream object ({} bytes): {}", end - pos, e);
+                                self.dropped_bytes += end - pos;
+                            }
+                        }
+                        pos = end;
+                        continue;
+                    }
+                    // Object still arriving
+                    Some(Err(e)) if e.is_eof() => break,
+                    Some(Err(e)) => {
+                        log::warn!("[Gemini] Malformed stream object, resyncing: {}", e);
+                        pos + 1
+                    }
+                    None => break,
+                }
+            } else {
+                pos
+            };
+
+            let next = next_object_start(&self.buffer, skip_from);
+            log::warn!("[Gemini] Dropped {} bytes of stream data", next - pos);
+            self.dropped_bytes += next - pos;
+            pos = next;
+        }
+
+        self.buffer.drain(..pos);
+        responses
+    }
+
+    /// End of stream: logs and counts anything left unparsed. Returns total bytes dropped.
+    pub fn finish(mut self) -> usize {
+        let leftover = self
+            .buffer
+            .iter()
+            .filter(|&&b| !(matches!(b, b']' | b',') || b.is_ascii_whitespace()))
+            .count();
+        if leftover > 0 {
+            log::warn!("[Gemini] Stream ended with {} bytes of incomplete data", self.buffer.len());
+            self.dropped_bytes += self.buffer.len();
+        }
+        self.dropped_bytes
+    }
+}
+
 /// Parse a Gemini response part and extract events
 pub fn parse_gemini_chunk(
     part: GeminiPart,
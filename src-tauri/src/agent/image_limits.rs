@@ -0,0 +1,62 @@
+// Attachment caps - trims oversized image batches before any upload/describe work
+// so one message can't blow past provider request limits.
+
+use crate::config::AppConfig;
+use serde_json::json;
+
+/// Default cap on images attached to a single message
+pub const DEFAULT_MAX_IMAGES_PER_MESSAGE: usize = 10;
+/// Default cap on decoded image bytes per message (20 MB)
+pub const DEFAULT_MAX_TOTAL_IMAGE_BYTES: usize = 20 * 1024 * 1024;
+
+/// Decoded size of a base64 payload, without decoding it
+pub fn base64_decoded_len(data: &str) -> usize {
+    let data = data.trim_end();
+    let padding = data.bytes().rev().take_while(|&b| b == b'=').count();
+    (data.len() / 4 * 3 + data.len() % 4 * 3 / 4).saturating_sub(padding)
+}
+
+/// Keep the leading images that fit both caps and drop the rest. `emit_warning` is
+/// called with an `agent-warning` payload when anything was dropped.
+pub fn enforce_image_limits(
+    bases: &mut Vec<String>,
+    mimes: &mut Vec<String>,
+    config: &AppConfig,
+    emit_warning: impl FnOnce(serde_json::Value),
+) {
+    let max_images = config.max_images_per_message.unwrap_or(DEFAULT_MAX_IMAGES_PER_MESSAGE);
+    let max_bytes = config.max_total_image_bytes.unwrap_or(DEFAULT_MAX_TOTAL_IMAGE_BYTES);
+
+    let received = bases.len();
+    let mut total_bytes = 0;
+    let mut keep = 0;
+    for data in bases.iter().take(max_images) {
+        let size = base64_decoded_len(data);
+        if total_bytes + size > max_bytes {
+            break;
+        }
+        total_bytes += size;
+        keep += 1;
+    }
+    if keep == received {
+        return;
+    }
+
+    bases.truncate(keep);
+    mimes.truncate(keep);
+
+    let reason = if keep == max_images.min(received) {
+        format!("at most {} images can be attached to one message", max_images)
+    } else {
+        format!(
+            "attachments are limited to {:.1} MB per message",
+            max_bytes as f64 / (1024.0 * 1024.0)
+        )
+    };
+    log::warn!("[Agent] Dropped {} of {} images: {}", received - keep, received, reason);
+    emit_warning(json!({
+        "title": "Some images were not sent",
+        "details": format!("Sent {} of {} images; {}.", keep, received, reason),
+        "dropped": received - keep
+    }));
+}
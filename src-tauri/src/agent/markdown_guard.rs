@@ -0,0 +1,115 @@
+// Streaming markdown guard - holds back half-streamed LaTeX/code delimiters
+// so the UI never renders a `$$` block or ``` fence split across chunks.
+
+/// Unclosed display-math blocks longer than this are released anyway
+const MAX_HELD_BYTES: usize = 4000;
+
+/// Stateful buffer between the model stream and `agent-response-chunk` emits
+#[derive(Debug, Default)]
+pub struct MarkdownStreamGuard {
+    pending: String,
+    in_code_fence: bool,
+    in_display_math: bool,
+}
+
+impl MarkdownStreamGuard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a streamed chunk and return the text that is safe to emit now
+    pub fn push(&mut self, chunk: &str) -> String {
+        self.pending.push_str(chunk);
+        let scan = scan_safe_prefix(&self.pending, self.in_code_fence, self.in_display_math);
+        self.in_code_fence = scan.in_code_fence;
+        self.in_display_math = scan.in_display_math;
+
+        let held = self.pending.split_off(scan.safe_len);
+        std::mem::replace(&mut self.pending, held)
+    }
+
+    /// Flush everything still held (call at stream end)
+    pub fn finish(&mut self) -> String {
+        self.in_code_fence = false;
+        self.in_display_math = false;
+        std::mem::take(&mut self.pending)
+    }
+}
+
+struct ScanResult {
+    safe_len: usize,
+    in_code_fence: bool,
+    in_display_math: bool,
+}
+
+/// Find how much of `text` can be emitted, and the fence/math state at that point.
+/// Holds back a trailing partial delimiter (`$`, `` ` ``, ``` `` ```) and any
+/// display-math block whose closing `$$` hasn't arrived yet.
+fn scan_safe_prefix(text: &str, mut in_code: bool, mut in_math: bool) -> ScanResult {
+    let bytes = text.as_bytes();
+    // Start of a display-math block opened within this text
+    let mut math_start: Option<usize> = None;
+    let mut i = 0;
+
+    let held_at = |idx: usize, in_code: bool, in_math: bool, math_start: Option<usize>| match math_start {
+        Some(start) => ScanResult {
+            safe_len: start,
+            in_code_fence: in_code,
+            in_display_math: false,
+        },
+        None => ScanResult {
+            safe_len: idx,
+            in_code_fence: in_code,
+            in_display_math: in_math,
+        },
+    };
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'`' => {
+                let run = bytes[i..].iter().take_while(|&&b| b == b'`').count();
+                if i + run == bytes.len() && run < 3 {
+                    // Could still grow into a fence
+                    return held_at(i, in_code, in_math, math_start);
+                }
+                if run >= 3 && !in_math {
+                    in_code = !in_code;
+                }
+                i += run;
+            }
+            b'$' if !in_code => {
+                let run = bytes[i..].iter().take_while(|&&b| b == b'$').count();
+                if i + run == bytes.len() && run < 2 {
+                    // Could be the first half of `$$`
+                    return held_at(i, in_code, in_math, math_start);
+                }
+                if run >= 2 {
+                    if in_math {
+                        in_math = false;
+                        math_start = None;
+                    } else {
+                        in_math = true;
+                        math_start = Some(i);
+                    }
+                    i += 2;
+                } else {
+                    i += 1;
+                }
+            }
+            _ => i += 1,
+        }
+    }
+
+    match math_start {
+        Some(start) if bytes.len() - start <= MAX_HELD_BYTES => ScanResult {
+            safe_len: start,
+            in_code_fence: in_code,
+            in_display_math: false,
+        },
+        _ => ScanResult {
+            safe_len: bytes.len(),
+            in_code_fence: in_code,
+            in_display_math: in_math,
+        },
+    }
+}
@@ -1,20 +1,57 @@
 /**
  * Agent module - AI chat agent with Gemini and OpenRouter support
  */
+mod anthropic;
+mod chunk_coalescer;
 mod gemini;
+mod image_limits;
+mod markdown_guard;
 mod openrouter;
+mod reasoning_buffer;
+mod sessions;
+mod transcript;
 mod types;
 
-pub use gemini::{construct_gemini_messages, parse_gemini_chunk, AgentEvent};
+pub use gemini::{
+    build_thinking_config, chunk_finish_reason, construct_gemini_messages, parse_gemini_chunk,
+    supports_thinking, AgentEvent, FinishReason, GeminiStreamReader, MAX_INLINE_IMAGE_BYTES,
+};
+pub use anthropic::{
+    anthropic_model_id, construct_anthropic_messages, is_anthropic_model, parse_anthropic_event,
+    AnthropicStreamEvent, ToolUseAccumulator,
+};
+pub use chunk_coalescer::{next_or_flush, ChunkCoalescer, DEFAULT_COALESCE_CHARS, DEFAULT_COALESCE_MS};
+pub use image_limits::{
+    base64_decoded_len, enforce_image_limits, DEFAULT_MAX_IMAGES_PER_MESSAGE, DEFAULT_MAX_TOTAL_IMAGE_BYTES,
+};
+pub use markdown_guard::MarkdownStreamGuard;
+pub use openrouter::{resolve_provider, ProviderEndpoint, DEFAULT_LOCAL_BASE_URL};
+pub use reasoning_buffer::{ReasoningBuffer, DEFAULT_MAX_REASONING_CHARS, REASONING_TRUNCATION_MARKER};
+pub use sessions::{
+    delete_session_file, list_sessions, load_session, migrate_legacy_history, read_active_session,
+    read_session_model, save_session, session_title, write_active_session, write_session_model,
+    SessionInfo, DEFAULT_SESSION_ID,
+    LEGACY_HISTORY_FILENAME,
+};
+pub use transcript::{history_to_markdown, parse_chat_markdown};
 pub use types::*;
 
 use crate::integrations::{
-    arxiv::{perform_arxiv_lookup, read_arxiv_paper},
+    arxiv::{perform_arxiv_lookup, read_arxiv_paper, ArxivSearchOptions},
+    discussions::perform_discussion_search,
+    feeds::{read_feed, DEFAULT_FEED_ITEMS},
     finance::perform_finance_lookup,
-    weather::perform_weather_lookup,
-    web_search::perform_web_search,
-    wikipedia::perform_wikipedia_lookup,
+    format_code::format_code,
+    github::read_github,
+    local_file::read_local_file,
+    translate::translate_with,
+    url_summary::{fetch_url_text, save_url_summary_with},
+    weather::{perform_weather_forecast, perform_weather_lookup, WeatherLocation, MAX_FORECAST_DAYS},
+    world_clock::get_time_in_zone,
+    web_search::{perform_web_search, Freshness, SearchOptions},
+    wikipedia::{perform_wikipedia_lookup, read_wikipedia_section, WikipediaSectionResult},
 };
+use crate::error::ShardError;
 use reqwest::Client;
 use serde_json::{json, Value};
 use tauri::{AppHandle, Emitter, Manager, Runtime};
@@ -27,6 +64,26 @@ pub struct Agent {
     uploaded_files: Mutex<Vec<String>>,
     backup_history: Mutex<Option<Vec<ChatMessage>>>,
     data_dir: std::path::PathBuf,
+    /// Conversation `history` belongs to, persisted as chats/<id>.json
+    session_id: std::sync::Mutex<String>,
+    /// Model pinned to the active session; overrides `selected_model` and routing
+    session_model: std::sync::Mutex<Option<String>>,
+    /// Where the config lives, re-read on each persist so setting changes apply immediately
+    config_dir: Option<std::path::PathBuf>,
+    /// Interaction log writes that failed and are waiting for a retry
+    failed_interactions: std::sync::Mutex<crate::interactions::InteractionRetryQueue>,
+    /// Last generated title, keyed by the first user message it was made from
+    chat_title: std::sync::Mutex<Option<(String, String)>>,
+    /// Set when the last Gemini turn ended with something other than STOP
+    abnormal_finish: std::sync::atomic::AtomicBool,
+    /// Tool calls executed while answering the current message
+    tool_calls_seen: std::sync::Mutex<ToolCallTracker>,
+    /// Embedding failures, reported to the UI once per session
+    rag_health: std::sync::Mutex<DegradationTracker>,
+    /// Sources visited by research tools while answering the current message
+    citations: std::sync::Mutex<CitationTracker>,
+    /// Raw system prompt / sampling overrides sent with the current message
+    generation_overrides: std::sync::Mutex<GenerationOverrides>,
 }
 
 impl Agent {
@@ -36,33 +93,32 @@ impl Agent {
             .app_data_dir()
             .expect("failed to get app data dir");
         std::fs::create_dir_all(&app_data_dir).expect("failed to create app data dir");
+        let config_dir = app_handle.path().app_config_dir().ok();
 
+        let timeout_secs = crate::config::load_config(&app_handle)
+            .ok()
+            .and_then(|c| c.http_timeout_secs)
+            .filter(|secs| *secs > 0)
+            .unwrap_or(crate::config::DEFAULT_HTTP_TIMEOUT_SECS);
         let http_client = Client::builder()
-            .timeout(std::time::Duration::from_secs(60))
+            .timeout(std::time::Duration::from_secs(timeout_secs))
             .build()
             .unwrap_or_else(|_| Client::new());
 
-        // Load persisted history if it exists
-        let history_path = app_data_dir.join("chat_history.json");
-        let history = if history_path.exists() {
-            match std::fs::read_to_string(&history_path) {
-                Ok(contents) => match serde_json::from_str::<Vec<ChatMessage>>(&contents) {
-                    Ok(msgs) => {
-                        log::info!("Loaded {} messages from persisted history", msgs.len());
-                        msgs
-                    }
-                    Err(e) => {
-                        log::warn!("Failed to parse chat history: {}", e);
-                        Vec::new()
-                    }
-                },
-                Err(e) => {
-                    log::warn!("Failed to read chat history: {}", e);
-                    Vec::new()
-                }
+        // Load the active session, migrating the old single-file history first
+        if let Err(e) = sessions::migrate_legacy_history(&app_data_dir) {
+            log::warn!("{}", e);
+        }
+        let session_id = sessions::read_active_session(&app_data_dir);
+        let history = match sessions::load_session(&app_data_dir, &session_id) {
+            Ok(msgs) => {
+                log::info!("Loaded {} messages from session {}", msgs.len(), session_id);
+                msgs
+            }
+            Err(e) => {
+                log::warn!("{}", e);
+                Vec::new()
             }
-        } else {
-            Vec::new()
         };
 
         Self {
@@ -71,12 +127,25 @@ impl Agent {
             uploaded_files: Mutex::new(Vec::new()),
             backup_history: Mutex::new(None),
             data_dir: app_data_dir,
+            session_model: std::sync::Mutex::new(sessions::read_session_model(&app_data_dir, &session_id)),
+            session_id: std::sync::Mutex::new(session_id),
+            config_dir,
+            failed_interactions: std::sync::Mutex::new(
+                crate::interactions::InteractionRetryQueue::new(),
+            ),
+            chat_title: std::sync::Mutex::new(None),
+            abnormal_finish: std::sync::atomic::AtomicBool::new(false),
+            tool_calls_seen: std::sync::Mutex::new(ToolCallTracker::default()),
+            rag_health: std::sync::Mutex::new(DegradationTracker::default()),
+            citations: std::sync::Mutex::new(CitationTracker::default()),
+            generation_overrides: std::sync::Mutex::new(GenerationOverrides::default()),
         }
     }
 
     pub async fn clear_history(&self, api_key: Option<String>) {
         let mut history = self.history.lock().await;
         history.clear();
+        self.reset_turn_start(&history);
 
         let mut uploaded_files = self.uploaded_files.lock().await;
         if !uploaded_files.is_empty() {
@@ -100,6 +169,54 @@ impl Agent {
         self.persist_history().await;
     }
 
+    /// Files in the Gemini Files API uploaded with `api_key`
+    pub async fn list_uploaded_files(&self, api_key: &str) -> Result<Vec<crate::gemini_files::GeminiFileInfo>, String> {
+        crate::gemini_files::list_gemini_files(&self.http_client, api_key).await
+    }
+
+    /// Delete uploaded files no chat refers to (e.g. left behind by a crash before
+    /// clear_history ran). Files attached to the current history, its backup or any
+    /// saved session are kept. Returns how many were deleted.
+    pub async fn cleanup_orphaned_files(&self, api_key: &str) -> Result<usize, String> {
+        let files = self.list_uploaded_files(api_key).await?;
+
+        let mut referenced = crate::gemini_files::referenced_file_ids(self.history.lock().await.iter());
+        // Uploads for a turn still in flight aren't in history yet
+        let pending = self.uploaded_files.lock().await;
+        referenced.extend(pending.iter().map(|uri| crate::gemini_files::file_id(uri).to_string()));
+        drop(pending);
+        if let Some(backup) = self.backup_history.lock().await.as_ref() {
+            referenced.extend(crate::gemini_files::referenced_file_ids(backup));
+        }
+        let active_id = self.active_session_id();
+        for session in sessions::list_sessions(&self.data_dir)? {
+            if session.id == active_id {
+                continue;
+            }
+            match sessions::load_session(&self.data_dir, &session.id) {
+                Ok(messages) => referenced.extend(crate::gemini_files::referenced_file_ids(&messages)),
+                Err(e) => log::warn!("[Agent] Skipping session {} while collecting file references: {}", session.id, e),
+            }
+        }
+
+        let orphans = crate::gemini_files::find_orphaned_files(&files, &referenced);
+        let mut deleted = 0;
+        for file in &orphans {
+            match crate::gemini_files::delete_gemini_file(&self.http_client, api_key, &file.name).await {
+                Ok(()) => deleted += 1,
+                Err(e) => log::warn!("[Agent] Failed to delete orphaned file {}: {}", file.name, e),
+            }
+        }
+        log::info!("[Agent] Deleted {}/{} orphaned Gemini files", deleted, orphans.len());
+        Ok(deleted)
+    }
+
+    /// Point focus mode at the last user message after history was rewritten,
+    /// so a later retry doesn't slice with an index from the old history
+    fn reset_turn_start(&self, history: &[ChatMessage]) {
+        self.generation_overrides.lock().unwrap().turn_start = last_turn_start(history);
+    }
+
     pub async fn rewind_history(&self) {
         let mut history = self.history.lock().await;
         if history.is_empty() {
@@ -111,6 +228,41 @@ impl Agent {
                 break;
             }
         }
+        self.reset_turn_start(&history);
+    }
+
+    /// Fork the conversation at `index`, keeping messages 0..index.
+    /// The full history is backed up so restore_history can undo the branch.
+    pub async fn branch_from_message(&self, index: usize) -> Result<(), String> {
+        let mut history = self.history.lock().await;
+        let full_history = branch_history(&mut history, index)?;
+        self.reset_turn_start(&history);
+
+        let mut backup = self.backup_history.lock().await;
+        *backup = Some(full_history);
+
+        drop(history); // Release locks before persist
+        drop(backup);
+        self.persist_history().await;
+        Ok(())
+    }
+
+    /// Replace history with a parsed Markdown transcript, backing up the current one
+    /// so restore_history can undo the import.
+    pub async fn import_markdown(&self, markdown: &str) -> Result<usize, String> {
+        let imported = parse_chat_markdown(markdown)?;
+        let count = imported.len();
+
+        let mut history = self.history.lock().await;
+        let mut backup = self.backup_history.lock().await;
+        *backup = Some(std::mem::replace(&mut *history, imported));
+        self.reset_turn_start(&history);
+
+        drop(history); // Release locks before persist
+        drop(backup);
+        self.persist_history().await;
+        log::info!("[Agent] Imported {} messages from transcript", count);
+        Ok(count)
     }
 
     pub async fn save_and_clear_history(&self) {
@@ -118,6 +270,7 @@ impl Agent {
         let mut backup = self.backup_history.lock().await;
         *backup = Some(history.clone());
         history.clear();
+        self.reset_turn_start(&history);
     }
 
     pub async fn restore_history(&self) -> Result<(), String> {
@@ -126,6 +279,7 @@ impl Agent {
 
         if let Some(saved) = backup.take() {
             *history = saved;
+            self.reset_turn_start(&history);
             Ok(())
         } else {
             Err("No backup available".to_string())
@@ -137,6 +291,17 @@ impl Agent {
         history.clone()
     }
 
+    /// Get a page of history, optionally filtered to the given roles
+    pub async fn get_history_page(
+        &self,
+        offset: usize,
+        limit: usize,
+        roles: Option<&[String]>,
+    ) -> HistoryPage {
+        let history = self.history.lock().await;
+        paginate_history(&history, offset, limit, roles)
+    }
+
     pub async fn get_message_count(&self) -> usize {
         let history = self.history.lock().await;
         history.len()
@@ -147,6 +312,196 @@ impl Agent {
         backup.is_some()
     }
 
+    /// Short title for the current chat, generated once per conversation.
+    /// Cached until the first user message changes (new chat, branch, rewind).
+    pub async fn generate_chat_title(
+        &self,
+        config: &crate::config::AppConfig,
+    ) -> Result<String, String> {
+        let (key, prompt) = {
+            let history = self.history.lock().await;
+            let key = first_message_text(&history, "user")
+                .ok_or("Cannot generate a title for an empty chat")?
+                .to_string();
+            (key, build_title_prompt(&history)?)
+        };
+
+        if let Some((cached_key, title)) = self.chat_title.lock().unwrap().as_ref() {
+            if *cached_key == key {
+                return Ok(title.clone());
+            }
+        }
+
+        let model = config
+            .background_model
+            .as_deref()
+            .unwrap_or(crate::background::DEFAULT_BACKGROUND_MODEL);
+        let reply =
+            crate::background::call_background_llm(&self.http_client, config, model, &prompt).await?;
+        let title = clean_title(&reply);
+        if title.is_empty() {
+            return Err("Title model returned an empty response".to_string());
+        }
+
+        *self.chat_title.lock().unwrap() = Some((key, title.clone()));
+        Ok(title)
+    }
+
+    /// Prose summary of the current chat for handing off to another session or person.
+    /// Unlike titles this goes to the user's chat model, not the background model.
+    pub async fn summarize_conversation(
+        &self,
+        config: &crate::config::AppConfig,
+        max_words: usize,
+    ) -> Result<String, String> {
+        let prompt = {
+            let history = self.history.lock().await;
+            build_summary_prompt(&history, max_words)?
+        };
+
+        let summary = self.complete_with_chat_model(config, &prompt).await?;
+        let summary = summary.trim();
+        if summary.is_empty() {
+            return Err("Summary model returned an empty response".to_string());
+        }
+        Ok(summary.to_string())
+    }
+
+    /// Single non-streaming completion against the session's chat provider, no tools.
+    /// Shares provider routing, network retries and debug logging with chat turns.
+    async fn complete_with_chat_model(
+        &self,
+        config: &crate::config::AppConfig,
+        prompt: &str,
+    ) -> Result<String, String> {
+        let session_config = with_session_model(config, self.session_model().as_deref());
+        let config = &session_config;
+        let selected_model = config
+            .selected_model
+            .clone()
+            .unwrap_or("gemini-2.5-flash-lite".to_string());
+        let max_retries = config
+            .max_network_retries
+            .unwrap_or(crate::error::DEFAULT_MAX_NETWORK_RETRIES);
+
+        match chat_provider(&selected_model, config) {
+            ChatProvider::Anthropic => {
+                let api_key = config
+                    .anthropic_api_key
+                    .as_ref()
+                    .ok_or_else(|| ShardError::missing_api_key("Anthropic"))?;
+                let payload = serde_json::json!({
+                    "model": anthropic_model_id(&selected_model),
+                    "max_tokens": anthropic::DEFAULT_ANTHROPIC_MAX_TOKENS,
+                    "messages": [{ "role": "user", "content": prompt }]
+                });
+                crate::api_debug::log_request(
+                    config,
+                    &self.data_dir,
+                    "Anthropic",
+                    anthropic::ANTHROPIC_MESSAGES_URL,
+                    None,
+                    &payload,
+                );
+                let res = send_with_retry("Anthropic", max_retries, || {
+                    self.http_client
+                        .post(anthropic::ANTHROPIC_MESSAGES_URL)
+                        .header("x-api-key", api_key)
+                        .header("anthropic-version", anthropic::ANTHROPIC_VERSION)
+                        .header("Content-Type", "application/json")
+                        .json(&payload)
+                        .send()
+                })
+                .await?;
+                let body = self.read_completion_body(config, "Anthropic", res).await?;
+                Ok(body["content"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter(|block| block["type"] == "text")
+                    .filter_map(|block| block["text"].as_str())
+                    .collect())
+            }
+            ChatProvider::Gemini => {
+                let api_key = config
+                    .gemini_api_key
+                    .as_ref()
+                    .ok_or_else(|| ShardError::missing_api_key("Gemini"))?;
+                let url = format!(
+                    "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent?key={}",
+                    selected_model.trim(),
+                    api_key
+                );
+                let payload = serde_json::json!({
+                    "contents": [{ "role": "user", "parts": [{ "text": prompt }] }]
+                });
+                crate::api_debug::log_request(config, &self.data_dir, "Gemini", &url, None, &payload);
+                let res = send_with_retry("Gemini", max_retries, || {
+                    self.http_client.post(&url).json(&payload).send()
+                })
+                .await?;
+                let body = self.read_completion_body(config, "Gemini", res).await?;
+                Ok(body["candidates"][0]["content"]["parts"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter(|part| part["thought"].as_bool() != Some(true))
+                    .filter_map(|part| part["text"].as_str())
+                    .collect())
+            }
+            ChatProvider::OpenAiCompatible => {
+                let endpoint = openrouter::resolve_provider(&selected_model, config)?;
+                let payload = serde_json::json!({
+                    "model": endpoint.model,
+                    "messages": [{ "role": "user", "content": prompt }],
+                    "stream": false
+                });
+                let url = endpoint.chat_completions_url();
+                let auth = endpoint.authorization_header();
+                crate::api_debug::log_request(
+                    config,
+                    &self.data_dir,
+                    endpoint.provider_name,
+                    &url,
+                    auth.as_deref(),
+                    &payload,
+                );
+                let res = send_with_retry(endpoint.provider_name, max_retries, || {
+                    let mut request = self.http_client.post(&url).header("Content-Type", "application/json");
+                    if let Some(auth) = &auth {
+                        request = request.header("Authorization", auth);
+                    }
+                    request.json(&payload).send()
+                })
+                .await?;
+                let body = self.read_completion_body(config, endpoint.provider_name, res).await?;
+                Ok(body["choices"][0]["message"]["content"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string())
+            }
+        }
+    }
+
+    /// Log a non-streaming completion response and parse its JSON body
+    async fn read_completion_body(
+        &self,
+        config: &crate::config::AppConfig,
+        provider: &str,
+        res: reqwest::Response,
+    ) -> Result<Value, String> {
+        let status = res.status();
+        let text = res
+            .text()
+            .await
+            .map_err(|e| format!("{} API network error: {}", provider, e))?;
+        crate::api_debug::log_response(config, &self.data_dir, provider, status.as_u16(), &text);
+        if !status.is_success() {
+            return Err(format!("{} API error: {}", provider, text));
+        }
+        serde_json::from_str(&text).map_err(|e| format!("Failed to parse {} response: {}", provider, e))
+    }
+
     /// Retry the last response with a hint about KaTeX errors
     /// Called by frontend when KaTeX parsing fails
     pub async fn retry_with_katex_hint<R: Runtime>(
@@ -154,7 +509,7 @@ impl Agent {
         app_handle: &AppHandle<R>,
         katex_errors: Vec<String>,
         config: &crate::config::AppConfig,
-    ) -> Result<(), String> {
+    ) -> Result<(), ShardError> {
         let mut history = self.history.lock().await;
 
         // Check if retry on KaTeX is enabled
@@ -168,7 +523,7 @@ impl Agent {
                 history.pop();
 
                 // Add the retry hint
-                let hint = RetryReason::MalformedLatex { errors: katex_errors }.get_hint();
+                let hint = RetryReason::MalformedLatex { errors: katex_errors }.get_hint(config.retry_hints.as_ref());
                 history.push(ChatMessage {
                     role: "user".to_string(),
                     content: Some(hint),
@@ -176,6 +531,7 @@ impl Agent {
                     tool_calls: None,
                     tool_call_id: None,
                     images: None,
+                    citations: None,
                 });
 
                 // Emit retry event
@@ -193,20 +549,97 @@ impl Agent {
                 // Note: We need to trigger a new processing loop without a new user message
                 // This is handled by calling process_message with an empty message that gets ignored
                 // Actually, we'll just re-use the existing flow by calling the internal method
-                self.run_retry_turn(app_handle, config).await?;
+                self.run_retry_turn(app_handle, config, None).await?;
             }
         }
 
         Ok(())
     }
 
-    /// Internal method to run a retry turn after hint injection
+    /// Replace all but the last `keep_recent` turns with a model-written summary note.
+    /// Returns how many messages were compacted (0 when there's nothing old enough).
+    pub async fn compact_history(
+        &self,
+        keep_recent: usize,
+        config: &crate::config::AppConfig,
+    ) -> Result<usize, String> {
+        // Summarize a copy so chat, rewind and persist aren't blocked on the network call
+        let compacted = {
+            let history = self.history.lock().await;
+            let split = compaction_split_index(&history, keep_recent);
+            history[..split].to_vec()
+        };
+        if compacted.is_empty() {
+            return Ok(0);
+        }
+
+        let model = config
+            .background_model
+            .as_deref()
+            .unwrap_or(crate::background::DEFAULT_BACKGROUND_MODEL);
+        let prompt = build_compaction_prompt(&compacted);
+        let summary =
+            crate::background::call_background_llm(&self.http_client, config, model, &prompt).await?;
+        if summary.trim().is_empty() {
+            return Err("Summary model returned an empty response".to_string());
+        }
+
+        let mut history = self.history.lock().await;
+        if !splice_compaction_summary(&mut history, &compacted, &summary) {
+            return Err("Chat history changed while it was being summarized; try again".to_string());
+        }
+        self.reset_turn_start(&history);
+        let split = compacted.len();
+        log::info!("Compacted {} messages into a summary note", split);
+
+        drop(history); // Release lock before persist
+        self.persist_history().await;
+        Ok(split)
+    }
+
+    /// Regenerate the response to the last user message, optionally with a different model.
+    /// The previous response (and its tool exchange) is restored if the new turn fails.
+    pub async fn reprocess_last<R: Runtime>(
+        &self,
+        app_handle: &AppHandle<R>,
+        model_override: Option<String>,
+        config: &crate::config::AppConfig,
+    ) -> Result<(), ShardError> {
+        let popped = {
+            let mut history = self.history.lock().await;
+            let popped = pop_last_response(&mut history)?;
+            self.reset_turn_start(&history);
+            popped
+        };
+
+        let model_override = model_override.filter(|m| !m.trim().is_empty());
+        if let Some(model) = &model_override {
+            log::info!("Reprocessing last message with {}", model);
+        }
+
+        if let Err(e) = self.run_retry_turn(app_handle, config, model_override.as_deref()).await {
+            let mut history = self.history.lock().await;
+            pop_last_response(&mut history).ok();
+            history.extend(popped);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Internal method to run a retry turn after hint injection, following any tool calls
+    /// until the model answers. An explicit `model_override` beats the session's pinned model.
     async fn run_retry_turn<R: Runtime>(
         &self,
         app_handle: &AppHandle<R>,
         config: &crate::config::AppConfig,
-    ) -> Result<(), String> {
-        let mut history = self.history.lock().await;
+        model_override: Option<&str>,
+    ) -> Result<(), ShardError> {
+        let session_config = with_model_override(config, self.session_model().as_deref(), model_override);
+        let config = &session_config;
+        // Held for the whole retry; the wrapper lets each turn's future borrow it in turn
+        let history = tokio::sync::Mutex::new(self.history.lock().await);
+        self.tool_calls_seen.lock().unwrap().reset();
+        self.citations.lock().unwrap().reset();
 
         let stream_id = crate::CURRENT_STREAM_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
 
@@ -215,34 +648,17 @@ impl Agent {
             .clone()
             .unwrap_or("gemini-2.5-flash-lite".to_string());
 
-        let is_gemini = !selected_model.contains("/")
-            && !selected_model.contains("(Cerebras)")
-            && !selected_model.contains("(Groq)");
-
-        let _continue_turn = if is_gemini {
-            let api_key = config.gemini_api_key.as_ref().ok_or("No Gemini API key")?;
-            self.process_gemini_turn(
-                app_handle,
-                config,
-                &mut history,
-                stream_id,
-                &selected_model,
-                api_key,
-                None, // No RAG context for retry
-                false, // Not research mode
-            )
-            .await?
-        } else {
-            self.process_openrouter_turn(
-                app_handle,
-                config,
-                &mut history,
-                stream_id,
-                None,
-                false,
-            )
-            .await?
-        };
+        let history_ref = &history;
+        let selected_model_ref = selected_model.as_str();
+        run_turns(max_turns_for(config, false), move || async move {
+            let mut history = history_ref.lock().await;
+            self.run_provider_turn(app_handle, config, &mut history, stream_id, selected_model_ref)
+                .await
+        })
+        .await?;
+
+        let mut history = history.into_inner();
+        self.attach_citations(app_handle, &mut history);
 
         // Persist the new response
         drop(history);
@@ -251,14 +667,61 @@ impl Agent {
         Ok(())
     }
 
+    /// One provider turn without RAG context or research mode; true when a tool was called
+    async fn run_provider_turn<R: Runtime>(
+        &self,
+        app_handle: &AppHandle<R>,
+        config: &crate::config::AppConfig,
+        history: &mut Vec<ChatMessage>,
+        stream_id: u64,
+        selected_model: &str,
+    ) -> Result<bool, ShardError> {
+        match chat_provider(selected_model, config) {
+            ChatProvider::Anthropic => {
+                self.process_anthropic_turn(app_handle, config, history, stream_id, None, false)
+                    .await
+            }
+            ChatProvider::Gemini => {
+                let api_key = config
+                    .gemini_api_key
+                    .as_ref()
+                    .ok_or_else(|| ShardError::missing_api_key("Gemini"))?;
+                self.process_gemini_turn(
+                    app_handle,
+                    config,
+                    history,
+                    stream_id,
+                    selected_model,
+                    api_key,
+                    None, // No RAG context for retry
+                    false, // Not research mode
+                )
+                .await
+            }
+            ChatProvider::OpenAiCompatible => {
+                self.process_openrouter_turn(app_handle, config, history, stream_id, None, false)
+                    .await
+            }
+        }
+    }
+
     /// Persist current chat history to disk
     pub async fn persist_history(&self) {
+        self.flush_failed_interactions();
+
+        let persist_reasoning = self
+            .config_dir
+            .as_deref()
+            .and_then(|dir| crate::config::load_config_in_dir(dir).ok())
+            .and_then(|config| config.persist_reasoning)
+            .unwrap_or(true);
+
         let history = self.history.lock().await;
-        let history_path = self.data_dir.join("chat_history.json");
+        let session_id = self.session_id.lock().unwrap().clone();
 
-        match serde_json::to_string_pretty(&*history) {
+        match serialize_history(&history, persist_reasoning) {
             Ok(json) => {
-                if let Err(e) = std::fs::write(&history_path, json) {
+                if let Err(e) = sessions::save_session(&self.data_dir, &session_id, &json) {
                     log::error!("Failed to persist chat history: {}", e);
                 }
             }
@@ -268,15 +731,224 @@ impl Agent {
         }
     }
 
+    /// Save the current conversation and start an empty one; returns its id
+    pub async fn new_session(&self) -> Result<String, String> {
+        let id = sessions::new_session_id();
+        self.open_session(&id, Vec::new()).await?;
+        self.persist_history().await;
+        Ok(id)
+    }
+
+    /// Save the current conversation and load session `id` into `history`
+    pub async fn switch_session(&self, id: &str) -> Result<(), String> {
+        if !sessions::session_exists(&self.data_dir, id) {
+            return Err(format!("Session not found: {}", id));
+        }
+        let messages = sessions::load_session(&self.data_dir, id)?;
+        self.open_session(id, messages).await
+    }
+
+    pub fn list_sessions(&self) -> Result<Vec<SessionInfo>, String> {
+        sessions::list_sessions(&self.data_dir)
+    }
+
+    /// Delete a stored session. Deleting the active one starts a new empty session.
+    pub async fn delete_session(&self, id: &str) -> Result<bool, String> {
+        let is_active = *self.session_id.lock().unwrap() == id;
+        if is_active {
+            // Switch away first so the outgoing persist can't recreate the deleted file
+            self.new_session().await?;
+        }
+        let deleted = sessions::delete_session_file(&self.data_dir, id)?;
+        if deleted {
+            log::info!("Session deleted: {}", id);
+        }
+        Ok(deleted)
+    }
+
+    pub fn active_session_id(&self) -> String {
+        self.session_id.lock().unwrap().clone()
+    }
+
+    /// Pin a model to the active session (None or "" goes back to the configured model)
+    pub fn set_session_model(&self, model: Option<String>) -> Result<(), String> {
+        let model = model.map(|m| m.trim().to_string()).filter(|m| !m.is_empty());
+        let session_id = self.session_id.lock().unwrap().clone();
+        sessions::write_session_model(&self.data_dir, &session_id, model.as_deref())?;
+        log::info!("Session {} model: {:?}", session_id, model);
+        *self.session_model.lock().unwrap() = model;
+        Ok(())
+    }
+
+    pub fn session_model(&self) -> Option<String> {
+        self.session_model.lock().unwrap().clone()
+    }
+
+    /// Persist the outgoing session, then make `id` active with `messages` as its history
+    async fn open_session(&self, id: &str, messages: Vec<ChatMessage>) -> Result<(), String> {
+        sessions::session_path(&self.data_dir, id)?;
+        self.persist_history().await;
+
+        let mut history = self.history.lock().await;
+        *history = messages;
+        self.reset_turn_start(&history);
+        *self.backup_history.lock().await = None;
+        *self.chat_title.lock().unwrap() = None;
+        self.uploaded_files.lock().await.clear();
+        *self.session_id.lock().unwrap() = id.to_string();
+        *self.session_model.lock().unwrap() = sessions::read_session_model(&self.data_dir, id);
+        drop(history);
+
+        sessions::write_active_session(&self.data_dir, id)?;
+        log::info!("Switched to session {}", id);
+        Ok(())
+    }
+
+    /// Embed text for RAG, telling the UI once per session when embeddings keep failing.
+    /// Chat carries on without retrieval either way.
+    async fn embed_or_degrade<R: Runtime>(
+        &self,
+        app_handle: &AppHandle<R>,
+        text: &str,
+        api_key: &str,
+    ) -> Option<Vec<f32>> {
+        match crate::interactions::generate_embedding(&self.http_client, text, api_key).await {
+            Ok(embedding) => {
+                self.rag_health.lock().unwrap().record_success();
+                Some(embedding)
+            }
+            Err(e) => {
+                log::warn!("[RAG] Embedding failed, continuing without retrieval: {}", e);
+                if self.rag_health.lock().unwrap().record_failure() {
+                    app_handle.emit("agent-degraded", degraded_event("rag", &e).to_string()).ok();
+                }
+                None
+            }
+        }
+    }
+
+    /// Embedding of the user's message for retrieval; None in incognito mode or without a key
+    async fn embed_user_message<R: Runtime>(
+        &self,
+        app_handle: &AppHandle<R>,
+        config: &crate::config::AppConfig,
+        message: &str,
+    ) -> Option<Vec<f32>> {
+        if config.incognito_mode.unwrap_or(false) {
+            return None;
+        }
+        let api_key = crate::interactions::embedding_api_key(config)?;
+        self.embed_or_degrade(app_handle, message, api_key).await
+    }
+
+    /// Run the prompt assembly of `process_message` (embedding, retrieval, memories,
+    /// research detection, routing, prompt selection) and stop before the model request.
+    /// History is left untouched.
+    pub async fn preview_prompt<R: Runtime>(
+        &self,
+        app_handle: &AppHandle<R>,
+        message: &str,
+        config: &crate::config::AppConfig,
+    ) -> Result<PromptPreview, String> {
+        let session_config = with_session_model(config, self.session_model().as_deref());
+        let config = &session_config;
+
+        let user_embedding = self.embed_user_message(app_handle, config, message).await;
+        // Read-only: a preview doesn't count towards topic/insight usage stats
+        let (rag_context, context_used) =
+            assemble_rag_context(app_handle, config, message, user_embedding.as_deref(), false);
+
+        // The intent classifier costs a request; previews go by the config alone
+        let research_mode = config.research_mode.unwrap_or(false);
+        let config = with_routed_model(config, classify_task(research_mode, message));
+        let model = config
+            .selected_model
+            .clone()
+            .unwrap_or("gemini-2.5-flash-lite".to_string());
+
+        let memory_context = load_memory_context(app_handle, &config);
+        let system_prompt = select_system_prompt(
+            &config,
+            &model,
+            research_mode,
+            memory_context.as_deref(),
+            rag_context.as_deref(),
+        );
+
+        Ok(PromptPreview {
+            system_prompt,
+            rag_context,
+            memory_context,
+            model,
+            research_mode,
+            context_used,
+        })
+    }
+
+    /// Write an interaction to the RAG log, queueing it for retry on failure
+    fn log_or_queue_interaction(&self, mut pending: crate::interactions::PendingInteraction) {
+        let dir = self.data_dir.join("interactions");
+        if let Err(e) = crate::interactions::write_interaction_in_dir(&dir, &mut pending) {
+            log::warn!("[Agent] Failed to log interaction, queued for retry: {}", e);
+            if let Ok(mut queue) = self.failed_interactions.lock() {
+                queue.push(pending);
+            }
+        }
+    }
+
+    /// Retry interaction writes that failed on earlier turns
+    fn flush_failed_interactions(&self) {
+        let Ok(mut queue) = self.failed_interactions.lock() else {
+            return;
+        };
+        if queue.is_empty() {
+            return;
+        }
+        let flushed = queue.flush_to_dir(&self.data_dir.join("interactions"));
+        log::info!(
+            "[Agent] Flushed {} queued interaction(s), {} still pending",
+            flushed,
+            queue.len()
+        );
+    }
+
     pub async fn process_message<R: Runtime>(
         &self,
         app_handle: &AppHandle<R>,
         message: String,
-        images_base64: Option<Vec<String>>,
-        images_mime_types: Option<Vec<String>>,
+        mut images_base64: Option<Vec<String>>,
+        mut images_mime_types: Option<Vec<String>>,
         config: &crate::config::AppConfig,
-    ) -> Result<(), String> {
+        overrides: GenerationOverrides,
+    ) -> Result<(), ShardError> {
         println!("process_message called. Message len: {}", message.len());
+        // Lets background jobs back off while the user is chatting
+        crate::background::record_activity();
+        self.tool_calls_seen.lock().unwrap().reset();
+        self.citations.lock().unwrap().reset();
+        let focus_mode = overrides.focus_mode;
+        *self.generation_overrides.lock().unwrap() = overrides;
+
+        // A model pinned to this session beats the configured one
+        let session_config = with_session_model(config, self.session_model().as_deref());
+        let config = &session_config;
+
+        // Trim oversized attachment batches before any upload/describe work
+        if let (Some(bases), Some(mimes)) = (images_base64.as_mut(), images_mime_types.as_mut()) {
+            enforce_image_limits(bases, mimes, config, |warning| {
+                app_handle.emit("agent-warning", warning.to_string()).ok();
+            });
+        }
+
+        // Images pin the message to the vision route so attachments suit that provider
+        let has_images = images_base64.as_ref().is_some_and(|b| !b.is_empty());
+        let vision_config;
+        let config = if has_images {
+            vision_config = with_routed_model(config, TaskType::Vision);
+            &vision_config
+        } else {
+            config
+        };
 
         let mut history = self.history.lock().await;
 
@@ -285,10 +957,12 @@ impl Agent {
             .selected_model
             .clone()
             .unwrap_or("gemini-2.5-flash-lite".to_string());
-        let is_gemini = !selected_model.contains("/");
+        let provider = chat_provider(&selected_model, config);
+        let is_anthropic = provider == ChatProvider::Anthropic;
+        let is_gemini = provider == ChatProvider::Gemini;
 
-        // Process images: upload to Gemini Files API if using Gemini model,
-        // or describe via Vision LLM for other providers
+        // Process images: upload to Gemini Files API if using Gemini model, send inline
+        // to Anthropic, or describe via Vision LLM for other providers
         let mut image_descriptions: Vec<String> = Vec::new();
         let uploaded_images: Option<Vec<ImageAttachment>> = if let (Some(bases), Some(mimes)) =
             (images_base64.as_ref(), images_mime_types.as_ref())
@@ -305,7 +979,10 @@ impl Agent {
                             &self.http_client,
                             img_data,
                             mime_type,
-                            config.gemini_api_key.as_ref().ok_or("No Gemini API key")?,
+                            config
+                                .gemini_api_key
+                                .as_ref()
+                                .ok_or_else(|| ShardError::missing_api_key("Gemini"))?,
                         )
                         .await
                         {
@@ -317,12 +994,16 @@ impl Agent {
                                 Some(file_uri.file_uri)
                             }
                             Err(e) => {
-                                return Err(format!(
-                                    "Failed to upload image to Gemini Files API: {}",
-                                    e
-                                ))
+                                return Err(ShardError::Other {
+                                    message: format!(
+                                        "Failed to upload image to Gemini Files API: {}",
+                                        e
+                                    ),
+                                })
                             }
                         }
+                    } else if is_anthropic {
+                        None // Sent as base64 image blocks
                     } else {
                         // For non-Gemini providers, use Vision LLM to describe the image
                         match crate::integrations::vision_llm::describe_image(
@@ -330,10 +1011,14 @@ impl Agent {
                             img_data,
                             mime_type,
                             config,
+                            config.ocr_language.as_deref(),
                         )
                         .await
                         {
                             Ok(description) => {
+                                if crate::integrations::vision_llm::is_ocr_only(&description) {
+                                    log::info!("[Agent] No vision model available, using local OCR text");
+                                }
                                 log::info!("[Agent] Vision LLM described image: {} chars", description.len());
                                 image_descriptions.push(description);
                             }
@@ -373,97 +1058,58 @@ impl Agent {
             tool_calls: None,
             tool_call_id: None,
             images: uploaded_images,
+            citations: None,
         });
+        // Focus mode sends the model only this turn's messages
+        self.generation_overrides.lock().unwrap().turn_start = history.len() - 1;
 
         // Incognito mode: skip all RAG/memory retrieval and storage
         let incognito = config.incognito_mode.unwrap_or(false);
-
-        // RAG: Generate embedding and retrieve relevant interactions using hybrid search (BM25 + Dense + RRF)
-        // Skip in incognito mode to avoid using previous context
-        let user_embedding = if !incognito {
-            if let Some(api_key) = &config.gemini_api_key {
-                crate::interactions::generate_embedding(&self.http_client, &message, api_key)
-                    .await
-                    .ok()
-            } else {
-                None
-            }
-        } else {
-            None
-        };
-
-        let relevant_interactions = if let Some(emb) = &user_embedding {
-            // Use hybrid search with RRF fusion of BM25 and dense results
-            crate::interactions::hybrid_search_interactions(
-                app_handle, &message, emb, /* limit= */ 5,
-            )
-            .unwrap_or_default()
-        } else {
-            Vec::new()
-        };
-
-        let mut rag_context_str = if !relevant_interactions.is_empty() {
-            let mut s = String::from("\n\nRelevant Past Interactions:\n");
-            for entry in relevant_interactions {
-                s.push_str(&format!(
-                    "- [{}] {}: {}\n",
-                    entry.ts.format("%Y-%m-%d"),
-                    entry.role,
-                    entry.content
-                ));
-            }
-            Some(s)
-        } else {
-            None
-        };
-
-        // RAG: Context from Topics or Insights (Tier 2 / 2.5)
-        if let Some(emb) = &user_embedding {
-            if let Ok(Some((name, content, is_insight))) =
-                crate::memories::find_relevant_context(app_handle, emb)
-            {
-                let s = rag_context_str.get_or_insert_with(String::new);
-                if is_insight {
-                    s.push_str("\n\nRelevant Insight:\n");
-                    s.push_str(&format!("### Insight: {}\n{}\n\n", name, content));
-                    log::debug!("[Agent] Using insight: {}", name);
-                } else {
-                    s.push_str("\n\nRelevant Topic Summary:\n");
-                    s.push_str(&format!("### Topic: {}\n{}\n\n", name, content));
-                    log::debug!("[Agent] Using topic: {}", name);
-                }
-            }
+        if focus_mode {
+            log::info!("[Agent] Focus mode: skipping retrieval and memories");
         }
+        let (user_embedding, rag_context_str, context_used) = gather_rag_context(
+            focus_mode,
+            || self.embed_user_message(app_handle, config, &message),
+            |embedding| assemble_rag_context(app_handle, config, &message, embedding, true),
+        )
+        .await;
+        // Lets the UI show which memories shaped the answer
+        emit_context_used(&context_used, |payload| {
+            app_handle.emit("agent-context-used", payload).ok();
+        });
 
         app_handle.emit("agent-processing-start", ()).ok();
         let stream_id =
             crate::CURRENT_STREAM_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
 
         // Detect research mode: either from config OR dynamically via intent classification
-        let is_research_mode = if config.research_mode.unwrap_or(false) {
-            true
-        } else if let Some(api_key) = config.gemini_api_key.as_ref() {
-            // Dynamically detect research queries using LLM
-            if let Some(last_msg) = history.last() {
-                if last_msg.role == "user" {
-                    self.classify_intent(&last_msg.content.clone().unwrap_or_default(), api_key)
-                        .await
-                        .unwrap_or(false)
-                } else {
-                    false
-                }
-            } else {
-                false
+        let last_user_message = history
+            .last()
+            .filter(|m| m.role == "user")
+            .map(|m| m.content.clone().unwrap_or_default());
+        let is_research_mode = detect_research_mode(config, last_user_message.as_deref(), |query| async move {
+            match config.gemini_api_key.as_ref() {
+                Some(api_key) => self.classify_intent(config, &query, api_key).await,
+                None => Ok(false),
             }
-        } else {
-            false
-        };
+        })
+        .await;
 
         if is_research_mode {
             log::info!("[Agent] Research mode detected - using extended turn limit");
         }
 
-        let max_turns = if is_research_mode { 15 } else { 5 };
+        let task_config;
+        let config = if has_images {
+            config
+        } else {
+            let task = classify_task(is_research_mode, last_user_message.as_deref().unwrap_or_default());
+            task_config = with_routed_model(config, task);
+            &task_config
+        };
+
+        let max_turns = max_turns_for(config, is_research_mode);
         let mut current_turn = 0;
 
         // Auto-retry state
@@ -484,9 +1130,9 @@ impl Agent {
                 .unwrap_or("gemini-2.5-flash-lite".to_string());
 
             // Detect provider: Gemini models don't have slash or provider suffixes
-            let is_gemini = !selected_model.contains("/")
-                && !selected_model.contains("(Cerebras)")
-                && !selected_model.contains("(Groq)");
+            let provider = chat_provider(&selected_model, config);
+            let is_anthropic = provider == ChatProvider::Anthropic;
+            let is_gemini = provider == ChatProvider::Gemini;
 
             // Inject retry hint if pending (from previous failed attempt)
             if let Some(hint) = pending_retry_hint.take() {
@@ -497,11 +1143,25 @@ impl Agent {
                     tool_calls: None,
                     tool_call_id: None,
                     images: None,
+                    citations: None,
                 });
             }
 
-            let continue_turn = if is_gemini {
-                let api_key = config.gemini_api_key.as_ref().ok_or("No Gemini API key")?;
+            let continue_turn = if is_anthropic {
+                self.process_anthropic_turn(
+                    app_handle,
+                    config,
+                    &mut history,
+                    stream_id,
+                    rag_context_str.as_deref(),
+                    is_research_mode,
+                )
+                .await?
+            } else if is_gemini {
+                let api_key = config
+                    .gemini_api_key
+                    .as_ref()
+                    .ok_or_else(|| ShardError::missing_api_key("Gemini"))?;
                 self.process_gemini_turn(
                     app_handle,
                     config,
@@ -526,8 +1186,11 @@ impl Agent {
                 .await?
             };
 
-            // Check if we need to retry (empty response with reasoning)
-            if !continue_turn && retry_on_empty && retry_count < max_retries {
+            // Check if we need to retry (empty response with reasoning); a truncated or
+            // blocked Gemini response would just fail the same way again
+            let abnormal_finish = is_gemini
+                && self.abnormal_finish.load(std::sync::atomic::Ordering::Relaxed);
+            if !continue_turn && !abnormal_finish && retry_on_empty && retry_count < max_retries {
                 if let Some(last_msg) = history.last() {
                     let has_reasoning = last_msg.reasoning.as_ref().map(|r| !r.is_empty()).unwrap_or(false);
                     let has_content = last_msg.content.as_ref().map(|c| !c.trim().is_empty()).unwrap_or(false);
@@ -554,7 +1217,7 @@ impl Agent {
                         history.pop();
 
                         // Set up retry hint for next iteration
-                        pending_retry_hint = Some(RetryReason::EmptyResponse.get_hint());
+                        pending_retry_hint = Some(RetryReason::EmptyResponse.get_hint(config.retry_hints.as_ref()));
 
                         // Don't break - continue the loop for retry
                         continue;
@@ -566,14 +1229,20 @@ impl Agent {
                 break;
             }
         }
+        self.attach_citations(app_handle, &mut history);
 
         // Log interactions for future RAG (skip in incognito mode - use variable defined earlier)
         if !incognito {
-            // 1. Log user message
-            if let Some(emb) = user_embedding {
-                crate::interactions::log_interaction(app_handle, "user", &message, Some(emb))
-                    .await
-                    .ok();
+            // 0. Retry any writes that failed on earlier turns (keeps log order)
+            self.flush_failed_interactions();
+
+            // 1. Log user message (focus mode logs it unembedded; backfill_embeddings catches up)
+            if user_embedding.is_some() || focus_mode {
+                self.log_or_queue_interaction(crate::interactions::PendingInteraction::new(
+                    "user",
+                    &message,
+                    user_embedding,
+                ));
             }
 
             // 2. Log assistant response
@@ -582,21 +1251,15 @@ impl Agent {
                     && last_msg.content.is_some()
                 {
                     let content = last_msg.content.as_ref().unwrap();
-                    let response_embedding = if let Some(api_key) = &config.gemini_api_key {
-                        crate::interactions::generate_embedding(&self.http_client, content, api_key)
-                            .await
-                            .ok()
-                    } else {
-                        None
+                    let response_embedding = match crate::interactions::embedding_api_key(config) {
+                        Some(api_key) if !focus_mode => self.embed_or_degrade(app_handle, content, api_key).await,
+                        _ => None,
                     };
-                    crate::interactions::log_interaction(
-                        app_handle,
+                    self.log_or_queue_interaction(crate::interactions::PendingInteraction::new(
                         "model",
                         content,
                         response_embedding,
-                    )
-                    .await
-                    .ok();
+                    ));
                 }
             }
 
@@ -615,20 +1278,58 @@ impl Agent {
         args: &Value,
         config: &crate::config::AppConfig,
     ) -> String {
-        // Check cache first for cacheable tools
-        if let Some(cached) = crate::cache::get_cached_result(app_handle, function_name, args) {
-            log::info!("[Tool] Cache HIT for {} - returning cached result", function_name);
-            return cached;
+        // A model stuck in a loop gets the earlier result back instead of another execution
+        let call_key = crate::cache::make_cache_key(function_name, args);
+        if let Some(repeat) = self.tool_calls_seen.lock().unwrap().begin_call(&call_key, function_name) {
+            log::warn!("[Tool] Short-circuiting repeated {} call", function_name);
+            return repeat;
         }
 
-        let result = self.execute_tool_uncached(app_handle, function_name, args, config).await;
+        // Disabled tools and memory writes during incognito never reach the dispatcher
+        let result = crate::tools::run_guarded_tool(config, function_name, || async move {
+            crate::tool_stats::record_event(app_handle, function_name, crate::tool_stats::ToolEvent::Call);
 
-        // Cache the result if eligible
-        crate::cache::cache_result(app_handle, function_name, args, &result);
+            // Check cache first for cacheable tools
+            if let Some(cached) = crate::cache::get_cached_result(app_handle, function_name, args) {
+                log::info!("[Tool] Cache HIT for {} - returning cached result", function_name);
+                return cached;
+            }
+
+            let result = self.execute_tool_uncached(app_handle, function_name, args, config).await;
+
+            if crate::tool_stats::is_error_result(&result) {
+                crate::tool_stats::record_event(app_handle, function_name, crate::tool_stats::ToolEvent::Error);
+            }
 
+            // Cache the result if eligible
+            crate::cache::cache_result(app_handle, function_name, args, &result);
+
+            result
+        })
+        .await;
+
+        self.tool_calls_seen.lock().unwrap().record_result(&call_key, &result);
+        if is_citation_tool(function_name) {
+            self.citations.lock().unwrap().extend(extract_citations(function_name, &result));
+        }
         result
     }
 
+    /// Store the collected sources on the final assistant message and emit `agent-citations`
+    fn attach_citations<R: Runtime>(&self, app_handle: &AppHandle<R>, history: &mut [ChatMessage]) {
+        let citations = self.citations.lock().unwrap().take();
+        if citations.is_empty() {
+            return;
+        }
+        if let Some(last_msg) = history
+            .last_mut()
+            .filter(|m| m.role == "model" || m.role == "assistant")
+        {
+            last_msg.citations = Some(citations.clone());
+        }
+        app_handle.emit("agent-citations", json!(citations).to_string()).ok();
+    }
+
     /// The actual tool execution logic (separated for caching wrapper)
     async fn execute_tool_uncached<R: Runtime>(
         &self,
@@ -639,23 +1340,93 @@ impl Agent {
     ) -> String {
         match function_name {
             "get_weather" => {
-                let location = args["location"].as_str().unwrap_or_default();
-                match perform_weather_lookup(&self.http_client, location).await {
-                    Ok(Some((temp, unit, loc))) => format!("Weather in {}: {} {}", loc, temp, unit),
+                let location = match WeatherLocation::from_args(args) {
+                    Ok(location) => location,
+                    Err(e) => return format!("Error: {}", e),
+                };
+                let allow_ip = config.ip_geolocation.unwrap_or(false);
+                let days = args["days"].as_u64().unwrap_or(1).clamp(1, MAX_FORECAST_DAYS as u64) as u32;
+
+                let mut note = String::new();
+                if days > 1 {
+                    match perform_weather_forecast(&self.http_client, &location, allow_ip, days).await {
+                        Ok(Some((loc, forecast))) if !forecast.is_empty() => {
+                            let lines: Vec<String> = forecast
+                                .iter()
+                                .map(|d| {
+                                    format!(
+                                        "- {}: High {:.0}°F / Low {:.0}°F, {}",
+                                        d.date, d.high_f, d.low_f, d.condition
+                                    )
+                                })
+                                .collect();
+                            return format!("{}-day forecast for {}:\n{}", days, loc, lines.join("\n"));
+                        }
+                        Ok(None) => return "Weather data not found.".to_string(),
+                        // No forecast available - degrade to current conditions
+                        Ok(Some(_)) | Err(_) => {
+                            note = format!("Forecast unavailable for {}; showing current conditions.\n", location);
+                        }
+                    }
+                }
+
+                match perform_weather_lookup(&self.http_client, &location, allow_ip).await {
+                    Ok(Some((temp, unit, loc))) => format!("{}Weather in {}: {} {}", note, loc, temp, unit),
                     Ok(None) => "Weather data not found.".to_string(),
                     Err(e) => format!("Error: {}", e),
                 }
             }
+            "get_time" => {
+                let timezone = args["timezone"].as_str().unwrap_or_default();
+                match get_time_in_zone(timezone) {
+                    Ok(t) => format!(
+                        "Time in {}: {} ({}, UTC{})",
+                        t.timezone, t.local_time, t.abbreviation, t.utc_offset
+                    ),
+                    Err(e) => format!("Error: {}", e),
+                }
+            }
             "search_wikipedia" => {
                 let query = args["query"].as_str().unwrap_or_default();
                 match perform_wikipedia_lookup(&self.http_client, query).await {
-                    Ok(Some((title, summary, _))) => {
-                        format!("Wikipedia Title: {}\nSummary: {}", title, summary)
+                    Ok(Some((title, summary, source_url))) => {
+                        format!("Wikipedia Title: {}\nSource: {}\nSummary: {}", title, source_url, summary)
                     }
                     Ok(None) => "No Wikipedia results found.".to_string(),
                     Err(e) => format!("Error: {}", e),
                 }
             }
+            "read_wikipedia_section" => {
+                let title = args["title"].as_str().unwrap_or_default();
+                let section = args["section"].as_str();
+                match read_wikipedia_section(&self.http_client, title, section).await {
+                    Ok(WikipediaSectionResult::Sections { title, sections }) if sections.is_empty() => {
+                        format!("Wikipedia article '{}' has no sections.", title)
+                    }
+                    Ok(WikipediaSectionResult::Sections { title, sections }) => {
+                        let lines: Vec<String> = sections
+                            .iter()
+                            .map(|s| {
+                                let indent = "  ".repeat(s.level.saturating_sub(1) as usize);
+                                format!("{}{} {}", indent, s.number, s.title)
+                            })
+                            .collect();
+                        format!("Wikipedia Title: {}\nSections:\n{}", title, lines.join("\n"))
+                    }
+                    Ok(WikipediaSectionResult::Section { title, section, text }) => {
+                        let source_url = format!(
+                            "https://en.wikipedia.org/wiki/{}#{}",
+                            title.replace(' ', "_"),
+                            section.anchor
+                        );
+                        format!(
+                            "Wikipedia Title: {}\nSection: {}\nSource: {}\n\n{}",
+                            title, section.title, source_url, text
+                        )
+                    }
+                    Err(e) => format!("Error: {}", e),
+                }
+            }
             "get_stock_price" => {
                 let symbol = args["symbol"].as_str().unwrap_or_default();
                 perform_finance_lookup(symbol)
@@ -664,7 +1435,11 @@ impl Agent {
             }
             "search_arxiv" => {
                 let query = args["query"].as_str().unwrap_or_default();
-                match perform_arxiv_lookup(&self.http_client, query, 3).await {
+                let options = ArxivSearchOptions {
+                    category: args["category"].as_str().map(str::to_string),
+                    max_age_days: args["max_age_days"].as_u64().map(|d| d.min(u32::MAX as u64) as u32),
+                };
+                match perform_arxiv_lookup(&self.http_client, query, 3, &options).await {
                     Ok(papers) => {
                         let summaries: Vec<String> = papers
                             .iter()
@@ -685,11 +1460,12 @@ impl Agent {
             }
             "read_arxiv_paper" => {
                 let paper_id = args["paper_id"].as_str().unwrap_or_default();
-                match read_arxiv_paper(&self.http_client, paper_id).await {
+                let progress = |stage: &str| emit_tool_progress(app_handle, function_name, stage);
+                match read_arxiv_paper(&self.http_client, paper_id, &progress).await {
                     Ok(paper) => {
                         format!(
-                            "# {}\n\n**Abstract:** {}\n\n{}",
-                            paper.title, paper.abstract_text, paper.content
+                            "# {}\nSource: https://arxiv.org/abs/{}\n\n**Abstract:** {}\n\n{}",
+                            paper.title, paper.id, paper.abstract_text, paper.content
                         )
                     }
                     Err(e) => format!("Error reading paper: {}", e),
@@ -697,7 +1473,19 @@ impl Agent {
             }
             "web_search" => {
                 let query = args["query"].as_str().unwrap_or_default();
-                match perform_web_search(query, config.brave_api_key.as_deref()).await {
+                let options = SearchOptions {
+                    site: args["site"].as_str().map(str::to_string),
+                    freshness: args["freshness"].as_str().and_then(Freshness::parse),
+                };
+                // Only Brave is metered; the DuckDuckGo fallback is free
+                let brave_key = config.brave_api_key.as_deref().filter(|k| !k.is_empty());
+                let limit = config
+                    .web_search_monthly_limit
+                    .unwrap_or(crate::rate_limit::DEFAULT_WEB_SEARCH_MONTHLY_LIMIT);
+                if brave_key.is_some() && !crate::rate_limit::consume_web_search_quota(app_handle, limit) {
+                    return crate::rate_limit::web_search_quota_message(limit);
+                }
+                match perform_web_search(query, &options, brave_key).await {
                     Ok(results) => {
                         // Full format with snippets for the model to understand
                         let snippets: Vec<String> = results
@@ -709,37 +1497,77 @@ impl Agent {
                     Err(e) => format!("Error: {}", e),
                 }
             }
-            "save_memory" => {
-                // Block in incognito mode
-                if config.incognito_mode.unwrap_or(false) {
-                    return "Skipped: Memory saving is disabled in incognito mode.".to_string();
+            "search_discussions" => {
+                let query = args["query"].as_str().unwrap_or_default();
+                let source = args["source"].as_str().unwrap_or("hackernews");
+                match perform_discussion_search(&self.http_client, query, source).await {
+                    Ok(threads) if threads.is_empty() => {
+                        format!("No {} discussions found for '{}'.", source, query)
+                    }
+                    Ok(threads) => {
+                        let lines: Vec<String> = threads
+                            .iter()
+                            .map(|t| {
+                                format!(
+                                    "- [{}]({}) : {} points, {} comments",
+                                    t.title, t.url, t.score, t.num_comments
+                                )
+                            })
+                            .collect();
+                        format!("Discussion Results ({}):\n{}", source, lines.join("\n\n"))
+                    }
+                    Err(e) => format!("Error: {}", e),
                 }
-                // Quiet tool - no UI feedback, just log
-                let category_str = args["category"].as_str().unwrap_or("fact");
-                let content = args["content"].as_str().unwrap_or_default().to_string();
-                let importance = args["importance"].as_u64().unwrap_or(3) as u8;
-
-                let category = match category_str {
-                    "preference" => crate::memories::MemoryCategory::Preference,
-                    "project" => crate::memories::MemoryCategory::Project,
-                    "interaction" => crate::memories::MemoryCategory::Interaction,
-                    _ => crate::memories::MemoryCategory::Fact,
-                };
-
-                match crate::memories::add_memory(app_handle, category, content.clone(), importance)
-                {
-                    Ok(_) => format!("Memory saved: {}", content),
-                    Err(e) => format!("Failed to save memory: {}", e),
+            }
+            "read_feed" => {
+                let url = args["url"].as_str().unwrap_or_default();
+                let limit = args["limit"]
+                    .as_u64()
+                    .map(|n| n as usize)
+                    .unwrap_or(DEFAULT_FEED_ITEMS);
+                match read_feed(&self.http_client, url, limit).await {
+                    Ok(items) if items.is_empty() => format!("No items found in feed {}.", url),
+                    Ok(items) => {
+                        let lines: Vec<String> = items
+                            .iter()
+                            .map(|item| {
+                                let published = item.published.as_deref().unwrap_or("undated");
+                                format!(
+                                    "- [{}]({}) ({})\n  {}",
+                                    item.title, item.link, published, item.summary
+                                )
+                            })
+                            .collect();
+                        format!("Feed Items ({}):\n{}", url, lines.join("\n\n"))
+                    }
+                    Err(e) => format!("Error: {}", e),
                 }
             }
-            "update_topic_summary" => {
-                // Block in incognito mode
-                if config.incognito_mode.unwrap_or(false) {
-                    return "Skipped: Topic updates are disabled in incognito mode.".to_string();
+            "read_github" => {
+                let repo = args["repo"].as_str().unwrap_or_default();
+                let path = args["path"].as_str();
+                match read_github(&self.http_client, repo, path, config.github_token.as_deref()).await {
+                    Ok(file) => {
+                        let source = file.html_url.unwrap_or_else(|| format!("{}/{}", repo, file.path));
+                        format!("GitHub: {}\n\n{}", source, file.content)
+                    }
+                    Err(e) => format!("Error: {}", e),
+                }
+            }
+            "read_local_file" => {
+                let path = args["path"].as_str().unwrap_or_default();
+                let roots = config.allowed_file_roots.as_deref().unwrap_or_default();
+                match read_local_file(path, roots) {
+                    Ok((resolved, content)) => format!("File: {}\n\n{}", resolved.display(), content),
+                    Err(e) => format!("Error: {}", e),
                 }
+            }
+            // Quiet tool - no UI feedback, just log
+            "save_memory" => crate::memories::save_memory_tool(app_handle, args),
+            "update_topic_summary" => {
                 let topic = args["topic"].as_str().unwrap_or_default();
                 let content = args["content"].as_str().unwrap_or_default();
-                if let Some(api_key) = config.gemini_api_key.as_ref() {
+                if let Some(api_key) = crate::interactions::embedding_api_key(config) {
                     match crate::memories::update_topic_summary(
                         app_handle,
                         &self.http_client,
@@ -753,7 +1581,77 @@ impl Agent {
                         Err(e) => format!("Failed to update topic summary: {}", e),
                     }
                 } else {
-                    "Failed: No Gemini API key available for embedding generation".to_string()
+                    "Failed: No API key available for embedding generation".to_string()
+                }
+            }
+            "save_url_summary" => {
+                let url = args["url"].as_str().unwrap_or_default();
+                let topic = args["topic"].as_str().unwrap_or_default();
+                let Some(api_key) = crate::interactions::embedding_api_key(config) else {
+                    return "Failed: No API key available for embedding generation".to_string();
+                };
+                let background_model = config
+                    .background_model
+                    .as_deref()
+                    .unwrap_or(crate::background::DEFAULT_BACKGROUND_MODEL);
+                let client = &self.http_client;
+                let progress = |stage: &str| emit_tool_progress(app_handle, function_name, stage);
+
+                match save_url_summary_with(
+                    url,
+                    topic,
+                    |url| {
+                        progress("Fetching page…");
+                        async move { fetch_url_text(client, &url).await }
+                    },
+                    |prompt| {
+                        progress("Summarizing…");
+                        async move {
+                            crate::background::call_background_llm(client, config, background_model, &prompt)
+                                .await
+                        }
+                    },
+                    |topic, content| {
+                        progress("Saving summary…");
+                        async move {
+                            crate::memories::update_topic_summary(app_handle, client, api_key, &topic, &content)
+                                .await
+                        }
+                    },
+                )
+                .await
+                {
+                    Ok(topic) => format!("Saved summary of {} under topic: {}", url, topic),
+                    Err(e) => format!("Failed to save URL summary: {}", e),
+                }
+            }
+            "translate" => {
+                let text = args["text"].as_str().unwrap_or_default();
+                let target_lang = args["target_lang"].as_str().unwrap_or_default();
+                let background_model = config
+                    .background_model
+                    .as_deref()
+                    .unwrap_or(crate::background::DEFAULT_BACKGROUND_MODEL);
+                let client = &self.http_client;
+
+                match translate_with(text, target_lang, |prompt| async move {
+                    crate::background::call_background_llm(client, config, background_model, &prompt).await
+                })
+                .await
+                {
+                    Ok(t) => format!(
+                        "Detected source language: {}\nTranslation ({}):\n{}",
+                        t.source_lang, target_lang, t.translation
+                    ),
+                    Err(e) => format!("Error: {}", e),
+                }
+            }
+            "format_code" => {
+                let code = args["code"].as_str().unwrap_or_default();
+                let language = args["language"].as_str().unwrap_or_default();
+                match format_code(code, language).await {
+                    Ok(formatted) => format!("Formatted {} code:\n```{}\n{}\n```", language, language, formatted.trim_end()),
+                    Err(e) => format!("Error: {}", e),
                 }
             }
             "read_topic_summary" => {
@@ -764,11 +1662,11 @@ impl Agent {
                     Err(e) => format!("Failed to read topic summary: {}", e),
                 }
             }
+            "list_known_topics" => match crate::memories::list_known_topics(app_handle) {
+                Ok(topics) => format_known_topics(&topics),
+                Err(e) => format!("Failed to list topics: {}", e),
+            },
             "refresh_memories" => {
-                // Block in incognito mode
-                if config.incognito_mode.unwrap_or(false) {
-                    return "Skipped: Memory refresh is disabled in incognito mode.".to_string();
-                }
                 match crate::background::run_summary_job_from_agent(app_handle).await {
                     Ok(result) => {
                         let mut msg = format!(
@@ -791,10 +1689,20 @@ impl Agent {
         }
     }
 
-    async fn classify_intent(&self, query: &str, api_key: &str) -> Result<bool, String> {
+    async fn classify_intent(
+        &self,
+        config: &crate::config::AppConfig,
+        query: &str,
+        api_key: &str,
+    ) -> Result<bool, String> {
+        let model = config
+            .intent_classifier_model
+            .as_deref()
+            .filter(|m| !m.trim().is_empty())
+            .unwrap_or(DEFAULT_INTENT_CLASSIFIER_MODEL);
         let url = format!(
-            "https://generativelanguage.googleapis.com/v1beta/models/gemini-2.5-flash-lite:generateContent?key={}",
-            api_key
+            "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent?key={}",
+            model.trim(), api_key
         );
 
         let payload = serde_json::json!({
@@ -850,34 +1758,21 @@ impl Agent {
         api_key: &str,
         rag_context: Option<&str>,
         is_research_mode: bool,
-    ) -> Result<bool, String> {
+    ) -> Result<bool, ShardError> {
         let enable_tools = config.enable_tools.unwrap_or(true);
         let url = format!(
             "https://generativelanguage.googleapis.com/v1beta/models/{}:streamGenerateContent?key={}",
             selected_model, api_key
         );
 
-        // Load memories for injection into system prompt (skip in incognito mode)
-        let incognito_mode = config.incognito_mode.unwrap_or(false);
-        let memory_context = if incognito_mode {
-            None
-        } else {
-            crate::memories::get_memories_for_prompt(app_handle)
-                .ok()
-                .filter(|s| !s.is_empty())
-        };
-
-        let system_prompt_content = if incognito_mode {
-            crate::prompts::get_jailbreak_prompt(&selected_model)
-        } else if is_research_mode {
-            crate::prompts::get_research_system_prompt()
-        } else {
-            config.system_prompt.clone().unwrap_or_else(|| {
-                crate::prompts::get_default_system_prompt(memory_context.as_deref(), rag_context)
-            })
-        };
+        // Load memories for injection into system prompt (skip in incognito and focus mode)
+        let overrides = self.generation_overrides.lock().unwrap().clone();
+        let memory_context = overrides.unless_focused(|| load_memory_context(app_handle, config));
+        let system_prompt_content = overrides.system_prompt_or(|| {
+            select_system_prompt(config, &selected_model, is_research_mode, memory_context.as_deref(), rag_context)
+        });
 
-        let contents = construct_gemini_messages(history);
+        let contents = construct_gemini_messages(overrides.visible_history(history));
         let system_instruction = Some(GeminiContent {
             role: None,
             parts: vec![GeminiPart::Text {
@@ -887,7 +1782,7 @@ impl Agent {
 
         let gemini_tools = if enable_tools {
             Some(vec![GeminiTool {
-                function_declarations: crate::tools::get_all_tools()
+                function_declarations: crate::tools::get_mode_tools(config, is_research_mode)
                     .iter()
                     .map(|t| t.function.clone())
                     .collect(),
@@ -896,124 +1791,114 @@ impl Agent {
             None
         };
 
-        let supports_thinking =
-            selected_model.contains("2.5") || selected_model.contains("gemini-3") || selected_model.contains("thinking");
-
         let request_body = GenerateContentRequest {
             contents,
             tools: gemini_tools,
             system_instruction,
-            generation_config: Some(GenerationConfig {
-                thinking_config: if supports_thinking {
-                    Some(ThinkingConfig {
-                        include_thoughts: true,
-                        thinking_budget: Some(1024),
-                    })
-                } else {
-                    None
-                },
-            }),
+            generation_config: Some(overrides.generation_config(build_thinking_config(selected_model, config))),
         };
 
-        let response = self
-            .http_client
-            .post(&url)
-            .header("Content-Type", "application/json")
-            .json(&request_body)
-            .send()
-            .await
-            .map_err(|e| format!("API network error: {}", e))?;
+        crate::api_debug::log_request(config, &self.data_dir, "Gemini", &url, None, &request_body);
+
+        let max_retries = config
+            .max_network_retries
+            .unwrap_or(crate::error::DEFAULT_MAX_NETWORK_RETRIES);
+        let response = send_with_retry("Gemini", max_retries, || {
+            self.http_client
+                .post(&url)
+                .header("Content-Type", "application/json")
+                .json(&request_body)
+                .send()
+        })
+        .await?;
 
         if !response.status().is_success() {
+            let status = response.status().as_u16();
             let error_text = response.text().await.unwrap_or_default();
-            app_handle.emit("agent-error", format!("Gemini API Error: {}", error_text)).ok();
-            return Err(format!("Gemini API Error: {}", error_text));
+            crate::api_debug::log_response(config, &self.data_dir, "Gemini", status, &error_text);
+            let err = ShardError::from_api_response("Gemini", status, &error_text);
+            app_handle.emit("agent-error", err.to_string()).ok();
+            return Err(err);
         }
 
-        use futures_util::StreamExt;
+        let response_status = response.status().as_u16();
+        let debug_requests = crate::api_debug::is_enabled(config);
+        let mut raw_response = String::new();
         let mut stream = response.bytes_stream();
-        let mut buffer = Vec::new();
+        let mut reader = GeminiStreamReader::new();
         let mut full_text = String::new();
-        let mut full_reasoning = String::new();
+        let mut reasoning_buffer = ReasoningBuffer::from_config(config);
         let mut tool_calls: Vec<GeminiFunctionCallWithSignature> = Vec::new();
-
-        while let Some(item) = stream.next().await {
+        let mut md_guard = MarkdownStreamGuard::new();
+        let mut coalescer = ChunkCoalescer::from_config(config);
+        let mut finish_reason: Option<FinishReason> = None;
+
+        while let Some(item) = next_or_flush(&mut stream, &mut coalescer, |text| {
+            app_handle.emit("agent-response-chunk", text).ok();
+        })
+        .await
+        {
             if stream_id == crate::CANCELLED_STREAM_ID.load(std::sync::atomic::Ordering::Relaxed) {
                 break;
             }
 
-            let chunk = item.map_err(|e| format!("Stream error: {}", e))?;
-            buffer.extend_from_slice(&chunk);
-
-            let mut consumed = 0;
-            let mut depth = 0;
-            let mut in_string = false;
-            let mut escape = false;
-            let mut start_idx = None;
-
-            for (idx, &b) in buffer.iter().enumerate() {
-                let c = b as char;
-                if !in_string {
-                    if c == '{' {
-                        if depth == 0 {
-                            start_idx = Some(idx);
+            let chunk = item.map_err(|e| ShardError::from_reqwest("Gemini", &e))?;
+            if debug_requests {
+                raw_response.push_str(&String::from_utf8_lossy(&chunk));
+            }
+            for json_obj in reader.push(&chunk) {
+                if let Some(reason) = chunk_finish_reason(&json_obj) {
+                    finish_reason = Some(reason);
+                }
+                for candidate in json_obj.candidates.into_iter().flatten() {
+                    for part in candidate.content.parts {
+                        let calls_before = tool_calls.len();
+                        let mut part_reasoning = String::new();
+                        let events = parse_gemini_chunk(part, &mut full_text, &mut part_reasoning, &mut tool_calls);
+                        reasoning_buffer.push_str(&part_reasoning);
+                        // Text before a tool call shouldn't wait on the window
+                        if tool_calls.len() > calls_before {
+                            if let Some(text) = coalescer.flush() {
+                                app_handle.emit("agent-response-chunk", text).ok();
+                            }
                         }
-                        depth += 1;
-                    } else if c == '}' {
-                        depth -= 1;
-                        if depth == 0 {
-                            if let Some(start) = start_idx {
-                                let slice = &buffer[start..=idx];
-                                if let Ok(json_obj) =
-                                    serde_json::from_slice::<GenerateContentResponse>(slice)
-                                {
-                                    if let Some(candidates) = json_obj.candidates {
-                                        for candidate in candidates {
-                                            for part in candidate.content.parts {
-                                                let events = parse_gemini_chunk(
-                                                    part,
-                                                    &mut full_text,
-                                                    &mut full_reasoning,
-                                                    &mut tool_calls,
-                                                );
-                                                for event in events {
-                                                    match event {
-                                                        AgentEvent::ResponseChunk(text) => {
-                                                            app_handle
-                                                                .emit("agent-response-chunk", text)
-                                                                .ok();
-                                                        }
-                                                        AgentEvent::ReasoningChunk(text) => {
-                                                            app_handle
-                                                                .emit("agent-reasoning-chunk", text)
-                                                                .ok();
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                        }
+                        for event in events {
+                            match event {
+                                AgentEvent::ResponseChunk(text) => {
+                                    let safe = md_guard.push(&text);
+                                    if let Some(batch) = coalescer.push(&safe, std::time::Instant::now()) {
+                                        app_handle.emit("agent-response-chunk", batch).ok();
                                     }
-                                    consumed = idx + 1;
-                                    start_idx = None;
+                                }
+                                AgentEvent::ReasoningChunk(text) => {
+                                    app_handle.emit("agent-reasoning-chunk", text).ok();
                                 }
                             }
                         }
                     }
                 }
-                if c == '"' && !escape {
-                    in_string = !in_string;
-                }
-                if c == '\\' && !escape {
-                    escape = true;
-                } else {
-                    escape = false;
-                }
             }
+        }
+        reader.finish();
+        // The UI already has all of it; only a bounded head + tail is kept for the history
+        let full_reasoning = reasoning_buffer.finish();
+        crate::api_debug::log_response(config, &self.data_dir, "Gemini", response_status, &raw_response);
+
+        // Release anything the markdown guard or coalescer was still holding
+        let mut held = coalescer.flush().unwrap_or_default();
+        held.push_str(&md_guard.finish());
+        if !held.is_empty() {
+            app_handle.emit("agent-response-chunk", held).ok();
+        }
 
-            if consumed > 0 {
-                buffer.drain(0..consumed);
-            }
+        // MAX_TOKENS / SAFETY etc.: tell the UI, and keep the empty-response retry from looping
+        let abnormal = finish_reason.as_ref().and_then(FinishReason::abnormal_event);
+        if let Some((event, message)) = &abnormal {
+            log::warn!("[Gemini] Finished with {:?}", finish_reason);
+            app_handle.emit(event, message.clone()).ok();
         }
+        self.abnormal_finish
+            .store(abnormal.is_some(), std::sync::atomic::Ordering::Relaxed);
 
         if !tool_calls.is_empty() {
             history.push(ChatMessage {
@@ -1045,6 +1930,7 @@ impl Agent {
                 ),
                 tool_call_id: None,
                 images: None,
+                citations: None,
             });
 
             for (idx, fc) in tool_calls.into_iter().enumerate() {
@@ -1078,6 +1964,7 @@ impl Agent {
                     tool_calls: None,
                     tool_call_id: Some(format!("call_{}_{}", fc.function_call.name, idx)),
                     images: None,
+                    citations: None,
                 });
             }
             Ok(true) // Continue loop so model can respond to tool results
@@ -1097,6 +1984,7 @@ impl Agent {
                 tool_calls: None,
                 tool_call_id: None,
                 images: None,
+                citations: None,
             });
             Ok(false) // No tool calls = final response, stop the loop
         }
@@ -1110,7 +1998,7 @@ impl Agent {
         stream_id: u64,
         rag_context: Option<&str>,
         is_research_mode: bool,
-    ) -> Result<bool, String> {
+    ) -> Result<bool, ShardError> {
         let selected_model = config
             .selected_model
             .clone()
@@ -1121,72 +2009,20 @@ impl Agent {
         let is_cerebras = selected_model.contains("(Cerebras)");
         let is_groq = selected_model.contains("(Groq)");
 
-        let (api_key, base_url, model, reasoning_effort, provider_name) = if is_cerebras {
-            // Cerebras: strip suffix and use Cerebras endpoint
-            let key = config
-                .cerebras_api_key
-                .as_ref()
-                .ok_or("No Cerebras API key configured")?;
-            let clean_model = selected_model.replace(" (Cerebras)", "").trim().to_string();
-            (
-                key.clone(),
-                "https://api.cerebras.ai/v1/".to_string(),
-                clean_model,
-                Some("high".to_string()), // Cerebras supports reasoning_effort
-                "Cerebras",
-            )
-        } else if is_groq {
-            // Groq: strip suffix, add openai/ prefix, and use Groq endpoint
-            let key = config
-                .groq_api_key
-                .as_ref()
-                .ok_or("No Groq API key configured")?;
-            // Groq expects model names like "openai/gpt-oss-120b"
-            let base_model = selected_model.replace(" (Groq)", "").trim().to_string();
-            let clean_model = format!("openai/{}", base_model);
-            (
-                key.clone(),
-                "https://api.groq.com/openai/v1/".to_string(),
-                clean_model,
-                Some("high".to_string()), // Groq GPT-OSS supports reasoning_effort
-                "Groq",
-            )
-        } else {
-            // OpenRouter
-            let key = config
-                .openrouter_api_key
-                .as_ref()
-                .ok_or("No OpenRouter API key configured")?;
-            (
-                key.clone(),
-                "https://openrouter.ai/api/v1/".to_string(),
-                selected_model,
-                None, // OpenRouter doesn't use reasoning_effort
-                "OpenRouter",
-            )
-        };
-
-        let url = format!("{}chat/completions", base_url);
-
-        // Load memories for injection into system prompt (skip in incognito mode)
-        let incognito_mode = config.incognito_mode.unwrap_or(false);
-        let memory_context = if incognito_mode {
-            None
-        } else {
-            crate::memories::get_memories_for_prompt(app_handle)
-                .ok()
-                .filter(|s| !s.is_empty())
-        };
-
-        let system_prompt_content = if incognito_mode {
-            crate::prompts::get_jailbreak_prompt(&model)
-        } else if is_research_mode {
-            crate::prompts::get_research_system_prompt()
-        } else {
-            config.system_prompt.clone().unwrap_or_else(|| {
-                crate::prompts::get_default_system_prompt(memory_context.as_deref(), rag_context)
-            })
-        };
+        let endpoint = openrouter::resolve_provider(&selected_model, config)?;
+        let provider_name = endpoint.provider_name;
+        let model = endpoint.model.clone();
+        let url = endpoint.chat_completions_url();
+        let auth_header = endpoint.authorization_header();
+        let reasoning_effort = endpoint.reasoning_effort.clone();
+        let include_reasoning = endpoint.include_reasoning;
+
+        // Load memories for injection into system prompt (skip in incognito and focus mode)
+        let overrides = self.generation_overrides.lock().unwrap().clone();
+        let memory_context = overrides.unless_focused(|| load_memory_context(app_handle, config));
+        let system_prompt_content = overrides.system_prompt_or(|| {
+            select_system_prompt(config, &model, is_research_mode, memory_context.as_deref(), rag_context)
+        });
 
         let mut messages_with_system = vec![ChatMessage {
             role: "system".to_string(),
@@ -1195,8 +2031,9 @@ impl Agent {
             tool_calls: None,
             tool_call_id: None,
             images: None,
+            citations: None,
         }];
-        messages_with_system.extend(history.clone());
+        messages_with_system.extend_from_slice(overrides.visible_history(history));
 
         let api_messages: Vec<ApiChatMessage> = messages_with_system
             .iter()
@@ -1209,45 +2046,42 @@ impl Agent {
             .collect();
 
         let make_request = |tools_opt: Option<Vec<ToolDefinition>>| {
-            let model = model.clone();
-            let messages = api_messages.clone();
+            let request_body = overrides.chat_completion_request(
+                model.clone(),
+                api_messages.clone(),
+                tools_opt,
+                reasoning_effort.clone(),
+                include_reasoning,
+            );
             let url = url.clone();
-            let api_key = api_key.clone();
+            let auth_header = auth_header.clone();
             let client = self.http_client.clone();
-            let use_tools = tools_opt.is_some();
-            let reasoning_effort = reasoning_effort.clone();
+            let debug_dir = self.data_dir.clone();
 
             async move {
-                let request_body = ChatCompletionRequest {
-                    model,
-                    messages,
-                    tools: tools_opt,
-                    tool_choice: if use_tools {
-                        Some("auto".to_string())
-                    } else {
-                        None
-                    },
-                    reasoning_effort,
-                    reasoning: None,
-                    include_reasoning: if is_cerebras || is_groq { None } else { Some(true) },
-                    stream: true,
-                };
-
-                client
+                let mut request = client
                     .post(&url)
-                    .header("Authorization", format!("Bearer {}", api_key))
                     .header("Content-Type", "application/json")
-                    .header("User-Agent", "rust-reqwest/0.12")
-                    .json(&request_body)
-                    .send()
-                    .await
+                    .header("User-Agent", "rust-reqwest/0.12");
+                crate::api_debug::log_request(
+                    config,
+                    &debug_dir,
+                    provider_name,
+                    &url,
+                    auth_header.as_deref(),
+                    &request_body,
+                );
+                if let Some(auth) = auth_header {
+                    request = request.header("Authorization", auth);
+                }
+                request.json(&request_body).send().await
             }
         };
 
         let is_olmo_think = model.contains("olmo-3.1-32b-think");
         let current_tools = if enable_tools && !is_olmo_think {
             Some(
-                crate::tools::get_all_tools()
+                crate::tools::get_mode_tools(config, is_research_mode)
                     .iter()
                     .map(|t| ToolDefinition {
                         tool_type: t.tool_type.clone(),
@@ -1264,24 +2098,25 @@ impl Agent {
             None
         };
 
-        let mut response = make_request(current_tools.clone())
-            .await
-            .map_err(|e| format!("{} network error: {}", provider_name, e))?;
+        let max_retries = config
+            .max_network_retries
+            .unwrap_or(crate::error::DEFAULT_MAX_NETWORK_RETRIES);
+        let mut response =
+            send_with_retry(provider_name, max_retries, || make_request(current_tools.clone())).await?;
 
         if response.status() == 404 && enable_tools {
             println!("[{}] Got 404 with tools, retrying without tools...", provider_name);
             response = make_request(None)
                 .await
-                .map_err(|e| format!("{} network error (retry): {}", provider_name, e))?;
+                .map_err(|e| ShardError::from_reqwest(provider_name, &e))?;
         }
 
         // Check for token quota errors on Cerebras/Groq and fallback to OpenRouter
         if !response.status().is_success() {
+            let status = response.status().as_u16();
             let error_text = response.text().await.unwrap_or_default();
-            let is_quota_error = error_text.contains("token_quota_exceeded")
-                || error_text.contains("too_many_tokens")
-                || error_text.contains("rate_limit")
-                || error_text.contains("tokens per minute");
+            crate::api_debug::log_response(config, &self.data_dir, provider_name, status, &error_text);
+            let is_quota_error = crate::error::is_quota_error(status, &error_text);
 
             // Only fallback for Cerebras/Groq quota errors, not OpenRouter
             if is_quota_error && (is_cerebras || is_groq) {
@@ -1299,21 +2134,22 @@ impl Agent {
                     // Use GPT-OSS-120b on OpenRouter as fallback
                     let fallback_model = "openai/gpt-oss-120b:free".to_string();
 
-                    let fallback_body = ChatCompletionRequest {
-                        model: fallback_model,
-                        messages: api_messages.clone(),
-                        tools: current_tools.clone(),
-                        tool_choice: if current_tools.is_some() {
-                            Some("auto".to_string())
-                        } else {
-                            None
-                        },
-                        reasoning_effort: None,
-                        reasoning: None,
-                        include_reasoning: Some(true),
-                        stream: true,
-                    };
-
+                    let fallback_body = overrides.chat_completion_request(
+                        fallback_model,
+                        api_messages.clone(),
+                        current_tools.clone(),
+                        None,
+                        Some(true),
+                    );
+
+                    crate::api_debug::log_request(
+                        config,
+                        &self.data_dir,
+                        "OpenRouter",
+                        openrouter_url,
+                        Some(&format!("Bearer {}", openrouter_key)),
+                        &fallback_body,
+                    );
                     response = self.http_client
                         .post(openrouter_url)
                         .header("Authorization", format!("Bearer {}", openrouter_key))
@@ -1322,44 +2158,66 @@ impl Agent {
                         .json(&fallback_body)
                         .send()
                         .await
-                        .map_err(|e| format!("OpenRouter fallback network error: {}", e))?;
+                        .map_err(|e| ShardError::from_reqwest("OpenRouter", &e))?;
 
                     // Check if fallback succeeded
                     if !response.status().is_success() {
+                        let fallback_status = response.status().as_u16();
                         let fallback_error = response.text().await.unwrap_or_default();
-                        app_handle.emit("agent-error", format!("OpenRouter fallback error: {}", fallback_error)).ok();
-                        return Err(format!("OpenRouter fallback error: {}", fallback_error));
+                        crate::api_debug::log_response(
+                            config,
+                            &self.data_dir,
+                            "OpenRouter",
+                            fallback_status,
+                            &fallback_error,
+                        );
+                        let err = ShardError::from_api_response("OpenRouter", fallback_status, &fallback_error);
+                        app_handle.emit("agent-error", format!("OpenRouter fallback error: {}", err)).ok();
+                        return Err(err);
                     }
                     // Continue with fallback response
                 } else {
                     // No OpenRouter key available, show original error
-                    app_handle.emit("agent-error", format!("{} error: {}", provider_name, error_text)).ok();
-                    return Err(format!("{} error: {}", provider_name, error_text));
+                    let err = ShardError::from_api_response(provider_name, status, &error_text);
+                    app_handle.emit("agent-error", err.to_string()).ok();
+                    return Err(err);
                 }
             } else {
                 // Not a quota error or already on OpenRouter, show original error
-                app_handle.emit("agent-error", format!("{} error: {}", provider_name, error_text)).ok();
-                return Err(format!("{} error: {}", provider_name, error_text));
+                let err = ShardError::from_api_response(provider_name, status, &error_text);
+                app_handle.emit("agent-error", err.to_string()).ok();
+                return Err(err);
             }
         }
 
         let mut full_content = String::new();
-        let mut full_reasoning = String::new();
+        let mut reasoning_buffer = ReasoningBuffer::from_config(config);
         let mut tool_calls_buffer: Vec<ToolCall> = Vec::new();
-        use futures_util::StreamExt;
+        let mut md_guard = MarkdownStreamGuard::new();
+        let mut coalescer = ChunkCoalescer::from_config(config);
 
+        let response_status = response.status().as_u16();
+        let debug_requests = crate::api_debug::is_enabled(config);
+        let mut raw_response = String::new();
         let mut stream = response.bytes_stream();
         let mut buffer = String::new();
 
-        while let Some(item) = stream.next().await {
+        while let Some(item) = next_or_flush(&mut stream, &mut coalescer, |text| {
+            app_handle.emit("agent-response-chunk", text).ok();
+        })
+        .await
+        {
             if stream_id == crate::CANCELLED_STREAM_ID.load(std::sync::atomic::Ordering::Relaxed) {
                 break;
             }
             let chunk = item.map_err(|e| {
                 log::debug!("Stream chunk error: {}", e);
-                format!("Stream error: {}", e)
+                ShardError::from_reqwest(provider_name, &e)
             })?;
             let chunk_str = String::from_utf8_lossy(&chunk);
+            if debug_requests {
+                raw_response.push_str(&chunk_str);
+            }
             buffer.push_str(&chunk_str);
 
             let mut consumed = 0;
@@ -1376,10 +2234,15 @@ impl Agent {
                         if let Ok(json) = serde_json::from_str::<serde_json::Value>(json_str) {
                             if let Some(choices) = json.get("choices").and_then(|c| c.as_array()) {
                                 if let Some(choice) = choices.first() {
-                                    if let Some(reasoning) = choice["delta"].get("reasoning") {
+                                    // Local servers (LM Studio, llama.cpp) use reasoning_content
+                                    let reasoning = choice["delta"]
+                                        .get("reasoning")
+                                        .filter(|r| r.is_string())
+                                        .or_else(|| choice["delta"].get("reasoning_content"));
+                                    if let Some(reasoning) = reasoning {
                                         if !reasoning.is_null() && reasoning.as_str().is_some() {
                                             let reasoning_str = reasoning.as_str().unwrap();
-                                            full_reasoning.push_str(reasoning_str);
+                                            reasoning_buffer.push_str(reasoning_str);
                                             app_handle
                                                 .emit("agent-reasoning-chunk", reasoning_str)
                                                 .ok();
@@ -1390,12 +2253,19 @@ impl Agent {
                                         choice["delta"].get("content").and_then(|c| c.as_str())
                                     {
                                         full_content.push_str(content);
-                                        app_handle.emit("agent-response-chunk", content).ok();
+                                        let safe = md_guard.push(content);
+                                        if let Some(batch) = coalescer.push(&safe, std::time::Instant::now()) {
+                                            app_handle.emit("agent-response-chunk", batch).ok();
+                                        }
                                     }
 
                                     if let Some(delta_tool_calls) =
                                         choice["delta"].get("tool_calls")
                                     {
+                                        // Text before a tool call shouldn't wait on the window
+                                        if let Some(text) = coalescer.flush() {
+                                            app_handle.emit("agent-response-chunk", text).ok();
+                                        }
                                         if let Some(tool_calls_arr) = delta_tool_calls.as_array() {
                                             for tool_call_json in tool_calls_arr {
                                                 let index =
@@ -1442,7 +2312,16 @@ impl Agent {
                 buffer.drain(0..consumed);
             }
         }
+        crate::api_debug::log_response(config, &self.data_dir, provider_name, response_status, &raw_response);
+
+        // Release anything the markdown guard or coalescer was still holding
+        let mut held = coalescer.flush().unwrap_or_default();
+        held.push_str(&md_guard.finish());
+        if !held.is_empty() {
+            app_handle.emit("agent-response-chunk", held).ok();
+        }
 
+        let full_reasoning = reasoning_buffer.finish();
         if !full_content.is_empty() || !tool_calls_buffer.is_empty() || !full_reasoning.is_empty() {
             history.push(ChatMessage {
                 role: "assistant".to_string(),
@@ -1463,6 +2342,7 @@ impl Agent {
                 },
                 tool_call_id: None,
                 images: None,
+                citations: None,
             });
 
             if !tool_calls_buffer.is_empty() {
@@ -1498,6 +2378,7 @@ impl Agent {
                         tool_calls: None,
                         tool_call_id: Some(tool_call.id.clone()),
                         images: None,
+                        citations: None,
                     });
                 }
                 Ok(true) // Continue loop so model can respond to tool results
@@ -1508,4 +2389,1222 @@ impl Agent {
             Ok(false) // No content = stop
         }
     }
+
+    /// Process a turn against Anthropic's Messages API
+    /// Returns Ok(true) if there are tool calls to continue with, Ok(false) if done
+    async fn process_anthropic_turn<R: Runtime>(
+        &self,
+        app_handle: &AppHandle<R>,
+        config: &crate::config::AppConfig,
+        history: &mut Vec<ChatMessage>,
+        stream_id: u64,
+        rag_context: Option<&str>,
+        is_research_mode: bool,
+    ) -> Result<bool, ShardError> {
+        let selected_model = config.selected_model.clone().unwrap_or_default();
+        let model = anthropic_model_id(&selected_model);
+        let api_key = config
+            .anthropic_api_key
+            .clone()
+            .ok_or_else(|| ShardError::missing_api_key("Anthropic"))?;
+        let enable_tools = config.enable_tools.unwrap_or(true);
+
+        // Load memories for injection into system prompt (skip in incognito and focus mode)
+        let overrides = self.generation_overrides.lock().unwrap().clone();
+        let memory_context = overrides.unless_focused(|| load_memory_context(app_handle, config));
+        let system_prompt = overrides.system_prompt_or(|| {
+            select_system_prompt(config, &model, is_research_mode, memory_context.as_deref(), rag_context)
+        });
+
+        let tools = if enable_tools {
+            Some(anthropic::to_anthropic_tools(&crate::tools::get_mode_tools(config, is_research_mode)))
+        } else {
+            None
+        };
+        let request_body = AnthropicRequest {
+            model,
+            max_tokens: anthropic::DEFAULT_ANTHROPIC_MAX_TOKENS,
+            system: Some(system_prompt),
+            messages: construct_anthropic_messages(overrides.visible_history(history)),
+            tools,
+            temperature: overrides.temperature,
+            top_p: overrides.top_p,
+            stream: true,
+        };
+
+        crate::api_debug::log_request(
+            config,
+            &self.data_dir,
+            "Anthropic",
+            anthropic::ANTHROPIC_MESSAGES_URL,
+            None,
+            &request_body,
+        );
+        let max_retries = config
+            .max_network_retries
+            .unwrap_or(crate::error::DEFAULT_MAX_NETWORK_RETRIES);
+        let response = send_with_retry("Anthropic", max_retries, || {
+            self.http_client
+                .post(anthropic::ANTHROPIC_MESSAGES_URL)
+                .header("x-api-key", &api_key)
+                .header("anthropic-version", anthropic::ANTHROPIC_VERSION)
+                .header("Content-Type", "application/json")
+                .json(&request_body)
+                .send()
+        })
+        .await?;
+
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let error_text = response.text().await.unwrap_or_default();
+            crate::api_debug::log_response(config, &self.data_dir, "Anthropic", status, &error_text);
+            let err = ShardError::from_api_response("Anthropic", status, &error_text);
+            app_handle.emit("agent-error", err.to_string()).ok();
+            return Err(err);
+        }
+
+        let mut full_content = String::new();
+        let mut reasoning_buffer = ReasoningBuffer::from_config(config);
+        let mut tool_uses = ToolUseAccumulator::default();
+        let mut md_guard = MarkdownStreamGuard::new();
+        let mut coalescer = ChunkCoalescer::from_config(config);
+
+        let response_status = response.status().as_u16();
+        let debug_requests = crate::api_debug::is_enabled(config);
+        let mut raw_response = String::new();
+        let mut stream = response.bytes_stream();
+        let mut buffer = String::new();
+
+        while let Some(item) = next_or_flush(&mut stream, &mut coalescer, |text| {
+            app_handle.emit("agent-response-chunk", text).ok();
+        })
+        .await
+        {
+            if stream_id == crate::CANCELLED_STREAM_ID.load(std::sync::atomic::Ordering::Relaxed) {
+                break;
+            }
+            let chunk = item.map_err(|e| ShardError::from_reqwest("Anthropic", &e))?;
+            let chunk_str = String::from_utf8_lossy(&chunk);
+            if debug_requests {
+                raw_response.push_str(&chunk_str);
+            }
+            buffer.push_str(&chunk_str);
+
+            let Some(last_newline) = buffer.rfind('\n') else {
+                continue;
+            };
+            for line in buffer[..last_newline].lines() {
+                let Some(data) = line.trim().strip_prefix("data: ") else {
+                    continue;
+                };
+                match parse_anthropic_event(data) {
+                    Some(AnthropicStreamEvent::Text(text)) => {
+                        full_content.push_str(&text);
+                        let safe = md_guard.push(&text);
+                        if let Some(batch) = coalescer.push(&safe, std::time::Instant::now()) {
+                            app_handle.emit("agent-response-chunk", batch).ok();
+                        }
+                    }
+                    Some(AnthropicStreamEvent::Thinking(thinking)) => {
+                        reasoning_buffer.push_str(&thinking);
+                        app_handle.emit("agent-reasoning-chunk", thinking).ok();
+                    }
+                    Some(AnthropicStreamEvent::ToolUseStart { index, id, name }) => {
+                        // Text before a tool call shouldn't wait on the window
+                        if let Some(text) = coalescer.flush() {
+                            app_handle.emit("agent-response-chunk", text).ok();
+                        }
+                        tool_uses.start(index, id, name);
+                    }
+                    Some(AnthropicStreamEvent::ToolInputDelta { index, partial_json }) => {
+                        tool_uses.push_input(index, &partial_json);
+                    }
+                    Some(AnthropicStreamEvent::Stop(reason)) => {
+                        log::debug!("[Anthropic] Stop reason: {}", reason);
+                    }
+                    Some(AnthropicStreamEvent::Error(message)) => {
+                        let err = ShardError::Other {
+                            message: format!("Anthropic stream error: {}", message),
+                        };
+                        app_handle.emit("agent-error", err.to_string()).ok();
+                        return Err(err);
+                    }
+                    None => {}
+                }
+            }
+            buffer.drain(..=last_newline);
+        }
+        crate::api_debug::log_response(config, &self.data_dir, "Anthropic", response_status, &raw_response);
+
+        // Release anything the markdown guard or coalescer was still holding
+        let mut held = coalescer.flush().unwrap_or_default();
+        held.push_str(&md_guard.finish());
+        if !held.is_empty() {
+            app_handle.emit("agent-response-chunk", held).ok();
+        }
+
+        let tool_calls = tool_uses.finish();
+        let full_reasoning = reasoning_buffer.finish();
+        if full_content.is_empty() && tool_calls.is_empty() && full_reasoning.is_empty() {
+            return Ok(false); // No content = stop
+        }
+
+        history.push(ChatMessage {
+            role: "assistant".to_string(),
+            content: Some(full_content).filter(|c| !c.is_empty()),
+            reasoning: Some(full_reasoning).filter(|r| !r.is_empty()),
+            tool_calls: if tool_calls.is_empty() {
+                None
+            } else {
+                Some(tool_calls.clone())
+            },
+            tool_call_id: None,
+            images: None,
+            citations: None,
+        });
+
+        for tool_call in &tool_calls {
+            let function_name = &tool_call.function.name;
+            let args: Value = serde_json::from_str(&tool_call.function.arguments).unwrap_or(json!({}));
+
+            let tool_call_event = json!({
+                "name": function_name,
+                "args": args
+            });
+            app_handle.emit("agent-tool-call", tool_call_event.to_string()).ok();
+
+            let tool_result = self.execute_tool(app_handle, function_name, &args, config).await;
+
+            let result_payload = json!({
+                "name": function_name,
+                "result": tool_result.clone()
+            });
+            app_handle.emit("agent-tool-result", result_payload.to_string()).ok();
+
+            history.push(ChatMessage {
+                role: "tool".to_string(),
+                content: Some(tool_result),
+                reasoning: None,
+                tool_calls: None,
+                tool_call_id: Some(tool_call.id.clone()),
+                images: None,
+                citations: None,
+            });
+        }
+
+        // Continue the loop so the model can respond to tool results
+        Ok(!tool_calls.is_empty())
+    }
+}
+
+// ============================================================================
+// Research Mode
+// ============================================================================
+
+/// Turn cap for research-mode requests when the config doesn't set one
+pub const DEFAULT_RESEARCH_MAX_TURNS: u32 = 15;
+/// Turn cap for normal requests when the config doesn't set one
+pub const DEFAULT_NORMAL_MAX_TURNS: u32 = 5;
+/// Gemini model used for research intent classification by default
+pub const DEFAULT_INTENT_CLASSIFIER_MODEL: &str = "gemini-2.5-flash-lite";
+
+/// Max agent turns for this request (at least 1)
+pub fn max_turns_for(config: &crate::config::AppConfig, is_research_mode: bool) -> u32 {
+    let turns = if is_research_mode {
+        config.research_max_turns.unwrap_or(DEFAULT_RESEARCH_MAX_TURNS)
+    } else {
+        config.normal_max_turns.unwrap_or(DEFAULT_NORMAL_MAX_TURNS)
+    };
+    turns.max(1)
+}
+
+/// Run `turn` until it finishes without a tool call, at most `max_turns` times.
+/// Returns how many turns ran.
+pub async fn run_turns<F, Fut>(max_turns: u32, mut turn: F) -> Result<u32, ShardError>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<bool, ShardError>>,
+{
+    let mut turns_run = 0;
+    while turns_run < max_turns {
+        turns_run += 1;
+        if !turn().await? {
+            break;
+        }
+    }
+    Ok(turns_run)
+}
+
+/// Research mode is forced by `research_mode`; otherwise the classifier decides,
+/// unless `auto_research_detection` is off (then no classifier call is made)
+pub async fn detect_research_mode<F, Fut>(
+    config: &crate::config::AppConfig,
+    last_user_message: Option<&str>,
+    classify: F,
+) -> bool
+where
+    F: FnOnce(String) -> Fut,
+    Fut: std::future::Future<Output = Result<bool, String>>,
+{
+    if config.research_mode.unwrap_or(false) {
+        return true;
+    }
+    if !config.auto_research_detection.unwrap_or(true) {
+        return false;
+    }
+    match last_user_message {
+        Some(query) if !query.trim().is_empty() => classify(query.to_string()).await.unwrap_or(false),
+        _ => false,
+    }
+}
+
+// ============================================================================
+// Network Retries
+// ============================================================================
+
+/// Send a provider request, retrying 429/500/502/503 with exponential backoff + jitter.
+/// Returns the last response (success or not) so callers keep their own error handling.
+async fn send_with_retry<F, Fut>(
+    provider: &str,
+    max_retries: u32,
+    mut send: F,
+) -> Result<reqwest::Response, ShardError>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<reqwest::Response, reqwest::Error>>,
+{
+    let mut attempt = 0;
+    loop {
+        let response = send()
+            .await
+            .map_err(|e| ShardError::from_reqwest(provider, &e))?;
+        let status = response.status().as_u16();
+        if !crate::error::is_retryable_status(status) || attempt >= max_retries {
+            return Ok(response);
+        }
+
+        let delay = crate::error::backoff_delay(attempt, crate::error::jitter_fraction());
+        log::warn!(
+            "[{}] Got {}, retrying in {}ms ({}/{})",
+            provider,
+            status,
+            delay.as_millis(),
+            attempt + 1,
+            max_retries
+        );
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+// ============================================================================
+// Prompt Assembly
+// ============================================================================
+
+/// Past interactions, the best matching topic/insight and favorited notes for a message,
+/// plus the retrieved sources behind it. The context is None when nothing relevant was
+/// found (or without an embedding, apart from favorites). `record_usage` counts the
+/// picked topic/insight towards its usage stats; previews pass false.
+fn assemble_rag_context<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    config: &crate::config::AppConfig,
+    message: &str,
+    user_embedding: Option<&[f32]>,
+    record_usage: bool,
+) -> (Option<String>, Vec<ContextUsed>) {
+    let incognito = config.incognito_mode.unwrap_or(false);
+
+    let search = if let Some(emb) = user_embedding {
+        // Use hybrid search with RRF fusion of BM25 and dense results
+        crate::interactions::hybrid_search_hits(
+            app_handle, message, emb, /* limit= */ 5,
+        )
+        .unwrap_or_default()
+    } else {
+        Default::default()
+    };
+    let crate::interactions::HybridSearchResults { interactions: interaction_hits, topic_chunks } = search;
+    let relevant_interactions: Vec<crate::interactions::InteractionEntry> =
+        interaction_hits.iter().map(|h| h.entry.clone()).collect();
+    let mut relevant_context = None;
+
+    let rag_budget = config
+        .rag_context_chars
+        .unwrap_or(crate::interactions::DEFAULT_RAG_CONTEXT_CHARS) as usize;
+    let block = crate::interactions::build_rag_context(&relevant_interactions, rag_budget);
+    // Only hits that survived the budget/dedup pass count as used
+    let interaction_hits: Vec<crate::interactions::InteractionSearchHit> = block
+        .as_ref()
+        .map(|block| block.kept.iter().map(|&idx| interaction_hits[idx].clone()).collect())
+        .unwrap_or_default();
+    let mut rag_context = block.map(|block| block.text);
+
+    // RAG: Context from Topics or Insights (Tier 2 / 2.5)
+    let mut matched_context: Option<String> = None;
+    if let Some(emb) = user_embedding {
+        let threshold = config
+            .context_similarity_threshold
+            .unwrap_or(crate::memories::DEFAULT_CONTEXT_THRESHOLD);
+        let insight_tie_threshold = config
+            .insight_tie_threshold
+            .unwrap_or(crate::memories::DEFAULT_INSIGHT_TIE_THRESHOLD);
+
+        let found = if record_usage {
+            crate::memories::find_relevant_context(app_handle, emb, threshold, insight_tie_threshold)
+        } else {
+            crate::memories::select_relevant_context(app_handle, emb, threshold, insight_tie_threshold)
+        };
+        if let Ok(Some(context)) = found {
+            matched_context = Some(context.name.clone());
+            relevant_context = Some(context.clone());
+            let s = rag_context.get_or_insert_with(String::new);
+            if context.is_insight {
+                s.push_str("\n\nRelevant Insight:\n");
+                s.push_str(&format!("### Insight: {}\n{}\n\n", context.name, context.content));
+                log::info!("[Agent] Using insight: {} (score {:.3})", context.name, context.score);
+            } else {
+                s.push_str("\n\nRelevant Topic Summary:\n");
+                s.push_str(&format!("### Topic: {}\n{}\n\n", context.name, context.content));
+                log::info!("[Agent] Using topic: {} (score {:.3})", context.name, context.score);
+            }
+        }
+    }
+
+    // Topic chunks that ranked with the interactions, minus the topic already included whole
+    let topic_chunks: Vec<crate::interactions::TopicChunkHit> = topic_chunks
+        .into_iter()
+        .filter(|chunk| {
+            relevant_context
+                .as_ref()
+                .is_none_or(|context| context.is_insight || context.name != chunk.topic)
+        })
+        .collect();
+    if !topic_chunks.is_empty() {
+        let s = rag_context.get_or_insert_with(String::new);
+        s.push_str("\n\nRelevant Topic Excerpts:\n");
+        for chunk in &topic_chunks {
+            s.push_str(&format!("### Topic: {}\n{}\n\n", chunk.topic, chunk.content));
+        }
+    }
+
+    // Favorited topics/insights are included regardless of similarity
+    if !incognito {
+        let favorites = crate::memories::find_favorite_context(
+            app_handle,
+            user_embedding,
+            matched_context.as_deref(),
+        )
+        .unwrap_or_default();
+        if !favorites.is_empty() {
+            let s = rag_context.get_or_insert_with(String::new);
+            s.push_str("\n\nFavorite Notes:\n");
+            for fav in favorites {
+                let kind = if fav.is_insight { "Insight" } else { "Topic" };
+                s.push_str(&format!("### {}: {}\n{}\n\n", kind, fav.name, fav.content));
+            }
+        }
+    }
+
+    let used = context_used_sources(&interaction_hits, &topic_chunks, relevant_context.as_ref());
+    (rag_context, used)
+}
+
+/// Embed the message and assemble its RAG context, returning the embedding for logging.
+/// Focus mode skips both: no embedding request and no retrieval.
+pub async fn gather_rag_context<E, Fut, A>(
+    focus_mode: bool,
+    embed: E,
+    assemble: A,
+) -> (Option<Vec<f32>>, Option<String>, Vec<ContextUsed>)
+where
+    E: FnOnce() -> Fut,
+    Fut: std::future::Future<Output = Option<Vec<f32>>>,
+    A: FnOnce(Option<&[f32]>) -> (Option<String>, Vec<ContextUsed>),
+{
+    if focus_mode {
+        return (None, None, Vec::new());
+    }
+    let embedding = embed().await;
+    let (rag_context, used) = assemble(embedding.as_deref());
+    (embedding, rag_context, used)
+}
+
+/// A retrieved source that contributed to the prompt, sent as `agent-context-used`
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ContextUsed {
+    #[serde(rename = "type")]
+    pub kind: String, // "topic" | "insight" | "interaction"
+    pub name: String, // Topic/insight name, or the start of the interaction's content
+    pub score: f32,
+}
+
+/// Characters of an interaction's content used as its name
+const CONTEXT_USED_NAME_CHARS: usize = 80;
+
+/// Sources behind the RAG context: fused interaction hits, then topic chunk hits (each
+/// counts as its topic), then the topic/insight picked by `find_relevant_context`
+pub fn context_used_sources(
+    interaction_hits: &[crate::interactions::InteractionSearchHit],
+    topic_chunks: &[crate::interactions::TopicChunkHit],
+    relevant_context: Option<&crate::memories::RelevantContext>,
+) -> Vec<ContextUsed> {
+    let mut used: Vec<ContextUsed> = interaction_hits
+        .iter()
+        .map(|h| ContextUsed {
+            kind: "interaction".to_string(),
+            name: h.entry.content.chars().take(CONTEXT_USED_NAME_CHARS).collect::<String>().trim().to_string(),
+            score: h.hit.score,
+        })
+        .collect();
+    used.extend(topic_chunks.iter().map(|chunk| ContextUsed {
+        kind: "topic".to_string(),
+        name: chunk.topic.clone(),
+        score: chunk.hit.score,
+    }));
+    if let Some(context) = relevant_context {
+        used.push(ContextUsed {
+            kind: if context.is_insight { "insight" } else { "topic" }.to_string(),
+            name: context.name.clone(),
+            score: context.score,
+        });
+    }
+    // A topic can rank through several chunks and via find_relevant_context; report it once
+    let mut seen = std::collections::HashSet::new();
+    used.retain(|source| seen.insert((source.kind.clone(), source.name.clone())));
+    used
+}
+
+/// Send each source as an `agent-context-used` JSON string payload
+pub fn emit_context_used<F: FnMut(String)>(sources: &[ContextUsed], mut emit: F) {
+    for source in sources {
+        emit(json!(source).to_string());
+    }
+}
+
+/// Stored memories for the system prompt; None in incognito mode or when there are none
+fn load_memory_context<R: Runtime>(app_handle: &AppHandle<R>, config: &crate::config::AppConfig) -> Option<String> {
+    if config.incognito_mode.unwrap_or(false) {
+        return None;
+    }
+    crate::memories::get_memories_for_prompt(app_handle)
+        .ok()
+        .filter(|s| !s.is_empty())
+}
+
+/// System prompt for a turn: the jailbreak prompt in incognito mode, the research prompt
+/// in research mode, otherwise the configured prompt with memories and RAG context
+pub fn select_system_prompt(
+    config: &crate::config::AppConfig,
+    model: &str,
+    is_research_mode: bool,
+    memory_context: Option<&str>,
+    rag_context: Option<&str>,
+) -> String {
+    if config.incognito_mode.unwrap_or(false) {
+        crate::prompts::get_jailbreak_prompt(model)
+    } else if is_research_mode {
+        crate::prompts::get_research_system_prompt()
+    } else {
+        crate::prompts::build_system_prompt(config, is_research_mode, memory_context, rag_context)
+    }
+}
+
+/// What `process_message` would send for a message, without sending it
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct PromptPreview {
+    pub system_prompt: String,
+    pub rag_context: Option<String>,
+    pub memory_context: Option<String>,
+    pub model: String,
+    pub research_mode: bool,
+    pub context_used: Vec<ContextUsed>,
+}
+
+// ============================================================================
+// Chat Titles
+// ============================================================================
+
+/// Longest title accepted from the model
+pub const MAX_TITLE_WORDS: usize = 6;
+/// Per-message character budget when building the title prompt
+const TITLE_EXCERPT_CHARS: usize = 500;
+
+/// Text of the first non-empty message with `role`
+fn first_message_text<'a>(history: &'a [ChatMessage], role: &str) -> Option<&'a str> {
+    history
+        .iter()
+        .filter(|msg| msg.role == role)
+        .filter_map(|msg| msg.content.as_deref())
+        .map(str::trim)
+        .find(|text| !text.is_empty())
+}
+
+/// Prompt asking for a short title from the first user message and first assistant reply.
+/// Errors when the history has no user message to title.
+pub fn build_title_prompt(history: &[ChatMessage]) -> Result<String, String> {
+    let excerpt = |text: &str| -> String { text.chars().take(TITLE_EXCERPT_CHARS).collect() };
+
+    let user = first_message_text(history, "user")
+        .ok_or("Cannot generate a title for an empty chat")?;
+    let mut prompt = format!(
+        "Write a short, specific title (at most {} words) for the conversation below. \
+Respond with ONLY the title: no quotes, no trailing punctuation, no explanation.\n\n\
+User: {}",
+        MAX_TITLE_WORDS,
+        excerpt(user)
+    );
+    if let Some(reply) = first_message_text(history, "assistant") {
+        prompt.push_str(&format!("\nAssistant: {}", excerpt(reply)));
+    }
+    Ok(prompt)
+}
+
+/// Normalize a model-written title: first line, quotes/markdown stripped, at most MAX_TITLE_WORDS
+pub fn clean_title(raw: &str) -> String {
+    let line = raw.trim().lines().next().unwrap_or_default();
+    let line = line.trim_start_matches('#').trim();
+    let line = line
+        .strip_prefix("Title:")
+        .or_else(|| line.strip_prefix("title:"))
+        .unwrap_or(line);
+
+    line.split_whitespace()
+        .take(MAX_TITLE_WORDS)
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_matches(|c: char| matches!(c, '"' | '\'' | '*' | '`' | '.' | ':'))
+        .trim()
+        .to_string()
+}
+
+// ============================================================================
+// Conversation Summary
+// ============================================================================
+
+/// Summary length used when the caller passes 0
+pub const DEFAULT_SUMMARY_WORDS: usize = 200;
+/// Per-message character budget in the summary transcript
+const SUMMARY_EXCERPT_CHARS: usize = 2000;
+
+/// Prompt asking for a handoff summary of the user/assistant exchange. System prompts,
+/// tool results and tool-call-only assistant turns are left out of the transcript.
+/// Errors when there is nothing to summarize.
+pub fn build_summary_prompt(history: &[ChatMessage], max_words: usize) -> Result<String, String> {
+    let max_words = if max_words == 0 { DEFAULT_SUMMARY_WORDS } else { max_words };
+
+    let transcript: Vec<String> = history
+        .iter()
+        .filter_map(|msg| {
+            let speaker = match msg.role.as_str() {
+                "user" => "User",
+                "assistant" | "model" => "Assistant",
+                _ => return None,
+            };
+            let text = msg.content.as_deref().map(str::trim).filter(|t| !t.is_empty())?;
+            let excerpt: String = text.chars().take(SUMMARY_EXCERPT_CHARS).collect();
+            Some(format!("{}: {}", speaker, excerpt))
+        })
+        .collect();
+    if transcript.is_empty() {
+        return Err("Cannot summarize an empty chat".to_string());
+    }
+
+    Ok(format!(
+        "Summarize the conversation below so someone else can pick it up where it left off. \
+Cover the goal, what has been established or decided, and any open questions or next steps. \
+Write plain prose in at most {} words. Respond with ONLY the summary.\n\n{}",
+        max_words,
+        transcript.join("\n\n")
+    ))
+}
+
+// ============================================================================
+// History Branching
+// ============================================================================
+
+/// Truncate `history` to messages 0..index and return the full pre-branch history.
+/// Rejects indices that would split an assistant tool call from its tool results.
+pub fn branch_history(
+    history: &mut Vec<ChatMessage>,
+    index: usize,
+) -> Result<Vec<ChatMessage>, String> {
+    if index > history.len() {
+        return Err(format!(
+            "Invalid branch index {} (history has {} messages)",
+            index,
+            history.len()
+        ));
+    }
+
+    if history.get(index).is_some_and(|msg| msg.role == "tool") {
+        return Err(format!(
+            "Cannot branch at index {}: it would separate a tool result from its tool call",
+            index
+        ));
+    }
+
+    if index > 0 {
+        let last_kept = &history[index - 1];
+        if last_kept.tool_calls.as_ref().is_some_and(|calls| !calls.is_empty()) {
+            return Err(format!(
+                "Cannot branch at index {}: it would drop the results of a pending tool call",
+                index
+            ));
+        }
+    }
+
+    let full_history = history.clone();
+    history.truncate(index);
+    Ok(full_history)
+}
+
+/// Remove everything after the last user message (assistant replies, tool calls and
+/// results from that turn) and return it. Errors if there's no user message to answer.
+pub fn pop_last_response(history: &mut Vec<ChatMessage>) -> Result<Vec<ChatMessage>, String> {
+    let last_user = history
+        .iter()
+        .rposition(|msg| msg.role == "user")
+        .ok_or("No user message to respond to")?;
+    Ok(history.split_off(last_user + 1))
+}
+
+/// Index of the last user message, where the turn being answered starts (0 if there is none)
+pub fn last_turn_start(history: &[ChatMessage]) -> usize {
+    history.iter().rposition(|msg| msg.role == "user").unwrap_or(0)
+}
+
+// ============================================================================
+// History Compaction
+// ============================================================================
+
+/// Prefix of the synthetic note that replaces compacted turns
+pub const COMPACTED_SUMMARY_HEADER: &str = "[Summary of earlier conversation]";
+/// Per-message character budget in the compaction transcript
+const COMPACTION_EXCERPT_CHARS: usize = 2000;
+
+/// Index where the last `keep_recent` turns begin (a turn starts at a user message).
+/// Messages before it get summarized; 0 means there's nothing to compact.
+pub fn compaction_split_index(history: &[ChatMessage], keep_recent: usize) -> usize {
+    let turn_starts: Vec<usize> = history
+        .iter()
+        .enumerate()
+        .filter(|(_, msg)| msg.role == "user")
+        .map(|(i, _)| i)
+        .collect();
+
+    if keep_recent == 0 {
+        return history.len();
+    }
+    if turn_starts.len() <= keep_recent {
+        return 0;
+    }
+    turn_starts[turn_starts.len() - keep_recent]
+}
+
+/// Replace `compacted`, the history prefix that was summarized, with the summary note.
+/// False (history untouched) when the history no longer starts with those messages.
+pub fn splice_compaction_summary(history: &mut Vec<ChatMessage>, compacted: &[ChatMessage], summary: &str) -> bool {
+    if !history.starts_with(compacted) {
+        return false;
+    }
+    let note = ChatMessage {
+        role: "assistant".to_string(),
+        content: Some(format!("{}\n{}", COMPACTED_SUMMARY_HEADER, summary.trim())),
+        reasoning: None,
+        tool_calls: None,
+        tool_call_id: None,
+        images: None,
+        citations: None,
+    };
+    history.drain(..compacted.len());
+    history.insert(0, note);
+    true
+}
+
+/// Transcript of the messages being compacted, with instructions to summarize them
+pub fn build_compaction_prompt(messages: &[ChatMessage]) -> String {
+    let mut transcript = String::new();
+    for msg in messages {
+        let text = match (&msg.content, &msg.tool_calls) {
+            (Some(content), _) if !content.trim().is_empty() => {
+                content.chars().take(COMPACTION_EXCERPT_CHARS).collect::<String>()
+            }
+            (_, Some(calls)) if !calls.is_empty() => {
+                let names: Vec<&str> = calls.iter().map(|c| c.function.name.as_str()).collect();
+                format!("(called tools: {})", names.join(", "))
+            }
+            _ => continue,
+        };
+        transcript.push_str(&format!("{}: {}\n\n", msg.role, text.trim()));
+    }
+
+    format!(
+        "Summarize the earlier part of this conversation so it can continue without the full \
+transcript. Keep facts, decisions, user preferences, open questions, and important tool results; \
+drop pleasantries. Write concise bullet points in the third person.\n\n\
+Transcript:\n{}",
+        transcript.trim_end()
+    )
+}
+
+// ============================================================================
+// History Pagination
+// ============================================================================
+
+/// A slice of chat history for virtualized rendering
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct HistoryPage {
+    pub messages: Vec<ChatMessage>,
+    /// Index of each returned message in the full history (for rewind/branch)
+    pub indices: Vec<usize>,
+    /// Total messages matching the role filter
+    pub total: usize,
+}
+
+/// Return `limit` messages starting at `offset` among those whose role is in `roles`
+/// (all roles when None). Out-of-range offsets yield an empty page.
+pub fn paginate_history(
+    history: &[ChatMessage],
+    offset: usize,
+    limit: usize,
+    roles: Option<&[String]>,
+) -> HistoryPage {
+    let matching: Vec<(usize, &ChatMessage)> = history
+        .iter()
+        .enumerate()
+        .filter(|(_, msg)| roles.is_none_or(|roles| roles.iter().any(|r| *r == msg.role)))
+        .collect();
+
+    let page: Vec<(usize, &ChatMessage)> =
+        matching.iter().skip(offset).take(limit).copied().collect();
+
+    HistoryPage {
+        messages: page.iter().map(|(_, msg)| (*msg).clone()).collect(),
+        indices: page.iter().map(|(i, _)| *i).collect(),
+        total: matching.len(),
+    }
+}
+
+// ============================================================================
+// History Persistence
+// ============================================================================
+
+/// Serialize history for a session file; reasoning is dropped unless `persist_reasoning`
+/// (the in-memory history keeps it for the current session either way)
+pub fn serialize_history(history: &[ChatMessage], persist_reasoning: bool) -> serde_json::Result<String> {
+    if persist_reasoning {
+        return serde_json::to_string_pretty(history);
+    }
+    let stripped: Vec<ChatMessage> = history
+        .iter()
+        .cloned()
+        .map(|msg| ChatMessage { reasoning: None, ..msg })
+        .collect();
+    serde_json::to_string_pretty(&stripped)
+}
+
+// ============================================================================
+// Repeated Tool Calls
+// ============================================================================
+
+/// Identical calls (same tool and args) executed per message; later ones are short-circuited
+pub const MAX_IDENTICAL_TOOL_CALLS: u32 = 3;
+/// Previous result length echoed back in the short-circuit message
+const REPEAT_RESULT_PREVIEW_CHARS: usize = 2000;
+
+/// Counts `(tool, args hash)` pairs within a single `process_message` invocation
+#[derive(Debug, Default)]
+pub struct ToolCallTracker {
+    calls: std::collections::HashMap<String, (u32, Option<String>)>,
+}
+
+impl ToolCallTracker {
+    pub fn reset(&mut self) {
+        self.calls.clear();
+    }
+
+    /// Count a call keyed by `make_cache_key`; returns the short-circuit reply once it
+    /// has already run `MAX_IDENTICAL_TOOL_CALLS` times
+    pub fn begin_call(&mut self, key: &str, tool_name: &str) -> Option<String> {
+        let entry = self.calls.entry(key.to_string()).or_insert((0, None));
+        if entry.0 >= MAX_IDENTICAL_TOOL_CALLS {
+            return Some(repeated_tool_call_message(tool_name, entry.0, entry.1.as_deref()));
+        }
+        entry.0 += 1;
+        None
+    }
+
+    pub fn record_result(&mut self, key: &str, result: &str) {
+        if let Some(entry) = self.calls.get_mut(key) {
+            entry.1 = Some(result.to_string());
+        }
+    }
+}
+
+/// Tool reply telling the model it already has this result and should answer from it
+pub fn repeated_tool_call_message(tool_name: &str, times: u32, previous: Option<&str>) -> String {
+    let mut msg = format!(
+        "Already performed this {} call {} times with identical arguments in this response.",
+        tool_name, times
+    );
+    if let Some(previous) = previous {
+        let preview: String = previous.chars().take(REPEAT_RESULT_PREVIEW_CHARS).collect();
+        msg.push_str("\nPrevious result:\n");
+        msg.push_str(&preview);
+    }
+    msg.push_str(
+        "\n\nDo not repeat this call. Synthesize your answer from the results you already have, \
+         or try a different query if something is genuinely missing.",
+    );
+    msg
+}
+
+// ============================================================================
+// Known Topics
+// ============================================================================
+
+/// Tool reply for `list_known_topics`
+pub fn format_known_topics(topics: &[crate::memories::KnownTopic]) -> String {
+    if topics.is_empty() {
+        return "No topic summaries saved yet.".to_string();
+    }
+    let lines: Vec<String> = topics
+        .iter()
+        .map(|t| {
+            if t.preview.is_empty() {
+                format!("- {}", t.name)
+            } else {
+                format!("- {}: {}", t.name, t.preview)
+            }
+        })
+        .collect();
+    format!(
+        "Known topics ({}), readable with read_topic_summary:\n{}",
+        topics.len(),
+        lines.join("\n")
+    )
+}
+
+// ============================================================================
+// Citations
+// ============================================================================
+
+/// Tools whose sources are collected for the `agent-citations` channel
+const CITATION_TOOLS: &[&str] = &["web_search", "read_arxiv_paper", "search_wikipedia"];
+
+pub fn is_citation_tool(tool_name: &str) -> bool {
+    CITATION_TOOLS.contains(&tool_name)
+}
+
+/// Read the sources back out of a citation tool's formatted result.
+/// Works on cached results too, since those skip the tool arm entirely.
+pub fn extract_citations(tool_name: &str, result: &str) -> Vec<Citation> {
+    match tool_name {
+        // "- [title](url) : snippet" per result
+        "web_search" => result
+            .lines()
+            .filter_map(|line| {
+                let (title, rest) = line.strip_prefix("- [")?.split_once("](")?;
+                let (url, _) = rest.split_once(") : ")?;
+                Some(Citation { title: title.to_string(), url: url.to_string() })
+            })
+            .collect(),
+        // Title line followed by a "Source: url" line
+        "search_wikipedia" | "read_arxiv_paper" => {
+            let mut lines = result.lines();
+            let title = lines.next().and_then(|line| {
+                line.strip_prefix("Wikipedia Title: ").or_else(|| line.strip_prefix("# "))
+            });
+            let url = lines.next().and_then(|line| line.strip_prefix("Source: "));
+            match (title, url) {
+                (Some(title), Some(url)) => vec![Citation { title: title.to_string(), url: url.to_string() }],
+                _ => Vec::new(),
+            }
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Sources gathered across every tool call within one `process_message` invocation
+#[derive(Debug, Default)]
+pub struct CitationTracker {
+    citations: Vec<Citation>,
+}
+
+impl CitationTracker {
+    pub fn reset(&mut self) {
+        self.citations.clear();
+    }
+
+    /// Add sources in visit order, skipping URLs already cited this turn
+    pub fn extend(&mut self, citations: Vec<Citation>) {
+        for citation in citations {
+            if !self.citations.iter().any(|c| c.url == citation.url) {
+                self.citations.push(citation);
+            }
+        }
+    }
+
+    /// Drain the collected sources for the finished message
+    pub fn take(&mut self) -> Vec<Citation> {
+        std::mem::take(&mut self.citations)
+    }
+}
+
+// ============================================================================
+// Raw Passthrough
+// ============================================================================
+
+/// Per-message overrides from the `chat` command; unset fields keep the computed values
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GenerationOverrides {
+    pub system_prompt: Option<String>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    /// Answer from the message alone: no retrieval, memories or earlier history
+    pub focus_mode: bool,
+    /// History index of the message being answered (set by process_message)
+    pub turn_start: usize,
+}
+
+impl GenerationOverrides {
+    /// Blank prompts and non-finite numbers are treated as unset
+    pub fn new(system_prompt: Option<String>, temperature: Option<f32>, top_p: Option<f32>) -> Self {
+        Self {
+            system_prompt: system_prompt.filter(|p| !p.trim().is_empty()),
+            temperature: temperature.filter(|t| t.is_finite()),
+            top_p: top_p.filter(|p| p.is_finite()),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_focus_mode(mut self, focus_mode: bool) -> Self {
+        self.focus_mode = focus_mode;
+        self
+    }
+
+    /// Run `load` for stored context, or skip it in focus mode
+    pub fn unless_focused<T: Default>(&self, load: impl FnOnce() -> T) -> T {
+        if self.focus_mode {
+            T::default()
+        } else {
+            load()
+        }
+    }
+
+    /// History sent to the model: only the current turn in focus mode
+    pub fn visible_history<'a>(&self, history: &'a [ChatMessage]) -> &'a [ChatMessage] {
+        if self.focus_mode {
+            &history[self.turn_start.min(history.len())..]
+        } else {
+            history
+        }
+    }
+
+    /// The override prompt if set, otherwise the one `computed` builds
+    pub fn system_prompt_or(&self, computed: impl FnOnce() -> String) -> String {
+        self.system_prompt.clone().unwrap_or_else(computed)
+    }
+
+    pub fn generation_config(&self, thinking_config: Option<ThinkingConfig>) -> GenerationConfig {
+        GenerationConfig {
+            thinking_config,
+            temperature: self.temperature,
+            top_p: self.top_p,
+        }
+    }
+
+    /// Streaming OpenAI-compatible request body; tools are offered with `tool_choice: auto`
+    pub fn chat_completion_request(
+        &self,
+        model: String,
+        messages: Vec<ApiChatMessage>,
+        tools: Option<Vec<ToolDefinition>>,
+        reasoning_effort: Option<String>,
+        include_reasoning: Option<bool>,
+    ) -> ChatCompletionRequest {
+        ChatCompletionRequest {
+            model,
+            messages,
+            tool_choice: tools.as_ref().map(|_| "auto".to_string()),
+            tools,
+            reasoning_effort,
+            reasoning: None,
+            include_reasoning,
+            temperature: self.temperature,
+            top_p: self.top_p,
+            stream: true,
+        }
+    }
+}
+
+// ============================================================================
+// Degraded Features
+// ============================================================================
+
+/// Consecutive embedding failures before RAG is reported as unavailable
+pub const EMBEDDING_FAILURES_BEFORE_DEGRADED: u32 = 2;
+
+/// Counts consecutive embedding failures so a single blip doesn't alarm the UI
+#[derive(Debug, Default)]
+pub struct DegradationTracker {
+    consecutive_failures: u32,
+    reported: bool,
+}
+
+impl DegradationTracker {
+    /// Record a failure; true exactly once, when the `agent-degraded` event should fire
+    pub fn record_failure(&mut self) -> bool {
+        self.consecutive_failures += 1;
+        if self.reported || self.consecutive_failures < EMBEDDING_FAILURES_BEFORE_DEGRADED {
+            return false;
+        }
+        self.reported = true;
+        true
+    }
+
+    pub fn record_success(&mut self) {
+        self.consecutive_failures = 0;
+    }
+}
+
+/// `agent-degraded` payload; the reason is redacted since request errors can embed keyed URLs
+pub fn degraded_event(feature: &str, reason: &str) -> Value {
+    json!({
+        "feature": feature,
+        "reason": crate::api_debug::redact_secrets(reason)
+    })
+}
+
+// ============================================================================
+// Tool Progress
+// ============================================================================
+
+/// Emit an interim `agent-tool-progress` stage; a no-op for tools that haven't opted in
+fn emit_tool_progress<R: Runtime>(app_handle: &AppHandle<R>, tool_name: &str, stage: &str) {
+    if !crate::integrations::reports_progress(tool_name) {
+        return;
+    }
+    let event = json!({
+        "name": tool_name,
+        "stage": stage
+    });
+    app_handle.emit("agent-tool-progress", event.to_string()).ok();
+}
+
+// ============================================================================
+// Model Routing
+// ============================================================================
+
+/// Backend that serves a chat model
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatProvider {
+    Anthropic,
+    Gemini,
+    /// Cerebras, Groq, OpenRouter or a local server (see `resolve_provider`)
+    OpenAiCompatible,
+}
+
+/// Provider for `model`: Anthropic by name, Gemini for bare ids without a slash or
+/// provider suffix, otherwise an OpenAI-compatible endpoint
+pub fn chat_provider(model: &str, config: &crate::config::AppConfig) -> ChatProvider {
+    if is_anthropic_model(model, config) {
+        ChatProvider::Anthropic
+    } else if model.contains('/')
+        || model.contains("(Cerebras)")
+        || model.contains("(Groq)")
+        || model.contains("(Local)")
+    {
+        ChatProvider::OpenAiCompatible
+    } else {
+        ChatProvider::Gemini
+    }
+}
+
+/// Words that mark a message as a coding task for routing
+const CODE_TASK_KEYWORDS: &[&str] = &[
+    "code", "function", "compile", "compiler", "bug", "debug", "refactor", "implement",
+    "stacktrace", "traceback", "regex", "sql", "rust", "python", "javascript", "typescript",
+    "struct", "class", "api", "segfault", "panic",
+];
+
+/// Task type used to pick a model from `model_routes`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskType {
+    Chat,
+    Research,
+    Code,
+    Vision,
+}
+
+/// Heuristic: fenced code, or any coding keyword as a whole word
+pub fn looks_like_code_task(message: &str) -> bool {
+    if message.contains("```") {
+        return true;
+    }
+    message
+        .split(|c: char| !c.is_alphanumeric())
+        .any(|word| CODE_TASK_KEYWORDS.contains(&word.to_lowercase().as_str()))
+}
+
+/// Research wins over code, which wins over plain chat
+pub fn classify_task(is_research_mode: bool, message: &str) -> TaskType {
+    if is_research_mode {
+        TaskType::Research
+    } else if looks_like_code_task(message) {
+        TaskType::Code
+    } else {
+        TaskType::Chat
+    }
+}
+
+/// Model for a task: its route, then the `default` route, then `selected_model`
+pub fn route_model(config: &crate::config::AppConfig, task: TaskType) -> Option<String> {
+    let set = |model: &Option<String>| model.clone().filter(|m| !m.trim().is_empty());
+    let routed = config.model_routes.as_ref().and_then(|routes| {
+        let specific = match task {
+            TaskType::Chat => None,
+            TaskType::Research => set(&routes.research),
+            TaskType::Code => set(&routes.code),
+            TaskType::Vision => set(&routes.vision),
+        };
+        specific.or_else(|| set(&routes.default))
+    });
+    routed.or_else(|| config.selected_model.clone())
+}
+
+/// Copy of `config` with the session's pinned model, if any, as `selected_model`.
+/// A pin also disables task routing so every turn of that session uses the pinned model.
+pub fn with_session_model(
+    config: &crate::config::AppConfig,
+    session_model: Option<&str>,
+) -> crate::config::AppConfig {
+    let mut pinned = config.clone();
+    if let Some(model) = session_model.map(str::trim).filter(|m| !m.is_empty()) {
+        pinned.selected_model = Some(model.to_string());
+        pinned.model_routes = None;
+    }
+    pinned
+}
+
+/// Model for a retry/reprocess turn: an explicit override, else the session pin, else the config
+pub fn with_model_override(
+    config: &crate::config::AppConfig,
+    session_model: Option<&str>,
+    model_override: Option<&str>,
+) -> crate::config::AppConfig {
+    match model_override.map(str::trim).filter(|m| !m.is_empty()) {
+        Some(model) => with_session_model(config, Some(model)),
+        None => with_session_model(config, session_model),
+    }
+}
+
+/// Copy of `config` with `selected_model` replaced by the routed model
+pub fn with_routed_model(config: &crate::config::AppConfig, task: TaskType) -> crate::config::AppConfig {
+    let mut routed = config.clone();
+    routed.selected_model = route_model(config, task);
+    if routed.selected_model != config.selected_model {
+        log::info!("[Agent] Routing {:?} task to {:?}", task, routed.selected_model);
+    }
+    routed
 }
@@ -4,6 +4,8 @@
 #![allow(dead_code)]
 
 use super::types::*;
+use crate::config::AppConfig;
+use crate::error::ShardError;
 
 /// Convert chat messages to OpenRouter/OpenAI API format (text-only)
 pub fn to_api_messages(messages: &[ChatMessage]) -> Vec<ApiChatMessage> {
@@ -99,3 +101,110 @@ pub fn has_images(messages: &[ChatMessage]) -> bool {
 pub fn supports_tools(model: &str) -> bool {
     !model.contains("olmo-3.1-32b-think")
 }
+
+// ============================================================================
+// Provider Resolution
+// ============================================================================
+
+/// Model name suffix that routes to a local OpenAI-compatible server
+pub const LOCAL_MODEL_SUFFIX: &str = "(Local)";
+/// Default local endpoint (Ollama); LM Studio users can set `local_base_url`
+pub const DEFAULT_LOCAL_BASE_URL: &str = "http://localhost:11434/v1/";
+
+/// Check if a model name targets a local OpenAI-compatible server
+pub fn is_local_model(model: &str) -> bool {
+    model.contains(LOCAL_MODEL_SUFFIX)
+}
+
+/// Resolved endpoint for an OpenAI-compatible chat-completions provider
+#[derive(Debug, Clone)]
+pub struct ProviderEndpoint {
+    pub provider_name: &'static str,
+    pub base_url: String,
+    pub model: String,
+    /// None for providers that don't take an Authorization header (local)
+    pub api_key: Option<String>,
+    pub reasoning_effort: Option<String>,
+    pub include_reasoning: Option<bool>,
+}
+
+impl ProviderEndpoint {
+    pub fn chat_completions_url(&self) -> String {
+        if self.base_url.ends_with('/') {
+            format!("{}chat/completions", self.base_url)
+        } else {
+            format!("{}/chat/completions", self.base_url)
+        }
+    }
+
+    pub fn authorization_header(&self) -> Option<String> {
+        self.api_key.as_ref().map(|key| format!("Bearer {}", key))
+    }
+}
+
+/// Detect the provider from the model name suffix and build its endpoint
+pub fn resolve_provider(
+    selected_model: &str,
+    config: &AppConfig,
+) -> Result<ProviderEndpoint, ShardError> {
+    if selected_model.contains("(Cerebras)") {
+        // Cerebras: strip suffix and use Cerebras endpoint
+        let key = config
+            .cerebras_api_key
+            .as_ref()
+            .ok_or_else(|| ShardError::missing_api_key("Cerebras"))?;
+        Ok(ProviderEndpoint {
+            provider_name: "Cerebras",
+            base_url: "https://api.cerebras.ai/v1/".to_string(),
+            model: selected_model.replace(" (Cerebras)", "").trim().to_string(),
+            api_key: Some(key.clone()),
+            reasoning_effort: Some("high".to_string()), // Cerebras supports reasoning_effort
+            include_reasoning: None,
+        })
+    } else if selected_model.contains("(Groq)") {
+        // Groq: strip suffix, add openai/ prefix, and use Groq endpoint
+        let key = config
+            .groq_api_key
+            .as_ref()
+            .ok_or_else(|| ShardError::missing_api_key("Groq"))?;
+        // Groq expects model names like "openai/gpt-oss-120b"
+        let base_model = selected_model.replace(" (Groq)", "").trim().to_string();
+        Ok(ProviderEndpoint {
+            provider_name: "Groq",
+            base_url: "https://api.groq.com/openai/v1/".to_string(),
+            model: format!("openai/{}", base_model),
+            api_key: Some(key.clone()),
+            reasoning_effort: Some("high".to_string()), // Groq GPT-OSS supports reasoning_effort
+            include_reasoning: None,
+        })
+    } else if is_local_model(selected_model) {
+        // Local (Ollama/LM Studio): strip suffix, no API key
+        let base_url = config
+            .local_base_url
+            .clone()
+            .filter(|url| !url.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_LOCAL_BASE_URL.to_string());
+        Ok(ProviderEndpoint {
+            provider_name: "Local",
+            base_url,
+            model: selected_model.replace(LOCAL_MODEL_SUFFIX, "").trim().to_string(),
+            api_key: None,
+            reasoning_effort: None,
+            include_reasoning: None,
+        })
+    } else {
+        // OpenRouter
+        let key = config
+            .openrouter_api_key
+            .as_ref()
+            .ok_or_else(|| ShardError::missing_api_key("OpenRouter"))?;
+        Ok(ProviderEndpoint {
+            provider_name: "OpenRouter",
+            base_url: "https://openrouter.ai/api/v1/".to_string(),
+            model: selected_model.to_string(),
+            api_key: Some(key.clone()),
+            reasoning_effort: None, // OpenRouter doesn't use reasoning_effort
+            include_reasoning: Some(true),
+        })
+    }
+}
@@ -0,0 +1,92 @@
+// Reasoning retention - keeps a bounded head + tail of a turn's reasoning for the
+// saved message while the full stream still goes to the UI live.
+
+/// Default cap on retained reasoning characters
+pub const DEFAULT_MAX_REASONING_CHARS: usize = 32_000;
+/// Placed between the retained head and tail when the middle was dropped
+pub const REASONING_TRUNCATION_MARKER: &str = "\n\n[…truncated…]\n\n";
+
+/// Accumulates streamed reasoning, keeping the first and last `max_chars / 2` characters.
+/// Memory stays bounded during the stream; a zero cap keeps everything.
+#[derive(Debug)]
+pub struct ReasoningBuffer {
+    head: String,
+    head_chars: usize,
+    tail: String,
+    tail_chars: usize,
+    max_chars: usize,
+    truncated: bool,
+}
+
+impl ReasoningBuffer {
+    pub fn new(max_chars: usize) -> Self {
+        Self {
+            head: String::new(),
+            head_chars: 0,
+            tail: String::new(),
+            tail_chars: 0,
+            max_chars,
+            truncated: false,
+        }
+    }
+
+    /// Cap from `max_reasoning_chars`
+    pub fn from_config(config: &crate::config::AppConfig) -> Self {
+        Self::new(config.max_reasoning_chars.unwrap_or(DEFAULT_MAX_REASONING_CHARS))
+    }
+
+    fn tail_cap(&self) -> usize {
+        self.max_chars - self.max_chars / 2
+    }
+
+    pub fn push_str(&mut self, text: &str) {
+        if self.max_chars == 0 {
+            self.head.push_str(text);
+            return;
+        }
+
+        let mut rest = text;
+        let head_room = self.max_chars / 2 - self.head_chars;
+        if head_room > 0 {
+            let split = rest.char_indices().nth(head_room).map_or(rest.len(), |(i, _)| i);
+            self.head.push_str(&rest[..split]);
+            self.head_chars += rest[..split].chars().count();
+            rest = &rest[split..];
+        }
+        if rest.is_empty() {
+            return;
+        }
+
+        self.tail.push_str(rest);
+        self.tail_chars += rest.chars().count();
+        // Trim in batches so long streams don't shift the tail on every chunk
+        if self.tail_chars > 2 * self.tail_cap() {
+            self.trim_tail();
+        }
+    }
+
+    fn trim_tail(&mut self) {
+        let excess = self.tail_chars.saturating_sub(self.tail_cap());
+        if excess == 0 {
+            return;
+        }
+        let split = self.tail.char_indices().nth(excess).map_or(self.tail.len(), |(i, _)| i);
+        self.tail.drain(..split);
+        self.tail_chars -= excess;
+        self.truncated = true;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.is_empty() && self.tail.is_empty()
+    }
+
+    /// Retained reasoning, with the marker where the middle was dropped
+    pub fn finish(mut self) -> String {
+        self.trim_tail();
+        if self.truncated {
+            format!("{}{}{}", self.head, REASONING_TRUNCATION_MARKER, self.tail)
+        } else {
+            self.head + &self.tail
+        }
+    }
+}
@@ -0,0 +1,189 @@
+// Chat sessions - each conversation persisted as chats/<id>.json under the app data dir.
+// The pre-sessions chat_history.json is migrated to the "default" session on first load.
+
+use super::types::ChatMessage;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Session the legacy single-file history is migrated into
+pub const DEFAULT_SESSION_ID: &str = "default";
+/// Pre-sessions history file
+pub const LEGACY_HISTORY_FILENAME: &str = "chat_history.json";
+const SESSIONS_DIRNAME: &str = "chats";
+/// Remembers the active session across restarts
+const ACTIVE_SESSION_FILENAME: &str = "active_session";
+/// Characters of the first user message used as a session's title
+const SESSION_TITLE_CHARS: usize = 60;
+/// Sidecar next to chats/<id>.json holding the session's pinned model
+const SESSION_MODEL_EXTENSION: &str = "model";
+
+/// Summary of a stored conversation for the session picker
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SessionInfo {
+    pub id: String,
+    pub title: String,
+    pub updated_at: DateTime<Utc>,
+    pub message_count: usize,
+}
+
+pub fn new_session_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+/// Path of a session's history file; ids are restricted so they can't escape `chats/`
+pub fn session_path(data_dir: &Path, id: &str) -> Result<PathBuf, String> {
+    let valid = !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(format!("Invalid session id: {}", id));
+    }
+    Ok(data_dir.join(SESSIONS_DIRNAME).join(format!("{}.json", id)))
+}
+
+/// Move chat_history.json into the default session. Returns true if a file was migrated.
+pub fn migrate_legacy_history(data_dir: &Path) -> Result<bool, String> {
+    let legacy = data_dir.join(LEGACY_HISTORY_FILENAME);
+    let target = session_path(data_dir, DEFAULT_SESSION_ID)?;
+    if !legacy.exists() || target.exists() {
+        return Ok(false);
+    }
+
+    fs::create_dir_all(data_dir.join(SESSIONS_DIRNAME))
+        .map_err(|e| format!("Failed to create sessions dir: {}", e))?;
+    fs::rename(&legacy, &target).map_err(|e| format!("Failed to migrate chat history: {}", e))?;
+    log::info!("Migrated {} to the default session", LEGACY_HISTORY_FILENAME);
+    Ok(true)
+}
+
+/// Read a session's messages; a session that was never saved is empty
+pub fn load_session(data_dir: &Path, id: &str) -> Result<Vec<ChatMessage>, String> {
+    let path = session_path(data_dir, id)?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let contents = fs::read_to_string(&path).map_err(|e| format!("Failed to read session {}: {}", id, e))?;
+    serde_json::from_str(&contents).map_err(|e| format!("Failed to parse session {}: {}", id, e))
+}
+
+/// Write already-serialized history (see `serialize_history`) for a session
+pub fn save_session(data_dir: &Path, id: &str, json: &str) -> Result<(), String> {
+    let path = session_path(data_dir, id)?;
+    fs::create_dir_all(data_dir.join(SESSIONS_DIRNAME))
+        .map_err(|e| format!("Failed to create sessions dir: {}", e))?;
+    crate::retrieval::write_atomic(&path, json).map_err(|e| format!("Failed to write session {}: {}", id, e))
+}
+
+/// Delete a session's file and pinned model. Returns false if the history didn't exist.
+pub fn delete_session_file(data_dir: &Path, id: &str) -> Result<bool, String> {
+    let path = session_path(data_dir, id)?;
+    write_session_model(data_dir, id, None)?;
+    if !path.exists() {
+        return Ok(false);
+    }
+    fs::remove_file(&path).map_err(|e| format!("Failed to delete session {}: {}", id, e))?;
+    Ok(true)
+}
+
+/// Model pinned to a session, if any
+pub fn read_session_model(data_dir: &Path, id: &str) -> Option<String> {
+    let path = session_path(data_dir, id).ok()?.with_extension(SESSION_MODEL_EXTENSION);
+    fs::read_to_string(path)
+        .ok()
+        .map(|model| model.trim().to_string())
+        .filter(|model| !model.is_empty())
+}
+
+/// Pin `model` to a session, or clear the pin with None
+pub fn write_session_model(data_dir: &Path, id: &str, model: Option<&str>) -> Result<(), String> {
+    let path = session_path(data_dir, id)?.with_extension(SESSION_MODEL_EXTENSION);
+    match model.map(str::trim).filter(|m| !m.is_empty()) {
+        Some(model) => {
+            fs::create_dir_all(data_dir.join(SESSIONS_DIRNAME))
+                .map_err(|e| format!("Failed to create sessions dir: {}", e))?;
+            fs::write(&path, model).map_err(|e| format!("Failed to pin model for session {}: {}", id, e))
+        }
+        None if path.exists() => {
+            fs::remove_file(&path).map_err(|e| format!("Failed to clear model for session {}: {}", id, e))
+        }
+        None => Ok(()),
+    }
+}
+
+pub fn session_exists(data_dir: &Path, id: &str) -> bool {
+    session_path(data_dir, id).is_ok_and(|path| path.exists())
+}
+
+/// All stored sessions, most recently updated first. Unreadable files are skipped.
+pub fn list_sessions(data_dir: &Path) -> Result<Vec<SessionInfo>, String> {
+    let dir = data_dir.join(SESSIONS_DIRNAME);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let entries = fs::read_dir(&dir).map_err(|e| format!("Failed to read sessions dir: {}", e))?;
+    let mut sessions: Vec<SessionInfo> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            if path.extension().and_then(|s| s.to_str()) != Some("json") {
+                return None;
+            }
+            let id = path.file_stem()?.to_str()?.to_string();
+            let messages = match load_session(data_dir, &id) {
+                Ok(messages) => messages,
+                Err(e) => {
+                    log::warn!("[Sessions] Skipping {}: {}", id, e);
+                    return None;
+                }
+            };
+            let updated_at = entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .map(DateTime::<Utc>::from)
+                .unwrap_or_else(|_| Utc::now());
+            Some(SessionInfo {
+                title: session_title(&messages),
+                id,
+                updated_at,
+                message_count: messages.len(),
+            })
+        })
+        .collect();
+
+    sessions.sort_by(|a, b| b.updated_at.cmp(&a.updated_at).then_with(|| a.id.cmp(&b.id)));
+    Ok(sessions)
+}
+
+/// Title from the first user message, or "New chat" for an empty session
+pub fn session_title(messages: &[ChatMessage]) -> String {
+    let first = messages
+        .iter()
+        .find(|m| m.role == "user")
+        .and_then(|m| m.content.as_deref())
+        .map(|c| c.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|c| !c.is_empty());
+
+    match first {
+        Some(text) if text.chars().count() > SESSION_TITLE_CHARS => {
+            let cut: String = text.chars().take(SESSION_TITLE_CHARS).collect();
+            format!("{}…", cut.trim_end())
+        }
+        Some(text) => text,
+        None => "New chat".to_string(),
+    }
+}
+
+/// The session to open on startup (default if none was recorded)
+pub fn read_active_session(data_dir: &Path) -> String {
+    fs::read_to_string(data_dir.join(ACTIVE_SESSION_FILENAME))
+        .ok()
+        .map(|id| id.trim().to_string())
+        .filter(|id| session_path(data_dir, id).is_ok())
+        .unwrap_or_else(|| DEFAULT_SESSION_ID.to_string())
+}
+
+pub fn write_active_session(data_dir: &Path, id: &str) -> Result<(), String> {
+    fs::write(data_dir.join(ACTIVE_SESSION_FILENAME), id)
+        .map_err(|e| format!("Failed to record active session: {}", e))
+}
@@ -0,0 +1,252 @@
+// Markdown transcripts - a readable export of the chat history that can be loaded
+// back in. Each message is a `## Role` section; assistant reasoning is a blockquote
+// and tool calls/results are fenced blocks. Images and citations are not carried.
+
+use super::types::*;
+
+const TRANSCRIPT_TITLE: &str = "# Chat Transcript";
+const REASONING_HEADER: &str = "> **Reasoning**";
+const TOOL_CALL_INFO: &str = "tool_call";
+const TOOL_RESULT_INFO: &str = "tool_result";
+
+/// `## Role` heading for a message role
+fn role_heading(role: &str) -> String {
+    let mut chars = role.chars();
+    match chars.next() {
+        Some(first) => format!("## {}{}", first.to_uppercase(), chars.as_str()),
+        None => "## Unknown".to_string(),
+    }
+}
+
+/// Role (and tool call id) from a `## Role` or `## Tool (id)` heading
+fn parse_role_heading(line: &str) -> Option<(String, Option<String>)> {
+    let heading = line.strip_prefix("## ")?.trim();
+    let (name, call_id) = match heading.split_once(" (") {
+        Some((name, rest)) => (name, rest.strip_suffix(')').map(|id| id.trim().to_string())),
+        None => (heading, None),
+    };
+    let role = name.trim().to_lowercase();
+    matches!(role.as_str(), "user" | "assistant" | "model" | "system" | "tool").then_some((role, call_id))
+}
+
+fn is_section_heading(line: &str) -> bool {
+    line.starts_with("# ") || line.starts_with("## ")
+}
+
+/// Whether a content line needs a `\` so it isn't read as a section heading or a
+/// tool fence. Lines already starting with backslashes are escaped too, keeping import symmetric.
+fn needs_escape(line: &str) -> bool {
+    let line = line.trim_start_matches('\\');
+    is_section_heading(line) || tool_fence_open(line).is_some()
+}
+
+fn escape_content(text: &str) -> String {
+    text.lines()
+        .map(|line| if needs_escape(line) { format!("\\{}", line) } else { line.to_string() })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn unescape_line(line: &str) -> &str {
+    match line.strip_prefix('\\') {
+        Some(rest) if needs_escape(rest) => rest,
+        _ => line,
+    }
+}
+
+/// A backtick fence longer than any run inside `body`
+fn fence_for(body: &str) -> String {
+    let mut longest = 0;
+    let mut run = 0;
+    for c in body.chars() {
+        run = if c == '`' { run + 1 } else { 0 };
+        longest = longest.max(run);
+    }
+    "`".repeat((longest + 1).max(3))
+}
+
+fn push_fenced(out: &mut String, info: &str, body: &str) {
+    let fence = fence_for(body);
+    out.push_str(&format!("{}{}\n{}\n{}\n\n", fence, info, body, fence));
+}
+
+/// Render history as a Markdown transcript
+pub fn history_to_markdown(history: &[ChatMessage]) -> String {
+    let mut out = format!("{}\n\n", TRANSCRIPT_TITLE);
+    for msg in history {
+        match (&msg.role[..], &msg.tool_call_id) {
+            ("tool", Some(id)) => out.push_str(&format!("{} ({})\n\n", role_heading("tool"), id)),
+            (role, _) => out.push_str(&format!("{}\n\n", role_heading(role))),
+        }
+
+        if let Some(reasoning) = msg.reasoning.as_ref().filter(|r| !r.trim().is_empty()) {
+            out.push_str(REASONING_HEADER);
+            out.push('\n');
+            for line in reasoning.trim_end().lines() {
+                if line.is_empty() {
+                    out.push_str(">\n");
+                } else {
+                    out.push_str(&format!("> {}\n", line));
+                }
+            }
+            out.push('\n');
+        }
+
+        if let Some(content) = msg.content.as_ref().filter(|c| !c.is_empty()) {
+            if msg.role == "tool" {
+                push_fenced(&mut out, TOOL_RESULT_INFO, content);
+            } else {
+                out.push_str(&escape_content(content));
+                out.push_str("\n\n");
+            }
+        }
+
+        for call in msg.tool_calls.iter().flatten() {
+            let info = format!("{} {} {}", TOOL_CALL_INFO, call.function.name, call.id);
+            push_fenced(&mut out, &info, &call.function.arguments);
+        }
+    }
+    out
+}
+
+/// Opening fence: its backtick count and info string
+fn parse_fence_open(line: &str) -> Option<(usize, &str)> {
+    let ticks = line.chars().take_while(|&c| c == '`').count();
+    (ticks >= 3).then_some((ticks, line[ticks..].trim()))
+}
+
+/// Opening fence of a tool call/result block: its backtick count
+fn tool_fence_open(line: &str) -> Option<usize> {
+    let (ticks, info) = parse_fence_open(line)?;
+    let kind = info.split_whitespace().next().unwrap_or_default();
+    (kind == TOOL_CALL_INFO || kind == TOOL_RESULT_INFO).then_some(ticks)
+}
+
+fn is_fence_close(line: &str, ticks: usize) -> bool {
+    let trimmed = line.trim_end();
+    trimmed.len() >= ticks && trimmed.chars().all(|c| c == '`')
+}
+
+/// Parse one section body (the lines under a role heading) into a message
+fn parse_section(role: String, call_id: Option<String>, lines: &[&str]) -> ChatMessage {
+    let mut idx = 0;
+    while idx < lines.len() && lines[idx].trim().is_empty() {
+        idx += 1;
+    }
+
+    let mut reasoning = Vec::new();
+    if lines.get(idx).map(|l| l.trim_end()) == Some(REASONING_HEADER) {
+        idx += 1;
+        while let Some(line) = lines.get(idx).and_then(|l| l.strip_prefix('>')) {
+            reasoning.push(line.strip_prefix(' ').unwrap_or(line));
+            idx += 1;
+        }
+    }
+
+    let mut content: Vec<&str> = Vec::new();
+    let mut tool_result: Option<String> = None;
+    let mut tool_calls = Vec::new();
+    while idx < lines.len() {
+        let line = lines[idx];
+        idx += 1;
+        let Some((ticks, info)) = parse_fence_open(line) else {
+            content.push(unescape_line(line));
+            continue;
+        };
+        let mut parts = info.split_whitespace();
+        let kind = parts.next().unwrap_or_default();
+        if kind != TOOL_CALL_INFO && kind != TOOL_RESULT_INFO {
+            // An ordinary code block inside the message text
+            content.push(line);
+            continue;
+        }
+
+        let start = idx;
+        while idx < lines.len() && !is_fence_close(lines[idx], ticks) {
+            idx += 1;
+        }
+        let body = lines[start..idx].join("\n");
+        idx += 1; // Closing fence (or end of section)
+
+        if kind == TOOL_RESULT_INFO {
+            tool_result = Some(body);
+        } else {
+            let name = parts.next().unwrap_or_default().to_string();
+            let id = parts.next().map(str::to_string).unwrap_or_else(|| format!("call_{}", tool_calls.len()));
+            tool_calls.push(ToolCall {
+                id,
+                tool_type: "function".to_string(),
+                function: FunctionCall { name, arguments: body },
+                thought_signature: None,
+            });
+        }
+    }
+
+    let text = content.join("\n").trim_matches('\n').to_string();
+    let content = tool_result.or(Some(text)).filter(|c| !c.is_empty());
+    let reasoning = reasoning.join("\n").trim_matches('\n').to_string();
+    ChatMessage {
+        role,
+        content,
+        reasoning: Some(reasoning).filter(|r| !r.is_empty()),
+        tool_calls: Some(tool_calls).filter(|calls| !calls.is_empty()),
+        tool_call_id: call_id,
+        images: None,
+        citations: None,
+    }
+}
+
+/// Parse a transcript produced by `history_to_markdown`. Text before the first role
+/// heading and sections with unrecognized headings are skipped.
+pub fn parse_chat_markdown(md: &str) -> Result<Vec<ChatMessage>, String> {
+    let mut messages = Vec::new();
+    let mut current: Option<(String, Option<String>)> = None;
+    let mut lines: Vec<&str> = Vec::new();
+    let mut fence: Option<usize> = None;
+
+    for line in md.lines() {
+        // Headings inside a tool block belong to the block. Code fences in message text
+        // are left alone: an unterminated one (e.g. a cancelled reply) must not swallow
+        // later sections, and headings inside them are escaped on export anyway.
+        if let Some(ticks) = fence {
+            if is_fence_close(line, ticks) {
+                fence = None;
+            }
+            lines.push(line);
+            continue;
+        }
+        if let Some(ticks) = tool_fence_open(line) {
+            fence = Some(ticks);
+            lines.push(line);
+            continue;
+        }
+
+        if let Some(heading) = parse_role_heading(line) {
+            if let Some((role, call_id)) = current.take() {
+                messages.push(parse_section(role, call_id, &lines));
+            }
+            current = Some(heading);
+            lines.clear();
+        } else if is_section_heading(line) {
+            if let Some((role, call_id)) = current.take() {
+                messages.push(parse_section(role, call_id, &lines));
+            }
+            if line.trim() != TRANSCRIPT_TITLE {
+                log::debug!("[Transcript] Skipping unrecognized section: {}", line.trim());
+            }
+            lines.clear();
+        } else if current.is_some() {
+            lines.push(line);
+        }
+    }
+    if let Some((role, call_id)) = current {
+        messages.push(parse_section(role, call_id, &lines));
+    }
+
+    // A heading with nothing under it isn't a message
+    messages.retain(|m| m.content.is_some() || m.reasoning.is_some() || m.tool_calls.is_some());
+    if messages.is_empty() {
+        return Err("No chat messages found in the transcript".to_string());
+    }
+    Ok(messages)
+}
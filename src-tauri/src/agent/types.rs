@@ -39,7 +39,7 @@ where
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ChatMessage {
     pub role: String,
     pub content: Option<String>,
@@ -57,9 +57,19 @@ pub struct ChatMessage {
         skip_serializing_if = "Option::is_none"
     )]
     pub images: Option<Vec<ImageAttachment>>,
+    /// Sources visited by tools while producing this (assistant) message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub citations: Option<Vec<Citation>>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// A source surfaced by a research tool, shown alongside the answer instead of inline
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Citation {
+    pub title: String,
+    pub url: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ImageAttachment {
     pub base64: String,
     pub mime_type: String,
@@ -81,7 +91,7 @@ pub struct ApiChatMessage {
 // Tool Call Types
 // ============================================================================
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ToolCall {
     pub id: String,
     #[serde(rename = "type")]
@@ -92,7 +102,7 @@ pub struct ToolCall {
     pub thought_signature: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct FunctionCall {
     pub name: String,
     pub arguments: String,
@@ -128,26 +138,33 @@ pub enum RetryReason {
     MalformedLatex { errors: Vec<String> },
 }
 
+/// Built-in hint for a response with reasoning but no visible answer
+pub const DEFAULT_EMPTY_RESPONSE_HINT: &str = "[RETRY HINT] Your previous response contained only internal reasoning \
+    but no user-facing content. Please provide a complete, visible answer to the user's question.";
+
+/// Built-in hint for KaTeX errors; `{errors}` becomes the error list
+pub const DEFAULT_MALFORMED_LATEX_HINT: &str = "[RETRY HINT] Your previous response had LaTeX rendering errors:\n{errors}\n\n\
+    Please rewrite using correct LaTeX syntax:\n\
+    - Use single $ for inline math: $x^2$\n\
+    - Use $$ on separate lines for display math (not inline)\n\
+    - Ensure all delimiters are properly paired\n\
+    - Avoid nested $ symbols like $a$b$ - use $a$ $b$ instead";
+
 impl RetryReason {
-    /// Get the retry hint to inject as a system message
-    pub fn get_hint(&self) -> String {
+    /// Get the retry hint to inject as a system message, preferring the configured
+    /// `retry_hints` text over the built-in default
+    pub fn get_hint(&self, hints: Option<&crate::config::RetryHints>) -> String {
+        let custom = match self {
+            RetryReason::EmptyResponse => hints.and_then(|h| h.empty_response.as_deref()),
+            RetryReason::MalformedLatex { .. } => hints.and_then(|h| h.malformed_latex.as_deref()),
+        }
+        .filter(|hint| !hint.trim().is_empty());
+
         match self {
-            RetryReason::EmptyResponse => {
-                "[RETRY HINT] Your previous response contained only internal reasoning \
-                but no user-facing content. Please provide a complete, visible answer to the user's question."
-                    .to_string()
-            }
-            RetryReason::MalformedLatex { errors } => {
-                format!(
-                    "[RETRY HINT] Your previous response had LaTeX rendering errors:\n{}\n\n\
-                    Please rewrite using correct LaTeX syntax:\n\
-                    - Use single $ for inline math: $x^2$\n\
-                    - Use $$ on separate lines for display math (not inline)\n\
-                    - Ensure all delimiters are properly paired\n\
-                    - Avoid nested $ symbols like $a$b$ - use $a$ $b$ instead",
-                    errors.join("\n")
-                )
-            }
+            RetryReason::EmptyResponse => custom.unwrap_or(DEFAULT_EMPTY_RESPONSE_HINT).to_string(),
+            RetryReason::MalformedLatex { errors } => custom
+                .unwrap_or(DEFAULT_MALFORMED_LATEX_HINT)
+                .replace("{errors}", &errors.join("\n")),
         }
     }
 }
@@ -170,6 +187,10 @@ pub struct ChatCompletionRequest {
     pub reasoning: Option<ReasoningConfig>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub include_reasoning: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
     pub stream: bool,
 }
 
@@ -199,6 +220,10 @@ pub struct GenerateContentRequest {
 pub struct GenerationConfig {
     #[serde(skip_serializing_if = "Option::is_none", rename = "thinkingConfig")]
     pub thinking_config: Option<ThinkingConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none", rename = "topP")]
+    pub top_p: Option<f32>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -209,10 +234,11 @@ pub struct ThinkingConfig {
     pub thinking_budget: Option<i32>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct GeminiContent {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub role: Option<String>,
+    #[serde(default)]
     pub parts: Vec<GeminiPart>,
 }
 
@@ -224,6 +250,10 @@ pub enum GeminiPart {
         #[serde(rename = "fileData")]
         file_data: GeminiFileData,
     },
+    InlineData {
+        #[serde(rename = "inlineData")]
+        inline_data: GeminiInlineData,
+    },
     FunctionCall {
         #[serde(rename = "functionCall")]
         function_call: GeminiFunctionCall,
@@ -245,6 +275,14 @@ pub struct GeminiFileData {
     pub file_uri: String,
 }
 
+/// Base64 image sent directly in the request (no Files API upload)
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GeminiInlineData {
+    #[serde(rename = "mimeType")]
+    pub mime_type: String,
+    pub data: String,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GeminiTool {
     #[serde(rename = "functionDeclarations")]
@@ -271,11 +309,78 @@ pub struct GeminiFunctionResponse {
 }
 
 #[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
 pub struct GenerateContentResponse {
     pub candidates: Option<Vec<GeminiCandidate>>,
+    /// Present when the prompt itself was blocked (no candidates are returned)
+    #[serde(default)]
+    pub prompt_feedback: Option<GeminiPromptFeedback>,
 }
 
 #[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
 pub struct GeminiCandidate {
+    /// Missing on blocked candidates
+    #[serde(default)]
     pub content: GeminiContent,
+    /// Only set on the final chunk: STOP, MAX_TOKENS, SAFETY, RECITATION, ...
+    #[serde(default)]
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiPromptFeedback {
+    #[serde(default)]
+    pub block_reason: Option<String>,
+}
+
+// ============================================================================
+// Anthropic API Types
+// ============================================================================
+
+#[derive(Serialize, Debug)]
+pub struct AnthropicRequest {
+    pub model: String,
+    pub max_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+    pub messages: Vec<AnthropicMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<AnthropicTool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    pub stream: bool,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct AnthropicMessage {
+    pub role: String, // "user" or "assistant"
+    pub content: Vec<AnthropicContent>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnthropicContent {
+    Text { text: String },
+    Image { source: AnthropicImageSource },
+    ToolUse { id: String, name: String, input: Value },
+    ToolResult { tool_use_id: String, content: String },
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct AnthropicImageSource {
+    #[serde(rename = "type")]
+    pub source_type: String, // "base64"
+    pub media_type: String,
+    pub data: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct AnthropicTool {
+    pub name: String,
+    pub description: String,
+    pub input_schema: Value,
 }
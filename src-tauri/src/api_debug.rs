@@ -0,0 +1,168 @@
+/**
+ * API Debug Log
+ *
+ * Opt-in (`debug_log_requests`) record of provider request bodies and raw responses,
+ * kept as an artifact for provider-specific bug reports.
+ * - Redaction: API keys in URLs (`?key=`) and Authorization headers never reach disk
+ * - Rotation: once `api_debug.log` passes MAX_LOG_BYTES it moves to `api_debug.log.1`
+ * - Incognito: nothing is logged, since bodies carry prompts, RAG context and memories
+ */
+use regex::Regex;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::LazyLock;
+
+pub const DEBUG_LOG_FILENAME: &str = "api_debug.log";
+/// Size at which the log is rotated; one previous file is kept
+pub const MAX_LOG_BYTES: u64 = 5 * 1024 * 1024;
+
+/// Whether request/response logging is turned on (never in incognito mode)
+pub fn is_enabled(config: &crate::config::AppConfig) -> bool {
+    config.debug_log_requests.unwrap_or(false) && !config.incognito_mode.unwrap_or(false)
+}
+
+static QUERY_KEY_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)([?&](?:key|api_key|apikey|access_token|token)=)[^&\s'\x22]+").unwrap());
+static AUTH_HEADER_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)((?:authorization|x-goog-api-key|x-api-key)['\x22]?\s*[:=]\s*['\x22]?(?:bearer\s+)?)[^\s'\x22,}]+")
+        .unwrap()
+});
+static BEARER_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)(bearer\s+)[A-Za-z0-9._~+/=\-]+").unwrap());
+
+/// Replace API keys in query strings, auth headers and bearer tokens with `[REDACTED]`
+pub fn redact_secrets(text: &str) -> String {
+    let text = QUERY_KEY_RE.replace_all(text, "${1}[REDACTED]");
+    let text = AUTH_HEADER_RE.replace_all(&text, "${1}[REDACTED]");
+    BEARER_RE.replace_all(&text, "${1}[REDACTED]").into_owned()
+}
+
+/// Append a redacted entry to the debug log in `dir`, rotating first if it would pass `max_bytes`
+pub fn append_in_dir(dir: &Path, entry: &str, max_bytes: u64) -> Result<(), String> {
+    let path = dir.join(DEBUG_LOG_FILENAME);
+    let entry = redact_secrets(entry);
+
+    let current = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    if current > 0 && current + entry.len() as u64 > max_bytes {
+        fs::rename(&path, dir.join(format!("{}.1", DEBUG_LOG_FILENAME)))
+            .map_err(|e| format!("Failed to rotate debug log: {}", e))?;
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| format!("Failed to open debug log: {}", e))?;
+    file.write_all(entry.as_bytes())
+        .map_err(|e| format!("Failed to write debug log: {}", e))
+}
+
+fn append_entry(dir: &Path, entry: String) {
+    if let Err(e) = append_in_dir(dir, &entry, MAX_LOG_BYTES) {
+        log::warn!("[ApiDebug] {}", e);
+    }
+}
+
+/// Record an outbound provider request (no-op unless enabled)
+pub fn log_request<T: serde::Serialize>(
+    config: &crate::config::AppConfig,
+    dir: &Path,
+    provider: &str,
+    url: &str,
+    auth_header: Option<&str>,
+    body: &T,
+) {
+    if !is_enabled(config) {
+        return;
+    }
+    let body = serde_json::to_string_pretty(body).unwrap_or_default();
+    let auth = auth_header
+        .map(|auth| format!("Authorization: {}\n", auth))
+        .unwrap_or_default();
+    append_entry(
+        dir,
+        format!(
+            "=== {} {} request ===\nPOST {}\n{}\n{}\n\n",
+            chrono::Utc::now().to_rfc3339(),
+            provider,
+            url,
+            auth,
+            body
+        ),
+    );
+}
+
+/// Record a raw provider response body (no-op unless enabled)
+pub fn log_response(config: &crate::config::AppConfig, dir: &Path, provider: &str, status: u16, body: &str) {
+    if !is_enabled(config) {
+        return;
+    }
+    append_entry(
+        dir,
+        format!(
+            "=== {} {} response ({}) ===\n{}\n\n",
+            chrono::Utc::now().to_rfc3339(),
+            provider,
+            status,
+            body
+        ),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_secrets() {
+        let url = "https://generativelanguage.googleapis.com/v1beta/models/gemini:streamGenerateContent?alt=sse&key=AIzaSyExample123";
+        let redacted = redact_secrets(url);
+        assert!(!redacted.contains("AIzaSyExample123"));
+        assert!(redacted.ends_with("?alt=sse&key=[REDACTED]"));
+
+        let redacted = redact_secrets("embedContent?key=abc-123&other=1");
+        assert_eq!(redacted, "embedContent?key=[REDACTED]&other=1");
+
+        let header = "Authorization: Bearer sk-or-v1-secret\n{\"model\":\"x\"}";
+        let redacted = redact_secrets(header);
+        assert!(!redacted.contains("sk-or-v1-secret"));
+        assert!(redacted.starts_with("Authorization: Bearer [REDACTED]\n"));
+        assert!(redacted.contains("{\"model\":\"x\"}"));
+
+        let json = r#"{"headers":{"authorization":"Bearer gsk_abc","x-goog-api-key":"AIzaXYZ"}}"#;
+        let redacted = redact_secrets(json);
+        assert!(!redacted.contains("gsk_abc"));
+        assert!(!redacted.contains("AIzaXYZ"));
+
+        // Ordinary text mentioning keys is left alone
+        let plain = "The key insight is that monkeys like bananas.";
+        assert_eq!(redact_secrets(plain), plain);
+    }
+
+    #[test]
+    fn test_disabled_in_incognito() {
+        let config = crate::config::AppConfig {
+            debug_log_requests: Some(true),
+            ..Default::default()
+        };
+        assert!(is_enabled(&config));
+        let incognito = crate::config::AppConfig { incognito_mode: Some(true), ..config };
+        assert!(!is_enabled(&incognito));
+
+        let dir = tempfile::TempDir::new().unwrap();
+        log_response(&incognito, dir.path(), "Gemini", 200, "my private prompt");
+        assert!(!dir.path().join(DEBUG_LOG_FILENAME).exists());
+    }
+
+    #[test]
+    fn test_append_rotates_when_over_cap() {
+        let dir = tempfile::TempDir::new().unwrap();
+        append_in_dir(dir.path(), "first entry ?key=secret1\n", 40).unwrap();
+        append_in_dir(dir.path(), "second entry\n", 40).unwrap();
+
+        let current = fs::read_to_string(dir.path().join(DEBUG_LOG_FILENAME)).unwrap();
+        let rotated = fs::read_to_string(dir.path().join("api_debug.log.1")).unwrap();
+        assert_eq!(current, "second entry\n");
+        assert_eq!(rotated, "first entry ?key=[REDACTED]\n");
+    }
+}
@@ -6,14 +6,17 @@
  * - Cleanup: LLM-filter generic/redundant entries from interaction logs
  *
  * Both jobs run sequentially every 6 hours (Summary first, then Cleanup).
+ * A run is deferred while the user is active or inside configured quiet hours,
+ * and `cancel_background_jobs` stops a running one at its next checkpoint.
  */
-use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use chrono::{DateTime, Duration as ChronoDuration, Local, Timelike, Utc};
 use serde::{Deserialize, Serialize};
 use std::fs::{self, OpenOptions};
 use std::io::{BufRead, BufReader, Write};
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
 use tauri::{AppHandle, Manager, Runtime};
-use tokio::time::{self, Duration};
+use tokio::time::{self, Duration, MissedTickBehavior};
 
 /// Configuration for background jobs
 pub const JOB_INTERVAL_HOURS: u64 = 6;
@@ -23,6 +26,152 @@ pub const LOG_RETENTION_DAYS: i64 = 30; // Fallback for date-based cleanup
 pub const DEFAULT_BACKGROUND_MODEL: &str = "gpt-oss-120b (Groq)";
 /// Skip job execution if less than this fraction of the interval has passed
 const SKIP_INTERVAL_FRACTION: f64 = 0.5;
+/// Defer jobs if the agent handled a message within this many minutes
+pub const ACTIVITY_DEFER_MINUTES: i64 = 10;
+/// How long to wait before re-checking a deferred run
+const DEFER_RECHECK_MINUTES: u64 = 15;
+
+// ============================================================================
+// Activity Tracking / Deferral
+// ============================================================================
+
+/// Unix millis of the last processed chat message (0 = none yet)
+static LAST_ACTIVITY_MS: AtomicI64 = AtomicI64::new(0);
+
+/// Record that the user is active (called when the agent processes a message)
+pub fn record_activity() {
+    LAST_ACTIVITY_MS.store(Utc::now().timestamp_millis(), Ordering::Relaxed);
+}
+
+fn last_activity() -> Option<DateTime<Utc>> {
+    match LAST_ACTIVITY_MS.load(Ordering::Relaxed) {
+        0 => None,
+        ms => DateTime::from_timestamp_millis(ms),
+    }
+}
+
+/// Why a scheduled run is being postponed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeferReason {
+    RecentActivity,
+    QuietHours,
+}
+
+/// Whether `hour` (0-23, local) falls in [start, end); wraps past midnight when start > end
+pub fn in_quiet_hours(hour: u32, start: u32, end: u32) -> bool {
+    if start == end {
+        return false;
+    }
+    if start < end {
+        hour >= start && hour < end
+    } else {
+        hour >= start || hour < end
+    }
+}
+
+/// Decide whether to postpone a scheduled run
+pub fn defer_reason(
+    now: DateTime<Utc>,
+    last_activity: Option<DateTime<Utc>>,
+    local_hour: u32,
+    quiet_hours: Option<(u32, u32)>,
+) -> Option<DeferReason> {
+    if let Some(last) = last_activity {
+        if now.signed_duration_since(last) < ChronoDuration::minutes(ACTIVITY_DEFER_MINUTES) {
+            return Some(DeferReason::RecentActivity);
+        }
+    }
+    match quiet_hours {
+        Some((start, end)) if in_quiet_hours(local_hour, start % 24, end % 24) => {
+            Some(DeferReason::QuietHours)
+        }
+        _ => None,
+    }
+}
+
+/// Quiet-hours window from config; both ends must be set
+fn quiet_hours_from_config<R: Runtime>(app_handle: &AppHandle<R>) -> Option<(u32, u32)> {
+    let config = crate::config::load_config(app_handle).ok()?;
+    Some((config.quiet_hours_start?, config.quiet_hours_end?))
+}
+
+/// Wait until neither recent activity nor quiet hours block a run
+async fn wait_until_idle<R: Runtime>(app_handle: &AppHandle<R>) {
+    loop {
+        let reason = defer_reason(
+            Utc::now(),
+            last_activity(),
+            Local::now().hour(),
+            quiet_hours_from_config(app_handle),
+        );
+        let Some(reason) = reason else {
+            return;
+        };
+        log::info!(
+            "[Background] Deferring scheduled jobs ({:?}); re-checking in {} minutes",
+            reason,
+            DEFER_RECHECK_MINUTES
+        );
+        time::sleep(Duration::from_secs(DEFER_RECHECK_MINUTES * 60)).await;
+    }
+}
+
+// ============================================================================
+// Cancellation
+// ============================================================================
+
+/// Error returned by a job stopped with `cancel_background_jobs`
+pub const JOB_CANCELLED: &str = "Background job cancelled";
+
+/// Bumped by `cancel_background_jobs`; jobs started before the bump stop at their next checkpoint
+static CANCEL_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Stop running background jobs before their next LLM or embedding call.
+/// Jobs started afterwards run normally.
+pub fn cancel_background_jobs() {
+    CancelCheckpoint::cancel(&CANCEL_GENERATION);
+    log::info!("[Background] Cancellation requested");
+}
+
+/// Cooperative cancellation for one job run. Jobs call `check` between remote calls and
+/// only at points where stopping leaves logs and indexes consistent.
+#[derive(Debug, Clone, Copy)]
+pub struct CancelCheckpoint {
+    counter: &'static AtomicU64,
+    generation: u64,
+}
+
+impl CancelCheckpoint {
+    /// Checkpoint for a run starting now against `counter`
+    pub fn new(counter: &'static AtomicU64) -> Self {
+        Self {
+            counter,
+            generation: counter.load(Ordering::SeqCst),
+        }
+    }
+
+    fn for_background_jobs() -> Self {
+        Self::new(&CANCEL_GENERATION)
+    }
+
+    /// Cancel every run started against `counter` so far
+    pub fn cancel(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.counter.load(Ordering::SeqCst) != self.generation
+    }
+
+    /// `Err(JOB_CANCELLED)` once cancelled; `stage` is logged as where the job stopped
+    pub fn check(&self, stage: &str) -> Result<(), String> {
+        if self.is_cancelled() {
+            log::info!("[Background] Job cancelled {}", stage);
+            return Err(JOB_CANCELLED.to_string());
+        }
+        Ok(())
+    }
+}
 
 // ============================================================================
 // Last Run Persistence
@@ -158,7 +307,7 @@ pub struct CleanupDecision {
 
 /// Make an LLM call for background processing
 /// Routes to Groq or Cerebras based on the model name
-async fn call_background_llm(
+pub(crate) async fn call_background_llm(
     http_client: &reqwest::Client,
     config: &crate::config::AppConfig,
     model: &str,
@@ -295,11 +444,15 @@ pub fn parse_cleanup_decision(llm_response: &str) -> Result<CleanupDecision, Str
 pub fn start_background_jobs<R: Runtime>(app_handle: AppHandle<R>) {
     tauri::async_runtime::spawn(async move {
         let mut job_interval = time::interval(Duration::from_secs(JOB_INTERVAL_HOURS * 3600));
+        // A long deferral shouldn't cause a burst of catch-up ticks
+        job_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
         loop {
             job_interval.tick().await;
+            wait_until_idle(&app_handle).await;
 
-            log::info!("[Background] Starting scheduled jobs (Summary → Cleanup)...");
+            log::info!("[Background] Starting scheduled jobs (Summary → Cleanup → Decay)...");
+            let cancel = CancelCheckpoint::for_background_jobs();
 
             // Load last run info to check if we should skip
             let mut last_run_info = load_last_run_info(&app_handle);
@@ -313,7 +466,7 @@ pub fn start_background_jobs<R: Runtime>(app_handle: AppHandle<R>) {
                 );
             } else {
                 log::info!("[Background] Running summary job...");
-                match run_summary_job(&app_handle).await {
+                match run_summary_job(&app_handle, &cancel).await {
                     Ok(result) => {
                         log::info!(
                             "[Summary] Complete. {} interactions analyzed, {} topics updated.",
@@ -324,6 +477,9 @@ pub fn start_background_jobs<R: Runtime>(app_handle: AppHandle<R>) {
                         last_run_info.summary_last_run = Some(now.clone());
                         save_last_run_info(&app_handle, &last_run_info);
                     }
+                    Err(e) if e == JOB_CANCELLED => {
+                        log::info!("[Background] Summary job stopped by cancellation");
+                    }
                     Err(e) => {
                         log::error!("[Background] Summary job failed: {}", e);
                     }
@@ -331,14 +487,17 @@ pub fn start_background_jobs<R: Runtime>(app_handle: AppHandle<R>) {
             }
 
             // Cleanup job with skip check
-            if should_skip_job(last_run_info.cleanup_last_run.as_deref()) {
+            if cancel.is_cancelled() {
+                log::info!("[Background] Skipping cleanup and decay - jobs were cancelled");
+                continue;
+            } else if should_skip_job(last_run_info.cleanup_last_run.as_deref()) {
                 log::info!(
                     "[Background] Skipping cleanup job - less than {} hours since last run",
                     (JOB_INTERVAL_HOURS as f64 * SKIP_INTERVAL_FRACTION) as u64
                 );
             } else {
                 log::info!("[Background] Running cleanup job...");
-                match run_cleanup_job(&app_handle).await {
+                match run_cleanup_job(&app_handle, &cancel).await {
                     Ok(result) => {
                         log::info!(
                             "[Cleanup] Complete. Removed {} entries, freed {} bytes.",
@@ -349,12 +508,25 @@ pub fn start_background_jobs<R: Runtime>(app_handle: AppHandle<R>) {
                         last_run_info.cleanup_last_run = Some(Utc::now().to_rfc3339());
                         save_last_run_info(&app_handle, &last_run_info);
                     }
+                    Err(e) if e == JOB_CANCELLED => {
+                        log::info!("[Background] Cleanup job stopped by cancellation");
+                    }
                     Err(e) => {
                         log::error!("[Background] Cleanup job failed: {}", e);
                     }
                 }
             }
 
+            // Importance decay is cheap and idempotent, so it runs on every tick
+            match crate::memories::decay_memories(&app_handle) {
+                Ok(result) => log::info!(
+                    "[Memories] Decay sweep: {} decayed, {} removed.",
+                    result.decayed,
+                    result.removed
+                ),
+                Err(e) => log::error!("[Background] Memory decay failed: {}", e),
+            }
+
             log::info!(
                 "[Background] All jobs complete. Next run in {} hours.",
                 JOB_INTERVAL_HOURS
@@ -367,8 +539,12 @@ pub fn start_background_jobs<R: Runtime>(app_handle: AppHandle<R>) {
 // Summary Job
 // ============================================================================
 
-/// Analyze recent interactions and update topic summaries using LLM
-async fn run_summary_job<R: Runtime>(app_handle: &AppHandle<R>) -> Result<SummaryResult, String> {
+/// Analyze recent interactions and update topic summaries using LLM.
+/// Checks `cancel` before the LLM call and before each topic/insight embedding update.
+async fn run_summary_job<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    cancel: &CancelCheckpoint,
+) -> Result<SummaryResult, String> {
     let app_data_dir = app_handle
         .path()
         .app_data_dir()
@@ -475,6 +651,7 @@ Return at most 5 topics and 5 insights. Ignore generic greetings/one-off queries
         LOOKBACK_HOURS, existing_topics, existing_insights, candidates_context, interactions
     );
 
+    cancel.check("before topic extraction")?;
     let http_client = reqwest::Client::new();
     let llm_response = call_background_llm(&http_client, &config, background_model, &prompt).await;
 
@@ -488,11 +665,12 @@ Return at most 5 topics and 5 insights. Ignore generic greetings/one-off queries
             // Try new combined format first
             match parse_extraction_response(&response) {
                 Ok(extraction) => {
-                    let gemini_api_key = config.gemini_api_key.as_ref();
+                    let embedding_api_key = crate::interactions::embedding_api_key(&config);
 
                     // Process topics
                     for update in extraction.topics {
-                        if let Some(api_key) = gemini_api_key {
+                        cancel.check("between topic updates")?;
+                        if let Some(api_key) = embedding_api_key {
                             match crate::memories::update_topic_summary(
                                 app_handle,
                                 &http_client,
@@ -519,7 +697,8 @@ Return at most 5 topics and 5 insights. Ignore generic greetings/one-off queries
 
                     // Process insights
                     for insight in extraction.insights {
-                        if let Some(api_key) = gemini_api_key {
+                        cancel.check("between insight updates")?;
+                        if let Some(api_key) = embedding_api_key {
                             match crate::memories::update_insight(
                                 app_handle,
                                 &http_client,
@@ -544,7 +723,9 @@ Return at most 5 topics and 5 insights. Ignore generic greetings/one-off queries
                         }
                     }
 
-                    // Process promotions (delete old insights)
+                    // Process promotions (delete old insights); a cancelled run must not
+                    // delete insights whose topic update may have been skipped
+                    cancel.check("before insight promotions")?;
                     for promotion in extraction.promotions {
                         match crate::memories::delete_insight(app_handle, &promotion.insight_title) {
                             Ok(true) => {
@@ -567,9 +748,10 @@ Return at most 5 topics and 5 insights. Ignore generic greetings/one-off queries
                         e
                     );
                     if let Ok(updates) = parse_topic_updates(&response) {
-                        let gemini_api_key = config.gemini_api_key.as_ref();
+                        let embedding_api_key = crate::interactions::embedding_api_key(&config);
                         for update in updates {
-                            if let Some(api_key) = gemini_api_key {
+                            cancel.check("between topic updates")?;
+                            if let Some(api_key) = embedding_api_key {
                                 if let Ok(_) = crate::memories::update_topic_summary(
                                     app_handle,
                                     &http_client,
@@ -613,8 +795,13 @@ Return at most 5 topics and 5 insights. Ignore generic greetings/one-off queries
 // Cleanup Job
 // ============================================================================
 
-/// Clean up redundant interaction entries using LLM judgment
-async fn run_cleanup_job<R: Runtime>(app_handle: &AppHandle<R>) -> Result<CleanupResult, String> {
+/// Clean up redundant interaction entries using LLM judgment.
+/// Checks `cancel` before the LLM call and before removing entries, never between
+/// removing log entries and updating the BM25 index.
+async fn run_cleanup_job<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    cancel: &CancelCheckpoint,
+) -> Result<CleanupResult, String> {
     let app_data_dir = app_handle
         .path()
         .app_data_dir()
@@ -622,6 +809,11 @@ async fn run_cleanup_job<R: Runtime>(app_handle: &AppHandle<R>) -> Result<Cleanu
 
     let interactions_dir = app_data_dir.join("interactions");
 
+    // Near-duplicates only need the stored embeddings, so collapse them before anything else
+    if let Err(e) = crate::interactions::merge_duplicate_interactions_in_dir(&interactions_dir) {
+        log::warn!("[Cleanup] Duplicate merge failed: {}", e);
+    }
+
     let config = crate::config::load_config(app_handle)?;
     let background_model = config.background_model.as_deref()
         .unwrap_or(DEFAULT_BACKGROUND_MODEL);
@@ -674,8 +866,10 @@ Interaction Entries:
         topics_context, interactions
     );
 
+    cancel.check("before cleanup review")?;
     let http_client = reqwest::Client::new();
     let llm_response = call_background_llm(&http_client, &config, background_model, &prompt).await;
+    cancel.check("before removing entries")?;
 
     match llm_response {
         Ok(response) => {
@@ -688,7 +882,7 @@ Interaction Entries:
                         if let Err(e) = crate::retrieval::prune_bm25_index(
                             app_handle,
                             LOG_RETENTION_DAYS,
-                            10000,
+                            crate::retrieval::BM25_MAX_DOCS,
                         ) {
                             log::warn!("[Cleanup] BM25 prune failed: {}", e);
                         }
@@ -700,12 +894,19 @@ Interaction Entries:
                     }
 
                     // Remove entries by timestamp
-                    let (deleted, bytes) =
+                    let (deleted, bytes, removed_doc_ids) =
                         remove_entries_by_timestamp(&interactions_dir, &decision.to_remove)?;
 
+                    // Drop the removed entries from BM25 so they stop matching queries
+                    if let Err(e) =
+                        crate::retrieval::remove_bm25_documents(app_handle, &removed_doc_ids)
+                    {
+                        log::warn!("[Cleanup] BM25 document removal failed: {}", e);
+                    }
+
                     // Also prune BM25 index
                     if let Err(e) =
-                        crate::retrieval::prune_bm25_index(app_handle, LOG_RETENTION_DAYS, 10000)
+                        crate::retrieval::prune_bm25_index(app_handle, LOG_RETENTION_DAYS, crate::retrieval::BM25_MAX_DOCS)
                     {
                         log::warn!("[Cleanup] BM25 prune failed: {}", e);
                     }
@@ -725,7 +926,7 @@ Interaction Entries:
                         cleanup_interactions_in_dir(&interactions_dir, LOG_RETENTION_DAYS)?;
                     // Also prune BM25 index
                     if let Err(e) =
-                        crate::retrieval::prune_bm25_index(app_handle, LOG_RETENTION_DAYS, 10000)
+                        crate::retrieval::prune_bm25_index(app_handle, LOG_RETENTION_DAYS, crate::retrieval::BM25_MAX_DOCS)
                     {
                         log::warn!("[Cleanup] BM25 prune failed: {}", e);
                     }
@@ -741,7 +942,7 @@ Interaction Entries:
             let result = cleanup_interactions_in_dir(&interactions_dir, LOG_RETENTION_DAYS)?;
             // Also prune BM25 index
             if let Err(e) =
-                crate::retrieval::prune_bm25_index(app_handle, LOG_RETENTION_DAYS, 10000)
+                crate::retrieval::prune_bm25_index(app_handle, LOG_RETENTION_DAYS, crate::retrieval::BM25_MAX_DOCS)
             {
                 log::warn!("[Cleanup] BM25 prune failed: {}", e);
             }
@@ -758,7 +959,7 @@ Interaction Entries:
 /// Also updates the last run timestamp to prevent redundant scheduled runs
 pub async fn force_summary<R: Runtime>(app_handle: &AppHandle<R>) -> Result<SummaryResult, String> {
     log::info!("[Background] Force-triggered summary job");
-    let result = run_summary_job(app_handle).await?;
+    let result = run_summary_job(app_handle, &CancelCheckpoint::for_background_jobs()).await?;
 
     // Update last run time on success
     let mut last_run_info = load_last_run_info(app_handle);
@@ -781,7 +982,7 @@ pub async fn run_summary_job_from_agent<R: Runtime>(
 /// Also updates the last run timestamp to prevent redundant scheduled runs
 pub async fn force_cleanup<R: Runtime>(app_handle: &AppHandle<R>) -> Result<CleanupResult, String> {
     log::info!("[Background] Force-triggered cleanup job");
-    let result = run_cleanup_job(app_handle).await?;
+    let result = run_cleanup_job(app_handle, &CancelCheckpoint::for_background_jobs()).await?;
 
     // Update last run time on success
     let mut last_run_info = load_last_run_info(app_handle);
@@ -841,19 +1042,30 @@ fn gather_recent_interactions(
             continue;
         }
 
-        // Check if file date is within lookback window
-        if let Some(filename) = path.file_stem().and_then(|s| s.to_str()) {
-            if let Some(date_str) = filename.strip_prefix("interactions-") {
-                if date_str < cutoff_str.as_str() && date_str != today_str {
-                    continue;
-                }
+        // Check if file date is within lookback window (daily logs or monthly archives)
+        let period = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(crate::interactions::parse_log_period);
+        if let Some(period) = &period {
+            if period.ends_before(&cutoff_str) && !period.reaches(&today_str) {
+                continue;
             }
         }
+        // An archive spans a whole month, so filter its entries individually
+        let is_archive = matches!(period, Some(crate::interactions::LogPeriod::Month(_)));
 
         if let Ok(file) = fs::File::open(&path) {
             let reader = BufReader::new(file);
             for line in reader.lines().flatten() {
                 if let Ok(entry) = serde_json::from_str::<serde_json::Value>(&line) {
+                    let ts = entry.get("ts").and_then(|v| v.as_str()).unwrap_or("");
+                    if is_archive
+                        && DateTime::parse_from_rfc3339(ts).is_ok_and(|t| t.with_timezone(&Utc) < cutoff)
+                    {
+                        continue;
+                    }
+
                     stats.total_interactions += 1;
 
                     let role = entry
@@ -861,7 +1073,6 @@ fn gather_recent_interactions(
                         .and_then(|v| v.as_str())
                         .unwrap_or("unknown");
                     let content = entry.get("content").and_then(|v| v.as_str()).unwrap_or("");
-                    let ts = entry.get("ts").and_then(|v| v.as_str()).unwrap_or("");
 
                     match role {
                         "user" => stats.user_messages += 1,
@@ -970,17 +1181,21 @@ fn load_insight_summaries_context<R: Runtime>(app_handle: &AppHandle<R>) -> Stri
 }
 
 /// Remove specific entries by timestamp from JSONL files
-fn remove_entries_by_timestamp(
+/// Returns (deleted_count, bytes_freed, removed BM25 doc_ids as RFC3339 timestamps)
+pub fn remove_entries_by_timestamp(
     interactions_dir: &std::path::Path,
     timestamps: &[String],
-) -> Result<(usize, u64), String> {
+) -> Result<(usize, u64, Vec<String>), String> {
     if !interactions_dir.exists() || timestamps.is_empty() {
-        return Ok((0, 0));
+        return Ok((0, 0, Vec::new()));
     }
 
     let mut deleted_count = 0;
     let mut bytes_freed = 0u64;
+    let mut removed_doc_ids = Vec::new();
 
+    // An append landing between the read and the rewrite would otherwise be lost
+    let _log_guard = crate::interactions::lock_interaction_logs();
     let entries = fs::read_dir(interactions_dir)
         .map_err(|e| format!("Failed to read interactions dir: {}", e))?;
 
@@ -1004,6 +1219,11 @@ fn remove_entries_by_timestamp(
                     if timestamps.iter().any(|t| ts.contains(t)) {
                         removed_in_file += 1;
                         bytes_freed += line.len() as u64 + 1; // +1 for newline
+                        // BM25 doc_ids use DateTime<Utc>::to_rfc3339, so normalize the raw ts
+                        let doc_id = DateTime::parse_from_rfc3339(ts)
+                            .map(|dt| dt.with_timezone(&Utc).to_rfc3339())
+                            .unwrap_or_else(|_| ts.to_string());
+                        removed_doc_ids.push(doc_id);
                     } else {
                         kept_lines.push(line);
                     }
@@ -1031,7 +1251,7 @@ fn remove_entries_by_timestamp(
         }
     }
 
-    Ok((deleted_count, bytes_freed))
+    Ok((deleted_count, bytes_freed, removed_doc_ids))
 }
 
 // ============================================================================
@@ -1068,17 +1288,18 @@ pub fn cleanup_interactions_in_dir(
             continue;
         }
 
-        if let Some(filename) = path.file_stem().and_then(|s| s.to_str()) {
-            if let Some(date_str) = filename.strip_prefix("interactions-") {
-                if date_str < cutoff_str.as_str() {
-                    if let Ok(metadata) = fs::metadata(&path) {
-                        bytes_freed += metadata.len();
-                    }
+        // Monthly archives are only deleted once the whole month is past retention
+        let period = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(crate::interactions::parse_log_period);
+        if period.is_some_and(|p| p.ends_before(&cutoff_str)) {
+            if let Ok(metadata) = fs::metadata(&path) {
+                bytes_freed += metadata.len();
+            }
 
-                    if fs::remove_file(&path).is_ok() {
-                        deleted_count += 1;
-                    }
-                }
+            if fs::remove_file(&path).is_ok() {
+                deleted_count += 1;
             }
         }
     }
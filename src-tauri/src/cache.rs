@@ -3,8 +3,8 @@
  *
  * Provides TTL-based caching for tool results to reduce API load.
  * Each tool type has its own expiration time:
- * - web_search, search_wikipedia, search_arxiv: 7 days
- * - get_weather, get_stock_price: 1 hour
+ * - web_search, search_wikipedia, read_wikipedia_section, search_arxiv, search_discussions, read_github: 7 days
+ * - get_weather, get_stock_price, read_feed: 1 hour
  * - Other tools: not cached
  */
 use chrono::{DateTime, Duration, Utc};
@@ -35,21 +35,38 @@ pub fn get_ttl_for_tool(tool_name: &str) -> Option<i64> {
         // Long TTL (7 days) - relatively stable data
         "web_search" => Some(7 * 24 * 60 * 60),       // 7 days
         "search_wikipedia" => Some(7 * 24 * 60 * 60), // 7 days
+        "read_wikipedia_section" => Some(7 * 24 * 60 * 60), // 7 days
         "search_arxiv" => Some(7 * 24 * 60 * 60),     // 7 days
         "read_arxiv_paper" => Some(7 * 24 * 60 * 60), // 7 days
+        "search_discussions" => Some(7 * 24 * 60 * 60), // 7 days
+        "read_github" => Some(7 * 24 * 60 * 60),      // 7 days
 
         // Short TTL (1 hour) - frequently changing data
         "get_weather" => Some(60 * 60),      // 1 hour
         "get_stock_price" => Some(60 * 60),  // 1 hour
+        "read_feed" => Some(60 * 60),        // 1 hour
 
         // Not cached
-        "save_memory" | "update_topic_summary" | "read_topic_summary" | "refresh_memories" => None,
+        "save_memory" | "update_topic_summary" | "read_topic_summary" | "list_known_topics" | "refresh_memories" => None,
 
         // Default: don't cache unknown tools
         _ => None,
     }
 }
 
+/// Whether this particular call may be served from or stored in the cache.
+/// "Here" weather lookups resolve through IP geolocation, so the args alone don't identify the result.
+pub fn is_cacheable_call(tool_name: &str, args: &serde_json::Value) -> bool {
+    if get_ttl_for_tool(tool_name).is_none() {
+        return false;
+    }
+    !(tool_name == "get_weather"
+        && matches!(
+            crate::integrations::weather::WeatherLocation::from_args(args),
+            Ok(crate::integrations::weather::WeatherLocation::Here)
+        ))
+}
+
 /// Generate a cache key from tool name and arguments
 pub fn make_cache_key(tool_name: &str, args: &serde_json::Value) -> String {
     // Sort args for consistent hashing
@@ -107,8 +124,8 @@ pub fn get_cached_result<R: Runtime>(
     tool_name: &str,
     args: &serde_json::Value,
 ) -> Option<String> {
-    // Check if this tool is cacheable
-    if get_ttl_for_tool(tool_name).is_none() {
+    // Check if this call is cacheable
+    if !is_cacheable_call(tool_name, args) {
         return None;
     }
 
@@ -118,15 +135,22 @@ pub fn get_cached_result<R: Runtime>(
     if let Some(entry) = cache.entries.get(&key) {
         if entry.expires_at > Utc::now() {
             log::debug!("[Cache] HIT for {} (expires {})", key, entry.expires_at);
+            crate::tool_stats::record_event(app_handle, tool_name, crate::tool_stats::ToolEvent::CacheHit);
             return Some(entry.value.clone());
         } else {
             log::debug!("[Cache] EXPIRED for {}", key);
         }
     }
 
+    crate::tool_stats::record_event(app_handle, tool_name, crate::tool_stats::ToolEvent::CacheMiss);
     None
 }
 
+/// Whether a tool result may be cached; failures ("Error: ...") are retried next time
+pub fn is_cacheable_result(result: &str) -> bool {
+    !result.trim_start().starts_with("Error:")
+}
+
 /// Cache a tool result
 pub fn cache_result<R: Runtime>(
     app_handle: &AppHandle<R>,
@@ -134,10 +158,17 @@ pub fn cache_result<R: Runtime>(
     args: &serde_json::Value,
     result: &str,
 ) {
-    // Check if this tool is cacheable
+    // Check if this call is cacheable
+    if !is_cacheable_call(tool_name, args) {
+        return;
+    }
     let Some(ttl_seconds) = get_ttl_for_tool(tool_name) else {
         return;
     };
+    if !is_cacheable_result(result) {
+        log::debug!("[Cache] Not caching failed {} result", tool_name);
+        return;
+    }
 
     let mut cache = load_cache(app_handle);
     let key = make_cache_key(tool_name, args);
@@ -187,6 +218,7 @@ mod tests {
     #[test]
     fn test_ttl_configuration() {
         assert_eq!(get_ttl_for_tool("web_search"), Some(7 * 24 * 60 * 60));
+        assert_eq!(get_ttl_for_tool("search_discussions"), Some(7 * 24 * 60 * 60));
         assert_eq!(get_ttl_for_tool("get_weather"), Some(60 * 60));
         assert_eq!(get_ttl_for_tool("save_memory"), None);
         assert_eq!(get_ttl_for_tool("unknown_tool"), None);
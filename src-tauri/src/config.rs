@@ -1,30 +1,143 @@
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tauri::{AppHandle, Manager, Runtime};
 
 const CONFIG_FILENAME: &str = "config.toml";
+/// File holding the name of the active profile (absent = default config.toml)
+const ACTIVE_PROFILE_FILENAME: &str = "active_profile";
+/// Reserved profile name that maps to the legacy config.toml
+pub const DEFAULT_PROFILE: &str = "default";
+/// Network timeout for API key validation requests
+const VALIDATION_TIMEOUT_SECS: u64 = 5;
+/// Base timeout for the shared HTTP client when `http_timeout_secs` is unset
+pub const DEFAULT_HTTP_TIMEOUT_SECS: u64 = 60;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AppConfig {
     pub api_key: Option<String>, // Generic/OpenAI key
     pub gemini_api_key: Option<String>,
     pub openrouter_api_key: Option<String>,
+    pub anthropic_api_key: Option<String>, // Direct Anthropic API for claude-* / "(Anthropic)" models
     pub cerebras_api_key: Option<String>,
     pub brave_api_key: Option<String>,
+    pub github_token: Option<String>, // Optional, raises GitHub API rate limits
+    pub allowed_file_roots: Option<Vec<String>>, // Folders read_local_file may read; None = tool refuses
+    pub ip_geolocation: Option<bool>, // Default: false, lets get_weather resolve "here" from the public IP
     pub selected_model: Option<String>,
+    pub model_routes: Option<ModelRoutes>, // Per-task models; unset routes fall back to selected_model
     pub api_base_url: Option<String>, // e.g., https://generativelanguage.googleapis.com/v1beta/openai/
     pub enable_web_search: Option<bool>,
     pub enable_tools: Option<bool>,
+    pub disabled_tools: Option<Vec<String>>, // Tool names hidden from the model, e.g. ["web_search"]
+    pub research_tools: Option<Vec<String>>, // Default: search/read tools only, offered in research mode
+    pub normal_tools: Option<Vec<String>>,   // Default: all tools, offered outside research mode
     pub system_prompt: Option<String>, // Custom system prompt, if None will use MCP default
+    pub system_prompt_template: Option<String>, // {{memories}}, {{rag}}, {{date}}, {{time}}, {{tools}}
+    pub verbosity: Option<Verbosity>, // Default: terse, answer length for the built-in prompt
     pub incognito_mode: Option<bool>,
     pub research_mode: Option<bool>,
+    pub auto_research_detection: Option<bool>, // Default: true, classify each query for research intent
+    pub intent_classifier_model: Option<String>, // Default: gemini-2.5-flash-lite
+    pub research_max_turns: Option<u32>,       // Default: 15
+    pub normal_max_turns: Option<u32>,         // Default: 5
     pub groq_api_key: Option<String>,
     pub background_model: Option<String>,
+    // Local hours (0-23) during which background jobs don't run; window may wrap midnight
+    pub quiet_hours_start: Option<u32>, // e.g. 23
+    pub quiet_hours_end: Option<u32>,   // e.g. 7
     // Auto-retry configuration
     pub max_auto_retries: Option<u32>,   // Default: 2
     pub retry_on_empty: Option<bool>,    // Retry empty responses after reasoning
     pub retry_on_katex: Option<bool>,    // Retry on frontend KaTeX parse errors
+    pub retry_hints: Option<RetryHints>, // Default: built-in hints, per-reason overrides
+    pub max_network_retries: Option<u32>, // Default: 2, backoff retries on 429/5xx
+    pub http_timeout_secs: Option<u64>,   // Default: 60, base timeout for the shared client (restart to apply)
+    pub debug_log_requests: Option<bool>, // Default: false, write redacted provider traffic to api_debug.log
+    // Response streaming: deltas are batched into one UI event per window
+    pub stream_coalesce_ms: Option<u64>,      // Default: 50, 0 emits every delta
+    pub stream_coalesce_chars: Option<usize>, // Default: 64, emit early once this much text is buffered
+    // Gemini thinking configuration (thinking-capable models only)
+    pub thinking_budget: Option<i32>,    // Default: 1024, 0 disables thinking
+    pub include_thoughts: Option<bool>,  // Default: true
+    pub persist_reasoning: Option<bool>, // Default: true, false keeps reasoning out of saved chats
+    pub max_reasoning_chars: Option<usize>, // Default: 32000, head + tail kept per message (0 = no cap)
+    // Topic/insight RAG thresholds (cosine similarity)
+    pub context_similarity_threshold: Option<f32>, // Default: 0.4
+    pub insight_tie_threshold: Option<f32>,        // Default: 0.55
+    pub rag_context_chars: Option<u32>,            // Default: 4000, cap on past-interaction context
+    // Hybrid search tuning (see retrieval::RetrievalTuning)
+    pub rrf_k: Option<f32>,             // Default: 60
+    pub min_dense_hits: Option<u32>,    // Default: 3, fewer dense hits falls back to BM25-only
+    pub temporal_tau_days: Option<f32>, // Default: 15, recency decay constant
+    pub query_expansion: Option<bool>,  // Default: false, add synonyms/acronyms to BM25 queries
+    pub query_synonyms: Option<Vec<Vec<String>>>, // Default: built-in groups (js/javascript, config/configuration, ...)
+    // Local OpenAI-compatible server for "(Local)" models (Ollama/LM Studio)
+    pub local_base_url: Option<String>, // Default: http://localhost:11434/v1/
+    // Default language hint for OCR / image description (e.g. "ja"); None = auto/English
+    pub ocr_language: Option<String>,
+    // Image description; a " (Groq)" suffix sends a model to Groq, otherwise OpenRouter
+    pub vision_models: Option<Vec<String>>, // Default: free OpenRouter models, then Llama 4 Scout on Groq
+    pub vision_prompt: Option<String>,      // Default: identify the subject, extract text, describe details
+    // Attachment caps; extra images are dropped with an `agent-warning` event
+    pub max_images_per_message: Option<usize>, // Default: 10
+    pub max_total_image_bytes: Option<usize>,  // Default: 20 MB, decoded size across all images
+    // Embeddings; changing provider or dims requires rebuilding the topic + insight indexes
+    pub embedding_provider: Option<EmbeddingProvider>, // Default: gemini
+    pub embedding_base_url: Option<String>, // Default: https://api.openai.com/v1/ (openai provider only)
+    pub embedding_api_key: Option<String>,  // openai provider; else api_key for api.openai.com, openrouter_api_key for openrouter.ai
+    pub embedding_model: Option<String>, // Default: gemini-embedding-001 / text-embedding-3-small
+    pub embedding_dims: Option<u32>,     // Default: 768 (Gemini) / model's native size (OpenAI-compatible, not sent)
+    pub embedding_rpm: Option<u32>,      // Default: 100 requests/minute, 0 disables pacing
+    pub web_search_monthly_limit: Option<u32>, // Default: 2000 Brave searches per calendar month
+}
+
+/// Service that generates embeddings for RAG
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EmbeddingProvider {
+    #[default]
+    Gemini,
+    /// Any OpenAI-compatible `/embeddings` endpoint (OpenAI, OpenRouter, local servers)
+    OpenAi,
+}
+
+impl EmbeddingProvider {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Gemini => "gemini",
+            Self::OpenAi => "openai",
+        }
+    }
+}
+
+/// Answer length requested by the built-in system prompt
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Verbosity {
+    #[default]
+    Terse,
+    Balanced,
+    Detailed,
+}
+
+/// Models picked per task type; `default` covers plain chat and any unset route
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct ModelRoutes {
+    pub default: Option<String>,
+    pub research: Option<String>,
+    pub code: Option<String>,
+    pub vision: Option<String>,
+}
+
+/// Custom retry nudges; unset or blank hints use the built-in text.
+/// `malformed_latex` may contain `{errors}`, replaced with the KaTeX error list.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct RetryHints {
+    pub empty_response: Option<String>,
+    pub malformed_latex: Option<String>,
 }
 
 impl Default for AppConfig {
@@ -33,35 +146,105 @@ impl Default for AppConfig {
             api_key: None,
             gemini_api_key: None,
             openrouter_api_key: None,
+            anthropic_api_key: None,
             cerebras_api_key: None,
             brave_api_key: None,
+            github_token: None,
+            allowed_file_roots: None,
+            ip_geolocation: None,
             selected_model: None,
+            model_routes: None,
             api_base_url: None,
             enable_web_search: None,
             enable_tools: Some(true),
+            disabled_tools: None,
+            research_tools: None,
+            normal_tools: None,
             system_prompt: None,
+            system_prompt_template: None,
+            verbosity: None,
             incognito_mode: None,
             research_mode: Some(false),
+            auto_research_detection: None,
+            intent_classifier_model: None,
+            research_max_turns: None,
+            normal_max_turns: None,
             groq_api_key: None,
             background_model: Some("gpt-oss-120b (Groq)".to_string()),
+            quiet_hours_start: None,
+            quiet_hours_end: None,
             // Auto-retry defaults
             max_auto_retries: Some(2),
             retry_on_empty: Some(true),
             retry_on_katex: Some(true),
+            retry_hints: None,
+            max_network_retries: Some(2),
+            http_timeout_secs: None,
+            debug_log_requests: None,
+            stream_coalesce_ms: None,
+            stream_coalesce_chars: None,
+            thinking_budget: None,
+            include_thoughts: None,
+            persist_reasoning: None,
+            max_reasoning_chars: None,
+            context_similarity_threshold: None,
+            insight_tie_threshold: None,
+            rag_context_chars: None,
+            rrf_k: None,
+            min_dense_hits: None,
+            temporal_tau_days: None,
+            local_base_url: None,
+            query_expansion: None,
+            query_synonyms: None,
+            ocr_language: None,
+            vision_models: None,
+            vision_prompt: None,
+            max_images_per_message: None,
+            max_total_image_bytes: None,
+            embedding_provider: None,
+            embedding_base_url: None,
+            embedding_api_key: None,
+            embedding_model: None,
+            embedding_dims: None,
+            embedding_rpm: None,
+            web_search_monthly_limit: None,
         }
     }
 }
 
-pub fn get_config_path<R: Runtime>(app_handle: &AppHandle<R>) -> Result<PathBuf, String> {
-    let resolver = app_handle.path();
-    match resolver.app_config_dir() {
-        Ok(dir) => Ok(dir.join(CONFIG_FILENAME)),
-        Err(e) => Err(format!("Failed to get app config directory: {}", e)),
-    }
+fn get_config_dir<R: Runtime>(app_handle: &AppHandle<R>) -> Result<PathBuf, String> {
+    app_handle
+        .path()
+        .app_config_dir()
+        .map_err(|e| format!("Failed to get app config directory: {}", e))
 }
 
+/// Load the active profile's config (falls back to config.toml)
 pub fn load_config<R: Runtime>(app_handle: &AppHandle<R>) -> Result<AppConfig, String> {
-    let config_path = get_config_path(app_handle)?;
+    load_config_in_dir(&get_config_dir(app_handle)?)
+}
+
+/// Save to the active profile's config (falls back to config.toml)
+pub fn save_config<R: Runtime>(app_handle: &AppHandle<R>, config: &AppConfig) -> Result<(), String> {
+    save_config_in_dir(&get_config_dir(app_handle)?, config)
+}
+
+pub fn load_config_in_dir(dir: &Path) -> Result<AppConfig, String> {
+    match get_active_profile_in_dir(dir).as_str() {
+        DEFAULT_PROFILE => load_default_config(dir),
+        name => load_profile_in_dir(dir, name),
+    }
+}
+
+pub fn save_config_in_dir(dir: &Path, config: &AppConfig) -> Result<(), String> {
+    match get_active_profile_in_dir(dir).as_str() {
+        DEFAULT_PROFILE => save_default_config(dir, config),
+        name => write_profile_file(dir, name, config),
+    }
+}
+
+fn load_default_config(dir: &Path) -> Result<AppConfig, String> {
+    let config_path = dir.join(CONFIG_FILENAME);
     if !config_path.exists() {
         return Ok(AppConfig::default());
     }
@@ -70,15 +253,235 @@ pub fn load_config<R: Runtime>(app_handle: &AppHandle<R>) -> Result<AppConfig, S
     toml::from_str(&content).map_err(|e| format!("Failed to parse config file: {}", e))
 }
 
-pub fn save_config<R: Runtime>(app_handle: &AppHandle<R>, config: &AppConfig) -> Result<(), String> {
-    let config_path = get_config_path(app_handle)?;
-    if let Some(parent_dir) = config_path.parent() {
-        if !parent_dir.exists() {
-            fs::create_dir_all(parent_dir)
-                .map_err(|e| format!("Failed to create config directory: {}", e))?;
-        }
-    }
+fn save_default_config(dir: &Path, config: &AppConfig) -> Result<(), String> {
+    ensure_dir(dir)?;
     let toml_string =
         toml::to_string_pretty(config).map_err(|e| format!("Failed to serialize config: {}", e))?;
-    fs::write(&config_path, toml_string).map_err(|e| format!("Failed to write config file: {}", e))
+    fs::write(dir.join(CONFIG_FILENAME), toml_string)
+        .map_err(|e| format!("Failed to write config file: {}", e))
+}
+
+fn ensure_dir(dir: &Path) -> Result<(), String> {
+    if !dir.exists() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create config directory: {}", e))?;
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Config Profiles
+// ============================================================================
+
+/// Profile names become filenames, so restrict them to a safe character set
+fn validate_profile_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > 64 {
+        return Err("Profile name must be 1-64 characters".to_string());
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!(
+            "Invalid profile name '{}': use letters, digits, '-' or '_'",
+            name
+        ));
+    }
+    Ok(())
+}
+
+fn get_profile_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(format!("config-{}.json", name))
+}
+
+fn write_profile_file(dir: &Path, name: &str, config: &AppConfig) -> Result<(), String> {
+    validate_profile_name(name)?;
+    ensure_dir(dir)?;
+    let json = serde_json::to_string_pretty(config)
+        .map_err(|e| format!("Failed to serialize profile: {}", e))?;
+    fs::write(get_profile_path(dir, name), json)
+        .map_err(|e| format!("Failed to write profile '{}': {}", name, e))
+}
+
+/// Name of the active profile. A missing or dangling pointer means the default profile.
+pub fn get_active_profile_in_dir(dir: &Path) -> String {
+    let name = fs::read_to_string(dir.join(ACTIVE_PROFILE_FILENAME))
+        .map(|s| s.trim().to_string())
+        .unwrap_or_default();
+
+    if name.is_empty() || name == DEFAULT_PROFILE || !get_profile_path(dir, &name).exists() {
+        return DEFAULT_PROFILE.to_string();
+    }
+    name
+}
+
+fn set_active_profile_in_dir(dir: &Path, name: &str) -> Result<(), String> {
+    ensure_dir(dir)?;
+    fs::write(dir.join(ACTIVE_PROFILE_FILENAME), name)
+        .map_err(|e| format!("Failed to write active profile: {}", e))
+}
+
+/// List saved profile names, always including the default profile first
+pub fn list_profiles_in_dir(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| {
+                    let file_name = entry.file_name().to_string_lossy().to_string();
+                    file_name
+                        .strip_prefix("config-")
+                        .and_then(|rest| rest.strip_suffix(".json"))
+                        .map(|name| name.to_string())
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names.insert(0, DEFAULT_PROFILE.to_string());
+    names
+}
+
+/// Save a config under a profile name without switching to it
+pub fn save_profile_in_dir(dir: &Path, name: &str, config: &AppConfig) -> Result<(), String> {
+    if name == DEFAULT_PROFILE {
+        return save_default_config(dir, config);
+    }
+    write_profile_file(dir, name, config)
+}
+
+/// Read a profile's config without switching to it
+pub fn load_profile_in_dir(dir: &Path, name: &str) -> Result<AppConfig, String> {
+    if name == DEFAULT_PROFILE {
+        return load_default_config(dir);
+    }
+    validate_profile_name(name)?;
+    let path = get_profile_path(dir, name);
+    let content = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read profile '{}': {}", name, e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse profile '{}': {}", name, e))
+}
+
+/// Make a profile active and return its config
+pub fn switch_profile_in_dir(dir: &Path, name: &str) -> Result<AppConfig, String> {
+    let config = load_profile_in_dir(dir, name)?;
+    set_active_profile_in_dir(dir, name)?;
+    log::info!("[Config] Switched to profile '{}'", name);
+    Ok(config)
+}
+
+/// Delete a profile. Deleting the active profile falls back to the default.
+pub fn delete_profile_in_dir(dir: &Path, name: &str) -> Result<(), String> {
+    if name == DEFAULT_PROFILE {
+        return Err("The default profile cannot be deleted".to_string());
+    }
+    validate_profile_name(name)?;
+    let was_active = get_active_profile_in_dir(dir) == name;
+
+    fs::remove_file(get_profile_path(dir, name))
+        .map_err(|e| format!("Failed to delete profile '{}': {}", name, e))?;
+
+    if was_active {
+        set_active_profile_in_dir(dir, DEFAULT_PROFILE)?;
+        log::info!("[Config] Deleted active profile '{}', falling back to default", name);
+    }
+    Ok(())
+}
+
+pub fn list_profiles<R: Runtime>(app_handle: &AppHandle<R>) -> Result<Vec<String>, String> {
+    Ok(list_profiles_in_dir(&get_config_dir(app_handle)?))
+}
+
+pub fn get_active_profile<R: Runtime>(app_handle: &AppHandle<R>) -> Result<String, String> {
+    Ok(get_active_profile_in_dir(&get_config_dir(app_handle)?))
+}
+
+pub fn save_profile<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    name: &str,
+    config: &AppConfig,
+) -> Result<(), String> {
+    save_profile_in_dir(&get_config_dir(app_handle)?, name, config)
+}
+
+pub fn load_profile<R: Runtime>(app_handle: &AppHandle<R>, name: &str) -> Result<AppConfig, String> {
+    switch_profile_in_dir(&get_config_dir(app_handle)?, name)
+}
+
+pub fn delete_profile<R: Runtime>(app_handle: &AppHandle<R>, name: &str) -> Result<(), String> {
+    delete_profile_in_dir(&get_config_dir(app_handle)?, name)
+}
+
+// ============================================================================
+// API Key Validation
+// ============================================================================
+
+/// Cheap authenticated endpoint used to validate a provider's API key.
+/// Returns (url, uses_bearer_auth) - Gemini takes the key as a query param instead.
+pub fn get_validation_endpoint(provider: &str) -> Option<(&'static str, bool)> {
+    match provider.trim().to_lowercase().as_str() {
+        "gemini" => Some(("https://generativelanguage.googleapis.com/v1beta/models", false)),
+        // OpenRouter's /models is public, so use the key info endpoint which requires auth
+        "openrouter" => Some(("https://openrouter.ai/api/v1/auth/key", true)),
+        "cerebras" => Some(("https://api.cerebras.ai/v1/models", true)),
+        "groq" => Some(("https://api.groq.com/openai/v1/models", true)),
+        _ => None,
+    }
+}
+
+/// Validate an API key with a single authenticated request.
+/// The key is never persisted - this only reports whether the provider accepts it.
+pub async fn validate_api_key(provider: &str, key: &str) -> Result<(), String> {
+    let key = key.trim();
+    if key.is_empty() {
+        return Err("API key is empty".to_string());
+    }
+
+    let (url, bearer_auth) = get_validation_endpoint(provider)
+        .ok_or_else(|| format!("Unknown provider: {}", provider))?;
+
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(VALIDATION_TIMEOUT_SECS))
+        .build()
+        .map_err(|e| format!("Failed to build client: {}", e))?;
+
+    let request = if bearer_auth {
+        client
+            .get(url)
+            .header("Authorization", format!("Bearer {}", key))
+    } else {
+        client.get(url).query(&[("key", key), ("pageSize", "1")])
+    };
+
+    let response = request.send().await.map_err(|e| {
+        if e.is_timeout() {
+            format!(
+                "{} validation timed out after {}s",
+                provider, VALIDATION_TIMEOUT_SECS
+            )
+        } else {
+            format!("{} network error: {}", provider, e)
+        }
+    })?;
+
+    let status = response.status();
+    if status.is_success() {
+        log::info!("[Config] {} API key validated", provider);
+        return Ok(());
+    }
+
+    let error_text = response.text().await.unwrap_or_default();
+    match status.as_u16() {
+        400 | 401 | 403 => Err(format!(
+            "{} rejected the API key ({}): {}",
+            provider, status, error_text
+        )),
+        429 => Err(format!(
+            "{} rate limited the validation request - the key may still be valid",
+            provider
+        )),
+        _ => Err(format!(
+            "{} validation failed ({}): {}",
+            provider, status, error_text
+        )),
+    }
 }
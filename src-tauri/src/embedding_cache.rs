@@ -0,0 +1,167 @@
+/**
+ * Embedding Cache Module
+ *
+ * Content-hash keyed cache for embedding vectors so identical texts
+ * (topic headers, repeated messages, index rebuilds) don't re-hit the embed API.
+ * - Key: FNV-1a hash of the text + output dimensionality
+ * - Eviction: least recently used once MAX_ENTRIES is exceeded
+ * - Persistence: `embedding_cache.json`, flushed every FLUSH_EVERY inserts
+ */
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use tauri::{AppHandle, Manager, Runtime};
+
+/// Max cached vectors (~6KB each as JSON at 768 dims)
+pub const MAX_ENTRIES: usize = 2000;
+/// Number of inserts between disk flushes
+const FLUSH_EVERY: usize = 20;
+
+/// Cached embedding vector with last access time for LRU eviction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedEmbedding {
+    pub values: Vec<f32>,
+    pub last_used: DateTime<Utc>,
+}
+
+/// Embedding cache stored on disk
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct EmbeddingCache {
+    pub entries: HashMap<String, CachedEmbedding>,
+    #[serde(skip)]
+    pending_writes: usize,
+}
+
+/// FNV-1a hash of the text, same scheme as the tool cache keys
+fn fnv1a(text: &str) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in text.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Cache key from text content and output dimensionality
+pub fn make_embedding_key(text: &str, dims: u32) -> String {
+    format!("{:016x}:{}", fnv1a(text), dims)
+}
+
+impl EmbeddingCache {
+    /// Look up a cached vector, refreshing its last-used time
+    pub fn get(&mut self, text: &str, dims: u32) -> Option<Vec<f32>> {
+        let entry = self.entries.get_mut(&make_embedding_key(text, dims))?;
+        entry.last_used = Utc::now();
+        Some(entry.values.clone())
+    }
+
+    /// Store a vector, evicting the least recently used entries over the cap
+    pub fn insert(&mut self, text: &str, dims: u32, values: Vec<f32>) {
+        self.entries.insert(
+            make_embedding_key(text, dims),
+            CachedEmbedding {
+                values,
+                last_used: Utc::now(),
+            },
+        );
+        self.pending_writes += 1;
+        self.evict_to(MAX_ENTRIES);
+    }
+
+    /// Drop least recently used entries until at most `cap` remain
+    pub fn evict_to(&mut self, cap: usize) {
+        if self.entries.len() <= cap {
+            return;
+        }
+        let mut by_age: Vec<(String, DateTime<Utc>)> = self
+            .entries
+            .iter()
+            .map(|(k, v)| (k.clone(), v.last_used))
+            .collect();
+        by_age.sort_by_key(|(_, last_used)| *last_used);
+
+        let excess = self.entries.len() - cap;
+        for (key, _) in by_age.into_iter().take(excess) {
+            self.entries.remove(&key);
+        }
+    }
+}
+
+// ============================================================================
+// Persistence
+// ============================================================================
+
+static CACHE_PATH: OnceLock<PathBuf> = OnceLock::new();
+static CACHE: OnceLock<Mutex<EmbeddingCache>> = OnceLock::new();
+
+/// Set the on-disk cache location (called once at startup)
+pub fn init<R: Runtime>(app_handle: &AppHandle<R>) {
+    if let Ok(app_data_dir) = app_handle.path().app_data_dir() {
+        let _ = CACHE_PATH.set(app_data_dir.join("embedding_cache.json"));
+    }
+}
+
+fn load_cache(path: Option<&Path>) -> EmbeddingCache {
+    path.and_then(|p| fs::read_to_string(p).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_cache(path: &Path, cache: &EmbeddingCache) {
+    if let Ok(content) = serde_json::to_string(cache) {
+        if let Err(e) = fs::write(path, content) {
+            log::warn!("[EmbeddingCache] Failed to write cache: {}", e);
+        }
+    }
+}
+
+fn global_cache() -> &'static Mutex<EmbeddingCache> {
+    CACHE.get_or_init(|| Mutex::new(load_cache(CACHE_PATH.get().map(|p| p.as_path()))))
+}
+
+/// Return a cached embedding or generate (and cache) a new one via the global cache
+pub async fn get_or_generate<F, Fut>(text: &str, dims: u32, generate: F) -> Result<Vec<f32>, String>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<Vec<f32>, String>>,
+{
+    get_or_generate_in(global_cache(), CACHE_PATH.get().map(|p| p.as_path()), text, dims, generate)
+        .await
+}
+
+/// Core lookup/insert logic against a given cache (testable)
+/// The lock is not held across the generate call.
+pub async fn get_or_generate_in<F, Fut>(
+    cache: &Mutex<EmbeddingCache>,
+    path: Option<&Path>,
+    text: &str,
+    dims: u32,
+    generate: F,
+) -> Result<Vec<f32>, String>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<Vec<f32>, String>>,
+{
+    if let Some(values) = cache.lock().ok().and_then(|mut c| c.get(text, dims)) {
+        log::debug!("[EmbeddingCache] HIT ({} chars)", text.len());
+        return Ok(values);
+    }
+
+    let values = generate().await?;
+
+    if let Ok(mut c) = cache.lock() {
+        c.insert(text, dims, values.clone());
+        if c.pending_writes >= FLUSH_EVERY {
+            if let Some(path) = path {
+                save_cache(path, &c);
+            }
+            c.pending_writes = 0;
+        }
+    }
+
+    Ok(values)
+}
@@ -0,0 +1,179 @@
+/**
+ * Error Module
+ *
+ * Structured errors for the agent so the frontend can tell a missing API key
+ * from a network timeout from a quota error. Serializes as a tagged object:
+ * `{ "kind": "quota", "provider": "Groq", "message": "..." }`
+ */
+use serde::Serialize;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ShardError {
+    /// No API key configured for the provider
+    MissingApiKey { provider: String },
+    /// The provider rejected the API key
+    Auth { provider: String, message: String },
+    /// Request timed out
+    Timeout { provider: String },
+    /// Connection or streaming failure
+    Network { provider: String, message: String },
+    /// Rate limit or token quota exceeded
+    Quota { provider: String, message: String },
+    /// Any other non-success API response
+    ApiError {
+        provider: String,
+        status: u16,
+        body: String,
+    },
+    /// Malformed response or payload
+    Parse { message: String },
+    /// Anything without a more specific variant
+    Other { message: String },
+}
+
+/// Error codes / statuses providers put in quota / rate-limit error bodies.
+/// Only machine-readable codes: free-text messages mention "quota" for unrelated errors.
+const QUOTA_MARKERS: &[&str] = &[
+    "token_quota_exceeded",
+    "too_many_tokens_error",
+    "rate_limit_exceeded",
+    "insufficient_quota",
+    "RESOURCE_EXHAUSTED",
+];
+
+/// Substrings providers use when an API key is rejected
+const AUTH_MARKERS: &[&str] = &[
+    "API_KEY_INVALID",
+    "invalid_api_key",
+    "Invalid API Key",
+    "invalid api key",
+    "No auth credentials found",
+];
+
+/// Whether a provider response is a quota or rate-limit failure (429 or a quota error code)
+pub fn is_quota_error(status: u16, body: &str) -> bool {
+    status == 429 || QUOTA_MARKERS.iter().any(|m| body.contains(m))
+}
+
+/// Retries on transient provider errors when the config doesn't set `max_network_retries`
+pub const DEFAULT_MAX_NETWORK_RETRIES: u32 = 2;
+/// First retry waits this long; each later retry doubles it
+const BACKOFF_BASE_MS: u64 = 500;
+/// Upper bound on a single backoff (before jitter)
+const BACKOFF_MAX_MS: u64 = 8_000;
+
+/// Transient statuses worth retrying (rate limit / server errors; never auth)
+pub fn is_retryable_status(status: u16) -> bool {
+    matches!(status, 429 | 500 | 502 | 503)
+}
+
+/// Delay before retry `attempt` (0-based): base * 2^attempt, capped, plus up to 25% jitter.
+/// `jitter` is in [0, 1) so the schedule is deterministic in tests.
+pub fn backoff_delay(attempt: u32, jitter: f64) -> std::time::Duration {
+    let base = BACKOFF_BASE_MS
+        .saturating_mul(1u64 << attempt.min(16))
+        .min(BACKOFF_MAX_MS);
+    let extra = (base as f64 * 0.25 * jitter.clamp(0.0, 1.0)) as u64;
+    std::time::Duration::from_millis(base + extra)
+}
+
+/// Cheap jitter source in [0, 1) from the clock's sub-second nanos
+pub fn jitter_fraction() -> f64 {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    nanos as f64 / 1_000_000_000.0
+}
+
+impl ShardError {
+    pub fn missing_api_key(provider: &str) -> Self {
+        ShardError::MissingApiKey {
+            provider: provider.to_string(),
+        }
+    }
+
+    /// Classify a non-success provider response by status code and body
+    pub fn from_api_response(provider: &str, status: u16, body: &str) -> Self {
+        let provider = provider.to_string();
+        if is_quota_error(status, body) {
+            ShardError::Quota {
+                provider,
+                message: body.to_string(),
+            }
+        } else if status == 401 || AUTH_MARKERS.iter().any(|m| body.contains(m)) {
+            ShardError::Auth {
+                provider,
+                message: body.to_string(),
+            }
+        } else {
+            ShardError::ApiError {
+                provider,
+                status,
+                body: body.to_string(),
+            }
+        }
+    }
+
+    /// Classify a transport-level reqwest failure
+    pub fn from_reqwest(provider: &str, err: &reqwest::Error) -> Self {
+        if err.is_timeout() {
+            ShardError::Timeout {
+                provider: provider.to_string(),
+            }
+        } else if err.is_decode() {
+            ShardError::Parse {
+                message: format!("{} response: {}", provider, err),
+            }
+        } else {
+            ShardError::Network {
+                provider: provider.to_string(),
+                message: err.to_string(),
+            }
+        }
+    }
+}
+
+impl fmt::Display for ShardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShardError::MissingApiKey { provider } => {
+                write!(f, "No {} API key configured", provider)
+            }
+            ShardError::Auth { provider, message } => {
+                write!(f, "{} rejected the API key: {}", provider, message)
+            }
+            ShardError::Timeout { provider } => write!(f, "{} request timed out", provider),
+            ShardError::Network { provider, message } => {
+                write!(f, "{} network error: {}", provider, message)
+            }
+            ShardError::Quota { provider, message } => {
+                write!(f, "{} quota exceeded: {}", provider, message)
+            }
+            ShardError::ApiError {
+                provider,
+                status,
+                body,
+            } => write!(f, "{} API error ({}): {}", provider, status, body),
+            ShardError::Parse { message } => write!(f, "Parse error: {}", message),
+            ShardError::Other { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ShardError {}
+
+/// Existing helpers return `Result<_, String>`; let `?` lift them into ShardError
+impl From<String> for ShardError {
+    fn from(message: String) -> Self {
+        ShardError::Other { message }
+    }
+}
+
+impl From<ShardError> for String {
+    fn from(err: ShardError) -> Self {
+        err.to_string()
+    }
+}
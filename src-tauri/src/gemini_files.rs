@@ -32,7 +32,7 @@ pub async fn upload_image_to_gemini_files_api(
 
     // Step 2: Initial POST to get upload_url
     // We generate a random display name to avoid collisions, though Gemini handles this.
-    let display_name = format!("image_{}.png", uuid::Uuid::new_v4());
+    let display_name = format!("{}{}{}", UPLOAD_NAME_PREFIX, uuid::Uuid::new_v4(), UPLOAD_NAME_SUFFIX);
 
     #[derive(Serialize)]
     struct FileMetadata {
@@ -114,3 +114,122 @@ pub async fn upload_image_to_gemini_files_api(
         file_uri: response_data.file.uri,
     })
 }
+
+// ============================================================================
+// Listing and Cleanup
+// ============================================================================
+
+const FILES_API_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
+const LIST_PAGE_SIZE: &str = "100";
+/// Display names given to our own uploads: `image_<uuid>.png`
+const UPLOAD_NAME_PREFIX: &str = "image_";
+const UPLOAD_NAME_SUFFIX: &str = ".png";
+
+/// A file stored in the Gemini Files API (uploads expire on Google's side after 48 hours)
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GeminiFileInfo {
+    pub name: String, // "files/abc123"
+    pub uri: String,
+    pub mime_type: String,
+    pub display_name: String,
+    pub size_bytes: String, // int64 encoded as a string by the API
+    pub create_time: String,
+    pub expiration_time: String,
+}
+
+/// The id at the end of a file URI or resource name ("files/abc123" -> "abc123")
+pub fn file_id(uri_or_name: &str) -> &str {
+    uri_or_name.rsplit('/').next().unwrap_or(uri_or_name)
+}
+
+/// Ids of the uploaded files attached to `messages`
+pub fn referenced_file_ids<'a>(
+    messages: impl IntoIterator<Item = &'a crate::agent::ChatMessage>,
+) -> std::collections::HashSet<String> {
+    messages
+        .into_iter()
+        .flat_map(|msg| msg.images.iter().flatten())
+        .filter_map(|image| image.file_uri.as_deref())
+        .map(|uri| file_id(uri).to_string())
+        .collect()
+}
+
+/// Whether a file was uploaded by this app (other tools may share the API key)
+pub fn is_app_upload(file: &GeminiFileInfo) -> bool {
+    file.display_name
+        .strip_prefix(UPLOAD_NAME_PREFIX)
+        .and_then(|rest| rest.strip_suffix(UPLOAD_NAME_SUFFIX))
+        .is_some_and(|id| uuid::Uuid::parse_str(id).is_ok())
+}
+
+/// This app's uploads whose id isn't in `referenced` (ids from `file_id`)
+pub fn find_orphaned_files<'a>(
+    files: &'a [GeminiFileInfo],
+    referenced: &std::collections::HashSet<String>,
+) -> Vec<&'a GeminiFileInfo> {
+    files
+        .iter()
+        .filter(|file| is_app_upload(file))
+        .filter(|file| !referenced.contains(file_id(&file.name)) && !referenced.contains(file_id(&file.uri)))
+        .collect()
+}
+
+/// All files uploaded with this API key, following pagination
+pub async fn list_gemini_files(client: &reqwest::Client, api_key: &str) -> Result<Vec<GeminiFileInfo>, String> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct ListResponse {
+        #[serde(default)]
+        files: Vec<GeminiFileInfo>,
+        next_page_token: Option<String>,
+    }
+
+    let mut files = Vec::new();
+    let mut page_token: Option<String> = None;
+    loop {
+        let mut query = vec![("key", api_key), ("pageSize", LIST_PAGE_SIZE)];
+        if let Some(token) = page_token.as_deref() {
+            query.push(("pageToken", token));
+        }
+        let response = client
+            .get(format!("{}/files", FILES_API_URL))
+            .query(&query)
+            .send()
+            .await
+            .map_err(|e| format!("List files request failed (network error): {}", e))?;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(format!("List files request failed (API error): {}", error_text));
+        }
+
+        let page: ListResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse list files response JSON: {}", e))?;
+        files.extend(page.files);
+
+        match page.next_page_token.filter(|t| !t.is_empty()) {
+            Some(token) => page_token = Some(token),
+            None => break,
+        }
+    }
+    Ok(files)
+}
+
+/// Delete one file by resource name ("files/abc123") or URI
+pub async fn delete_gemini_file(client: &reqwest::Client, api_key: &str, name: &str) -> Result<(), String> {
+    let response = client
+        .delete(format!("{}/files/{}", FILES_API_URL, file_id(name)))
+        .query(&[("key", api_key)])
+        .send()
+        .await
+        .map_err(|e| format!("Delete file request failed (network error): {}", e))?;
+
+    if !response.status().is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("Delete file request failed (API error): {}", error_text));
+    }
+    Ok(())
+}
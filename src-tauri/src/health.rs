@@ -0,0 +1,375 @@
+/**
+ * Healthcheck
+ *
+ * Self-diagnostic over the app data dir, for support requests. Surfaces the
+ * index drift that otherwise silently degrades retrieval:
+ * - BM25 doc count vs unique entries in the interaction logs (allowing for the prune's
+ *   retention window and doc cap)
+ * - Topic/insight index entries vs `.md` files on disk
+ */
+use crate::agent::{load_session, read_active_session, ChatMessage, LEGACY_HISTORY_FILENAME};
+use crate::config::AppConfig;
+use crate::memories::{resolve_file_stem, InsightIndex, TopicIndex};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use tauri::{AppHandle, Manager, Runtime};
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct HealthReport {
+    pub chat_history_ok: bool,
+    pub bm25: CountDrift,
+    pub topics: IndexDrift,
+    pub insights: IndexDrift,
+    pub memory_tokens: usize,
+    pub memory_token_budget: usize,
+    pub api_keys: ApiKeyStatus,
+    pub problems: Vec<String>, // Human-readable summary of everything above that's off
+}
+
+/// Indexed count vs what's actually on disk
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CountDrift {
+    pub indexed: usize,
+    pub on_disk: usize,
+    /// Fewest documents a healthy index holds (the index may hold up to `on_disk`)
+    pub expected_min: usize,
+    pub drift: i64, // indexed - on_disk
+}
+
+impl CountDrift {
+    pub fn new(indexed: usize, on_disk: usize) -> Self {
+        Self {
+            indexed,
+            on_disk,
+            expected_min: on_disk,
+            drift: indexed as i64 - on_disk as i64,
+        }
+    }
+
+    /// Drift for an index that is pruned to docs newer than the retention cutoff
+    /// (`recent_on_disk` of them) and capped at `max_docs`. Old docs linger until the
+    /// next prune, so anything from the pruned size up to everything on disk is fine.
+    pub fn pruned(indexed: usize, on_disk: usize, recent_on_disk: usize, max_docs: usize) -> Self {
+        Self {
+            expected_min: recent_on_disk.min(max_docs),
+            ..Self::new(indexed, on_disk)
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        (self.expected_min..=self.on_disk).contains(&self.indexed)
+    }
+}
+
+/// Index entries whose `.md` file is gone, and `.md` files the index doesn't know about
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct IndexDrift {
+    pub missing_files: Vec<String>,
+    pub unindexed_files: Vec<String>,
+}
+
+impl IndexDrift {
+    pub fn is_ok(&self) -> bool {
+        self.missing_files.is_empty() && self.unindexed_files.is_empty()
+    }
+}
+
+/// Which keys are set - never the keys themselves
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ApiKeyStatus {
+    pub gemini: bool,
+    pub openrouter: bool,
+    pub anthropic: bool,
+    pub cerebras: bool,
+    pub groq: bool,
+    pub openai: bool,
+    pub brave: bool,
+    pub github: bool,
+    pub embeddings: bool, // A key for the configured embeddings provider
+}
+
+impl ApiKeyStatus {
+    pub fn from_config(config: &AppConfig) -> Self {
+        let set = |key: &Option<String>| key.as_deref().is_some_and(|k| !k.trim().is_empty());
+        Self {
+            gemini: set(&config.gemini_api_key),
+            openrouter: set(&config.openrouter_api_key),
+            anthropic: set(&config.anthropic_api_key),
+            cerebras: set(&config.cerebras_api_key),
+            groq: set(&config.groq_api_key),
+            openai: set(&config.api_key),
+            brave: set(&config.brave_api_key),
+            github: set(&config.github_token),
+            embeddings: crate::interactions::embedding_api_key(config).is_some_and(|k| !k.trim().is_empty()),
+        }
+    }
+}
+
+/// Compare index entries (display names) against the `.md` stems present on disk
+pub fn index_file_drift<'a>(
+    names: impl Iterator<Item = &'a String>,
+    filenames: &HashMap<String, String>,
+    file_stems: &BTreeSet<String>,
+) -> IndexDrift {
+    let mut claimed = HashSet::new();
+    let mut missing_files = Vec::new();
+    for name in names {
+        match resolve_file_stem(filenames, name) {
+            Some(stem) if file_stems.contains(&stem) => {
+                claimed.insert(stem);
+            }
+            _ => missing_files.push(name.clone()),
+        }
+    }
+    missing_files.sort();
+
+    IndexDrift {
+        missing_files,
+        unindexed_files: file_stems.iter().filter(|stem| !claimed.contains(*stem)).cloned().collect(),
+    }
+}
+
+/// Unique interaction doc ids (timestamps) across the JSONL logs - what a BM25 rebuild would index
+pub fn count_interaction_docs(interactions_dir: &Path) -> usize {
+    interaction_doc_counts(interactions_dir, chrono::DateTime::<chrono::Utc>::MIN_UTC).0
+}
+
+/// Unique interaction docs on disk: all of them, and those at or after `cutoff`
+fn interaction_doc_counts(interactions_dir: &Path, cutoff: chrono::DateTime<chrono::Utc>) -> (usize, usize) {
+    let Ok(entries) = fs::read_dir(interactions_dir) else {
+        return (0, 0);
+    };
+    let mut doc_ids = HashSet::new();
+    let mut recent = HashSet::new();
+    for path in entries.flatten().map(|e| e.path()) {
+        if path.extension().and_then(|s| s.to_str()) != Some("jsonl") {
+            continue;
+        }
+        let Ok(content) = fs::read_to_string(&path) else {
+            continue;
+        };
+        for line in content.lines() {
+            if let Ok(entry) = serde_json::from_str::<crate::interactions::InteractionEntry>(line) {
+                if entry.ts >= cutoff {
+                    recent.insert(entry.ts.to_rfc3339());
+                }
+                doc_ids.insert(entry.ts.to_rfc3339());
+            }
+        }
+    }
+    (doc_ids.len(), recent.len())
+}
+
+fn md_file_stems(dir: &Path) -> BTreeSet<String> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return BTreeSet::new();
+    };
+    entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("md"))
+        .filter_map(|path| path.file_stem()?.to_str().map(|s| s.to_string()))
+        .collect()
+}
+
+/// Read an index without the dims/provider checks, so a stale index still gets diagnosed
+fn read_index<T: serde::de::DeserializeOwned + Default>(path: &Path) -> Result<T, String> {
+    if !path.exists() {
+        return Ok(T::default());
+    }
+    let content = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+}
+
+/// Parse the active session, plus a not-yet-migrated chat_history.json if one is present
+fn check_chat_history(data_dir: &Path) -> Result<(), String> {
+    let legacy = data_dir.join(LEGACY_HISTORY_FILENAME);
+    if legacy.exists() {
+        let content = fs::read_to_string(&legacy)
+            .map_err(|e| format!("Failed to read {}: {}", LEGACY_HISTORY_FILENAME, e))?;
+        serde_json::from_str::<Vec<ChatMessage>>(&content)
+            .map_err(|e| format!("Failed to parse {}: {}", LEGACY_HISTORY_FILENAME, e))?;
+    }
+    load_session(data_dir, &read_active_session(data_dir)).map(|_| ())
+}
+
+fn describe_index_drift(kind: &str, drift: &IndexDrift, problems: &mut Vec<String>) {
+    if !drift.missing_files.is_empty() {
+        problems.push(format!(
+            "{} index has {} entries without a file: {}",
+            kind,
+            drift.missing_files.len(),
+            drift.missing_files.join(", ")
+        ));
+    }
+    if !drift.unindexed_files.is_empty() {
+        problems.push(format!(
+            "{} files missing from the index (rebuild it): {}",
+            kind,
+            drift.unindexed_files.join(", ")
+        ));
+    }
+}
+
+/// Build the report for an app data directory
+pub fn healthcheck_in_dir(data_dir: &Path, config: &AppConfig) -> HealthReport {
+    let mut problems = Vec::new();
+
+    let chat_history_ok = match check_chat_history(data_dir) {
+        Ok(()) => true,
+        Err(e) => {
+            problems.push(format!("Chat history is unreadable: {}", e));
+            false
+        }
+    };
+
+    let interactions_dir = data_dir.join("interactions");
+    let bm25_path = interactions_dir.join(crate::retrieval::BM25_INDEX_FILENAME);
+    let bm25_index = crate::retrieval::load_bm25_index_from(&bm25_path).unwrap_or_default();
+    let cutoff = chrono::Utc::now() - chrono::Duration::days(crate::background::LOG_RETENTION_DAYS);
+    let (on_disk, recent_on_disk) = interaction_doc_counts(&interactions_dir, cutoff);
+    let bm25 = CountDrift::pruned(
+        bm25_index.doc_count as usize,
+        on_disk,
+        recent_on_disk,
+        crate::retrieval::BM25_MAX_DOCS,
+    );
+    if !bm25.is_ok() {
+        problems.push(format!(
+            "BM25 index has {} documents but the interaction logs have {} (at least {} expected; rebuild it)",
+            bm25.indexed, bm25.on_disk, bm25.expected_min
+        ));
+    }
+
+    let memories_dir = data_dir.join("memories");
+    let topics_dir = memories_dir.join("topics");
+    let topic_index: TopicIndex = read_index(&topics_dir.join("index.json")).unwrap_or_else(|e| {
+        problems.push(e);
+        TopicIndex::default()
+    });
+    let topics = index_file_drift(topic_index.topics.keys(), &topic_index.filenames, &md_file_stems(&topics_dir));
+    describe_index_drift("Topic", &topics, &mut problems);
+
+    let insights_dir = memories_dir.join("insights");
+    let insight_index: InsightIndex = read_index(&insights_dir.join("index.json")).unwrap_or_else(|e| {
+        problems.push(e);
+        InsightIndex::default()
+    });
+    let insights = index_file_drift(
+        insight_index.insights.keys(),
+        &insight_index.filenames,
+        &md_file_stems(&insights_dir),
+    );
+    describe_index_drift("Insight", &insights, &mut problems);
+
+    let memory_tokens = match crate::memories::load_memories_in_dir(&memories_dir) {
+        Ok(store) => store.total_tokens(),
+        Err(e) => {
+            problems.push(e);
+            0
+        }
+    };
+    let memory_token_budget = crate::memories::TOKEN_BUDGET;
+    if memory_tokens > memory_token_budget {
+        problems.push(format!(
+            "Memories use {} tokens, over the {} token budget",
+            memory_tokens, memory_token_budget
+        ));
+    }
+
+    let api_keys = ApiKeyStatus::from_config(config);
+    if !api_keys.embeddings {
+        problems.push("No API key for the embeddings provider; RAG is disabled".to_string());
+    }
+
+    HealthReport {
+        chat_history_ok,
+        bm25,
+        topics,
+        insights,
+        memory_tokens,
+        memory_token_budget,
+        api_keys,
+        problems,
+    }
+}
+
+pub fn healthcheck<R: Runtime>(app_handle: &AppHandle<R>) -> Result<HealthReport, String> {
+    let data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data dir: {}", e))?;
+    let config = crate::config::load_config(app_handle)?;
+    Ok(healthcheck_in_dir(&data_dir, &config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_drift_sign() {
+        assert_eq!(CountDrift::new(10, 10).drift, 0);
+        assert_eq!(CountDrift::new(8, 10).drift, -2);
+        assert_eq!(CountDrift::new(12, 10).drift, 2);
+    }
+
+    #[test]
+    fn test_pruned_count_drift_allows_retention_and_cap() {
+        // 15k docs on disk, 12k of them recent: a pruned index holds the newest 10k
+        assert!(CountDrift::pruned(10_000, 15_000, 12_000, 10_000).is_ok());
+        // Not yet pruned: old docs still indexed
+        assert!(CountDrift::pruned(15_000, 15_000, 12_000, 10_000).is_ok());
+        // Missing recent docs, or more docs than the logs have
+        assert!(!CountDrift::pruned(9_000, 15_000, 12_000, 10_000).is_ok());
+        assert!(!CountDrift::pruned(15_001, 15_000, 12_000, 10_000).is_ok());
+        // Old logs past the retention window don't count as missing
+        assert!(CountDrift::pruned(5, 20, 5, 10_000).is_ok());
+    }
+
+    #[test]
+    fn test_index_file_drift_follows_name_mapping() {
+        let names: Vec<String> = vec!["C++".to_string(), "rust".to_string(), "gone".to_string()];
+        let mut filenames = HashMap::new();
+        filenames.insert("C++".to_string(), "C__".to_string());
+        let stems: BTreeSet<String> = ["C__", "rust", "orphan"].iter().map(|s| s.to_string()).collect();
+
+        let drift = index_file_drift(names.iter(), &filenames, &stems);
+        assert_eq!(drift.missing_files, vec!["gone"]);
+        assert_eq!(drift.unindexed_files, vec!["orphan"]);
+        assert!(!drift.is_ok());
+
+        let stems: BTreeSet<String> = ["C__", "rust"].iter().map(|s| s.to_string()).collect();
+        assert!(index_file_drift(names[..2].iter(), &filenames, &stems).is_ok());
+    }
+
+    #[test]
+    fn test_healthcheck_reports_bm25_drift() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let interactions = dir.path().join("interactions");
+        fs::create_dir_all(&interactions).unwrap();
+        // Three recent lines, two unique timestamps: a rebuild would index two documents
+        let hello = chrono::Utc::now() - chrono::Duration::hours(1);
+        let reply = hello + chrono::Duration::seconds(5);
+        let line = |ts: chrono::DateTime<chrono::Utc>, role: &str, content: &str| {
+            serde_json::json!({"ts": ts, "role": role, "content": content}).to_string() + "\n"
+        };
+        fs::write(
+            interactions.join(format!("interactions-{}.jsonl", hello.format("%Y-%m-%d"))),
+            [line(hello, "user", "hello"), line(reply, "model", "hi there"), line(reply, "model", "hi there")].concat(),
+        )
+        .unwrap();
+        let mut index = crate::retrieval::BM25Index::new();
+        index.add_document(&hello.to_rfc3339(), "hello", Some(hello));
+        crate::retrieval::save_bm25_index_to(&interactions.join(crate::retrieval::BM25_INDEX_FILENAME), &index)
+            .unwrap();
+
+        let report = healthcheck_in_dir(dir.path(), &AppConfig::default());
+        assert!(report.chat_history_ok);
+        assert_eq!(report.bm25, CountDrift::new(1, 2));
+        assert!(report.problems.iter().any(|p| p.starts_with("BM25 index has 1 documents")));
+        assert!(!report.api_keys.gemini);
+    }
+}
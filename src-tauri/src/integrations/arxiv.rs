@@ -2,6 +2,8 @@ use reqwest;
 use serde::{Deserialize, Serialize};
 use log;
 use regex::Regex;
+use std::future::Future;
+use super::{request_error, tool_timeout};
 
 // ArXiv Atom XML Structs (Ported from legacy)
 #[derive(Debug, Deserialize)]
@@ -54,26 +56,90 @@ pub struct ArxivPaperSummary {
     pub pdf_url: String,
 }
 
+/// Optional filters for `search_arxiv`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ArxivSearchOptions {
+    /// arXiv category, e.g. "cs.LG" or "quant-ph"
+    pub category: Option<String>,
+    /// Drop papers first published more than this many days ago
+    pub max_age_days: Option<u32>,
+}
+
+/// Whether `category` looks like an arXiv category ("cs.LG", "hep-th", "math.AG")
+fn is_valid_category(category: &str) -> bool {
+    let mut parts = category.split('.');
+    let archive_ok = parts
+        .next()
+        .is_some_and(|a| !a.is_empty() && a.chars().all(|c| c.is_ascii_alphabetic() || c == '-'));
+    let subject_ok = parts.next().is_none_or(|s| !s.is_empty() && s.chars().all(|c| c.is_ascii_alphabetic()));
+    archive_ok && subject_ok && parts.next().is_none()
+}
+
+/// The API's `search_query`. Without a category the query passes through unchanged; with
+/// one, each word becomes an `all:` term ANDed with a `cat:` scope.
+pub fn build_arxiv_search_query(query: &str, category: Option<&str>) -> Result<String, String> {
+    let query = query.trim();
+    let Some(category) = category.map(str::trim).filter(|c| !c.is_empty()) else {
+        return Ok(query.to_string());
+    };
+    if !is_valid_category(category) {
+        return Err(format!("Invalid arXiv category: {}", category));
+    }
+
+    let mut terms = vec![format!("cat:{}", category)];
+    terms.extend(query.split_whitespace().map(|word| format!("all:{}", word)));
+    Ok(terms.join(" AND "))
+}
+
+/// Keep papers published within `max_age_days` of `now`; papers without a parseable date are dropped
+pub fn filter_by_age(
+    papers: Vec<ArxivPaperSummary>,
+    max_age_days: u32,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Vec<ArxivPaperSummary> {
+    let cutoff = now - chrono::Duration::days(max_age_days as i64);
+    papers
+        .into_iter()
+        .filter(|paper| {
+            paper
+                .published_date
+                .as_deref()
+                .and_then(|date| chrono::DateTime::parse_from_rfc3339(date.trim()).ok())
+                .is_some_and(|published| published >= cutoff)
+        })
+        .collect()
+}
+
 pub async fn perform_arxiv_lookup(
     client: &reqwest::Client,
     query: &str,
     max_results: usize,
+    options: &ArxivSearchOptions,
 ) -> Result<Vec<ArxivPaperSummary>, String> {
     let base_url = "http://export.arxiv.org/api/query";
-    let params = [
-        ("search_query", query),
+    let search_query = build_arxiv_search_query(query, options.category.as_deref())?;
+    let max_results = max_results.to_string();
+    let mut params = vec![
+        ("search_query", search_query.as_str()),
         ("start", "0"),
-        ("max_results", &max_results.to_string()),
+        ("max_results", max_results.as_str()),
     ];
+    // Newest first, otherwise the date filter would mostly discard relevance-ranked old papers
+    if options.max_age_days.is_some() {
+        params.push(("sortBy", "submittedDate"));
+        params.push(("sortOrder", "descending"));
+    }
 
-    log::info!("Performing ArXiv lookup for: {}", query);
+    log::info!("Performing ArXiv lookup for: {}", search_query);
 
+    let timeout = tool_timeout("search_arxiv");
     let response = client
         .get(base_url)
         .query(&params)
+        .timeout(timeout)
         .send()
         .await
-        .map_err(|e| format!("ArXiv network error: {}", e))?;
+        .map_err(|e| request_error("ArXiv", &e, timeout))?;
 
     if !response.status().is_success() {
         return Err(format!("ArXiv API error: {}", response.status()));
@@ -84,8 +150,16 @@ pub async fn perform_arxiv_lookup(
         .await
         .map_err(|e| format!("ArXiv read error: {}", e))?;
 
-    // Parse XML
-    let feed: ArxivFeed = quick_xml::de::from_str(&response_text)
+    let summaries = parse_arxiv_feed(&response_text)?;
+    Ok(match options.max_age_days {
+        Some(days) => filter_by_age(summaries, days, chrono::Utc::now()),
+        None => summaries,
+    })
+}
+
+/// Paper summaries from an arXiv Atom response
+pub fn parse_arxiv_feed(xml: &str) -> Result<Vec<ArxivPaperSummary>, String> {
+    let feed: ArxivFeed = quick_xml::de::from_str(xml)
         .map_err(|e| format!("ArXiv XML parse error: {}", e))?;
 
     let mut summaries = Vec::new();
@@ -168,41 +242,71 @@ pub fn extract_arxiv_id(input: &str) -> Option<String> {
     None
 }
 
+/// Progress stages reported while reading a paper
+pub const STAGE_FETCHING: &str = "Fetching paper…";
+pub const STAGE_PARSING: &str = "Parsing HTML…";
+pub const STAGE_EXTRACTING: &str = "Extracting text…";
+
 /// Read full paper content from ar5iv (ArXiv HTML version)
 pub async fn read_arxiv_paper(
     client: &reqwest::Client,
     paper_id_or_url: &str,
+    progress: &(dyn Fn(&str) + Send + Sync),
 ) -> Result<ArxivPaperContent, String> {
+    read_arxiv_paper_with(paper_id_or_url, |id| fetch_ar5iv_html(client, id), progress).await
+}
+
+/// Fetch -> parse -> extract pipeline with the fetch injected so it can be mocked.
+/// `progress` is called with each stage before it starts.
+pub async fn read_arxiv_paper_with<FetchFut>(
+    paper_id_or_url: &str,
+    fetch: impl FnOnce(String) -> FetchFut,
+    progress: &(dyn Fn(&str) + Send + Sync),
+) -> Result<ArxivPaperContent, String>
+where
+    FetchFut: Future<Output = Result<String, String>>,
+{
     let id = extract_arxiv_id(paper_id_or_url)
         .ok_or_else(|| format!("Could not extract ArXiv ID from: {}", paper_id_or_url))?;
 
+    progress(STAGE_FETCHING);
+    let html = fetch(id.clone()).await?;
+
+    progress(STAGE_PARSING);
+    let document = scraper::Html::parse_document(&html);
+
+    progress(STAGE_EXTRACTING);
+    let (title, abstract_text, content) = parse_arxiv_html(&document, &id);
+
+    Ok(ArxivPaperContent {
+        id,
+        title,
+        abstract_text,
+        content,
+    })
+}
+
+async fn fetch_ar5iv_html(client: &reqwest::Client, id: String) -> Result<String, String> {
     let url = format!("https://ar5iv.labs.arxiv.org/html/{}", id);
     log::info!("Fetching ArXiv paper from ar5iv: {}", url);
 
+    let timeout = tool_timeout("read_arxiv_paper");
     let response = client
         .get(&url)
         .header("User-Agent", "Mozilla/5.0 (compatible; Shard/1.0)")
+        .timeout(timeout)
         .send()
         .await
-        .map_err(|e| format!("ar5iv network error: {}", e))?;
+        .map_err(|e| request_error("ar5iv", &e, timeout))?;
 
     if !response.status().is_success() {
         return Err(format!("ar5iv error: {} for paper {}", response.status(), id));
     }
 
-    let html = response
+    response
         .text()
         .await
-        .map_err(|e| format!("ar5iv read error: {}", e))?;
-
-    let (title, abstract_text, content) = parse_arxiv_html(&html, &id);
-
-    Ok(ArxivPaperContent {
-        id,
-        title,
-        abstract_text,
-        content,
-    })
+        .map_err(|e| format!("ar5iv read error: {}", e))
 }
 
 /// Helper to extract clean text, filtering out MathML annotations
@@ -282,9 +386,7 @@ fn clean_text(element: scraper::ElementRef) -> String {
 }
 
 /// Parse ArXiv HTML content using an allowlist strategy
-fn parse_arxiv_html(html: &str, id: &str) -> (String, String, String) {
-    let document = scraper::Html::parse_document(html);
-
+fn parse_arxiv_html(document: &scraper::Html, id: &str) -> (String, String, String) {
     // Extract title
     let title_selector = scraper::Selector::parse("h1.ltx_title_document").unwrap();
     let title = document
@@ -427,6 +529,40 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_build_arxiv_search_query_scopes_category() {
+        assert_eq!(
+            build_arxiv_search_query(" reinforcement learning ", None).unwrap(),
+            "reinforcement learning"
+        );
+        assert_eq!(
+            build_arxiv_search_query("reinforcement learning", Some("cs.LG")).unwrap(),
+            "cat:cs.LG AND all:reinforcement AND all:learning"
+        );
+        assert_eq!(build_arxiv_search_query("", Some("quant-ph")).unwrap(), "cat:quant-ph");
+        assert_eq!(build_arxiv_search_query("qubits", Some("  ")).unwrap(), "qubits");
+        assert!(build_arxiv_search_query("x", Some("cs.LG OR all:y")).is_err());
+        assert!(build_arxiv_search_query("x", Some("cs.")).is_err());
+    }
+
+    #[test]
+    fn test_filter_by_age_drops_old_and_undated_papers() {
+        let feed = r#"<feed xmlns="http://www.w3.org/2005/Atom">
+            <entry><id>http://arxiv.org/abs/2501.00001v1</id><published>2025-01-10T12:00:00Z</published><title>Recent</title><summary>New</summary></entry>
+            <entry><id>http://arxiv.org/abs/2301.00002v1</id><published>2023-01-10T12:00:00Z</published><title>Old</title><summary>Stale</summary></entry>
+            <entry><id>http://arxiv.org/abs/2501.00003v1</id><title>Undated</title><summary>?</summary></entry>
+        </feed>"#;
+        let papers = parse_arxiv_feed(feed).unwrap();
+        assert_eq!(papers.len(), 3);
+
+        let now = chrono::DateTime::parse_from_rfc3339("2025-01-20T00:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let recent = filter_by_age(papers, 30, now);
+        let titles: Vec<&str> = recent.iter().map(|p| p.title.as_str()).collect();
+        assert_eq!(titles, vec!["Recent"]);
+    }
+
     #[test]
     fn test_extract_arxiv_id_invalid() {
         assert_eq!(extract_arxiv_id("not a valid id"), None);
@@ -463,7 +599,8 @@ mod tests {
         </html>
         "#;
 
-        let (title, abstract_text, content) = parse_arxiv_html(html, "test_id");
+        let (title, abstract_text, content) =
+            parse_arxiv_html(&scraper::Html::parse_document(html), "test_id");
 
         assert_eq!(title, "Test Paper Title");
         assert_eq!(abstract_text, "This is the abstract.");
@@ -501,4 +638,45 @@ mod tests {
         assert!(!cleaned.contains("NodeId("));
         assert!(!cleaned.contains("Element(<mi>)"));
     }
+
+    #[tokio::test]
+    async fn test_read_arxiv_paper_reports_progress_stages() {
+        let stages = std::sync::Mutex::new(Vec::new());
+        let html = r#"<html><body>
+            <h1 class="ltx_title_document">Mocked Paper</h1>
+            <div class="ltx_abstract"><p class="ltx_p">Short abstract.</p></div>
+        </body></html>"#;
+
+        let paper = read_arxiv_paper_with(
+            "https://arxiv.org/abs/2401.12345v2",
+            |id| async move {
+                assert_eq!(id, "2401.12345");
+                Ok(html.to_string())
+            },
+            &|stage| stages.lock().unwrap().push(stage.to_string()),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(paper.title, "Mocked Paper");
+        assert_eq!(
+            *stages.lock().unwrap(),
+            vec![STAGE_FETCHING, STAGE_PARSING, STAGE_EXTRACTING]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_arxiv_paper_stops_reporting_after_fetch_failure() {
+        let stages = std::sync::Mutex::new(Vec::new());
+
+        let result = read_arxiv_paper_with(
+            "2401.12345",
+            |_id| async { Err("ar5iv error: 404 Not Found for paper 2401.12345".to_string()) },
+            &|stage| stages.lock().unwrap().push(stage.to_string()),
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(*stages.lock().unwrap(), vec![STAGE_FETCHING]);
+    }
 }
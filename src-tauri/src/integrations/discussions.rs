@@ -0,0 +1,228 @@
+use log;
+use reqwest;
+use serde::{Deserialize, Serialize};
+use super::{request_error, tool_timeout};
+
+/// Max threads returned per search
+const MAX_THREADS: usize = 5;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DiscussionThread {
+    pub title: String,
+    pub url: String,
+    pub score: i64,
+    pub num_comments: u64,
+}
+
+/// HN Algolia search response structures
+#[derive(Debug, Deserialize)]
+struct HackerNewsResponse {
+    hits: Vec<HackerNewsHit>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HackerNewsHit {
+    #[serde(rename = "objectID")]
+    object_id: String,
+    title: Option<String>,
+    points: Option<i64>,
+    num_comments: Option<u64>,
+}
+
+/// Reddit JSON search response structures
+#[derive(Debug, Deserialize)]
+struct RedditResponse {
+    data: RedditListing,
+}
+
+#[derive(Debug, Deserialize)]
+struct RedditListing {
+    children: Vec<RedditChild>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RedditChild {
+    data: RedditPost,
+}
+
+#[derive(Debug, Deserialize)]
+struct RedditPost {
+    title: String,
+    permalink: String,
+    subreddit: Option<String>,
+    score: Option<i64>,
+    num_comments: Option<u64>,
+}
+
+/// Search community discussions on Hacker News or Reddit
+/// source: "hackernews" (default) or "reddit"
+pub async fn perform_discussion_search(
+    client: &reqwest::Client,
+    query: &str,
+    source: &str,
+) -> Result<Vec<DiscussionThread>, String> {
+    log::info!("Searching {} discussions for: {}", source, query);
+
+    match source {
+        "reddit" => search_reddit(client, query).await,
+        _ => search_hackernews(client, query).await,
+    }
+}
+
+async fn search_hackernews(client: &reqwest::Client, query: &str) -> Result<Vec<DiscussionThread>, String> {
+    let url = format!(
+        "https://hn.algolia.com/api/v1/search?query={}&tags=story&hitsPerPage={}",
+        urlencoding::encode(query),
+        MAX_THREADS
+    );
+
+    let timeout = tool_timeout("search_discussions");
+    let response = client
+        .get(&url)
+        .timeout(timeout)
+        .send()
+        .await
+        .map_err(|e| request_error("Hacker News search", &e, timeout))?;
+
+    if !response.status().is_success() {
+        return Err(format!("Hacker News search API error: {}", response.status()));
+    }
+
+    let body = response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read Hacker News response: {}", e))?;
+
+    parse_hackernews_response(&body)
+}
+
+async fn search_reddit(client: &reqwest::Client, query: &str) -> Result<Vec<DiscussionThread>, String> {
+    let url = format!(
+        "https://www.reddit.com/search.json?q={}&limit={}&sort=relevance&type=link",
+        urlencoding::encode(query),
+        MAX_THREADS
+    );
+
+    // Reddit rejects requests without a descriptive User-Agent
+    let timeout = tool_timeout("search_discussions");
+    let response = client
+        .get(&url)
+        .header("User-Agent", "shard-desktop/0.1 (discussion search)")
+        .timeout(timeout)
+        .send()
+        .await
+        .map_err(|e| request_error("Reddit search", &e, timeout))?;
+
+    if !response.status().is_success() {
+        return Err(format!("Reddit search API error: {}", response.status()));
+    }
+
+    let body = response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read Reddit response: {}", e))?;
+
+    parse_reddit_response(&body)
+}
+
+/// Parse an HN Algolia search response into threads
+pub fn parse_hackernews_response(body: &str) -> Result<Vec<DiscussionThread>, String> {
+    let parsed: HackerNewsResponse = serde_json::from_str(body)
+        .map_err(|e| format!("Failed to parse Hacker News response: {}", e))?;
+
+    Ok(parsed
+        .hits
+        .into_iter()
+        .filter_map(|hit| {
+            let title = hit.title.filter(|t| !t.is_empty())?;
+            Some(DiscussionThread {
+                title,
+                // Link to the comment thread rather than the submitted article
+                url: format!("https://news.ycombinator.com/item?id={}", hit.object_id),
+                score: hit.points.unwrap_or(0),
+                num_comments: hit.num_comments.unwrap_or(0),
+            })
+        })
+        .take(MAX_THREADS)
+        .collect())
+}
+
+/// Parse a Reddit search listing into threads
+pub fn parse_reddit_response(body: &str) -> Result<Vec<DiscussionThread>, String> {
+    let parsed: RedditResponse = serde_json::from_str(body)
+        .map_err(|e| format!("Failed to parse Reddit response: {}", e))?;
+
+    Ok(parsed
+        .data
+        .children
+        .into_iter()
+        .map(|child| {
+            let post = child.data;
+            let title = match post.subreddit {
+                Some(sub) => format!("{} (r/{})", post.title, sub),
+                None => post.title,
+            };
+            DiscussionThread {
+                title,
+                url: format!("https://www.reddit.com{}", post.permalink),
+                score: post.score.unwrap_or(0),
+                num_comments: post.num_comments.unwrap_or(0),
+            }
+        })
+        .take(MAX_THREADS)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HN_FIXTURE: &str = r#"{
+        "hits": [
+            {"objectID": "38000001", "title": "Show HN: A Rust BM25 library", "url": "https://github.com/x/bm25", "points": 245, "num_comments": 87},
+            {"objectID": "38000002", "title": null, "points": 3, "num_comments": 0},
+            {"objectID": "38000003", "title": "Ask HN: Tantivy vs Meilisearch?", "url": null, "points": null, "num_comments": 12}
+        ],
+        "nbHits": 3
+    }"#;
+
+    const REDDIT_FIXTURE: &str = r#"{
+        "kind": "Listing",
+        "data": {
+            "children": [
+                {"kind": "t3", "data": {"title": "Best way to do hybrid search?", "permalink": "/r/rust/comments/abc123/best_way/", "subreddit": "rust", "score": 132, "num_comments": 41}},
+                {"kind": "t3", "data": {"title": "RRF explained", "permalink": "/r/MachineLearning/comments/def456/rrf/", "subreddit": "MachineLearning", "score": 58, "num_comments": 9}}
+            ]
+        }
+    }"#;
+
+    #[test]
+    fn test_parse_hackernews_fixture() {
+        let threads = parse_hackernews_response(HN_FIXTURE).unwrap();
+        assert_eq!(threads.len(), 2, "Untitled hits should be skipped");
+        assert_eq!(threads[0].title, "Show HN: A Rust BM25 library");
+        assert_eq!(threads[0].url, "https://news.ycombinator.com/item?id=38000001");
+        assert_eq!(threads[0].score, 245);
+        assert_eq!(threads[0].num_comments, 87);
+        assert_eq!(threads[1].score, 0);
+    }
+
+    #[test]
+    fn test_parse_reddit_fixture() {
+        let threads = parse_reddit_response(REDDIT_FIXTURE).unwrap();
+        assert_eq!(threads.len(), 2);
+        assert_eq!(threads[0].title, "Best way to do hybrid search? (r/rust)");
+        assert_eq!(
+            threads[0].url,
+            "https://www.reddit.com/r/rust/comments/abc123/best_way/"
+        );
+        assert_eq!(threads[0].score, 132);
+        assert_eq!(threads[1].num_comments, 9);
+    }
+
+    #[test]
+    fn test_parse_invalid_json() {
+        assert!(parse_hackernews_response("not json").is_err());
+        assert!(parse_reddit_response("{}").is_err());
+    }
+}
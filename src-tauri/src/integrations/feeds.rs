@@ -0,0 +1,286 @@
+use log;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use regex::Regex;
+use reqwest;
+use serde::{Deserialize, Serialize};
+use super::{request_error, tool_timeout};
+
+/// Items returned when the caller doesn't pass a limit
+pub const DEFAULT_FEED_ITEMS: usize = 5;
+/// Upper bound on items returned per feed
+pub const MAX_FEED_ITEMS: usize = 20;
+/// Summaries are trimmed to keep tool output compact
+const MAX_SUMMARY_CHARS: usize = 300;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct FeedItem {
+    pub title: String,
+    pub link: String,
+    pub published: Option<String>,
+    pub summary: String,
+}
+
+/// Fetch an RSS 2.0 or Atom feed and return the latest `limit` items
+pub async fn read_feed(
+    client: &reqwest::Client,
+    url: &str,
+    limit: usize,
+) -> Result<Vec<FeedItem>, String> {
+    log::info!("Reading feed: {}", url);
+
+    let timeout = tool_timeout("read_feed");
+    let response = client
+        .get(url)
+        .header("User-Agent", "shard-v2/0.1")
+        .timeout(timeout)
+        .send()
+        .await
+        .map_err(|e| request_error("Feed", &e, timeout))?;
+
+    if !response.status().is_success() {
+        return Err(format!("Feed request failed: {}", response.status()));
+    }
+
+    let body = response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read feed body: {}", e))?;
+
+    let mut items = parse_feed(&body)?;
+    items.truncate(limit.clamp(1, MAX_FEED_ITEMS));
+    Ok(items)
+}
+
+/// Fields collected while inside an `<item>` / `<entry>`
+#[derive(Default)]
+struct PartialItem {
+    title: Option<String>,
+    link: Option<String>,
+    published: Option<String>,
+    summary: Option<String>,
+    content: Option<String>,
+}
+
+impl PartialItem {
+    fn finish(self) -> FeedItem {
+        let summary = self.summary.or(self.content).unwrap_or_default();
+        FeedItem {
+            title: self.title.unwrap_or_else(|| "Untitled".to_string()),
+            link: self.link.unwrap_or_default(),
+            published: self.published,
+            summary: clean_summary(&summary),
+        }
+    }
+}
+
+fn local_name(e: &BytesStart) -> String {
+    String::from_utf8_lossy(e.local_name().as_ref()).to_string()
+}
+
+/// Atom links carry the URL in `href`; only take alternate (or unlabelled) links
+fn atom_link_href(e: &BytesStart) -> Option<String> {
+    let mut href = None;
+    let mut rel = None;
+    for attr in e.attributes().flatten() {
+        let value = attr.unescape_value().ok()?.to_string();
+        match attr.key.local_name().as_ref() {
+            b"href" => href = Some(value),
+            b"rel" => rel = Some(value),
+            _ => {}
+        }
+    }
+    match rel.as_deref() {
+        None | Some("alternate") => href,
+        _ => None,
+    }
+}
+
+/// Parse RSS 2.0 `<item>` or Atom `<entry>` elements into feed items
+pub fn parse_feed(xml: &str) -> Result<Vec<FeedItem>, String> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut items = Vec::new();
+    let mut current: Option<PartialItem> = None;
+    // Depth relative to the open item; fields are direct children (depth 1)
+    let mut depth = 0usize;
+    let mut field: Option<String> = None;
+    let mut text = String::new();
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => {
+                let name = local_name(&e);
+                if current.is_none() {
+                    if name == "item" || name == "entry" {
+                        current = Some(PartialItem::default());
+                        depth = 0;
+                    }
+                    continue;
+                }
+                depth += 1;
+                if depth == 1 {
+                    if name == "link" {
+                        if let (Some(item), Some(href)) = (current.as_mut(), atom_link_href(&e)) {
+                            item.link.get_or_insert(href);
+                        }
+                    }
+                    field = Some(name);
+                    text.clear();
+                }
+            }
+            Ok(Event::Empty(e)) => {
+                if depth == 0 && local_name(&e) == "link" {
+                    if let (Some(item), Some(href)) = (current.as_mut(), atom_link_href(&e)) {
+                        item.link.get_or_insert(href);
+                    }
+                }
+            }
+            Ok(Event::Text(t)) => {
+                if field.is_some() {
+                    let value = t.unescape().map_err(|e| format!("Invalid feed text: {}", e))?;
+                    text.push_str(&value);
+                }
+            }
+            Ok(Event::CData(c)) => {
+                if field.is_some() {
+                    text.push_str(&String::from_utf8_lossy(&c.into_inner()));
+                }
+            }
+            Ok(Event::End(e)) => {
+                let Some(item) = current.as_mut() else {
+                    continue;
+                };
+                if depth == 0 {
+                    let name = String::from_utf8_lossy(e.local_name().as_ref()).to_string();
+                    if name == "item" || name == "entry" {
+                        if let Some(done) = current.take() {
+                            items.push(done.finish());
+                        }
+                    }
+                    continue;
+                }
+                if depth == 1 {
+                    if let Some(name) = field.take() {
+                        let value = text.trim().to_string();
+                        if !value.is_empty() {
+                            match name.as_str() {
+                                "title" => item.title = Some(value),
+                                "link" => {
+                                    item.link.get_or_insert(value);
+                                }
+                                "pubDate" | "published" | "updated" | "date" => {
+                                    item.published.get_or_insert(value);
+                                }
+                                "description" | "summary" => item.summary = Some(value),
+                                "encoded" | "content" => item.content = Some(value),
+                                _ => {}
+                            }
+                        }
+                    }
+                }
+                depth -= 1;
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(format!("Failed to parse feed XML: {}", e)),
+            _ => {}
+        }
+    }
+
+    Ok(items)
+}
+
+/// Strip HTML tags, collapse whitespace, and trim to MAX_SUMMARY_CHARS
+fn clean_summary(raw: &str) -> String {
+    let tag_re = Regex::new(r"<[^>]*>").unwrap();
+    let stripped = tag_re.replace_all(raw, " ");
+    let collapsed = stripped.split_whitespace().collect::<Vec<_>>().join(" ");
+    if collapsed.chars().count() > MAX_SUMMARY_CHARS {
+        let truncated: String = collapsed.chars().take(MAX_SUMMARY_CHARS).collect();
+        format!("{}...", truncated.trim_end())
+    } else {
+        collapsed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RSS_FIXTURE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:content="http://purl.org/rss/1.0/modules/content/">
+  <channel>
+    <title>Example Blog</title>
+    <link>https://example.com</link>
+    <item>
+      <title>Release 1.2 &amp; notes</title>
+      <link>https://example.com/posts/1-2</link>
+      <pubDate>Mon, 06 Jan 2025 10:00:00 GMT</pubDate>
+      <description><![CDATA[<p>New <b>features</b> shipped.</p>]]></description>
+    </item>
+    <item>
+      <title>Older post</title>
+      <link>https://example.com/posts/old</link>
+      <content:encoded><![CDATA[<div>Full body only</div>]]></content:encoded>
+    </item>
+  </channel>
+</rss>"#;
+
+    const ATOM_FIXTURE: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>Example Atom</title>
+  <link href="https://example.org/"/>
+  <entry>
+    <title type="html">Atom entry one</title>
+    <link rel="self" href="https://example.org/feed/1"/>
+    <link rel="alternate" href="https://example.org/entries/1"/>
+    <id>urn:uuid:1</id>
+    <updated>2025-02-01T12:00:00Z</updated>
+    <author><name>Jane</name></author>
+    <summary>Short summary</summary>
+  </entry>
+  <entry>
+    <title>Atom entry two</title>
+    <link href="https://example.org/entries/2"/>
+    <published>2025-01-15T08:30:00Z</published>
+    <content type="html">&lt;p&gt;Escaped HTML body&lt;/p&gt;</content>
+  </entry>
+</feed>"#;
+
+    #[test]
+    fn test_parse_rss_feed() {
+        let items = parse_feed(RSS_FIXTURE).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].title, "Release 1.2 & notes");
+        assert_eq!(items[0].link, "https://example.com/posts/1-2");
+        assert_eq!(items[0].published.as_deref(), Some("Mon, 06 Jan 2025 10:00:00 GMT"));
+        assert_eq!(items[0].summary, "New features shipped.");
+        // Falls back to content:encoded when there's no description
+        assert_eq!(items[1].summary, "Full body only");
+        assert_eq!(items[1].published, None);
+    }
+
+    #[test]
+    fn test_parse_atom_feed() {
+        let items = parse_feed(ATOM_FIXTURE).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].title, "Atom entry one");
+        // rel="self" is skipped in favour of the alternate link
+        assert_eq!(items[0].link, "https://example.org/entries/1");
+        assert_eq!(items[0].published.as_deref(), Some("2025-02-01T12:00:00Z"));
+        assert_eq!(items[0].summary, "Short summary");
+        // Nested <author><name> must not leak into the title
+        assert_eq!(items[1].title, "Atom entry two");
+        assert_eq!(items[1].link, "https://example.org/entries/2");
+        assert_eq!(items[1].summary, "Escaped HTML body");
+    }
+
+    #[test]
+    fn test_clean_summary_truncates() {
+        let long = "word ".repeat(200);
+        let cleaned = clean_summary(&long);
+        assert!(cleaned.ends_with("..."));
+        assert!(cleaned.chars().count() <= MAX_SUMMARY_CHARS + 3);
+    }
+}
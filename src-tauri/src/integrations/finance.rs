@@ -1,19 +1,136 @@
 use yahoo_finance_api as yfa;
 use time::OffsetDateTime;
 use log;
+use super::tool_timeout;
 
+/// Exchanges treated as a company's primary US listing when resolving names
+const US_EXCHANGES: &[&str] = &["NMS", "NYQ", "NGM", "NCM", "ASE", "PCX", "BTS"];
 
-pub async fn perform_finance_lookup(ticker: &str) -> Result<String, String> {
-    log::info!("Performing Finance lookup for: {}", ticker);
+/// Symbol-search hit reduced to the fields used for picking a ticker
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolCandidate {
+    pub symbol: String,
+    pub name: String,
+    pub quote_type: String,
+    pub exchange: String,
+}
+
+/// Whether the input can be used as a ticker directly, or needs a name search.
+/// Accepts e.g. `AAPL`, `msft`, `BRK.B`, `RY.TO`, `BTC-USD`, `^GSPC`.
+/// Lowercase words of 5+ letters ("apple", "nvidia") are treated as names.
+pub fn looks_like_ticker(input: &str) -> bool {
+    let input = input.trim();
+    if input.is_empty() || input.len() > 12 || input.contains(char::is_whitespace) {
+        return false;
+    }
+
+    let body = input.strip_prefix('^').unwrap_or(input);
+    let (base, suffix) = match body.split_once(['.', '-', '=']) {
+        Some((base, suffix)) => (base, Some(suffix)),
+        None => (body, None),
+    };
+
+    let base_ok = !base.is_empty()
+        && base.len() <= 6
+        && base.chars().all(|c| c.is_ascii_alphanumeric())
+        && base.chars().any(|c| c.is_ascii_alphabetic());
+    let suffix_ok = match suffix {
+        Some(s) => !s.is_empty() && s.len() <= 4 && s.chars().all(|c| c.is_ascii_alphanumeric()),
+        None => true,
+    };
+    if !base_ok || !suffix_ok {
+        return false;
+    }
+
+    // Plain lowercase words are ambiguous: short ones ("aapl") are tickers, longer ones names
+    let is_upper = body.chars().filter(|c| c.is_ascii_alphabetic()).all(|c| c.is_ascii_uppercase());
+    is_upper || suffix.is_some() || input.starts_with('^') || base.len() <= 4
+}
+
+/// Pick the primary ticker from search results:
+/// US-listed equity, then any equity, then ETF/index/crypto, then the top hit
+pub fn pick_primary_symbol(candidates: &[SymbolCandidate]) -> Option<&SymbolCandidate> {
+    let is_equity = |c: &&SymbolCandidate| c.quote_type.eq_ignore_ascii_case("EQUITY");
+    candidates
+        .iter()
+        .filter(is_equity)
+        .find(|c| US_EXCHANGES.contains(&c.exchange.as_str()))
+        .or_else(|| candidates.iter().find(is_equity))
+        .or_else(|| {
+            candidates.iter().find(|c| {
+                matches!(c.quote_type.to_uppercase().as_str(), "ETF" | "INDEX" | "CRYPTOCURRENCY" | "MUTUALFUND")
+            })
+        })
+        .or_else(|| candidates.first())
+}
+
+/// Map a company name to its primary ticker via Yahoo symbol search
+async fn resolve_symbol(
+    provider: &yfa::YahooConnector,
+    query: &str,
+) -> Result<SymbolCandidate, String> {
+    let results = provider
+        .search_ticker(query)
+        .await
+        .map_err(|e| format!("Yahoo symbol search error: {}", e))?;
+
+    let candidates: Vec<SymbolCandidate> = results
+        .quotes
+        .into_iter()
+        .map(|q| SymbolCandidate {
+            name: if q.long_name.is_empty() { q.short_name } else { q.long_name },
+            symbol: q.symbol,
+            quote_type: q.quote_type,
+            exchange: q.exchange,
+        })
+        .collect();
+
+    pick_primary_symbol(&candidates)
+        .cloned()
+        .ok_or_else(|| format!("No ticker found for '{}'", query))
+}
+
+/// Look up the latest quote for a ticker or company name
+pub async fn perform_finance_lookup(query: &str) -> Result<String, String> {
+    // The Yahoo connector owns its client, so bound the whole lookup instead of each request
+    let timeout = tool_timeout("get_stock_price");
+    tokio::time::timeout(timeout, finance_lookup(query))
+        .await
+        .map_err(|_| format!("Yahoo Finance request timed out after {}s", timeout.as_secs()))?
+}
+
+async fn finance_lookup(query: &str) -> Result<String, String> {
+    log::info!("Performing Finance lookup for: {}", query);
+    let query = query.trim();
 
     let provider = yfa::YahooConnector::new()
         .map_err(|e| format!("Failed to create Yahoo Connector: {}", e))?;
 
-    // Get the latest quotes
-    let response = provider
-        .get_latest_quotes(ticker, "1d")
-        .await
-        .map_err(|e| format!("Yahoo Finance API error: {}", e))?;
+    // Try the input as a ticker first; fall back to name search if it fails
+    let direct = if looks_like_ticker(query) {
+        match provider.get_latest_quotes(query, "1d").await {
+            Ok(response) => Some((query.to_uppercase(), None, response)),
+            Err(e) => {
+                log::info!("Finance: '{}' failed as a ticker ({}), trying symbol search", query, e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let (ticker, company, response) = match direct {
+        Some(found) => found,
+        None => {
+            let resolved = resolve_symbol(&provider, query).await?;
+            log::info!("Finance: resolved '{}' to {}", query, resolved.symbol);
+            let response = provider
+                .get_latest_quotes(&resolved.symbol, "1d")
+                .await
+                .map_err(|e| format!("Yahoo Finance API error: {}", e))?;
+            (resolved.symbol, Some(resolved.name), response)
+        }
+    };
 
     let quote = response.last_quote().map_err(|e| format!("No quote data found: {}", e))?;
 
@@ -21,12 +138,17 @@ pub async fn perform_finance_lookup(ticker: &str) -> Result<String, String> {
     let time = OffsetDateTime::from_unix_timestamp(quote.timestamp as i64)
         .map_err(|_| "Invalid timestamp")?;
 
-    // Try to get more info if possible, but for now basic price
-    // We could format this nicely
+    let stock_line = match company {
+        Some(name) if !name.is_empty() => {
+            format!("Stock: {} ({}, resolved from \"{}\")", ticker, name, query)
+        }
+        Some(_) => format!("Stock: {} (resolved from \"{}\")", ticker, query),
+        None => format!("Stock: {}", ticker),
+    };
 
     let result = format!(
-        "Stock: {}\nPrice: ${:.2}\nTime: {}\nVolume: {}",
-        ticker.to_uppercase(),
+        "{}\nPrice: ${:.2}\nTime: {}\nVolume: {}",
+        stock_line,
         price,
         time,
         quote.volume
@@ -34,3 +156,45 @@ pub async fn perform_finance_lookup(ticker: &str) -> Result<String, String> {
 
     Ok(result)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(symbol: &str, quote_type: &str, exchange: &str) -> SymbolCandidate {
+        SymbolCandidate {
+            symbol: symbol.to_string(),
+            name: format!("{} Inc.", symbol),
+            quote_type: quote_type.to_string(),
+            exchange: exchange.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_looks_like_ticker() {
+        for ticker in ["AAPL", "msft", "GOOGL", "BRK.B", "RY.TO", "BTC-USD", "^GSPC", "EURUSD=X", "F"] {
+            assert!(looks_like_ticker(ticker), "{} should be a ticker", ticker);
+        }
+        for name in ["Apple", "apple", "nvidia", "Apple Inc", "Berkshire Hathaway", "", "  ", "123"] {
+            assert!(!looks_like_ticker(name), "{:?} should need resolution", name);
+        }
+    }
+
+    #[test]
+    fn test_pick_primary_symbol() {
+        // Prefers the US-listed equity over foreign listings and ETFs
+        let candidates = vec![
+            candidate("AAPL.MX", "EQUITY", "MEX"),
+            candidate("AAPY", "ETF", "PCX"),
+            candidate("AAPL", "EQUITY", "NMS"),
+        ];
+        assert_eq!(pick_primary_symbol(&candidates).unwrap().symbol, "AAPL");
+
+        // Falls back to any equity, then the top hit
+        let candidates = vec![candidate("SAP.DE", "EQUITY", "GER"), candidate("SAP", "ETF", "NYQ")];
+        assert_eq!(pick_primary_symbol(&candidates).unwrap().symbol, "SAP.DE");
+        let candidates = vec![candidate("XYZ", "FUTURE", "CME")];
+        assert_eq!(pick_primary_symbol(&candidates).unwrap().symbol, "XYZ");
+        assert!(pick_primary_symbol(&[]).is_none());
+    }
+}
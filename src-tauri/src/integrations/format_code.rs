@@ -0,0 +1,178 @@
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// Largest snippet accepted for formatting
+pub const MAX_FORMAT_CHARS: usize = 100_000;
+/// Formatters that hang (e.g. waiting on a config prompt) are killed after this
+const FORMATTER_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CodeLanguage {
+    Rust,
+    Python,
+    Json,
+}
+
+impl CodeLanguage {
+    pub fn parse(language: &str) -> Result<Self, String> {
+        match language.trim().to_lowercase().as_str() {
+            "rust" | "rs" => Ok(CodeLanguage::Rust),
+            "python" | "py" => Ok(CodeLanguage::Python),
+            "json" => Ok(CodeLanguage::Json),
+            other => Err(format!(
+                "Unsupported language '{}' (supported: rust, python, json)",
+                other
+            )),
+        }
+    }
+}
+
+/// Pretty-print JSON, reporting the parse error position on invalid input.
+/// The text is reindented rather than re-serialized, so key order and numbers stay as written.
+pub fn format_json(code: &str) -> Result<String, String> {
+    serde_json::from_str::<serde::de::IgnoredAny>(code).map_err(|e| format!("Syntax error: {}", e))?;
+
+    let mut out = String::with_capacity(code.len() * 2);
+    let mut depth = 0usize;
+    let newline = |out: &mut String, depth: usize| {
+        out.push('\n');
+        out.push_str(&"  ".repeat(depth));
+    };
+
+    let mut chars = code.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                out.push(c);
+                while let Some(c) = chars.next() {
+                    out.push(c);
+                    match c {
+                        '\\' => out.extend(chars.next()),
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '{' | '[' => {
+                out.push(c);
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                // Empty containers stay on one line, as serde_json prints them
+                if let Some(close) = chars.next_if(|next| matches!((c, *next), ('{', '}') | ('[', ']'))) {
+                    out.push(close);
+                } else {
+                    depth += 1;
+                    newline(&mut out, depth);
+                }
+            }
+            '}' | ']' => {
+                depth = depth.saturating_sub(1);
+                newline(&mut out, depth);
+                out.push(c);
+            }
+            ',' => {
+                out.push(c);
+                newline(&mut out, depth);
+            }
+            ':' => out.push_str(": "),
+            c if c.is_whitespace() => {}
+            c => out.push(c),
+        }
+    }
+    Ok(out)
+}
+
+/// Pipe `code` through an external formatter's stdin and return its stdout.
+/// A missing binary and a non-zero exit (usually a syntax error) are reported separately.
+pub async fn run_formatter(program: &str, args: &[&str], code: &str) -> Result<String, String> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => {
+                format!("{} is not installed (or not on PATH), so this code can't be formatted", program)
+            }
+            _ => format!("Failed to start {}: {}", program, e),
+        })?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(code.as_bytes())
+            .await
+            .map_err(|e| format!("Failed to send code to {}: {}", program, e))?;
+        // Dropping stdin closes the pipe so the formatter sees EOF
+    }
+
+    let output = tokio::time::timeout(FORMATTER_TIMEOUT, child.wait_with_output())
+        .await
+        .map_err(|_| format!("{} timed out", program))?
+        .map_err(|e| format!("{} failed: {}", program, e))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(format!("Syntax error reported by {}:\n{}", program, stderr.trim()))
+    }
+}
+
+/// Format `code` with the local formatter for `language`
+pub async fn format_code(code: &str, language: &str) -> Result<String, String> {
+    if code.trim().is_empty() {
+        return Err("Code to format is required".to_string());
+    }
+    if code.chars().count() > MAX_FORMAT_CHARS {
+        return Err(format!("Code too long to format (max {} chars)", MAX_FORMAT_CHARS));
+    }
+
+    match CodeLanguage::parse(language)? {
+        CodeLanguage::Json => format_json(code),
+        CodeLanguage::Rust => run_formatter("rustfmt", &["--edition", "2021", "--emit", "stdout"], code).await,
+        CodeLanguage::Python => run_formatter("black", &["--quiet", "-"], code).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_json() {
+        let formatted = format_json(r#"{"b":[1,2],"a":{"c":null}}"#).unwrap();
+        assert_eq!(formatted, "{\n  \"b\": [\n    1,\n    2\n  ],\n  \"a\": {\n    \"c\": null\n  }\n}");
+
+        // Key order, number spelling and string contents are left as written
+        let formatted = format_json("{ \"z\" : 1.50, \"m\":{ }, \"a\": [ ], \"s\": \"x, {\\\"y\\\": [1]}\" }").unwrap();
+        assert_eq!(
+            formatted,
+            "{\n  \"z\": 1.50,\n  \"m\": {},\n  \"a\": [],\n  \"s\": \"x, {\\\"y\\\": [1]}\"\n}"
+        );
+
+        let err = format_json(r#"{"a": 1,}"#).unwrap_err();
+        assert!(err.starts_with("Syntax error:"), "{}", err);
+        assert!(err.contains("line 1"));
+    }
+
+    #[test]
+    fn test_parse_language() {
+        assert_eq!(CodeLanguage::parse(" Rust ").unwrap(), CodeLanguage::Rust);
+        assert_eq!(CodeLanguage::parse("py").unwrap(), CodeLanguage::Python);
+        assert!(CodeLanguage::parse("cobol").unwrap_err().contains("Unsupported language"));
+    }
+
+    #[tokio::test]
+    async fn test_format_code_errors_propagate() {
+        assert!(format_code("  ", "json").await.unwrap_err().contains("required"));
+        assert!(format_code("x = 1", "cobol").await.is_err());
+        assert!(format_code("[1, 2", "json").await.unwrap_err().starts_with("Syntax error"));
+
+        // A formatter that isn't installed is reported, not treated as a syntax error
+        let err = run_formatter("shard-no-such-formatter", &[], "code").await.unwrap_err();
+        assert!(err.contains("is not installed"), "{}", err);
+    }
+}
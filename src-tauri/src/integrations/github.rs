@@ -0,0 +1,221 @@
+use base64::{engine::general_purpose, Engine as _};
+use log;
+use reqwest;
+use serde::Deserialize;
+use super::{request_error, tool_timeout};
+
+/// Max characters of file content returned to the model
+const MAX_CONTENT_CHARS: usize = 20000;
+
+/// GitHub contents API response (file or README)
+#[derive(Debug, Deserialize)]
+struct GithubContent {
+    path: String,
+    #[serde(rename = "type")]
+    content_type: String,
+    content: Option<String>,
+    encoding: Option<String>,
+    html_url: Option<String>,
+}
+
+/// Directory listing entry
+#[derive(Debug, Deserialize)]
+struct GithubDirEntry {
+    name: String,
+    #[serde(rename = "type")]
+    entry_type: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GithubFile {
+    pub path: String,
+    pub content: String,
+    pub html_url: Option<String>,
+}
+
+/// Parse "owner/name" (or a github.com URL) into (owner, name)
+pub fn parse_repo_spec(repo: &str) -> Result<(String, String), String> {
+    let trimmed = repo
+        .trim()
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .trim_start_matches("www.")
+        .trim_start_matches("github.com/")
+        .trim_end_matches('/')
+        .trim_end_matches(".git");
+
+    let mut parts = trimmed.split('/');
+    match (parts.next(), parts.next()) {
+        (Some(owner), Some(name)) if !owner.is_empty() && !name.is_empty() => {
+            let valid = |s: &str| {
+                s.chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+            };
+            if valid(owner) && valid(name) {
+                Ok((owner.to_string(), name.to_string()))
+            } else {
+                Err(format!("Invalid repository '{}': expected 'owner/name'", repo))
+            }
+        }
+        _ => Err(format!("Invalid repository '{}': expected 'owner/name'", repo)),
+    }
+}
+
+/// Decode GitHub's base64 file content (wrapped with newlines)
+pub fn decode_github_content(encoded: &str) -> Result<String, String> {
+    let cleaned: String = encoded.chars().filter(|c| !c.is_whitespace()).collect();
+    let bytes = general_purpose::STANDARD
+        .decode(cleaned)
+        .map_err(|e| format!("Failed to decode file content: {}", e))?;
+    String::from_utf8(bytes).map_err(|_| "File is not valid UTF-8 text (binary file?)".to_string())
+}
+
+/// Fetch a file (or the README when `path` is empty) from a GitHub repository
+pub async fn read_github(
+    client: &reqwest::Client,
+    repo: &str,
+    path: Option<&str>,
+    token: Option<&str>,
+) -> Result<GithubFile, String> {
+    let (owner, name) = parse_repo_spec(repo)?;
+    let path = path.map(|p| p.trim().trim_start_matches('/')).filter(|p| !p.is_empty());
+
+    let url = match path {
+        Some(p) => format!("https://api.github.com/repos/{}/{}/contents/{}", owner, name, p),
+        None => format!("https://api.github.com/repos/{}/{}/readme", owner, name),
+    };
+
+    log::info!("Fetching GitHub content: {}", url);
+
+    let mut request = client
+        .get(&url)
+        .header("User-Agent", "shard-desktop")
+        .header("Accept", "application/vnd.github+json");
+    if let Some(token) = token.filter(|t| !t.is_empty()) {
+        request = request.header("Authorization", format!("Bearer {}", token));
+    }
+
+    let timeout = tool_timeout("read_github");
+    let response = request
+        .timeout(timeout)
+        .send()
+        .await
+        .map_err(|e| request_error("GitHub", &e, timeout))?;
+
+    let status = response.status().as_u16();
+    match status {
+        200 => {}
+        404 => {
+            return Err(format!(
+                "Not found: {}/{}{} (check the repository name and path)",
+                owner,
+                name,
+                path.map(|p| format!("/{}", p)).unwrap_or_else(|| " README".to_string())
+            ))
+        }
+        403 | 429 => {
+            let rate_limited = response
+                .headers()
+                .get("x-ratelimit-remaining")
+                .and_then(|v| v.to_str().ok())
+                .map_or(status == 429, |v| v == "0");
+            return Err(if rate_limited {
+                "GitHub API rate limit exceeded. Add a GitHub token in Settings to raise the limit.".to_string()
+            } else {
+                format!("GitHub API access denied ({})", status)
+            });
+        }
+        _ => return Err(format!("GitHub API error: {}", status)),
+    }
+
+    let body: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse GitHub response: {}", e))?;
+
+    // Directory paths return an array of entries
+    if body.is_array() {
+        let entries: Vec<GithubDirEntry> = serde_json::from_value(body)
+            .map_err(|e| format!("Failed to parse GitHub directory listing: {}", e))?;
+        let listing: Vec<String> = entries
+            .iter()
+            .map(|e| {
+                if e.entry_type == "dir" {
+                    format!("{}/", e.name)
+                } else {
+                    e.name.clone()
+                }
+            })
+            .collect();
+        return Ok(GithubFile {
+            path: path.unwrap_or_default().to_string(),
+            content: format!("Directory listing:\n{}", listing.join("\n")),
+            html_url: None,
+        });
+    }
+
+    let file: GithubContent = serde_json::from_value(body)
+        .map_err(|e| format!("Failed to parse GitHub file: {}", e))?;
+
+    if file.content_type != "file" {
+        return Err(format!("'{}' is a {}, not a file", file.path, file.content_type));
+    }
+
+    let content = match (file.content.as_deref(), file.encoding.as_deref()) {
+        (Some(c), Some("base64")) => decode_github_content(c)?,
+        (Some(c), _) => c.to_string(),
+        // Files over 1MB come back without inline content
+        (None, _) => return Err(format!("'{}' is too large to read via the contents API", file.path)),
+    };
+
+    Ok(GithubFile {
+        path: file.path,
+        content: content.chars().take(MAX_CONTENT_CHARS).collect(),
+        html_url: file.html_url,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_repo_spec() {
+        assert_eq!(
+            parse_repo_spec("tauri-apps/tauri").unwrap(),
+            ("tauri-apps".to_string(), "tauri".to_string())
+        );
+        assert_eq!(
+            parse_repo_spec("https://github.com/serde-rs/serde.git").unwrap(),
+            ("serde-rs".to_string(), "serde".to_string())
+        );
+        assert_eq!(
+            parse_repo_spec("github.com/rust-lang/rust/").unwrap(),
+            ("rust-lang".to_string(), "rust".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_repo_spec_invalid() {
+        assert!(parse_repo_spec("tauri").is_err());
+        assert!(parse_repo_spec("/tauri").is_err());
+        assert!(parse_repo_spec("owner/na me").is_err());
+    }
+
+    #[test]
+    fn test_decode_github_content_with_newlines() {
+        // GitHub wraps base64 at 60 chars with "\n"
+        let encoded = "IyBTaGFyZAoKQSBkZXNrdG9wIEFJ\nIGFzc2lzdGFudC4K\n";
+        assert_eq!(
+            decode_github_content(encoded).unwrap(),
+            "# Shard\n\nA desktop AI assistant.\n"
+        );
+    }
+
+    #[test]
+    fn test_decode_github_content_binary() {
+        // 0xFF 0xFE is not valid UTF-8
+        assert!(decode_github_content("//4=").is_err());
+        assert!(decode_github_content("not base64!").is_err());
+    }
+}
@@ -0,0 +1,140 @@
+use log;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Max characters of file content returned to the model
+const MAX_CONTENT_CHARS: usize = 20000;
+/// Files larger than this are rejected outright rather than read and truncated
+const MAX_FILE_BYTES: u64 = 2 * 1024 * 1024;
+/// Leading bytes checked for NULs when sniffing binary files
+const BINARY_SNIFF_BYTES: usize = 8192;
+
+/// Canonicalize `path` and confirm it lies inside one of the allowlisted roots.
+/// Both sides are canonicalized, so `..` segments and symlinks can't escape a root.
+pub fn resolve_allowed_path(path: &str, allowed_roots: &[String]) -> Result<PathBuf, String> {
+    if allowed_roots.iter().all(|r| r.trim().is_empty()) {
+        return Err("No folders are allowlisted for file reading. Add one under allowed_file_roots in Settings.".to_string());
+    }
+
+    let requested = fs::canonicalize(path.trim())
+        .map_err(|e| format!("Cannot access '{}': {}", path, e))?;
+
+    let inside = allowed_roots
+        .iter()
+        .filter(|r| !r.trim().is_empty())
+        .filter_map(|r| fs::canonicalize(r.trim()).ok())
+        .any(|root| requested.starts_with(&root));
+    if !inside {
+        return Err(format!("'{}' is outside the allowlisted folders", path));
+    }
+    Ok(requested)
+}
+
+/// Read a UTF-8 text file, truncated to `MAX_CONTENT_CHARS`
+fn read_text_file(path: &Path) -> Result<String, String> {
+    let metadata = fs::metadata(path).map_err(|e| format!("Cannot access file: {}", e))?;
+    if !metadata.is_file() {
+        return Err(format!("'{}' is not a file", path.display()));
+    }
+    if metadata.len() > MAX_FILE_BYTES {
+        return Err(format!(
+            "File is too large ({} KB, max {} KB)",
+            metadata.len() / 1024,
+            MAX_FILE_BYTES / 1024
+        ));
+    }
+
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read file: {}", e))?;
+    if bytes.iter().take(BINARY_SNIFF_BYTES).any(|b| *b == 0) {
+        return Err("File appears to be binary, not text".to_string());
+    }
+    let text = String::from_utf8(bytes).map_err(|_| "File is not valid UTF-8 text (binary file?)".to_string())?;
+
+    if text.chars().count() > MAX_CONTENT_CHARS {
+        let truncated: String = text.chars().take(MAX_CONTENT_CHARS).collect();
+        return Ok(format!("{}\n\n[Truncated at {} characters]", truncated, MAX_CONTENT_CHARS));
+    }
+    Ok(text)
+}
+
+/// Read a local file the user has allowlisted; returns (canonical path, content)
+pub fn read_local_file(path: &str, allowed_roots: &[String]) -> Result<(PathBuf, String), String> {
+    let resolved = resolve_allowed_path(path, allowed_roots)?;
+    log::info!("Reading local file: {}", resolved.display());
+    let content = read_text_file(&resolved)?;
+    Ok((resolved, content))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> (tempfile::TempDir, Vec<String>) {
+        let temp = tempfile::TempDir::new().unwrap();
+        let allowed = temp.path().join("allowed");
+        fs::create_dir_all(allowed.join("nested")).unwrap();
+        fs::write(allowed.join("notes.txt"), "hello").unwrap();
+        fs::write(allowed.join("nested/deep.md"), "# deep").unwrap();
+        fs::write(temp.path().join("secret.txt"), "top secret").unwrap();
+        let roots = vec![allowed.to_string_lossy().to_string()];
+        (temp, roots)
+    }
+
+    #[test]
+    fn test_paths_inside_root_are_allowed() {
+        let (temp, roots) = setup();
+        let allowed = temp.path().join("allowed");
+
+        assert!(resolve_allowed_path(&allowed.join("notes.txt").to_string_lossy(), &roots).is_ok());
+        assert!(resolve_allowed_path(&allowed.join("nested/deep.md").to_string_lossy(), &roots).is_ok());
+        // `..` that stays inside the root is fine
+        let inner = allowed.join("nested/../notes.txt");
+        assert!(resolve_allowed_path(&inner.to_string_lossy(), &roots).is_ok());
+    }
+
+    #[test]
+    fn test_traversal_outside_root_is_rejected() {
+        let (temp, roots) = setup();
+        let allowed = temp.path().join("allowed");
+
+        let escape = allowed.join("../secret.txt");
+        let err = resolve_allowed_path(&escape.to_string_lossy(), &roots).unwrap_err();
+        assert!(err.contains("outside the allowlisted folders"));
+
+        let escape = allowed.join("nested/../../secret.txt");
+        assert!(resolve_allowed_path(&escape.to_string_lossy(), &roots).is_err());
+        assert!(resolve_allowed_path(&temp.path().join("secret.txt").to_string_lossy(), &roots).is_err());
+
+        // A sibling whose name merely starts with the root's name is not inside it
+        let sibling = temp.path().join("allowed-evil");
+        fs::create_dir_all(&sibling).unwrap();
+        fs::write(sibling.join("x.txt"), "x").unwrap();
+        assert!(resolve_allowed_path(&sibling.join("x.txt").to_string_lossy(), &roots).is_err());
+    }
+
+    #[test]
+    fn test_no_roots_refuses_everything() {
+        let (temp, _) = setup();
+        let path = temp.path().join("allowed/notes.txt");
+        assert!(resolve_allowed_path(&path.to_string_lossy(), &[]).is_err());
+        assert!(resolve_allowed_path(&path.to_string_lossy(), &["  ".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_read_rejects_binary_and_truncates_long_text() {
+        let (temp, roots) = setup();
+        let allowed = temp.path().join("allowed");
+
+        fs::write(allowed.join("image.bin"), [0x89, 0x50, 0x4e, 0x47, 0x00, 0x01]).unwrap();
+        let err = read_local_file(&allowed.join("image.bin").to_string_lossy(), &roots).unwrap_err();
+        assert!(err.contains("binary"));
+
+        fs::write(allowed.join("long.txt"), "a".repeat(MAX_CONTENT_CHARS + 10)).unwrap();
+        let (_, content) = read_local_file(&allowed.join("long.txt").to_string_lossy(), &roots).unwrap();
+        assert!(content.ends_with(&format!("[Truncated at {} characters]", MAX_CONTENT_CHARS)));
+
+        let (_, content) = read_local_file(&allowed.join("notes.txt").to_string_lossy(), &roots).unwrap();
+        assert_eq!(content, "hello");
+        assert!(read_local_file(&allowed.to_string_lossy(), &roots).is_err());
+    }
+}
@@ -5,3 +5,65 @@ pub mod arxiv;
 pub mod ocr;
 pub mod web_search;
 pub mod vision_llm;
+pub mod url_summary;
+pub mod discussions;
+pub mod world_clock;
+pub mod github;
+pub mod feeds;
+pub mod translate;
+pub mod format_code;
+pub mod local_file;
+
+use std::time::Duration;
+
+/// Per-request timeout for fast lookups (weather, quotes, clocks)
+pub const QUICK_TIMEOUT: Duration = Duration::from_secs(10);
+/// Per-request timeout for ordinary searches and API calls
+pub const STANDARD_TIMEOUT: Duration = Duration::from_secs(20);
+/// Per-request timeout for full-text reads (papers, arbitrary pages)
+pub const LONG_READ_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Timeout applied to a tool's outbound requests via `RequestBuilder::timeout`
+pub fn tool_timeout(tool: &str) -> Duration {
+    match tool {
+        "get_weather" | "get_stock_price" | "get_time" => QUICK_TIMEOUT,
+        "read_arxiv_paper" | "save_url_summary" | "read_github" => LONG_READ_TIMEOUT,
+        _ => STANDARD_TIMEOUT,
+    }
+}
+
+/// Describe a transport failure, calling out timeouts separately from other network errors
+pub fn request_error(source: &str, err: &reqwest::Error, timeout: Duration) -> String {
+    if err.is_timeout() {
+        format!("{} request timed out after {}s", source, timeout.as_secs())
+    } else {
+        format!("{} network error: {}", source, err)
+    }
+}
+
+/// Tools that emit `agent-tool-progress` stages while they run
+pub const PROGRESS_TOOLS: &[&str] = &["read_arxiv_paper", "save_url_summary"];
+
+/// Whether a tool has opted into interim progress events
+pub fn reports_progress(tool: &str) -> bool {
+    PROGRESS_TOOLS.contains(&tool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_timeout_selection() {
+        for tool in ["get_weather", "get_stock_price", "get_time"] {
+            assert_eq!(tool_timeout(tool), QUICK_TIMEOUT, "{}", tool);
+        }
+        for tool in ["read_arxiv_paper", "save_url_summary", "read_github"] {
+            assert_eq!(tool_timeout(tool), LONG_READ_TIMEOUT, "{}", tool);
+        }
+        for tool in ["web_search", "search_arxiv", "search_wikipedia", "unknown_tool"] {
+            assert_eq!(tool_timeout(tool), STANDARD_TIMEOUT, "{}", tool);
+        }
+        assert!(QUICK_TIMEOUT < STANDARD_TIMEOUT && STANDARD_TIMEOUT < LONG_READ_TIMEOUT);
+    }
+}
@@ -1,13 +1,164 @@
-//! OCR module - Image text recognition via Vision LLM
+//! OCR module - Local text recognition fallback
 //!
-//! This module previously used Tesseract/Leptonica for OCR.
-//! Now OCR is handled via `vision_llm::describe_image` which uses
-//! Groq or OpenRouter Vision models for better multilingual support
+//! Image understanding is primarily handled by `vision_llm::describe_image`,
+//! which uses Groq or OpenRouter Vision models for better multilingual support
 //! and the ability to understand images without text.
 //!
-//! The `perform_ocr_capture` and `ocr_image` commands in lib.rs
-//! now call vision_llm directly.
+//! When no vision API key is configured (or every API attempt fails),
+//! `perform_ocr` shells out to a locally installed `tesseract` binary so
+//! offline users still get plain text extraction.
 
-// This file is kept for reference. All OCR functionality has moved to:
-// - integrations/vision_llm.rs - Vision LLM API calls
-// - lib.rs - perform_ocr_capture and ocr_image commands
+use base64::{engine::general_purpose, Engine as _};
+use std::fs;
+use std::process::Command;
+
+/// Locations to look for the tesseract binary. GUI apps on macOS don't inherit
+/// the shell PATH, so the Homebrew prefixes are checked explicitly.
+const TESSERACT_CANDIDATES: &[&str] = &[
+    "tesseract",
+    "/opt/homebrew/bin/tesseract",
+    "/usr/local/bin/tesseract",
+];
+
+/// Map an image MIME type to a file extension tesseract can detect
+fn extension_for_mime(mime_type: &str) -> &'static str {
+    match mime_type {
+        "image/jpeg" | "image/jpg" => "jpg",
+        "image/webp" => "webp",
+        "image/gif" => "gif",
+        "image/tiff" => "tiff",
+        "image/bmp" => "bmp",
+        _ => "png",
+    }
+}
+
+/// Known OCR languages: (ISO 639-1 code, tesseract traineddata, display name)
+const LANGUAGES: &[(&str, &str, &str)] = &[
+    ("en", "eng", "English"),
+    ("es", "spa", "Spanish"),
+    ("fr", "fra", "French"),
+    ("de", "deu", "German"),
+    ("it", "ita", "Italian"),
+    ("pt", "por", "Portuguese"),
+    ("nl", "nld", "Dutch"),
+    ("ru", "rus", "Russian"),
+    ("ja", "jpn", "Japanese"),
+    ("ko", "kor", "Korean"),
+    ("zh", "chi_sim", "Chinese"),
+    ("ar", "ara", "Arabic"),
+    ("hi", "hin", "Hindi"),
+];
+
+/// Normalize a language hint; None or "auto" means no hint (tesseract defaults to English)
+fn language_hint(lang: Option<&str>) -> Option<String> {
+    lang.map(|l| l.trim().to_lowercase())
+        .filter(|l| !l.is_empty() && l != "auto")
+}
+
+/// Map a language hint (ISO code, tesseract code, or English name) to tesseract traineddata.
+/// Unknown tesseract-style codes (e.g. "chi_tra", "eng+fra") are passed through.
+pub fn tesseract_language(lang: Option<&str>) -> Option<String> {
+    let lang = language_hint(lang)?;
+    if let Some((_, tess, _)) = LANGUAGES
+        .iter()
+        .find(|(iso, tess, name)| *iso == lang || *tess == lang || name.to_lowercase() == lang)
+    {
+        return Some(tess.to_string());
+    }
+    let is_code = lang.chars().all(|c| c.is_ascii_lowercase() || c == '_' || c == '+');
+    is_code.then_some(lang)
+}
+
+/// Human-readable language name for prompts (falls back to the hint as given)
+pub fn language_display_name(lang: Option<&str>) -> Option<String> {
+    let hint = language_hint(lang)?;
+    let name = LANGUAGES
+        .iter()
+        .find(|(iso, tess, name)| *iso == hint || *tess == hint || name.to_lowercase() == hint)
+        .map(|(_, _, name)| name.to_string())
+        .unwrap_or_else(|| lang.unwrap_or_default().trim().to_string());
+    Some(name)
+}
+
+/// Extract text from a base64-encoded image using the local tesseract CLI
+/// `lang` selects the traineddata language (default: tesseract's English)
+pub fn perform_ocr(image_base64: &str, mime_type: &str, lang: Option<&str>) -> Result<String, String> {
+    let image_bytes = general_purpose::STANDARD
+        .decode(image_base64)
+        .map_err(|e| format!("Failed to decode base64 image: {}", e))?;
+
+    let temp_path = std::env::temp_dir().join(format!(
+        "shard_ocr_{}.{}",
+        uuid::Uuid::new_v4(),
+        extension_for_mime(mime_type)
+    ));
+    fs::write(&temp_path, &image_bytes)
+        .map_err(|e| format!("Failed to write temp OCR file: {}", e))?;
+
+    let tess_lang = tesseract_language(lang);
+
+    let mut last_error = String::from("tesseract binary not found");
+    let mut output = None;
+    for candidate in TESSERACT_CANDIDATES {
+        let mut command = Command::new(candidate);
+        command.arg(&temp_path).arg("stdout");
+        if let Some(code) = &tess_lang {
+            command.arg("-l").arg(code);
+        }
+        match command.output() {
+            Ok(out) => {
+                output = Some(out);
+                break;
+            }
+            Err(e) => last_error = format!("{}: {}", candidate, e),
+        }
+    }
+
+    if let Err(e) = fs::remove_file(&temp_path) {
+        log::warn!("Failed to remove temp OCR file {}: {}", temp_path.display(), e);
+    }
+
+    let output = output.ok_or_else(|| format!("Local OCR unavailable ({})", last_error))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "Tesseract failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if text.is_empty() {
+        return Err("No text found in image".to_string());
+    }
+
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extension_for_mime() {
+        assert_eq!(extension_for_mime("image/jpeg"), "jpg");
+        assert_eq!(extension_for_mime("image/png"), "png");
+        assert_eq!(extension_for_mime("application/octet-stream"), "png");
+    }
+
+    #[test]
+    fn test_perform_ocr_rejects_invalid_base64() {
+        let result = perform_ocr("not base64!!", "image/png", None);
+        assert!(result.unwrap_err().contains("decode"));
+    }
+
+    #[test]
+    fn test_tesseract_language_mapping() {
+        assert_eq!(tesseract_language(None), None);
+        assert_eq!(tesseract_language(Some("auto")), None);
+        assert_eq!(tesseract_language(Some("ja")), Some("jpn".to_string()));
+        assert_eq!(tesseract_language(Some("German")), Some("deu".to_string()));
+        assert_eq!(tesseract_language(Some("eng+fra")), Some("eng+fra".to_string()));
+        assert_eq!(tesseract_language(Some("rm -rf")), None);
+    }
+}
@@ -0,0 +1,126 @@
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+
+/// Longest text accepted for a single translation call
+pub const MAX_TRANSLATE_CHARS: usize = 8000;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Translation {
+    /// Detected source language (English name, e.g. "Japanese")
+    pub source_lang: String,
+    pub translation: String,
+}
+
+/// Prompt asking the model to detect the source language and translate,
+/// replying with a single JSON object
+pub fn build_translation_prompt(text: &str, target_lang: &str) -> String {
+    format!(
+        "Detect the language of the text below and translate it into {}. \
+Preserve meaning, tone, formatting, and any code or URLs verbatim. \
+Respond with ONLY a JSON object of the form \
+{{\"source_lang\": \"<source language name in English>\", \"translation\": \"<translated text>\"}}.\n\n\
+Text:\n{}",
+        target_lang.trim(),
+        text
+    )
+}
+
+/// Parse the model's structured reply (tolerates code fences / surrounding text)
+pub fn parse_translation_response(llm_response: &str) -> Result<Translation, String> {
+    let json_start = llm_response.find('{');
+    let json_end = llm_response.rfind('}');
+
+    let (Some(start), Some(end)) = (json_start, json_end) else {
+        return Err("No JSON object found in translation response".to_string());
+    };
+
+    let mut parsed: Translation = serde_json::from_str(&llm_response[start..=end])
+        .map_err(|e| format!("Failed to parse translation response: {}", e))?;
+
+    parsed.source_lang = parsed.source_lang.trim().to_string();
+    if parsed.source_lang.is_empty() {
+        parsed.source_lang = "unknown".to_string();
+    }
+    if parsed.translation.trim().is_empty() {
+        return Err("Translator returned an empty translation".to_string());
+    }
+    Ok(parsed)
+}
+
+/// Validate input, call the model via `complete`, and parse its reply.
+/// `complete` takes the prompt and returns the raw model text (mockable in tests).
+pub async fn translate_with<Fut>(
+    text: &str,
+    target_lang: &str,
+    complete: impl FnOnce(String) -> Fut,
+) -> Result<Translation, String>
+where
+    Fut: Future<Output = Result<String, String>>,
+{
+    if text.trim().is_empty() {
+        return Err("Text to translate is required".to_string());
+    }
+    if target_lang.trim().is_empty() {
+        return Err("Target language is required".to_string());
+    }
+    if text.chars().count() > MAX_TRANSLATE_CHARS {
+        return Err(format!(
+            "Text too long to translate ({} chars, max {})",
+            text.chars().count(),
+            MAX_TRANSLATE_CHARS
+        ));
+    }
+
+    let reply = complete(build_translation_prompt(text, target_lang)).await?;
+    parse_translation_response(&reply)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_build_translation_prompt() {
+        let prompt = build_translation_prompt("Bonjour le monde", " Japanese ");
+        assert!(prompt.contains("translate it into Japanese."));
+        assert!(prompt.contains("\"source_lang\""));
+        assert!(prompt.ends_with("Text:\nBonjour le monde"));
+    }
+
+    #[test]
+    fn test_parse_translation_response() {
+        let reply = "```json\n{\"source_lang\": \" French \", \"translation\": \"Hello world\"}\n```";
+        let parsed = parse_translation_response(reply).unwrap();
+        assert_eq!(parsed.source_lang, "French");
+        assert_eq!(parsed.translation, "Hello world");
+
+        // Missing JSON or empty translations are errors
+        assert!(parse_translation_response("Hello world").is_err());
+        assert!(parse_translation_response("{\"source_lang\": \"French\", \"translation\": \"\"}").is_err());
+
+        // Blank source language falls back to "unknown"
+        let parsed =
+            parse_translation_response("{\"source_lang\": \"\", \"translation\": \"Hi\"}").unwrap();
+        assert_eq!(parsed.source_lang, "unknown");
+    }
+
+    #[tokio::test]
+    async fn test_translate_with_sends_prompt() {
+        let seen = Mutex::new(String::new());
+        let result = translate_with("Hola", "English", |prompt| {
+            *seen.lock().unwrap() = prompt;
+            async { Ok("{\"source_lang\": \"Spanish\", \"translation\": \"Hello\"}".to_string()) }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(result.source_lang, "Spanish");
+        assert_eq!(result.translation, "Hello");
+        assert!(seen.lock().unwrap().contains("translate it into English"));
+
+        // Empty input never reaches the model
+        let err = translate_with("  ", "English", |_| async { Ok(String::new()) }).await;
+        assert!(err.is_err());
+    }
+}
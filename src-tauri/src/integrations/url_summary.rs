@@ -0,0 +1,187 @@
+use log;
+use reqwest;
+use scraper::{Html, Selector};
+use std::future::Future;
+use super::{request_error, tool_timeout};
+
+/// Max characters of page text sent to the summarizer
+const MAX_PAGE_CHARS: usize = 12000;
+
+/// Fetch a URL and return its readable text content
+pub async fn fetch_url_text(client: &reqwest::Client, url: &str) -> Result<String, String> {
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err(format!("Invalid URL (must start with http:// or https://): {}", url));
+    }
+
+    log::info!("Fetching URL for summary: {}", url);
+
+    let timeout = tool_timeout("save_url_summary");
+    let response = client
+        .get(url)
+        .header("User-Agent", "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36")
+        .timeout(timeout)
+        .send()
+        .await
+        .map_err(|e| request_error(&format!("Fetching {}", url), &e, timeout))?;
+
+    if !response.status().is_success() {
+        return Err(format!("Failed to fetch {}: HTTP {}", url, response.status()));
+    }
+
+    let is_html = response
+        .headers()
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .map(|ct| ct.contains("html"))
+        .unwrap_or(true);
+
+    let body = response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read response body: {}", e))?;
+
+    let text = if is_html {
+        extract_readable_text(&body)
+    } else {
+        collapse_whitespace(&body)
+    };
+
+    if text.is_empty() {
+        return Err(format!("No readable text found at {}", url));
+    }
+
+    Ok(text.chars().take(MAX_PAGE_CHARS).collect())
+}
+
+/// Extract the readable text from an HTML page (title, headings, paragraphs, list items)
+/// Scripts, styles and navigation chrome are skipped by only selecting content elements.
+pub fn extract_readable_text(html: &str) -> String {
+    let document = Html::parse_document(html);
+    let selector = Selector::parse("title, h1, h2, h3, p, li, pre, blockquote").unwrap();
+    let nav_selector = Selector::parse("nav, header, footer, aside").unwrap();
+
+    // Collect node ids inside navigation chrome so their text can be skipped
+    let skip_ids: std::collections::HashSet<_> = document
+        .select(&nav_selector)
+        .flat_map(|el| el.descendants().map(|n| n.id()))
+        .collect();
+
+    document
+        .select(&selector)
+        .filter(|el| !skip_ids.contains(&el.id()))
+        .map(|el| collapse_whitespace(&el.text().collect::<String>()))
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Prompt asking the model to summarize fetched page text
+pub fn build_summary_prompt(url: &str, page_text: &str) -> String {
+    format!(
+        "Summarize the following web page for a personal knowledge base. \
+Use concise markdown: a one-sentence overview, then the key points as bullets. \
+Respond with the summary only.\n\nURL: {}\n\nPage content:\n{}",
+        url, page_text
+    )
+}
+
+/// Format the stored topic content, keeping the source URL for later reference
+pub fn format_url_summary(url: &str, summary: &str) -> String {
+    format!("Source: {}\n\n{}", url, summary.trim())
+}
+
+/// Fetch -> summarize -> store pipeline, with each step injected so it can be mocked.
+/// Returns the topic name on success.
+pub async fn save_url_summary_with<FetchFut, SummarizeFut, StoreFut>(
+    url: &str,
+    topic: &str,
+    fetch: impl FnOnce(String) -> FetchFut,
+    summarize: impl FnOnce(String) -> SummarizeFut,
+    store: impl FnOnce(String, String) -> StoreFut,
+) -> Result<String, String>
+where
+    FetchFut: Future<Output = Result<String, String>>,
+    SummarizeFut: Future<Output = Result<String, String>>,
+    StoreFut: Future<Output = Result<(), String>>,
+{
+    let topic = topic.trim();
+    if topic.is_empty() {
+        return Err("Topic name is required".to_string());
+    }
+
+    let page_text = fetch(url.to_string()).await?;
+    let summary = summarize(build_summary_prompt(url, &page_text)).await?;
+    if summary.trim().is_empty() {
+        return Err("Summarizer returned an empty summary".to_string());
+    }
+
+    store(topic.to_string(), format_url_summary(url, &summary)).await?;
+    Ok(topic.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_extract_readable_text_skips_chrome() {
+        let html = r#"<html><head><title>Paper</title><script>var x = 1;</script></head>
+            <body><nav><li>Home</li></nav><h1>Attention</h1><p>Transformers   use attention.</p></body></html>"#;
+        let text = extract_readable_text(html);
+        assert!(text.contains("Paper"));
+        assert!(text.contains("Transformers use attention."));
+        assert!(!text.contains("Home"));
+        assert!(!text.contains("var x"));
+    }
+
+    #[tokio::test]
+    async fn test_save_url_summary_stores_summary_under_topic() {
+        let stored: Mutex<Option<(String, String)>> = Mutex::new(None);
+
+        let result = save_url_summary_with(
+            "https://example.com/post",
+            "ML_papers",
+            |_url| async { Ok("Page body about attention".to_string()) },
+            |prompt| async move {
+                assert!(prompt.contains("Page body about attention"));
+                Ok("- Attention is all you need".to_string())
+            },
+            |topic, content| {
+                *stored.lock().unwrap() = Some((topic, content));
+                async { Ok(()) }
+            },
+        )
+        .await;
+
+        assert_eq!(result, Ok("ML_papers".to_string()));
+        let (topic, content) = stored.lock().unwrap().clone().unwrap();
+        assert_eq!(topic, "ML_papers");
+        assert!(content.starts_with("Source: https://example.com/post"));
+        assert!(content.contains("Attention is all you need"));
+    }
+
+    #[tokio::test]
+    async fn test_save_url_summary_fetch_failure_skips_store() {
+        let stored = Mutex::new(false);
+
+        let result = save_url_summary_with(
+            "https://example.com/missing",
+            "ML_papers",
+            |_url| async { Err("Failed to fetch: HTTP 404".to_string()) },
+            |_prompt| async { Ok("unused".to_string()) },
+            |_topic, _content| {
+                *stored.lock().unwrap() = true;
+                async { Ok(()) }
+            },
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert!(!*stored.lock().unwrap());
+    }
+}
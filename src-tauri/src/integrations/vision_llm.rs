@@ -1,23 +1,118 @@
 /// Vision LLM module - Use Groq or OpenRouter vision models for image understanding
 /// This replaces Tesseract OCR with API-based vision model calls for better
 /// multilingual support and the ability to understand images without text.
+/// Local Tesseract OCR is kept only as a last-resort fallback.
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 
 use crate::config::AppConfig;
 
 /// Default prompt for OCR-like image description
-const VISION_PROMPT: &str = "Identify the subject of this image specifically (e.g., 'Steam logo', 'Python code', 'Error message'). Extract ALL visible text exactly as shown. Describe key visual details (colors, shapes, layout) concisely but precisely as if you were describing it to a blind person.";
+pub const DEFAULT_VISION_PROMPT: &str = "Identify the subject of this image specifically (e.g., 'Steam logo', 'Python code', 'Error message'). Extract ALL visible text exactly as shown. Describe key visual details (colors, shapes, layout) concisely but precisely as if you were describing it to a blind person.";
 
-/// Groq Vision model (Llama 4 Scout with vision capabilities)
-const GROQ_VISION_MODEL: &str = "meta-llama/llama-4-scout-17b-16e-instruct";
+/// `vision_prompt` from config, or the default when unset or blank
+pub fn vision_prompt(config: &AppConfig) -> &str {
+    config
+        .vision_prompt
+        .as_deref()
+        .filter(|p| !p.trim().is_empty())
+        .unwrap_or(DEFAULT_VISION_PROMPT)
+}
 
-/// OpenRouter free vision models in priority order
-const OPENROUTER_VISION_MODELS: &[&str] = &[
+/// Build the vision prompt from `base`, asking for output in `lang` when a hint is given
+pub fn build_vision_prompt(base: &str, lang: Option<&str>) -> String {
+    match crate::integrations::ocr::language_display_name(lang) {
+        Some(name) => format!(
+            "{} The image text is likely in {}: transcribe it exactly as written and write your description in {}.",
+            base, name, name
+        ),
+        None => base.to_string(),
+    }
+}
+
+/// Default vision models in priority order: free OpenRouter models, then
+/// Llama 4 Scout on Groq
+pub const DEFAULT_VISION_MODELS: &[&str] = &[
     "google/gemma-3-27b-it:free",
     "nvidia/nemotron-nano-12b-v2-vl:free",
+    "meta-llama/llama-4-scout-17b-16e-instruct (Groq)",
 ];
 
+/// Marks a `vision_models` entry as a Groq model
+const GROQ_SUFFIX: &str = "(Groq)";
+
+/// Prefix marking a description produced by local OCR rather than a vision model
+pub const OCR_ONLY_TAG: &str = "[OCR only]";
+
+/// Image description backends, in the order `describe_image` tries them
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VisionBackend {
+    OpenRouter,
+    Groq,
+    LocalOcr,
+}
+
+/// Select which backends to try based on configured API keys.
+/// Local OCR is always appended as the final fallback.
+pub fn select_vision_backends(config: &AppConfig) -> Vec<VisionBackend> {
+    let has_key = |key: &Option<String>| key.as_ref().map(|k| !k.is_empty()).unwrap_or(false);
+
+    let mut backends = Vec::new();
+    if has_key(&config.openrouter_api_key) {
+        backends.push(VisionBackend::OpenRouter);
+    }
+    if has_key(&config.groq_api_key) {
+        backends.push(VisionBackend::Groq);
+    }
+    backends.push(VisionBackend::LocalOcr);
+    backends
+}
+
+impl VisionBackend {
+    fn chat_completions_url(self) -> Option<&'static str> {
+        match self {
+            VisionBackend::OpenRouter => Some("https://openrouter.ai/api/v1/chat/completions"),
+            VisionBackend::Groq => Some("https://api.groq.com/openai/v1/chat/completions"),
+            VisionBackend::LocalOcr => None,
+        }
+    }
+
+    fn api_key(self, config: &AppConfig) -> Option<&str> {
+        match self {
+            VisionBackend::OpenRouter => config.openrouter_api_key.as_deref(),
+            VisionBackend::Groq => config.groq_api_key.as_deref(),
+            VisionBackend::LocalOcr => None,
+        }
+    }
+}
+
+/// Vision models to try, in the configured (or default) priority order, paired with
+/// their backend. Models whose backend has no API key are left out.
+pub fn select_vision_models(config: &AppConfig) -> Vec<(VisionBackend, String)> {
+    let available = select_vision_backends(config);
+    let configured: Vec<String> = match config.vision_models.as_ref().filter(|models| !models.is_empty()) {
+        Some(models) => models.clone(),
+        None => DEFAULT_VISION_MODELS.iter().map(|m| m.to_string()).collect(),
+    };
+
+    configured
+        .iter()
+        .filter_map(|entry| {
+            let entry = entry.trim();
+            let (backend, model) = match entry.strip_suffix(GROQ_SUFFIX) {
+                Some(model) => (VisionBackend::Groq, model.trim()),
+                None => (VisionBackend::OpenRouter, entry),
+            };
+            (!model.is_empty() && available.contains(&backend)).then(|| (backend, model.to_string()))
+        })
+        .collect()
+}
+
+/// Check whether a description came from the local OCR fallback
+pub fn is_ocr_only(description: &str) -> bool {
+    description.starts_with(OCR_ONLY_TAG)
+}
+
 #[derive(Serialize, Debug)]
 struct OpenAIVisionRequest {
     model: String,
@@ -72,70 +167,62 @@ struct OpenAIError {
 }
 
 /// Describe an image using a Vision LLM.
-/// Tries OpenRouter first if API key is available, falls back to Groq.
+/// Tries each model from `select_vision_models` in order, then falls back to
+/// local Tesseract OCR (result prefixed with `OCR_ONLY_TAG`).
 pub async fn describe_image(
     http_client: &Client,
     image_base64: &str,
     mime_type: &str,
     config: &AppConfig,
+    lang: Option<&str>,
 ) -> Result<String, String> {
-    // Try OpenRouter first (priority 1)
-    if let Some(openrouter_key) = &config.openrouter_api_key {
-        log::info!("[VisionLLM] Attempting OpenRouter Vision...");
-
-        for model in OPENROUTER_VISION_MODELS {
-            match call_vision_api(
-                http_client,
-                "https://openrouter.ai/api/v1/chat/completions",
-                openrouter_key,
-                model,
-                image_base64,
-                mime_type,
-            )
-            .await
-            {
-                Ok(result) => {
-                    log::info!(
-                        "[VisionLLM] OpenRouter Vision success with model: {}",
-                        model
-                    );
-                    return Ok(result);
-                }
-                Err(e) => {
-                    log::warn!("[VisionLLM] OpenRouter model {} failed: {}", model, e);
-                }
-            }
-        }
-    }
+    let mut errors: Vec<String> = Vec::new();
+    let prompt = build_vision_prompt(vision_prompt(config), lang);
 
-    // Fallback to Groq (priority 2)
-    if let Some(groq_key) = &config.groq_api_key {
-        log::info!("[VisionLLM] Attempting Groq Vision...");
-        match call_vision_api(
-            http_client,
-            "https://api.groq.com/openai/v1/chat/completions",
-            groq_key,
-            GROQ_VISION_MODEL,
-            image_base64,
-            mime_type,
-        )
-        .await
-        {
+    for (backend, model) in select_vision_models(config) {
+        let (Some(url), Some(api_key)) = (backend.chat_completions_url(), backend.api_key(config)) else {
+            continue;
+        };
+        log::info!("[VisionLLM] Attempting {:?} Vision with model: {}", backend, model);
+        match call_vision_api(http_client, url, api_key, &model, image_base64, mime_type, &prompt).await {
             Ok(result) => {
-                log::info!("[VisionLLM] Groq Vision success");
+                log::info!("[VisionLLM] {:?} Vision success with model: {}", backend, model);
                 return Ok(result);
             }
             Err(e) => {
-                log::warn!(
-                    "[VisionLLM] Groq Vision failed: {}",
-                    e
-                );
+                log::warn!("[VisionLLM] {:?} model {} failed: {}", backend, model, e);
+                errors.push(format!("{:?} {}: {}", backend, model, e));
             }
         }
     }
 
-    // No API keys available or all failed
-    Err("No OpenRouter or Groq API key configured (or all attempts failed) for Vision LLM".to_string())
+    // Local OCR is the last resort
+    log::info!("[VisionLLM] Falling back to local Tesseract OCR...");
+    let image = image_base64.to_string();
+    let mime = mime_type.to_string();
+    let ocr_lang = lang.map(str::to_string);
+    let ocr_result = tokio::task::spawn_blocking(move || {
+        crate::integrations::ocr::perform_ocr(&image, &mime, ocr_lang.as_deref())
+    })
+    .await
+    .map_err(|e| format!("OCR task failed: {}", e))?;
+
+    match ocr_result {
+        Ok(text) => {
+            log::info!("[VisionLLM] Local OCR success ({} chars)", text.len());
+            return Ok(format!("{} Extracted text:\n{}", OCR_ONLY_TAG, text));
+        }
+        Err(e) => {
+            log::warn!("[VisionLLM] Local OCR failed: {}", e);
+            errors.push(format!("Local OCR: {}", e));
+        }
+    }
+
+    // No API keys available and local OCR failed
+    Err(format!(
+        "No vision model or local OCR available for image description: {}",
+        errors.join("; ")
+    ))
 }
 
 /// Call an OpenAI-compatible vision API endpoint
@@ -146,6 +233,7 @@ async fn call_vision_api(
     model: &str,
     image_base64: &str,
     mime_type: &str,
+    prompt: &str,
 ) -> Result<String, String> {
     let data_uri = format!("data:{};base64,{}", mime_type, image_base64);
 
@@ -155,7 +243,7 @@ async fn call_vision_api(
             role: "user".to_string(),
             content: vec![
                 VisionContent::Text {
-                    text: VISION_PROMPT.to_string(),
+                    text: prompt.to_string(),
                 },
                 VisionContent::ImageUrl {
                     image_url: ImageUrlPayload { url: data_uri },
@@ -201,6 +289,38 @@ async fn call_vision_api(
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_select_vision_backends_without_keys() {
+        let config = AppConfig::default();
+        assert_eq!(select_vision_backends(&config), vec![VisionBackend::LocalOcr]);
+    }
+
+    #[test]
+    fn test_select_vision_backends_priority() {
+        let config = AppConfig {
+            openrouter_api_key: Some("or-key".to_string()),
+            groq_api_key: Some("groq-key".to_string()),
+            ..AppConfig::default()
+        };
+        assert_eq!(
+            select_vision_backends(&config),
+            vec![VisionBackend::OpenRouter, VisionBackend::Groq, VisionBackend::LocalOcr]
+        );
+
+        // Empty keys are treated as absent
+        let config = AppConfig {
+            groq_api_key: Some(String::new()),
+            ..AppConfig::default()
+        };
+        assert_eq!(select_vision_backends(&config), vec![VisionBackend::LocalOcr]);
+    }
+
+    #[test]
+    fn test_is_ocr_only_tag() {
+        assert!(is_ocr_only(&format!("{} Extracted text:\nhello", OCR_ONLY_TAG)));
+        assert!(!is_ocr_only("A screenshot of Python code"));
+    }
+
     #[test]
     fn test_vision_content_serialization() {
         let content = VisionContent::Text {
@@ -219,4 +339,84 @@ mod tests {
         assert!(json.contains("\"type\":\"image_url\""));
         assert!(json.contains("\"url\":\"data:image/png;base64,abc123\""));
     }
+
+    #[test]
+    fn test_build_vision_prompt_without_hint() {
+        assert_eq!(build_vision_prompt(DEFAULT_VISION_PROMPT, None), DEFAULT_VISION_PROMPT);
+        assert_eq!(build_vision_prompt(DEFAULT_VISION_PROMPT, Some("auto")), DEFAULT_VISION_PROMPT);
+        assert_eq!(build_vision_prompt(DEFAULT_VISION_PROMPT, Some("  ")), DEFAULT_VISION_PROMPT);
+    }
+
+    #[test]
+    fn test_build_vision_prompt_with_hint() {
+        let prompt = build_vision_prompt(DEFAULT_VISION_PROMPT, Some("ja"));
+        assert!(prompt.starts_with(DEFAULT_VISION_PROMPT));
+        assert!(prompt.contains("likely in Japanese"));
+        assert!(prompt.contains("description in Japanese"));
+
+        // Unknown languages are named as given
+        assert!(build_vision_prompt(DEFAULT_VISION_PROMPT, Some("Tagalog")).contains("likely in Tagalog"));
+    }
+
+    #[test]
+    fn test_select_vision_models_defaults() {
+        let config = AppConfig {
+            openrouter_api_key: Some("or-key".to_string()),
+            groq_api_key: Some("groq-key".to_string()),
+            ..AppConfig::default()
+        };
+        let models = select_vision_models(&config);
+        assert_eq!(models.len(), DEFAULT_VISION_MODELS.len());
+        assert_eq!(models[0], (VisionBackend::OpenRouter, "google/gemma-3-27b-it:free".to_string()));
+        assert_eq!(
+            models.last().unwrap(),
+            &(VisionBackend::Groq, "meta-llama/llama-4-scout-17b-16e-instruct".to_string())
+        );
+
+        // Models are dropped when their backend has no key
+        let groq_only = AppConfig {
+            groq_api_key: Some("groq-key".to_string()),
+            ..AppConfig::default()
+        };
+        assert_eq!(select_vision_models(&groq_only).len(), 1);
+        assert!(select_vision_models(&AppConfig::default()).is_empty());
+    }
+
+    #[test]
+    fn test_select_vision_models_config_override() {
+        // The configured list replaces the defaults and its order wins, Groq first here
+        let config = AppConfig {
+            openrouter_api_key: Some("or-key".to_string()),
+            groq_api_key: Some("groq-key".to_string()),
+            vision_models: Some(vec![
+                "llama-3.2-90b-vision (Groq)".to_string(),
+                "  ".to_string(),
+                "openai/gpt-4o".to_string(),
+            ]),
+            ..AppConfig::default()
+        };
+        assert_eq!(
+            select_vision_models(&config),
+            vec![
+                (VisionBackend::Groq, "llama-3.2-90b-vision".to_string()),
+                (VisionBackend::OpenRouter, "openai/gpt-4o".to_string()),
+            ]
+        );
+
+        // An empty list falls back to the defaults
+        let empty = AppConfig { vision_models: Some(Vec::new()), ..config.clone() };
+        assert_eq!(select_vision_models(&empty).len(), DEFAULT_VISION_MODELS.len());
+    }
+
+    #[test]
+    fn test_vision_prompt_override() {
+        assert_eq!(vision_prompt(&AppConfig::default()), DEFAULT_VISION_PROMPT);
+        let config = AppConfig {
+            vision_prompt: Some("Transcribe the handwriting.".to_string()),
+            ..AppConfig::default()
+        };
+        assert_eq!(vision_prompt(&config), "Transcribe the handwriting.");
+        let blank = AppConfig { vision_prompt: Some("  ".to_string()), ..AppConfig::default() };
+        assert_eq!(vision_prompt(&blank), DEFAULT_VISION_PROMPT);
+    }
 }
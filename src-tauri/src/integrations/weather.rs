@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use reqwest;
 use log;
+use super::{request_error, tool_timeout};
 
 // --- Open-Meteo Geocoding API Structures ---
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -45,24 +47,252 @@ struct WeatherResponse {
     current: Option<WeatherCurrentData>,
 }
 
-pub async fn perform_weather_lookup(
+// --- Open-Meteo Daily Forecast Structures ---
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct DailyUnits {
+    temperature_2m_max: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+struct DailyData {
+    #[serde(default)]
+    time: Vec<String>,
+    #[serde(default)]
+    temperature_2m_max: Vec<Option<f32>>,
+    #[serde(default)]
+    temperature_2m_min: Vec<Option<f32>>,
+    #[serde(default)]
+    weather_code: Vec<Option<i32>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct ForecastResponse {
+    daily_units: Option<DailyUnits>,
+    daily: Option<DailyData>,
+}
+
+/// One day of forecast, temperatures in Fahrenheit
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DailyForecast {
+    pub date: String,
+    pub high_f: f32,
+    pub low_f: f32,
+    pub condition: String,
+}
+
+/// Maximum forecast length supported by the tool
+pub const MAX_FORECAST_DAYS: u32 = 7;
+
+pub fn celsius_to_fahrenheit(celsius: f32) -> f32 {
+    celsius * 9.0 / 5.0 + 32.0
+}
+
+/// Describe a WMO weather interpretation code (as used by Open-Meteo)
+pub fn weather_code_description(code: i32) -> &'static str {
+    match code {
+        0 => "Clear sky",
+        1 => "Mainly clear",
+        2 => "Partly cloudy",
+        3 => "Overcast",
+        45 | 48 => "Fog",
+        51 | 53 | 55 => "Drizzle",
+        56 | 57 => "Freezing drizzle",
+        61 | 63 | 65 => "Rain",
+        66 | 67 => "Freezing rain",
+        71 | 73 | 75 => "Snow",
+        77 => "Snow grains",
+        80..=82 => "Rain showers",
+        85 | 86 => "Snow showers",
+        95 => "Thunderstorm",
+        96 | 99 => "Thunderstorm with hail",
+        _ => "Unknown",
+    }
+}
+
+/// Parse an Open-Meteo daily forecast payload, converting to Fahrenheit.
+/// Days with missing temperatures are skipped.
+pub fn parse_daily_forecast(body: &str) -> Result<Vec<DailyForecast>, String> {
+    let response: ForecastResponse =
+        serde_json::from_str(body).map_err(|e| format!("Forecast JSON parse error: {}", e))?;
+
+    let Some(daily) = response.daily else {
+        return Ok(Vec::new());
+    };
+    let is_celsius = response
+        .daily_units
+        .and_then(|u| u.temperature_2m_max)
+        .map(|unit| !unit.contains('F'))
+        .unwrap_or(true);
+    let to_f = |t: f32| if is_celsius { celsius_to_fahrenheit(t) } else { t };
+
+    let days = daily
+        .time
+        .iter()
+        .enumerate()
+        .filter_map(|(i, date)| {
+            let high = daily.temperature_2m_max.get(i).copied().flatten()?;
+            let low = daily.temperature_2m_min.get(i).copied().flatten()?;
+            let code = daily.weather_code.get(i).copied().flatten();
+            Some(DailyForecast {
+                date: date.clone(),
+                high_f: to_f(high),
+                low_f: to_f(low),
+                condition: code.map(weather_code_description).unwrap_or("Unknown").to_string(),
+            })
+        })
+        .collect();
+
+    Ok(days)
+}
+
+/// Location value that asks for the user's approximate position via IP geolocation
+pub const CURRENT_LOCATION_KEYWORD: &str = "here";
+
+/// Where a weather lookup points, picked from the tool arguments
+#[derive(Debug, Clone, PartialEq)]
+pub enum WeatherLocation {
+    Name(String),
+    Coords { lat: f32, lon: f32 },
+    Here,
+}
+
+impl WeatherLocation {
+    /// Coordinates win when both `lat` and `lon` are numbers; otherwise `location`
+    /// is either the "here" keyword or a city/zip string
+    pub fn from_args(args: &Value) -> Result<Self, String> {
+        match (args["lat"].as_f64(), args["lon"].as_f64()) {
+            (Some(lat), Some(lon)) => {
+                if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+                    return Err(format!("Coordinates out of range: {}, {}", lat, lon));
+                }
+                return Ok(WeatherLocation::Coords { lat: lat as f32, lon: lon as f32 });
+            }
+            (Some(_), None) | (None, Some(_)) => {
+                return Err("Both lat and lon are required for a coordinate lookup".to_string());
+            }
+            (None, None) => {}
+        }
+
+        let location = args["location"].as_str().unwrap_or_default().trim();
+        if location.eq_ignore_ascii_case(CURRENT_LOCATION_KEYWORD) {
+            Ok(WeatherLocation::Here)
+        } else if location.is_empty() {
+            Err(format!(
+                "Provide a location name, lat/lon coordinates, or \"{}\"",
+                CURRENT_LOCATION_KEYWORD
+            ))
+        } else {
+            Ok(WeatherLocation::Name(location.to_string()))
+        }
+    }
+}
+
+impl std::fmt::Display for WeatherLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WeatherLocation::Name(name) => write!(f, "{}", name),
+            WeatherLocation::Coords { lat, lon } => write!(f, "{:.4}, {:.4}", lat, lon),
+            WeatherLocation::Here => write!(f, "your current location"),
+        }
+    }
+}
+
+// --- ipapi.co Geolocation Structure ---
+#[derive(Deserialize, Debug)]
+struct IpLocationResponse {
+    city: Option<String>,
+    region: Option<String>,
+    country_name: Option<String>,
+    latitude: Option<f32>,
+    longitude: Option<f32>,
+    #[serde(default)]
+    error: bool,
+    reason: Option<String>,
+}
+
+/// Parse an ipapi.co response into (latitude, longitude, display name)
+pub fn parse_ip_location(body: &str) -> Result<(f32, f32, String), String> {
+    let data: IpLocationResponse =
+        serde_json::from_str(body).map_err(|e| format!("IP geolocation JSON parse error: {}", e))?;
+    if data.error {
+        return Err(format!(
+            "IP geolocation failed: {}",
+            data.reason.unwrap_or_else(|| "unknown error".to_string())
+        ));
+    }
+
+    let lat = data.latitude.ok_or("IP geolocation returned no latitude")?;
+    let lon = data.longitude.ok_or("IP geolocation returned no longitude")?;
+    let name = [data.city, data.region, data.country_name]
+        .into_iter()
+        .flatten()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let name = if name.is_empty() { format!("{:.4}, {:.4}", lat, lon) } else { name };
+    Ok((lat, lon, format!("{} (approximate, from IP)", name)))
+}
+
+/// Look up the user's approximate location from their public IP
+async fn ip_geolocate(client: &reqwest::Client) -> Result<(f32, f32, String), String> {
+    log::info!("Performing IP geolocation lookup");
+
+    let timeout = tool_timeout("get_weather");
+    let resp = client
+        .get("https://ipapi.co/json/")
+        .header("User-Agent", "Shard/1.0")
+        .timeout(timeout)
+        .send()
+        .await
+        .map_err(|e| request_error("IP geolocation", &e, timeout))?;
+
+    if !resp.status().is_success() {
+        return Err(format!("IP geolocation API error: {}", resp.status()));
+    }
+
+    let body = resp
+        .text()
+        .await
+        .map_err(|e| format!("IP geolocation read error: {}", e))?;
+    parse_ip_location(&body)
+}
+
+/// Resolve any location shape to (latitude, longitude, display name).
+/// `allow_ip` gates the "here" lookup, which sends a request revealing the user's IP.
+async fn resolve_location(
     client: &reqwest::Client,
-    location: &str,
-) -> Result<Option<(f32, String, String)>, String> {
-    // (temperature, unit, description/location_name)
+    location: &WeatherLocation,
+    allow_ip: bool,
+) -> Result<Option<(f32, f32, String)>, String> {
+    match location {
+        WeatherLocation::Name(name) => geocode_location(client, name).await,
+        WeatherLocation::Coords { lat, lon } => Ok(Some((*lat, *lon, location.to_string()))),
+        WeatherLocation::Here if allow_ip => ip_geolocate(client).await.map(Some),
+        WeatherLocation::Here => Err(
+            "IP-based location is turned off. Enable ip_geolocation in settings, or ask for a city or coordinates."
+                .to_string(),
+        ),
+    }
+}
 
-    // 1. Geocoding
+/// Resolve a location name to (latitude, longitude, display name)
+async fn geocode_location(
+    client: &reqwest::Client,
+    location: &str,
+) -> Result<Option<(f32, f32, String)>, String> {
     let geo_url = "https://geocoding-api.open-meteo.com/v1/search";
     let geo_params = [("name", location), ("count", "1"), ("language", "en"), ("format", "json")];
 
     log::info!("Performing Geocoding lookup for: {}", location);
 
+    let timeout = tool_timeout("get_weather");
     let geo_resp = client
         .get(geo_url)
         .query(&geo_params)
+        .timeout(timeout)
         .send()
         .await
-        .map_err(|e| format!("Geocoding network error: {}", e))?;
+        .map_err(|e| request_error("Geocoding", &e, timeout))?;
 
     if !geo_resp.status().is_success() {
         return Err(format!("Geocoding API error: {}", geo_resp.status()));
@@ -85,7 +315,65 @@ pub async fn perform_weather_lookup(
     let lon = location_data.longitude.ok_or("Missing longitude")?;
     let name = location_data.name.clone().unwrap_or_default();
     let country = location_data.country.clone().unwrap_or_default();
-    let location_display = format!("{}, {}", name, country);
+    Ok(Some((lat, lon, format!("{}, {}", name, country))))
+}
+
+/// Fetch a daily forecast for `days` days (clamped to 1-7)
+/// Returns (location_name, forecast); the forecast is empty if the provider has none
+pub async fn perform_weather_forecast(
+    client: &reqwest::Client,
+    location: &WeatherLocation,
+    allow_ip: bool,
+    days: u32,
+) -> Result<Option<(String, Vec<DailyForecast>)>, String> {
+    let Some((lat, lon, location_display)) = resolve_location(client, location, allow_ip).await? else {
+        return Ok(None);
+    };
+
+    let days = days.clamp(1, MAX_FORECAST_DAYS);
+    let weather_url = "https://api.open-meteo.com/v1/forecast";
+    let params = [
+        ("latitude", lat.to_string()),
+        ("longitude", lon.to_string()),
+        ("daily", "temperature_2m_max,temperature_2m_min,weather_code".to_string()),
+        ("forecast_days", days.to_string()),
+        ("timezone", "auto".to_string()),
+    ];
+
+    log::info!("Performing {}-day forecast lookup for: {}", days, location_display);
+
+    let timeout = tool_timeout("get_weather");
+    let resp = client
+        .get(weather_url)
+        .query(&params)
+        .timeout(timeout)
+        .send()
+        .await
+        .map_err(|e| request_error("Weather", &e, timeout))?;
+
+    if !resp.status().is_success() {
+        return Err(format!("Weather API error: {}", resp.status()));
+    }
+
+    let body = resp
+        .text()
+        .await
+        .map_err(|e| format!("Weather response read error: {}", e))?;
+
+    Ok(Some((location_display, parse_daily_forecast(&body)?)))
+}
+
+pub async fn perform_weather_lookup(
+    client: &reqwest::Client,
+    location: &WeatherLocation,
+    allow_ip: bool,
+) -> Result<Option<(f32, String, String)>, String> {
+    // (temperature, unit, description/location_name)
+
+    // 1. Geocoding (or coordinates / IP location)
+    let Some((lat, lon, location_display)) = resolve_location(client, location, allow_ip).await? else {
+        return Ok(None);
+    };
 
     // 2. Weather
     let weather_url = "https://api.open-meteo.com/v1/forecast";
@@ -97,12 +385,14 @@ pub async fn perform_weather_lookup(
 
     log::info!("Performing Weather lookup for: {} ({}, {})", location_display, lat, lon);
 
+    let timeout = tool_timeout("get_weather");
     let weather_resp = client
         .get(weather_url)
         .query(&weather_params)
+        .timeout(timeout)
         .send()
         .await
-        .map_err(|e| format!("Weather network error: {}", e))?;
+        .map_err(|e| request_error("Weather", &e, timeout))?;
 
     if !weather_resp.status().is_success() {
         return Err(format!("Weather API error: {}", weather_resp.status()));
@@ -121,3 +411,94 @@ pub async fn perform_weather_lookup(
 
     Ok(None)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_celsius_to_fahrenheit() {
+        assert_eq!(celsius_to_fahrenheit(0.0), 32.0);
+        assert_eq!(celsius_to_fahrenheit(100.0), 212.0);
+        assert!((celsius_to_fahrenheit(-40.0) - -40.0).abs() < 1e-5);
+        assert!((celsius_to_fahrenheit(21.5) - 70.7).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_parse_daily_forecast_multi_day() {
+        let body = r#"{
+            "latitude": 48.86,
+            "longitude": 2.35,
+            "daily_units": {"time": "iso8601", "temperature_2m_max": "°C", "temperature_2m_min": "°C", "weather_code": "wmo code"},
+            "daily": {
+                "time": ["2025-06-01", "2025-06-02", "2025-06-03"],
+                "temperature_2m_max": [25.0, 18.5, null],
+                "temperature_2m_min": [15.0, 10.0, 9.0],
+                "weather_code": [0, 63, 3]
+            }
+        }"#;
+
+        let days = parse_daily_forecast(body).unwrap();
+        // Third day has no high, so it's skipped
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0].date, "2025-06-01");
+        assert_eq!(days[0].high_f, 77.0);
+        assert_eq!(days[0].low_f, 59.0);
+        assert_eq!(days[0].condition, "Clear sky");
+        assert!((days[1].high_f - 65.3).abs() < 1e-4);
+        assert_eq!(days[1].condition, "Rain");
+    }
+
+    #[test]
+    fn test_parse_daily_forecast_missing_or_fahrenheit() {
+        // No daily block -> empty forecast (caller degrades to current conditions)
+        assert!(parse_daily_forecast(r#"{"latitude": 1.0}"#).unwrap().is_empty());
+
+        // Already in Fahrenheit -> no double conversion
+        let body = r#"{
+            "daily_units": {"temperature_2m_max": "°F"},
+            "daily": {"time": ["2025-01-01"], "temperature_2m_max": [50.0], "temperature_2m_min": [40.0], "weather_code": [99]}
+        }"#;
+        let days = parse_daily_forecast(body).unwrap();
+        assert_eq!(days[0].high_f, 50.0);
+        assert_eq!(days[0].condition, "Thunderstorm with hail");
+    }
+
+    #[test]
+    fn test_weather_location_from_args() {
+        use serde_json::json;
+
+        let coords = json!({ "location": null, "lat": 48.8566, "lon": 2.3522, "days": null });
+        assert_eq!(
+            WeatherLocation::from_args(&coords),
+            Ok(WeatherLocation::Coords { lat: 48.8566, lon: 2.3522 })
+        );
+
+        // Coordinates take precedence over a name when both are given
+        let both = json!({ "location": "Paris", "lat": 0, "lon": 0 });
+        assert_eq!(WeatherLocation::from_args(&both), Ok(WeatherLocation::Coords { lat: 0.0, lon: 0.0 }));
+
+        let name = json!({ "location": " 94102 ", "lat": null, "lon": null });
+        assert_eq!(WeatherLocation::from_args(&name), Ok(WeatherLocation::Name("94102".to_string())));
+
+        for here in ["here", "HERE", " Here "] {
+            let args = json!({ "location": here });
+            assert_eq!(WeatherLocation::from_args(&args), Ok(WeatherLocation::Here));
+        }
+
+        assert!(WeatherLocation::from_args(&json!({ "location": "Paris", "lat": 1.0 })).is_err());
+        assert!(WeatherLocation::from_args(&json!({ "lat": 91.0, "lon": 0.0 })).is_err());
+        assert!(WeatherLocation::from_args(&json!({ "location": "  " })).is_err());
+    }
+
+    #[test]
+    fn test_parse_ip_location() {
+        let body = r#"{"ip": "1.2.3.4", "city": "Berlin", "region": "Land Berlin", "country_name": "Germany", "latitude": 52.52, "longitude": 13.405}"#;
+        let (lat, lon, name) = parse_ip_location(body).unwrap();
+        assert_eq!((lat, lon), (52.52, 13.405));
+        assert_eq!(name, "Berlin, Land Berlin, Germany (approximate, from IP)");
+
+        let limited = r#"{"error": true, "reason": "RateLimited"}"#;
+        assert_eq!(parse_ip_location(limited), Err("IP geolocation failed: RateLimited".to_string()));
+    }
+}
@@ -2,6 +2,7 @@ use reqwest;
 use scraper::{Html, Selector};
 use serde::{Deserialize, Serialize};
 use log;
+use super::{request_error, tool_timeout};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SearchResult {
@@ -28,15 +29,94 @@ struct BraveResult {
     description: Option<String>,
 }
 
+/// Recency window for search results
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Freshness {
+    Day,
+    Week,
+    Month,
+}
+
+impl Freshness {
+    /// Parse the tool argument ("day" | "week" | "month"); anything else means no filter
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "day" => Some(Freshness::Day),
+            "week" => Some(Freshness::Week),
+            "month" => Some(Freshness::Month),
+            _ => None,
+        }
+    }
+
+    /// Brave's `freshness` parameter value
+    fn brave_code(self) -> &'static str {
+        match self {
+            Freshness::Day => "pd",
+            Freshness::Week => "pw",
+            Freshness::Month => "pm",
+        }
+    }
+
+    /// DuckDuckGo's `df` parameter value
+    fn duckduckgo_code(self) -> &'static str {
+        match self {
+            Freshness::Day => "d",
+            Freshness::Week => "w",
+            Freshness::Month => "m",
+        }
+    }
+}
+
+/// Optional filters for `web_search`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchOptions {
+    /// Domain to restrict results to, e.g. "docs.rs"
+    pub site: Option<String>,
+    pub freshness: Option<Freshness>,
+}
+
+/// Query text with a `site:` operator appended when a domain restriction is set
+pub fn build_search_query(query: &str, site: Option<&str>) -> String {
+    let domain = site
+        .map(|s| {
+            s.trim()
+                .trim_start_matches("https://")
+                .trim_start_matches("http://")
+                .trim_end_matches('/')
+        })
+        .filter(|s| !s.is_empty());
+    match domain {
+        Some(domain) => format!("{} site:{}", query.trim(), domain),
+        None => query.trim().to_string(),
+    }
+}
+
+/// Brave Search request URL for a query and its filters
+pub fn build_brave_url(query: &str, options: &SearchOptions) -> String {
+    let mut url = format!(
+        "https://api.search.brave.com/res/v1/web/search?q={}&count=5",
+        urlencoding::encode(&build_search_query(query, options.site.as_deref()))
+    );
+    if let Some(freshness) = options.freshness {
+        url.push_str("&freshness=");
+        url.push_str(freshness.brave_code());
+    }
+    url
+}
+
 /// Perform web search using Brave Search API (primary) or DuckDuckGo fallback
 /// If brave_api_key is provided, uses Brave Search first
-pub async fn perform_web_search(query: &str, brave_api_key: Option<&str>) -> Result<Vec<SearchResult>, String> {
-    log::info!("Performing Web Search for: {}", query);
+pub async fn perform_web_search(
+    query: &str,
+    options: &SearchOptions,
+    brave_api_key: Option<&str>,
+) -> Result<Vec<SearchResult>, String> {
+    log::info!("Performing Web Search for: {} ({:?})", query, options);
 
     // Try Brave Search first if API key is provided
     if let Some(api_key) = brave_api_key {
         if !api_key.is_empty() {
-            match perform_brave_search(query, api_key).await {
+            match perform_brave_search(query, options, api_key).await {
                 Ok(results) if !results.is_empty() => return Ok(results),
                 Ok(_) => log::warn!("Brave Search returned no results, trying DuckDuckGo fallback"),
                 Err(e) => log::warn!("Brave Search failed: {}, trying DuckDuckGo fallback", e),
@@ -45,30 +125,33 @@ pub async fn perform_web_search(query: &str, brave_api_key: Option<&str>) -> Res
     }
 
     // Fallback to DuckDuckGo
-    perform_duckduckgo_search(query).await
+    perform_duckduckgo_search(query, options).await
 }
 
 /// Brave Search API (free tier: 2000 queries/month, no payment info required)
 /// Sign up at: https://brave.com/search/api/
-async fn perform_brave_search(query: &str, api_key: &str) -> Result<Vec<SearchResult>, String> {
+async fn perform_brave_search(
+    query: &str,
+    options: &SearchOptions,
+    api_key: &str,
+) -> Result<Vec<SearchResult>, String> {
     log::info!("Using Brave Search API");
 
     let client = reqwest::Client::builder()
         .build()
         .map_err(|e| format!("Failed to build client: {}", e))?;
 
-    let url = format!(
-        "https://api.search.brave.com/res/v1/web/search?q={}&count=5",
-        urlencoding::encode(query)
-    );
+    let url = build_brave_url(query, options);
 
+    let timeout = tool_timeout("web_search");
     let response = client
         .get(&url)
         .header("Accept", "application/json")
         .header("X-Subscription-Token", api_key)
+        .timeout(timeout)
         .send()
         .await
-        .map_err(|e| format!("Brave Search network error: {}", e))?;
+        .map_err(|e| request_error("Brave Search", &e, timeout))?;
 
     if !response.status().is_success() {
         return Err(format!("Brave Search API error: {}", response.status()));
@@ -98,7 +181,7 @@ async fn perform_brave_search(query: &str, api_key: &str) -> Result<Vec<SearchRe
 }
 
 /// DuckDuckGo HTML scraping fallback
-async fn perform_duckduckgo_search(query: &str) -> Result<Vec<SearchResult>, String> {
+async fn perform_duckduckgo_search(query: &str, options: &SearchOptions) -> Result<Vec<SearchResult>, String> {
     log::info!("Using DuckDuckGo HTML fallback");
 
     let client = reqwest::Client::builder()
@@ -107,14 +190,20 @@ async fn perform_duckduckgo_search(query: &str) -> Result<Vec<SearchResult>, Str
         .map_err(|e| format!("Failed to build client: {}", e))?;
 
     let url = "https://html.duckduckgo.com/html/";
-    let params = [("q", query)];
+    let full_query = build_search_query(query, options.site.as_deref());
+    let mut params = vec![("q", full_query.as_str())];
+    if let Some(freshness) = options.freshness {
+        params.push(("df", freshness.duckduckgo_code()));
+    }
 
+    let timeout = tool_timeout("web_search");
     let response = client
         .post(url)
         .form(&params)
+        .timeout(timeout)
         .send()
         .await
-        .map_err(|e| format!("Web search network error: {}", e))?;
+        .map_err(|e| request_error("Web search", &e, timeout))?;
 
     if !response.status().is_success() {
         return Err(format!("Web search API error: {}", response.status()));
@@ -170,3 +259,45 @@ async fn perform_duckduckgo_search(query: &str) -> Result<Vec<SearchResult>, Str
 
     Ok(results)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_search_query() {
+        assert_eq!(build_search_query(" rust async ", None), "rust async");
+        assert_eq!(build_search_query("tokio select", Some("docs.rs")), "tokio select site:docs.rs");
+        assert_eq!(
+            build_search_query("tokio select", Some("https://docs.rs/")),
+            "tokio select site:docs.rs"
+        );
+        assert_eq!(build_search_query("tokio", Some("  ")), "tokio");
+    }
+
+    #[test]
+    fn test_build_brave_url() {
+        let plain = build_brave_url("rust 2024", &SearchOptions::default());
+        assert_eq!(plain, "https://api.search.brave.com/res/v1/web/search?q=rust%202024&count=5");
+
+        let filtered = build_brave_url(
+            "rust 2024",
+            &SearchOptions {
+                site: Some("blog.rust-lang.org".to_string()),
+                freshness: Some(Freshness::Week),
+            },
+        );
+        assert_eq!(
+            filtered,
+            "https://api.search.brave.com/res/v1/web/search?q=rust%202024%20site%3Ablog.rust-lang.org&count=5&freshness=pw"
+        );
+    }
+
+    #[test]
+    fn test_freshness_parse() {
+        assert_eq!(Freshness::parse("day"), Some(Freshness::Day));
+        assert_eq!(Freshness::parse(" Month "), Some(Freshness::Month));
+        assert_eq!(Freshness::parse(""), None);
+        assert_eq!(Freshness::parse("year"), None);
+    }
+}
@@ -1,6 +1,13 @@
 use reqwest;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use log;
+use super::{request_error, tool_timeout};
+
+const WIKIPEDIA_API_URL: &str = "https://en.wikipedia.org/w/api.php";
+const WIKIPEDIA_USER_AGENT: &str = "Shard/1.0 (https://github.com/shard-app/shard)";
+/// Section text is trimmed to keep tool output compact
+const MAX_SECTION_CHARS: usize = 8000;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 struct WikipediaQueryPage {
@@ -40,10 +47,12 @@ pub async fn perform_wikipedia_lookup(
 
     log::info!("Performing Wikipedia lookup for: {}", search_term);
 
+    let timeout = tool_timeout("search_wikipedia");
     match client
         .get(base_url)
         .query(&params)
         .header("User-Agent", "Shard/1.0 (https://github.com/shard-app/shard)")
+        .timeout(timeout)
         .send()
         .await
     {
@@ -93,6 +102,315 @@ pub async fn perform_wikipedia_lookup(
                 Err(format!("Wikipedia API error: {} - {}", status, response_text))
             }
         }
-        Err(e) => Err(format!("Wikipedia network error: {}", e)),
+        Err(e) => Err(request_error("Wikipedia", &e, timeout)),
+    }
+}
+
+// ============================================================================
+// Sections - MediaWiki parse API
+// ============================================================================
+
+/// One entry from an article's table of contents
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WikipediaSection {
+    pub index: String,  // Section index for `action=parse&section=`
+    pub number: String, // Display number, e.g. "2.1"
+    pub level: u32,     // TOC depth (1 = top-level)
+    pub title: String,
+    pub anchor: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WikipediaSectionResult {
+    /// No section requested: the article's section list
+    Sections {
+        title: String,
+        sections: Vec<WikipediaSection>,
+    },
+    /// Plain text of one section
+    Section {
+        title: String,
+        section: WikipediaSection,
+        text: String,
+    },
+}
+
+#[derive(Deserialize, Debug)]
+struct ParseResponse {
+    parse: Option<ParseBody>,
+    error: Option<ParseError>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ParseBody {
+    title: String,
+    #[serde(default)]
+    sections: Vec<RawSection>,
+    text: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RawSection {
+    toclevel: u32,
+    line: String,
+    number: String,
+    index: String,
+    #[serde(default)]
+    anchor: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct ParseError {
+    code: String,
+    info: String,
+}
+
+fn parse_response(body: &str) -> Result<ParseBody, String> {
+    let response: ParseResponse =
+        serde_json::from_str(body).map_err(|e| format!("Wikipedia JSON parse error: {}", e))?;
+    if let Some(err) = response.error {
+        return Err(format!("Wikipedia API error ({}): {}", err.code, err.info));
+    }
+    response
+        .parse
+        .ok_or_else(|| "Wikipedia: parse response missing".to_string())
+}
+
+/// Parse a `prop=sections` response into (article title, sections)
+pub fn parse_section_list(body: &str) -> Result<(String, Vec<WikipediaSection>), String> {
+    let parsed = parse_response(body)?;
+    let sections = parsed
+        .sections
+        .into_iter()
+        .map(|s| WikipediaSection {
+            index: s.index,
+            number: s.number,
+            level: s.toclevel,
+            // Section headings may contain inline markup like <i>
+            title: html_to_plaintext(&s.line),
+            anchor: s.anchor,
+        })
+        .collect();
+    Ok((parsed.title, sections))
+}
+
+/// Parse a `prop=text` response into plain text
+pub fn parse_section_text(body: &str) -> Result<String, String> {
+    let parsed = parse_response(body)?;
+    let html = parsed
+        .text
+        .ok_or_else(|| "Wikipedia: section text missing".to_string())?;
+    Ok(html_to_plaintext(&html))
+}
+
+/// Match a requested section by title (case-insensitive), anchor, or number
+pub fn find_section<'a>(sections: &'a [WikipediaSection], query: &str) -> Option<&'a WikipediaSection> {
+    let query = query.trim();
+    let normalized = query.replace('_', " ").to_lowercase();
+    sections
+        .iter()
+        .find(|s| s.title.to_lowercase() == normalized)
+        .or_else(|| sections.iter().find(|s| s.anchor == query || s.number == query))
+        .or_else(|| sections.iter().find(|s| s.title.to_lowercase().contains(&normalized)))
+}
+
+/// Decode the HTML entities MediaWiki emits in rendered text
+fn decode_entities(text: &str) -> String {
+    let entity_re = Regex::new(r"&(#[0-9]+|#[xX][0-9a-fA-F]+|[a-zA-Z]+);").unwrap();
+    entity_re
+        .replace_all(text, |caps: &regex::Captures| {
+            let entity = &caps[1];
+            let decoded = if let Some(hex) = entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+            } else if let Some(dec) = entity.strip_prefix('#') {
+                dec.parse::<u32>().ok().and_then(char::from_u32)
+            } else {
+                match entity {
+                    "amp" => Some('&'),
+                    "lt" => Some('<'),
+                    "gt" => Some('>'),
+                    "quot" => Some('"'),
+                    "apos" => Some('\''),
+                    "nbsp" => Some(' '),
+                    "ndash" => Some('–'),
+                    "mdash" => Some('—'),
+                    _ => None,
+                }
+            };
+            decoded
+                .map(|c| c.to_string())
+                .unwrap_or_else(|| caps[0].to_string())
+        })
+        .to_string()
+}
+
+/// Convert rendered section HTML to plain text.
+/// Drops tables (infoboxes/navboxes), styles, comments, and citation markers.
+pub fn html_to_plaintext(html: &str) -> String {
+    let drop_re = Regex::new(
+        r#"(?s)<!--.*?-->|<style[^>]*>.*?</style>|<table[^>]*>.*?</table>|<sup[^>]*class="[^"]*reference[^"]*"[^>]*>.*?</sup>"#,
+    )
+    .unwrap();
+    let block_re = Regex::new(r"(?i)<br\s*/?>|</(p|li|h[1-6]|dd|dt|div|tr)>").unwrap();
+    let li_re = Regex::new(r"(?i)<li[^>]*>").unwrap();
+    let tag_re = Regex::new(r"<[^>]*>").unwrap();
+
+    let text = drop_re.replace_all(html, "");
+    let text = block_re.replace_all(&text, "\n");
+    let text = li_re.replace_all(&text, "- ");
+    let text = tag_re.replace_all(&text, "");
+    let text = decode_entities(&text).replace('\u{a0}', " ");
+
+    // Trim each line and collapse runs of blank lines
+    let mut lines: Vec<&str> = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() && matches!(lines.last(), None | Some(&"")) {
+            continue;
+        }
+        lines.push(line);
+    }
+    lines.join("\n").trim().to_string()
+}
+
+async fn fetch_parse(client: &reqwest::Client, params: &[(&str, &str)]) -> Result<String, String> {
+    let timeout = tool_timeout("read_wikipedia_section");
+    let response = client
+        .get(WIKIPEDIA_API_URL)
+        .query(params)
+        .header("User-Agent", WIKIPEDIA_USER_AGENT)
+        .timeout(timeout)
+        .send()
+        .await
+        .map_err(|e| request_error("Wikipedia", &e, timeout))?;
+
+    let status = response.status();
+    let body = response
+        .text()
+        .await
+        .map_err(|e| format!("Wikipedia: Failed to read response text: {}", e))?;
+    if !status.is_success() {
+        return Err(format!("Wikipedia API error: {} - {}", status, body));
+    }
+    Ok(body)
+}
+
+/// Read one section of an article as plain text, or list its sections when `section` is None
+pub async fn read_wikipedia_section(
+    client: &reqwest::Client,
+    title: &str,
+    section: Option<&str>,
+) -> Result<WikipediaSectionResult, String> {
+    log::info!("Reading Wikipedia sections for: {} ({:?})", title, section);
+
+    let body = fetch_parse(
+        client,
+        &[
+            ("action", "parse"),
+            ("format", "json"),
+            ("formatversion", "2"),
+            ("redirects", "1"),
+            ("page", title),
+            ("prop", "sections"),
+        ],
+    )
+    .await?;
+    let (resolved_title, sections) = parse_section_list(&body)?;
+
+    let Some(query) = section.map(str::trim).filter(|s| !s.is_empty()) else {
+        return Ok(WikipediaSectionResult::Sections {
+            title: resolved_title,
+            sections,
+        });
+    };
+
+    let found = find_section(&sections, query).cloned().ok_or_else(|| {
+        let names: Vec<&str> = sections.iter().map(|s| s.title.as_str()).collect();
+        format!(
+            "Section '{}' not found in '{}'. Available sections: {}",
+            query,
+            resolved_title,
+            names.join(", ")
+        )
+    })?;
+
+    let body = fetch_parse(
+        client,
+        &[
+            ("action", "parse"),
+            ("format", "json"),
+            ("formatversion", "2"),
+            ("page", resolved_title.as_str()),
+            ("prop", "text"),
+            ("section", found.index.as_str()),
+            ("disableeditsection", "1"),
+            ("disabletoc", "1"),
+        ],
+    )
+    .await?;
+    let mut text = parse_section_text(&body)?;
+    if text.chars().count() > MAX_SECTION_CHARS {
+        text = format!("{}... [truncated]", text.chars().take(MAX_SECTION_CHARS).collect::<String>());
+    }
+
+    Ok(WikipediaSectionResult::Section {
+        title: resolved_title,
+        section: found,
+        text,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECTIONS_FIXTURE: &str = r#"{"parse":{"title":"Alan Turing","pageid":1208,"sections":[
+        {"toclevel":1,"level":"2","line":"Early life and education","number":"1","index":"1","fromtitle":"Alan_Turing","byteoffset":100,"anchor":"Early_life_and_education","linkAnchor":"Early_life_and_education"},
+        {"toclevel":2,"level":"3","line":"Family","number":"1.1","index":"2","fromtitle":"Alan_Turing","byteoffset":200,"anchor":"Family","linkAnchor":"Family"},
+        {"toclevel":1,"level":"2","line":"<i>Enigma</i> work","number":"2","index":"3","fromtitle":"Alan_Turing","byteoffset":300,"anchor":"Enigma_work","linkAnchor":"Enigma_work"}
+    ]}}"#;
+
+    const TEXT_FIXTURE: &str = r##"{"parse":{"title":"Alan Turing","pageid":1208,"text":"<div class=\"mw-content-ltr mw-parser-output\" lang=\"en\" dir=\"ltr\"><div class=\"mw-heading mw-heading3\"><h3 id=\"Family\">Family</h3></div>\n<style data-mw-deduplicate=\"TemplateStyles:r1\">.mw-parser-output .hatnote{font-style:italic}</style><p>Turing was born in <a href=\"/wiki/Maida_Vale\" title=\"Maida Vale\">Maida Vale</a>, London.<sup id=\"cite_ref-1\" class=\"reference\"><a href=\"#cite_note-1\">&#91;1&#93;</a></sup> His father&#39;s family &amp; friends&#160;lived nearby.</p>\n<table class=\"infobox\"><tr><td>Born</td><td>1912</td></tr></table>\n<ul><li>First item</li><li>Second item</li></ul>\n<!-- \nNewPP limit report\n-->\n</div>"}}"##;
+
+    #[test]
+    fn test_parse_section_list() {
+        let (title, sections) = parse_section_list(SECTIONS_FIXTURE).unwrap();
+        assert_eq!(title, "Alan Turing");
+        assert_eq!(sections.len(), 3);
+        assert_eq!(sections[1].number, "1.1");
+        assert_eq!(sections[1].level, 2);
+        assert_eq!(sections[1].index, "2");
+        // Inline markup is stripped from headings
+        assert_eq!(sections[2].title, "Enigma work");
+    }
+
+    #[test]
+    fn test_find_section() {
+        let (_, sections) = parse_section_list(SECTIONS_FIXTURE).unwrap();
+        assert_eq!(find_section(&sections, "family").unwrap().index, "2");
+        assert_eq!(find_section(&sections, "Enigma_work").unwrap().index, "3");
+        assert_eq!(find_section(&sections, "1.1").unwrap().title, "Family");
+        assert_eq!(find_section(&sections, "early life").unwrap().index, "1");
+        assert!(find_section(&sections, "Legacy").is_none());
+    }
+
+    #[test]
+    fn test_parse_section_text_plaintext() {
+        let text = parse_section_text(TEXT_FIXTURE).unwrap();
+        assert!(text.starts_with("Family\n"));
+        assert!(text.contains("Turing was born in Maida Vale, London. His father's family & friends lived nearby."));
+        assert!(text.contains("- First item\n- Second item"));
+        // Citations, styles, infobox tables and comments are dropped
+        assert!(!text.contains("[1]"));
+        assert!(!text.contains("font-style"));
+        assert!(!text.contains("1912"));
+        assert!(!text.contains("NewPP"));
+        assert!(!text.contains('<'));
+    }
+
+    #[test]
+    fn test_parse_section_api_error() {
+        let body = r#"{"error":{"code":"missingtitle","info":"The page you specified doesn't exist."}}"#;
+        let err = parse_section_list(body).unwrap_err();
+        assert!(err.contains("missingtitle"));
     }
 }
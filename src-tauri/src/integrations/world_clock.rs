@@ -0,0 +1,80 @@
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ZoneTime {
+    pub timezone: String,
+    pub local_time: String,
+    pub abbreviation: String,
+    pub utc_offset: String,
+}
+
+/// Get the current local time in an IANA timezone (e.g. "Asia/Tokyo")
+pub fn get_time_in_zone(tz_name: &str) -> Result<ZoneTime, String> {
+    get_time_in_zone_at(tz_name, Utc::now())
+}
+
+/// Convert a UTC instant into an IANA timezone (testable core logic)
+pub fn get_time_in_zone_at(tz_name: &str, utc: DateTime<Utc>) -> Result<ZoneTime, String> {
+    let tz: Tz = tz_name.trim().parse().map_err(|_| {
+        format!(
+            "Unknown timezone '{}'. Use an IANA name like 'Asia/Tokyo' or 'America/New_York'.",
+            tz_name
+        )
+    })?;
+
+    let local = utc.with_timezone(&tz);
+    Ok(ZoneTime {
+        timezone: tz.name().to_string(),
+        local_time: local.format("%A, %Y-%m-%d %H:%M").to_string(),
+        abbreviation: local.format("%Z").to_string(),
+        utc_offset: local.format("%:z").to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_fixed_offset_zone() {
+        let utc = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        let tokyo = get_time_in_zone_at("Asia/Tokyo", utc).unwrap();
+        assert_eq!(tokyo.local_time, "Saturday, 2024-06-01 21:00");
+        assert_eq!(tokyo.utc_offset, "+09:00");
+        assert_eq!(tokyo.abbreviation, "JST");
+    }
+
+    #[test]
+    fn test_dst_transition_new_york() {
+        // DST starts 2024-03-10 at 02:00 local (07:00 UTC)
+        let before = Utc.with_ymd_and_hms(2024, 3, 10, 6, 30, 0).unwrap();
+        let after = Utc.with_ymd_and_hms(2024, 3, 10, 7, 30, 0).unwrap();
+
+        let est = get_time_in_zone_at("America/New_York", before).unwrap();
+        assert_eq!(est.local_time, "Sunday, 2024-03-10 01:30");
+        assert_eq!(est.utc_offset, "-05:00");
+        assert_eq!(est.abbreviation, "EST");
+
+        let edt = get_time_in_zone_at("America/New_York", after).unwrap();
+        assert_eq!(edt.local_time, "Sunday, 2024-03-10 03:30");
+        assert_eq!(edt.utc_offset, "-04:00");
+        assert_eq!(edt.abbreviation, "EDT");
+    }
+
+    #[test]
+    fn test_southern_hemisphere_summer() {
+        let utc = Utc.with_ymd_and_hms(2024, 1, 15, 0, 0, 0).unwrap();
+        let sydney = get_time_in_zone_at("Australia/Sydney", utc).unwrap();
+        assert_eq!(sydney.utc_offset, "+11:00");
+    }
+
+    #[test]
+    fn test_invalid_timezone() {
+        let err = get_time_in_zone_at("Mars/Olympus_Mons", Utc::now()).unwrap_err();
+        assert!(err.contains("Unknown timezone"));
+        assert!(get_time_in_zone_at("Tokyo", Utc::now()).is_err());
+    }
+}
@@ -3,7 +3,7 @@
  *
  * Implements Tier 3 of the memory system:
  * - Logs every turn to daily JSONL files
- * - Generates embeddings using gemini-embedding-001
+ * - Generates embeddings (Gemini gemini-embedding-001 by default, or any OpenAI-compatible endpoint)
  * - Performs semantic search for context retrieval
  */
 
@@ -12,10 +12,13 @@ use serde::{Deserialize, Serialize};
 use std::fs::{self, OpenOptions};
 use std::io::{BufRead, BufReader, Write};
 use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
 use tauri::{AppHandle, Manager, Runtime};
+use crate::config::EmbeddingProvider;
 use crate::retrieval::{
-    apply_temporal_boost, fuse_rrf_multi, load_bm25_index, min_dense_hits, rrf_k_default,
-    temporal_tau_days, HitSource, ScoredHit,
+    apply_temporal_boost, fuse_rrf_weighted, load_bm25_index_from,
+    make_snippet, temporal_decay, HitSource, RetrievalTuning, ScoredHit, Snippet, BM25_MIN_NORMALIZED_SCORE,
+    RRF_WEIGHT_BM25, RRF_WEIGHT_DENSE, RRF_WEIGHT_TOPIC_CHUNK, SNIPPET_CONTEXT_CHARS,
 };
 
 // ============================================================================
@@ -29,6 +32,18 @@ pub struct InteractionEntry {
     pub content: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub embedding: Option<Vec<f32>>,
+    /// Embedding space (`EmbeddingSettings::space_id`) the vector came from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding_model: Option<String>,
+}
+
+impl InteractionEntry {
+    /// The embedding if it is comparable with `space` vectors of length `dims`.
+    /// Entries logged before the space was recorded are assumed to match.
+    pub fn embedding_in_space(&self, space: &str, dims: usize) -> Option<&Vec<f32>> {
+        let same_space = self.embedding_model.as_deref().is_none_or(|model| model == space);
+        self.embedding.as_ref().filter(|e| same_space && e.len() == dims)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -58,47 +73,312 @@ struct EmbeddingValues {
     values: Vec<f32>,
 }
 
+#[derive(Serialize, Debug)]
+struct OpenAiEmbeddingRequest<'a> {
+    model: &'a str,
+    input: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dimensions: Option<u32>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenAiEmbeddingResponse {
+    data: Vec<OpenAiEmbeddingData>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenAiEmbeddingData {
+    embedding: Vec<f32>,
+}
+
 // ============================================================================
 // Embedding API
 // ============================================================================
 
+/// Embedding model used when the config doesn't set one
+pub const DEFAULT_EMBEDDING_MODEL: &str = "gemini-embedding-001";
+/// Model used by the OpenAI-compatible provider when the config doesn't set one
+pub const DEFAULT_OPENAI_EMBEDDING_MODEL: &str = "text-embedding-3-small";
+/// Endpoint base for the OpenAI-compatible provider
+pub const DEFAULT_OPENAI_EMBEDDING_BASE_URL: &str = "https://api.openai.com/v1/";
+/// Output dimensionality used when the config doesn't set one
+pub const DEFAULT_EMBEDDING_DIMS: u32 = 768;
+
+/// Native output size of an OpenAI-compatible model, used when no dimensions are configured
+pub fn native_openai_embedding_dims(model: &str) -> u32 {
+    if model.ends_with("text-embedding-3-large") {
+        3072
+    } else {
+        1536
+    }
+}
+
+/// Embedding provider, model + output dimensionality, read from `AppConfig`
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmbeddingSettings {
+    pub provider: EmbeddingProvider,
+    pub model: String,
+    pub dims: u32,
+    /// Dimensionality requested from the provider; None for an unconfigured OpenAI-compatible model
+    pub requested_dims: Option<u32>,
+    pub base_url: String, // OpenAI-compatible provider only
+    pub rpm: u32,         // Request pacing, 0 = unlimited
+}
+
+impl Default for EmbeddingSettings {
+    fn default() -> Self {
+        Self {
+            provider: EmbeddingProvider::Gemini,
+            model: DEFAULT_EMBEDDING_MODEL.to_string(),
+            dims: DEFAULT_EMBEDDING_DIMS,
+            requested_dims: Some(DEFAULT_EMBEDDING_DIMS),
+            base_url: DEFAULT_OPENAI_EMBEDDING_BASE_URL.to_string(),
+            rpm: crate::rate_limit::DEFAULT_EMBEDDING_RPM,
+        }
+    }
+}
+
+impl EmbeddingSettings {
+    pub fn from_config(config: &crate::config::AppConfig) -> Self {
+        let defaults = Self::default();
+        let provider = config.embedding_provider.unwrap_or_default();
+        let default_model = match provider {
+            EmbeddingProvider::Gemini => DEFAULT_EMBEDDING_MODEL,
+            EmbeddingProvider::OpenAi => DEFAULT_OPENAI_EMBEDDING_MODEL,
+        };
+        let non_empty = |value: &Option<String>| {
+            value
+                .as_deref()
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(|v| v.to_string())
+        };
+        let model = non_empty(&config.embedding_model).unwrap_or_else(|| default_model.to_string());
+        let configured_dims = config.embedding_dims.filter(|d| *d > 0);
+        // Many OpenAI-compatible models reject `dimensions`, so it is only sent when configured
+        let (dims, requested_dims) = match (provider, configured_dims) {
+            (_, Some(dims)) => (dims, Some(dims)),
+            (EmbeddingProvider::Gemini, None) => (defaults.dims, Some(defaults.dims)),
+            (EmbeddingProvider::OpenAi, None) => (native_openai_embedding_dims(&model), None),
+        };
+        Self {
+            provider,
+            model,
+            dims,
+            requested_dims,
+            base_url: non_empty(&config.embedding_base_url).unwrap_or(defaults.base_url),
+            rpm: config.embedding_rpm.unwrap_or(defaults.rpm),
+        }
+    }
+
+    /// Identifies the vector space: vectors from different providers or models
+    /// aren't comparable even at the same dimensionality
+    pub fn space_id(&self) -> String {
+        format!("{}:{}", self.provider.as_str(), self.model)
+    }
+}
+
+/// API key for the configured embeddings provider; RAG is skipped without one.
+/// The OpenAI-compatible provider only borrows a chat key when the base URL points
+/// at that key's own service, so keys never leak to a third-party endpoint.
+pub fn embedding_api_key(config: &crate::config::AppConfig) -> Option<&String> {
+    match config.embedding_provider.unwrap_or_default() {
+        EmbeddingProvider::Gemini => config.gemini_api_key.as_ref(),
+        EmbeddingProvider::OpenAi => {
+            if let Some(key) = config.embedding_api_key.as_ref().filter(|k| !k.trim().is_empty()) {
+                return Some(key);
+            }
+            let base_url = EmbeddingSettings::from_config(config).base_url;
+            if base_url.contains("openrouter.ai") {
+                config.openrouter_api_key.as_ref()
+            } else if base_url.contains("api.openai.com") {
+                config.api_key.as_ref()
+            } else {
+                None
+            }
+        }
+    }
+}
+
+static EMBEDDING_SETTINGS: std::sync::RwLock<Option<EmbeddingSettings>> = std::sync::RwLock::new(None);
+
+/// Apply embedding settings from config (called at startup and on config save)
+pub fn set_embedding_settings(settings: EmbeddingSettings) {
+    if let Ok(mut current) = EMBEDDING_SETTINGS.write() {
+        if current.as_ref() != Some(&settings) {
+            log::info!(
+                "[Embeddings] Using {} {} at {} dims",
+                settings.provider.as_str(),
+                settings.model,
+                settings.dims
+            );
+        }
+        *current = Some(settings);
+    }
+}
+
+/// Currently active embedding settings
+pub fn embedding_settings() -> EmbeddingSettings {
+    EMBEDDING_SETTINGS
+        .read()
+        .ok()
+        .and_then(|s| s.clone())
+        .unwrap_or_default()
+}
+
 pub async fn generate_embedding(
     client: &reqwest::Client,
     text: &str,
     api_key: &str,
 ) -> Result<Vec<f32>, String> {
-    let url = format!(
-        "https://generativelanguage.googleapis.com/v1beta/models/gemini-embedding-001:embedContent?key={}",
-        api_key
-    );
+    let settings = embedding_settings();
+    // Non-default models get their own cache namespace so vectors never cross models
+    let cache_text = match settings.provider {
+        EmbeddingProvider::Gemini if settings.model == DEFAULT_EMBEDDING_MODEL => text.to_string(),
+        EmbeddingProvider::Gemini => format!("[{}]\n{}", settings.model, text),
+        EmbeddingProvider::OpenAi => format!("[openai:{}]\n{}", settings.model, text),
+    };
+    // Only cache misses hit the API, so only they are paced
+    crate::embedding_cache::get_or_generate(&cache_text, settings.dims, || async {
+        crate::rate_limit::throttle_embeddings(settings.rpm).await;
+        let delay = crate::error::backoff_delay(0, crate::error::jitter_fraction());
+        retry_transient_embedding(delay, || request_embedding(client, text, api_key, &settings)).await
+    })
+    .await
+}
+
+/// Extra attempts made after a transient (429/5xx) embedding failure
+pub const EMBEDDING_RETRIES: u32 = 1;
 
-    let payload = EmbeddingRequest {
-        content: EmbeddingContent {
-            parts: vec![EmbeddingPart {
-                text: text.to_string(),
-            }],
+/// A failed embedding request, keeping the HTTP status so transient errors can be retried
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmbeddingFailure {
+    pub status: Option<u16>,
+    pub message: String,
+}
+
+/// Run an embedding request, retrying up to `EMBEDDING_RETRIES` times on 429/5xx after `delay`
+pub async fn retry_transient_embedding<F, Fut>(
+    delay: std::time::Duration,
+    mut request: F,
+) -> Result<Vec<f32>, String>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<Vec<f32>, EmbeddingFailure>>,
+{
+    let mut attempt = 0;
+    loop {
+        match request().await {
+            Ok(values) => return Ok(values),
+            Err(failure) => {
+                let transient = failure.status.is_some_and(crate::error::is_retryable_status);
+                if !transient || attempt >= EMBEDDING_RETRIES {
+                    return Err(failure.message);
+                }
+                log::warn!(
+                    "[Embeddings] Got {}, retrying in {}ms",
+                    failure.status.unwrap_or_default(),
+                    delay.as_millis()
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// A provider-specific embedding call, built before anything is sent
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmbeddingHttpRequest {
+    pub url: String,
+    pub bearer_token: Option<String>, // Gemini passes the key in the URL instead
+    pub body: serde_json::Value,
+}
+
+/// Build the embedding request for the configured provider
+pub fn build_embedding_request(settings: &EmbeddingSettings, text: &str, api_key: &str) -> EmbeddingHttpRequest {
+    match settings.provider {
+        EmbeddingProvider::Gemini => EmbeddingHttpRequest {
+            url: format!(
+                "https://generativelanguage.googleapis.com/v1beta/models/{}:embedContent?key={}",
+                settings.model, api_key
+            ),
+            bearer_token: None,
+            body: serde_json::to_value(EmbeddingRequest {
+                content: EmbeddingContent {
+                    parts: vec![EmbeddingPart {
+                        text: text.to_string(),
+                    }],
+                },
+                output_dimensionality: settings.requested_dims,
+            })
+            .unwrap_or_default(),
+        },
+        EmbeddingProvider::OpenAi => EmbeddingHttpRequest {
+            url: format!("{}/embeddings", settings.base_url.trim_end_matches('/')),
+            bearer_token: Some(api_key.to_string()),
+            body: serde_json::to_value(OpenAiEmbeddingRequest {
+                model: &settings.model,
+                input: text,
+                dimensions: settings.requested_dims,
+            })
+            .unwrap_or_default(),
         },
-        output_dimensionality: Some(768),
+    }
+}
+
+/// Extract the vector from a provider's embedding response body
+pub fn parse_embedding_response(provider: EmbeddingProvider, body: &str) -> Result<Vec<f32>, String> {
+    let parsed = match provider {
+        EmbeddingProvider::Gemini => {
+            serde_json::from_str::<EmbeddingResponse>(body).map(|r| Some(r.embedding.values))
+        }
+        EmbeddingProvider::OpenAi => serde_json::from_str::<OpenAiEmbeddingResponse>(body)
+            .map(|r| r.data.into_iter().next().map(|d| d.embedding)),
     };
+    match parsed {
+        Ok(Some(values)) => Ok(values),
+        Ok(None) => Err("Embedding response contained no vectors".to_string()),
+        Err(e) => Err(format!("Failed to parse embedding response: {}", e)),
+    }
+}
+
+/// Call the embedding API directly (bypasses the embedding cache)
+async fn request_embedding(
+    client: &reqwest::Client,
+    text: &str,
+    api_key: &str,
+    settings: &EmbeddingSettings,
+) -> Result<Vec<f32>, EmbeddingFailure> {
+    let request = build_embedding_request(settings, text, api_key);
+    let mut builder = client.post(&request.url).json(&request.body);
+    if let Some(token) = &request.bearer_token {
+        builder = builder.bearer_auth(token);
+    }
 
-    let res = client
-        .post(&url)
-        .json(&payload)
+    let res = builder
         .send()
         .await
-        .map_err(|e| format!("Embedding API network error: {}", e))?;
+        .map_err(|e| EmbeddingFailure {
+            status: None,
+            message: format!("Embedding API network error: {}", e),
+        })?;
 
-    if !res.status().is_success() {
+    let status = res.status();
+    if !status.is_success() {
         let error_text = res.text().await.unwrap_or_default();
-        return Err(format!("Embedding API error: {}", error_text));
+        return Err(EmbeddingFailure {
+            status: Some(status.as_u16()),
+            message: format!("Embedding API error: {}", error_text),
+        });
     }
 
-    let body: EmbeddingResponse = res
-        .json()
-        .await
-        .map_err(|e| format!("Failed to parse embedding response: {}", e))?;
+    let body = res.text().await.map_err(|e| EmbeddingFailure {
+        status: None,
+        message: format!("Failed to read embedding response: {}", e),
+    })?;
 
-    Ok(body.embedding.values)
+    parse_embedding_response(settings.provider, &body).map_err(|message| EmbeddingFailure { status: None, message })
 }
 
 // ============================================================================
@@ -119,54 +399,646 @@ fn get_interactions_dir<R: Runtime>(app_handle: &AppHandle<R>) -> Result<PathBuf
     Ok(dir)
 }
 
-fn get_today_log_path<R: Runtime>(app_handle: &AppHandle<R>) -> Result<PathBuf, String> {
-    let dir = get_interactions_dir(app_handle)?;
-    let today = Utc::now().format("%Y-%m-%d").to_string();
-    Ok(dir.join(format!("interactions-{}.jsonl", today)))
+/// An interaction waiting to be written (carries its embedding so retries don't re-embed)
+#[derive(Debug, Clone)]
+pub struct PendingInteraction {
+    pub entry: InteractionEntry,
+    /// JSONL line already appended; only the BM25 update remains
+    pub jsonl_written: bool,
 }
 
-pub async fn log_interaction<R: Runtime>(
-    app_handle: &AppHandle<R>,
-    role: &str,
-    content: &str,
-    embedding: Option<Vec<f32>>,
+impl PendingInteraction {
+    pub fn new(role: &str, content: &str, embedding: Option<Vec<f32>>) -> Self {
+        Self {
+            entry: InteractionEntry {
+                ts: Utc::now(),
+                role: role.to_string(),
+                content: content.to_string(),
+                embedding_model: embedding.as_ref().map(|_| embedding_settings().space_id()),
+                embedding,
+            },
+            jsonl_written: false,
+        }
+    }
+}
+
+/// Serializes appends to the logs with in-place rewrites (backfill, repair, dedup, compaction)
+static LOG_LOCK: Mutex<()> = Mutex::new(());
+
+pub(crate) fn lock_interaction_logs() -> MutexGuard<'static, ()> {
+    // Appends and rewrites are each atomic on disk, so a poisoned lock is still usable
+    LOG_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Append an interaction to its daily JSONL file and index it for BM25.
+/// Uses the entry's own timestamp so retried writes land in the right day/doc_id.
+pub fn write_interaction_in_dir(
+    dir: &std::path::Path,
+    pending: &mut PendingInteraction,
 ) -> Result<(), String> {
-    let entry = InteractionEntry {
-        ts: Utc::now(),
-        role: role.to_string(),
-        content: content.to_string(),
-        embedding,
-    };
+    let entry = &pending.entry;
 
-    let path = get_today_log_path(app_handle)?;
+    if !pending.jsonl_written {
+        if !dir.exists() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create interactions dir: {}", e))?;
+        }
+        let path = dir.join(format!("interactions-{}.jsonl", entry.ts.format("%Y-%m-%d")));
+        let _log_guard = lock_interaction_logs();
+
+        // A previous crash may have left a partial line without a newline;
+        // start on a fresh line so this entry stays parseable
+        let needs_newline = !ends_with_newline(&path);
 
-    let file = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(&path)
-        .map_err(|e| format!("Failed to open interaction log: {}", e))?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("Failed to open interaction log: {}", e))?;
 
-    let mut writer = std::io::BufWriter::new(file);
-    let json = serde_json::to_string(&entry)
-        .map_err(|e| format!("Failed to serialize interaction: {}", e))?;
+        let mut writer = std::io::BufWriter::new(file);
+        let json = serde_json::to_string(entry)
+            .map_err(|e| format!("Failed to serialize interaction: {}", e))?;
 
-    writeln!(writer, "{}", json)
-        .map_err(|e| format!("Failed to write interaction: {}", e))?;
+        if needs_newline {
+            writeln!(writer).map_err(|e| format!("Failed to write interaction: {}", e))?;
+        }
+        writeln!(writer, "{}", json)
+            .map_err(|e| format!("Failed to write interaction: {}", e))?;
+        writer
+            .flush()
+            .map_err(|e| format!("Failed to write interaction: {}", e))?;
+        // fsync so an app kill right after a turn can't truncate the line
+        writer
+            .get_ref()
+            .sync_all()
+            .map_err(|e| format!("Failed to sync interaction log: {}", e))?;
+        pending.jsonl_written = true;
+    }
 
-    // Also update BM25 index for hybrid retrieval
+    // Also update BM25 index for hybrid retrieval (add_document is idempotent per doc_id)
     let doc_id = entry.ts.to_rfc3339();
-    let mut bm25_index = crate::retrieval::load_bm25_index(app_handle)?;
-    bm25_index.add_document(&doc_id, content);
-    crate::retrieval::save_bm25_index(app_handle, &bm25_index)?;
+    let index_path = dir.join(crate::retrieval::BM25_INDEX_FILENAME);
+    let _guard = crate::retrieval::lock_bm25_index();
+    let mut bm25_index = crate::retrieval::load_bm25_index_from(&index_path)?;
+    bm25_index.add_document(&doc_id, &entry.content, Some(entry.ts));
+    crate::retrieval::save_bm25_index_to(&index_path, &bm25_index)?;
+    drop(_guard);
+
+    // Keep the HNSW index in sync; a failure here only costs recall until the next rebuild
+    if let Some(embedding) = &entry.embedding {
+        match crate::vector_index::append_to_dense_index_in_dir(dir, &doc_id, embedding) {
+            Ok(true) => crate::vector_index::schedule_pending_fold(dir),
+            Ok(false) => {}
+            Err(e) => log::warn!("Failed to add interaction to dense index: {}", e),
+        }
+    }
 
     Ok(())
 }
 
+/// Maximum failed writes kept for retry before the oldest are dropped
+pub const MAX_PENDING_INTERACTIONS: usize = 100;
+
+/// Dead-letter buffer for interaction writes that failed transiently
+#[derive(Debug, Default)]
+pub struct InteractionRetryQueue {
+    pending: Vec<PendingInteraction>,
+}
+
+impl InteractionRetryQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Queue a failed write, dropping the oldest entry when full
+    pub fn push(&mut self, pending: PendingInteraction) {
+        if self.pending.len() >= MAX_PENDING_INTERACTIONS {
+            let dropped = self.pending.remove(0);
+            log::warn!(
+                "[Interactions] Retry queue full, dropping entry from {}",
+                dropped.entry.ts
+            );
+        }
+        self.pending.push(pending);
+    }
+
+    /// Retry every queued write in order; failures stay queued.
+    /// Returns the number of entries successfully flushed.
+    pub fn flush_with<F>(&mut self, mut write: F) -> usize
+    where
+        F: FnMut(&mut PendingInteraction) -> Result<(), String>,
+    {
+        let mut flushed = 0;
+        let mut remaining = Vec::new();
+        for mut pending in self.pending.drain(..) {
+            match write(&mut pending) {
+                Ok(()) => flushed += 1,
+                Err(e) => {
+                    log::warn!("[Interactions] Retry failed for {}: {}", pending.entry.ts, e);
+                    remaining.push(pending);
+                }
+            }
+        }
+        self.pending = remaining;
+        flushed
+    }
+
+    /// Retry every queued write against an interactions directory
+    pub fn flush_to_dir(&mut self, dir: &std::path::Path) -> usize {
+        self.flush_with(|pending| write_interaction_in_dir(dir, pending))
+    }
+}
+
+// ============================================================================
+// Log Repair
+// ============================================================================
+
+/// True if the file is missing, empty, or its last byte is a newline
+fn ends_with_newline(path: &std::path::Path) -> bool {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let Ok(mut file) = fs::File::open(path) else {
+        return true;
+    };
+    if file.metadata().map(|m| m.len() == 0).unwrap_or(true) {
+        return true;
+    }
+    let mut last = [0u8; 1];
+    file.seek(SeekFrom::End(-1)).is_ok() && file.read_exact(&mut last).is_ok() && last[0] == b'\n'
+}
+
+/// Drop a trailing partial line (unterminated or unparseable) left by an interrupted write.
+/// Earlier lines are kept as-is, since they may be entries from another schema version.
+/// Returns the number of lines removed (0 or 1).
+pub fn repair_jsonl_file(path: &std::path::Path) -> Result<usize, String> {
+    let _guard = lock_interaction_logs();
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    let terminated = bytes.ends_with(b"\n");
+    let body = bytes.strip_suffix(b"\n").unwrap_or(&bytes);
+    let start = body.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1);
+    let final_line = &body[start..];
+    if final_line.trim_ascii().is_empty()
+        || (terminated && serde_json::from_slice::<InteractionEntry>(final_line).is_ok())
+    {
+        return Ok(0);
+    }
+
+    let tmp_path = path.with_extension("jsonl.tmp");
+    fs::write(&tmp_path, &bytes[..start]).map_err(|e| format!("Failed to write repaired log: {}", e))?;
+    fs::rename(&tmp_path, path).map_err(|e| format!("Failed to replace log: {}", e))?;
+
+    log::warn!("[Interactions] Repaired {}: dropped a partial final line", path.display());
+    Ok(1)
+}
+
+/// Repair every JSONL log in a directory, returning the total lines dropped
+pub fn repair_interaction_logs_in_dir(dir: &std::path::Path) -> Result<usize, String> {
+    if !dir.exists() {
+        return Ok(0);
+    }
+    let entries = fs::read_dir(dir).map_err(|e| format!("Failed to read interactions dir: {}", e))?;
+
+    let mut repaired = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|s| s.to_str()) == Some("jsonl") {
+            repaired += repair_jsonl_file(&path)?;
+        }
+    }
+    Ok(repaired)
+}
+
+/// Scan all interaction logs and drop partial/corrupt lines
+pub fn repair_interaction_logs<R: Runtime>(app_handle: &AppHandle<R>) -> Result<usize, String> {
+    let dir = get_interactions_dir(app_handle)?;
+    let repaired = repair_interaction_logs_in_dir(&dir)?;
+    log::info!("[Interactions] Log repair complete: {} line(s) dropped", repaired);
+    Ok(repaired)
+}
+
+// ============================================================================
+// Log Compaction
+// ============================================================================
+
+/// Daily logs older than this many days are merged into monthly archives
+pub const DEFAULT_COMPACTION_AGE_DAYS: i64 = 30;
+
+/// Span covered by one interaction log file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogPeriod {
+    /// `interactions-YYYY-MM-DD.jsonl`
+    Day(String),
+    /// `interactions-YYYY-MM.jsonl` (compacted archive)
+    Month(String),
+}
+
+impl LogPeriod {
+    /// Whether the file can contain entries on or after `day` (`YYYY-MM-DD`)
+    pub fn reaches(&self, day: &str) -> bool {
+        match self {
+            LogPeriod::Day(d) => d.as_str() >= day,
+            LogPeriod::Month(m) => m.as_str() >= &day[..day.len().min(7)],
+        }
+    }
+
+    /// Whether every entry in the file falls before `day` (`YYYY-MM-DD`)
+    pub fn ends_before(&self, day: &str) -> bool {
+        !self.reaches(day)
+    }
+}
+
+/// Parse the period from a log file stem; None for files that aren't interaction logs
+pub fn parse_log_period(stem: &str) -> Option<LogPeriod> {
+    let date = stem.strip_prefix("interactions-")?;
+    match date.len() {
+        10 if chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok() => {
+            Some(LogPeriod::Day(date.to_string()))
+        }
+        7 if chrono::NaiveDate::parse_from_str(&format!("{}-01", date), "%Y-%m-%d").is_ok() => {
+            Some(LogPeriod::Month(date.to_string()))
+        }
+        _ => None,
+    }
+}
+
+/// Log files that may hold the entry logged at `ts`: its daily file, then its monthly archive
+fn log_paths_for(dir: &std::path::Path, ts: &DateTime<Utc>) -> [PathBuf; 2] {
+    [
+        dir.join(format!("interactions-{}.jsonl", ts.format("%Y-%m-%d"))),
+        dir.join(format!("interactions-{}.jsonl", ts.format("%Y-%m"))),
+    ]
+}
+
+/// Merge daily logs older than `older_than_days` into `interactions-YYYY-MM.jsonl`
+/// archives, appending in date order, then delete the dailies. Returns files merged.
+pub fn compact_interaction_logs_in_dir(
+    dir: &std::path::Path,
+    older_than_days: i64,
+) -> Result<usize, String> {
+    if !dir.exists() {
+        return Ok(0);
+    }
+    let cutoff = (Utc::now() - chrono::Duration::days(older_than_days))
+        .format("%Y-%m-%d")
+        .to_string();
+
+    let mut by_month: std::collections::BTreeMap<String, Vec<(String, PathBuf)>> =
+        std::collections::BTreeMap::new();
+    let entries = fs::read_dir(dir).map_err(|e| format!("Failed to read interactions dir: {}", e))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|s| s.to_str()) != Some("jsonl") {
+            continue;
+        }
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+        if let Some(LogPeriod::Day(day)) = parse_log_period(stem) {
+            if day < cutoff {
+                by_month.entry(day[..7].to_string()).or_default().push((day, path));
+            }
+        }
+    }
+
+    // A retried write for an old day appends to its daily file, which is about to be removed
+    let _log_guard = lock_interaction_logs();
+    let mut merged = 0;
+    for (month, mut dailies) in by_month {
+        dailies.sort();
+        let archive_path = dir.join(format!("interactions-{}.jsonl", month));
+        let mut content = if archive_path.exists() {
+            fs::read_to_string(&archive_path)
+                .map_err(|e| format!("Failed to read {}: {}", archive_path.display(), e))?
+        } else {
+            String::new()
+        };
+
+        for (_, path) in &dailies {
+            let daily = fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            if !content.is_empty() && !content.ends_with('\n') {
+                content.push('\n');
+            }
+            content.push_str(&daily);
+        }
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+
+        // Archive is written in full before any daily is removed, so a crash loses nothing
+        crate::retrieval::write_atomic(&archive_path, content)
+            .map_err(|e| format!("Failed to write {}: {}", archive_path.display(), e))?;
+        for (_, path) in &dailies {
+            if let Err(e) = fs::remove_file(path) {
+                log::warn!("[Interactions] Merged {} but failed to delete it: {}", path.display(), e);
+            }
+        }
+        merged += dailies.len();
+    }
+    Ok(merged)
+}
+
+/// Merge old daily interaction logs into monthly archives
+pub fn compact_interaction_logs<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    older_than_days: i64,
+) -> Result<usize, String> {
+    let dir = get_interactions_dir(app_handle)?;
+    let merged = compact_interaction_logs_in_dir(&dir, older_than_days)?;
+    log::info!("[Interactions] Compacted {} daily log(s) into monthly archives", merged);
+    Ok(merged)
+}
+
+// ============================================================================
+// Deletion
+// ============================================================================
+
+/// Remove every logged line with this exact timestamp from the JSONL logs, the BM25
+/// index and the dense index. Returns the number of lines removed.
+pub fn delete_interaction_in_dir(dir: &std::path::Path, timestamp: &str) -> Result<usize, String> {
+    // Cleanup matches timestamps by substring, so insist on a full one here
+    let ts = DateTime::parse_from_rfc3339(timestamp.trim())
+        .map_err(|e| format!("Invalid interaction timestamp '{}': {}", timestamp, e))?
+        .with_timezone(&Utc);
+    // Logs hold the serde form ("...Z"); callers may pass the "+00:00" form
+    let mut forms = vec![timestamp.trim().to_string()];
+    let serde_form = ts.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true);
+    if !forms.contains(&serde_form) {
+        forms.push(serde_form);
+    }
+
+    let (removed, _, doc_ids) = crate::background::remove_entries_by_timestamp(dir, &forms)?;
+    if removed == 0 {
+        return Ok(0);
+    }
+
+    crate::retrieval::remove_bm25_documents_at(&dir.join(crate::retrieval::BM25_INDEX_FILENAME), &doc_ids)?;
+    crate::vector_index::remove_from_dense_index_in_dir(dir, &doc_ids)?;
+
+    log::info!("[Interactions] Deleted {} line(s) logged at {}", removed, timestamp);
+    Ok(removed)
+}
+
+/// Delete a specific interaction from the RAG store
+pub fn delete_interaction<R: Runtime>(app_handle: &AppHandle<R>, timestamp: &str) -> Result<usize, String> {
+    let dir = get_interactions_dir(app_handle)?;
+    delete_interaction_in_dir(&dir, timestamp)
+}
+
+// ============================================================================
+// Duplicate Merging
+// ============================================================================
+
+/// Embedding similarity at or above which two interactions count as duplicates
+pub const DUPLICATE_SIMILARITY: f32 = 0.97;
+/// Duplicates are only merged when logged within this many hours of their representative
+pub const DUPLICATE_WINDOW_HOURS: i64 = 24;
+
+/// Timestamps of near-duplicate interactions to drop. Each cluster (same role, cosine
+/// >= `threshold`, within `window` of its first entry) keeps its earliest entry.
+/// Entries without an embedding are never merged, and a timestamp shared with a kept
+/// entry is never returned since removal works by timestamp.
+pub fn find_duplicate_interactions(
+    entries: &[InteractionEntry],
+    threshold: f32,
+    window: chrono::Duration,
+) -> Vec<DateTime<Utc>> {
+    let mut sorted: Vec<&InteractionEntry> = entries.iter().collect();
+    sorted.sort_by_key(|e| e.ts);
+
+    // Representatives in time order; ones older than `window` can't absorb anything later
+    let mut representatives: std::collections::VecDeque<(&InteractionEntry, &[f32])> =
+        std::collections::VecDeque::new();
+    let mut duplicates = std::collections::BTreeSet::new();
+    let mut kept = std::collections::HashSet::new();
+    for entry in sorted {
+        let Some(embedding) = entry.embedding.as_deref().filter(|e| !e.is_empty()) else {
+            kept.insert(entry.ts);
+            continue;
+        };
+        while representatives.front().is_some_and(|(rep, _)| entry.ts - rep.ts > window) {
+            representatives.pop_front();
+        }
+        let is_duplicate = representatives.iter().any(|(rep, rep_embedding)| {
+            rep.role == entry.role
+                && rep.embedding_model == entry.embedding_model
+                && cosine_similarity(rep_embedding, embedding) >= threshold
+        });
+        if is_duplicate {
+            duplicates.insert(entry.ts);
+        } else {
+            kept.insert(entry.ts);
+            representatives.push_back((entry, embedding));
+        }
+    }
+
+    duplicates.into_iter().filter(|ts| !kept.contains(ts)).collect()
+}
+
+/// Collapse near-duplicate interactions in the logs, removing the extras from the
+/// JSONL files, BM25 and the dense index. Returns the number of lines removed.
+pub fn merge_duplicate_interactions_in_dir(dir: &std::path::Path) -> Result<usize, String> {
+    if !dir.exists() {
+        return Ok(0);
+    }
+
+    let mut entries = Vec::new();
+    let files = fs::read_dir(dir).map_err(|e| format!("Failed to read interactions dir: {}", e))?;
+    for path in files.flatten().map(|e| e.path()) {
+        if path.extension().and_then(|s| s.to_str()) != Some("jsonl") {
+            continue;
+        }
+        let Ok(file) = fs::File::open(&path) else {
+            continue;
+        };
+        for line in BufReader::new(file).lines().map_while(Result::ok) {
+            if let Ok(entry) = serde_json::from_str::<InteractionEntry>(&line) {
+                entries.push(entry);
+            }
+        }
+    }
+
+    let duplicates = find_duplicate_interactions(
+        &entries,
+        DUPLICATE_SIMILARITY,
+        chrono::Duration::hours(DUPLICATE_WINDOW_HOURS),
+    );
+    if duplicates.is_empty() {
+        return Ok(0);
+    }
+
+    // Match the serde form the logs were written with
+    let timestamps: Vec<String> = duplicates
+        .iter()
+        .map(|ts| ts.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true))
+        .collect();
+    let (removed, _, doc_ids) = crate::background::remove_entries_by_timestamp(dir, &timestamps)?;
+    if removed == 0 {
+        return Ok(0);
+    }
+
+    crate::retrieval::remove_bm25_documents_at(&dir.join(crate::retrieval::BM25_INDEX_FILENAME), &doc_ids)?;
+    crate::vector_index::remove_from_dense_index_in_dir(dir, &doc_ids)?;
+
+    log::info!("[Interactions] Merged {} near-duplicate interaction(s)", removed);
+    Ok(removed)
+}
+
+/// Collapse near-duplicate interactions in the RAG store
+pub fn merge_duplicate_interactions<R: Runtime>(app_handle: &AppHandle<R>) -> Result<usize, String> {
+    let dir = get_interactions_dir(app_handle)?;
+    merge_duplicate_interactions_in_dir(&dir)
+}
+
+// ============================================================================
+// Embedding Backfill
+// ============================================================================
+
+/// Write backfilled embeddings into a daily log under the log lock, matching lines by
+/// timestamp against the file as it is now. Returns the entries that were filled.
+fn merge_backfilled_embeddings(
+    path: &std::path::Path,
+    embeddings: &mut std::collections::HashMap<DateTime<Utc>, Vec<f32>>,
+) -> Result<Vec<InteractionEntry>, String> {
+    let space = embedding_settings().space_id();
+    let _guard = lock_interaction_logs();
+    let current = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let mut lines = Vec::new();
+    let mut added = Vec::new();
+    for line in current.lines() {
+        let entry = serde_json::from_str::<InteractionEntry>(line)
+            .ok()
+            .filter(|entry| entry.embedding.as_ref().filter(|e| !e.is_empty()).is_none());
+        let Some((mut entry, embedding)) =
+            entry.and_then(|entry| embeddings.remove(&entry.ts).map(|embedding| (entry, embedding)))
+        else {
+            lines.push(line.to_string());
+            continue;
+        };
+        entry.embedding = Some(embedding);
+        entry.embedding_model = Some(space.clone());
+        lines.push(serde_json::to_string(&entry).map_err(|e| format!("Failed to serialize interaction: {}", e))?);
+        added.push(entry);
+    }
+
+    if !added.is_empty() {
+        let mut rewritten = lines.join("\n");
+        rewritten.push('\n');
+        crate::retrieval::write_atomic(path, rewritten)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+    Ok(added)
+}
+
+/// Embed logged interactions that were written without an embedding (e.g. while no
+/// embeddings key was configured), filling their lines in place and adding them to
+/// the dense index. Stops at the first failed embed so a bad key or exhausted quota
+/// isn't hammered; lines filled before that are kept. Returns the number filled.
+///
+/// Embeds run without holding the log lock; each file is then re-read under the lock
+/// and merged by timestamp, so turns appended meanwhile aren't lost by the rewrite.
+pub async fn backfill_embeddings_in_dir<F, Fut>(dir: &std::path::Path, mut embed: F) -> Result<usize, String>
+where
+    F: FnMut(String) -> Fut,
+    Fut: std::future::Future<Output = Result<Vec<f32>, String>>,
+{
+    if !dir.exists() {
+        return Ok(0);
+    }
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read interactions dir: {}", e))?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("jsonl"))
+        .collect();
+    paths.sort();
+
+    let mut filled = 0;
+    let mut failed = false;
+    for path in paths {
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let missing: Vec<InteractionEntry> = content
+            .lines()
+            .filter_map(|line| serde_json::from_str::<InteractionEntry>(line).ok())
+            .filter(|entry| {
+                entry.embedding.as_ref().filter(|e| !e.is_empty()).is_none() && !entry.content.trim().is_empty()
+            })
+            .collect();
+
+        let mut embeddings: std::collections::HashMap<DateTime<Utc>, Vec<f32>> = std::collections::HashMap::new();
+        for entry in missing {
+            match embed(entry.content.clone()).await {
+                Ok(embedding) => {
+                    embeddings.insert(entry.ts, embedding);
+                }
+                Err(e) => {
+                    log::warn!(
+                        "[Interactions] Embedding backfill stopped after {} entries: {}",
+                        filled + embeddings.len(),
+                        e
+                    );
+                    failed = true;
+                    break;
+                }
+            }
+        }
+
+        if !embeddings.is_empty() {
+            let added = merge_backfilled_embeddings(&path, &mut embeddings)?;
+
+            for entry in &added {
+                if let Some(embedding) = &entry.embedding {
+                    match crate::vector_index::append_to_dense_index_in_dir(dir, &entry.ts.to_rfc3339(), embedding) {
+                        Ok(true) => crate::vector_index::schedule_pending_fold(dir),
+                        Ok(false) => {}
+                        Err(e) => log::warn!("Failed to add interaction to dense index: {}", e),
+                    }
+                }
+            }
+            filled += added.len();
+        }
+        if failed {
+            break;
+        }
+    }
+
+    log::info!("[Interactions] Backfilled embeddings for {} interaction(s)", filled);
+    Ok(filled)
+}
+
+/// Embed old interactions that were logged without one; a no-op without an embeddings key
+pub async fn backfill_embeddings<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    http_client: &reqwest::Client,
+) -> Result<usize, String> {
+    let config = crate::config::load_config(app_handle)?;
+    let Some(api_key) = embedding_api_key(&config).filter(|k| !k.trim().is_empty()) else {
+        log::info!("[Interactions] No embeddings key configured; skipping backfill");
+        return Ok(0);
+    };
+    let dir = get_interactions_dir(app_handle)?;
+    backfill_embeddings_in_dir(&dir, |text| async move { generate_embedding(http_client, &text, api_key).await })
+        .await
+}
+
 // ============================================================================
 // RAG Retrieval
 // ============================================================================
 
 pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    // Vectors from different embedding dims aren't comparable
+    if a.len() != b.len() {
+        return 0.0;
+    }
     let dot_product: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
     let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
     let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
@@ -187,6 +1059,7 @@ pub fn search_interactions<R: Runtime>(
 ) -> Result<Vec<InteractionEntry>, String> {
     let dir = get_interactions_dir(app_handle)?;
     let mut results: Vec<(f32, InteractionEntry)> = Vec::new();
+    let space = embedding_settings().space_id();
 
     // Read all jsonl files in the directory
     // In a production system, we'd use a proper vector DB or index,
@@ -199,7 +1072,8 @@ pub fn search_interactions<R: Runtime>(
                     let reader = BufReader::new(file);
                     for line in reader.lines().flatten() {
                         if let Ok(entry) = serde_json::from_str::<InteractionEntry>(&line) {
-                            if let Some(emb) = &entry.embedding {
+                            // Skip entries embedded by another model or at a different dimensionality
+                            if let Some(emb) = entry.embedding_in_space(&space, query_embedding.len()) {
                                 let score = cosine_similarity(query_embedding, emb);
                                 results.push((score, entry));
                             }
@@ -217,10 +1091,37 @@ pub fn search_interactions<R: Runtime>(
     Ok(results.into_iter().take(limit).map(|(_, entry)| entry).collect())
 }
 
+/// A fused hybrid search result with a snippet around the matched query terms
+#[derive(Debug, Clone)]
+pub struct InteractionSearchHit {
+    /// Fused hit (RRF score after the temporal boost)
+    pub hit: ScoredHit,
+    /// Every retriever that returned this doc before fusion
+    pub sources: Vec<HitSource>,
+    pub entry: InteractionEntry,
+    pub snippet: Snippet,
+}
+
+/// A topic summary chunk that ranked alongside the interactions in a hybrid search
+#[derive(Debug, Clone)]
+pub struct TopicChunkHit {
+    /// Fused hit (RRF score after the temporal boost)
+    pub hit: ScoredHit,
+    pub topic: String,
+    pub content: String,
+}
+
+/// Fused hybrid search results, split by kind (a topic chunk is not an interaction)
+#[derive(Debug, Clone, Default)]
+pub struct HybridSearchResults {
+    pub interactions: Vec<InteractionSearchHit>,
+    pub topic_chunks: Vec<TopicChunkHit>,
+}
+
 /// Hybrid search using RRF to fuse BM25 and dense retrieval results
 ///
 /// Features:
-/// - N-list RRF fusion (currently BM25 + dense interactions)
+/// - N-list RRF fusion (BM25 + dense interactions + dense topic chunks)
 /// - Fallback to BM25-only when dense results are sparse
 /// - Temporal boost for recency-sensitive queries
 pub fn hybrid_search_interactions<R: Runtime>(
@@ -229,31 +1130,204 @@ pub fn hybrid_search_interactions<R: Runtime>(
     query_embedding: &[f32],
     limit: usize,
 ) -> Result<Vec<InteractionEntry>, String> {
-    // Get BM25 results (N = 50 candidates)
-    let bm25_index = load_bm25_index(app_handle)?;
-    let bm25_results = bm25_index.search(query, 50);
+    let hits = hybrid_search_hits(app_handle, query, query_embedding, limit)?;
+    Ok(hits.interactions.into_iter().map(|h| h.entry).collect())
+}
+
+/// Hybrid search returning fused hits with their sources and snippets
+pub fn hybrid_search_hits<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    query: &str,
+    query_embedding: &[f32],
+    limit: usize,
+) -> Result<HybridSearchResults, String> {
+    let dir = get_interactions_dir(app_handle)?;
+    hybrid_search_hits_in_dir(
+        &dir,
+        query,
+        query_embedding,
+        &topic_chunk_hits(app_handle, query_embedding),
+        |topic, idx| crate::memories::read_topic_chunk(app_handle, topic, idx).ok(),
+        limit,
+        &load_retrieval_tuning(app_handle),
+    )
+}
+
+fn load_retrieval_tuning<R: Runtime>(app_handle: &AppHandle<R>) -> RetrievalTuning {
+    crate::config::load_config(app_handle)
+        .map(|config| RetrievalTuning::from_config(&config))
+        .unwrap_or_default()
+}
+
+/// Topic chunk hits (N = 10 candidates), dated by the topic file's last update
+fn topic_chunk_hits<R: Runtime>(app_handle: &AppHandle<R>, query_embedding: &[f32]) -> Vec<ScoredHit> {
+    let topics_dir = crate::memories::get_topics_dir(app_handle).ok();
+    crate::memories::find_topic_chunk_hits(app_handle, query_embedding, 10)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|mut hit| {
+            hit.ts = crate::memories::parse_chunk_key(&hit.doc_id)
+                .zip(topics_dir.as_ref())
+                .and_then(|((topic, _), dir)| topic_file_modified(dir, topic));
+            hit
+        })
+        .collect()
+}
+
+/// Hybrid search over the logs and indexes in `dir`, fused with precomputed topic chunk hits
+/// (`read_chunk` resolves a fused topic chunk to its text)
+pub fn hybrid_search_interactions_in_dir<F>(
+    dir: &std::path::Path,
+    query: &str,
+    query_embedding: &[f32],
+    topic_chunk_hits: &[ScoredHit],
+    read_chunk: F,
+    limit: usize,
+    tuning: &RetrievalTuning,
+) -> Result<Vec<InteractionEntry>, String>
+where
+    F: Fn(&str, usize) -> Option<String>,
+{
+    let hits =
+        hybrid_search_hits_in_dir(dir, query, query_embedding, topic_chunk_hits, read_chunk, limit, tuning)?;
+    Ok(hits.interactions.into_iter().map(|h| h.entry).collect())
+}
+
+/// Core of `hybrid_search_hits` for an explicit interactions dir
+pub fn hybrid_search_hits_in_dir<F>(
+    dir: &std::path::Path,
+    query: &str,
+    query_embedding: &[f32],
+    topic_chunk_hits: &[ScoredHit],
+    read_chunk: F,
+    limit: usize,
+    tuning: &RetrievalTuning,
+) -> Result<HybridSearchResults, String>
+where
+    F: Fn(&str, usize) -> Option<String>,
+{
+    let candidates = gather_candidates(dir, query, query_embedding, tuning)?;
+    let fused = fuse_interaction_hits(&candidates.bm25, &candidates.dense, topic_chunk_hits, limit, tuning);
+
+    let mut results = HybridSearchResults::default();
+    for scored in fused {
+        if scored.source == HitSource::DenseTopicChunk {
+            results.topic_chunks.extend(resolve_topic_chunk(scored, &read_chunk));
+            continue;
+        }
+        let Some(entry) = candidates.resolve(dir, &scored) else {
+            continue;
+        };
+
+        let sources = [
+            (HitSource::Bm25, &candidates.bm25[..]),
+            (HitSource::DenseInteraction, &candidates.dense[..]),
+        ]
+        .into_iter()
+        .filter(|(_, hits)| hits.iter().any(|h| h.doc_id == scored.doc_id))
+        .map(|(source, _)| source)
+        .collect();
+        let snippet = make_snippet(&entry.content, query, SNIPPET_CONTEXT_CHARS);
+
+        results.interactions.push(InteractionSearchHit {
+            hit: scored,
+            sources,
+            entry,
+            snippet,
+        });
+    }
+
+    Ok(results)
+}
+
+/// Load the text behind a fused topic chunk hit ("topic#idx")
+fn resolve_topic_chunk<F>(scored: ScoredHit, read_chunk: &F) -> Option<TopicChunkHit>
+where
+    F: Fn(&str, usize) -> Option<String>,
+{
+    let (topic, idx) = crate::memories::parse_chunk_key(&scored.doc_id)?;
+    let content = read_chunk(topic, idx)?;
+    Some(TopicChunkHit { topic: topic.to_string(), content, hit: scored })
+}
+
+/// Ranked BM25 and dense interaction candidates, before fusion
+struct HybridCandidates {
+    bm25: Vec<ScoredHit>,
+    dense: Vec<ScoredHit>,
+    /// Entries already loaded by the dense search, keyed by doc_id
+    entries: std::collections::HashMap<String, InteractionEntry>,
+}
+
+impl HybridCandidates {
+    /// Map a fused interaction hit back to its entry
+    fn resolve(&self, dir: &std::path::Path, scored: &ScoredHit) -> Option<InteractionEntry> {
+        if let Some(entry) = self.entries.get(&scored.doc_id) {
+            Some(entry.clone())
+        } else {
+            // Entry was in BM25 but not in dense (no embedding) - load from JSONL
+            find_entry_by_doc_id(dir, &scored.doc_id).ok()
+        }
+    }
+}
+
+/// Run the BM25 and dense retrievers over the interactions in `dir`
+fn gather_candidates(
+    dir: &std::path::Path,
+    query: &str,
+    query_embedding: &[f32],
+    tuning: &RetrievalTuning,
+) -> Result<HybridCandidates, String> {
+    // Get BM25 results (N = 50 candidates), dropping weak lexical matches
+    let bm25_index = load_bm25_index_from(&dir.join(crate::retrieval::BM25_INDEX_FILENAME))?;
+    let bm25_results =
+        bm25_index.search_with_scores_normalized(&tuning.bm25_query(query), 50, BM25_MIN_NORMALIZED_SCORE);
 
     // Convert BM25 results to ScoredHit
-    let bm25_hits: Vec<ScoredHit> = bm25_results
+    let bm25: Vec<ScoredHit> = bm25_results
         .iter()
-        .map(|d| {
-            let ts = chrono::DateTime::parse_from_rfc3339(&d.doc_id)
-                .ok()
-                .map(|dt| dt.with_timezone(&chrono::Utc));
-            ScoredHit {
-                doc_id: d.doc_id.clone(),
-                score: d.score,
-                source: HitSource::Bm25,
-                ts,
-            }
+        .map(|d| ScoredHit {
+            doc_id: d.doc_id.clone(),
+            score: d.score,
+            source: HitSource::Bm25,
+            ts: bm25_index.doc_timestamp(&d.doc_id),
         })
         .collect();
 
-    // Get dense results (N = 50 candidates)
-    let dir = get_interactions_dir(app_handle)?;
+    // Get dense results (N = 50 candidates) from the HNSW index, or a linear scan if it's missing/stale
+    let dense_results = match crate::vector_index::search_dense_index_in_dir(dir, query_embedding, 50) {
+        Some(hits) => load_dense_hits(dir, hits),
+        None => linear_dense_scan(dir, query_embedding, 50),
+    };
+
+    // Convert to ScoredHit format
+    let dense: Vec<ScoredHit> = dense_results
+        .iter()
+        .map(|(score, doc_id, entry)| ScoredHit {
+            doc_id: doc_id.clone(),
+            score: *score,
+            source: HitSource::DenseInteraction,
+            ts: Some(entry.ts),
+        })
+        .collect();
+
+    let entries = dense_results
+        .into_iter()
+        .map(|(_, doc_id, entry)| (doc_id, entry))
+        .collect();
+
+    Ok(HybridCandidates { bm25, dense, entries })
+}
+
+/// Exact dense search: cosine similarity against every embedded interaction
+fn linear_dense_scan(
+    dir: &std::path::Path,
+    query_embedding: &[f32],
+    limit: usize,
+) -> Vec<(f32, String, InteractionEntry)> {
     let mut dense_results: Vec<(f32, String, InteractionEntry)> = Vec::new();
+    let space = embedding_settings().space_id();
 
-    if let Ok(entries) = fs::read_dir(&dir) {
+    if let Ok(entries) = fs::read_dir(dir) {
         for entry in entries.flatten() {
             let path = entry.path();
             if path.extension().and_then(|s| s.to_str()) == Some("jsonl") {
@@ -261,7 +1335,8 @@ pub fn hybrid_search_interactions<R: Runtime>(
                     let reader = BufReader::new(file);
                     for line in reader.lines().flatten() {
                         if let Ok(entry) = serde_json::from_str::<InteractionEntry>(&line) {
-                            if let Some(emb) = &entry.embedding {
+                            // Skip entries embedded by another model or at a different dimensionality
+                            if let Some(emb) = entry.embedding_in_space(&space, query_embedding.len()) {
                                 let score = cosine_similarity(query_embedding, emb);
                                 let doc_id = entry.ts.to_rfc3339();
                                 dense_results.push((score, doc_id, entry));
@@ -273,66 +1348,190 @@ pub fn hybrid_search_interactions<R: Runtime>(
         }
     }
 
-    // Sort dense results and take top 50
     dense_results.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
-    dense_results.truncate(50);
+    dense_results.truncate(limit);
+    dense_results
+}
 
-    // Convert to ScoredHit format
-    let dense_hits: Vec<ScoredHit> = dense_results
-        .iter()
-        .map(|(score, doc_id, entry)| ScoredHit {
-            doc_id: doc_id.clone(),
-            score: *score,
-            source: HitSource::DenseInteraction,
-            ts: Some(entry.ts),
+/// Resolve dense index hits to their entries, reading only the log files they fall in.
+/// Hits whose entry no longer exists (removed by cleanup) are dropped.
+fn load_dense_hits(
+    dir: &std::path::Path,
+    hits: Vec<(String, f32)>,
+) -> Vec<(f32, String, InteractionEntry)> {
+    let mut by_file: std::collections::HashMap<PathBuf, Vec<&str>> = std::collections::HashMap::new();
+    for (doc_id, _) in &hits {
+        if let Ok(ts) = DateTime::parse_from_rfc3339(doc_id) {
+            // Entries live in their daily file, or the monthly archive once compacted
+            for path in log_paths_for(dir, &ts.with_timezone(&Utc)) {
+                by_file.entry(path).or_default().push(doc_id);
+            }
+        }
+    }
+
+    let mut found: std::collections::HashMap<String, InteractionEntry> = std::collections::HashMap::new();
+    for (path, doc_ids) in by_file {
+        let Ok(file) = fs::File::open(&path) else {
+            continue;
+        };
+        for line in BufReader::new(file).lines().map_while(Result::ok) {
+            if let Ok(entry) = serde_json::from_str::<InteractionEntry>(&line) {
+                let doc_id = entry.ts.to_rfc3339();
+                if doc_ids.contains(&doc_id.as_str()) {
+                    found.insert(doc_id, entry);
+                }
+            }
+        }
+    }
+
+    hits.into_iter()
+        .filter_map(|(doc_id, score)| {
+            let entry = found.remove(&doc_id)?;
+            Some((score, doc_id, entry))
         })
-        .collect();
+        .collect()
+}
+
+/// Fuse BM25, dense interaction and topic chunk hits with RRF, then apply the temporal boost
+///
+/// Falls back to BM25 (plus topic chunks) when dense interaction results are sparse. The boost is applied
+/// to the full candidate pool before truncating so a recent hit just outside
+/// the top `limit` can still overtake a stale one.
+pub fn fuse_interaction_hits(
+    bm25_hits: &[ScoredHit],
+    dense_hits: &[ScoredHit],
+    topic_chunk_hits: &[ScoredHit],
+    limit: usize,
+    tuning: &RetrievalTuning,
+) -> Vec<ScoredHit> {
+    let mut fused = fuse_rrf_candidates(bm25_hits, dense_hits, topic_chunk_hits, tuning);
 
-    // Perform RRF fusion with fallback for sparse dense results
-    let mut fused = if dense_hits.len() < min_dense_hits() {
+    // Apply temporal boost for recency
+    apply_temporal_boost(&mut fused, tuning.temporal_tau_days);
+    fused.truncate(limit);
+    fused
+}
+
+/// Plain RRF over every candidate, before the temporal boost and the final limit
+fn fuse_rrf_candidates(
+    bm25_hits: &[ScoredHit],
+    dense_hits: &[ScoredHit],
+    topic_chunk_hits: &[ScoredHit],
+    tuning: &RetrievalTuning,
+) -> Vec<ScoredHit> {
+    let candidate_limit = bm25_hits.len() + dense_hits.len() + topic_chunk_hits.len();
+    if dense_hits.len() < tuning.min_dense_hits {
         log::debug!(
             "[Hybrid] Sparse dense results ({}), using BM25-only fallback",
             dense_hits.len()
         );
-        fuse_rrf_multi(&[&bm25_hits], rrf_k_default(), limit)
+        // Topic chunks come from their own index, so sparse interaction embeddings don't discount them
+        fuse_rrf_weighted(
+            &[(bm25_hits, RRF_WEIGHT_BM25), (topic_chunk_hits, RRF_WEIGHT_TOPIC_CHUNK)],
+            tuning.rrf_k,
+            candidate_limit,
+        )
     } else {
-        fuse_rrf_multi(&[&bm25_hits, &dense_hits], rrf_k_default(), limit)
-    };
+        fuse_rrf_weighted(
+            &[
+                (bm25_hits, RRF_WEIGHT_BM25),
+                (dense_hits, RRF_WEIGHT_DENSE),
+                (topic_chunk_hits, RRF_WEIGHT_TOPIC_CHUNK),
+            ],
+            tuning.rrf_k,
+            candidate_limit,
+        )
+    }
+}
 
-    // Apply temporal boost for recency
-    apply_temporal_boost(&mut fused, temporal_tau_days());
+// ============================================================================
+// Hybrid Search Debugging
+// ============================================================================
 
-    // Map fused doc_ids back to InteractionEntry
-    // Build lookup from doc_id -> entry
-    let entry_map: std::collections::HashMap<String, InteractionEntry> = dense_results
-        .into_iter()
-        .map(|(_, doc_id, entry)| (doc_id, entry))
-        .collect();
+/// One candidate from `debug_hybrid_search` with each stage's contribution (ranks are 1-based)
+#[derive(Debug, Clone, Serialize)]
+pub struct HybridDebugHit {
+    pub doc_id: String,
+    pub content: String,
+    pub bm25_rank: Option<usize>,
+    pub dense_rank: Option<usize>,
+    pub topic_chunk_rank: Option<usize>,
+    /// Fused RRF score before the temporal boost
+    pub rrf_score: f32,
+    /// Recency multiplier applied to the RRF score
+    pub temporal_score: f32,
+    pub final_score: f32,
+}
 
-    // Also need to load entries for BM25-only results
-    let mut final_results: Vec<InteractionEntry> = Vec::with_capacity(fused.len());
-    for scored in fused {
-        if let Some(entry) = entry_map.get(&scored.doc_id) {
-            final_results.push(entry.clone());
-        } else {
-            // Entry was in BM25 but not in dense (no embedding) - load from JSONL
-            if let Ok(entry) = find_entry_by_doc_id(app_handle, &scored.doc_id) {
-                final_results.push(entry);
+/// Run the hybrid pipeline for `query` and explain every fused candidate (no final limit)
+pub fn debug_hybrid_search<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    query: &str,
+    query_embedding: &[f32],
+) -> Result<Vec<HybridDebugHit>, String> {
+    let dir = get_interactions_dir(app_handle)?;
+    debug_hybrid_search_in_dir(
+        &dir,
+        query,
+        query_embedding,
+        &topic_chunk_hits(app_handle, query_embedding),
+        |topic, idx| crate::memories::read_topic_chunk(app_handle, topic, idx).ok(),
+        &load_retrieval_tuning(app_handle),
+    )
+}
+
+/// Core of `debug_hybrid_search` for an explicit interactions dir
+pub fn debug_hybrid_search_in_dir<F>(
+    dir: &std::path::Path,
+    query: &str,
+    query_embedding: &[f32],
+    topic_chunk_hits: &[ScoredHit],
+    read_chunk: F,
+    tuning: &RetrievalTuning,
+) -> Result<Vec<HybridDebugHit>, String>
+where
+    F: Fn(&str, usize) -> Option<String>,
+{
+    let candidates = gather_candidates(dir, query, query_embedding, tuning)?;
+    let fused = fuse_rrf_candidates(&candidates.bm25, &candidates.dense, topic_chunk_hits, tuning);
+    let rank_in = |hits: &[ScoredHit], doc_id: &str| hits.iter().position(|h| h.doc_id == doc_id).map(|i| i + 1);
+    let now = Utc::now();
+
+    let mut explained: Vec<HybridDebugHit> = fused
+        .iter()
+        .map(|scored| {
+            let temporal_score = temporal_decay(scored.ts, now, tuning.temporal_tau_days);
+            HybridDebugHit {
+                doc_id: scored.doc_id.clone(),
+                content: if scored.source == HitSource::DenseTopicChunk {
+                    resolve_topic_chunk(scored.clone(), &read_chunk).map(|chunk| chunk.content)
+                } else {
+                    candidates.resolve(dir, scored).map(|entry| entry.content)
+                }
+                .unwrap_or_default(),
+                bm25_rank: rank_in(&candidates.bm25, &scored.doc_id),
+                dense_rank: rank_in(&candidates.dense, &scored.doc_id),
+                topic_chunk_rank: rank_in(topic_chunk_hits, &scored.doc_id),
+                rrf_score: scored.score,
+                temporal_score,
+                final_score: scored.score * temporal_score,
             }
-        }
-    }
+        })
+        .collect();
+    explained.sort_by(|a, b| b.final_score.partial_cmp(&a.final_score).unwrap_or(std::cmp::Ordering::Equal));
+    Ok(explained)
+}
 
-    Ok(final_results)
+/// Last-modified time of a topic summary file
+fn topic_file_modified(topics_dir: &std::path::Path, topic: &str) -> Option<DateTime<Utc>> {
+    let path = crate::memories::topic_path_in_dir(topics_dir, topic).ok()?;
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    Some(DateTime::<Utc>::from(modified))
 }
 
 /// Find an interaction entry by its doc_id (RFC3339 timestamp)
-fn find_entry_by_doc_id<R: Runtime>(
-    app_handle: &AppHandle<R>,
-    doc_id: &str,
-) -> Result<InteractionEntry, String> {
-    let dir = get_interactions_dir(app_handle)?;
-
-    if let Ok(entries) = fs::read_dir(&dir) {
+fn find_entry_by_doc_id(dir: &std::path::Path, doc_id: &str) -> Result<InteractionEntry, String> {
+    if let Ok(entries) = fs::read_dir(dir) {
         for entry in entries.flatten() {
             let path = entry.path();
             if path.extension().and_then(|s| s.to_str()) == Some("jsonl") {
@@ -353,6 +1552,96 @@ fn find_entry_by_doc_id<R: Runtime>(
     Err(format!("Entry not found: {}", doc_id))
 }
 
+// ============================================================================
+// RAG Context
+// ============================================================================
+
+/// Default character budget for the "Relevant Past Interactions" block
+pub const DEFAULT_RAG_CONTEXT_CHARS: u32 = 4000;
+/// Don't start another entry with less room than this
+const MIN_RAG_ENTRY_CHARS: usize = 80;
+/// Token-set overlap above which two interactions count as duplicates
+const NEAR_DUPLICATE_JACCARD: f32 = 0.9;
+
+/// Cut `text` to at most `max_chars`, ending at the last sentence boundary when one
+/// falls in the back half of the window (otherwise at a word boundary). Appends "…" when cut.
+pub fn truncate_at_sentence(text: &str, max_chars: usize) -> String {
+    let text = text.trim();
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+
+    let window: String = text.chars().take(max_chars.saturating_sub(1)).collect();
+    let sentence_end = window
+        .char_indices()
+        .filter(|&(i, c)| {
+            matches!(c, '.' | '!' | '?' | '\n')
+                && window[i + c.len_utf8()..].starts_with(char::is_whitespace)
+        })
+        .map(|(i, c)| i + c.len_utf8())
+        .last();
+
+    let cut = match sentence_end {
+        Some(end) if end >= window.len() / 2 => end,
+        _ => window.rfind(char::is_whitespace).unwrap_or(window.len()),
+    };
+    format!("{}…", window[..cut].trim_end())
+}
+
+/// Whether two interactions say (almost) the same thing
+fn is_near_duplicate(a: &[String], b: &[String]) -> bool {
+    if a.is_empty() || b.is_empty() {
+        return a.is_empty() && b.is_empty();
+    }
+    let a: std::collections::HashSet<&String> = a.iter().collect();
+    let b: std::collections::HashSet<&String> = b.iter().collect();
+    let shared = a.intersection(&b).count();
+    let union = a.union(&b).count();
+    shared as f32 / union as f32 >= NEAR_DUPLICATE_JACCARD
+}
+
+/// The prompt's past-interactions block and the entries that made it in
+#[derive(Debug, Clone, PartialEq)]
+pub struct RagContextBlock {
+    pub text: String,
+    /// Indices into the input entries, in prompt order
+    pub kept: Vec<usize>,
+}
+
+/// Format retrieved interactions (best first) into the prompt's past-interactions block.
+/// Near-duplicates are dropped, and entries are truncated at sentence boundaries so the
+/// block stays within `budget_chars`; no single entry may take more than half the budget.
+pub fn build_rag_context(entries: &[InteractionEntry], budget_chars: usize) -> Option<RagContextBlock> {
+    const HEADER: &str = "\n\nRelevant Past Interactions:\n";
+
+    let mut out = String::from(HEADER);
+    let mut remaining = budget_chars.saturating_sub(HEADER.len());
+    let entry_cap = (budget_chars / 2).max(MIN_RAG_ENTRY_CHARS);
+    let mut seen: Vec<Vec<String>> = Vec::new();
+    let mut kept = Vec::new();
+
+    for (idx, entry) in entries.iter().enumerate() {
+        let tokens = crate::retrieval::tokenize(&entry.content);
+        if seen.iter().any(|prev| is_near_duplicate(prev, &tokens)) {
+            continue;
+        }
+
+        let prefix = format!("- [{}] {}: ", entry.ts.format("%Y-%m-%d"), entry.role);
+        let room = remaining.saturating_sub(prefix.chars().count() + 1).min(entry_cap);
+        if room < MIN_RAG_ENTRY_CHARS {
+            break;
+        }
+
+        let line = format!("{}{}\n", prefix, truncate_at_sentence(&entry.content, room));
+        remaining = remaining.saturating_sub(line.chars().count());
+        out.push_str(&line);
+        seen.push(tokens);
+        kept.push(idx);
+    }
+
+    (!kept.is_empty()).then_some(RagContextBlock { text: out, kept })
+}
+
 // ============================================================================
 // Tests
 // ============================================================================
@@ -11,6 +11,7 @@ static CURRENT_STREAM_ID: AtomicU64 = AtomicU64::new(0);
 static CANCELLED_STREAM_ID: AtomicU64 = AtomicU64::new(0);
 
 mod config;
+mod error;
 mod integrations;
 mod tools;
 mod prompts;
@@ -20,7 +21,13 @@ mod memories;
 mod interactions;
 mod background;
 mod cache;
+mod tool_stats;
+mod embedding_cache;
+mod api_debug;
+mod health;
+mod rate_limit;
 pub mod retrieval;
+pub mod vector_index;
 
 #[cfg(test)]
 mod tests;
@@ -42,7 +49,45 @@ async fn get_config(app_handle: AppHandle) -> Result<config::AppConfig, String>
 
 #[tauri::command]
 async fn save_config(app_handle: AppHandle, config: config::AppConfig) -> Result<(), String> {
-    config::save_config(&app_handle, &config)
+    config::save_config(&app_handle, &config)?;
+    interactions::set_embedding_settings(interactions::EmbeddingSettings::from_config(&config));
+    Ok(())
+}
+
+#[tauri::command]
+async fn list_profiles(app_handle: AppHandle) -> Result<Vec<String>, String> {
+    config::list_profiles(&app_handle)
+}
+
+#[tauri::command]
+async fn get_active_profile(app_handle: AppHandle) -> Result<String, String> {
+    config::get_active_profile(&app_handle)
+}
+
+#[tauri::command]
+async fn save_profile(
+    app_handle: AppHandle,
+    name: String,
+    config: config::AppConfig,
+) -> Result<(), String> {
+    config::save_profile(&app_handle, &name, &config)
+}
+
+/// Switch to a profile; subsequent get_config/save_config calls operate on it
+#[tauri::command]
+async fn load_profile(app_handle: AppHandle, name: String) -> Result<config::AppConfig, String> {
+    config::load_profile(&app_handle, &name)
+}
+
+#[tauri::command]
+async fn delete_profile(app_handle: AppHandle, name: String) -> Result<(), String> {
+    config::delete_profile(&app_handle, &name)
+}
+
+/// Check a newly entered API key against its provider without saving it
+#[tauri::command]
+async fn validate_api_key(provider: String, key: String) -> Result<(), String> {
+    config::validate_api_key(&provider, &key).await
 }
 
 #[derive(serde::Serialize)]
@@ -50,10 +95,12 @@ struct OcrResult {
     text: String,
     image_base64: String,
     mime_type: String,
+    /// Language hint to forward to `ocr_image` (None = auto/English)
+    lang: Option<String>,
 }
 
 #[tauri::command]
-async fn perform_ocr_capture(_app_handle: AppHandle) -> Result<OcrResult, String> {
+async fn perform_ocr_capture(_app_handle: AppHandle, lang: Option<String>) -> Result<OcrResult, String> {
     // Load config for API keys
     // let config = config::load_config(&app_handle)?;
 
@@ -93,20 +140,28 @@ async fn perform_ocr_capture(_app_handle: AppHandle) -> Result<OcrResult, String
         text: "[Processing...]".to_string(),
         image_base64,
         mime_type: "image/png".to_string(),
+        lang,
     })
 }
 
 // Perform OCR on a base64-encoded image (for pasted images)
 #[tauri::command]
-async fn ocr_image(app_handle: AppHandle, image_base64: String, mime_type: Option<String>) -> Result<String, String> {
+async fn ocr_image(
+    app_handle: AppHandle,
+    image_base64: String,
+    mime_type: Option<String>,
+    lang: Option<String>,
+) -> Result<String, String> {
     // Load config for API keys
     let config = config::load_config(&app_handle)?;
 
     let mime = mime_type.unwrap_or_else(|| "image/png".to_string());
+    // Explicit hint wins over the configured default (None = auto/English)
+    let lang = lang.or_else(|| config.ocr_language.clone());
 
-    // Use Vision LLM for OCR instead of Tesseract
+    // Use Vision LLM for OCR (falls back to local Tesseract when no keys are configured)
     let http_client = reqwest::Client::new();
-    vision_llm::describe_image(&http_client, &image_base64, &mime, &config).await
+    vision_llm::describe_image(&http_client, &image_base64, &mime, &config, lang.as_deref()).await
 }
 
 #[tauri::command]
@@ -116,9 +171,29 @@ async fn chat(
     message: String,
     images_base64: Option<Vec<String>>,
     images_mime_types: Option<Vec<String>>,
-) -> Result<(), String> {
+    system_prompt_override: Option<String>,
+    temperature: Option<f32>,
+    top_p: Option<f32>,
+    focus_mode: Option<bool>,
+) -> Result<(), error::ShardError> {
     let config = config::load_config(&app_handle)?;
-    state.agent.process_message(&app_handle, message, images_base64, images_mime_types, &config).await
+    let overrides = agent::GenerationOverrides::new(system_prompt_override, temperature, top_p)
+        .with_focus_mode(focus_mode.unwrap_or(false));
+    state
+        .agent
+        .process_message(&app_handle, message, images_base64, images_mime_types, &config, overrides)
+        .await
+}
+
+/// The assembled system prompt, retrieval and memory context for a message, without sending it
+#[tauri::command]
+async fn preview_prompt(
+    app_handle: AppHandle,
+    state: tauri::State<'_, AppState>,
+    message: String,
+) -> Result<agent::PromptPreview, String> {
+    let config = config::load_config(&app_handle).map_err(|e| e.to_string())?;
+    state.agent.preview_prompt(&app_handle, &message, &config).await
 }
 
 #[tauri::command]
@@ -154,12 +229,121 @@ async fn get_chat_history(state: tauri::State<'_, AppState>) -> Result<Vec<crate
     Ok(state.agent.get_history().await)
 }
 
+/// Start a new empty conversation (the current one stays saved); returns its id
+#[tauri::command]
+async fn new_session(state: tauri::State<'_, AppState>) -> Result<String, String> {
+    state.agent.new_session().await
+}
+
+#[tauri::command]
+async fn switch_session(state: tauri::State<'_, AppState>, id: String) -> Result<(), String> {
+    state.agent.switch_session(&id).await
+}
+
+#[tauri::command]
+async fn list_sessions(state: tauri::State<'_, AppState>) -> Result<Vec<crate::agent::SessionInfo>, String> {
+    state.agent.list_sessions()
+}
+
+/// Pin a model to the active session; None goes back to the configured model
+#[tauri::command]
+async fn set_session_model(state: tauri::State<'_, AppState>, model: Option<String>) -> Result<(), String> {
+    state.agent.set_session_model(model)
+}
+
+#[tauri::command]
+async fn get_session_model(state: tauri::State<'_, AppState>) -> Result<Option<String>, String> {
+    Ok(state.agent.session_model())
+}
+
+/// Delete a saved conversation; deleting the active one opens a new empty session
+#[tauri::command]
+async fn delete_session(state: tauri::State<'_, AppState>, id: String) -> Result<bool, String> {
+    state.agent.delete_session(&id).await
+}
+
+/// Paginated history for long sessions; `roles` filters e.g. to ["user", "model"]
+#[tauri::command]
+async fn get_history_page(
+    state: tauri::State<'_, AppState>,
+    offset: usize,
+    limit: usize,
+    roles: Option<Vec<String>>,
+) -> Result<crate::agent::HistoryPage, String> {
+    Ok(state
+        .agent
+        .get_history_page(offset, limit, roles.as_deref())
+        .await)
+}
+
+/// Short (≤6 word) title for the current chat; cached per conversation
+#[tauri::command]
+async fn generate_chat_title(
+    app_handle: AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let config = config::load_config(&app_handle).map_err(|e| e.to_string())?;
+    state.agent.generate_chat_title(&config).await
+}
+
+/// Prose summary (≤ max_words) of the current chat for handoff
+#[tauri::command]
+async fn summarize_conversation(
+    app_handle: AppHandle,
+    state: tauri::State<'_, AppState>,
+    max_words: usize,
+) -> Result<String, String> {
+    let config = config::load_config(&app_handle).map_err(|e| e.to_string())?;
+    state.agent.summarize_conversation(&config, max_words).await
+}
+
 #[tauri::command]
 async fn rewind_history(state: tauri::State<'_, AppState>) -> Result<(), String> {
     state.agent.rewind_history().await;
     Ok(())
 }
 
+/// Fork the chat at a message index; the full history can be restored with restore_chat
+#[tauri::command]
+async fn branch_from_message(state: tauri::State<'_, AppState>, index: usize) -> Result<(), String> {
+    state.agent.branch_from_message(index).await
+}
+
+/// Export the current chat as a Markdown transcript
+#[tauri::command]
+async fn export_chat_markdown(state: tauri::State<'_, AppState>) -> Result<String, String> {
+    Ok(agent::history_to_markdown(&state.agent.get_history().await))
+}
+
+/// Replace the chat with an exported transcript; the previous chat can be restored with restore_chat
+#[tauri::command]
+async fn import_chat_markdown(state: tauri::State<'_, AppState>, markdown: String) -> Result<usize, String> {
+    state.agent.import_markdown(&markdown).await
+}
+
+/// Files currently stored in the Gemini Files API
+#[tauri::command]
+async fn list_uploaded_files(
+    app_handle: AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<gemini_files::GeminiFileInfo>, String> {
+    let config = config::load_config(&app_handle).map_err(|e| e.to_string())?;
+    let api_key = config
+        .gemini_api_key
+        .ok_or_else(|| error::ShardError::missing_api_key("Gemini").to_string())?;
+    state.agent.list_uploaded_files(&api_key).await
+}
+
+/// Delete uploaded Gemini files that no chat references; returns how many were deleted
+#[tauri::command]
+async fn cleanup_orphaned_files(app_handle: AppHandle, state: tauri::State<'_, AppState>) -> Result<usize, String> {
+    let config = config::load_config(&app_handle).map_err(|e| e.to_string())?;
+    let api_key = config
+        .gemini_api_key
+        .ok_or_else(|| error::ShardError::missing_api_key("Gemini").to_string())?;
+    state.agent.cleanup_orphaned_files(&api_key).await
+}
+
 /// Retry the last response with a hint about KaTeX rendering errors
 /// Called by frontend when KaTeX parsing fails
 #[tauri::command]
@@ -167,11 +351,33 @@ async fn retry_with_katex_hint(
     app_handle: AppHandle,
     state: tauri::State<'_, AppState>,
     katex_errors: Vec<String>,
-) -> Result<(), String> {
+) -> Result<(), error::ShardError> {
     let config = config::load_config(&app_handle)?;
     state.agent.retry_with_katex_hint(&app_handle, katex_errors, &config).await
 }
 
+/// Summarize all but the last `keep_recent` turns into a single note; returns messages compacted
+#[tauri::command]
+async fn compact_history(
+    app_handle: AppHandle,
+    state: tauri::State<'_, AppState>,
+    keep_recent: usize,
+) -> Result<usize, String> {
+    let config = config::load_config(&app_handle).map_err(|e| e.to_string())?;
+    state.agent.compact_history(keep_recent, &config).await
+}
+
+/// Regenerate the last response, optionally with a different model for this turn only
+#[tauri::command]
+async fn reprocess_message(
+    app_handle: AppHandle,
+    state: tauri::State<'_, AppState>,
+    model_override: Option<String>,
+) -> Result<(), error::ShardError> {
+    let config = config::load_config(&app_handle)?;
+    state.agent.reprocess_last(&app_handle, model_override, &config).await
+}
+
 #[tauri::command]
 async fn cancel_current_stream() -> Result<(), String> {
     let current_stream = CURRENT_STREAM_ID.load(Ordering::Relaxed);
@@ -179,6 +385,20 @@ async fn cancel_current_stream() -> Result<(), String> {
     Ok(())
 }
 
+/// Stop running background summary/cleanup jobs at their next checkpoint
+#[tauri::command]
+async fn cancel_background_jobs() -> Result<(), String> {
+    background::cancel_background_jobs();
+    Ok(())
+}
+
+/// Stop the current stream and any running background jobs
+#[tauri::command]
+async fn cancel_all() -> Result<(), String> {
+    cancel_current_stream().await?;
+    cancel_background_jobs().await
+}
+
 #[tauri::command]
 async fn hide_window(app_handle: AppHandle) -> Result<(), String> {
     if let Some(window) = app_handle.get_webview_window("main") {
@@ -230,9 +450,9 @@ async fn force_summary(app_handle: AppHandle) -> Result<SummaryStats, String> {
 #[tauri::command]
 async fn rebuild_topic_index(app_handle: AppHandle) -> Result<usize, String> {
     let config = config::load_config(&app_handle)?;
-    let api_key = config
-        .gemini_api_key
-        .ok_or("No Gemini API key configured for embedding generation")?;
+    let api_key = interactions::embedding_api_key(&config)
+        .cloned()
+        .ok_or("No API key configured for embedding generation")?;
     let http_client = reqwest::Client::new();
     memories::rebuild_topic_index(&app_handle, &http_client, &api_key).await
 }
@@ -240,18 +460,164 @@ async fn rebuild_topic_index(app_handle: AppHandle) -> Result<usize, String> {
 #[tauri::command]
 async fn rebuild_insight_index(app_handle: AppHandle) -> Result<usize, String> {
     let config = config::load_config(&app_handle)?;
-    let api_key = config
-        .gemini_api_key
-        .ok_or("No Gemini API key configured for embedding generation")?;
+    let api_key = interactions::embedding_api_key(&config)
+        .cloned()
+        .ok_or("No API key configured for embedding generation")?;
     let http_client = reqwest::Client::new();
     memories::rebuild_insight_index(&app_handle, &http_client, &api_key).await
 }
 
+/// Merge the selected insights into a single new insight
+#[tauri::command]
+async fn merge_insights(app_handle: AppHandle, titles: Vec<String>, new_title: String) -> Result<(), String> {
+    let config = config::load_config(&app_handle)?;
+    let api_key = interactions::embedding_api_key(&config)
+        .cloned()
+        .ok_or("No API key configured for embedding generation")?;
+    let http_client = reqwest::Client::new();
+    memories::merge_insights(&app_handle, &http_client, &api_key, &titles, &new_title).await
+}
+
+#[tauri::command]
+async fn list_topics(app_handle: AppHandle) -> Result<Vec<memories::TopicInfo>, String> {
+    memories::list_topics(&app_handle)
+}
+
+#[tauri::command]
+async fn get_topic(app_handle: AppHandle, name: String) -> Result<String, String> {
+    memories::read_topic_summary(&app_handle, &name)
+}
+
+#[tauri::command]
+async fn delete_topic(app_handle: AppHandle, name: String) -> Result<bool, String> {
+    memories::delete_topic(&app_handle, &name)
+}
+
+/// Access counts and last-access times per topic, for pruning unused ones
+#[tauri::command]
+async fn get_topic_stats(app_handle: AppHandle) -> Result<Vec<memories::TopicStats>, String> {
+    memories::get_topic_stats(&app_handle)
+}
+
+/// Self-diagnostic of the data dir (index drift, parse failures, configured keys)
+#[tauri::command]
+async fn healthcheck(app_handle: AppHandle) -> Result<health::HealthReport, String> {
+    health::healthcheck(&app_handle)
+}
+
+/// Per-category memory counts and token budget use, for the settings dashboard
+#[tauri::command]
+async fn get_memory_categories_summary(app_handle: AppHandle) -> Result<memories::MemorySummary, String> {
+    memories::get_memory_categories_summary(&app_handle)
+}
+
+#[tauri::command]
+async fn pin_memory(app_handle: AppHandle, id: String) -> Result<bool, String> {
+    memories::pin_memory(&app_handle, &id)
+}
+
+#[tauri::command]
+async fn edit_memory(
+    app_handle: AppHandle,
+    id: String,
+    content: String,
+    importance: Option<u8>,
+) -> Result<memories::Memory, String> {
+    memories::edit_memory(&app_handle, &id, content, importance)
+}
+
+/// Revert the most recent memory add/edit; false if there is nothing to undo
+#[tauri::command]
+async fn undo_last_memory(app_handle: AppHandle) -> Result<bool, String> {
+    memories::undo_last_memory(&app_handle)
+}
+
+/// Toggle whether an insight is always injected into context; returns the new state
+#[tauri::command]
+async fn toggle_favorite_insight(app_handle: AppHandle, title: String) -> Result<bool, String> {
+    memories::toggle_favorite_insight(&app_handle, &title)
+}
+
+/// Toggle whether a topic is always injected into context; returns the new state
+#[tauri::command]
+async fn toggle_favorite_topic(app_handle: AppHandle, name: String) -> Result<bool, String> {
+    memories::toggle_favorite_topic(&app_handle, &name)
+}
+
+#[tauri::command]
+async fn export_memory_bundle(app_handle: AppHandle) -> Result<String, String> {
+    memories::export_memory_bundle(&app_handle)
+}
+
+#[tauri::command]
+async fn import_memory_bundle(app_handle: AppHandle, data: String) -> Result<(), String> {
+    memories::import_memory_bundle(&app_handle, &data)
+}
+
+#[tauri::command]
+async fn get_tool_stats(
+    app_handle: AppHandle,
+) -> Result<std::collections::HashMap<String, tool_stats::ToolCounters>, String> {
+    Ok(tool_stats::get_tool_stats(&app_handle))
+}
+
 #[tauri::command]
 async fn rebuild_bm25_index(app_handle: AppHandle) -> Result<usize, String> {
     retrieval::rebuild_bm25_index(&app_handle)
 }
 
+#[tauri::command]
+async fn rebuild_dense_index(app_handle: AppHandle) -> Result<usize, String> {
+    vector_index::rebuild_dense_index(&app_handle)
+}
+
+#[tauri::command]
+async fn repair_interaction_logs(app_handle: AppHandle) -> Result<usize, String> {
+    interactions::repair_interaction_logs(&app_handle)
+}
+
+#[tauri::command]
+async fn delete_interaction(app_handle: AppHandle, timestamp: String) -> Result<usize, String> {
+    interactions::delete_interaction(&app_handle, &timestamp)
+}
+
+/// Developer view of the hybrid retrieval pipeline: every candidate with its per-stage scores
+#[tauri::command]
+async fn debug_hybrid_search(
+    app_handle: AppHandle,
+    query: String,
+) -> Result<Vec<interactions::HybridDebugHit>, String> {
+    let config = config::load_config(&app_handle)?;
+    let api_key = interactions::embedding_api_key(&config)
+        .cloned()
+        .ok_or("No API key configured for embedding generation")?;
+    let http_client = reqwest::Client::new();
+    let embedding = interactions::generate_embedding(&http_client, &query, &api_key).await?;
+    interactions::debug_hybrid_search(&app_handle, &query, &embedding)
+}
+
+/// Collapse near-duplicate interactions (e.g. repeated "thanks") into one entry each
+#[tauri::command]
+async fn merge_duplicate_interactions(app_handle: AppHandle) -> Result<usize, String> {
+    interactions::merge_duplicate_interactions(&app_handle)
+}
+
+/// Embed interactions logged without an embedding; returns how many were filled
+#[tauri::command]
+async fn backfill_embeddings(app_handle: AppHandle) -> Result<usize, String> {
+    let http_client = reqwest::Client::new();
+    interactions::backfill_embeddings(&app_handle, &http_client).await
+}
+
+#[tauri::command]
+async fn compact_interaction_logs(
+    app_handle: AppHandle,
+    older_than_days: Option<i64>,
+) -> Result<usize, String> {
+    let days = older_than_days.unwrap_or(interactions::DEFAULT_COMPACTION_AGE_DAYS).max(1);
+    interactions::compact_interaction_logs(&app_handle, days)
+}
+
 // --- Main Run Function ---
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -271,6 +637,20 @@ pub fn run() {
         .setup(|app| {
             let _app_handle = app.handle();
 
+            // Load the on-disk embedding cache location before anything embeds
+            embedding_cache::init(app.handle());
+            if let Ok(config) = config::load_config(app.handle()) {
+                interactions::set_embedding_settings(interactions::EmbeddingSettings::from_config(&config));
+            }
+
+            // Build the HNSW index off the main thread if it's missing or stale
+            let index_handle = app.handle().clone();
+            tauri::async_runtime::spawn_blocking(move || {
+                if let Err(e) = vector_index::ensure_dense_index(&index_handle) {
+                    log::warn!("Failed to build dense index: {}", e);
+                }
+            });
+
             // Start background jobs
             background::start_background_jobs(app.handle().clone());
 
@@ -333,25 +713,78 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             get_config,
             save_config,
+            list_profiles,
+            get_active_profile,
+            save_profile,
+            load_profile,
+            delete_profile,
+            validate_api_key,
             perform_ocr_capture,
             ocr_image,
             chat,
+            preview_prompt,
             clear_chat,
             save_and_clear_chat,
             restore_chat,
             get_message_count,
             has_backup,
             get_chat_history,
+            get_history_page,
+            new_session,
+            switch_session,
+            list_sessions,
+            delete_session,
+            set_session_model,
+            get_session_model,
+            generate_chat_title,
+            summarize_conversation,
             cancel_current_stream,
+            cancel_background_jobs,
+            cancel_all,
             rewind_history,
+            branch_from_message,
+            export_chat_markdown,
+            import_chat_markdown,
+            list_uploaded_files,
+            cleanup_orphaned_files,
             hide_window,
             force_cleanup,
             force_summary,
             rebuild_topic_index,
             rebuild_insight_index,
+            merge_insights,
             rebuild_bm25_index,
-            retry_with_katex_hint
+            rebuild_dense_index,
+            repair_interaction_logs,
+            compact_interaction_logs,
+            merge_duplicate_interactions,
+            backfill_embeddings,
+            debug_hybrid_search,
+            delete_interaction,
+            list_topics,
+            get_topic,
+            delete_topic,
+            get_topic_stats,
+            healthcheck,
+            get_memory_categories_summary,
+            pin_memory,
+            edit_memory,
+            undo_last_memory,
+            toggle_favorite_insight,
+            toggle_favorite_topic,
+            export_memory_bundle,
+            import_memory_bundle,
+            get_tool_stats,
+            retry_with_katex_hint,
+            reprocess_message,
+            compact_history
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app_handle, event| {
+            if let tauri::RunEvent::Exit = event {
+                // Persist tool counters still buffered below the flush threshold
+                tool_stats::flush_stats(app_handle);
+            }
+        });
 }
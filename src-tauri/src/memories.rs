@@ -5,25 +5,50 @@
  * and interaction summaries across chat sessions.
  */
 
+use crate::config::EmbeddingProvider;
 use chrono::{DateTime, Utc};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::{self};
 use std::path::PathBuf;
-use tauri::{AppHandle, Manager, Runtime};
+use tauri::{AppHandle, Emitter, Manager, Runtime};
 use serde::{Deserialize, Serialize};
 
 // ============================================================================
 // Data Structures
 // ============================================================================
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct TopicIndex {
     pub topics: HashMap<String, Vec<f32>>, // topic_name -> embedding
+    #[serde(default)]
+    pub chunks: HashMap<String, Vec<f32>>, // "topic#chunk_idx" -> embedding
+    #[serde(default)]
+    pub embedding_dims: Option<u32>, // None for indexes written before dims were recorded
+    #[serde(default)]
+    pub embedding_provider: Option<EmbeddingProvider>, // None for indexes written before providers (Gemini)
+    #[serde(default)]
+    pub favorites: HashSet<String>, // Topics always injected into context
+    #[serde(default)]
+    pub access: HashMap<String, TopicAccess>, // How often each topic was chosen as context
+    #[serde(default)]
+    pub filenames: HashMap<String, String>, // topic_name -> file stem (see assign_file_stem)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct TopicAccess {
+    pub count: u32,
+    pub last_accessed: Option<DateTime<Utc>>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct InsightIndex {
     pub insights: HashMap<String, InsightMeta>, // title -> metadata
+    #[serde(default)]
+    pub embedding_dims: Option<u32>,
+    #[serde(default)]
+    pub embedding_provider: Option<EmbeddingProvider>,
+    #[serde(default)]
+    pub filenames: HashMap<String, String>, // title -> file stem (see assign_file_stem)
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -32,6 +57,8 @@ pub struct InsightMeta {
     pub reference_count: u32,  // Track access frequency
     pub update_count: u32,     // Track how many times information was added (for up-leveling)
     pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub favorite: bool,        // Always injected into context
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -61,6 +88,10 @@ pub struct Memory {
     pub content: String,
     pub created_at: DateTime<Utc>,
     pub importance: u8, // 1-5
+    #[serde(default)]
+    pub pinned: bool, // Pinned memories are never pruned
+    #[serde(default)]
+    pub decayed_through: Option<DateTime<Utc>>, // How far importance decay has been applied
 }
 
 impl Memory {
@@ -71,6 +102,8 @@ impl Memory {
             content,
             created_at: Utc::now(),
             importance: importance.clamp(1, 5),
+            pinned: false,
+            decayed_through: None,
         }
     }
 
@@ -80,6 +113,24 @@ impl Memory {
     }
 }
 
+/// Jaccard similarity at or above which two memories are treated as duplicates
+const DEDUP_SIMILARITY_THRESHOLD: f32 = 0.8;
+
+/// Token-set Jaccard similarity between two memory contents
+/// Uses the BM25 tokenizer, so case and punctuation differences are ignored
+pub fn content_similarity(a: &str, b: &str) -> f32 {
+    let a_tokens: HashSet<String> = crate::retrieval::tokenize(a).into_iter().collect();
+    let b_tokens: HashSet<String> = crate::retrieval::tokenize(b).into_iter().collect();
+
+    let union = a_tokens.union(&b_tokens).count();
+    if union == 0 {
+        return 0.0;
+    }
+
+    let intersection = a_tokens.intersection(&b_tokens).count();
+    intersection as f32 / union as f32
+}
+
 // ============================================================================
 // Memory Store
 // ============================================================================
@@ -104,6 +155,35 @@ impl MemoryStore {
         self.memories.push(memory);
     }
 
+    /// Add a memory, merging it into a near-duplicate in the same category if one exists
+    ///
+    /// On a match the higher-importance content is kept and `created_at` is refreshed.
+    /// Returns the stored memory (either the merged existing entry or the new one).
+    pub fn add_or_merge(&mut self, memory: Memory) -> Memory {
+        let duplicate = self
+            .memories
+            .iter_mut()
+            .filter(|m| m.category == memory.category)
+            .map(|m| (content_similarity(&m.content, &memory.content), m))
+            .filter(|(score, _)| *score >= DEDUP_SIMILARITY_THRESHOLD)
+            .max_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(_, m)| m);
+
+        if let Some(existing) = duplicate {
+            if memory.importance > existing.importance {
+                existing.content = memory.content;
+                existing.importance = memory.importance;
+            }
+            // A re-mentioned memory is fresh again, so decay restarts from the new timestamp
+            existing.created_at = memory.created_at;
+            existing.decayed_through = None;
+            return existing.clone();
+        }
+
+        self.memories.push(memory.clone());
+        memory
+    }
+
     /// Remove a memory by ID
     pub fn remove(&mut self, id: &str) -> bool {
         let len_before = self.memories.len();
@@ -124,7 +204,15 @@ impl MemoryStore {
         self.memories.iter().map(|m| m.estimated_tokens()).sum()
     }
 
+    /// Toggle the pinned flag on a memory, returning the new state (None if not found)
+    pub fn toggle_pin(&mut self, id: &str) -> Option<bool> {
+        let memory = self.memories.iter_mut().find(|m| m.id == id)?;
+        memory.pinned = !memory.pinned;
+        Some(memory.pinned)
+    }
+
     /// Prune to fit within token budget by removing lowest importance memories
+    /// Pinned memories are never removed, even if they alone exceed the budget.
     pub fn prune_to_token_budget(&mut self, max_tokens: usize) {
         if self.total_tokens() <= max_tokens {
             return;
@@ -133,14 +221,61 @@ impl MemoryStore {
         // Sort by importance (ascending) so we remove lowest first
         self.memories.sort_by(|a, b| a.importance.cmp(&b.importance));
 
-        while self.total_tokens() > max_tokens && !self.memories.is_empty() {
-            self.memories.remove(0);
+        while self.total_tokens() > max_tokens {
+            match self.memories.iter().position(|m| !m.pinned) {
+                Some(idx) => {
+                    self.memories.remove(idx);
+                }
+                None => break,
+            }
+        }
+
+        if self.total_tokens() > max_tokens {
+            log::warn!(
+                "[Memories] Pinned memories use ~{} tokens, over the {} token budget; keeping them anyway",
+                self.total_tokens(),
+                max_tokens
+            );
         }
 
         // Re-sort by created_at for consistent ordering
         self.memories.sort_by(|a, b| a.created_at.cmp(&b.created_at));
     }
 
+    /// Lower the importance of stale memories one step per elapsed decay interval (floor 1)
+    /// and drop importance-1 memories past the age cap. Pinned memories are left alone.
+    /// Safe to run repeatedly: `decayed_through` records how much decay was already applied.
+    pub fn decay(&mut self, now: DateTime<Utc>) -> DecayResult {
+        let interval = chrono::Duration::days(DECAY_INTERVAL_DAYS);
+        let mut result = DecayResult::default();
+
+        for memory in self.memories.iter_mut().filter(|m| !m.pinned) {
+            let start = memory
+                .decayed_through
+                .unwrap_or(memory.created_at + chrono::Duration::days(DECAY_GRACE_DAYS));
+            let steps = (now - start).num_days() / DECAY_INTERVAL_DAYS;
+            if steps <= 0 {
+                continue;
+            }
+
+            // Advance by whole intervals so partial progress carries into the next sweep
+            memory.decayed_through = Some(start + interval * steps as i32);
+            let decayed = (memory.importance as i64 - steps).max(1) as u8;
+            if decayed != memory.importance {
+                memory.importance = decayed;
+                result.decayed += 1;
+            }
+        }
+
+        let max_age = chrono::Duration::days(DECAY_MAX_AGE_DAYS);
+        let before = self.memories.len();
+        self.memories
+            .retain(|m| m.pinned || m.importance > 1 || now - m.created_at <= max_age);
+        result.removed = before - self.memories.len();
+
+        result
+    }
+
     /// Format memories as markdown for injection into system prompt
     pub fn format_for_prompt(&self) -> String {
         if self.memories.is_empty() {
@@ -170,6 +305,62 @@ impl MemoryStore {
 
         output
     }
+
+    /// Per-category counts and token use, with the `top_n` most important items of each
+    pub fn category_summary(&self, token_budget: usize, top_n: usize) -> MemorySummary {
+        let categories = [
+            MemoryCategory::Preference,
+            MemoryCategory::Project,
+            MemoryCategory::Fact,
+            MemoryCategory::Interaction,
+        ]
+        .into_iter()
+        .map(|category| {
+            let mut items = self.get_by_category(&category);
+            let tokens = items.iter().map(|m| m.estimated_tokens()).sum();
+            let count = items.len();
+            // Most important first, newest breaking ties
+            items.sort_by(|a, b| b.importance.cmp(&a.importance).then_with(|| b.created_at.cmp(&a.created_at)));
+            CategorySummary {
+                category,
+                count,
+                tokens,
+                top: items.into_iter().take(top_n).cloned().collect(),
+            }
+        })
+        .collect();
+
+        let total_tokens = self.total_tokens();
+        MemorySummary {
+            total_tokens,
+            token_budget,
+            headroom: token_budget as i64 - total_tokens as i64,
+            categories,
+        }
+    }
+}
+
+// ============================================================================
+// Memory Summary - Budget dashboard
+// ============================================================================
+
+/// Items listed per category in the summary
+pub const SUMMARY_TOP_ITEMS: usize = 3;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct CategorySummary {
+    pub category: MemoryCategory,
+    pub count: usize,
+    pub tokens: usize,
+    pub top: Vec<Memory>, // Highest importance first
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct MemorySummary {
+    pub total_tokens: usize,
+    pub token_budget: usize,
+    pub headroom: i64, // token_budget - total_tokens; negative when pinned memories overflow
+    pub categories: Vec<CategorySummary>,
 }
 
 // ============================================================================
@@ -178,7 +369,7 @@ impl MemoryStore {
 
 const MEMORIES_FILENAME: &str = "MEMORIES.json";
 const MEMORIES_MD_FILENAME: &str = "MEMORIES.md";
-const TOKEN_BUDGET: usize = 1000;
+pub(crate) const TOKEN_BUDGET: usize = 1000;
 
 /// Get the path to the memories directory
 pub fn get_memories_dir<R: Runtime>(app_handle: &AppHandle<R>) -> Result<PathBuf, String> {
@@ -217,20 +408,38 @@ fn get_topic_index_path<R: Runtime>(app_handle: &AppHandle<R>) -> Result<PathBuf
 
 fn load_topic_index<R: Runtime>(app_handle: &AppHandle<R>) -> Result<TopicIndex, String> {
     let path = get_topic_index_path(app_handle)?;
+    let settings = crate::interactions::embedding_settings();
+    let expected = settings.dims;
     if !path.exists() {
-        return Ok(TopicIndex { topics: HashMap::new() });
+        return Ok(TopicIndex {
+            embedding_dims: Some(expected),
+            embedding_provider: Some(settings.provider),
+            ..Default::default()
+        });
     }
     let content = fs::read_to_string(&path)
         .map_err(|e| format!("Failed to read topic index: {}", e))?;
-    serde_json::from_str(&content)
-        .map_err(|e| format!("Failed to parse topic index: {}", e))
+    let mut index: TopicIndex = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse topic index: {}", e))?;
+
+    let is_empty = index.topics.is_empty() && index.chunks.is_empty();
+    if let Err(found) = check_index_provider(index.embedding_provider, is_empty, settings.provider) {
+        return Err(index_provider_mismatch(app_handle, "topic", found, settings.provider));
+    }
+    let vectors = index.topics.values().chain(index.chunks.values());
+    if let Err(found) = check_index_dims(index.embedding_dims, vectors, expected) {
+        return Err(index_dims_mismatch(app_handle, "topic", found, expected));
+    }
+    index.embedding_dims = Some(expected);
+    index.embedding_provider = Some(settings.provider);
+    Ok(index)
 }
 
 fn save_topic_index<R: Runtime>(app_handle: &AppHandle<R>, index: &TopicIndex) -> Result<(), String> {
     let path = get_topic_index_path(app_handle)?;
     let content = serde_json::to_string_pretty(index)
         .map_err(|e| format!("Failed to serialize topic index: {}", e))?;
-    fs::write(&path, content)
+    crate::retrieval::write_atomic(&path, content)
         .map_err(|e| format!("Failed to write topic index: {}", e))
 }
 
@@ -239,10 +448,7 @@ pub fn read_topic_summary<R: Runtime>(
     app_handle: &AppHandle<R>,
     topic: &str,
 ) -> Result<String, String> {
-    let topics_dir = get_topics_dir(app_handle)?;
-    // Sanitize filename
-    let filename = format!("{}.md", topic.trim().replace(|c: char| !c.is_alphanumeric() && c != '_' && c != '-', "_"));
-    let path = topics_dir.join(filename);
+    let path = topic_path_in_dir(&get_topics_dir(app_handle)?, topic)?;
 
     if !path.exists() {
         return Err(format!("Topic summary not found: {}", topic));
@@ -261,9 +467,11 @@ pub async fn update_topic_summary<R: Runtime>(
     content: &str,
 ) -> Result<(), String> {
     let topics_dir = get_topics_dir(app_handle)?;
-    // Sanitize filename
-    let filename = format!("{}.md", topic.trim().replace(|c: char| !c.is_alphanumeric() && c != '_' && c != '-', "_"));
-    let path = topics_dir.join(filename);
+    let mut index = load_topic_index(app_handle)?;
+    let stem = assign_file_stem(&mut index.filenames, index.topics.keys(), topic)?;
+    let path = topics_dir.join(format!("{}.md", stem));
+    // Record the mapping first so a crash can't leave a file no name maps to
+    save_topic_index(app_handle, &index)?;
 
     fs::write(&path, format!("# {}\n\n{}", topic, content))
         .map_err(|e| format!("Failed to write topic summary: {}", e))?;
@@ -272,10 +480,14 @@ pub async fn update_topic_summary<R: Runtime>(
     // We'll use the first 1000 chars of content to represent the topic semantically
     let embedding_text = format!("Topic: {}\nContent: {}", topic, content.chars().take(1000).collect::<String>());
     let embedding = crate::interactions::generate_embedding(http_client, &embedding_text, api_key).await?;
+    let chunk_embeddings = embed_topic_chunks(http_client, api_key, topic, &format!("# {}\n\n{}", topic, content)).await?;
 
-    // Update index
+    // Update index (drop stale chunks - the new summary may have fewer)
     let mut index = load_topic_index(app_handle)?;
+    index.filenames.insert(topic.to_string(), stem);
     index.topics.insert(topic.to_string(), embedding);
+    index.chunks.retain(|key, _| parse_chunk_key(key).map(|(t, _)| t) != Some(topic));
+    index.chunks.extend(chunk_embeddings);
     save_topic_index(app_handle, &index)?;
 
     log::info!("Topic summary updated: {}", topic);
@@ -290,24 +502,32 @@ pub async fn rebuild_topic_index<R: Runtime>(
     api_key: &str,
 ) -> Result<usize, String> {
     let topics_dir = get_topics_dir(app_handle)?;
+    let settings = crate::interactions::embedding_settings();
     let mut new_index = TopicIndex {
-        topics: std::collections::HashMap::new(),
+        embedding_dims: Some(settings.dims),
+        embedding_provider: Some(settings.provider),
+        ..Default::default()
     };
+    let (old_favorites, old_access) = load_topic_index(app_handle)
+        .map(|old| (old.favorites, old.access))
+        .unwrap_or_default();
+    // Recover display names from the old mapping (read raw - it may fail the dims check)
+    let old_names = display_names_by_stem(
+        read_json_or_default::<TopicIndex>(&topics_dir.join("index.json"))
+            .map(|old| old.filenames)
+            .unwrap_or_default(),
+    );
     let mut count = 0;
 
-    let entries = fs::read_dir(&topics_dir)
-        .map_err(|e| format!("Failed to read topics dir: {}", e))?;
-
-    for entry in entries.flatten() {
-        let path = entry.path();
-
-        // Skip index.json and non-.md files
-        if path.extension().and_then(|s| s.to_str()) != Some("md") {
-            continue;
-        }
+    // Skips index.json and other non-.md files
+    let paths = md_files(&topics_dir)?;
+    let total = paths.len();
 
-        if let Some(topic) = path.file_stem().and_then(|s| s.to_str()) {
-            let content = fs::read_to_string(&path)
+    for (done, path) in paths.iter().enumerate() {
+        emit_rebuild_progress(app_handle, "topic", done, total);
+        if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+            let topic = old_names.get(stem).map(String::as_str).unwrap_or(stem);
+            let content = fs::read_to_string(path)
                 .map_err(|e| format!("Failed to read {}: {}", topic, e))?;
 
             // Generate embedding
@@ -321,11 +541,24 @@ pub async fn rebuild_topic_index<R: Runtime>(
                     .await?;
 
             new_index.topics.insert(topic.to_string(), embedding);
+            new_index.filenames.insert(topic.to_string(), stem.to_string());
+            new_index
+                .chunks
+                .extend(embed_topic_chunks(http_client, api_key, topic, &content).await?);
             count += 1;
             log::info!("[Index] Rebuilt embedding for topic: {}", topic);
         }
     }
+    emit_rebuild_progress(app_handle, "topic", total, total);
 
+    new_index.favorites = old_favorites
+        .into_iter()
+        .filter(|topic| new_index.topics.contains_key(topic))
+        .collect();
+    new_index.access = old_access
+        .into_iter()
+        .filter(|(topic, _)| new_index.topics.contains_key(topic))
+        .collect();
     save_topic_index(app_handle, &new_index)?;
     log::info!("[Index] Rebuilt index with {} topics", count);
     Ok(count)
@@ -364,6 +597,382 @@ pub fn find_relevant_topics<R: Runtime>(
     Ok(None)
 }
 
+// ============================================================================
+// Topic Management - List/delete topics for the frontend
+// ============================================================================
+
+/// Topic file metadata for the topics panel
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TopicInfo {
+    pub name: String,
+    pub updated_at: DateTime<Utc>,
+    pub size: u64, // bytes
+}
+
+/// List topic summaries in a topics directory, most recently updated first
+pub fn list_topics_in_dir(topics_dir: &std::path::Path) -> Result<Vec<TopicInfo>, String> {
+    let entries = fs::read_dir(topics_dir)
+        .map_err(|e| format!("Failed to read topics dir: {}", e))?;
+
+    let mut topics: Vec<TopicInfo> = entries
+        .flatten()
+        .filter(|entry| entry.path().extension().and_then(|s| s.to_str()) == Some("md"))
+        .filter_map(|entry| {
+            let path = entry.path();
+            let name = path.file_stem()?.to_str()?.to_string();
+            let meta = entry.metadata().ok()?;
+            let updated_at = meta.modified().map(DateTime::<Utc>::from).unwrap_or_else(|_| Utc::now());
+            Some(TopicInfo {
+                name,
+                updated_at,
+                size: meta.len(),
+            })
+        })
+        .collect();
+
+    topics.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+    Ok(topics)
+}
+
+/// Delete a topic's `.md` file and its whole-topic + chunk index entries.
+/// Returns false if neither the file nor an index entry existed.
+pub fn delete_topic_in_dir(topics_dir: &std::path::Path, topic: &str) -> Result<bool, String> {
+    let index_path = topics_dir.join("index.json");
+    let mut index: TopicIndex = read_json_or_default(&index_path)?;
+    let filenames = index.filenames.clone();
+    let filename = resolve_file_stem(&filenames, topic);
+    let path = filename.as_ref().map(|stem| topics_dir.join(format!("{}.md", stem)));
+    let file_removed = match path.filter(|path| path.exists()) {
+        Some(path) => {
+            fs::remove_file(&path).map_err(|e| format!("Failed to delete topic file: {}", e))?;
+            true
+        }
+        None => false,
+    };
+
+    // Index keys use the display name; match on the file stem so either works
+    let owns_file =
+        |name: &str| name == topic || (filename.is_some() && resolve_file_stem(&filenames, name) == filename);
+    let before = index.topics.len() + index.chunks.len() + index.favorites.len() + index.access.len();
+    index.topics.retain(|name, _| !owns_file(name));
+    index
+        .chunks
+        .retain(|key, _| !parse_chunk_key(key).map(|(t, _)| owns_file(t)).unwrap_or(false));
+    index.favorites.retain(|name| !owns_file(name));
+    index.access.retain(|name, _| !owns_file(name));
+    index.filenames.retain(|name, stem| name != topic && Some(&*stem) != filename.as_ref());
+    let index_changed = index.topics.len() + index.chunks.len() + index.favorites.len() + index.access.len() != before
+        || index.filenames.len() != filenames.len();
+
+    if index_changed {
+        let content = serde_json::to_string_pretty(&index)
+            .map_err(|e| format!("Failed to serialize topic index: {}", e))?;
+        crate::retrieval::write_atomic(&index_path, content)
+            .map_err(|e| format!("Failed to write topic index: {}", e))?;
+    }
+
+    Ok(file_removed || index_changed)
+}
+
+/// Characters of a topic's first line shown by `list_known_topics`
+const TOPIC_PREVIEW_CHARS: usize = 100;
+
+/// A topic the model can read, with a one-line preview of its summary
+#[derive(Debug, Clone, PartialEq)]
+pub struct KnownTopic {
+    pub name: String,
+    pub preview: String,
+}
+
+/// Topic names (from each file's `# heading`, else the file name) and their first content line.
+/// Reads only the markdown files, no embeddings. Sorted by name.
+pub fn list_known_topics_in_dir(topics_dir: &std::path::Path) -> Result<Vec<KnownTopic>, String> {
+    if !topics_dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut topics: Vec<KnownTopic> = list_topics_in_dir(topics_dir)?
+        .into_iter()
+        .map(|info| {
+            let content = fs::read_to_string(topics_dir.join(format!("{}.md", info.name))).unwrap_or_default();
+            let mut lines = content.lines().map(str::trim).filter(|line| !line.is_empty()).peekable();
+            let name = lines
+                .next_if(|line| line.starts_with("# "))
+                .map(|heading| heading.trim_start_matches("# ").trim().to_string())
+                .unwrap_or(info.name);
+            let first_line = lines.next().unwrap_or_default();
+            let mut preview: String = first_line.chars().take(TOPIC_PREVIEW_CHARS).collect();
+            if first_line.chars().count() > TOPIC_PREVIEW_CHARS {
+                preview.push('…');
+            }
+            KnownTopic { name, preview }
+        })
+        .collect();
+    topics.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+    Ok(topics)
+}
+
+/// Topics available to `read_topic_summary`, for the `list_known_topics` tool
+pub fn list_known_topics<R: Runtime>(app_handle: &AppHandle<R>) -> Result<Vec<KnownTopic>, String> {
+    list_known_topics_in_dir(&get_topics_dir(app_handle)?)
+}
+
+/// List all topic summaries
+pub fn list_topics<R: Runtime>(app_handle: &AppHandle<R>) -> Result<Vec<TopicInfo>, String> {
+    list_topics_in_dir(&get_topics_dir(app_handle)?)
+}
+
+/// Delete a topic summary and its index entries
+pub fn delete_topic<R: Runtime>(app_handle: &AppHandle<R>, topic: &str) -> Result<bool, String> {
+    let deleted = delete_topic_in_dir(&get_topics_dir(app_handle)?, topic)?;
+    if deleted {
+        log::info!("Topic deleted: {}", topic);
+    }
+    Ok(deleted)
+}
+
+// ============================================================================
+// Embedding Dimensions - Detect indexes built with a different embedding config
+// ============================================================================
+
+/// Event emitted when an index needs a rebuild (payload: "topic" / "insight")
+pub const INDEX_REBUILD_EVENT: &str = "embedding-index-rebuild-required";
+/// Event emitted after each file during a rebuild (payload: {index, done, total})
+pub const INDEX_REBUILD_PROGRESS_EVENT: &str = "embedding-index-rebuild-progress";
+
+/// `.md` files in an index directory
+fn md_files(dir: &std::path::Path) -> Result<Vec<PathBuf>, String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    Ok(entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("md"))
+        .collect())
+}
+
+fn emit_rebuild_progress<R: Runtime>(app_handle: &AppHandle<R>, kind: &str, done: usize, total: usize) {
+    app_handle
+        .emit(INDEX_REBUILD_PROGRESS_EVENT, serde_json::json!({"index": kind, "done": done, "total": total}))
+        .ok();
+}
+
+/// Check an index against the configured dims.
+/// Uses the recorded dims, or infers them from the first vector for older indexes.
+/// Returns `Err(found_dims)` on mismatch; empty indexes always pass.
+pub fn check_index_dims<'a>(
+    recorded: Option<u32>,
+    mut vectors: impl Iterator<Item = &'a Vec<f32>>,
+    expected: u32,
+) -> Result<(), u32> {
+    let found = match recorded {
+        Some(dims) => dims,
+        None => match vectors.next() {
+            Some(v) => v.len() as u32,
+            None => return Ok(()),
+        },
+    };
+    if found == expected {
+        Ok(())
+    } else {
+        Err(found)
+    }
+}
+
+/// Log the mismatch, ask the frontend to prompt for a rebuild, and build the error
+fn index_dims_mismatch<R: Runtime>(app_handle: &AppHandle<R>, kind: &str, found: u32, expected: u32) -> String {
+    log::warn!(
+        "[Index] {} index has {}-dim embeddings but config expects {}; rebuild required",
+        kind, found, expected
+    );
+    app_handle.emit(INDEX_REBUILD_EVENT, kind).ok();
+    format!(
+        "The {} index was built with {}-dimension embeddings but the current embedding config uses {}. Rebuild the {} index to continue.",
+        kind, found, expected, kind
+    )
+}
+
+/// Check an index's recorded embeddings provider (unrecorded means Gemini, the only
+/// provider before this was tracked). Returns `Err(found)` on mismatch; empty indexes always pass.
+pub fn check_index_provider(
+    recorded: Option<EmbeddingProvider>,
+    is_empty: bool,
+    expected: EmbeddingProvider,
+) -> Result<(), EmbeddingProvider> {
+    let found = recorded.unwrap_or(EmbeddingProvider::Gemini);
+    if is_empty || found == expected {
+        Ok(())
+    } else {
+        Err(found)
+    }
+}
+
+/// Same as `index_dims_mismatch`, for an index built by a different provider
+fn index_provider_mismatch<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    kind: &str,
+    found: EmbeddingProvider,
+    expected: EmbeddingProvider,
+) -> String {
+    log::warn!(
+        "[Index] {} index was built with {} embeddings but config uses {}; rebuild required",
+        kind,
+        found.as_str(),
+        expected.as_str()
+    );
+    app_handle.emit(INDEX_REBUILD_EVENT, kind).ok();
+    format!(
+        "The {} index was built with {} embeddings but the current embedding provider is {}. Rebuild the {} index to continue.",
+        kind,
+        found.as_str(),
+        expected.as_str(),
+        kind
+    )
+}
+
+// ============================================================================
+// Topic Chunks - Chunk-level embeddings so long summaries still match
+// ============================================================================
+
+/// Target chunk size for topic embeddings (~4 chars per token)
+pub const TOPIC_CHUNK_TOKENS: usize = 500;
+
+/// Index key for a topic chunk embedding
+pub fn chunk_key(topic: &str, idx: usize) -> String {
+    format!("{}#{}", topic, idx)
+}
+
+/// Split a chunk key back into (topic, chunk_idx)
+pub fn parse_chunk_key(key: &str) -> Option<(&str, usize)> {
+    let (topic, idx) = key.rsplit_once('#')?;
+    Some((topic, idx.parse().ok()?))
+}
+
+/// Split topic content into chunks of roughly `max_tokens` tokens
+/// Breaks on paragraph boundaries, falling back to word boundaries for long paragraphs
+pub fn split_into_chunks(content: &str, max_tokens: usize) -> Vec<String> {
+    let max_chars = max_tokens.max(1) * 4;
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+
+    let mut pieces: Vec<String> = Vec::new();
+    for paragraph in content.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+        if paragraph.len() <= max_chars {
+            pieces.push(paragraph.to_string());
+            continue;
+        }
+        // Oversized paragraph: split on words
+        let mut piece = String::new();
+        for word in paragraph.split_whitespace() {
+            if !piece.is_empty() && piece.len() + word.len() + 1 > max_chars {
+                pieces.push(std::mem::take(&mut piece));
+            }
+            if !piece.is_empty() {
+                piece.push(' ');
+            }
+            piece.push_str(word);
+        }
+        if !piece.is_empty() {
+            pieces.push(piece);
+        }
+    }
+
+    for piece in pieces {
+        if !current.is_empty() && current.len() + piece.len() + 2 > max_chars {
+            chunks.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push_str("\n\n");
+        }
+        current.push_str(&piece);
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+
+    chunks
+}
+
+/// Embed each chunk of a topic, keyed by `topic#chunk_idx`
+async fn embed_topic_chunks(
+    http_client: &reqwest::Client,
+    api_key: &str,
+    topic: &str,
+    content: &str,
+) -> Result<Vec<(String, Vec<f32>)>, String> {
+    let mut embeddings = Vec::new();
+    for (idx, chunk) in split_into_chunks(content, TOPIC_CHUNK_TOKENS).iter().enumerate() {
+        let embedding_text = format!("Topic: {}\nContent: {}", topic, chunk);
+        let embedding =
+            crate::interactions::generate_embedding(http_client, &embedding_text, api_key).await?;
+        embeddings.push((chunk_key(topic, idx), embedding));
+    }
+    Ok(embeddings)
+}
+
+/// Score chunk embeddings against a query, best first
+/// Topics indexed before chunking (no chunks) fall back to their whole-topic embedding.
+pub fn score_topic_chunks(
+    index: &TopicIndex,
+    query_embedding: &[f32],
+    limit: usize,
+) -> Vec<crate::retrieval::ScoredHit> {
+    let chunked_topics: HashSet<&str> = index
+        .chunks
+        .keys()
+        .filter_map(|key| parse_chunk_key(key).map(|(topic, _)| topic))
+        .collect();
+
+    let chunk_scores = index.chunks.iter().map(|(key, embedding)| {
+        (key.clone(), crate::interactions::cosine_similarity(query_embedding, embedding))
+    });
+    let legacy_scores = index
+        .topics
+        .iter()
+        .filter(|(topic, _)| !chunked_topics.contains(topic.as_str()))
+        .map(|(topic, embedding)| {
+            (
+                chunk_key(topic, 0),
+                crate::interactions::cosine_similarity(query_embedding, embedding),
+            )
+        });
+
+    let mut hits: Vec<crate::retrieval::ScoredHit> = chunk_scores
+        .chain(legacy_scores)
+        .map(|(doc_id, score)| crate::retrieval::ScoredHit {
+            doc_id,
+            score,
+            source: crate::retrieval::HitSource::DenseTopicChunk,
+            ts: None,
+        })
+        .collect();
+
+    hits.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+    hits.truncate(limit);
+    hits
+}
+
+/// Find topic chunks relevant to a query (for hybrid fusion)
+pub fn find_topic_chunk_hits<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    query_embedding: &[f32],
+    limit: usize,
+) -> Result<Vec<crate::retrieval::ScoredHit>, String> {
+    let index = load_topic_index(app_handle)?;
+    Ok(score_topic_chunks(&index, query_embedding, limit))
+}
+
+/// Read the text of a single topic chunk
+pub fn read_topic_chunk<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    topic: &str,
+    idx: usize,
+) -> Result<String, String> {
+    let content = read_topic_summary(app_handle, topic)?;
+    split_into_chunks(&content, TOPIC_CHUNK_TOKENS)
+        .into_iter()
+        .nth(idx)
+        .ok_or_else(|| format!("Topic chunk not found: {}", chunk_key(topic, idx)))
+}
+
 // ============================================================================
 // Insights (Tier 2.5) - Granular atomic facts for specific queries
 // ============================================================================
@@ -389,20 +998,53 @@ fn get_insight_index_path<R: Runtime>(app_handle: &AppHandle<R>) -> Result<PathB
 
 pub fn load_insight_index<R: Runtime>(app_handle: &AppHandle<R>) -> Result<InsightIndex, String> {
     let path = get_insight_index_path(app_handle)?;
+    let settings = crate::interactions::embedding_settings();
+    let expected = settings.dims;
     if !path.exists() {
-        return Ok(InsightIndex::default());
+        return Ok(InsightIndex {
+            embedding_dims: Some(expected),
+            embedding_provider: Some(settings.provider),
+            ..Default::default()
+        });
     }
-    let content = fs::read_to_string(&path)
+    let mut index = read_insight_index_file(&path)?;
+
+    if let Err(found) = check_index_provider(index.embedding_provider, index.insights.is_empty(), settings.provider) {
+        return Err(index_provider_mismatch(app_handle, "insight", found, settings.provider));
+    }
+    let vectors = index.insights.values().map(|meta| &meta.embedding);
+    if let Err(found) = check_index_dims(index.embedding_dims, vectors, expected) {
+        return Err(index_dims_mismatch(app_handle, "insight", found, expected));
+    }
+    index.embedding_dims = Some(expected);
+    index.embedding_provider = Some(settings.provider);
+    Ok(index)
+}
+
+pub fn save_insight_index<R: Runtime>(app_handle: &AppHandle<R>, index: &InsightIndex) -> Result<(), String> {
+    save_insight_index_in_dir(&get_insights_dir(app_handle)?, index)
+}
+
+fn read_insight_index_file(path: &std::path::Path) -> Result<InsightIndex, String> {
+    let content = fs::read_to_string(path)
         .map_err(|e| format!("Failed to read insight index: {}", e))?;
     serde_json::from_str(&content)
         .map_err(|e| format!("Failed to parse insight index: {}", e))
 }
 
-pub fn save_insight_index<R: Runtime>(app_handle: &AppHandle<R>, index: &InsightIndex) -> Result<(), String> {
-    let path = get_insight_index_path(app_handle)?;
+/// Load the insight index from a specific insights directory (no dims check)
+pub fn load_insight_index_in_dir(insights_dir: &std::path::Path) -> Result<InsightIndex, String> {
+    let path = insights_dir.join("index.json");
+    if !path.exists() {
+        return Ok(InsightIndex::default());
+    }
+    read_insight_index_file(&path)
+}
+
+pub fn save_insight_index_in_dir(insights_dir: &std::path::Path, index: &InsightIndex) -> Result<(), String> {
     let content = serde_json::to_string_pretty(index)
         .map_err(|e| format!("Failed to serialize insight index: {}", e))?;
-    fs::write(&path, content)
+    crate::retrieval::write_atomic(&insights_dir.join("index.json"), content)
         .map_err(|e| format!("Failed to write insight index: {}", e))
 }
 
@@ -411,18 +1053,89 @@ fn sanitize_filename(title: &str) -> String {
     title.trim().replace(|c: char| !c.is_alphanumeric() && c != '_' && c != '-', "_")
 }
 
+fn insight_path(insights_dir: &std::path::Path, index: &InsightIndex, title: &str) -> Option<PathBuf> {
+    resolve_file_stem(&index.filenames, title).map(|stem| insights_dir.join(format!("{}.md", stem)))
+}
+
+// ============================================================================
+// File Names - Display names map to file stems so sanitized collisions can't overwrite
+// ============================================================================
+
+/// File stem for an existing name: its recorded mapping, else the plain sanitized form
+/// (entries written before mappings were recorded). None when that form belongs to another name.
+pub fn resolve_file_stem(filenames: &HashMap<String, String>, name: &str) -> Option<String> {
+    if let Some(stem) = filenames.get(name) {
+        return Some(stem.clone());
+    }
+    let stem = sanitize_filename(name);
+    (!filenames.values().any(|owned| *owned == stem)).then_some(stem)
+}
+
+/// Pick and record the file stem for `name`. A mapped name keeps its file; a new one
+/// gets its sanitized form, suffixed `_2`, `_3`, ... if another entry already uses it
+/// (e.g. "C++" and "C**" both sanitize to "C__").
+pub fn assign_file_stem<'a>(
+    filenames: &mut HashMap<String, String>,
+    existing: impl Iterator<Item = &'a String>,
+    name: &str,
+) -> Result<String, String> {
+    if let Some(stem) = filenames.get(name) {
+        return Ok(stem.clone());
+    }
+    let base = sanitize_filename(name);
+    if base.is_empty() {
+        return Err("Name cannot be empty".to_string());
+    }
+
+    let taken: HashSet<String> = existing
+        .filter(|other| other.as_str() != name)
+        .filter_map(|other| resolve_file_stem(filenames, other))
+        .chain(filenames.values().cloned())
+        .collect();
+    let mut stem = base.clone();
+    let mut suffix = 2;
+    while taken.contains(&stem) {
+        stem = format!("{}_{}", base, suffix);
+        suffix += 1;
+    }
+
+    filenames.insert(name.to_string(), stem.clone());
+    Ok(stem)
+}
+
+/// Invert a name -> stem mapping, for recovering display names when rebuilding an index
+fn display_names_by_stem(filenames: HashMap<String, String>) -> HashMap<String, String> {
+    filenames.into_iter().map(|(name, stem)| (stem, name)).collect()
+}
+
+/// Path of a topic's summary file, following the index's name mapping
+pub fn topic_path_in_dir(topics_dir: &std::path::Path, topic: &str) -> Result<PathBuf, String> {
+    let index: TopicIndex = read_json_or_default(&topics_dir.join("index.json"))?;
+    resolve_file_stem(&index.filenames, topic)
+        .map(|stem| topics_dir.join(format!("{}.md", stem)))
+        .ok_or_else(|| format!("Topic summary not found: {}", topic))
+}
+
+/// Text embedded for an insight (title plus the start of its content)
+fn insight_embedding_text(title: &str, content: &str) -> String {
+    format!("Insight: {}\nContent: {}", title, content.chars().take(1000).collect::<String>())
+}
+
 /// Read an insight file
 pub fn read_insight<R: Runtime>(
     app_handle: &AppHandle<R>,
     title: &str,
 ) -> Result<String, String> {
-    let insights_dir = get_insights_dir(app_handle)?;
-    let filename = format!("{}.md", sanitize_filename(title));
-    let path = insights_dir.join(filename);
+    read_insight_in_dir(&get_insights_dir(app_handle)?, title)
+}
 
-    if !path.exists() {
+/// Read an insight file from a specific insights directory
+pub fn read_insight_in_dir(insights_dir: &std::path::Path, title: &str) -> Result<String, String> {
+    let Some(path) = insight_path(insights_dir, &load_insight_index_in_dir(insights_dir)?, title)
+        .filter(|path| path.exists())
+    else {
         return Err(format!("Insight not found: {}", title));
-    }
+    };
 
     fs::read_to_string(&path)
         .map_err(|e| format!("Failed to read insight: {}", e))
@@ -436,32 +1149,48 @@ pub async fn update_insight<R: Runtime>(
     title: &str,
     content: &str,
 ) -> Result<(), String> {
+    // Surface a dims mismatch (and the rebuild prompt) before writing anything
+    load_insight_index(app_handle)?;
     let insights_dir = get_insights_dir(app_handle)?;
-    let filename = format!("{}.md", sanitize_filename(title));
-    let path = insights_dir.join(&filename);
+
+    let embedding_text = insight_embedding_text(title, content);
+    let embedding = crate::interactions::generate_embedding(http_client, &embedding_text, api_key).await?;
+
+    store_insight_in_dir(&insights_dir, title, content, embedding)
+}
+
+/// Write an insight file and its index entry, preserving counts if it already exists
+pub fn store_insight_in_dir(
+    insights_dir: &std::path::Path,
+    title: &str,
+    content: &str,
+    embedding: Vec<f32>,
+) -> Result<(), String> {
+    let mut index = load_insight_index_in_dir(insights_dir)?;
+    let stem = assign_file_stem(&mut index.filenames, index.insights.keys(), title)?;
+    // Record the mapping first so a crash can't leave a file no name maps to
+    save_insight_index_in_dir(insights_dir, &index)?;
 
     // Write markdown with heading format
     let formatted_content = format!("# {}\n\n{}", title, content);
-    fs::write(&path, formatted_content)
+    fs::write(insights_dir.join(format!("{}.md", stem)), formatted_content)
         .map_err(|e| format!("Failed to write insight: {}", e))?;
 
-    // Generate embedding
-    let embedding_text = format!("Insight: {}\nContent: {}", title, content.chars().take(1000).collect::<String>());
-    let embedding = crate::interactions::generate_embedding(http_client, &embedding_text, api_key).await?;
-
     // Update index (preserve counts if exists)
-    let mut index = load_insight_index(app_handle)?;
-    let (reference_count, update_count) = index.insights.get(title)
-        .map(|m| (m.reference_count, m.update_count + 1))
-        .unwrap_or((0, 1)); // Start at 1 for new insights
+    let (reference_count, update_count, favorite) = index.insights.get(title)
+        .map(|m| (m.reference_count, m.update_count + 1, m.favorite))
+        .unwrap_or((0, 1, false)); // Start at 1 for new insights
 
+    index.embedding_dims = Some(embedding.len() as u32);
+    index.embedding_provider = Some(crate::interactions::embedding_settings().provider);
     index.insights.insert(title.to_string(), InsightMeta {
         embedding,
         reference_count,
         update_count,
         created_at: Utc::now(),
+        favorite,
     });
-    save_insight_index(app_handle, &index)?;
+    save_insight_index_in_dir(insights_dir, &index)?;
 
     log::info!("Insight updated: {}", title);
     Ok(())
@@ -472,11 +1201,17 @@ pub fn delete_insight<R: Runtime>(
     app_handle: &AppHandle<R>,
     title: &str,
 ) -> Result<bool, String> {
-    let insights_dir = get_insights_dir(app_handle)?;
-    let filename = format!("{}.md", sanitize_filename(title));
-    let path = insights_dir.join(&filename);
+    // Same dims guard as every other index load
+    load_insight_index(app_handle)?;
+    delete_insight_in_dir(&get_insights_dir(app_handle)?, title)
+}
+
+/// Delete an insight file and its index entry within a specific insights directory
+pub fn delete_insight_in_dir(insights_dir: &std::path::Path, title: &str) -> Result<bool, String> {
+    let mut index = load_insight_index_in_dir(insights_dir)?;
+    let path = insight_path(insights_dir, &index, title).filter(|path| path.exists());
 
-    let file_deleted = if path.exists() {
+    let file_deleted = if let Some(path) = path {
         fs::remove_file(&path)
             .map_err(|e| format!("Failed to delete insight file: {}", e))?;
         true
@@ -485,21 +1220,103 @@ pub fn delete_insight<R: Runtime>(
     };
 
     // Remove from index
-    let mut index = load_insight_index(app_handle)?;
     let was_in_index = index.insights.remove(title).is_some();
-    if was_in_index {
-        save_insight_index(app_handle, &index)?;
+    let was_mapped = index.filenames.remove(title).is_some();
+    if was_in_index || was_mapped {
+        save_insight_index_in_dir(insights_dir, &index)?;
     }
 
     log::info!("Insight deleted: {}", title);
     Ok(file_deleted || was_in_index)
 }
 
+/// Strip the "# title" heading `store_insight_in_dir` writes, leaving the body
+fn insight_body<'a>(raw: &'a str, title: &str) -> &'a str {
+    raw.strip_prefix(&format!("# {}", title))
+        .map(str::trim_start)
+        .unwrap_or(raw)
+        .trim_end()
+}
+
+/// Merge several insights into one (manual counterpart to promotion)
+pub async fn merge_insights<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    http_client: &reqwest::Client,
+    api_key: &str,
+    titles: &[String],
+    new_title: &str,
+) -> Result<(), String> {
+    load_insight_index(app_handle)?;
+    let insights_dir = get_insights_dir(app_handle)?;
+    merge_insights_in_dir(&insights_dir, titles, new_title, |text| async move {
+        crate::interactions::generate_embedding(http_client, &text, api_key).await
+    })
+    .await
+}
+
+/// Concatenate the given insights into `new_title` (re-embedded via `embed`), then delete
+/// the originals. The merged insight keeps the highest reference count and stays a
+/// favorite if any original was one. `new_title` may reuse one of the original titles.
+pub async fn merge_insights_in_dir<F, Fut>(
+    insights_dir: &std::path::Path,
+    titles: &[String],
+    new_title: &str,
+    embed: F,
+) -> Result<(), String>
+where
+    F: FnOnce(String) -> Fut,
+    Fut: std::future::Future<Output = Result<Vec<f32>, String>>,
+{
+    let new_title = new_title.trim();
+    if new_title.is_empty() {
+        return Err("New insight title is required".to_string());
+    }
+    let mut originals: Vec<&str> = Vec::new();
+    for title in titles.iter().map(|t| t.trim()) {
+        if !title.is_empty() && !originals.contains(&title) {
+            originals.push(title);
+        }
+    }
+    if originals.len() < 2 {
+        return Err("Select at least two insights to merge".to_string());
+    }
+
+    // Read everything up front so a missing insight aborts before any writes
+    let index = load_insight_index_in_dir(insights_dir)?;
+    let mut sections = Vec::with_capacity(originals.len());
+    for title in &originals {
+        let raw = read_insight_in_dir(insights_dir, title)?;
+        sections.push(insight_body(&raw, title).to_string());
+    }
+    let metas: Vec<&InsightMeta> = originals.iter().filter_map(|t| index.insights.get(*t)).collect();
+    let reference_count = metas.iter().map(|m| m.reference_count).max().unwrap_or(0);
+    let favorite = metas.iter().any(|m| m.favorite);
+
+    let content = sections.join("\n\n");
+    let embedding = embed(insight_embedding_text(new_title, &content)).await?;
+    store_insight_in_dir(insights_dir, new_title, &content, embedding)?;
+
+    for title in originals.iter().filter(|t| **t != new_title) {
+        delete_insight_in_dir(insights_dir, title)?;
+    }
+
+    let mut index = load_insight_index_in_dir(insights_dir)?;
+    if let Some(meta) = index.insights.get_mut(new_title) {
+        meta.reference_count = reference_count;
+        meta.favorite = favorite;
+    }
+    save_insight_index_in_dir(insights_dir, &index)?;
+
+    log::info!("Merged {} insights into: {}", originals.len(), new_title);
+    Ok(())
+}
+
 /// Increment reference count for an insight
 pub fn increment_insight_reference<R: Runtime>(
     app_handle: &AppHandle<R>,
     title: &str,
 ) -> Result<u32, String> {
+    let _guard = lock_usage_counters();
     let mut index = load_insight_index(app_handle)?;
     if let Some(meta) = index.insights.get_mut(title) {
         meta.reference_count += 1;
@@ -529,6 +1346,7 @@ pub fn get_promotion_candidates<R: Runtime>(
 pub fn find_relevant_insights<R: Runtime>(
     app_handle: &AppHandle<R>,
     query_embedding: &[f32],
+    threshold: f32,
 ) -> Result<Option<(String, String, f32)>, String> {
     let index = load_insight_index(app_handle)?;
     let mut best_score = -1.0f32;
@@ -542,8 +1360,7 @@ pub fn find_relevant_insights<R: Runtime>(
         }
     }
 
-    // Same threshold as topics (0.4)
-    if best_score > 0.4 {
+    if best_score > threshold {
         if let Some(title) = best_title {
             if let Ok(content) = read_insight(app_handle, &title) {
                 return Ok(Some((title, content, best_score)));
@@ -554,58 +1371,337 @@ pub fn find_relevant_insights<R: Runtime>(
     Ok(None)
 }
 
-/// Find best match between topics and insights, preferring insights on tie
-/// Returns (name, content, is_insight)
+/// Default minimum cosine similarity for topic/insight context
+pub const DEFAULT_CONTEXT_THRESHOLD: f32 = 0.4;
+/// Default minimum insight score for an insight to beat an equal-or-weaker topic
+pub const DEFAULT_INSIGHT_TIE_THRESHOLD: f32 = 0.55;
+
+/// Topic or insight context selected for a query
+#[derive(Debug, Clone)]
+pub struct RelevantContext {
+    pub name: String,
+    pub content: String,
+    pub is_insight: bool,
+    pub score: f32,
+}
+
+/// Which kind of context to use given the best insight/topic scores
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContextChoice {
+    Insight,
+    Topic,
+}
+
+/// Decide between the best insight and best topic (pure scoring logic)
+///
+/// Both must clear `threshold`. An insight only wins a tie (insight >= topic)
+/// against a qualifying topic when it also clears `insight_tie_threshold`,
+/// so marginal insights don't displace solid topic matches.
+pub fn choose_context(
+    insight_score: Option<f32>,
+    topic_score: Option<f32>,
+    threshold: f32,
+    insight_tie_threshold: f32,
+) -> Option<ContextChoice> {
+    let insight = insight_score.filter(|s| *s > threshold);
+    let topic = topic_score.filter(|s| *s > threshold);
+
+    match (insight, topic) {
+        (Some(i), Some(t)) => {
+            if i >= t && i >= insight_tie_threshold {
+                Some(ContextChoice::Insight)
+            } else {
+                Some(ContextChoice::Topic)
+            }
+        }
+        (Some(_), None) => Some(ContextChoice::Insight),
+        (None, Some(_)) => Some(ContextChoice::Topic),
+        (None, None) => None,
+    }
+}
+
+/// Find best match between topics and insights
+/// Insights win ties only when they clear `insight_tie_threshold`
 pub fn find_relevant_context<R: Runtime>(
     app_handle: &AppHandle<R>,
     query_embedding: &[f32],
-) -> Result<Option<(String, String, bool)>, String> {
-    let insight_result = find_relevant_insights(app_handle, query_embedding)?;
+    threshold: f32,
+    insight_tie_threshold: f32,
+) -> Result<Option<RelevantContext>, String> {
+    let context = select_relevant_context(app_handle, query_embedding, threshold, insight_tie_threshold)?;
+    if let Some(context) = &context {
+        // Count the selection towards the insight's references / topic's access stats
+        let recorded = if context.is_insight {
+            increment_insight_reference(app_handle, &context.name)
+        } else {
+            increment_topic_access(app_handle, &context.name)
+        };
+        if let Err(e) = recorded {
+            log::warn!("[Memories] Failed to record use of {}: {}", context.name, e);
+        }
+    }
+    Ok(context)
+}
+
+/// Read-only core of `find_relevant_context`: picks the context without recording its use
+pub fn select_relevant_context<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    query_embedding: &[f32],
+    threshold: f32,
+    insight_tie_threshold: f32,
+) -> Result<Option<RelevantContext>, String> {
+    let insight_result = find_relevant_insights(app_handle, query_embedding, threshold)?;
 
-    // Get topic score for comparison (need to duplicate some logic)
+    // Best topic is the parent of the best matching chunk
     let topic_index = load_topic_index(app_handle)?;
-    let mut topic_score = -1.0f32;
-    let mut best_topic = None;
-    for (topic, embedding) in topic_index.topics.iter() {
-        let score = crate::interactions::cosine_similarity(query_embedding, embedding);
-        if score > topic_score {
-            topic_score = score;
-            best_topic = Some(topic.clone());
-        }
-    }
-
-    match insight_result {
-        Some((title, content, insight_score)) => {
-            // Prefer insight if score >= topic score (insight wins ties)
-            if insight_score >= topic_score {
-                // Increment reference count for this insight
-                let _ = increment_insight_reference(app_handle, &title);
-                Ok(Some((title, content, true)))
-            } else if topic_score > 0.4 {
-                if let Some(topic) = best_topic {
-                    if let Ok(content) = read_topic_summary(app_handle, &topic) {
-                        return Ok(Some((topic, content, false)));
-                    }
-                }
-                Ok(None)
-            } else {
-                Ok(None)
-            }
+    let best_chunk = score_topic_chunks(&topic_index, query_embedding, 1).into_iter().next();
+    let best_topic = best_chunk
+        .as_ref()
+        .and_then(|hit| parse_chunk_key(&hit.doc_id))
+        .map(|(topic, _)| topic.to_string());
+
+    let insight_score = insight_result.as_ref().map(|(_, _, score)| *score);
+    let topic_score = best_chunk.as_ref().map(|hit| hit.score);
+
+    match choose_context(insight_score, topic_score, threshold, insight_tie_threshold) {
+        Some(ContextChoice::Insight) => {
+            let Some((title, content, score)) = insight_result else {
+                return Ok(None);
+            };
+            Ok(Some(RelevantContext {
+                name: title,
+                content,
+                is_insight: true,
+                score,
+            }))
         }
-        None => {
-            // No insight match, try topics
-            if topic_score > 0.4 {
-                if let Some(topic) = best_topic {
-                    if let Ok(content) = read_topic_summary(app_handle, &topic) {
-                        return Ok(Some((topic, content, false)));
-                    }
+        Some(ContextChoice::Topic) => {
+            if let (Some(topic), Some(score)) = (best_topic, topic_score) {
+                if let Ok(content) = read_topic_summary(app_handle, &topic) {
+                    return Ok(Some(RelevantContext {
+                        name: topic,
+                        content,
+                        is_insight: false,
+                        score,
+                    }));
                 }
             }
             Ok(None)
         }
+        None => Ok(None),
+    }
+}
+
+// ============================================================================
+// Topic Access Stats - Which topic summaries actually get used
+// ============================================================================
+
+/// Per-topic usage, for spotting topics that are never retrieved
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TopicStats {
+    pub name: String,
+    pub access_count: u32,
+    pub last_accessed: Option<DateTime<Utc>>,
+}
+
+impl TopicIndex {
+    /// Count a selection of `topic` as context, returning its new access count
+    pub fn record_access(&mut self, topic: &str, now: DateTime<Utc>) -> u32 {
+        let access = self.access.entry(topic.to_string()).or_default();
+        access.count += 1;
+        access.last_accessed = Some(now);
+        access.count
+    }
+
+    /// Stats for every indexed topic (never-used ones included), most accessed first
+    pub fn stats(&self) -> Vec<TopicStats> {
+        let mut stats: Vec<TopicStats> = self
+            .topics
+            .keys()
+            .map(|name| {
+                let access = self.access.get(name).cloned().unwrap_or_default();
+                TopicStats {
+                    name: name.clone(),
+                    access_count: access.count,
+                    last_accessed: access.last_accessed,
+                }
+            })
+            .collect();
+        stats.sort_by(|a, b| b.access_count.cmp(&a.access_count).then_with(|| a.name.cmp(&b.name)));
+        stats
+    }
+}
+
+/// Serializes read-modify-write updates of the topic/insight usage counters
+static USAGE_COUNTER_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+fn lock_usage_counters() -> std::sync::MutexGuard<'static, ()> {
+    // Index writes are atomic on disk, so a poisoned lock is still usable
+    USAGE_COUNTER_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Record a topic selection in the index under `topics_dir`
+pub fn record_topic_access_in_dir(
+    topics_dir: &std::path::Path,
+    topic: &str,
+    now: DateTime<Utc>,
+) -> Result<u32, String> {
+    let _guard = lock_usage_counters();
+    let index_path = topics_dir.join("index.json");
+    let mut index: TopicIndex = read_json_or_default(&index_path)?;
+    if !index.topics.contains_key(topic) {
+        return Err(format!("Topic not found in index: {}", topic));
+    }
+    let count = index.record_access(topic, now);
+    let content = serde_json::to_string_pretty(&index)
+        .map_err(|e| format!("Failed to serialize topic index: {}", e))?;
+    crate::retrieval::write_atomic(&index_path, content)
+        .map_err(|e| format!("Failed to write topic index: {}", e))?;
+    Ok(count)
+}
+
+/// Increment access count for a topic chosen as context
+pub fn increment_topic_access<R: Runtime>(app_handle: &AppHandle<R>, topic: &str) -> Result<u32, String> {
+    record_topic_access_in_dir(&get_topics_dir(app_handle)?, topic, Utc::now())
+}
+
+/// Access counts and last-access times for all topics (no embedding dims check needed)
+pub fn get_topic_stats<R: Runtime>(app_handle: &AppHandle<R>) -> Result<Vec<TopicStats>, String> {
+    let index: TopicIndex = read_json_or_default(&get_topic_index_path(app_handle)?)?;
+    Ok(index.stats())
+}
+
+// ============================================================================
+// Favorites - Insights/topics injected into context regardless of similarity
+// ============================================================================
+
+/// Most favorites injected per turn (in addition to the similarity match)
+pub const MAX_FAVORITE_CONTEXT: usize = 3;
+
+impl InsightIndex {
+    /// Toggle an insight's favorite flag, returning the new state (None if not indexed)
+    pub fn toggle_favorite(&mut self, title: &str) -> Option<bool> {
+        let meta = self.insights.get_mut(title)?;
+        meta.favorite = !meta.favorite;
+        Some(meta.favorite)
+    }
+}
+
+impl TopicIndex {
+    /// Toggle a topic's favorite flag, returning the new state (None if not indexed)
+    pub fn toggle_favorite(&mut self, topic: &str) -> Option<bool> {
+        if !self.topics.contains_key(topic) {
+            return None;
+        }
+        if self.favorites.remove(topic) {
+            Some(false)
+        } else {
+            self.favorites.insert(topic.to_string());
+            Some(true)
+        }
     }
 }
 
+/// A favorited insight or topic picked for injection
+#[derive(Debug, Clone, PartialEq)]
+pub struct FavoriteRef {
+    pub name: String,
+    pub is_insight: bool,
+    /// Similarity to the query (0 without a query embedding)
+    pub score: f32,
+}
+
+/// Pick up to `cap` favorites, most query-relevant first (by name without a query).
+/// `exclude` skips the item already chosen by similarity so it isn't injected twice.
+pub fn select_favorites(
+    insights: &InsightIndex,
+    topics: &TopicIndex,
+    query_embedding: Option<&[f32]>,
+    exclude: Option<&str>,
+    cap: usize,
+) -> Vec<FavoriteRef> {
+    let score = |embedding: Option<&Vec<f32>>| match (query_embedding, embedding) {
+        (Some(query), Some(embedding)) => crate::interactions::cosine_similarity(query, embedding),
+        _ => 0.0,
+    };
+
+    let favorite_insights = insights
+        .insights
+        .iter()
+        .filter(|(_, meta)| meta.favorite)
+        .map(|(title, meta)| FavoriteRef {
+            name: title.clone(),
+            is_insight: true,
+            score: score(Some(&meta.embedding)),
+        });
+    let favorite_topics = topics.favorites.iter().map(|topic| FavoriteRef {
+        name: topic.clone(),
+        is_insight: false,
+        score: score(topics.topics.get(topic)),
+    });
+
+    let mut favorites: Vec<FavoriteRef> = favorite_insights
+        .chain(favorite_topics)
+        .filter(|fav| Some(fav.name.as_str()) != exclude)
+        .collect();
+    favorites.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    favorites.truncate(cap);
+    favorites
+}
+
+/// Load the favorites to inject this turn, skipping `exclude` (the similarity match)
+pub fn find_favorite_context<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    query_embedding: Option<&[f32]>,
+    exclude: Option<&str>,
+) -> Result<Vec<RelevantContext>, String> {
+    let insights = load_insight_index(app_handle)?;
+    let topics = load_topic_index(app_handle)?;
+
+    let favorites = select_favorites(&insights, &topics, query_embedding, exclude, MAX_FAVORITE_CONTEXT);
+    Ok(favorites
+        .into_iter()
+        .filter_map(|fav| {
+            let content = if fav.is_insight {
+                read_insight(app_handle, &fav.name)
+            } else {
+                read_topic_summary(app_handle, &fav.name)
+            };
+            content.ok().map(|content| RelevantContext {
+                name: fav.name,
+                content,
+                is_insight: fav.is_insight,
+                score: fav.score,
+            })
+        })
+        .collect())
+}
+
+/// Toggle an insight's favorite flag, returning the new state
+pub fn toggle_favorite_insight<R: Runtime>(app_handle: &AppHandle<R>, title: &str) -> Result<bool, String> {
+    let mut index = load_insight_index(app_handle)?;
+    let favorite = index
+        .toggle_favorite(title)
+        .ok_or_else(|| format!("Insight not found in index: {}", title))?;
+    save_insight_index(app_handle, &index)?;
+    Ok(favorite)
+}
+
+/// Toggle a topic's favorite flag, returning the new state
+pub fn toggle_favorite_topic<R: Runtime>(app_handle: &AppHandle<R>, topic: &str) -> Result<bool, String> {
+    let mut index = load_topic_index(app_handle)?;
+    let favorite = index
+        .toggle_favorite(topic)
+        .ok_or_else(|| format!("Topic not found in index: {}", topic))?;
+    save_topic_index(app_handle, &index)?;
+    Ok(favorite)
+}
+
 /// Rebuild the insight index by regenerating embeddings for all insight files
 pub async fn rebuild_insight_index<R: Runtime>(
     app_handle: &AppHandle<R>,
@@ -617,35 +1713,55 @@ pub async fn rebuild_insight_index<R: Runtime>(
         return Ok(0);
     }
 
-    let mut index = InsightIndex::default();
+    // Keep favorites across the rebuild (the old index may be unreadable after a dims change)
+    let old_favorites: HashSet<String> = load_insight_index(app_handle)
+        .map(|old| {
+            old.insights
+                .into_iter()
+                .filter(|(_, meta)| meta.favorite)
+                .map(|(title, _)| title)
+                .collect()
+        })
+        .unwrap_or_default();
+    let old_names = display_names_by_stem(load_insight_index_in_dir(&insights_dir).map(|old| old.filenames).unwrap_or_default());
+
+    let settings = crate::interactions::embedding_settings();
+    let mut index = InsightIndex {
+        embedding_dims: Some(settings.dims),
+        embedding_provider: Some(settings.provider),
+        ..Default::default()
+    };
     let mut count = 0;
 
-    if let Ok(entries) = fs::read_dir(&insights_dir) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.extension().and_then(|s| s.to_str()) == Some("md") {
-                if let Some(title) = path.file_stem().and_then(|s| s.to_str()) {
-                    if let Ok(content) = fs::read_to_string(&path) {
-                        let embedding_text = format!("Insight: {}\nContent: {}", title, content.chars().take(1000).collect::<String>());
-                        match crate::interactions::generate_embedding(http_client, &embedding_text, api_key).await {
-                            Ok(embedding) => {
-                                index.insights.insert(title.to_string(), InsightMeta {
-                                    embedding,
-                                    reference_count: 0,
-                                    update_count: 1, // Assume 1 update for existing files
-                                    created_at: Utc::now(),
-                                });
-                                count += 1;
-                                log::info!("Indexed insight: {}", title);
-                            }
-                            Err(e) => {
-                                log::error!("Failed to generate embedding for insight {}: {}", title, e);
-                            }
+    if let Ok(paths) = md_files(&insights_dir) {
+        let total = paths.len();
+        for (done, path) in paths.iter().enumerate() {
+            emit_rebuild_progress(app_handle, "insight", done, total);
+            if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                let title = old_names.get(stem).map(String::as_str).unwrap_or(stem);
+                if let Ok(content) = fs::read_to_string(path) {
+                    let embedding_text = format!("Insight: {}\nContent: {}", title, content.chars().take(1000).collect::<String>());
+                    match crate::interactions::generate_embedding(http_client, &embedding_text, api_key).await {
+                        Ok(embedding) => {
+                            index.insights.insert(title.to_string(), InsightMeta {
+                                embedding,
+                                reference_count: 0,
+                                update_count: 1, // Assume 1 update for existing files
+                                created_at: Utc::now(),
+                                favorite: old_favorites.contains(title),
+                            });
+                            index.filenames.insert(title.to_string(), stem.to_string());
+                            count += 1;
+                            log::info!("Indexed insight: {}", title);
+                        }
+                        Err(e) => {
+                            log::error!("Failed to generate embedding for insight {}: {}", title, e);
                         }
                     }
                 }
             }
         }
+        emit_rebuild_progress(app_handle, "insight", total, total);
     }
 
     save_insight_index(app_handle, &index)?;
@@ -655,6 +1771,16 @@ pub async fn rebuild_insight_index<R: Runtime>(
 /// Load memories from disk
 pub fn load_memories<R: Runtime>(app_handle: &AppHandle<R>) -> Result<MemoryStore, String> {
     let memories_dir = get_memories_dir(app_handle)?;
+    load_memories_in_dir(&memories_dir)
+}
+
+/// Memory budget dashboard: per-category counts and the most important items
+pub fn get_memory_categories_summary<R: Runtime>(app_handle: &AppHandle<R>) -> Result<MemorySummary, String> {
+    Ok(load_memories(app_handle)?.category_summary(TOKEN_BUDGET, SUMMARY_TOP_ITEMS))
+}
+
+/// Load memories from a specific memories directory
+pub fn load_memories_in_dir(memories_dir: &std::path::Path) -> Result<MemoryStore, String> {
     let json_path = memories_dir.join(MEMORIES_FILENAME);
 
     if !json_path.exists() {
@@ -671,13 +1797,17 @@ pub fn load_memories<R: Runtime>(app_handle: &AppHandle<R>) -> Result<MemoryStor
 /// Save memories to disk (both JSON and human-readable MD)
 pub fn save_memories<R: Runtime>(app_handle: &AppHandle<R>, store: &MemoryStore) -> Result<(), String> {
     let memories_dir = get_memories_dir(app_handle)?;
+    save_memories_in_dir(&memories_dir, store)
+}
 
+/// Save memories (JSON + markdown view) into a specific memories directory
+fn save_memories_in_dir(memories_dir: &std::path::Path, store: &MemoryStore) -> Result<(), String> {
     // Save JSON (source of truth)
     let json_path = memories_dir.join(MEMORIES_FILENAME);
     let json_content = serde_json::to_string_pretty(store)
         .map_err(|e| format!("Failed to serialize memories: {}", e))?;
 
-    fs::write(&json_path, json_content)
+    crate::retrieval::write_atomic(&json_path, json_content)
         .map_err(|e| format!("Failed to write memories JSON: {}", e))?;
 
     // Also write human-readable markdown
@@ -687,38 +1817,241 @@ pub fn save_memories<R: Runtime>(app_handle: &AppHandle<R>, store: &MemoryStore)
         store.format_for_prompt()
     );
 
-    fs::write(&md_path, md_content)
+    crate::retrieval::write_atomic(&md_path, md_content)
         .map_err(|e| format!("Failed to write memories MD: {}", e))?;
 
     Ok(())
 }
 
-/// Add a memory and save to disk (enforces token budget)
-pub fn add_memory<R: Runtime>(
-    app_handle: &AppHandle<R>,
+/// The `save_memory` tool: add a memory from the model's arguments and report the outcome
+pub fn save_memory_tool<R: Runtime>(app_handle: &AppHandle<R>, args: &serde_json::Value) -> String {
+    match get_memories_dir(app_handle) {
+        Ok(memories_dir) => save_memory_tool_in_dir(&memories_dir, args),
+        Err(e) => format!("Failed to save memory: {}", e),
+    }
+}
+
+/// The `save_memory` tool within a specific memories directory
+pub fn save_memory_tool_in_dir(memories_dir: &std::path::Path, args: &serde_json::Value) -> String {
+    let content = args["content"].as_str().unwrap_or_default().to_string();
+    let importance = args["importance"].as_u64().unwrap_or(3) as u8;
+    let category = match args["category"].as_str().unwrap_or("fact") {
+        "preference" => MemoryCategory::Preference,
+        "project" => MemoryCategory::Project,
+        "interaction" => MemoryCategory::Interaction,
+        _ => MemoryCategory::Fact,
+    };
+
+    match add_memory_in_dir(memories_dir, category, content.clone(), importance) {
+        Ok(_) => format!("Memory saved: {}", content),
+        Err(e) => format!("Failed to save memory: {}", e),
+    }
+}
+
+/// Add a memory within a specific memories directory
+pub fn add_memory_in_dir(
+    memories_dir: &std::path::Path,
     category: MemoryCategory,
     content: String,
     importance: u8,
 ) -> Result<Memory, String> {
-    let mut store = load_memories(app_handle)?;
+    let mut store = load_memories_in_dir(memories_dir)?;
+    let before = store.clone();
 
-    let memory = Memory::new(category, content, importance);
-    store.add(memory.clone());
+    // Merge near-duplicates instead of accumulating repeated entries
+    let memory = store.add_or_merge(Memory::new(category, content, importance));
 
     // Enforce token budget
     store.prune_to_token_budget(TOKEN_BUDGET);
 
-    save_memories(app_handle, &store)?;
+    save_memories_in_dir(memories_dir, &store)?;
+    record_undo_in_dir(memories_dir, &before, &store, &memory.id);
 
     log::info!("Memory saved: {} (importance: {})", memory.content, memory.importance);
 
     Ok(memory)
 }
 
+/// Edit a memory's content (and optionally importance) by ID
+pub fn edit_memory<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    id: &str,
+    content: String,
+    importance: Option<u8>,
+) -> Result<Memory, String> {
+    let memories_dir = get_memories_dir(app_handle)?;
+    edit_memory_in_dir(&memories_dir, id, content, importance)
+}
+
+/// Edit a memory within a specific memories directory
+pub fn edit_memory_in_dir(
+    memories_dir: &std::path::Path,
+    id: &str,
+    content: String,
+    importance: Option<u8>,
+) -> Result<Memory, String> {
+    if content.trim().is_empty() {
+        return Err("Memory content cannot be empty".to_string());
+    }
+
+    let mut store = load_memories_in_dir(memories_dir)?;
+    let before = store.clone();
+    let memory = store
+        .memories
+        .iter_mut()
+        .find(|m| m.id == id)
+        .ok_or_else(|| format!("Memory not found: {}", id))?;
+    memory.content = content;
+    if let Some(importance) = importance {
+        memory.importance = importance.clamp(1, 5);
+    }
+    let memory = memory.clone();
+
+    save_memories_in_dir(memories_dir, &store)?;
+    record_undo_in_dir(memories_dir, &before, &store, id);
+
+    log::info!("Memory edited: {}", id);
+    Ok(memory)
+}
+
+// ============================================================================
+// Undo - Revert recent memory writes
+// ============================================================================
+
+/// Memory writes that can be undone, most recent last
+pub const MEMORY_UNDO_DEPTH: usize = 10;
+const MEMORY_UNDO_FILENAME: &str = "memory_undo.json";
+
+/// What a single add/edit changed, enough to put it back
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MemoryUndoEntry {
+    pub memory_id: String,
+    /// The memory before the write; None when the write created it
+    pub before: Option<Memory>,
+    /// Other memories evicted by the token budget during the write
+    #[serde(default)]
+    pub pruned: Vec<Memory>,
+}
+
+/// Push an undo entry for a write that turned `before` into `after`.
+/// Failures only cost the undo, so they're logged rather than returned.
+fn record_undo_in_dir(memories_dir: &std::path::Path, before: &MemoryStore, after: &MemoryStore, memory_id: &str) {
+    let path = memories_dir.join(MEMORY_UNDO_FILENAME);
+    let mut journal: Vec<MemoryUndoEntry> = match read_json_or_default(&path) {
+        Ok(journal) => journal,
+        Err(e) => {
+            log::warn!("[Memories] Resetting unreadable undo journal: {}", e);
+            Vec::new()
+        }
+    };
+
+    let after_ids: HashSet<&str> = after.memories.iter().map(|m| m.id.as_str()).collect();
+    journal.push(MemoryUndoEntry {
+        memory_id: memory_id.to_string(),
+        before: before.memories.iter().find(|m| m.id == memory_id).cloned(),
+        pruned: before
+            .memories
+            .iter()
+            .filter(|m| m.id != memory_id && !after_ids.contains(m.id.as_str()))
+            .cloned()
+            .collect(),
+    });
+    if journal.len() > MEMORY_UNDO_DEPTH {
+        journal.drain(..journal.len() - MEMORY_UNDO_DEPTH);
+    }
+
+    let result = serde_json::to_string_pretty(&journal)
+        .map_err(|e| e.to_string())
+        .and_then(|content| crate::retrieval::write_atomic(&path, content).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        log::warn!("[Memories] Failed to record undo entry: {}", e);
+    }
+}
+
+/// Revert the most recent memory add/edit. Returns false if there was nothing to undo.
+pub fn undo_last_memory<R: Runtime>(app_handle: &AppHandle<R>) -> Result<bool, String> {
+    let memories_dir = get_memories_dir(app_handle)?;
+    undo_last_memory_in_dir(&memories_dir)
+}
+
+/// Revert the most recent memory write within a specific memories directory
+pub fn undo_last_memory_in_dir(memories_dir: &std::path::Path) -> Result<bool, String> {
+    let path = memories_dir.join(MEMORY_UNDO_FILENAME);
+    let mut journal: Vec<MemoryUndoEntry> = read_json_or_default(&path)?;
+    let Some(entry) = journal.pop() else {
+        return Ok(false);
+    };
+
+    let mut store = load_memories_in_dir(memories_dir)?;
+    match entry.before {
+        None => {
+            store.remove(&entry.memory_id);
+        }
+        Some(before) => match store.memories.iter_mut().find(|m| m.id == before.id) {
+            Some(current) => *current = before,
+            None => store.memories.push(before),
+        },
+    }
+    for memory in entry.pruned {
+        if !store.memories.iter().any(|m| m.id == memory.id) {
+            store.memories.push(memory);
+        }
+    }
+
+    save_memories_in_dir(memories_dir, &store)?;
+    let content = serde_json::to_string_pretty(&journal)
+        .map_err(|e| format!("Failed to serialize undo journal: {}", e))?;
+    crate::retrieval::write_atomic(&path, content)
+        .map_err(|e| format!("Failed to write undo journal: {}", e))?;
+
+    log::info!("Memory write undone: {}", entry.memory_id);
+    Ok(true)
+}
+
+// ============================================================================
+// Importance Decay - Stale low-value memories fade out
+// ============================================================================
+
+/// Memories younger than this keep their importance
+pub const DECAY_GRACE_DAYS: i64 = 30;
+/// Each full interval past the grace period costs one importance step
+pub const DECAY_INTERVAL_DAYS: i64 = 30;
+/// Importance-1 memories older than this are removed
+pub const DECAY_MAX_AGE_DAYS: i64 = 180;
+
+/// Outcome of a decay sweep
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct DecayResult {
+    pub decayed: usize,
+    pub removed: usize,
+}
+
+/// Run a decay sweep over the memories in a specific directory, saving only if something changed
+pub fn decay_memories_in_dir(
+    memories_dir: &std::path::Path,
+    now: DateTime<Utc>,
+) -> Result<DecayResult, String> {
+    let mut store = load_memories_in_dir(memories_dir)?;
+    let before = serde_json::to_string(&store).unwrap_or_default();
+    let result = store.decay(now);
+
+    // decayed_through can advance without a visible change, so compare the serialized store
+    if serde_json::to_string(&store).unwrap_or_default() != before {
+        save_memories_in_dir(memories_dir, &store)?;
+    }
+
+    Ok(result)
+}
+
+/// Daily decay sweep, called from the background job
+pub fn decay_memories<R: Runtime>(app_handle: &AppHandle<R>) -> Result<DecayResult, String> {
+    let memories_dir = get_memories_dir(app_handle)?;
+    decay_memories_in_dir(&memories_dir, Utc::now())
+}
+
 // TODO: Feature Request - Background cleanup job that runs daily to:
-// 1. Remove stale/low-importance memories
-// 2. Summarize old interaction memories
-// 3. Consolidate duplicate preferences
+// 1. Summarize old interaction memories
+// 2. Consolidate duplicate preferences
 /// Delete a memory by ID
 #[allow(dead_code)]
 pub fn delete_memory<R: Runtime>(app_handle: &AppHandle<R>, id: &str) -> Result<bool, String> {
@@ -733,9 +2066,232 @@ pub fn delete_memory<R: Runtime>(app_handle: &AppHandle<R>, id: &str) -> Result<
     Ok(removed)
 }
 
+/// Toggle a memory's pinned flag by ID, returning the new state
+pub fn pin_memory<R: Runtime>(app_handle: &AppHandle<R>, id: &str) -> Result<bool, String> {
+    let mut store = load_memories(app_handle)?;
+    let pinned = store
+        .toggle_pin(id)
+        .ok_or_else(|| format!("Memory not found: {}", id))?;
+
+    save_memories(app_handle, &store)?;
+    log::info!("Memory {}: {}", if pinned { "pinned" } else { "unpinned" }, id);
+
+    Ok(pinned)
+}
+
 /// Get formatted memories for prompt injection
 pub fn get_memories_for_prompt<R: Runtime>(app_handle: &AppHandle<R>) -> Result<String, String> {
     let store = load_memories(app_handle)?;
     Ok(store.format_for_prompt())
 }
 
+// ============================================================================
+// Export / Import Bundle - Carry memory between machines
+// ============================================================================
+
+/// Current memory bundle format version
+pub const MEMORY_BUNDLE_VERSION: u32 = 1;
+
+/// Everything needed to restore the memory system on another machine
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MemoryBundle {
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub memories: MemoryStore,
+    pub topics: std::collections::BTreeMap<String, String>, // filename -> markdown
+    pub topic_index: TopicIndex,
+    pub insights: std::collections::BTreeMap<String, String>, // filename -> markdown
+    pub insight_index: InsightIndex,
+}
+
+/// Read all `.md` files in a directory (missing directory = empty)
+fn read_markdown_files(dir: &std::path::Path) -> Result<std::collections::BTreeMap<String, String>, String> {
+    let mut files = std::collections::BTreeMap::new();
+    if !dir.exists() {
+        return Ok(files);
+    }
+    let entries = fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|s| s.to_str()) != Some("md") {
+            continue;
+        }
+        if let Some(name) = path.file_name().and_then(|s| s.to_str()) {
+            let content = fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read {}: {}", name, e))?;
+            files.insert(name.to_string(), content);
+        }
+    }
+    Ok(files)
+}
+
+/// Read a JSON file, returning the default when missing
+fn read_json_or_default<T: serde::de::DeserializeOwned + Default>(
+    path: &std::path::Path,
+) -> Result<T, String> {
+    if !path.exists() {
+        return Ok(T::default());
+    }
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+}
+
+/// Build a bundle from a memories directory
+pub fn export_bundle_from_dir(memories_dir: &std::path::Path) -> Result<MemoryBundle, String> {
+    let topics_dir = memories_dir.join("topics");
+    let insights_dir = memories_dir.join("insights");
+
+    Ok(MemoryBundle {
+        version: MEMORY_BUNDLE_VERSION,
+        exported_at: Utc::now(),
+        memories: read_json_or_default(&memories_dir.join(MEMORIES_FILENAME))?,
+        topics: read_markdown_files(&topics_dir)?,
+        topic_index: read_json_or_default(&topics_dir.join("index.json"))?,
+        insights: read_markdown_files(&insights_dir)?,
+        insight_index: read_json_or_default(&insights_dir.join("index.json"))?,
+    })
+}
+
+/// Check a bundle's version and file names before touching disk
+pub fn validate_bundle(bundle: &MemoryBundle) -> Result<(), String> {
+    if bundle.version != MEMORY_BUNDLE_VERSION {
+        return Err(format!(
+            "Unsupported memory bundle version {} (expected {})",
+            bundle.version, MEMORY_BUNDLE_VERSION
+        ));
+    }
+    for name in bundle.topics.keys().chain(bundle.insights.keys()) {
+        let stem = name.strip_suffix(".md").unwrap_or("");
+        if stem.is_empty() || sanitize_filename(stem) != stem {
+            return Err(format!("Invalid file name in memory bundle: {}", name));
+        }
+    }
+    Ok(())
+}
+
+/// Write a bundle's contents into an empty directory, dropping index entries without files
+fn write_bundle_to_dir(dir: &std::path::Path, bundle: &MemoryBundle) -> Result<(), String> {
+    let topics_dir = dir.join("topics");
+    let insights_dir = dir.join("insights");
+    fs::create_dir_all(&topics_dir).map_err(|e| format!("Failed to create topics directory: {}", e))?;
+    fs::create_dir_all(&insights_dir)
+        .map_err(|e| format!("Failed to create insights directory: {}", e))?;
+
+    save_memories_in_dir(dir, &bundle.memories)?;
+
+    for (name, content) in &bundle.topics {
+        fs::write(topics_dir.join(name), content)
+            .map_err(|e| format!("Failed to write topic {}: {}", name, e))?;
+    }
+    for (name, content) in &bundle.insights {
+        fs::write(insights_dir.join(name), content)
+            .map_err(|e| format!("Failed to write insight {}: {}", name, e))?;
+    }
+
+    // Rebuild indexes so they only reference files that exist
+    let topic_filenames = &bundle.topic_index.filenames;
+    let has_topic =
+        |topic: &str| {
+            resolve_file_stem(topic_filenames, topic).is_some_and(|stem| bundle.topics.contains_key(&format!("{}.md", stem)))
+        };
+    let mut topic_index = bundle.topic_index.clone();
+    topic_index.topics.retain(|topic, _| has_topic(topic));
+    topic_index
+        .chunks
+        .retain(|key, _| parse_chunk_key(key).map(|(topic, _)| has_topic(topic)).unwrap_or(false));
+    topic_index.favorites.retain(|topic| has_topic(topic));
+    topic_index.access.retain(|topic, _| has_topic(topic));
+    topic_index.filenames.retain(|topic, _| has_topic(topic));
+    let mut insight_index = bundle.insight_index.clone();
+    let insight_filenames = &bundle.insight_index.filenames;
+    let has_insight =
+        |title: &str| {
+            resolve_file_stem(insight_filenames, title)
+                .is_some_and(|stem| bundle.insights.contains_key(&format!("{}.md", stem)))
+        };
+    insight_index.insights.retain(|title, _| has_insight(title));
+    insight_index.filenames.retain(|title, _| has_insight(title));
+
+    fs::write(
+        topics_dir.join("index.json"),
+        serde_json::to_string_pretty(&topic_index)
+            .map_err(|e| format!("Failed to serialize topic index: {}", e))?,
+    )
+    .map_err(|e| format!("Failed to write topic index: {}", e))?;
+    fs::write(
+        insights_dir.join("index.json"),
+        serde_json::to_string_pretty(&insight_index)
+            .map_err(|e| format!("Failed to serialize insight index: {}", e))?,
+    )
+    .map_err(|e| format!("Failed to write insight index: {}", e))?;
+
+    Ok(())
+}
+
+/// Replace a memories directory with a bundle's contents.
+/// Everything is written to a sibling temp directory first and swapped in with
+/// renames, so a failure never leaves a partially imported memory store.
+pub fn import_bundle_into_dir(memories_dir: &std::path::Path, bundle: &MemoryBundle) -> Result<(), String> {
+    validate_bundle(bundle)?;
+
+    let parent = memories_dir
+        .parent()
+        .ok_or("Memories directory has no parent")?;
+    let tmp_dir = parent.join("memories.import-tmp");
+    let backup_dir = parent.join("memories.import-bak");
+    for dir in [&tmp_dir, &backup_dir] {
+        if dir.exists() {
+            fs::remove_dir_all(dir).map_err(|e| format!("Failed to clear {}: {}", dir.display(), e))?;
+        }
+    }
+
+    if let Err(e) = write_bundle_to_dir(&tmp_dir, bundle) {
+        let _ = fs::remove_dir_all(&tmp_dir);
+        return Err(e);
+    }
+
+    if memories_dir.exists() {
+        fs::rename(memories_dir, &backup_dir)
+            .map_err(|e| format!("Failed to move existing memories aside: {}", e))?;
+    }
+    if let Err(e) = fs::rename(&tmp_dir, memories_dir) {
+        // Put the original store back
+        if backup_dir.exists() {
+            let _ = fs::rename(&backup_dir, memories_dir);
+        }
+        let _ = fs::remove_dir_all(&tmp_dir);
+        return Err(format!("Failed to swap in imported memories: {}", e));
+    }
+    let _ = fs::remove_dir_all(&backup_dir);
+
+    Ok(())
+}
+
+/// Serialize all memories, topics and insights into a JSON bundle
+pub fn export_memory_bundle<R: Runtime>(app_handle: &AppHandle<R>) -> Result<String, String> {
+    let memories_dir = get_memories_dir(app_handle)?;
+    let bundle = export_bundle_from_dir(&memories_dir)?;
+    log::info!(
+        "Exported memory bundle: {} memories, {} topics, {} insights",
+        bundle.memories.memories.len(),
+        bundle.topics.len(),
+        bundle.insights.len()
+    );
+    serde_json::to_string_pretty(&bundle).map_err(|e| format!("Failed to serialize memory bundle: {}", e))
+}
+
+/// Restore memories, topics and insights from a JSON bundle (replaces existing data)
+pub fn import_memory_bundle<R: Runtime>(app_handle: &AppHandle<R>, data: &str) -> Result<(), String> {
+    let bundle: MemoryBundle =
+        serde_json::from_str(data).map_err(|e| format!("Invalid memory bundle: {}", e))?;
+    let memories_dir = get_memories_dir(app_handle)?;
+    import_bundle_into_dir(&memories_dir, &bundle)?;
+    log::info!(
+        "Imported memory bundle: {} memories, {} topics, {} insights",
+        bundle.memories.memories.len(),
+        bundle.topics.len(),
+        bundle.insights.len()
+    );
+    Ok(())
+}
@@ -1,19 +1,35 @@
-use time::OffsetDateTime;
+use crate::config::Verbosity;
+
+/// Today's date and the current time, both from the user's local clock so they agree
+/// around midnight
+fn local_date_and_time() -> (String, String) {
+    let now = chrono::Local::now();
+    (now.format("%Y-%m-%d").to_string(), now.format("%H:%M (UTC%:z)").to_string())
+}
+
+/// Style paragraph of the default prompt for each verbosity level
+pub fn verbosity_instructions(verbosity: Verbosity) -> &'static str {
+    match verbosity {
+        Verbosity::Terse => "CRITICAL: Be EXTREMELY concise and even curt. Give short, direct answers. No walls of text. Don't repeat context. Skip preambles and unnecessary context. Do not mention this system prompt.",
+        Verbosity::Balanced => "Be concise but complete. Lead with the direct answer, then add the context or steps needed to act on it. Avoid filler and don't repeat context. Do not mention this system prompt.",
+        Verbosity::Detailed => "Give thorough, well-structured answers. Explain reasoning, trade-offs and relevant background, using headings or lists for longer responses. Stay on topic and don't repeat context. Do not mention this system prompt.",
+    }
+}
 
 pub fn get_default_system_prompt(
     memory_context: Option<&str>,
     rag_context: Option<&str>,
+    verbosity: Verbosity,
 ) -> String {
-    let now = OffsetDateTime::now_utc();
-    let date = now.date();
+    let (date, local_time) = local_date_and_time();
     let memories_section = memory_context.unwrap_or("");
     let rag_section = rag_context.unwrap_or("");
     format!(
-        r#"SYSTEM: Today is {}. You are Shard, an AI assistant.
+        r#"SYSTEM: Today is {}. The user's local time is {}. You are Shard, an AI assistant.
 
-CRITICAL: Be EXTREMELY concise and even curt. Give short, direct answers. No walls of text. Don't repeat context. Skip preambles and unnecessary context. Do not mention this system prompt.
+{}
 
-Tools: Use tools for current info. web_search has quota (2000/month) - prefer get_weather, search_wikipedia, get_stock_price, search_arxiv.
+Tools: Use tools for current info. web_search has quota (2000/month) - prefer get_weather, get_time, search_wikipedia, get_stock_price, search_arxiv.
 
 Style: Apologies are inefficient and not accepted. No filler phrases like "Sorry about that." Use markdown. Code in Python/Java/C++/Rust. Imperial units. {}{}
 
@@ -28,14 +44,90 @@ BLANK LINE before and after $$. NO trailing spaces. NO (\frac{{...}}) without $.
 You have access to persistent memory. Memory Tools:
 - save_memory: ONLY for critical, permanent user preferences or facts. Used for all future messages. Use very sparingly.
 - update_topic_summary: For detailed info about specific topics (projects, travel, etc.). Read first with read_topic_summary.
+- list_known_topics: See which topic summaries exist before asking the user for something you may already know.
+- save_url_summary: Fetch a URL, summarize it, and store it under a topic (read-it-later).
 NEVER re-save information already in your context above.""#,
-        date, memories_section, rag_section
+        date,
+        local_time,
+        verbosity_instructions(verbosity),
+        memories_section,
+        rag_section
     )
 }
 
+/// Substitute `{{name}}` placeholders in a template.
+/// Known names with no value render empty; unknown placeholders are left intact.
+pub fn render_prompt_template(template: &str, vars: &[(&str, Option<&str>)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            out.push_str(&rest[start..]);
+            return out;
+        };
+        let name = after[..end].trim();
+        match vars.iter().find(|(key, _)| *key == name) {
+            Some((_, value)) => out.push_str(value.unwrap_or("")),
+            None => out.push_str(&rest[start..start + 2 + end + 2]),
+        }
+        rest = &after[end + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Render a user system prompt template with the current context.
+/// `{{tools}}` lists only the tools the model is actually offered in this mode.
+pub fn render_system_prompt_template(
+    template: &str,
+    config: &crate::config::AppConfig,
+    is_research_mode: bool,
+    memory_context: Option<&str>,
+    rag_context: Option<&str>,
+) -> String {
+    let (date, local_time) = local_date_and_time();
+    let tools = if config.enable_tools.unwrap_or(true) {
+        crate::tools::get_mode_tools(config, is_research_mode)
+            .iter()
+            .map(|t| t.function.name.clone())
+            .collect::<Vec<_>>()
+            .join(", ")
+    } else {
+        String::new()
+    };
+
+    let vars = [
+        ("memories", memory_context),
+        ("rag", rag_context),
+        ("date", Some(date.as_str())),
+        ("time", Some(local_time.as_str())),
+        ("tools", Some(tools.as_str())),
+    ];
+    render_prompt_template(template, &vars)
+}
+
+/// Pick the system prompt for a normal chat turn:
+/// custom prompt > custom template > built-in default
+pub fn build_system_prompt(
+    config: &crate::config::AppConfig,
+    is_research_mode: bool,
+    memory_context: Option<&str>,
+    rag_context: Option<&str>,
+) -> String {
+    if let Some(prompt) = &config.system_prompt {
+        return prompt.clone();
+    }
+    match config.system_prompt_template.as_deref().filter(|t| !t.trim().is_empty()) {
+        Some(template) => render_system_prompt_template(template, config, is_research_mode, memory_context, rag_context),
+        None => get_default_system_prompt(memory_context, rag_context, config.verbosity.unwrap_or_default()),
+    }
+}
+
 pub fn get_research_system_prompt() -> String {
-    let now = OffsetDateTime::now_utc();
-    let date = now.date();
+    let (date, _) = local_date_and_time();
     format!(
         r#"SYSTEM: Today is {}. You are a Deep Research agent that conducts multi-step, tool-driven investigations. You plan, browse, analyze, verify, and synthesize high‑quality insights. The only user-facing deliverable inpms a concise executive summary; do not include citations, links, quotes, appendices, or artifacts in the final output.
 
@@ -44,9 +136,8 @@ Operating principles:
 - Tools:
   - web_search: discover, filter, and read authoritative sources.
   - search_wikipedia: for general knowledge and background.
+  - read_wikipedia_section: for a specific section of an article when the summary isn't enough.
   - search_arxiv: for scientific and technical papers.
-  - get_stock_price: for financial data.
-  - get_weather: for current conditions (if relevant).
 - Recursion & backtracking: If evidence is weak or conflicts arise, pivot, expand scope, or revisit prior steps.
 - Rigor (internal): Prefer primary data. Triangulate key claims across independent sources.
 - Integrity: Never fabricate data. If something cannot be substantiated, reflect uncertainty succinctly.
@@ -0,0 +1,172 @@
+/**
+ * Rate Limits
+ *
+ * Client-side pacing for metered APIs:
+ * - Embeddings: a token bucket (requests per minute) in front of every
+ *   uncached `generate_embedding` call, so index rebuilds pace themselves
+ *   instead of tripping provider 429s
+ * - Brave web search: a monthly counter persisted to `web_search_quota.json`
+ *   that refuses searches once the plan's monthly allowance is used up
+ */
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, Runtime};
+
+/// Default embedding requests per minute (Gemini free tier)
+pub const DEFAULT_EMBEDDING_RPM: u32 = 100;
+/// Default Brave searches per calendar month (free plan)
+pub const DEFAULT_WEB_SEARCH_MONTHLY_LIMIT: u32 = 2000;
+
+const QUOTA_FILENAME: &str = "web_search_quota.json";
+
+// ============================================================================
+// Token Bucket
+// ============================================================================
+
+/// Refills at `rpm` tokens per minute and holds at most ten seconds' worth, so a
+/// burst can't spend a whole minute's budget at once. Callers reserve a token and
+/// wait out the returned delay; reservations past an empty bucket queue up.
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    rpm: u32,
+    capacity: f64,
+    tokens: f64,
+    last_refill: Option<Instant>,
+}
+
+impl TokenBucket {
+    pub fn new(rpm: u32) -> Self {
+        let capacity = (rpm as f64 / 6.0).max(1.0);
+        Self {
+            rpm,
+            capacity,
+            tokens: capacity,
+            last_refill: None,
+        }
+    }
+
+    pub fn rpm(&self) -> u32 {
+        self.rpm
+    }
+
+    /// Take one token, returning how long to wait before making the request.
+    /// A zero rpm disables the limit.
+    pub fn reserve(&mut self, now: Instant) -> Duration {
+        if self.rpm == 0 {
+            return Duration::ZERO;
+        }
+        let per_sec = self.rpm as f64 / 60.0;
+        if let Some(last) = self.last_refill {
+            let elapsed = now.saturating_duration_since(last).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * per_sec).min(self.capacity);
+        }
+        self.last_refill = Some(now);
+
+        self.tokens -= 1.0;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / per_sec)
+        }
+    }
+}
+
+static EMBEDDING_BUCKET: Mutex<Option<TokenBucket>> = Mutex::new(None);
+
+/// Wait for an embedding request slot. The bucket is recreated when `rpm` changes.
+pub async fn throttle_embeddings(rpm: u32) {
+    let delay = {
+        let Ok(mut guard) = EMBEDDING_BUCKET.lock() else {
+            return;
+        };
+        if guard.as_ref().map(TokenBucket::rpm) != Some(rpm) {
+            *guard = Some(TokenBucket::new(rpm));
+        }
+        guard.as_mut().map(|bucket| bucket.reserve(Instant::now())).unwrap_or_default()
+    };
+    if !delay.is_zero() {
+        log::debug!("[RateLimit] Pacing embedding request by {:?}", delay);
+        tokio::time::sleep(delay).await;
+    }
+}
+
+// ============================================================================
+// Web Search Quota
+// ============================================================================
+
+/// Searches used in the current calendar month (UTC)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct WebSearchQuota {
+    pub month: String, // "2025-01"
+    pub used: u32,
+}
+
+impl WebSearchQuota {
+    /// Count one search against `limit`, starting over when the month changes.
+    /// Returns false (without counting) once the limit is reached.
+    pub fn try_consume(&mut self, month: &str, limit: u32) -> bool {
+        if self.month != month {
+            self.month = month.to_string();
+            self.used = 0;
+        }
+        if self.used >= limit {
+            return false;
+        }
+        self.used += 1;
+        true
+    }
+}
+
+pub fn quota_month(now: DateTime<Utc>) -> String {
+    now.format("%Y-%m").to_string()
+}
+
+static QUOTA_LOCK: Mutex<()> = Mutex::new(());
+
+/// Consume one search from the quota stored in `data_dir`
+pub fn consume_web_search_quota_in_dir(data_dir: &Path, now: DateTime<Utc>, limit: u32) -> bool {
+    let _guard = QUOTA_LOCK.lock();
+    let path = data_dir.join(QUOTA_FILENAME);
+    let mut quota: WebSearchQuota = fs::read_to_string(&path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default();
+
+    let allowed = quota.try_consume(&quota_month(now), limit);
+    if allowed {
+        match serde_json::to_string_pretty(&quota) {
+            Ok(content) => {
+                if let Err(e) = fs::write(&path, content) {
+                    log::warn!("[RateLimit] Failed to write web search quota: {}", e);
+                }
+            }
+            Err(e) => log::warn!("[RateLimit] Failed to serialize web search quota: {}", e),
+        }
+    } else {
+        log::warn!("[RateLimit] Web search quota of {} for {} is used up", limit, quota.month);
+    }
+    allowed
+}
+
+/// Tool result when the quota is used up. It stays an "Error:" result so the tool
+/// cache never keeps it past the monthly reset.
+pub fn web_search_quota_message(limit: u32) -> String {
+    format!(
+        "Error: The monthly web search quota ({} searches) is used up; it resets on the 1st. Answer from what you know or use search_wikipedia / search_arxiv instead.",
+        limit
+    )
+}
+
+pub fn consume_web_search_quota<R: Runtime>(app_handle: &AppHandle<R>, limit: u32) -> bool {
+    match app_handle.path().app_data_dir() {
+        Ok(dir) => consume_web_search_quota_in_dir(&dir, Utc::now(), limit),
+        Err(e) => {
+            log::warn!("[RateLimit] Failed to get app data dir, not counting search: {}", e);
+            true
+        }
+    }
+}
@@ -7,10 +7,13 @@
  * - Hybrid search combining both modalities
  */
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
 use tauri::{AppHandle, Manager, Runtime};
 
 
@@ -25,6 +28,9 @@ pub struct BM25Index {
     pub inverted_index: HashMap<String, Vec<(String, u32)>>,
     /// Document lengths (in tokens)
     pub doc_lengths: HashMap<String, u32>,
+    /// When each document was written (absent in indexes saved before this was tracked)
+    #[serde(default)]
+    pub doc_timestamps: HashMap<String, DateTime<Utc>>,
     /// Total token count across all documents (for avg calculation)
     pub total_tokens: u64,
     /// Total document count
@@ -32,11 +38,11 @@ pub struct BM25Index {
 }
 
 /// Source of a retrieval hit (for debugging and fusion weighting)
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum HitSource {
     Bm25,
     DenseInteraction,
-    DenseTopicChunk, // future-proofing for chunked topic retrieval
+    DenseTopicChunk, // doc_id is "topic#chunk_idx"
 }
 
 /// A scored retrieval hit with metadata for fusion
@@ -69,6 +75,16 @@ const RRF_K_DEFAULT: f32 = 60.0;
 const MIN_DENSE_HITS: usize = 3;
 /// Default temporal decay half-life in days
 const TEMPORAL_TAU_DAYS: f32 = 15.0;
+/// Default RRF weight for the BM25 (lexical) list - raise for keyword-heavy corpora
+pub const RRF_WEIGHT_BM25: f32 = 1.0;
+/// Default RRF weight for dense (semantic) lists - raise for conceptual queries
+pub const RRF_WEIGHT_DENSE: f32 = 1.0;
+/// Default RRF weight for the topic-chunk list (curated summaries)
+pub const RRF_WEIGHT_TOPIC_CHUNK: f32 = 1.0;
+/// Normalized BM25 score below which lexical hits are dropped from hybrid search
+pub const BM25_MIN_NORMALIZED_SCORE: f32 = 0.1;
+/// Documents kept by the background BM25 prune (oldest are dropped first)
+pub const BM25_MAX_DOCS: usize = 10000;
 
 // ============================================================================
 // Tokenization
@@ -107,8 +123,8 @@ impl BM25Index {
         self.total_tokens as f32 / self.doc_count as f32
     }
 
-    /// Add a document to the index
-    pub fn add_document(&mut self, doc_id: &str, content: &str) {
+    /// Add a document to the index, recording `ts` for pruning and temporal boosting
+    pub fn add_document(&mut self, doc_id: &str, content: &str, ts: Option<DateTime<Utc>>) {
         let tokens = tokenize(content);
         let doc_length = tokens.len() as u32;
 
@@ -133,6 +149,9 @@ impl BM25Index {
 
         // Update document stats
         self.doc_lengths.insert(doc_id.to_string(), doc_length);
+        if let Some(ts) = ts {
+            self.doc_timestamps.insert(doc_id.to_string(), ts);
+        }
         self.total_tokens += doc_length as u64;
         self.doc_count += 1;
     }
@@ -140,6 +159,7 @@ impl BM25Index {
     /// Remove a document from the index
     pub fn remove_document(&mut self, doc_id: &str) {
         if let Some(doc_length) = self.doc_lengths.remove(doc_id) {
+            self.doc_timestamps.remove(doc_id);
             self.total_tokens = self.total_tokens.saturating_sub(doc_length as u64);
             self.doc_count = self.doc_count.saturating_sub(1);
 
@@ -153,6 +173,49 @@ impl BM25Index {
         }
     }
 
+    /// Stored timestamp of a document. Indexes written before timestamps were stored
+    /// fall back to parsing the doc_id, which was the entry's RFC3339 timestamp.
+    pub fn doc_timestamp(&self, doc_id: &str) -> Option<DateTime<Utc>> {
+        self.doc_timestamps.get(doc_id).copied().or_else(|| {
+            DateTime::parse_from_rfc3339(doc_id)
+                .ok()
+                .map(|dt| dt.with_timezone(&Utc))
+        })
+    }
+
+    /// Remove documents older than `cutoff`, then the oldest until at most `max_docs` remain.
+    /// Documents without a known timestamp are kept by the age cutoff but trimmed first.
+    /// Returns how many were removed.
+    pub fn prune(&mut self, cutoff: DateTime<Utc>, max_docs: usize) -> usize {
+        let initial_count = self.doc_count as usize;
+
+        let expired: Vec<String> = self
+            .doc_lengths
+            .keys()
+            .filter(|doc_id| self.doc_timestamp(doc_id).is_some_and(|ts| ts < cutoff))
+            .cloned()
+            .collect();
+        for doc_id in &expired {
+            self.remove_document(doc_id);
+        }
+
+        if self.doc_count as usize > max_docs {
+            let mut by_age: Vec<(Option<DateTime<Utc>>, String)> = self
+                .doc_lengths
+                .keys()
+                .map(|doc_id| (self.doc_timestamp(doc_id), doc_id.clone()))
+                .collect();
+            by_age.sort();
+
+            let to_trim = self.doc_count as usize - max_docs;
+            for (_, doc_id) in by_age.into_iter().take(to_trim) {
+                self.remove_document(&doc_id);
+            }
+        }
+
+        initial_count - self.doc_count as usize
+    }
+
     /// Compute IDF for a term
     fn idf(&self, term: &str) -> f32 {
         let n = self.doc_count as f32;
@@ -211,6 +274,27 @@ impl BM25Index {
         results.truncate(limit);
         results
     }
+
+    /// Like `search`, but scores are divided by the top score so they fall in (0, 1],
+    /// and hits below `min_score` are dropped. Use `search` where only ranks matter (RRF).
+    pub fn search_with_scores_normalized(
+        &self,
+        query: &str,
+        limit: usize,
+        min_score: f32,
+    ) -> Vec<ScoredDocument> {
+        let mut results = self.search(query, limit);
+        let max = results.first().map(|d| d.score).unwrap_or(0.0);
+        if max <= 0.0 {
+            return Vec::new();
+        }
+
+        for doc in &mut results {
+            doc.score = (doc.score / max).clamp(0.0, 1.0);
+        }
+        results.retain(|d| d.score >= min_score);
+        results
+    }
 }
 
 // ============================================================================
@@ -251,20 +335,27 @@ pub fn compute_rrf(
     results
 }
 
-/// Compute RRF fusion over N ranked lists of ScoredHit
+/// Compute RRF fusion over N ranked lists of ScoredHit (equal weights)
 ///
 /// RRF(d) = Σ_L 1/(k + rank_L(d))
 /// Returns fused results sorted by RRF score
 pub fn fuse_rrf_multi(lists: &[&[ScoredHit]], k: f32, limit: usize) -> Vec<ScoredHit> {
-    use std::collections::HashMap;
+    let weighted: Vec<(&[ScoredHit], f32)> = lists.iter().map(|list| (*list, 1.0)).collect();
+    fuse_rrf_weighted(&weighted, k, limit)
+}
 
+/// Compute weighted RRF fusion over N ranked lists of ScoredHit
+///
+/// RRF(d) = Σ_L w_L / (k + rank_L(d))
+/// Each list carries a multiplier applied to its RRF contribution.
+pub fn fuse_rrf_weighted(lists: &[(&[ScoredHit], f32)], k: f32, limit: usize) -> Vec<ScoredHit> {
     let mut rrf_scores: HashMap<String, f32> = HashMap::new();
     let mut hit_metadata: HashMap<String, (HitSource, Option<chrono::DateTime<chrono::Utc>>)> =
         HashMap::new();
 
-    for list in lists {
+    for (list, weight) in lists {
         for (rank, hit) in list.iter().enumerate() {
-            let rrf_contribution = 1.0 / (k + (rank + 1) as f32);
+            let rrf_contribution = weight / (k + (rank + 1) as f32);
             *rrf_scores.entry(hit.doc_id.clone()).or_insert(0.0) += rrf_contribution;
 
             // Keep first source we encounter (arbitrary but consistent)
@@ -301,40 +392,245 @@ pub fn fuse_rrf_multi(lists: &[&[ScoredHit]], k: f32, limit: usize) -> Vec<Score
 /// Hits without timestamps are left unchanged.
 pub fn apply_temporal_boost(hits: &mut [ScoredHit], tau_days: f32) {
     let now = chrono::Utc::now();
-    let tau_secs = tau_days * 24.0 * 3600.0;
 
     for hit in hits.iter_mut() {
-        if let Some(ts) = hit.ts {
-            let age_secs = (now - ts).num_seconds().max(0) as f32;
-            let decay = (-age_secs / tau_secs).exp();
-            hit.score *= decay;
-        }
+        hit.score *= temporal_decay(hit.ts, now, tau_days);
     }
 
     // Re-sort after boosting
     hits.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
 }
 
-/// Get the default minimum dense hits threshold (for external use)
-pub fn min_dense_hits() -> usize {
-    MIN_DENSE_HITS
+/// Recency multiplier `exp(-age / τ)` applied by the temporal boost (1.0 without a timestamp)
+pub fn temporal_decay(
+    ts: Option<chrono::DateTime<chrono::Utc>>,
+    now: chrono::DateTime<chrono::Utc>,
+    tau_days: f32,
+) -> f32 {
+    let Some(ts) = ts else {
+        return 1.0;
+    };
+    let tau_secs = tau_days * 24.0 * 3600.0;
+    let age_secs = (now - ts).num_seconds().max(0) as f32;
+    (-age_secs / tau_secs).exp()
 }
 
-/// Get the default temporal decay half-life in days (for external use)
-pub fn temporal_tau_days() -> f32 {
-    TEMPORAL_TAU_DAYS
+// ============================================================================
+// Query Expansion
+// ============================================================================
+
+/// Built-in synonym/acronym groups; a query term from a group also searches the others
+pub const DEFAULT_QUERY_SYNONYMS: &[&[&str]] = &[
+    &["js", "javascript"],
+    &["ts", "typescript"],
+    &["py", "python"],
+    &["config", "configuration", "settings"],
+    &["db", "database"],
+    &["k8s", "kubernetes"],
+    &["auth", "authentication"],
+    &["repo", "repository"],
+    &["env", "environment"],
+    &["docs", "documentation"],
+    &["ml", "machine learning"],
+    &["ai", "artificial intelligence"],
+];
+
+/// Adds synonyms to a query before BM25 search, so lexical matching isn't limited to
+/// the user's exact wording. Multi-word entries ("machine learning") are only expansions;
+/// single-word entries are also lookup keys.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryExpander {
+    synonyms: HashMap<String, Vec<String>>,
 }
 
-/// Get the default RRF k constant (for external use)
-pub fn rrf_k_default() -> f32 {
-    RRF_K_DEFAULT
+impl QueryExpander {
+    pub fn new<G, T>(groups: G) -> Self
+    where
+        G: IntoIterator,
+        G::Item: IntoIterator<Item = T>,
+        T: AsRef<str>,
+    {
+        let mut synonyms: HashMap<String, Vec<String>> = HashMap::new();
+        for group in groups {
+            let terms: Vec<Vec<String>> = group.into_iter().map(|term| tokenize(term.as_ref())).collect();
+            for (i, key) in terms.iter().enumerate() {
+                let [key] = key.as_slice() else {
+                    continue;
+                };
+                let expansions = synonyms.entry(key.clone()).or_default();
+                for token in terms.iter().enumerate().filter(|(j, _)| *j != i).flat_map(|(_, t)| t) {
+                    if token != key && !expansions.contains(token) {
+                        expansions.push(token.clone());
+                    }
+                }
+            }
+        }
+        Self { synonyms }
+    }
+
+    /// `query` followed by the synonyms of its terms that it doesn't already contain
+    pub fn expand(&self, query: &str) -> String {
+        let tokens = tokenize(query);
+        let mut added: Vec<&str> = Vec::new();
+        for token in &tokens {
+            for synonym in self.synonyms.get(token).into_iter().flatten() {
+                if !tokens.contains(synonym) && !added.contains(&synonym.as_str()) {
+                    added.push(synonym);
+                }
+            }
+        }
+        if added.is_empty() {
+            query.to_string()
+        } else {
+            format!("{} {}", query, added.join(" "))
+        }
+    }
+}
+
+/// Hybrid search knobs, read from `AppConfig` with the constants above as defaults
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetrievalTuning {
+    /// RRF dampening constant; lower values reward top ranks more
+    pub rrf_k: f32,
+    /// Dense hits needed before dense results join the fusion
+    pub min_dense_hits: usize,
+    /// Temporal decay constant in days; lower values favor recent hits more
+    pub temporal_tau_days: f32,
+    /// Synonym expansion for the BM25 query; None searches the query as written
+    pub query_expansion: Option<QueryExpander>,
+}
+
+impl Default for RetrievalTuning {
+    fn default() -> Self {
+        Self {
+            rrf_k: RRF_K_DEFAULT,
+            min_dense_hits: MIN_DENSE_HITS,
+            temporal_tau_days: TEMPORAL_TAU_DAYS,
+            query_expansion: None,
+        }
+    }
+}
+
+impl RetrievalTuning {
+    /// Non-positive config values fall back to the defaults; an empty synonym list
+    /// falls back to `DEFAULT_QUERY_SYNONYMS`
+    pub fn from_config(config: &crate::config::AppConfig) -> Self {
+        let defaults = Self::default();
+        Self {
+            rrf_k: config.rrf_k.filter(|k| *k > 0.0).unwrap_or(defaults.rrf_k),
+            min_dense_hits: config
+                .min_dense_hits
+                .map(|n| n as usize)
+                .unwrap_or(defaults.min_dense_hits),
+            temporal_tau_days: config
+                .temporal_tau_days
+                .filter(|tau| *tau > 0.0)
+                .unwrap_or(defaults.temporal_tau_days),
+            query_expansion: config.query_expansion.unwrap_or(false).then(|| {
+                match config.query_synonyms.as_ref().filter(|groups| !groups.is_empty()) {
+                    Some(groups) => QueryExpander::new(groups),
+                    None => QueryExpander::new(DEFAULT_QUERY_SYNONYMS.iter().copied()),
+                }
+            }),
+        }
+    }
+
+    /// The query the BM25 arm searches: expanded when query expansion is on
+    pub fn bm25_query(&self, query: &str) -> String {
+        match &self.query_expansion {
+            Some(expander) => expander.expand(query),
+            None => query.to_string(),
+        }
+    }
+}
+
+// ============================================================================
+// Snippets
+// ============================================================================
+
+/// Characters of context kept on each side of the matched term
+pub const SNIPPET_CONTEXT_CHARS: usize = 80;
+
+/// Excerpt of a hit's text around the first matched query term
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Snippet {
+    pub text: String,
+    /// Char range of the matched term within `text`, for highlighting
+    pub highlight: Option<(usize, usize)>,
+}
+
+/// Char span of the first word in `chars` that is one of the query's BM25 tokens
+fn first_match_span(chars: &[char], query: &str) -> Option<(usize, usize)> {
+    let tokens = tokenize(query);
+    let mut start = 0;
+    while start < chars.len() {
+        if !chars[start].is_alphanumeric() {
+            start += 1;
+            continue;
+        }
+        let end = chars[start..]
+            .iter()
+            .position(|c| !c.is_alphanumeric())
+            .map_or(chars.len(), |len| start + len);
+        let word: String = chars[start..end].iter().collect();
+        if tokens.contains(&word.to_lowercase()) {
+            return Some((start, end));
+        }
+        start = end;
+    }
+    None
+}
+
+/// `context_chars` on either side of the first query term, with "…" where text was cut.
+/// Without a match, the start of the text is returned unhighlighted.
+pub fn make_snippet(text: &str, query: &str, context_chars: usize) -> Snippet {
+    let chars: Vec<char> = text.chars().collect();
+    let span = first_match_span(&chars, query);
+    let (from, to) = match span {
+        Some((start, end)) => (start.saturating_sub(context_chars), (end + context_chars).min(chars.len())),
+        None => (0, (context_chars * 2).min(chars.len())),
+    };
+
+    let prefix = if from > 0 { "…" } else { "" };
+    let suffix = if to < chars.len() { "…" } else { "" };
+    let body: String = chars[from..to].iter().collect();
+    let offset = prefix.chars().count();
+
+    Snippet {
+        text: format!("{}{}{}", prefix, body, suffix),
+        highlight: span.map(|(start, end)| (start - from + offset, end - from + offset)),
+    }
 }
 
 // ============================================================================
 // Index Persistence
 // ============================================================================
 
-const BM25_INDEX_FILENAME: &str = "bm25_index.json";
+pub(crate) const BM25_INDEX_FILENAME: &str = "bm25_index.json";
+
+/// Serializes BM25 load-modify-save cycles between the agent and background jobs
+static BM25_LOCK: Mutex<()> = Mutex::new(());
+
+/// Hold this across a BM25 load + save so concurrent writers don't drop each other's updates
+pub(crate) fn lock_bm25_index() -> MutexGuard<'static, ()> {
+    // A panic mid-update leaves nothing half-written on disk, so a poisoned lock is still usable
+    BM25_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Write `contents` to a `.tmp` sibling, fsync, then rename over `path`.
+/// A crash mid-write leaves the previous file intact (plus a stray `.tmp`).
+pub(crate) fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+
+    let mut file = fs::File::create(&tmp_path)?;
+    file.write_all(contents.as_ref())?;
+    file.sync_all()?;
+    drop(file);
+
+    fs::rename(&tmp_path, path)
+}
 
 fn get_bm25_index_path<R: Runtime>(app_handle: &AppHandle<R>) -> Result<PathBuf, String> {
     let app_data_dir = app_handle
@@ -354,12 +650,16 @@ fn get_bm25_index_path<R: Runtime>(app_handle: &AppHandle<R>) -> Result<PathBuf,
 /// Load BM25 index from disk with graceful fallback
 pub fn load_bm25_index<R: Runtime>(app_handle: &AppHandle<R>) -> Result<BM25Index, String> {
     let path = get_bm25_index_path(app_handle)?;
+    load_bm25_index_from(&path)
+}
 
+/// Load BM25 index from an explicit path with graceful fallback
+pub fn load_bm25_index_from(path: &Path) -> Result<BM25Index, String> {
     if !path.exists() {
         return Ok(BM25Index::new());
     }
 
-    match fs::read_to_string(&path) {
+    match fs::read_to_string(path) {
         Ok(content) => match serde_json::from_str(&content) {
             Ok(index) => Ok(index),
             Err(e) => {
@@ -380,10 +680,15 @@ pub fn save_bm25_index<R: Runtime>(
     index: &BM25Index,
 ) -> Result<(), String> {
     let path = get_bm25_index_path(app_handle)?;
+    save_bm25_index_to(&path, index)
+}
+
+/// Save BM25 index to an explicit path (atomic: tmp file + rename)
+pub fn save_bm25_index_to(path: &Path, index: &BM25Index) -> Result<(), String> {
     let content = serde_json::to_string(index)
         .map_err(|e| format!("Failed to serialize BM25 index: {}", e))?;
 
-    fs::write(&path, content).map_err(|e| format!("Failed to write BM25 index: {}", e))
+    write_atomic(path, content).map_err(|e| format!("Failed to write BM25 index: {}", e))
 }
 
 /// Rebuild BM25 index from all JSONL interaction files
@@ -417,56 +722,67 @@ pub fn rebuild_bm25_index<R: Runtime>(app_handle: &AppHandle<R>) -> Result<usize
                 if let Ok(entry) = serde_json::from_str::<crate::interactions::InteractionEntry>(line) {
                     // Use timestamp as doc_id for uniqueness
                     let doc_id = entry.ts.to_rfc3339();
-                    index.add_document(&doc_id, &entry.content);
+                    index.add_document(&doc_id, &entry.content, Some(entry.ts));
                     count += 1;
                 }
             }
         }
     }
 
+    let _guard = lock_bm25_index();
     save_bm25_index(app_handle, &index)?;
     log::info!("[BM25] Rebuilt index with {} documents", count);
 
     Ok(count)
 }
 
-/// Prune old entries from BM25 index (called by background cleanup)
-pub fn prune_bm25_index<R: Runtime>(
+/// Remove specific documents from the BM25 index (called after cleanup deletes entries)
+pub fn remove_bm25_documents<R: Runtime>(
     app_handle: &AppHandle<R>,
-    max_age_days: i64,
-    max_docs: usize,
+    doc_ids: &[String],
 ) -> Result<usize, String> {
-    let mut index = load_bm25_index(app_handle)?;
-    let initial_count = index.doc_count as usize;
+    if doc_ids.is_empty() {
+        return Ok(0);
+    }
 
-    // Parse doc_ids as timestamps and remove old ones
-    let cutoff = chrono::Utc::now() - chrono::Duration::days(max_age_days);
-    let mut to_remove: Vec<String> = Vec::new();
+    let path = get_bm25_index_path(app_handle)?;
+    remove_bm25_documents_at(&path, doc_ids)
+}
 
-    for doc_id in index.doc_lengths.keys() {
-        if let Ok(ts) = chrono::DateTime::parse_from_rfc3339(doc_id) {
-            if ts < cutoff {
-                to_remove.push(doc_id.clone());
-            }
-        }
+/// Remove documents from the BM25 index stored at an explicit path
+pub fn remove_bm25_documents_at(path: &Path, doc_ids: &[String]) -> Result<usize, String> {
+    if doc_ids.is_empty() {
+        return Ok(0);
     }
 
-    for doc_id in &to_remove {
+    let _guard = lock_bm25_index();
+    let mut index = load_bm25_index_from(path)?;
+    let initial_count = index.doc_count as usize;
+
+    for doc_id in doc_ids {
         index.remove_document(doc_id);
     }
 
-    // If still over max_docs, remove oldest
-    if index.doc_count as usize > max_docs {
-        let mut doc_ids: Vec<_> = index.doc_lengths.keys().cloned().collect();
-        doc_ids.sort(); // RFC3339 timestamps sort chronologically
-
-        let to_trim = index.doc_count as usize - max_docs;
-        for doc_id in doc_ids.into_iter().take(to_trim) {
-            index.remove_document(&doc_id);
-        }
+    let removed = initial_count - index.doc_count as usize;
+    if removed > 0 {
+        save_bm25_index_to(path, &index)?;
+        log::info!("[BM25] Removed {} entries from index", removed);
     }
 
-    let removed = initial_count - index.doc_count as usize;
+    Ok(removed)
+}
+
+/// Prune old entries from BM25 index (called by background cleanup)
+pub fn prune_bm25_index<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    max_age_days: i64,
+    max_docs: usize,
+) -> Result<usize, String> {
+    let _guard = lock_bm25_index();
+    let mut index = load_bm25_index(app_handle)?;
+
+    let cutoff = chrono::Utc::now() - chrono::Duration::days(max_age_days);
+    let removed = index.prune(cutoff, max_docs);
     if removed > 0 {
         save_bm25_index(app_handle, &index)?;
         log::info!("[BM25] Pruned {} old entries from index", removed);
@@ -505,7 +821,7 @@ mod tests {
     #[test]
     fn test_bm25_add_document() {
         let mut index = BM25Index::new();
-        index.add_document("doc1", "the quick brown fox");
+        index.add_document("doc1", "the quick brown fox", None);
 
         assert_eq!(index.doc_count, 1);
         assert!(index.inverted_index.contains_key("quick"));
@@ -517,8 +833,8 @@ mod tests {
     #[test]
     fn test_bm25_remove_document() {
         let mut index = BM25Index::new();
-        index.add_document("doc1", "hello world");
-        index.add_document("doc2", "goodbye world");
+        index.add_document("doc1", "hello world", None);
+        index.add_document("doc2", "goodbye world", None);
 
         assert_eq!(index.doc_count, 2);
 
@@ -527,12 +843,61 @@ mod tests {
         assert!(!index.doc_lengths.contains_key("doc1"));
     }
 
+    #[test]
+    fn test_bm25_stores_doc_timestamps() {
+        use chrono::TimeZone;
+        let ts = Utc.with_ymd_and_hms(2025, 3, 1, 12, 0, 0).unwrap();
+
+        let mut index = BM25Index::new();
+        index.add_document("note-a", "hello world", Some(ts));
+        index.add_document("2025-01-01T10:00:00+00:00", "legacy entry", None);
+        index.add_document("note-b", "no timestamp", None);
+
+        assert_eq!(index.doc_timestamp("note-a"), Some(ts));
+        // Legacy doc_ids still parse as timestamps; anything else has none
+        assert_eq!(
+            index.doc_timestamp("2025-01-01T10:00:00+00:00"),
+            Some(Utc.with_ymd_and_hms(2025, 1, 1, 10, 0, 0).unwrap())
+        );
+        assert_eq!(index.doc_timestamp("note-b"), None);
+
+        // Survives a save/load round trip and is dropped with the document
+        let restored: BM25Index = serde_json::from_str(&serde_json::to_string(&index).unwrap()).unwrap();
+        assert_eq!(restored.doc_timestamp("note-a"), Some(ts));
+        index.remove_document("note-a");
+        assert!(index.doc_timestamps.is_empty());
+    }
+
+    #[test]
+    fn test_bm25_prune_uses_stored_timestamps() {
+        use chrono::TimeZone;
+        let day = |d: u32| Utc.with_ymd_and_hms(2025, 3, d, 0, 0, 0).unwrap();
+
+        let mut index = BM25Index::new();
+        index.add_document("old", "first note", Some(day(1)));
+        index.add_document("middle", "second note", Some(day(10)));
+        index.add_document("recent", "third note", Some(day(20)));
+        index.add_document("newest", "fourth note", Some(day(25)));
+        index.add_document("unknown", "undated note", None);
+
+        // Age cutoff removes only dated documents older than it
+        assert_eq!(index.prune(day(5), 10), 1);
+        assert!(!index.doc_lengths.contains_key("old"));
+        assert!(index.doc_lengths.contains_key("unknown"));
+
+        // Over the cap: undated first, then oldest by stored timestamp
+        assert_eq!(index.prune(day(5), 2), 2);
+        let mut remaining: Vec<_> = index.doc_lengths.keys().cloned().collect();
+        remaining.sort();
+        assert_eq!(remaining, vec!["newest".to_string(), "recent".to_string()]);
+    }
+
     #[test]
     fn test_bm25_search_exact_match() {
         let mut index = BM25Index::new();
-        index.add_document("doc1", "rust programming language");
-        index.add_document("doc2", "python programming language");
-        index.add_document("doc3", "javascript framework");
+        index.add_document("doc1", "rust programming language", None);
+        index.add_document("doc2", "python programming language", None);
+        index.add_document("doc3", "javascript framework", None);
 
         let results = index.search("rust programming", 10);
         assert!(!results.is_empty());
@@ -542,9 +907,9 @@ mod tests {
     #[test]
     fn test_bm25_search_partial_match() {
         let mut index = BM25Index::new();
-        index.add_document("doc1", "machine learning with neural networks");
-        index.add_document("doc2", "deep learning algorithms");
-        index.add_document("doc3", "cooking recipes");
+        index.add_document("doc1", "machine learning with neural networks", None);
+        index.add_document("doc2", "deep learning algorithms", None);
+        index.add_document("doc3", "cooking recipes", None);
 
         let results = index.search("learning", 10);
         assert_eq!(results.len(), 2);
@@ -554,6 +919,92 @@ mod tests {
         assert!(doc_ids.contains(&"doc2".to_string()));
     }
 
+    #[test]
+    fn test_bm25_normalized_scores_in_unit_range() {
+        let mut index = BM25Index::new();
+        index.add_document("doc1", "rust rust rust ownership borrowing", None);
+        index.add_document("doc2", "rust programming language", None);
+        index.add_document("doc3", "cooking recipes with rust colored peppers and many other words", None);
+        index.add_document("doc4", "gardening tips", None);
+
+        let raw = index.search("rust ownership", 10);
+        let normalized = index.search_with_scores_normalized("rust ownership", 10, 0.0);
+
+        // Same ranking as the raw search, top hit is exactly 1.0
+        assert_eq!(
+            raw.iter().map(|d| &d.doc_id).collect::<Vec<_>>(),
+            normalized.iter().map(|d| &d.doc_id).collect::<Vec<_>>()
+        );
+        assert!((normalized[0].score - 1.0).abs() < 1e-6);
+        assert!(normalized.iter().all(|d| d.score > 0.0 && d.score <= 1.0));
+
+        // No matches -> empty
+        assert!(index.search_with_scores_normalized("quantum", 10, 0.0).is_empty());
+    }
+
+    #[test]
+    fn test_bm25_normalized_min_score_cutoff() {
+        let mut index = BM25Index::new();
+        index.add_document("doc1", "rust rust rust ownership borrowing", None);
+        index.add_document("doc2", "cooking recipes with rust colored peppers and many other words", None);
+        index.add_document("doc3", "gardening tips", None);
+
+        let all = index.search_with_scores_normalized("rust ownership", 10, 0.0);
+        assert_eq!(all.len(), 2);
+        let weak = all[1].score;
+        assert!(weak < 1.0);
+
+        // A cutoff above the weak match keeps only the strong one
+        let filtered = index.search_with_scores_normalized("rust ownership", 10, weak + 0.01);
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].doc_id, "doc1");
+    }
+
+    #[test]
+    fn test_query_expansion_matches_synonyms() {
+        let mut index = BM25Index::new();
+        index.add_document("doc1", "updated the configuration for the javascript bundler", None);
+        index.add_document("doc2", "gardening tips for spring", None);
+
+        // The raw query shares no terms with doc1
+        assert!(index.search("js config", 10).is_empty());
+
+        let expander = QueryExpander::new(DEFAULT_QUERY_SYNONYMS.iter().copied());
+        let expanded = expander.expand("js config");
+        assert!(expanded.starts_with("js config "));
+        let results = index.search(&expanded, 10);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].doc_id, "doc1");
+
+        // Groups work in both directions; terms already in the query aren't repeated
+        assert_eq!(expander.expand("javascript"), "javascript js");
+        assert_eq!(expander.expand("js javascript"), "js javascript");
+        // Multi-word entries expand but aren't lookup keys
+        assert_eq!(expander.expand("ml"), "ml machine learning");
+        assert_eq!(expander.expand("machine"), "machine");
+    }
+
+    #[test]
+    fn test_query_expansion_config() {
+        use crate::config::AppConfig;
+
+        // Off by default
+        let tuning = RetrievalTuning::from_config(&AppConfig::default());
+        assert_eq!(tuning.query_expansion, None);
+        assert_eq!(tuning.bm25_query("js config"), "js config");
+
+        let config = AppConfig {
+            query_expansion: Some(true),
+            query_synonyms: Some(vec![vec!["pg".to_string(), "postgres".to_string()]]),
+            ..AppConfig::default()
+        };
+        let tuning = RetrievalTuning::from_config(&config);
+        assert_eq!(tuning.bm25_query("pg js"), "pg js postgres");
+
+        let defaults = AppConfig { query_synonyms: Some(Vec::new()), ..config };
+        assert_eq!(RetrievalTuning::from_config(&defaults).bm25_query("js"), "js javascript");
+    }
+
     #[test]
     fn test_rrf_fusion() {
         let bm25_results = vec![
@@ -646,6 +1097,46 @@ mod tests {
         assert!(fused[0].score > 0.04);
     }
 
+    #[test]
+    fn test_fuse_rrf_weighted_boosting_bm25_reorders() {
+        let now = chrono::Utc::now();
+        let hit = |id: &str, source| ScoredHit { doc_id: id.to_string(), score: 1.0, source, ts: Some(now) };
+        // Mirrored rankings: equal weights tie A and B
+        let bm25_hits = vec![hit("A", HitSource::Bm25), hit("B", HitSource::Bm25)];
+        let dense_hits = vec![hit("B", HitSource::DenseInteraction), hit("A", HitSource::DenseInteraction)];
+
+        let equal = fuse_rrf_weighted(&[(&bm25_hits, 1.0), (&dense_hits, 1.0)], 60.0, 10);
+        assert!((equal[0].score - equal[1].score).abs() < 1e-6);
+
+        // Boosting BM25 puts its top hit first
+        let bm25_boosted = fuse_rrf_weighted(&[(&bm25_hits, 2.0), (&dense_hits, 1.0)], 60.0, 10);
+        assert_eq!(bm25_boosted[0].doc_id, "A");
+        assert!(bm25_boosted[0].score > bm25_boosted[1].score);
+
+        // Boosting dense flips the order
+        let dense_boosted = fuse_rrf_weighted(&[(&bm25_hits, 1.0), (&dense_hits, 2.0)], 60.0, 10);
+        assert_eq!(dense_boosted[0].doc_id, "B");
+    }
+
+    #[test]
+    fn test_fuse_rrf_multi_matches_unit_weights() {
+        let now = chrono::Utc::now();
+        let list1 = vec![
+            ScoredHit { doc_id: "A".to_string(), score: 1.0, source: HitSource::Bm25, ts: Some(now) },
+            ScoredHit { doc_id: "B".to_string(), score: 0.5, source: HitSource::Bm25, ts: Some(now) },
+        ];
+        let list2 = vec![ScoredHit { doc_id: "B".to_string(), score: 0.9, source: HitSource::DenseInteraction, ts: Some(now) }];
+
+        let multi = fuse_rrf_multi(&[&list1, &list2], 60.0, 10);
+        let weighted = fuse_rrf_weighted(&[(&list1, 1.0), (&list2, 1.0)], 60.0, 10);
+
+        assert_eq!(multi.len(), weighted.len());
+        for (m, w) in multi.iter().zip(weighted.iter()) {
+            assert_eq!(m.doc_id, w.doc_id);
+            assert!((m.score - w.score).abs() < 1e-6);
+        }
+    }
+
     #[test]
     fn test_temporal_boost_recent_first() {
         let now = chrono::Utc::now();
@@ -678,4 +1169,70 @@ mod tests {
         let no_ts = hits.iter().find(|h| h.doc_id == "no_ts").unwrap();
         assert!((no_ts.score - 1.0).abs() < 0.01); // Unchanged
     }
+
+    #[test]
+    fn test_interrupted_save_keeps_prior_index() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join(BM25_INDEX_FILENAME);
+
+        let mut index = BM25Index::new();
+        index.add_document("doc1", "the quick brown fox", None);
+        save_bm25_index_to(&path, &index).unwrap();
+
+        // Simulate a crash mid-write: truncated tmp sibling left behind
+        let tmp_path = dir.path().join(format!("{}.tmp", BM25_INDEX_FILENAME));
+        fs::write(&tmp_path, "{\"inverted_index\": {\"qu").unwrap();
+
+        let loaded = load_bm25_index_from(&path).unwrap();
+        assert_eq!(loaded.doc_count, 1);
+        assert!(loaded.inverted_index.contains_key("fox"));
+
+        // The next save replaces the stray tmp file and lands atomically
+        index.add_document("doc2", "lazy dog", None);
+        save_bm25_index_to(&path, &index).unwrap();
+        assert!(!tmp_path.exists());
+        assert_eq!(load_bm25_index_from(&path).unwrap().doc_count, 2);
+    }
+
+    #[test]
+    fn test_snippet_window_in_middle() {
+        let text = "aaaa bbbb needle cccc dddd";
+        let snippet = make_snippet(text, "Needle", 5);
+        assert_eq!(snippet.text, "…bbbb needle cccc…");
+        let (start, end) = snippet.highlight.unwrap();
+        let highlighted: String = snippet.text.chars().skip(start).take(end - start).collect();
+        assert_eq!(highlighted, "needle");
+    }
+
+    #[test]
+    fn test_snippet_term_at_start_and_end() {
+        let snippet = make_snippet("needle then a long tail of text", "needle", 5);
+        assert_eq!(snippet.text, "needle then…");
+        assert_eq!(snippet.highlight, Some((0, 6)));
+
+        let snippet = make_snippet("a long head of text then needle", "needle", 5);
+        assert_eq!(snippet.text, "…then needle");
+        assert_eq!(snippet.highlight, Some((6, 12)));
+
+        // Whole text fits: no ellipses
+        let snippet = make_snippet("the needle", "needle", 50);
+        assert_eq!(snippet.text, "the needle");
+        assert_eq!(snippet.highlight, Some((4, 10)));
+    }
+
+    #[test]
+    fn test_snippet_matches_whole_words_and_falls_back() {
+        // "needles" is not the token "needle"; first whole-word match wins
+        let snippet = make_snippet("needles and one needle", "needle", 3);
+        assert_eq!(snippet.text, "…ne needle");
+        assert_eq!(snippet.highlight, Some((4, 10)));
+
+        // Multibyte text is windowed by chars, not bytes
+        let snippet = make_snippet("café ☕ needle ☕ naïve", "needle", 2);
+        assert_eq!(snippet.text, "…☕ needle ☕…");
+
+        let snippet = make_snippet("nothing relevant here", "needle", 4);
+        assert_eq!(snippet.text, "nothing …");
+        assert!(snippet.highlight.is_none());
+    }
 }
@@ -11,6 +11,7 @@ mod tests {
             tool_call_id: None,
             images: None,
             reasoning: None,
+            citations: None,
         };
         let json = serde_json::to_string(&msg).unwrap();
         assert!(json.contains("Hello"));
@@ -30,6 +31,7 @@ mod tests {
                 mime_type: "image/png".to_string(),
                 file_uri: Some("https://example.com/image.png".to_string()),
             }]),
+            citations: None,
         };
         let json = serde_json::to_string(&msg).unwrap();
         assert!(json.contains("base64data"));
@@ -75,6 +77,7 @@ mod tests {
                 tool_calls: None,
                 tool_call_id: None,
                 images: None,
+                citations: None,
             },
             ChatMessage {
                 role: "assistant".to_string(),
@@ -83,6 +86,7 @@ mod tests {
                 tool_calls: None,
                 tool_call_id: None,
                 images: None,
+                citations: None,
             },
         ];
 
@@ -165,9 +169,1352 @@ mod tests {
         assert_eq!(events.len(), 0);
     }
 
+    fn msg(role: &str, content: &str) -> ChatMessage {
+        ChatMessage {
+            role: role.to_string(),
+            content: Some(content.to_string()),
+            reasoning: None,
+            tool_calls: None,
+            tool_call_id: None,
+            images: None,
+            citations: None,
+        }
+    }
+
+    /// user, assistant(tool call), tool, assistant, user, assistant
+    fn history_with_tool_call() -> Vec<ChatMessage> {
+        use crate::agent::{FunctionCall, ToolCall};
+
+        let mut call = msg("assistant", "");
+        call.tool_calls = Some(vec![ToolCall {
+            id: "call_1".to_string(),
+            tool_type: "function".to_string(),
+            function: FunctionCall {
+                name: "get_weather".to_string(),
+                arguments: "{\"location\":\"Paris\"}".to_string(),
+            },
+            thought_signature: None,
+        }]);
+        let mut result = msg("tool", "Weather in Paris: 20 C");
+        result.tool_call_id = Some("call_1".to_string());
+
+        vec![
+            msg("user", "Weather in Paris?"),
+            call,
+            result,
+            msg("assistant", "It's 20 C in Paris."),
+            msg("user", "And tomorrow?"),
+            msg("assistant", "Probably similar."),
+        ]
+    }
+
+    #[test]
+    fn test_serialize_history_strips_reasoning_when_disabled() {
+        use crate::agent::serialize_history;
+
+        let mut history = history_with_tool_call();
+        history[3].reasoning = Some("The forecast API said 20 C, so answer briefly.".to_string());
+
+        let kept = serialize_history(&history, true).unwrap();
+        assert!(kept.contains("\"reasoning\""));
+        assert!(kept.contains("answer briefly"));
+
+        let stripped = serialize_history(&history, false).unwrap();
+        assert!(!stripped.contains("\"reasoning\""));
+        assert!(!stripped.contains("answer briefly"));
+        assert!(stripped.contains("It's 20 C in Paris."));
+
+        // Stripping only affects the serialized copy
+        assert!(history[3].reasoning.is_some());
+        let restored: Vec<ChatMessage> = serde_json::from_str(&stripped).unwrap();
+        assert_eq!(restored.len(), history.len());
+    }
+
+    #[test]
+    fn test_branch_history_truncation_boundaries() {
+        use crate::agent::branch_history;
+
+        // Branching at 0 clears everything, at len keeps everything
+        let mut history = history_with_tool_call();
+        branch_history(&mut history, 0).unwrap();
+        assert!(history.is_empty());
+
+        let mut history = history_with_tool_call();
+        branch_history(&mut history, 6).unwrap();
+        assert_eq!(history.len(), 6);
+
+        // Branching before the second user turn keeps the completed tool exchange
+        let mut history = history_with_tool_call();
+        branch_history(&mut history, 4).unwrap();
+        assert_eq!(history.len(), 4);
+        assert_eq!(history.last().unwrap().role, "assistant");
+
+        // Out of range
+        let mut history = history_with_tool_call();
+        assert!(branch_history(&mut history, 7).is_err());
+        assert_eq!(history.len(), 6, "Failed branch must not modify history");
+    }
+
+    #[test]
+    fn test_branch_history_rejects_split_tool_pair() {
+        use crate::agent::branch_history;
+
+        // Index 2 keeps the tool call but drops its result
+        let mut history = history_with_tool_call();
+        assert!(branch_history(&mut history, 2).is_err());
+
+        // Index 1 drops the whole tool exchange, which is fine
+        let mut history = history_with_tool_call();
+        assert!(branch_history(&mut history, 1).is_ok());
+        assert_eq!(history.len(), 1);
+    }
+
+    #[test]
+    fn test_branch_history_backup_restore_round_trip() {
+        use crate::agent::branch_history;
+
+        let original = history_with_tool_call();
+        let mut history = original.clone();
+        let backup = branch_history(&mut history, 4).unwrap();
+        assert_eq!(history.len(), 4);
+
+        // restore_history swaps the backup back in
+        history = backup;
+        assert_eq!(history.len(), original.len());
+        for (restored, orig) in history.iter().zip(original.iter()) {
+            assert_eq!(restored.role, orig.role);
+            assert_eq!(restored.content, orig.content);
+        }
+    }
+
     // Note: execute_tool is async and requires Agent instance with HTTP client.
     // We can't easily unit test it without mocking the HTTP client or making it public and accepting a client.
     // However, we can test the logic if we extract the match block into a pure function,
     // but it depends on perform_*_lookup which are async and use the client.
     // For now, we rely on integration tests or manual verification for tool execution.
+
+    #[test]
+    fn test_resolve_local_provider_default_url_no_auth() {
+        use crate::agent::{resolve_provider, DEFAULT_LOCAL_BASE_URL};
+        use crate::config::AppConfig;
+
+        let config = AppConfig::default();
+        let endpoint = resolve_provider("llama3.2 (Local)", &config).unwrap();
+
+        assert_eq!(endpoint.provider_name, "Local");
+        assert_eq!(endpoint.model, "llama3.2");
+        assert_eq!(endpoint.base_url, DEFAULT_LOCAL_BASE_URL);
+        assert_eq!(
+            endpoint.chat_completions_url(),
+            "http://localhost:11434/v1/chat/completions"
+        );
+        assert_eq!(endpoint.authorization_header(), None);
+    }
+
+    #[test]
+    fn test_resolve_local_provider_custom_url() {
+        use crate::agent::resolve_provider;
+        use crate::config::AppConfig;
+
+        // LM Studio base URL without trailing slash; OpenRouter key must not leak to it
+        let config = AppConfig {
+            local_base_url: Some("http://localhost:1234/v1".to_string()),
+            openrouter_api_key: Some("or-key".to_string()),
+            ..AppConfig::default()
+        };
+        let endpoint = resolve_provider("qwen2.5-coder (Local)", &config).unwrap();
+
+        assert_eq!(endpoint.chat_completions_url(), "http://localhost:1234/v1/chat/completions");
+        assert_eq!(endpoint.authorization_header(), None);
+    }
+
+    #[test]
+    fn test_resolve_remote_providers_keep_auth() {
+        use crate::agent::resolve_provider;
+        use crate::config::AppConfig;
+
+        let config = AppConfig {
+            openrouter_api_key: Some("or-key".to_string()),
+            ..AppConfig::default()
+        };
+        let endpoint = resolve_provider("openai/gpt-4o", &config).unwrap();
+        assert_eq!(endpoint.provider_name, "OpenRouter");
+        assert_eq!(endpoint.authorization_header(), Some("Bearer or-key".to_string()));
+
+        // Groq without a key is a missing-key error, not a silent local fallback
+        assert!(resolve_provider("gpt-oss-120b (Groq)", &config).is_err());
+    }
+
+    #[test]
+    fn test_paginate_history_offset_limit_bounds() {
+        use crate::agent::paginate_history;
+
+        let history = history_with_tool_call();
+
+        let page = paginate_history(&history, 0, 2, None);
+        assert_eq!(page.total, 6);
+        assert_eq!(page.indices, vec![0, 1]);
+
+        // Limit past the end is clamped
+        let page = paginate_history(&history, 4, 10, None);
+        assert_eq!(page.indices, vec![4, 5]);
+        assert_eq!(page.messages[1].content.as_deref(), Some("Probably similar."));
+
+        // Offset past the end gives an empty page but still reports the total
+        let page = paginate_history(&history, 6, 5, None);
+        assert!(page.messages.is_empty());
+        assert_eq!(page.total, 6);
+
+        assert!(paginate_history(&history, 0, 0, None).messages.is_empty());
+        assert_eq!(paginate_history(&[], 0, 10, None).total, 0);
+    }
+
+    #[test]
+    fn test_paginate_history_role_filter() {
+        use crate::agent::paginate_history;
+
+        let history = history_with_tool_call();
+        let roles = vec!["user".to_string(), "assistant".to_string()];
+
+        // Tool message (index 2) is excluded; indices still point into the full history
+        let page = paginate_history(&history, 0, 10, Some(&roles));
+        assert_eq!(page.total, 5);
+        assert_eq!(page.indices, vec![0, 1, 3, 4, 5]);
+        assert!(page.messages.iter().all(|m| m.role != "tool"));
+
+        // Offset applies to the filtered list
+        let users = vec!["user".to_string()];
+        let page = paginate_history(&history, 1, 10, Some(&users));
+        assert_eq!(page.total, 2);
+        assert_eq!(page.indices, vec![4]);
+    }
+
+    /// Feed chunks through the guard, returning every emit plus the final flush
+    fn stream_through_guard(chunks: &[&str]) -> (Vec<String>, String) {
+        use crate::agent::MarkdownStreamGuard;
+
+        let mut guard = MarkdownStreamGuard::new();
+        let emitted = chunks.iter().map(|c| guard.push(c)).collect();
+        (emitted, guard.finish())
+    }
+
+    #[test]
+    fn test_chunk_coalescer_batches_without_losing_text() {
+        use crate::agent::ChunkCoalescer;
+        use std::time::{Duration, Instant};
+
+        // (delta, ms since stream start)
+        let deltas: Vec<(String, u64)> = (0..40).map(|i| (format!("tok{} ", i), i * 5)).collect();
+        let start = Instant::now();
+        let mut coalescer = ChunkCoalescer::new(Duration::from_millis(50), 64);
+
+        let mut emitted: Vec<String> = deltas
+            .iter()
+            .filter_map(|(text, ms)| coalescer.push(text, start + Duration::from_millis(*ms)))
+            .collect();
+        emitted.extend(coalescer.flush());
+
+        let expected: String = deltas.iter().map(|(text, _)| text.as_str()).collect();
+        assert_eq!(emitted.concat(), expected);
+        // Far fewer events than deltas, none over the size cap by more than one delta
+        assert!(emitted.len() < deltas.len() / 3, "{} emits", emitted.len());
+        assert!(emitted.iter().all(|e| e.chars().count() < 64 + "tok39 ".len()));
+        // The first delta isn't held back
+        assert_eq!(emitted[0], "tok0 ");
+    }
+
+    #[test]
+    fn test_chunk_coalescer_zero_window_passes_through() {
+        use crate::agent::ChunkCoalescer;
+        use std::time::{Duration, Instant};
+
+        let now = Instant::now();
+        let mut coalescer = ChunkCoalescer::new(Duration::ZERO, 64);
+        assert_eq!(coalescer.push("a", now).as_deref(), Some("a"));
+        assert_eq!(coalescer.push("b", now).as_deref(), Some("b"));
+        assert_eq!(coalescer.push("", now), None);
+        assert_eq!(coalescer.flush(), None);
+    }
+
+    #[tokio::test]
+    async fn test_stalled_stream_flushes_held_text_on_deadline() {
+        use crate::agent::{next_or_flush, ChunkCoalescer};
+        use std::time::{Duration, Instant};
+
+        let mut coalescer = ChunkCoalescer::new(Duration::from_millis(20), 64);
+        assert_eq!(coalescer.push("first ", Instant::now()).as_deref(), Some("first "));
+        assert_eq!(coalescer.push("held", Instant::now()), None);
+
+        // The stream never produces another chunk
+        let mut stream = futures_util::stream::pending::<()>();
+        let mut emitted = Vec::new();
+        let waited = tokio::time::timeout(
+            Duration::from_millis(500),
+            next_or_flush(&mut stream, &mut coalescer, |text| emitted.push(text)),
+        )
+        .await;
+
+        assert!(waited.is_err());
+        assert_eq!(emitted, vec!["held".to_string()]);
+        assert_eq!(coalescer.flush(), None);
+    }
+
+    #[test]
+    fn test_markdown_guard_joins_split_display_math() {
+        let chunks = ["Energy: $", "$E = mc^2$", "$ done"];
+        let (emitted, rest) = stream_through_guard(&chunks);
+
+        // Lone `$` is held, then the whole block is held until its closing `$$`
+        assert_eq!(emitted[0], "Energy: ");
+        assert_eq!(emitted[1], "");
+        assert_eq!(emitted[2], "$$E = mc^2$$ done");
+        assert_eq!(rest, "");
+        assert!(emitted.iter().all(|e| e.matches("$$").count() % 2 == 0));
+    }
+
+    #[test]
+    fn test_markdown_guard_joins_split_code_fence() {
+        let chunks = ["Here:\n``", "`rust\nlet x = 1;\n``", "`\nAfter"];
+        let (emitted, rest) = stream_through_guard(&chunks);
+
+        assert_eq!(emitted[0], "Here:\n");
+        assert_eq!(emitted[1], "```rust\nlet x = 1;\n");
+        assert_eq!(emitted[2], "```\nAfter");
+        assert_eq!(rest, "");
+    }
+
+    #[test]
+    fn test_markdown_guard_ignores_dollars_inside_code() {
+        let chunks = ["```sh\necho $", "HOME\n```\n"];
+        let (emitted, rest) = stream_through_guard(&chunks);
+
+        // `$` inside a fence is shell, not LaTeX, so nothing is held back
+        assert_eq!(emitted[0], "```sh\necho $");
+        assert_eq!(emitted[1], "HOME\n```\n");
+        assert_eq!(rest, "");
+    }
+
+    #[test]
+    fn test_markdown_guard_finish_flushes_unclosed_block() {
+        let chunks = ["Start $$\\frac{a}", "{b}"];
+        let (emitted, rest) = stream_through_guard(&chunks);
+
+        assert_eq!(emitted.concat(), "Start ");
+        assert_eq!(rest, "$$\\frac{a}{b}");
+    }
+
+    #[test]
+    fn test_markdown_guard_preserves_all_text() {
+        let chunks = [
+            "Intro `inline` and $x$ ",
+            "then $",
+            "$\\sum_i",
+            " i$$ and\n``",
+            "`py\nprint('$$')\n`",
+            "``\nend $",
+        ];
+        let (emitted, rest) = stream_through_guard(&chunks);
+
+        let mut combined = emitted.concat();
+        combined.push_str(&rest);
+        assert_eq!(combined, chunks.concat());
+        assert_eq!(rest, "$");
+    }
+
+    #[test]
+    fn test_build_title_prompt() {
+        use crate::agent::{build_title_prompt, MAX_TITLE_WORDS};
+
+        let prompt = build_title_prompt(&history_with_tool_call()).unwrap();
+        assert!(prompt.contains(&format!("at most {} words", MAX_TITLE_WORDS)));
+        assert!(prompt.contains("User: Weather in Paris?"));
+        // The empty tool-call message is skipped in favour of the first real reply
+        assert!(prompt.ends_with("Assistant: It's 20 C in Paris."));
+        assert!(!prompt.contains("And tomorrow?"));
+
+        // User-only history still works
+        let prompt = build_title_prompt(&[msg("user", "Explain lifetimes")]).unwrap();
+        assert!(!prompt.contains("Assistant:"));
+    }
+
+    #[test]
+    fn test_build_title_prompt_empty_history() {
+        use crate::agent::build_title_prompt;
+
+        assert!(build_title_prompt(&[]).is_err());
+        assert!(build_title_prompt(&[msg("user", "   ")]).is_err());
+        assert!(build_title_prompt(&[msg("assistant", "Hello!")]).is_err());
+    }
+
+    #[test]
+    fn test_build_summary_prompt() {
+        use crate::agent::{build_summary_prompt, DEFAULT_SUMMARY_WORDS};
+
+        let mut history = vec![msg("system", "You are Shard.")];
+        history.extend(history_with_tool_call());
+        let prompt = build_summary_prompt(&history, 150).unwrap();
+        assert!(prompt.contains("at most 150 words"));
+        assert!(prompt.contains("User: Weather in Paris?"));
+        assert!(prompt.ends_with("User: And tomorrow?\n\nAssistant: Probably similar."));
+        // System prompt, tool results and the empty tool-call turn stay out of the transcript
+        assert!(!prompt.contains("You are Shard."));
+        assert!(!prompt.contains("Weather in Paris: 20 C"));
+        assert!(!prompt.contains("Assistant: \n"));
+
+        let prompt = build_summary_prompt(&history, 0).unwrap();
+        assert!(prompt.contains(&format!("at most {} words", DEFAULT_SUMMARY_WORDS)));
+
+        assert!(build_summary_prompt(&[], 100).is_err());
+        assert!(build_summary_prompt(&[msg("system", "You are Shard.")], 100).is_err());
+    }
+
+    #[test]
+    fn test_clean_title() {
+        use crate::agent::clean_title;
+
+        assert_eq!(clean_title("\"Paris Weather Forecast\"\n"), "Paris Weather Forecast");
+        assert_eq!(clean_title("Title: Rust Lifetimes Explained."), "Rust Lifetimes Explained");
+        assert_eq!(
+            clean_title("One two three four five six seven eight"),
+            "One two three four five six"
+        );
+        assert_eq!(clean_title("  "), "");
+    }
+
+    #[test]
+    fn test_pop_last_response_removes_whole_turn() {
+        use crate::agent::pop_last_response;
+
+        // Last turn is a plain reply: only it is popped
+        let mut history = history_with_tool_call();
+        let popped = pop_last_response(&mut history).unwrap();
+        assert_eq!(popped.len(), 1);
+        assert_eq!(popped[0].content.as_deref(), Some("Probably similar."));
+        assert_eq!(history.len(), 5);
+        assert_eq!(history.last().unwrap().role, "user");
+
+        // Last turn used a tool: the call, its result and the reply all go
+        let mut history = history_with_tool_call();
+        history.truncate(4);
+        let popped = pop_last_response(&mut history).unwrap();
+        let roles: Vec<&str> = popped.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, vec!["assistant", "tool", "assistant"]);
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].content.as_deref(), Some("Weather in Paris?"));
+    }
+
+    #[tokio::test]
+    async fn test_reprocess_follows_tool_call_to_an_answer() {
+        use crate::agent::run_turns;
+
+        // The first re-run turn calls a tool; the second answers
+        let mut history = vec![msg("user", "Weather in Paris?")];
+        let replies = history_with_tool_call();
+        let turns = run_turns(5, || {
+            let done = history.len() > 1;
+            history.extend_from_slice(if done { &replies[3..4] } else { &replies[1..3] });
+            async move { Ok(!done) }
+        })
+        .await
+        .unwrap();
+        assert_eq!(turns, 2);
+        assert_eq!(history.len(), 4);
+        assert_eq!(history.last().unwrap().content.as_deref(), Some("It's 20 C in Paris."));
+
+        // A model that keeps calling tools is cut off at the turn limit
+        let mut calls = 0;
+        let turns = run_turns(3, || {
+            calls += 1;
+            async { Ok(true) }
+        })
+        .await
+        .unwrap();
+        assert_eq!((turns, calls), (3, 3));
+    }
+
+    #[test]
+    fn test_pop_last_response_requires_user_message() {
+        use crate::agent::pop_last_response;
+
+        assert!(pop_last_response(&mut Vec::new()).is_err());
+
+        let mut history = vec![msg("assistant", "Hello! How can I help?")];
+        assert!(pop_last_response(&mut history).is_err());
+        assert_eq!(history.len(), 1, "Failed pop must not modify history");
+
+        // Unanswered user message: nothing to pop, but still valid to re-run
+        let mut history = vec![msg("user", "Hi")];
+        assert!(pop_last_response(&mut history).unwrap().is_empty());
+        assert_eq!(history.len(), 1);
+    }
+
+    #[test]
+    fn test_turn_start_follows_rewritten_history() {
+        use crate::agent::{branch_history, last_turn_start, GenerationOverrides};
+
+        let mut history = history_with_tool_call();
+        assert_eq!(last_turn_start(&history), 4);
+        assert_eq!(last_turn_start(&[]), 0);
+        assert_eq!(last_turn_start(&[msg("assistant", "Hello!")]), 0);
+
+        // A stale index from the longer history would slice past the end after a branch
+        let mut focused = GenerationOverrides::default().with_focus_mode(true);
+        focused.turn_start = 4;
+        branch_history(&mut history, 3).unwrap();
+        assert!(focused.visible_history(&history).is_empty());
+
+        focused.turn_start = last_turn_start(&history);
+        let visible = focused.visible_history(&history);
+        assert_eq!(visible.len(), 3);
+        assert_eq!(visible[0].content.as_deref(), Some("Weather in Paris?"));
+    }
+
+    #[test]
+    fn test_compaction_split_index() {
+        use crate::agent::compaction_split_index;
+
+        // Two turns: [user, call, tool, assistant] then [user, assistant]
+        let history = history_with_tool_call();
+        assert_eq!(compaction_split_index(&history, 1), 4);
+        // Keeping as many turns as exist (or more) compacts nothing
+        assert_eq!(compaction_split_index(&history, 2), 0);
+        assert_eq!(compaction_split_index(&history, 10), 0);
+        // keep_recent = 0 summarizes everything
+        assert_eq!(compaction_split_index(&history, 0), 6);
+        assert_eq!(compaction_split_index(&[], 3), 0);
+
+        // The kept part starts at a user message, so tool pairs are never split
+        let split = compaction_split_index(&history, 1);
+        assert_eq!(history[split].role, "user");
+        assert_eq!(history[split].content.as_deref(), Some("And tomorrow?"));
+    }
+
+    #[test]
+    fn test_compaction_summary_only_replaces_an_unchanged_prefix() {
+        use crate::agent::{splice_compaction_summary, COMPACTED_SUMMARY_HEADER};
+
+        let original = history_with_tool_call();
+        let compacted = original[..4].to_vec();
+
+        // Messages appended while summarizing are kept after the note
+        let mut history = original.clone();
+        history.push(msg("user", "And the weekend?"));
+        assert!(splice_compaction_summary(&mut history, &compacted, " - Asked about Paris weather \n"));
+        assert_eq!(history.len(), 4);
+        assert_eq!(
+            history[0].content.as_deref(),
+            Some(format!("{}\n- Asked about Paris weather", COMPACTED_SUMMARY_HEADER).as_str())
+        );
+        assert_eq!(history[1..3], original[4..]);
+        assert_eq!(history[3].content.as_deref(), Some("And the weekend?"));
+
+        // A rewind or branch meanwhile leaves the history alone
+        let mut rewound = original[..2].to_vec();
+        assert!(!splice_compaction_summary(&mut rewound, &compacted, "summary"));
+        assert_eq!(rewound, original[..2]);
+    }
+
+    #[test]
+    fn test_build_compaction_prompt() {
+        use crate::agent::build_compaction_prompt;
+
+        let history = history_with_tool_call();
+        let prompt = build_compaction_prompt(&history[..4]);
+        assert!(prompt.contains("user: Weather in Paris?"));
+        assert!(prompt.contains("assistant: (called tools: get_weather)"));
+        assert!(prompt.contains("tool: Weather in Paris: 20 C"));
+        assert!(prompt.ends_with("assistant: It's 20 C in Paris."));
+        assert!(!prompt.contains("And tomorrow?"));
+    }
+
+    #[test]
+    fn test_repeated_tool_call_detection() {
+        use crate::agent::{ToolCallTracker, MAX_IDENTICAL_TOOL_CALLS};
+        use crate::cache::make_cache_key;
+
+        let mut tracker = ToolCallTracker::default();
+        let key = make_cache_key("web_search", &serde_json::json!({ "query": "rust 2024" }));
+        let other = make_cache_key("web_search", &serde_json::json!({ "query": "rust 2025" }));
+
+        for _ in 0..MAX_IDENTICAL_TOOL_CALLS {
+            assert!(tracker.begin_call(&key, "web_search").is_none());
+            tracker.record_result(&key, "Web Search Results:\n- [Rust](https://rust-lang.org)");
+        }
+        // Different args are tracked separately
+        assert!(tracker.begin_call(&other, "web_search").is_none());
+
+        let repeat = tracker.begin_call(&key, "web_search").expect("should short-circuit");
+        assert!(repeat.contains("Already performed this web_search call 3 times"));
+        assert!(repeat.contains("https://rust-lang.org"));
+        assert!(repeat.contains("Synthesize your answer"));
+
+        // A new message starts with a clean slate
+        tracker.reset();
+        assert!(tracker.begin_call(&key, "web_search").is_none());
+    }
+
+    #[test]
+    fn test_repeated_tool_call_message_without_result() {
+        use crate::agent::repeated_tool_call_message;
+
+        let msg = repeated_tool_call_message("get_weather", 3, None);
+        assert!(!msg.contains("Previous result"));
+        assert!(msg.contains("Do not repeat this call"));
+    }
+
+    #[test]
+    fn test_classify_task() {
+        use crate::agent::{classify_task, TaskType};
+
+        assert_eq!(classify_task(true, "Fix this Rust function"), TaskType::Research);
+        assert_eq!(classify_task(false, "Why does this Python traceback happen?"), TaskType::Code);
+        assert_eq!(classify_task(false, "```\nfn main() {}\n```"), TaskType::Code);
+        assert_eq!(classify_task(false, "What's a good pasta recipe?"), TaskType::Chat);
+        // Keywords match whole words only ("encode" is not "code")
+        assert_eq!(classify_task(false, "How do I encode my thoughts?"), TaskType::Chat);
+    }
+
+    #[test]
+    fn test_route_model_matrix() {
+        use crate::agent::{route_model, TaskType};
+        use crate::config::{AppConfig, ModelRoutes};
+
+        let all = [TaskType::Chat, TaskType::Research, TaskType::Code, TaskType::Vision];
+
+        // No routes: everything uses selected_model
+        let config = AppConfig {
+            selected_model: Some("picked".to_string()),
+            ..AppConfig::default()
+        };
+        for task in all {
+            assert_eq!(route_model(&config, task).as_deref(), Some("picked"));
+        }
+
+        // Specific routes win, unset ones use the default route
+        let config = AppConfig {
+            selected_model: Some("picked".to_string()),
+            model_routes: Some(ModelRoutes {
+                default: Some("cheap".to_string()),
+                research: Some("strong".to_string()),
+                code: Some("coder".to_string()),
+                vision: None,
+            }),
+            ..AppConfig::default()
+        };
+        assert_eq!(route_model(&config, TaskType::Chat).as_deref(), Some("cheap"));
+        assert_eq!(route_model(&config, TaskType::Research).as_deref(), Some("strong"));
+        assert_eq!(route_model(&config, TaskType::Code).as_deref(), Some("coder"));
+        assert_eq!(route_model(&config, TaskType::Vision).as_deref(), Some("cheap"));
+
+        // Without a default route (or with a blank one), unset routes fall back to selected_model
+        let config = AppConfig {
+            selected_model: Some("picked".to_string()),
+            model_routes: Some(ModelRoutes {
+                default: Some("  ".to_string()),
+                vision: Some("seer".to_string()),
+                ..ModelRoutes::default()
+            }),
+            ..AppConfig::default()
+        };
+        assert_eq!(route_model(&config, TaskType::Chat).as_deref(), Some("picked"));
+        assert_eq!(route_model(&config, TaskType::Research).as_deref(), Some("picked"));
+        assert_eq!(route_model(&config, TaskType::Vision).as_deref(), Some("seer"));
+    }
+
+    #[test]
+    fn test_degraded_event_fires_once_per_session() {
+        use crate::agent::{DegradationTracker, EMBEDDING_FAILURES_BEFORE_DEGRADED};
+
+        let mut tracker = DegradationTracker::default();
+
+        // A single failure followed by a success is just a blip
+        assert!(!tracker.record_failure());
+        tracker.record_success();
+
+        // Repeated failures report exactly once
+        let fired: Vec<bool> = (0..EMBEDDING_FAILURES_BEFORE_DEGRADED + 3)
+            .map(|_| tracker.record_failure())
+            .collect();
+        assert_eq!(fired.iter().filter(|f| **f).count(), 1);
+        assert!(fired[EMBEDDING_FAILURES_BEFORE_DEGRADED as usize - 1]);
+
+        // Recovery and a fresh outage don't re-report within the session
+        tracker.record_success();
+        for _ in 0..EMBEDDING_FAILURES_BEFORE_DEGRADED + 1 {
+            assert!(!tracker.record_failure());
+        }
+    }
+
+    #[test]
+    fn test_degraded_event_redacts_keyed_urls() {
+        use crate::agent::degraded_event;
+
+        let reason = "error sending request for url (https://generativelanguage.googleapis.com/v1beta/models/embed:embedContent?key=AIzaSecret123)";
+        let event = degraded_event("rag", reason);
+
+        assert_eq!(event["feature"], "rag");
+        let reported = event["reason"].as_str().unwrap();
+        assert!(!reported.contains("AIzaSecret123"));
+        assert!(reported.contains("?key=[REDACTED]"));
+    }
+
+    #[test]
+    fn test_citations_accumulate_across_tool_calls() {
+        use crate::agent::{extract_citations, CitationTracker};
+
+        let search = "Web Search Results:\n- [Rust Book](https://doc.rust-lang.org/book/) : The book\n\n\
+                      - [Tokio](https://tokio.rs) : Async runtime";
+        let wiki = "Wikipedia Title: Rust (programming language)\n\
+                    Source: https://en.wikipedia.org/wiki/Rust_(programming_language)\nSummary: A language";
+        let paper = "# Attention Is All You Need\nSource: https://arxiv.org/abs/1706.03762\n\n**Abstract:** ...";
+        // Repeated source from a second search, and a failed call
+        let search_again = "Web Search Results:\n- [Tokio docs](https://tokio.rs) : Same site";
+
+        let mut tracker = CitationTracker::default();
+        tracker.extend(extract_citations("web_search", search));
+        tracker.extend(extract_citations("search_wikipedia", wiki));
+        tracker.extend(extract_citations("read_arxiv_paper", paper));
+        tracker.extend(extract_citations("web_search", search_again));
+        tracker.extend(extract_citations("read_arxiv_paper", "Error reading paper: 404"));
+
+        let citations = tracker.take();
+        let urls: Vec<&str> = citations.iter().map(|c| c.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            vec![
+                "https://doc.rust-lang.org/book/",
+                "https://tokio.rs",
+                "https://en.wikipedia.org/wiki/Rust_(programming_language)",
+                "https://arxiv.org/abs/1706.03762",
+            ]
+        );
+        assert_eq!(citations[0].title, "Rust Book");
+        assert_eq!(citations[2].title, "Rust (programming language)");
+        assert_eq!(citations[3].title, "Attention Is All You Need");
+
+        // Taking drains the turn's sources
+        assert!(tracker.take().is_empty());
+    }
+
+    fn session_json(messages: &[ChatMessage]) -> String {
+        serde_json::to_string(messages).unwrap()
+    }
+
+    #[test]
+    fn test_session_create_switch_delete() {
+        use crate::agent::{
+            delete_session_file, list_sessions, load_session, read_active_session, save_session,
+            write_active_session, DEFAULT_SESSION_ID,
+        };
+
+        let dir = tempfile::TempDir::new().unwrap();
+        assert!(list_sessions(dir.path()).unwrap().is_empty());
+        assert_eq!(read_active_session(dir.path()), DEFAULT_SESSION_ID);
+
+        // Create two sessions
+        save_session(dir.path(), "work", &session_json(&[msg("user", "Plan the Q3 roadmap"), msg("model", "Sure")]))
+            .unwrap();
+        save_session(dir.path(), "fun", &session_json(&[])).unwrap();
+        let sessions = list_sessions(dir.path()).unwrap();
+        assert_eq!(sessions.len(), 2);
+        let work = sessions.iter().find(|s| s.id == "work").unwrap();
+        assert_eq!(work.title, "Plan the Q3 roadmap");
+        assert_eq!(work.message_count, 2);
+        assert_eq!(sessions.iter().find(|s| s.id == "fun").unwrap().title, "New chat");
+
+        // Switching loads that session's messages and is remembered
+        write_active_session(dir.path(), "work").unwrap();
+        assert_eq!(read_active_session(dir.path()), "work");
+        let history = load_session(dir.path(), "work").unwrap();
+        assert_eq!(history[1].content.as_deref(), Some("Sure"));
+        assert!(load_session(dir.path(), "never-saved").unwrap().is_empty());
+
+        // Delete
+        assert!(delete_session_file(dir.path(), "fun").unwrap());
+        assert!(!delete_session_file(dir.path(), "fun").unwrap());
+        let ids: Vec<String> = list_sessions(dir.path()).unwrap().into_iter().map(|s| s.id).collect();
+        assert_eq!(ids, vec!["work"]);
+
+        // Ids can't escape the chats directory
+        assert!(save_session(dir.path(), "../config", "[]").is_err());
+        assert!(delete_session_file(dir.path(), "..").is_err());
+    }
+
+    #[test]
+    fn test_session_model_overrides_configured_model() {
+        use crate::agent::{route_model, with_session_model, TaskType};
+        use crate::config::{AppConfig, ModelRoutes};
+
+        let config = AppConfig {
+            selected_model: Some("gemini-2.5-flash".to_string()),
+            model_routes: Some(ModelRoutes {
+                code: Some("qwen-3-coder (Cerebras)".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        // No pin (or a blank one) keeps the configured model and routes
+        let unpinned = with_session_model(&config, None);
+        assert_eq!(unpinned.selected_model.as_deref(), Some("gemini-2.5-flash"));
+        assert!(unpinned.model_routes.is_some());
+        assert_eq!(with_session_model(&config, Some("  ")).selected_model, config.selected_model);
+
+        // A pin wins over selected_model and over task routing
+        let pinned = with_session_model(&config, Some("claude-sonnet-4-5"));
+        assert_eq!(pinned.selected_model.as_deref(), Some("claude-sonnet-4-5"));
+        assert_eq!(route_model(&pinned, TaskType::Code).as_deref(), Some("claude-sonnet-4-5"));
+    }
+
+    #[test]
+    fn test_session_model_persists_per_session() {
+        use crate::agent::{delete_session_file, read_session_model, save_session, write_session_model};
+
+        let dir = tempfile::TempDir::new().unwrap();
+        save_session(dir.path(), "work", "[]").unwrap();
+        save_session(dir.path(), "fun", "[]").unwrap();
+
+        write_session_model(dir.path(), "work", Some("claude-sonnet-4-5")).unwrap();
+        assert_eq!(read_session_model(dir.path(), "work").as_deref(), Some("claude-sonnet-4-5"));
+        assert_eq!(read_session_model(dir.path(), "fun"), None);
+
+        write_session_model(dir.path(), "work", None).unwrap();
+        assert_eq!(read_session_model(dir.path(), "work"), None);
+
+        // Deleting the session drops its pin too
+        write_session_model(dir.path(), "fun", Some("gemini-2.5-pro")).unwrap();
+        assert!(delete_session_file(dir.path(), "fun").unwrap());
+        assert_eq!(read_session_model(dir.path(), "fun"), None);
+    }
+
+    #[test]
+    fn test_legacy_history_migrates_to_default_session() {
+        use crate::agent::{
+            list_sessions, load_session, migrate_legacy_history, DEFAULT_SESSION_ID, LEGACY_HISTORY_FILENAME,
+        };
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let legacy = dir.path().join(LEGACY_HISTORY_FILENAME);
+        std::fs::write(&legacy, session_json(&[msg("user", "Old question"), msg("model", "Old answer")])).unwrap();
+
+        assert!(migrate_legacy_history(dir.path()).unwrap());
+        assert!(!legacy.exists());
+        let history = load_session(dir.path(), DEFAULT_SESSION_ID).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].content.as_deref(), Some("Old question"));
+        assert_eq!(list_sessions(dir.path()).unwrap()[0].id, DEFAULT_SESSION_ID);
+
+        // Nothing left to migrate, and an existing default session is never overwritten
+        assert!(!migrate_legacy_history(dir.path()).unwrap());
+        std::fs::write(&legacy, "[]").unwrap();
+        assert!(!migrate_legacy_history(dir.path()).unwrap());
+        assert_eq!(load_session(dir.path(), DEFAULT_SESSION_ID).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_construct_anthropic_messages_maps_tool_calls() {
+        use crate::agent::{construct_anthropic_messages, AnthropicContent};
+
+        let mut history = vec![msg("system", "Be brief.")];
+        history.extend(history_with_tool_call());
+        // A second user message right after another is merged into the same turn
+        history.push(msg("user", "Also Berlin?"));
+        history.push(msg("user", "Thanks"));
+
+        let messages = construct_anthropic_messages(&history);
+        let roles: Vec<&str> = messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, vec!["user", "assistant", "user", "assistant", "user", "assistant", "user"]);
+
+        assert_eq!(
+            messages[1].content,
+            vec![AnthropicContent::ToolUse {
+                id: "call_1".to_string(),
+                name: "get_weather".to_string(),
+                input: serde_json::json!({"location": "Paris"}),
+            }]
+        );
+        assert_eq!(
+            messages[2].content,
+            vec![AnthropicContent::ToolResult {
+                tool_use_id: "call_1".to_string(),
+                content: "Weather in Paris: 20 C".to_string(),
+            }]
+        );
+        assert_eq!(messages[6].content.len(), 2);
+
+        let json = serde_json::to_value(&messages[1]).unwrap();
+        assert_eq!(json["content"][0]["type"], "tool_use");
+        assert_eq!(json["content"][0]["input"]["location"], "Paris");
+    }
+
+    #[test]
+    fn test_parse_anthropic_stream_events() {
+        use crate::agent::{parse_anthropic_event, AnthropicStreamEvent, ToolUseAccumulator};
+
+        let text = r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hello"}}"#;
+        assert_eq!(parse_anthropic_event(text), Some(AnthropicStreamEvent::Text("Hello".to_string())));
+
+        let start = r#"{"type":"content_block_start","index":1,"content_block":{"type":"tool_use","id":"toolu_1","name":"get_weather","input":{}}}"#;
+        let part1 = r#"{"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"{\"location\": "}}"#;
+        let part2 = r#"{"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"\"Paris\"}"}}"#;
+        let mut tools = ToolUseAccumulator::default();
+        for data in [start, part1, part2] {
+            match parse_anthropic_event(data) {
+                Some(AnthropicStreamEvent::ToolUseStart { index, id, name }) => tools.start(index, id, name),
+                Some(AnthropicStreamEvent::ToolInputDelta { index, partial_json }) => {
+                    tools.push_input(index, &partial_json)
+                }
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+        let calls = tools.finish();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].id, "toolu_1");
+        assert_eq!(calls[0].function.name, "get_weather");
+        assert_eq!(calls[0].function.arguments, "{\"location\": \"Paris\"}");
+
+        let stop = r#"{"type":"message_delta","delta":{"stop_reason":"tool_use","stop_sequence":null},"usage":{"output_tokens":15}}"#;
+        assert_eq!(parse_anthropic_event(stop), Some(AnthropicStreamEvent::Stop("tool_use".to_string())));
+        assert_eq!(parse_anthropic_event(r#"{"type":"ping"}"#), None);
+        assert_eq!(
+            parse_anthropic_event(r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#),
+            Some(AnthropicStreamEvent::Error("Overloaded".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_anthropic_model_detection() {
+        use crate::agent::{anthropic_model_id, is_anthropic_model};
+        use crate::config::AppConfig;
+
+        let config = AppConfig::default();
+        assert!(is_anthropic_model("claude-sonnet-4-5", &config));
+        assert!(is_anthropic_model("claude-opus-4-1 (Anthropic)", &config));
+        // OpenRouter ids stay on OpenRouter unless an Anthropic key is set
+        assert!(!is_anthropic_model("anthropic/claude-sonnet-4.5", &config));
+        let with_key = AppConfig {
+            anthropic_api_key: Some("sk-ant".to_string()),
+            ..Default::default()
+        };
+        assert!(is_anthropic_model("anthropic/claude-sonnet-4.5", &with_key));
+        assert!(!is_anthropic_model("gemini-2.5-flash", &with_key));
+
+        assert_eq!(anthropic_model_id("anthropic/claude-sonnet-4.5"), "claude-sonnet-4-5");
+        assert_eq!(anthropic_model_id("claude-opus-4-1 (Anthropic)"), "claude-opus-4-1");
+    }
+
+    #[test]
+    fn test_base64_decoded_len() {
+        use crate::agent::base64_decoded_len;
+
+        assert_eq!(base64_decoded_len("QUJD"), 3);
+        assert_eq!(base64_decoded_len("QUI="), 2);
+        assert_eq!(base64_decoded_len("QQ=="), 1);
+        assert_eq!(base64_decoded_len("QUJDRA"), 4); // unpadded
+        assert_eq!(base64_decoded_len(""), 0);
+    }
+
+    #[test]
+    fn test_enforce_image_limits_count_cap() {
+        use crate::agent::enforce_image_limits;
+        use crate::config::AppConfig;
+
+        let config = AppConfig {
+            max_images_per_message: Some(2),
+            ..Default::default()
+        };
+        let mut bases = vec!["QUJD".to_string(); 5];
+        let mut mimes = vec!["image/png".to_string(); 5];
+        let mut warnings = Vec::new();
+        enforce_image_limits(&mut bases, &mut mimes, &config, |w| warnings.push(w));
+
+        assert_eq!(bases.len(), 2);
+        assert_eq!(mimes.len(), 2);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0]["dropped"], 3);
+        let details = warnings[0]["details"].as_str().unwrap();
+        assert!(details.contains("Sent 2 of 5 images"), "{}", details);
+        assert!(details.contains("at most 2 images"), "{}", details);
+    }
+
+    #[test]
+    fn test_enforce_image_limits_byte_cap() {
+        use crate::agent::enforce_image_limits;
+        use crate::config::AppConfig;
+
+        // Each image decodes to 300 bytes; only the first two fit in 700
+        let config = AppConfig {
+            max_total_image_bytes: Some(700),
+            ..Default::default()
+        };
+        let mut bases = vec!["QUJD".repeat(100); 3];
+        let mut mimes = vec!["image/jpeg".to_string(); 3];
+        let mut warnings = Vec::new();
+        enforce_image_limits(&mut bases, &mut mimes, &config, |w| warnings.push(w));
+
+        assert_eq!(bases.len(), 2);
+        assert_eq!(mimes.len(), 2);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0]["details"].as_str().unwrap().contains("MB per message"));
+    }
+
+    #[test]
+    fn test_enforce_image_limits_within_caps_is_silent() {
+        use crate::agent::enforce_image_limits;
+        use crate::config::AppConfig;
+
+        let mut bases = vec!["QUJD".to_string(); 3];
+        let mut mimes = vec!["image/png".to_string(); 3];
+        let mut warned = false;
+        enforce_image_limits(&mut bases, &mut mimes, &AppConfig::default(), |_| warned = true);
+
+        assert_eq!(bases.len(), 3);
+        assert!(!warned);
+    }
+
+    #[test]
+    fn test_generation_overrides_precedence() {
+        use crate::agent::GenerationOverrides;
+
+        let none = GenerationOverrides::default();
+        assert_eq!(none.system_prompt_or(|| "computed".to_string()), "computed");
+
+        let custom = GenerationOverrides::new(Some("You are a pirate.".to_string()), Some(0.2), None);
+        assert_eq!(custom.system_prompt_or(|| "computed".to_string()), "You are a pirate.");
+
+        // Blank prompts and NaN fall back to the computed defaults
+        let blank = GenerationOverrides::new(Some("   ".to_string()), Some(f32::NAN), Some(0.9));
+        assert_eq!(blank.system_prompt_or(|| "computed".to_string()), "computed");
+        assert_eq!(blank.temperature, None);
+        assert_eq!(blank.top_p, Some(0.9));
+    }
+
+    #[test]
+    fn test_generation_overrides_populate_request_bodies() {
+        use crate::agent::{GenerationOverrides, ThinkingConfig};
+
+        let overrides = GenerationOverrides::new(None, Some(0.5), Some(0.8));
+        let gemini = serde_json::to_value(overrides.generation_config(Some(ThinkingConfig {
+            include_thoughts: true,
+            thinking_budget: Some(1024),
+        })))
+        .unwrap();
+        assert_eq!(gemini["temperature"], 0.5);
+        assert!((gemini["topP"].as_f64().unwrap() - 0.8).abs() < 1e-6);
+        assert_eq!(gemini["thinkingConfig"]["thinkingBudget"], 1024);
+
+        // Omitted overrides leave the Gemini body exactly as before
+        let plain = serde_json::to_value(GenerationOverrides::default().generation_config(None)).unwrap();
+        assert_eq!(plain, serde_json::json!({}));
+
+        // The same builder the OpenAI-compatible turn and its fallback send
+        let request = |overrides: &GenerationOverrides, tools| {
+            let request = overrides.chat_completion_request("openai/gpt-oss-120b".to_string(), vec![], tools, None, None);
+            serde_json::to_value(request).unwrap()
+        };
+        let body = request(&overrides, None);
+        assert_eq!(body["temperature"], 0.5);
+        assert!((body["top_p"].as_f64().unwrap() - 0.8).abs() < 1e-6);
+        assert_eq!(body["stream"], true);
+        assert!(body.get("tool_choice").is_none());
+        let body = request(&GenerationOverrides::default(), Some(vec![]));
+        assert!(body.get("temperature").is_none());
+        assert!(body.get("top_p").is_none());
+        assert_eq!(body["tool_choice"], "auto");
+    }
+
+    #[test]
+    fn test_select_system_prompt_includes_memory_and_rag_sections() {
+        use crate::agent::select_system_prompt;
+        use crate::config::AppConfig;
+
+        let memory = "- Prefers metric units";
+        let rag = "Relevant Topic Summary:\n### Topic: rust\nAsked about lifetimes";
+        let config = AppConfig::default();
+
+        let prompt = select_system_prompt(&config, "gemini-2.5-flash", false, Some(memory), Some(rag));
+        assert!(prompt.contains(memory));
+        assert!(prompt.contains("### Topic: rust"));
+
+        let bare = select_system_prompt(&config, "gemini-2.5-flash", false, None, None);
+        assert!(!bare.contains(memory));
+        assert!(!bare.contains("### Topic: rust"));
+
+        // Research and incognito prompts never carry stored context
+        let research = select_system_prompt(&config, "gemini-2.5-flash", true, Some(memory), Some(rag));
+        assert!(!research.contains(memory));
+        let incognito = AppConfig {
+            incognito_mode: Some(true),
+            ..Default::default()
+        };
+        let prompt = select_system_prompt(&incognito, "gemini-2.5-flash", false, Some(memory), Some(rag));
+        assert!(!prompt.contains(memory));
+        assert!(!prompt.contains("### Topic: rust"));
+    }
+
+    #[test]
+    fn test_context_used_reports_each_contributing_source() {
+        use crate::agent::context_used_sources;
+        use crate::interactions::{InteractionEntry, InteractionSearchHit, TopicChunkHit};
+        use crate::memories::RelevantContext;
+        use crate::retrieval::{HitSource, ScoredHit, Snippet};
+
+        let hit = |doc_id: &str, source: HitSource, score: f32, role: &str, content: &str| InteractionSearchHit {
+            hit: ScoredHit { doc_id: doc_id.to_string(), score, source, ts: None },
+            sources: vec![source],
+            entry: InteractionEntry {
+                ts: chrono::Utc::now(),
+                role: role.to_string(),
+                content: content.to_string(),
+                embedding: None,
+                embedding_model: None,
+            },
+            snippet: Snippet { text: String::new(), highlight: None },
+        };
+        let hits = vec![hit("2025-01-01T10:00:00+00:00", HitSource::Bm25, 0.03, "user", "  How do I pin a memory?")];
+        let chunks = vec![TopicChunkHit {
+            hit: ScoredHit { doc_id: "rust#2".to_string(), score: 0.02, source: HitSource::DenseTopicChunk, ts: None },
+            topic: "rust".to_string(),
+            content: "Lifetimes...".to_string(),
+        }];
+        let insight = RelevantContext {
+            name: "prefers metric".to_string(),
+            content: "Use metric units.".to_string(),
+            is_insight: true,
+            score: 0.71,
+        };
+
+        let used = context_used_sources(&hits, &chunks, Some(&insight));
+        let payloads: Vec<serde_json::Value> = used.iter().map(|u| serde_json::to_value(u).unwrap()).collect();
+        assert_eq!(
+            payloads,
+            vec![
+                serde_json::json!({"type": "interaction", "name": "How do I pin a memory?", "score": 0.03f32}),
+                serde_json::json!({"type": "topic", "name": "rust", "score": 0.02f32}),
+                serde_json::json!({"type": "insight", "name": "prefers metric", "score": 0.71f32}),
+            ]
+        );
+
+        assert!(context_used_sources(&[], &[], None).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_context_used_event_fires_once_per_chosen_source() {
+        use crate::agent::{context_used_sources, emit_context_used, gather_rag_context};
+        use crate::interactions::TopicChunkHit;
+        use crate::memories::RelevantContext;
+        use crate::retrieval::{HitSource, ScoredHit};
+
+        let chunk = |idx: usize, score: f32| TopicChunkHit {
+            hit: ScoredHit { doc_id: format!("rust#{}", idx), score, source: HitSource::DenseTopicChunk, ts: None },
+            topic: "rust".to_string(),
+            content: "Lifetimes...".to_string(),
+        };
+        let topic = RelevantContext {
+            name: "rust".to_string(),
+            content: "Rust notes.".to_string(),
+            is_insight: false,
+            score: 0.8,
+        };
+
+        let (_, _, used) = gather_rag_context(
+            false,
+            || async { Some(vec![1.0, 0.0]) },
+            |_| (Some("context".to_string()), context_used_sources(&[], &[chunk(0, 0.03), chunk(4, 0.02)], Some(&topic))),
+        )
+        .await;
+
+        let mut events = Vec::new();
+        emit_context_used(&used, |payload| events.push(payload));
+        assert_eq!(events.len(), 1);
+        let payload: serde_json::Value = serde_json::from_str(&events[0]).unwrap();
+        assert_eq!(payload, serde_json::json!({"type": "topic", "name": "rust", "score": 0.03f32}));
+    }
+
+    #[test]
+    fn test_retry_hints_substitute_errors_and_fall_back_to_defaults() {
+        use crate::agent::{RetryReason, DEFAULT_EMPTY_RESPONSE_HINT};
+        use crate::config::RetryHints;
+
+        let latex = RetryReason::MalformedLatex {
+            errors: vec!["Undefined control sequence \\foo".to_string(), "Missing $".to_string()],
+        };
+
+        // No config: built-in text, with the errors listed
+        assert_eq!(RetryReason::EmptyResponse.get_hint(None), DEFAULT_EMPTY_RESPONSE_HINT);
+        let default_hint = latex.get_hint(None);
+        assert!(default_hint.contains("Undefined control sequence \\foo\nMissing $"));
+        assert!(!default_hint.contains("{errors}"));
+
+        let hints = RetryHints {
+            empty_response: Some("  ".to_string()),
+            malformed_latex: Some("Fix these: {errors}. Use \\( \\) delimiters.".to_string()),
+        };
+        assert_eq!(
+            latex.get_hint(Some(&hints)),
+            "Fix these: Undefined control sequence \\foo\nMissing $. Use \\( \\) delimiters."
+        );
+        // A blank override falls back to the default
+        assert_eq!(RetryReason::EmptyResponse.get_hint(Some(&hints)), DEFAULT_EMPTY_RESPONSE_HINT);
+    }
+
+    #[test]
+    fn test_reasoning_buffer_keeps_head_and_tail() {
+        use crate::agent::{ReasoningBuffer, REASONING_TRUNCATION_MARKER};
+
+        // Under the cap: kept verbatim across chunks
+        let mut buffer = ReasoningBuffer::new(10);
+        buffer.push_str("abc");
+        buffer.push_str("defg");
+        assert_eq!(buffer.finish(), "abcdefg");
+
+        // Over the cap: first and last five chars around the marker, split on char boundaries
+        let mut buffer = ReasoningBuffer::new(10);
+        for chunk in ["héllo", " wörld ", "and then ", "sømething", " élse"] {
+            buffer.push_str(chunk);
+        }
+        assert_eq!(buffer.finish(), format!("héllo{} élse", REASONING_TRUNCATION_MARKER));
+
+        // Exactly at the cap is not truncated
+        let mut buffer = ReasoningBuffer::new(4);
+        buffer.push_str("ab");
+        buffer.push_str("cd");
+        assert_eq!(buffer.finish(), "abcd");
+
+        // Zero disables the cap
+        let mut buffer = ReasoningBuffer::new(0);
+        let long = "x".repeat(100_000);
+        buffer.push_str(&long);
+        assert!(!buffer.is_empty());
+        assert_eq!(buffer.finish(), long);
+    }
+
+    #[test]
+    fn test_chat_markdown_round_trip() {
+        use crate::agent::{history_to_markdown, parse_chat_markdown};
+
+        let mut history = history_with_tool_call();
+        history[3].reasoning = Some("Check the tool result.\n\nThen answer.".to_string());
+        // Headings and fences inside content must not split the message
+        history[5].content = Some("## Summary\n```rust\nlet x = 1;\n```\n\\## Escaped".to_string());
+        history[2].content = Some("```\n## User\n```".to_string());
+
+        let markdown = history_to_markdown(&history);
+        let imported = parse_chat_markdown(&markdown).unwrap();
+
+        assert_eq!(imported.len(), history.len());
+        for (original, parsed) in history.iter().zip(&imported) {
+            assert_eq!(parsed.role, original.role);
+            assert_eq!(parsed.content, original.content.clone().filter(|c| !c.is_empty()));
+            assert_eq!(parsed.reasoning, original.reasoning);
+            assert_eq!(parsed.tool_call_id, original.tool_call_id);
+        }
+        let call = &imported[1].tool_calls.as_ref().unwrap()[0];
+        assert_eq!(call.id, "call_1");
+        assert_eq!(call.function.name, "get_weather");
+        assert_eq!(call.function.arguments, "{\"location\":\"Paris\"}");
+
+        // Preamble and unrecognized sections are skipped
+        let lenient = format!("Exported from elsewhere\n\n{}\n# Appendix\nnotes", markdown);
+        assert_eq!(parse_chat_markdown(&lenient).unwrap().len(), history.len());
+        assert!(parse_chat_markdown("# Notes\nnothing here").is_err());
+    }
+
+    #[test]
+    fn test_chat_markdown_round_trip_with_unterminated_code_block() {
+        use crate::agent::{history_to_markdown, parse_chat_markdown};
+
+        let history = vec![
+            msg("user", "Write a loop"),
+            // A cancelled reply leaves its code block open
+            msg("assistant", "Sure:\n```python\nfor i in range(3):"),
+            msg("user", "Show it as a tool block:\n```tool_call get_weather call_9\n{}"),
+            msg("assistant", "Done."),
+        ];
+
+        let imported = parse_chat_markdown(&history_to_markdown(&history)).unwrap();
+        assert_eq!(imported.len(), history.len());
+        for (original, parsed) in history.iter().zip(&imported) {
+            assert_eq!(parsed.role, original.role);
+            assert_eq!(parsed.content, original.content);
+            assert!(parsed.tool_calls.is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_focus_mode_skips_rag_and_memory() {
+        use crate::agent::{gather_rag_context, ContextUsed, GenerationOverrides};
+        use std::cell::Cell;
+
+        let embedded = Cell::new(false);
+        let assembled = Cell::new(false);
+        let used = || vec![ContextUsed { kind: "topic".to_string(), name: "Rust".to_string(), score: 0.9 }];
+
+        let (embedding, rag, sources) = gather_rag_context(
+            true,
+            || async { embedded.set(true); Some(vec![0.1, 0.2]) },
+            |_| { assembled.set(true); (Some("context".to_string()), used()) },
+        )
+        .await;
+        assert!(!embedded.get() && !assembled.get());
+        assert_eq!((embedding, rag, sources), (None, None, Vec::new()));
+
+        let (embedding, rag, sources) = gather_rag_context(
+            false,
+            || async { embedded.set(true); Some(vec![0.1, 0.2]) },
+            |emb| { assembled.set(emb.is_some()); (Some("context".to_string()), used()) },
+        )
+        .await;
+        assert!(embedded.get() && assembled.get());
+        assert_eq!((embedding, rag, sources), (Some(vec![0.1, 0.2]), Some("context".to_string()), used()));
+
+        // Memories are never loaded and only the current turn is sent
+        let mut focused = GenerationOverrides::default().with_focus_mode(true);
+        focused.turn_start = 4;
+        let memories: Option<String> = focused.unless_focused(|| panic!("memories loaded in focus mode"));
+        assert_eq!(memories, None);
+        let history = history_with_tool_call();
+        let visible = focused.visible_history(&history);
+        assert_eq!(visible.len(), 2);
+        assert_eq!(visible[0].content.as_deref(), Some("And tomorrow?"));
+
+        let normal = GenerationOverrides { turn_start: 4, ..Default::default() };
+        assert_eq!(normal.unless_focused(|| Some("memories".to_string())), Some("memories".to_string()));
+        assert_eq!(normal.visible_history(&history).len(), history.len());
+    }
+
+    #[test]
+    fn test_reprocess_override_beats_session_pin() {
+        use crate::agent::with_model_override;
+        use crate::config::AppConfig;
+
+        let config = AppConfig { selected_model: Some("gemini-2.5-flash".to_string()), ..Default::default() };
+        let pin = Some("claude-sonnet-4-5");
+
+        // Pinned session + explicit override: the override runs
+        let overridden = with_model_override(&config, pin, Some("openai/gpt-4o"));
+        assert_eq!(overridden.selected_model.as_deref(), Some("openai/gpt-4o"));
+
+        // No (or a blank) override: the pin still applies
+        assert_eq!(with_model_override(&config, pin, None).selected_model.as_deref(), pin);
+        assert_eq!(with_model_override(&config, pin, Some(" ")).selected_model.as_deref(), pin);
+        assert_eq!(with_model_override(&config, None, None).selected_model, config.selected_model);
+    }
+
+    #[test]
+    fn test_chat_provider_routing() {
+        use crate::agent::{chat_provider, ChatProvider};
+        use crate::config::AppConfig;
+
+        let config = AppConfig::default();
+        assert_eq!(chat_provider("gemini-2.5-flash", &config), ChatProvider::Gemini);
+        assert_eq!(chat_provider("claude-sonnet-4-5", &config), ChatProvider::Anthropic);
+        for model in ["qwen/qwen3-235b", "llama-3.3-70b (Cerebras)", "gpt-oss-120b (Groq)", "llama3 (Local)"] {
+            assert_eq!(chat_provider(model, &config), ChatProvider::OpenAiCompatible, "{}", model);
+        }
+        // anthropic/ ids go direct only with an Anthropic key, otherwise through OpenRouter
+        assert_eq!(chat_provider("anthropic/claude-sonnet-4.5", &config), ChatProvider::OpenAiCompatible);
+        let direct = AppConfig { anthropic_api_key: Some("sk-ant".to_string()), ..Default::default() };
+        assert_eq!(chat_provider("anthropic/claude-sonnet-4.5", &direct), ChatProvider::Anthropic);
+    }
 }
@@ -6,8 +6,9 @@
  */
 
 use crate::background::{
-    analyze_interactions_in_dir, cleanup_interactions_in_dir, parse_cleanup_decision,
-    parse_topic_updates, LOOKBACK_HOURS, LOG_RETENTION_DAYS,
+    analyze_interactions_in_dir, cleanup_interactions_in_dir, defer_reason, in_quiet_hours,
+    parse_cleanup_decision, parse_topic_updates, remove_entries_by_timestamp, DeferReason,
+    ACTIVITY_DEFER_MINUTES, LOOKBACK_HOURS, LOG_RETENTION_DAYS,
 };
 use chrono::{Duration as ChronoDuration, Utc};
 use std::fs;
@@ -289,3 +290,107 @@ fn test_parse_cleanup_decision_no_json() {
 
     assert!(result.is_err());
 }
+
+#[test]
+fn test_removed_entries_drop_out_of_bm25_search() {
+    use crate::retrieval::BM25Index;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let interactions_dir = temp_dir.path().join("interactions");
+    fs::create_dir_all(&interactions_dir).expect("Failed to create interactions dir");
+
+    let path = interactions_dir.join("interactions-2024-06-01.jsonl");
+    let mut file = fs::File::create(&path).expect("Failed to create test file");
+    let entries = [
+        ("2024-06-01T10:00:00Z", "hello there, how are you"),
+        ("2024-06-01T11:00:00Z", "kubernetes ingress configuration notes"),
+    ];
+
+    // Index the entries the same way write_interaction_in_dir does (doc_id = ts.to_rfc3339())
+    let mut index = BM25Index::new();
+    for (ts, content) in &entries {
+        let entry = serde_json::json!({ "ts": ts, "role": "user", "content": content });
+        writeln!(file, "{}", entry).expect("Failed to write entry");
+        let ts = chrono::DateTime::parse_from_rfc3339(ts).unwrap().with_timezone(&Utc);
+        index.add_document(&ts.to_rfc3339(), content, Some(ts));
+    }
+    drop(file);
+
+    let (deleted, _, removed_doc_ids) =
+        remove_entries_by_timestamp(&interactions_dir, &["2024-06-01T10:00:00".to_string()])
+            .expect("Removal should succeed");
+    assert_eq!(deleted, 1);
+    assert_eq!(removed_doc_ids.len(), 1);
+
+    for doc_id in &removed_doc_ids {
+        index.remove_document(doc_id);
+    }
+
+    assert!(index.search("hello there", 10).is_empty());
+    assert_eq!(index.search("kubernetes ingress", 10).len(), 1);
+    assert_eq!(index.doc_count, 1);
+}
+
+#[test]
+fn test_defer_on_recent_activity() {
+    let now = Utc::now();
+
+    // Active a minute ago: defer
+    let recent = Some(now - ChronoDuration::minutes(1));
+    assert_eq!(defer_reason(now, recent, 14, None), Some(DeferReason::RecentActivity));
+
+    // Idle past the window: run
+    let stale = Some(now - ChronoDuration::minutes(ACTIVITY_DEFER_MINUTES + 1));
+    assert_eq!(defer_reason(now, stale, 14, None), None);
+
+    // No activity recorded yet: run
+    assert_eq!(defer_reason(now, None, 14, None), None);
+}
+
+#[test]
+fn test_defer_in_quiet_hours() {
+    let now = Utc::now();
+    let overnight = Some((23, 7));
+
+    assert_eq!(defer_reason(now, None, 2, overnight), Some(DeferReason::QuietHours));
+    assert_eq!(defer_reason(now, None, 23, overnight), Some(DeferReason::QuietHours));
+    assert_eq!(defer_reason(now, None, 7, overnight), None);
+    assert_eq!(defer_reason(now, None, 12, overnight), None);
+
+    // Recent activity is reported first
+    let recent = Some(now - ChronoDuration::minutes(2));
+    assert_eq!(defer_reason(now, recent, 2, overnight), Some(DeferReason::RecentActivity));
+}
+
+#[test]
+fn test_in_quiet_hours_windows() {
+    // Same-day window
+    assert!(in_quiet_hours(13, 12, 14));
+    assert!(!in_quiet_hours(14, 12, 14));
+    // Wrapping window
+    assert!(in_quiet_hours(0, 22, 6));
+    assert!(!in_quiet_hours(21, 22, 6));
+    // Empty window never matches
+    assert!(!in_quiet_hours(5, 5, 5));
+}
+
+#[test]
+fn test_cancel_checkpoint_stops_runs_started_before_cancel() {
+    use crate::background::{CancelCheckpoint, JOB_CANCELLED};
+    use std::sync::atomic::AtomicU64;
+
+    static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+    let running = CancelCheckpoint::new(&GENERATION);
+    assert!(running.check("before topic extraction").is_ok());
+
+    CancelCheckpoint::cancel(&GENERATION);
+    assert!(running.is_cancelled());
+    assert_eq!(running.check("between topic updates"), Err(JOB_CANCELLED.to_string()));
+
+    // A run started after the cancel isn't affected by it
+    let next = CancelCheckpoint::new(&GENERATION);
+    assert!(!next.is_cancelled());
+    assert!(next.check("before cleanup review").is_ok());
+    assert!(running.is_cancelled());
+}
@@ -6,7 +6,7 @@
 
 #[cfg(test)]
 mod tests {
-    use crate::cache::{get_ttl_for_tool, make_cache_key};
+    use crate::cache::{get_ttl_for_tool, is_cacheable_call, is_cacheable_result, make_cache_key};
 
     #[test]
     fn test_cache_key_consistency() {
@@ -74,4 +74,19 @@ mod tests {
 
         assert!(key.starts_with("refresh_memories:"));
     }
+
+    #[test]
+    fn test_error_results_are_not_cacheable() {
+        assert!(is_cacheable_result("Paris: 18°C, partly cloudy"));
+        assert!(is_cacheable_result("Results mention an Error: in passing"));
+        assert!(!is_cacheable_result("Error: Request timed out"));
+        assert!(!is_cacheable_result("\nError: Wikipedia returned 503"));
+    }
+
+    #[test]
+    fn test_current_location_weather_is_not_cached() {
+        assert!(is_cacheable_call("get_weather", &serde_json::json!({"location": "Paris"})));
+        assert!(!is_cacheable_call("get_weather", &serde_json::json!({"location": "here"})));
+        assert!(!is_cacheable_call("save_memory", &serde_json::json!({"content": "x"})));
+    }
 }
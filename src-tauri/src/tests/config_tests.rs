@@ -20,3 +20,107 @@ fn test_config_serialization() {
     let deserialized: AppConfig = toml::from_str(&serialized).unwrap();
     assert_eq!(deserialized.research_mode, Some(true));
 }
+
+#[test]
+fn test_validation_endpoint_known_providers() {
+    use crate::config::get_validation_endpoint;
+
+    let (gemini_url, gemini_bearer) = get_validation_endpoint("gemini").unwrap();
+    assert!(gemini_url.contains("generativelanguage.googleapis.com"));
+    assert!(!gemini_bearer, "Gemini takes the key as a query param");
+
+    assert!(get_validation_endpoint("OpenRouter").unwrap().1);
+    assert!(get_validation_endpoint("cerebras").unwrap().0.contains("cerebras.ai"));
+    assert!(get_validation_endpoint("groq").unwrap().0.contains("groq.com"));
+}
+
+#[test]
+fn test_validation_endpoint_unknown_provider() {
+    use crate::config::get_validation_endpoint;
+    assert!(get_validation_endpoint("anthropic-typo").is_none());
+}
+
+#[test]
+fn test_profile_creation_and_listing() {
+    use crate::config::{list_profiles_in_dir, load_profile_in_dir, save_profile_in_dir};
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let dir = temp_dir.path();
+
+    assert_eq!(list_profiles_in_dir(dir), vec!["default"]);
+
+    let work = AppConfig {
+        research_mode: Some(true),
+        selected_model: Some("gpt-oss-120b (Cerebras)".to_string()),
+        ..AppConfig::default()
+    };
+    save_profile_in_dir(dir, "work", &work).unwrap();
+    save_profile_in_dir(dir, "personal", &AppConfig::default()).unwrap();
+
+    assert_eq!(list_profiles_in_dir(dir), vec!["default", "personal", "work"]);
+    assert!(dir.join("config-work.json").exists());
+
+    let loaded = load_profile_in_dir(dir, "work").unwrap();
+    assert_eq!(loaded.research_mode, Some(true));
+}
+
+#[test]
+fn test_profile_switching_routes_load_and_save() {
+    use crate::config::{
+        get_active_profile_in_dir, load_config_in_dir, save_config_in_dir, save_profile_in_dir,
+        switch_profile_in_dir,
+    };
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let dir = temp_dir.path();
+
+    save_config_in_dir(dir, &AppConfig::default()).unwrap();
+    let personal = AppConfig {
+        incognito_mode: Some(true),
+        ..AppConfig::default()
+    };
+    save_profile_in_dir(dir, "personal", &personal).unwrap();
+    assert_eq!(get_active_profile_in_dir(dir), "default");
+
+    switch_profile_in_dir(dir, "personal").unwrap();
+    assert_eq!(get_active_profile_in_dir(dir), "personal");
+    assert_eq!(load_config_in_dir(dir).unwrap().incognito_mode, Some(true));
+
+    // save_config writes to the active profile, leaving config.toml untouched
+    let updated = AppConfig {
+        incognito_mode: Some(true),
+        research_mode: Some(true),
+        ..AppConfig::default()
+    };
+    save_config_in_dir(dir, &updated).unwrap();
+    switch_profile_in_dir(dir, "default").unwrap();
+    assert_eq!(load_config_in_dir(dir).unwrap().research_mode, Some(false));
+}
+
+#[test]
+fn test_deleting_active_profile_falls_back_to_default() {
+    use crate::config::{
+        delete_profile_in_dir, get_active_profile_in_dir, load_config_in_dir, save_profile_in_dir,
+        switch_profile_in_dir,
+    };
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let dir = temp_dir.path();
+
+    let work = AppConfig {
+        research_mode: Some(true),
+        ..AppConfig::default()
+    };
+    save_profile_in_dir(dir, "work", &work).unwrap();
+    switch_profile_in_dir(dir, "work").unwrap();
+
+    delete_profile_in_dir(dir, "work").unwrap();
+    assert_eq!(get_active_profile_in_dir(dir), "default");
+    assert_eq!(load_config_in_dir(dir).unwrap().research_mode, Some(false));
+    assert!(delete_profile_in_dir(dir, "default").is_err());
+}
+
+#[test]
+fn test_invalid_profile_names_rejected() {
+    use crate::config::save_profile_in_dir;
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    assert!(save_profile_in_dir(temp_dir.path(), "../evil", &AppConfig::default()).is_err());
+    assert!(save_profile_in_dir(temp_dir.path(), "", &AppConfig::default()).is_err());
+}
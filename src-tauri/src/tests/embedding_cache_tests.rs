@@ -0,0 +1,73 @@
+/**
+ * Embedding Cache Tests
+ *
+ * Tests for content-hash keyed embedding caching and LRU eviction.
+ */
+
+#[cfg(test)]
+mod tests {
+    use crate::embedding_cache::{get_or_generate_in, make_embedding_key, EmbeddingCache};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    #[tokio::test]
+    async fn test_second_identical_call_uses_cache() {
+        let cache = Mutex::new(EmbeddingCache::default());
+        let requests = AtomicUsize::new(0);
+
+        let fetch = || async {
+            requests.fetch_add(1, Ordering::SeqCst);
+            Ok(vec![0.1, 0.2, 0.3])
+        };
+
+        let first = get_or_generate_in(&cache, None, "Topic: SHARD", 768, fetch)
+            .await
+            .unwrap();
+        let second = get_or_generate_in(&cache, None, "Topic: SHARD", 768, fetch)
+            .await
+            .unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(requests.load(Ordering::SeqCst), 1, "Second call should not hit the API");
+    }
+
+    #[tokio::test]
+    async fn test_generate_error_is_not_cached() {
+        let cache = Mutex::new(EmbeddingCache::default());
+
+        let result = get_or_generate_in(&cache, None, "text", 768, || async {
+            Err("Embedding API error: quota".to_string())
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert!(cache.lock().unwrap().entries.is_empty());
+    }
+
+    #[test]
+    fn test_key_includes_dimensionality() {
+        assert_eq!(make_embedding_key("hello", 768), make_embedding_key("hello", 768));
+        assert_ne!(make_embedding_key("hello", 768), make_embedding_key("hello", 1536));
+        assert_ne!(make_embedding_key("hello", 768), make_embedding_key("world", 768));
+    }
+
+    #[test]
+    fn test_eviction_drops_least_recently_used() {
+        let pause = || std::thread::sleep(std::time::Duration::from_millis(5));
+        let mut cache = EmbeddingCache::default();
+        cache.insert("old", 768, vec![1.0]);
+        pause();
+        cache.insert("newer", 768, vec![2.0]);
+        pause();
+        cache.insert("newest", 768, vec![3.0]);
+        pause();
+
+        // Touch "old" so it becomes the most recently used
+        assert!(cache.get("old", 768).is_some());
+
+        cache.evict_to(2);
+        assert_eq!(cache.entries.len(), 2);
+        assert!(cache.get("old", 768).is_some());
+        assert!(cache.get("newer", 768).is_none());
+    }
+}
@@ -0,0 +1,116 @@
+/**
+ * Error Tests
+ *
+ * Tests mapping representative provider error bodies to ShardError variants.
+ */
+
+#[cfg(test)]
+mod tests {
+    use crate::error::{backoff_delay, is_quota_error, is_retryable_status, ShardError};
+
+    #[test]
+    fn test_gemini_resource_exhausted_is_quota() {
+        let body = r#"{"error": {"code": 429, "message": "Resource has been exhausted (e.g. check quota).", "status": "RESOURCE_EXHAUSTED"}}"#;
+        let err = ShardError::from_api_response("Gemini", 429, body);
+        assert!(matches!(err, ShardError::Quota { ref provider, .. } if provider == "Gemini"));
+    }
+
+    #[test]
+    fn test_cerebras_token_quota_is_quota_even_without_429() {
+        let body = r#"{"message":"Tokens per day limit exceeded","type":"too_many_tokens_error","code":"token_quota_exceeded"}"#;
+        assert!(is_quota_error(400, body));
+        let err = ShardError::from_api_response("Cerebras", 400, body);
+        assert!(matches!(err, ShardError::Quota { .. }));
+    }
+
+    #[test]
+    fn test_groq_rate_limit_is_quota() {
+        let body = r#"{"error":{"message":"Rate limit reached for model","type":"tokens","code":"rate_limit_exceeded"}}"#;
+        let err = ShardError::from_api_response("Groq", 429, body);
+        assert!(matches!(err, ShardError::Quota { .. }));
+    }
+
+    #[test]
+    fn test_message_mentioning_quota_is_not_quota() {
+        let body = r#"{"error":{"message":"Invalid request: max_tokens exceeds the model's output quota","type":"invalid_request_error","code":"invalid_value"}}"#;
+        assert!(!is_quota_error(400, body));
+        assert!(matches!(
+            ShardError::from_api_response("Groq", 400, body),
+            ShardError::ApiError { status: 400, .. }
+        ));
+    }
+
+    #[test]
+    fn test_invalid_key_maps_to_auth() {
+        let gemini = r#"{"error": {"code": 400, "message": "API key not valid. Please pass a valid API key.", "status": "INVALID_ARGUMENT", "details": [{"reason": "API_KEY_INVALID"}]}}"#;
+        assert!(matches!(
+            ShardError::from_api_response("Gemini", 400, gemini),
+            ShardError::Auth { .. }
+        ));
+
+        let openrouter = r#"{"error":{"message":"No auth credentials found","code":401}}"#;
+        assert!(matches!(
+            ShardError::from_api_response("OpenRouter", 401, openrouter),
+            ShardError::Auth { .. }
+        ));
+    }
+
+    #[test]
+    fn test_other_failures_map_to_api_error() {
+        let body = r#"{"error":{"message":"The model `foo` does not exist","code":404}}"#;
+        let err = ShardError::from_api_response("OpenRouter", 404, body);
+        assert_eq!(
+            err,
+            ShardError::ApiError {
+                provider: "OpenRouter".to_string(),
+                status: 404,
+                body: body.to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_serializes_as_tagged_object() {
+        let err = ShardError::missing_api_key("Groq");
+        let json = serde_json::to_value(&err).unwrap();
+        assert_eq!(json["kind"], "missing_api_key");
+        assert_eq!(json["provider"], "Groq");
+        assert_eq!(err.to_string(), "No Groq API key configured");
+    }
+
+    #[test]
+    fn test_string_errors_convert_to_other() {
+        let err: ShardError = "Failed to read config file".to_string().into();
+        assert!(matches!(err, ShardError::Other { .. }));
+        let back: String = err.into();
+        assert_eq!(back, "Failed to read config file");
+    }
+
+    #[test]
+    fn test_retryable_status_classification() {
+        for status in [429, 500, 502, 503] {
+            assert!(is_retryable_status(status), "{} should retry", status);
+        }
+        // Auth, bad request and not-found errors fail fast
+        for status in [400, 401, 403, 404, 504] {
+            assert!(!is_retryable_status(status), "{} should not retry", status);
+        }
+    }
+
+    #[test]
+    fn test_backoff_schedule() {
+        use std::time::Duration;
+
+        // No jitter: 500ms doubling, capped at 8s
+        assert_eq!(backoff_delay(0, 0.0), Duration::from_millis(500));
+        assert_eq!(backoff_delay(1, 0.0), Duration::from_millis(1000));
+        assert_eq!(backoff_delay(2, 0.0), Duration::from_millis(2000));
+        assert_eq!(backoff_delay(5, 0.0), Duration::from_millis(8000));
+        assert_eq!(backoff_delay(40, 0.0), Duration::from_millis(8000));
+
+        // Jitter adds at most 25% on top
+        assert_eq!(backoff_delay(1, 0.5), Duration::from_millis(1125));
+        assert!(backoff_delay(1, 0.999) < Duration::from_millis(1250));
+        assert_eq!(backoff_delay(1, 7.0), Duration::from_millis(1250));
+    }
+}
@@ -78,4 +78,281 @@ mod tests {
             panic!("Expected FunctionResponse variant");
         }
     }
+
+    #[test]
+    fn test_supports_thinking_detection() {
+        use crate::agent::supports_thinking;
+
+        assert!(supports_thinking("gemini-2.5-flash"));
+        assert!(supports_thinking("gemini-3-pro-preview"));
+        assert!(supports_thinking("gemini-2.0-flash-thinking-exp"));
+        assert!(!supports_thinking("gemini-2.0-flash"));
+        assert!(!supports_thinking("gemini-1.5-pro"));
+    }
+
+    #[test]
+    fn test_thinking_config_override_precedence() {
+        use crate::agent::build_thinking_config;
+        use crate::config::AppConfig;
+
+        // Defaults match the previous hardcoded values
+        let defaults = build_thinking_config("gemini-2.5-flash", &AppConfig::default()).unwrap();
+        assert!(defaults.include_thoughts);
+        assert_eq!(defaults.thinking_budget, Some(1024));
+
+        // Config overrides win
+        let config = AppConfig {
+            thinking_budget: Some(8192),
+            include_thoughts: Some(false),
+            ..AppConfig::default()
+        };
+        let custom = build_thinking_config("gemini-2.5-pro", &config).unwrap();
+        assert!(!custom.include_thoughts);
+        assert_eq!(custom.thinking_budget, Some(8192));
+
+        // Budget 0 disables thinking even if thoughts are requested
+        let config = AppConfig {
+            thinking_budget: Some(0),
+            include_thoughts: Some(true),
+            ..AppConfig::default()
+        };
+        let disabled = build_thinking_config("gemini-2.5-flash", &config).unwrap();
+        assert!(!disabled.include_thoughts);
+        assert_eq!(disabled.thinking_budget, Some(0));
+
+        // Non-thinking models never get a thinkingConfig
+        assert!(build_thinking_config("gemini-2.0-flash", &config).is_none());
+    }
+
+    fn user_message_with_image(image: crate::agent::ImageAttachment) -> crate::agent::ChatMessage {
+        crate::agent::ChatMessage {
+            role: "user".to_string(),
+            content: Some("What is in this image?".to_string()),
+            tool_calls: None,
+            tool_call_id: None,
+            images: Some(vec![image]),
+            reasoning: None,
+            citations: None,
+        }
+    }
+
+    #[test]
+    fn test_base64_only_image_becomes_inline_data() {
+        use crate::agent::{construct_gemini_messages, ImageAttachment};
+
+        let msg = user_message_with_image(ImageAttachment {
+            base64: "iVBORw0KGgo=".to_string(),
+            mime_type: "image/png".to_string(),
+            file_uri: None,
+        });
+        let contents = construct_gemini_messages(&[msg]);
+        let json = serde_json::to_value(&contents[0].parts).unwrap();
+
+        assert_eq!(json[0]["text"], "What is in this image?");
+        assert_eq!(json[1]["inlineData"]["mimeType"], "image/png");
+        assert_eq!(json[1]["inlineData"]["data"], "iVBORw0KGgo=");
+        assert!(json[1].get("fileData").is_none());
+    }
+
+    #[test]
+    fn test_uploaded_image_prefers_file_data_and_oversized_inline_is_dropped() {
+        use crate::agent::{construct_gemini_messages, ImageAttachment, MAX_INLINE_IMAGE_BYTES};
+
+        let uploaded = user_message_with_image(ImageAttachment {
+            base64: "iVBORw0KGgo=".to_string(),
+            mime_type: "image/png".to_string(),
+            file_uri: Some("https://generativelanguage.googleapis.com/v1beta/files/abc".to_string()),
+        });
+        let json = serde_json::to_value(&construct_gemini_messages(&[uploaded])[0].parts).unwrap();
+        assert!(json[1].get("fileData").is_some());
+        assert!(json[1].get("inlineData").is_none());
+
+        // ~5MB decoded: too big to inline, so only the text part remains
+        let oversized = user_message_with_image(ImageAttachment {
+            base64: "A".repeat((MAX_INLINE_IMAGE_BYTES + 1024 * 1024) / 3 * 4),
+            mime_type: "image/jpeg".to_string(),
+            file_uri: None,
+        });
+        let contents = construct_gemini_messages(&[oversized]);
+        assert_eq!(contents[0].parts.len(), 1);
+    }
+
+    fn finish_of(chunk: serde_json::Value) -> Option<crate::agent::FinishReason> {
+        use crate::agent::{chunk_finish_reason, GenerateContentResponse};
+
+        let response: GenerateContentResponse =
+            serde_json::from_value(chunk).expect("Failed to deserialize chunk");
+        chunk_finish_reason(&response)
+    }
+
+    #[test]
+    fn test_finish_reason_stop_and_mid_stream() {
+        use crate::agent::FinishReason;
+
+        // Mid-stream chunks carry no finish reason
+        let chunk = json!({"candidates": [{"content": {"role": "model", "parts": [{"text": "Hel"}]}}]});
+        assert_eq!(finish_of(chunk), None);
+
+        let chunk = json!({"candidates": [{
+            "content": {"role": "model", "parts": [{"text": "lo"}]},
+            "finishReason": "STOP"
+        }]});
+        let reason = finish_of(chunk).unwrap();
+        assert_eq!(reason, FinishReason::Stop);
+        assert!(reason.abnormal_event().is_none());
+    }
+
+    #[test]
+    fn test_finish_reason_max_tokens_is_truncated() {
+        use crate::agent::FinishReason;
+
+        let chunk = json!({"candidates": [{
+            "content": {"role": "model", "parts": [{"text": "The answer is"}]},
+            "finishReason": "MAX_TOKENS"
+        }]});
+        let reason = finish_of(chunk).unwrap();
+        assert_eq!(reason, FinishReason::MaxTokens);
+        let (event, message) = reason.abnormal_event().unwrap();
+        assert_eq!(event, "agent-truncated");
+        assert!(message.contains("token limit"));
+    }
+
+    #[test]
+    fn test_finish_reason_safety_without_content() {
+        use crate::agent::FinishReason;
+
+        // Blocked candidates have no content at all
+        let chunk = json!({"candidates": [{
+            "finishReason": "SAFETY",
+            "safetyRatings": [{"category": "HARM_CATEGORY_DANGEROUS_CONTENT", "probability": "HIGH"}]
+        }]});
+        let reason = finish_of(chunk).unwrap();
+        assert_eq!(reason, FinishReason::Blocked("SAFETY".to_string()));
+        let (event, message) = reason.abnormal_event().unwrap();
+        assert_eq!(event, "agent-error");
+        assert!(message.contains("SAFETY"));
+
+        // A blocked prompt returns no candidates, only promptFeedback
+        let chunk = json!({"promptFeedback": {"blockReason": "PROHIBITED_CONTENT"}});
+        assert_eq!(
+            finish_of(chunk),
+            Some(FinishReason::Blocked("PROHIBITED_CONTENT".to_string()))
+        );
+
+        // Unknown reasons are still abnormal
+        let chunk = json!({"candidates": [{"finishReason": "MALFORMED_FUNCTION_CALL"}]});
+        let reason = finish_of(chunk).unwrap();
+        assert_eq!(reason, FinishReason::Other("MALFORMED_FUNCTION_CALL".to_string()));
+        assert_eq!(reason.abnormal_event().unwrap().0, "agent-error");
+    }
+
+    fn stream_texts(responses: Vec<crate::agent::GenerateContentResponse>) -> Vec<String> {
+        responses
+            .into_iter()
+            .flat_map(|r| r.candidates.into_iter().flatten())
+            .flat_map(|c| c.content.parts)
+            .filter_map(|part| match part {
+                GeminiPart::Text { text } => Some(text),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_stream_reader_survives_adversarial_splits() {
+        use crate::agent::GeminiStreamReader;
+
+        let texts = [
+            r#"braces } in { strings"#,
+            r#"quote \" and backslash \\"#,
+            r#"ends with backslash \\\\"#,
+            "multibyte é and \\u00e9 escape",
+        ];
+        let objects: Vec<String> = texts
+            .iter()
+            .map(|t| format!(r#"{{"candidates": [{{"content": {{"parts": [{{"text": "{}"}}]}}}}]}}"#, t))
+            .collect();
+        let body = format!("[{}\n]", objects.join(",\r\n"));
+        let bytes = body.as_bytes();
+        let expected: Vec<String> = texts
+            .iter()
+            .map(|t| serde_json::from_str::<String>(&format!("\"{}\"", t)).unwrap())
+            .collect();
+
+        // Every two-chunk split point, including mid-string, mid-escape and mid-codepoint
+        for split in 0..=bytes.len() {
+            let mut reader = GeminiStreamReader::new();
+            let mut responses = reader.push(&bytes[..split]);
+            responses.extend(reader.push(&bytes[split..]));
+            assert_eq!(stream_texts(responses), expected, "split at {}", split);
+            assert_eq!(reader.finish(), 0, "split at {}", split);
+        }
+
+        // Byte by byte
+        let mut reader = GeminiStreamReader::new();
+        let responses: Vec<_> = bytes.iter().flat_map(|b| reader.push(std::slice::from_ref(b))).collect();
+        assert_eq!(stream_texts(responses), expected);
+        assert_eq!(reader.finish(), 0);
+    }
+
+    #[test]
+    fn test_stream_reader_resyncs_after_malformed_object() {
+        use crate::agent::GeminiStreamReader;
+
+        let mut reader = GeminiStreamReader::new();
+        let first = reader.push(br#"[{"candidates": [{"content": {"parts": [{"text": "one"}]}}]}, {"candidates": oops},"#);
+        let second = reader.push(br#" {"candidates": [{"content": {"parts": [{"text": "two"}]}}]}, {"candidates": ["#);
+        assert_eq!(stream_texts(first), vec!["one"]);
+        assert_eq!(stream_texts(second), vec!["two"]);
+        // The malformed object and the unterminated tail are both reported
+        assert!(reader.finish() > r#"{"candidates": oops}"#.len());
+    }
+
+    #[test]
+    fn test_find_orphaned_gemini_files() {
+        use crate::agent::ImageAttachment;
+        use crate::gemini_files::{file_id, find_orphaned_files, referenced_file_ids, GeminiFileInfo};
+
+        let file = |id: &str| GeminiFileInfo {
+            name: format!("files/{}", id),
+            uri: format!("https://generativelanguage.googleapis.com/v1beta/files/{}", id),
+            display_name: format!("image_{}.png", uuid::Uuid::new_v4()),
+            ..Default::default()
+        };
+        let image = |uri: Option<&str>| ImageAttachment {
+            base64: String::new(),
+            mime_type: "image/png".to_string(),
+            file_uri: uri.map(str::to_string),
+        };
+        let mut message = user_message_with_image(image(None));
+        message.images = Some(vec![
+            image(Some("https://generativelanguage.googleapis.com/v1beta/files/kept1")),
+            image(Some("https://generativelanguage.googleapis.com/v1beta/files/kept2")),
+            image(None), // Sent inline, never uploaded
+        ]);
+        let history = vec![message];
+
+        let referenced = referenced_file_ids(&history);
+        assert_eq!(referenced.len(), 2);
+        assert_eq!(file_id("files/kept1"), "kept1");
+
+        let files = vec![file("kept1"), file("orphan1"), file("kept2"), file("orphan2")];
+        let orphans: Vec<&str> = find_orphaned_files(&files, &referenced)
+            .into_iter()
+            .map(|f| f.name.as_str())
+            .collect();
+        assert_eq!(orphans, vec!["files/orphan1", "files/orphan2"]);
+
+        // Nothing referenced: every file is an orphan
+        assert_eq!(find_orphaned_files(&files, &Default::default()).len(), 4);
+
+        // Files uploaded by other tools sharing the key are never touched
+        let foreign = |display_name: &str| GeminiFileInfo { display_name: display_name.to_string(), ..file("foreign") };
+        let files = vec![foreign("report.pdf"), foreign("image_not-a-uuid.png"), foreign(""), file("orphan3")];
+        let orphans: Vec<&str> = find_orphaned_files(&files, &Default::default())
+            .into_iter()
+            .map(|f| f.name.as_str())
+            .collect();
+        assert_eq!(orphans, vec!["files/orphan3"]);
+    }
 }
@@ -1,6 +1,7 @@
 #[cfg(test)]
 mod tests {
     use crate::interactions::*;
+    use crate::retrieval::RetrievalTuning;
     use chrono::Utc;
 
     #[test]
@@ -10,6 +11,7 @@ mod tests {
             role: "user".to_string(),
             content: "Hello".to_string(),
             embedding: Some(vec![0.1, 0.2, 0.3]),
+            embedding_model: None,
         };
 
         let json = serde_json::to_string(&entry).unwrap();
@@ -20,6 +22,98 @@ mod tests {
         assert_eq!(entry.embedding, deserialized.embedding);
     }
 
+    #[test]
+    fn test_hybrid_fusion_recent_entry_wins_tie() {
+        use crate::retrieval::{HitSource, ScoredHit};
+
+        let now = Utc::now();
+        let old_ts = now - chrono::Duration::days(180);
+        let new_ts = now - chrono::Duration::days(1);
+        let hit = |id: &str, source: HitSource, ts| ScoredHit {
+            doc_id: id.to_string(),
+            score: 1.0,
+            source,
+            ts: Some(ts),
+        };
+
+        // Mirrored ranks across both lists give "old" and "new" identical RRF scores
+        let bm25_hits = vec![
+            hit("old", HitSource::Bm25, old_ts),
+            hit("new", HitSource::Bm25, new_ts),
+            hit("filler_a", HitSource::Bm25, old_ts),
+        ];
+        let dense_hits = vec![
+            hit("new", HitSource::DenseInteraction, new_ts),
+            hit("old", HitSource::DenseInteraction, old_ts),
+            hit("filler_b", HitSource::DenseInteraction, old_ts),
+        ];
+
+        let fused = fuse_interaction_hits(&bm25_hits, &dense_hits, &[], 2, &RetrievalTuning::default());
+
+        assert_eq!(fused.len(), 2);
+        assert_eq!(fused[0].doc_id, "new");
+        assert_eq!(fused[1].doc_id, "old");
+    }
+
+    #[test]
+    fn test_smaller_tau_favors_recent_hits() {
+        use crate::retrieval::{HitSource, ScoredHit};
+
+        let now = Utc::now();
+        let hit = |id: &str, source: HitSource, age_days: i64| ScoredHit {
+            doc_id: id.to_string(),
+            score: 1.0,
+            source,
+            ts: Some(now - chrono::Duration::days(age_days)),
+        };
+
+        // "stale" outranks "recent" in both lists, so only recency can lift "recent"
+        let bm25_hits = vec![
+            hit("stale", HitSource::Bm25, 10),
+            hit("filler_a", HitSource::Bm25, 400),
+            hit("filler_b", HitSource::Bm25, 400),
+            hit("recent", HitSource::Bm25, 1),
+        ];
+        let dense_hits = vec![
+            hit("stale", HitSource::DenseInteraction, 10),
+            hit("filler_b", HitSource::DenseInteraction, 400),
+            hit("filler_a", HitSource::DenseInteraction, 400),
+            hit("recent", HitSource::DenseInteraction, 1),
+        ];
+
+        let patient = RetrievalTuning {
+            temporal_tau_days: 365.0,
+            ..RetrievalTuning::default()
+        };
+        let fused = fuse_interaction_hits(&bm25_hits, &dense_hits, &[], 2, &patient);
+        assert_eq!(fused[0].doc_id, "stale");
+
+        let aggressive = RetrievalTuning {
+            temporal_tau_days: 3.0,
+            ..RetrievalTuning::default()
+        };
+        let fused = fuse_interaction_hits(&bm25_hits, &dense_hits, &[], 2, &aggressive);
+        assert_eq!(fused[0].doc_id, "recent");
+    }
+
+    #[test]
+    fn test_retrieval_tuning_from_config() {
+        use crate::config::AppConfig;
+
+        assert_eq!(RetrievalTuning::from_config(&AppConfig::default()), RetrievalTuning::default());
+
+        let config = AppConfig {
+            rrf_k: Some(20.0),
+            min_dense_hits: Some(1),
+            temporal_tau_days: Some(-5.0),
+            ..AppConfig::default()
+        };
+        let tuning = RetrievalTuning::from_config(&config);
+        assert_eq!(tuning.rrf_k, 20.0);
+        assert_eq!(tuning.min_dense_hits, 1);
+        assert_eq!(tuning.temporal_tau_days, RetrievalTuning::default().temporal_tau_days);
+    }
+
     #[test]
     fn test_cosine_similarity_logic() {
         // We can't access the private function directly, but we can copy the logic to verify it
@@ -42,4 +136,593 @@ mod tests {
         let c = vec![0.0, 1.0, 0.0];
         assert!((cosine_similarity(&a, &c) - 0.0).abs() < 1e-5);
     }
+
+    #[test]
+    fn test_failed_interaction_write_is_retried_on_flush() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let dir = temp.path().join("interactions");
+
+        let mut queue = InteractionRetryQueue::new();
+        queue.push(PendingInteraction::new("user", "Queued message", Some(vec![0.5, 0.5])));
+
+        // Simulated transient disk failure keeps the entry queued
+        let flushed = queue.flush_with(|_| Err("disk full".to_string()));
+        assert_eq!(flushed, 0);
+        assert_eq!(queue.len(), 1);
+
+        // Next successful flush lands the entry (with its embedding) in the JSONL
+        assert_eq!(queue.flush_to_dir(&dir), 1);
+        assert!(queue.is_empty());
+
+        let jsonl = std::fs::read_dir(&dir)
+            .unwrap()
+            .flatten()
+            .map(|e| e.path())
+            .find(|p| p.extension().map(|e| e == "jsonl").unwrap_or(false))
+            .expect("JSONL file should exist");
+        let content = std::fs::read_to_string(jsonl).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 1);
+        let entry: InteractionEntry = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(entry.content, "Queued message");
+        assert_eq!(entry.embedding, Some(vec![0.5, 0.5]));
+
+        let index = crate::retrieval::load_bm25_index_from(
+            &dir.join(crate::retrieval::BM25_INDEX_FILENAME),
+        )
+        .unwrap();
+        assert!(index.doc_lengths.contains_key(&entry.ts.to_rfc3339()));
+    }
+
+    #[test]
+    fn test_retry_after_jsonl_written_does_not_duplicate_line() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let dir = temp.path().to_path_buf();
+
+        let mut pending = PendingInteraction::new("model", "Partial write", None);
+        write_interaction_in_dir(&dir, &mut pending).unwrap();
+        assert!(pending.jsonl_written);
+
+        // Retrying an entry whose JSONL line already landed only redoes the BM25 step
+        write_interaction_in_dir(&dir, &mut pending).unwrap();
+        let path = dir.join(format!("interactions-{}.jsonl", pending.entry.ts.format("%Y-%m-%d")));
+        assert_eq!(std::fs::read_to_string(path).unwrap().lines().count(), 1);
+    }
+
+    #[test]
+    fn test_repair_drops_truncated_final_line() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let path = temp.path().join("interactions-2025-01-01.jsonl");
+
+        let good_line = serde_json::to_string(&entry("Complete line")).unwrap();
+        let truncated = &good_line[..good_line.len() / 2];
+        std::fs::write(&path, format!("{}\n{}\n{}", good_line, good_line, truncated)).unwrap();
+
+        assert_eq!(repair_interaction_logs_in_dir(temp.path()).unwrap(), 1);
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(content.lines().count(), 2);
+        assert!(content
+            .lines()
+            .all(|l| serde_json::from_str::<InteractionEntry>(l).is_ok()));
+
+        // Already-clean files are left alone
+        assert_eq!(repair_interaction_logs_in_dir(temp.path()).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_repair_keeps_corrupt_mid_file_lines() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let path = temp.path().join("interactions-2025-01-01.jsonl");
+
+        let good_line = serde_json::to_string(&entry("Complete line")).unwrap();
+        let original = format!("{}\n{{\"future_schema\": true}}\n{}\n", good_line, good_line);
+        std::fs::write(&path, &original).unwrap();
+
+        assert_eq!(repair_interaction_logs_in_dir(temp.path()).unwrap(), 0);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), original);
+
+        // A valid but unterminated final line is still an interrupted write
+        std::fs::write(&path, format!("{}{}", original, good_line)).unwrap();
+        assert_eq!(repair_interaction_logs_in_dir(temp.path()).unwrap(), 1);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), original);
+    }
+
+    #[test]
+    fn test_write_after_partial_line_starts_fresh_line() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let mut pending = PendingInteraction::new("user", "After crash", None);
+        let path = temp
+            .path()
+            .join(format!("interactions-{}.jsonl", pending.entry.ts.format("%Y-%m-%d")));
+        std::fs::write(&path, "{\"ts\":\"2025-01-01T00:").unwrap();
+
+        write_interaction_in_dir(temp.path(), &mut pending).unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let last = content.lines().last().unwrap();
+        let entry: InteractionEntry = serde_json::from_str(last).unwrap();
+        assert_eq!(entry.content, "After crash");
+    }
+
+    fn entry(content: &str) -> InteractionEntry {
+        InteractionEntry {
+            ts: Utc::now(),
+            role: "user".to_string(),
+            content: content.to_string(),
+            embedding: None,
+            embedding_model: None,
+        }
+    }
+
+    /// Unit vector along `axis` in the configured embedding space
+    fn unit_embedding(axis: usize) -> Vec<f32> {
+        let mut embedding = vec![0.0; embedding_settings().dims as usize];
+        embedding[axis] = 1.0;
+        embedding
+    }
+
+    /// Log a user message to `dir` and return the entry as written
+    fn write_user_entry(dir: &std::path::Path, content: &str, embedding: Option<Vec<f32>>) -> InteractionEntry {
+        let mut pending = PendingInteraction::new("user", content, embedding);
+        write_interaction_in_dir(dir, &mut pending).unwrap();
+        pending.entry
+    }
+
+    #[test]
+    fn test_truncate_at_sentence_boundary() {
+        let text = "Rust has ownership. Borrowing is checked at compile time. Lifetimes annotate references.";
+
+        // Short text is untouched
+        assert_eq!(truncate_at_sentence(text, 500), text);
+
+        // Cut lands after the last full sentence that fits
+        let cut = truncate_at_sentence(text, 70);
+        assert_eq!(cut, "Rust has ownership. Borrowing is checked at compile time.…");
+        assert!(cut.chars().count() <= 70);
+
+        // No sentence boundary in the back half: fall back to a word boundary
+        let cut = truncate_at_sentence("Short. averyveryverylongword another verylongword here", 40);
+        assert_eq!(cut, "Short. averyveryverylongword another…");
+    }
+
+    #[test]
+    fn test_build_rag_context_enforces_budget() {
+        let long = "This sentence is about retrieval budgets. ".repeat(50);
+        let entries = vec![
+            entry(&long),
+            entry("Second hit: how do I configure quiet hours?"),
+            entry("Third hit mentions embeddings and HNSW indexes."),
+        ];
+
+        let context = build_rag_context(&entries, 600).unwrap().text;
+        assert!(context.chars().count() <= 600, "len {}", context.chars().count());
+        assert!(context.starts_with("\n\nRelevant Past Interactions:\n"));
+        // The long top hit is capped, leaving room for lower-ranked hits
+        assert!(context.contains("budgets.…"));
+        assert!(context.contains("quiet hours"));
+
+        // A budget too small for any entry yields no block at all
+        assert!(build_rag_context(&entries, 50).is_none());
+        assert!(build_rag_context(&[], 4000).is_none());
+    }
+
+    #[test]
+    fn test_build_rag_context_dedupes_near_identical() {
+        let entries = vec![
+            entry("What is the weather in Paris today?"),
+            entry("what is the weather in Paris today"),
+            entry("Explain the borrow checker"),
+        ];
+
+        let block = build_rag_context(&entries, 4000).unwrap();
+        assert_eq!(block.text.matches("weather in Paris").count(), 1);
+        assert!(block.text.contains("borrow checker"));
+        assert_eq!(block.kept, vec![0, 2]);
+    }
+
+    #[test]
+    fn test_compact_merges_old_dailies_into_monthly_archives() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let dir = temp.path();
+        let write = |name: &str, body: &str| std::fs::write(dir.join(name), body).unwrap();
+
+        write("interactions-2020-01-03.jsonl", "{\"n\":3}\n");
+        write("interactions-2020-01-01.jsonl", "{\"n\":1}\n{\"n\":2}"); // missing trailing newline
+        write("interactions-2020-02-10.jsonl", "{\"n\":4}\n");
+        let today = format!("interactions-{}.jsonl", Utc::now().format("%Y-%m-%d"));
+        write(&today, "{\"n\":5}\n");
+
+        assert_eq!(compact_interaction_logs_in_dir(dir, 30).unwrap(), 3);
+
+        let january = std::fs::read_to_string(dir.join("interactions-2020-01.jsonl")).unwrap();
+        assert_eq!(january, "{\"n\":1}\n{\"n\":2}\n{\"n\":3}\n");
+        assert!(dir.join("interactions-2020-02.jsonl").exists());
+        assert!(!dir.join("interactions-2020-01-01.jsonl").exists());
+        assert!(!dir.join("interactions-2020-01-03.jsonl").exists());
+        // Recent dailies are left alone
+        assert!(dir.join(&today).exists());
+
+        // A late-arriving daily is appended after the existing archive contents
+        write("interactions-2020-01-20.jsonl", "{\"n\":6}\n");
+        assert_eq!(compact_interaction_logs_in_dir(dir, 30).unwrap(), 1);
+        let january = std::fs::read_to_string(dir.join("interactions-2020-01.jsonl")).unwrap();
+        assert!(january.ends_with("{\"n\":3}\n{\"n\":6}\n"));
+    }
+
+    #[test]
+    fn test_parse_log_period_handles_daily_and_monthly_files() {
+        assert_eq!(
+            parse_log_period("interactions-2024-03-15"),
+            Some(LogPeriod::Day("2024-03-15".to_string()))
+        );
+        assert_eq!(
+            parse_log_period("interactions-2024-03"),
+            Some(LogPeriod::Month("2024-03".to_string()))
+        );
+        assert_eq!(parse_log_period("interactions-2024-13"), None);
+        assert_eq!(parse_log_period("interactions-notes"), None);
+        assert_eq!(parse_log_period("dense_index_pending"), None);
+
+        // A monthly archive reaches any cutoff inside its month, but not a later month
+        let march = LogPeriod::Month("2024-03".to_string());
+        assert!(march.reaches("2024-03-31"));
+        assert!(march.reaches("2024-02-10"));
+        assert!(march.ends_before("2024-04-01"));
+
+        let day = LogPeriod::Day("2024-03-15".to_string());
+        assert!(day.reaches("2024-03-15"));
+        assert!(day.ends_before("2024-03-16"));
+    }
+
+    #[test]
+    fn test_deleted_interaction_disappears_from_hybrid_search() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let dir = temp.path().to_path_buf();
+        let secret_emb = unit_embedding(0);
+        let secret = write_user_entry(&dir, "My bank PIN is hidden under the lamp", Some(secret_emb.clone()));
+        write_user_entry(&dir, "The lamp in the hallway flickers", Some(unit_embedding(1)));
+        crate::vector_index::rebuild_dense_index_in_dir(&dir).unwrap();
+
+        let search = || {
+            hybrid_search_interactions_in_dir(
+                &dir,
+                "bank PIN lamp",
+                &secret_emb,
+                &[],
+                |_, _| None,
+                5,
+                &RetrievalTuning::default(),
+            )
+            .unwrap()
+        };
+        assert!(search().iter().any(|e| e.content.contains("bank PIN")));
+
+        // Delete using the timestamp as it appears in the JSONL
+        let ts = serde_json::to_value(secret.ts).unwrap();
+        assert_eq!(delete_interaction_in_dir(&dir, ts.as_str().unwrap()).unwrap(), 1);
+
+        let results = search();
+        assert!(!results.iter().any(|e| e.content.contains("bank PIN")));
+        assert!(results.iter().any(|e| e.content.contains("hallway")));
+
+        // Gone from both indexes, not just filtered at lookup time
+        let doc_id = secret.ts.to_rfc3339();
+        let bm25 = crate::retrieval::load_bm25_index_from(&dir.join(crate::retrieval::BM25_INDEX_FILENAME)).unwrap();
+        assert!(!bm25.doc_lengths.contains_key(&doc_id));
+        let dense = crate::vector_index::load_dense_index_from(&dir).unwrap();
+        assert!(!dense.contains(&doc_id));
+
+        // Partial timestamps are rejected rather than substring-matched
+        assert!(delete_interaction_in_dir(&dir, "2024").is_err());
+    }
+
+    #[test]
+    fn test_find_duplicate_interactions_collapses_near_identical_embeddings() {
+        let base = Utc::now() - chrono::Duration::days(2);
+        let at = |minutes: i64, role: &str, embedding: Option<Vec<f32>>| InteractionEntry {
+            ts: base + chrono::Duration::minutes(minutes),
+            role: role.to_string(),
+            embedding,
+            ..entry("thanks")
+        };
+        let thanks = vec![1.0, 0.0, 0.0];
+        let thanks_again = vec![0.99, 0.05, 0.0]; // cosine ~0.999
+        let other = vec![0.0, 1.0, 0.0];
+
+        let entries = vec![
+            at(0, "user", Some(thanks.clone())),
+            at(5, "user", Some(thanks_again.clone())),
+            at(10, "user", Some(thanks.clone())),
+            at(15, "user", Some(other)),
+            at(20, "model", Some(thanks.clone())),       // Different role
+            at(25, "user", None),                         // No embedding: never merged
+            at(60 * 30, "user", Some(thanks_again)),     // Outside the window
+        ];
+        let window = chrono::Duration::hours(DUPLICATE_WINDOW_HOURS);
+        let duplicates = find_duplicate_interactions(&entries, DUPLICATE_SIMILARITY, window);
+        assert_eq!(duplicates, vec![entries[1].ts, entries[2].ts]);
+
+        // Nothing to compare without embeddings
+        let bare: Vec<InteractionEntry> = (0..3).map(|m| at(m, "user", None)).collect();
+        assert!(find_duplicate_interactions(&bare, DUPLICATE_SIMILARITY, window).is_empty());
+    }
+
+    #[test]
+    fn test_merge_duplicate_interactions_removes_from_logs_and_bm25() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let dir = temp.path().to_path_buf();
+
+        let mut written = Vec::new();
+        for (content, axis) in [("thanks", 0), ("thanks!", 0), ("How do lifetimes work?", 1), ("thanks", 0)] {
+            written.push(write_user_entry(&dir, content, Some(unit_embedding(axis))));
+            std::thread::sleep(std::time::Duration::from_millis(2)); // Distinct timestamps
+        }
+
+        assert_eq!(merge_duplicate_interactions_in_dir(&dir).unwrap(), 2);
+        let bm25 = crate::retrieval::load_bm25_index_from(&dir.join(crate::retrieval::BM25_INDEX_FILENAME)).unwrap();
+        assert_eq!(bm25.doc_count, 2);
+        assert!(bm25.doc_lengths.contains_key(&written[0].ts.to_rfc3339()));
+        assert!(!bm25.doc_lengths.contains_key(&written[1].ts.to_rfc3339()));
+        assert!(!bm25.doc_lengths.contains_key(&written[3].ts.to_rfc3339()));
+        assert_eq!(crate::health::count_interaction_docs(&dir), 2);
+
+        // Already collapsed
+        assert_eq!(merge_duplicate_interactions_in_dir(&dir).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_debug_hybrid_search_reports_per_source_scores() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let dir = temp.path().to_path_buf();
+        let tuning = RetrievalTuning { min_dense_hits: 1, ..RetrievalTuning::default() };
+
+        let query_emb = unit_embedding(0);
+        let both = write_user_entry(&dir, "Sourdough starter needs daily feeding", Some(query_emb.clone()));
+        write_user_entry(&dir, "Tomatoes ripen on the windowsill", Some(unit_embedding(1)));
+        crate::vector_index::rebuild_dense_index_in_dir(&dir).unwrap();
+
+        let hits =
+            debug_hybrid_search_in_dir(&dir, "sourdough starter", &query_emb, &[], |_, _| None, &tuning).unwrap();
+
+        // Nothing is cut to a final limit
+        assert_eq!(hits.len(), 2);
+
+        let top = &hits[0];
+        assert_eq!(top.doc_id, both.ts.to_rfc3339());
+        assert_eq!(top.content, "Sourdough starter needs daily feeding");
+        assert_eq!(top.bm25_rank, Some(1));
+        assert_eq!(top.dense_rank, Some(1));
+        assert_eq!(top.topic_chunk_rank, None);
+        assert!(top.rrf_score > 0.0);
+        assert!(top.temporal_score > 0.99 && top.temporal_score <= 1.0);
+        assert!((top.final_score - top.rrf_score * top.temporal_score).abs() < 1e-6);
+
+        let other = &hits[1];
+        assert_eq!(other.bm25_rank, None);
+        assert_eq!(other.dense_rank, Some(2));
+        assert!(other.rrf_score < top.rrf_score);
+    }
+
+    #[test]
+    fn test_embedding_request_per_provider() {
+        use crate::config::{AppConfig, EmbeddingProvider};
+
+        let gemini = EmbeddingSettings::default();
+        let request = build_embedding_request(&gemini, "hello", "gkey");
+        assert_eq!(
+            request.url,
+            "https://generativelanguage.googleapis.com/v1beta/models/gemini-embedding-001:embedContent?key=gkey"
+        );
+        assert_eq!(request.bearer_token, None);
+        assert_eq!(request.body["content"]["parts"][0]["text"], "hello");
+        assert_eq!(request.body["outputDimensionality"], 768);
+
+        let config = AppConfig {
+            embedding_provider: Some(EmbeddingProvider::OpenAi),
+            embedding_base_url: Some("https://openrouter.ai/api/v1/".to_string()),
+            embedding_dims: Some(512),
+            openrouter_api_key: Some("or-key".to_string()),
+            ..Default::default()
+        };
+        let openai = EmbeddingSettings::from_config(&config);
+        assert_eq!(openai.model, DEFAULT_OPENAI_EMBEDDING_MODEL);
+        assert_eq!(embedding_api_key(&config).map(String::as_str), Some("or-key"));
+
+        // Chat keys only go to their own provider's endpoint
+        let custom = AppConfig {
+            embedding_base_url: Some("https://embeddings.example.com/v1/".to_string()),
+            api_key: Some("sk-openai".to_string()),
+            ..config.clone()
+        };
+        assert_eq!(embedding_api_key(&custom), None);
+        let openai_default = AppConfig {
+            embedding_base_url: None,
+            api_key: Some("sk-openai".to_string()),
+            ..config.clone()
+        };
+        assert_eq!(embedding_api_key(&openai_default).map(String::as_str), Some("sk-openai"));
+        let dedicated = AppConfig { embedding_api_key: Some("emb-key".to_string()), ..custom };
+        assert_eq!(embedding_api_key(&dedicated).map(String::as_str), Some("emb-key"));
+
+        let request = build_embedding_request(&openai, "hello", "or-key");
+        assert_eq!(request.url, "https://openrouter.ai/api/v1/embeddings");
+        assert_eq!(request.bearer_token.as_deref(), Some("or-key"));
+        assert_eq!(
+            request.body,
+            serde_json::json!({"model": "text-embedding-3-small", "input": "hello", "dimensions": 512})
+        );
+
+        // Without configured dims the model's native size is used and `dimensions` isn't sent
+        let native = EmbeddingSettings::from_config(&AppConfig { embedding_dims: None, ..config.clone() });
+        assert_eq!(native.dims, 1536);
+        let request = build_embedding_request(&native, "hello", "or-key");
+        assert_eq!(request.body, serde_json::json!({"model": "text-embedding-3-small", "input": "hello"}));
+        assert_ne!(native.space_id(), EmbeddingSettings::default().space_id());
+
+        let body = r#"{"object":"list","data":[{"object":"embedding","index":0,"embedding":[0.25,-0.5]}]}"#;
+        assert_eq!(parse_embedding_response(EmbeddingProvider::OpenAi, body), Ok(vec![0.25, -0.5]));
+        let body = r#"{"embedding":{"values":[0.1,0.2]}}"#;
+        assert_eq!(parse_embedding_response(EmbeddingProvider::Gemini, body), Ok(vec![0.1, 0.2]));
+        assert!(parse_embedding_response(EmbeddingProvider::OpenAi, r#"{"data":[]}"#).is_err());
+    }
+
+    fn failure(status: Option<u16>) -> EmbeddingFailure {
+        EmbeddingFailure {
+            status,
+            message: format!("Embedding API error: {:?}", status),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_embedding_retries_once_on_transient_status() {
+        let attempts = std::sync::Mutex::new(0);
+        let result = retry_transient_embedding(std::time::Duration::ZERO, || {
+            let attempt = {
+                let mut n = attempts.lock().unwrap();
+                *n += 1;
+                *n
+            };
+            async move {
+                if attempt == 1 {
+                    Err(failure(Some(503)))
+                } else {
+                    Ok(vec![0.5, 0.5])
+                }
+            }
+        })
+        .await;
+
+        assert_eq!(result, Ok(vec![0.5, 0.5]));
+        assert_eq!(*attempts.lock().unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_embedding_retry_gives_up_and_skips_permanent_errors() {
+        // Persistent 429: one retry, then the error surfaces
+        let attempts = std::sync::Mutex::new(0);
+        let result = retry_transient_embedding(std::time::Duration::ZERO, || {
+            *attempts.lock().unwrap() += 1;
+            async { Err(failure(Some(429))) }
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(*attempts.lock().unwrap(), 1 + EMBEDDING_RETRIES);
+
+        // Auth errors and network failures are not retried
+        for status in [Some(400), Some(403), None] {
+            let attempts = std::sync::Mutex::new(0);
+            let result = retry_transient_embedding(std::time::Duration::ZERO, || {
+                *attempts.lock().unwrap() += 1;
+                async move { Err(failure(status)) }
+            })
+            .await;
+            assert!(result.is_err());
+            assert_eq!(*attempts.lock().unwrap(), 1, "{:?}", status);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_backfill_embeds_entries_missing_one() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let log = dir.path().join("interactions-2025-01-01.jsonl");
+        std::fs::write(
+            &log,
+            concat!(
+                r#"{"ts":"2025-01-01T10:00:00Z","role":"user","content":"already embedded","embedding":[1.0,0.0]}"#, "\n",
+                r#"{"ts":"2025-01-01T10:00:05Z","role":"model","content":"logged without a key"}"#, "\n",
+                "not json\n",
+            ),
+        )
+        .unwrap();
+
+        let embedded = std::sync::Mutex::new(Vec::new());
+        let filled = backfill_embeddings_in_dir(dir.path(), |text| {
+            embedded.lock().unwrap().push(text);
+            async { Ok(vec![0.25, 0.75]) }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(filled, 1);
+        assert_eq!(*embedded.lock().unwrap(), vec!["logged without a key"]);
+        let content = std::fs::read_to_string(&log).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[2], "not json");
+        let entries: Vec<InteractionEntry> =
+            lines[..2].iter().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(entries[0].embedding, Some(vec![1.0, 0.0]));
+        assert_eq!(entries[1].embedding, Some(vec![0.25, 0.75]));
+
+        // Nothing left to fill, and a failing embed leaves the logs untouched
+        let filled = backfill_embeddings_in_dir(dir.path(), |_| async { Err("no quota".to_string()) })
+            .await
+            .unwrap();
+        assert_eq!(filled, 0);
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), content);
+    }
+
+    #[tokio::test]
+    async fn test_backfill_keeps_entries_appended_during_embed() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let log = dir.path().join("interactions-2025-01-01.jsonl");
+        std::fs::write(&log, concat!(r#"{"ts":"2025-01-01T10:00:00Z","role":"user","content":"no key yet"}"#, "\n")).unwrap();
+
+        // A turn is logged while the backfill is waiting on the embeddings API
+        let filled = backfill_embeddings_in_dir(dir.path(), |_| {
+            let mut pending = PendingInteraction::new("user", "logged mid-backfill", None);
+            pending.entry.ts = "2025-01-01T11:00:00Z".parse().unwrap();
+            write_interaction_in_dir(dir.path(), &mut pending).unwrap();
+            async { Ok(vec![0.5, 0.5]) }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(filled, 1);
+        let entries: Vec<InteractionEntry> = std::fs::read_to_string(&log)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].embedding, Some(vec![0.5, 0.5]));
+        assert_eq!(entries[1].content, "logged mid-backfill");
+        assert_eq!(entries[1].embedding, None);
+    }
+
+    #[test]
+    fn test_topic_chunks_are_returned_apart_from_interactions() {
+        use crate::retrieval::{HitSource, ScoredHit};
+
+        let temp = tempfile::TempDir::new().unwrap();
+        let dir = temp.path();
+        // No embedding, so dense results are sparse and the BM25 fallback runs
+        let mut pending = PendingInteraction::new("user", "How do Rust lifetimes work?", None);
+        write_interaction_in_dir(dir, &mut pending).unwrap();
+
+        let chunk = ScoredHit {
+            doc_id: "rust#0".to_string(),
+            score: 0.9,
+            source: HitSource::DenseTopicChunk,
+            ts: None,
+        };
+        let dims = embedding_settings().dims as usize;
+        let results = hybrid_search_hits_in_dir(
+            dir,
+            "rust lifetimes",
+            &vec![0.0; dims],
+            &[chunk],
+            |topic, idx| (topic == "rust" && idx == 0).then(|| "Lifetimes bound references.".to_string()),
+            5,
+            &RetrievalTuning::default(),
+        )
+        .unwrap();
+
+        assert_eq!(results.interactions.len(), 1);
+        assert_eq!(results.interactions[0].entry.role, "user");
+        assert_eq!(results.topic_chunks.len(), 1);
+        assert_eq!(results.topic_chunks[0].topic, "rust");
+        assert_eq!(results.topic_chunks[0].content, "Lifetimes bound references.");
+    }
 }
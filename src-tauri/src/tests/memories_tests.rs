@@ -1,7 +1,16 @@
 /**
  * Memory system tests
  */
-use crate::memories::{Memory, MemoryCategory, MemoryStore};
+use crate::config::AppConfig;
+use crate::memories::{
+    add_memory_in_dir, check_index_dims, choose_context, chunk_key, content_similarity, delete_topic_in_dir,
+    export_bundle_from_dir, import_bundle_into_dir, list_topics_in_dir,
+    parse_chunk_key, record_topic_access_in_dir, score_topic_chunks, select_favorites, split_into_chunks,
+    ContextChoice, DecayResult, InsightIndex, InsightMeta, Memory, MemoryCategory, MemoryStore, TopicIndex,
+    MAX_FAVORITE_CONTEXT, MEMORY_BUNDLE_VERSION,
+};
+use crate::retrieval::HitSource;
+use std::collections::HashMap;
 
 #[test]
 fn test_memory_creation() {
@@ -64,6 +73,155 @@ fn test_token_budget_pruning() {
     assert!(store.memories.iter().any(|m| m.importance == 5));
 }
 
+#[test]
+fn test_pinned_memories_survive_pruning() {
+    let mut store = MemoryStore::new();
+
+    // Low-importance pinned memory among many high-importance ones
+    let mut pinned = Memory::new(MemoryCategory::Fact, "User is allergic to peanuts".to_string(), 1);
+    pinned.pinned = true;
+    let pinned_id = pinned.id.clone();
+    store.add(pinned);
+
+    for i in 0..10 {
+        store.add(Memory::new(
+            MemoryCategory::Fact,
+            format!("Critical fact number {} with enough content to take up tokens", i),
+            5,
+        ));
+    }
+
+    store.prune_to_token_budget(50);
+
+    assert!(store.memories.iter().any(|m| m.id == pinned_id));
+    assert!(store.total_tokens() <= 50 || store.memories.iter().all(|m| m.pinned));
+}
+
+#[test]
+fn test_pinned_memories_kept_over_budget() {
+    let mut store = MemoryStore::new();
+    for i in 0..5 {
+        let mut mem = Memory::new(
+            MemoryCategory::Preference,
+            format!("Pinned preference {} that is long enough to blow a tiny budget", i),
+            2,
+        );
+        mem.pinned = true;
+        store.add(mem);
+    }
+    store.add(Memory::new(MemoryCategory::Fact, "Unpinned fact".to_string(), 5));
+
+    // Budget smaller than the pinned set: unpinned goes, all pinned stay
+    store.prune_to_token_budget(10);
+    assert_eq!(store.memories.len(), 5);
+    assert!(store.memories.iter().all(|m| m.pinned));
+}
+
+#[test]
+fn test_toggle_pin_and_legacy_deserialize() {
+    let mut store = MemoryStore::new();
+    let mem = Memory::new(MemoryCategory::Fact, "Some fact".to_string(), 3);
+    let id = mem.id.clone();
+    store.add(mem);
+
+    assert_eq!(store.toggle_pin(&id), Some(true));
+    assert_eq!(store.toggle_pin(&id), Some(false));
+    assert_eq!(store.toggle_pin("missing"), None);
+
+    // Memories saved before the pinned field existed default to unpinned
+    let json = r#"{"id":"a","category":"fact","content":"x","created_at":"2025-01-01T00:00:00Z","importance":3}"#;
+    let legacy: Memory = serde_json::from_str(json).unwrap();
+    assert!(!legacy.pinned);
+}
+
+fn aged_memory(content: &str, importance: u8, age_days: i64, now: chrono::DateTime<chrono::Utc>) -> Memory {
+    let mut mem = Memory::new(MemoryCategory::Fact, content.to_string(), importance);
+    mem.created_at = now - chrono::Duration::days(age_days);
+    mem
+}
+
+#[test]
+fn test_decay_lowers_stale_importance_and_drops_old_low_value() {
+    let now = chrono::Utc::now();
+    let mut store = MemoryStore::new();
+    store.add(aged_memory("fresh", 3, 10, now));
+    store.add(aged_memory("within one interval of grace", 3, 40, now));
+    store.add(aged_memory("one interval stale", 4, 65, now));
+    store.add(aged_memory("two intervals stale", 5, 100, now));
+    store.add(aged_memory("floored but young enough", 2, 150, now));
+    store.add(aged_memory("floored and past age cap", 2, 200, now));
+    let mut pinned = aged_memory("pinned ancient", 1, 400, now);
+    pinned.pinned = true;
+    store.add(pinned);
+
+    let result = store.decay(now);
+    assert_eq!(result, DecayResult { decayed: 4, removed: 1 });
+
+    let importance: HashMap<&str, u8> =
+        store.memories.iter().map(|m| (m.content.as_str(), m.importance)).collect();
+    assert_eq!(importance.len(), 6);
+    assert_eq!(importance["fresh"], 3);
+    assert_eq!(importance["within one interval of grace"], 3);
+    assert_eq!(importance["one interval stale"], 3);
+    assert_eq!(importance["two intervals stale"], 3);
+    assert_eq!(importance["floored but young enough"], 1);
+    assert_eq!(importance["pinned ancient"], 1);
+    assert!(!importance.contains_key("floored and past age cap"));
+
+    // A second sweep at the same time applies nothing new
+    assert_eq!(store.decay(now), DecayResult::default());
+
+    // One interval later, stale memories lose exactly one more step
+    let later = now + chrono::Duration::days(crate::memories::DECAY_INTERVAL_DAYS);
+    store.decay(later);
+    let one_step = store.memories.iter().find(|m| m.content == "one interval stale").unwrap();
+    assert_eq!(one_step.importance, 2);
+    let grace = store.memories.iter().find(|m| m.content == "within one interval of grace").unwrap();
+    assert_eq!(grace.importance, 2);
+}
+
+#[test]
+fn test_decay_persists_to_dir() {
+    let dir = tempfile::TempDir::new().unwrap();
+    add_memory_in_dir(dir.path(), MemoryCategory::Fact, "User once asked about tides".to_string(), 2).unwrap();
+
+    let much_later = chrono::Utc::now() + chrono::Duration::days(365);
+    let result = crate::memories::decay_memories_in_dir(dir.path(), much_later).unwrap();
+    assert_eq!(result, DecayResult { decayed: 1, removed: 1 });
+    assert!(crate::memories::load_memories_in_dir(dir.path()).unwrap().memories.is_empty());
+}
+
+#[test]
+fn test_undo_last_memory_write() {
+    use crate::memories::{edit_memory_in_dir, load_memories_in_dir, undo_last_memory_in_dir};
+
+    let dir = tempfile::TempDir::new().unwrap();
+    assert!(!undo_last_memory_in_dir(dir.path()).unwrap());
+
+    // Undoing a save removes the new memory
+    let kept = add_memory_in_dir(dir.path(), MemoryCategory::Fact, "User lives in Lisbon".to_string(), 3).unwrap();
+    add_memory_in_dir(dir.path(), MemoryCategory::Preference, "User hates tabs".to_string(), 2).unwrap();
+    assert!(undo_last_memory_in_dir(dir.path()).unwrap());
+    let store = load_memories_in_dir(dir.path()).unwrap();
+    assert_eq!(store.memories.len(), 1);
+    assert_eq!(store.memories[0].id, kept.id);
+
+    // Undoing an edit restores the prior content and importance
+    edit_memory_in_dir(dir.path(), &kept.id, "User lives in Porto".to_string(), Some(5)).unwrap();
+    assert_eq!(load_memories_in_dir(dir.path()).unwrap().memories[0].content, "User lives in Porto");
+    assert!(undo_last_memory_in_dir(dir.path()).unwrap());
+    let restored = &load_memories_in_dir(dir.path()).unwrap().memories[0];
+    assert_eq!(restored.content, "User lives in Lisbon");
+    assert_eq!(restored.importance, 3);
+
+    // The original save is next on the stack
+    assert!(undo_last_memory_in_dir(dir.path()).unwrap());
+    assert!(load_memories_in_dir(dir.path()).unwrap().memories.is_empty());
+    assert!(!undo_last_memory_in_dir(dir.path()).unwrap());
+
+    assert!(edit_memory_in_dir(dir.path(), &kept.id, "gone".to_string(), None).is_err());
+}
+
 #[test]
 fn test_format_for_prompt() {
     let mut store = MemoryStore::new();
@@ -84,3 +242,707 @@ fn test_format_for_prompt() {
     assert!(formatted.contains("### Preferences"));
     assert!(formatted.contains("### Project Context"));
 }
+
+#[test]
+fn test_add_or_merge_exact_duplicate() {
+    let mut store = MemoryStore::new();
+    store.add_or_merge(Memory::new(MemoryCategory::Preference, "User prefers Rust".to_string(), 3));
+    store.add_or_merge(Memory::new(MemoryCategory::Preference, "User prefers Rust".to_string(), 3));
+
+    assert_eq!(store.memories.len(), 1);
+}
+
+#[test]
+fn test_add_or_merge_near_duplicate_keeps_higher_importance() {
+    let mut store = MemoryStore::new();
+    let original = store.add_or_merge(Memory::new(
+        MemoryCategory::Preference,
+        "User prefers Rust".to_string(),
+        2,
+    ));
+    let merged = store.add_or_merge(Memory::new(
+        MemoryCategory::Preference,
+        "user prefers rust!".to_string(),
+        4,
+    ));
+
+    assert_eq!(store.memories.len(), 1);
+    assert_eq!(merged.id, original.id, "Should update the existing entry in place");
+    assert_eq!(store.memories[0].importance, 4);
+    assert_eq!(store.memories[0].content, "user prefers rust!");
+    assert!(store.memories[0].created_at >= original.created_at);
+
+    // Lower-importance duplicate should not overwrite the existing content
+    store.add_or_merge(Memory::new(MemoryCategory::Preference, "User, prefers RUST.".to_string(), 1));
+    assert_eq!(store.memories.len(), 1);
+    assert_eq!(store.memories[0].importance, 4);
+    assert_eq!(store.memories[0].content, "user prefers rust!");
+}
+
+#[test]
+fn test_add_or_merge_distinct_or_cross_category() {
+    let mut store = MemoryStore::new();
+    store.add_or_merge(Memory::new(MemoryCategory::Preference, "User prefers Rust".to_string(), 3));
+    store.add_or_merge(Memory::new(MemoryCategory::Preference, "User prefers Python".to_string(), 3));
+    store.add_or_merge(Memory::new(MemoryCategory::Fact, "User prefers Rust".to_string(), 3));
+
+    assert_eq!(store.memories.len(), 3);
+}
+
+#[test]
+fn test_content_similarity() {
+    assert!((content_similarity("User prefers Rust", "user PREFERS rust.") - 1.0).abs() < 1e-5);
+    assert!(content_similarity("User prefers Rust", "User prefers Python") < 0.8);
+    assert_eq!(content_similarity("", ""), 0.0);
+}
+
+#[test]
+fn test_choose_context_threshold_boundary() {
+    // Scores must be strictly above the threshold
+    assert_eq!(choose_context(None, Some(0.4), 0.4, 0.55), None);
+    assert_eq!(choose_context(Some(0.4), None, 0.4, 0.55), None);
+    assert_eq!(choose_context(None, Some(0.41), 0.4, 0.55), Some(ContextChoice::Topic));
+    assert_eq!(choose_context(Some(0.41), None, 0.4, 0.55), Some(ContextChoice::Insight));
+
+    // A stricter configured threshold filters weak matches
+    assert_eq!(choose_context(Some(0.5), Some(0.5), 0.6, 0.65), None);
+}
+
+#[test]
+fn test_choose_context_insight_vs_topic_ties() {
+    // Strong insight wins a tie
+    assert_eq!(choose_context(Some(0.7), Some(0.7), 0.4, 0.55), Some(ContextChoice::Insight));
+    // Marginal insight loses a tie to a solid topic
+    assert_eq!(choose_context(Some(0.5), Some(0.5), 0.4, 0.55), Some(ContextChoice::Topic));
+    // Marginal insight slightly above the topic still loses
+    assert_eq!(choose_context(Some(0.52), Some(0.48), 0.4, 0.55), Some(ContextChoice::Topic));
+    // Strong insight beats a weaker topic
+    assert_eq!(choose_context(Some(0.6), Some(0.45), 0.4, 0.55), Some(ContextChoice::Insight));
+    // Topic beats a weaker insight regardless of tie threshold
+    assert_eq!(choose_context(Some(0.6), Some(0.8), 0.4, 0.55), Some(ContextChoice::Topic));
+    // Below-threshold topic doesn't block a marginal insight
+    assert_eq!(choose_context(Some(0.45), Some(0.3), 0.4, 0.55), Some(ContextChoice::Insight));
+}
+
+#[test]
+fn test_split_into_chunks_respects_size() {
+    let paragraph = "word ".repeat(150); // ~750 chars
+    let content = vec![paragraph.trim(); 6].join("\n\n");
+    let chunks = split_into_chunks(&content, 500);
+
+    assert!(chunks.len() >= 2, "~4500 chars should not fit in one 500-token chunk");
+    assert!(chunks.iter().all(|c| c.len() <= 2000));
+    // No words lost across chunk boundaries
+    let words: usize = chunks.iter().map(|c| c.split_whitespace().count()).sum();
+    assert_eq!(words, 900);
+}
+
+#[test]
+fn test_split_into_chunks_short_and_oversized() {
+    assert_eq!(split_into_chunks("# Rust\n\nShort summary.", 500), vec!["# Rust\n\nShort summary."]);
+    assert!(split_into_chunks("  \n\n ", 500).is_empty());
+
+    // A single paragraph longer than the limit is split on word boundaries
+    let long = "token ".repeat(1000);
+    let chunks = split_into_chunks(&long, 100);
+    assert!(chunks.len() > 1);
+    assert!(chunks.iter().all(|c| c.len() <= 400 && !c.starts_with(' ')));
+}
+
+#[test]
+fn test_chunk_key_roundtrip() {
+    assert_eq!(parse_chunk_key(&chunk_key("rust", 3)), Some(("rust", 3)));
+    assert_eq!(parse_chunk_key(&chunk_key("C#", 0)), Some(("C#", 0)));
+    assert_eq!(parse_chunk_key("no-chunk-index"), None);
+}
+
+#[test]
+fn test_score_topic_chunks_picks_best_chunk() {
+    let mut chunks = HashMap::new();
+    chunks.insert(chunk_key("rust", 0), vec![1.0, 0.0, 0.0]);
+    chunks.insert(chunk_key("rust", 1), vec![0.0, 1.0, 0.0]);
+    chunks.insert(chunk_key("cooking", 0), vec![0.6, 0.8, 0.0]);
+    let mut topics = HashMap::new();
+    topics.insert("rust".to_string(), vec![1.0, 0.0, 0.0]);
+    topics.insert("cooking".to_string(), vec![0.6, 0.8, 0.0]);
+    // Legacy topic indexed before chunking
+    topics.insert("travel".to_string(), vec![0.0, 0.0, 1.0]);
+    let index = TopicIndex {
+        topics,
+        chunks,
+        embedding_dims: None,
+        ..Default::default()
+    };
+
+    // A query matching the second rust chunk should rank it above the whole-topic lead
+    let hits = score_topic_chunks(&index, &[0.0, 1.0, 0.0], 10);
+    assert_eq!(hits[0].doc_id, "rust#1");
+    assert!((hits[0].score - 1.0).abs() < 1e-5);
+    assert_eq!(hits[1].doc_id, "cooking#0");
+    assert!(hits.iter().all(|h| h.source == HitSource::DenseTopicChunk));
+
+    // Legacy topics are scored via their whole-topic embedding
+    let hits = score_topic_chunks(&index, &[0.0, 0.0, 1.0], 1);
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].doc_id, "travel#0");
+}
+
+/// Populate a memories directory the way the app lays it out on disk
+fn write_sample_memories(dir: &std::path::Path) {
+    let mut store = MemoryStore::new();
+    store.add(Memory::new(MemoryCategory::Preference, "User prefers Rust".to_string(), 4));
+    std::fs::create_dir_all(dir.join("topics")).unwrap();
+    std::fs::create_dir_all(dir.join("insights")).unwrap();
+    std::fs::write(dir.join("MEMORIES.json"), serde_json::to_string(&store).unwrap()).unwrap();
+    std::fs::write(dir.join("topics/rust.md"), "# rust\n\nOwnership notes").unwrap();
+    std::fs::write(
+        dir.join("topics/index.json"),
+        r#"{"topics":{"rust":[1.0,0.0]},"chunks":{"rust#0":[1.0,0.0]}}"#,
+    )
+    .unwrap();
+    std::fs::write(dir.join("insights/Borrow_checker.md"), "Lifetimes are inferred").unwrap();
+    std::fs::write(
+        dir.join("insights/index.json"),
+        r#"{"insights":{"Borrow checker":{"embedding":[0.0,1.0],"reference_count":2,"update_count":1,"created_at":"2025-01-01T00:00:00Z"}}}"#,
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_memory_bundle_round_trip() {
+    let temp = tempfile::TempDir::new().unwrap();
+    let memories_dir = temp.path().join("memories");
+    write_sample_memories(&memories_dir);
+
+    let exported = export_bundle_from_dir(&memories_dir).unwrap();
+    let json = serde_json::to_string(&exported).unwrap();
+
+    // Wipe, then restore from the serialized bundle
+    std::fs::remove_dir_all(&memories_dir).unwrap();
+    let bundle = serde_json::from_str(&json).unwrap();
+    import_bundle_into_dir(&memories_dir, &bundle).unwrap();
+
+    let mut reexported = export_bundle_from_dir(&memories_dir).unwrap();
+    reexported.exported_at = exported.exported_at;
+    assert_eq!(
+        serde_json::to_value(&reexported).unwrap(),
+        serde_json::to_value(&exported).unwrap()
+    );
+    assert_eq!(reexported.memories.memories[0].content, "User prefers Rust");
+    assert!(memories_dir.join("MEMORIES.md").exists());
+    assert!(!temp.path().join("memories.import-tmp").exists());
+    assert!(!temp.path().join("memories.import-bak").exists());
+}
+
+#[test]
+fn test_memory_bundle_rejects_bad_input_without_touching_disk() {
+    let temp = tempfile::TempDir::new().unwrap();
+    let memories_dir = temp.path().join("memories");
+    write_sample_memories(&memories_dir);
+
+    let mut bundle = export_bundle_from_dir(&memories_dir).unwrap();
+    bundle.version = MEMORY_BUNDLE_VERSION + 1;
+    assert!(import_bundle_into_dir(&memories_dir, &bundle).is_err());
+
+    let mut bundle = export_bundle_from_dir(&memories_dir).unwrap();
+    bundle.topics.insert("../escape.md".to_string(), "nope".to_string());
+    assert!(import_bundle_into_dir(&memories_dir, &bundle).is_err());
+
+    // Existing store is untouched
+    assert_eq!(
+        std::fs::read_to_string(memories_dir.join("topics/rust.md")).unwrap(),
+        "# rust\n\nOwnership notes"
+    );
+    assert!(!temp.path().join("escape.md").exists());
+}
+
+#[test]
+fn test_check_index_dims_detects_mismatch() {
+    let v768 = vec![0.1f32; 768];
+    let v1536 = vec![0.1f32; 1536];
+
+    // Recorded dims win over the vectors
+    assert_eq!(check_index_dims(Some(768), [&v768].into_iter(), 768), Ok(()));
+    assert_eq!(check_index_dims(Some(1536), [&v768].into_iter(), 768), Err(1536));
+
+    // Legacy indexes (no recorded dims) are inferred from the first vector
+    assert_eq!(check_index_dims(None, [&v768].into_iter(), 768), Ok(()));
+    assert_eq!(check_index_dims(None, [&v1536].into_iter(), 768), Err(1536));
+
+    // Nothing to compare against in an empty legacy index
+    assert_eq!(check_index_dims(None, std::iter::empty(), 768), Ok(()));
+}
+
+#[test]
+fn test_index_records_embedding_provider() {
+    use crate::config::EmbeddingProvider;
+    use crate::memories::{check_index_provider, load_insight_index_in_dir, store_insight_in_dir};
+
+    let dir = tempfile::TempDir::new().unwrap();
+    store_insight_in_dir(dir.path(), "editor", "Uses vim.", vec![0.5; 3]).unwrap();
+    let index = load_insight_index_in_dir(dir.path()).unwrap();
+    assert_eq!(index.embedding_dims, Some(3));
+    assert_eq!(index.embedding_provider, Some(EmbeddingProvider::Gemini));
+
+    // Indexes from before providers were recorded count as Gemini
+    assert_eq!(check_index_provider(None, false, EmbeddingProvider::Gemini), Ok(()));
+    assert_eq!(
+        check_index_provider(None, false, EmbeddingProvider::OpenAi),
+        Err(EmbeddingProvider::Gemini)
+    );
+    assert_eq!(
+        check_index_provider(Some(EmbeddingProvider::OpenAi), false, EmbeddingProvider::Gemini),
+        Err(EmbeddingProvider::OpenAi)
+    );
+    assert_eq!(check_index_provider(None, true, EmbeddingProvider::OpenAi), Ok(()));
+}
+
+#[test]
+fn test_topic_index_without_dims_still_parses() {
+    let json = r#"{"topics": {"rust": [1.0, 0.0]}, "chunks": {}}"#;
+    let index: TopicIndex = serde_json::from_str(json).unwrap();
+    assert_eq!(index.embedding_dims, None);
+    assert_eq!(check_index_dims(index.embedding_dims, index.topics.values(), 2), Ok(()));
+}
+
+#[test]
+fn test_list_topics_ignores_index() {
+    let dir = tempfile::TempDir::new().unwrap();
+    std::fs::write(dir.path().join("rust.md"), "# rust\n\nOwnership notes").unwrap();
+    std::fs::write(dir.path().join("cooking.md"), "# cooking").unwrap();
+    std::fs::write(dir.path().join("index.json"), "{\"topics\": {}}").unwrap();
+    std::fs::write(dir.path().join("index.json.tmp"), "partial").unwrap();
+
+    let topics = list_topics_in_dir(dir.path()).unwrap();
+    let mut names: Vec<&str> = topics.iter().map(|t| t.name.as_str()).collect();
+    names.sort();
+    assert_eq!(names, vec!["cooking", "rust"]);
+
+    let rust = topics.iter().find(|t| t.name == "rust").unwrap();
+    assert_eq!(rust.size, "# rust\n\nOwnership notes".len() as u64);
+}
+
+#[test]
+fn test_list_known_topics_returns_current_topics() {
+    use crate::memories::list_known_topics_in_dir;
+
+    let dir = tempfile::TempDir::new().unwrap();
+    assert!(list_known_topics_in_dir(&dir.path().join("missing")).unwrap().is_empty());
+
+    std::fs::write(dir.path().join("Home_Lab.md"), "# Home Lab\n\nProxmox cluster on three NUCs\nMore detail").unwrap();
+    std::fs::write(dir.path().join("cooking.md"), format!("# cooking\n\n{}", "a".repeat(150))).unwrap();
+    std::fs::write(dir.path().join("empty.md"), "").unwrap();
+    std::fs::write(dir.path().join("index.json"), "{\"topics\": {}}").unwrap();
+
+    let topics = list_known_topics_in_dir(dir.path()).unwrap();
+    let names: Vec<&str> = topics.iter().map(|t| t.name.as_str()).collect();
+    assert_eq!(names, vec!["cooking", "empty", "Home Lab"]);
+    assert_eq!(topics[2].preview, "Proxmox cluster on three NUCs");
+    assert!(topics[0].preview.ends_with('…'));
+    assert_eq!(topics[0].preview.chars().count(), 101);
+    assert_eq!(topics[1].preview, "");
+
+    let reply = crate::agent::format_known_topics(&topics);
+    assert!(reply.contains("- Home Lab: Proxmox cluster on three NUCs"));
+    assert!(reply.contains("- empty\n"));
+
+    // Deleted topics drop out
+    std::fs::remove_file(dir.path().join("cooking.md")).unwrap();
+    assert_eq!(list_known_topics_in_dir(dir.path()).unwrap().len(), 2);
+}
+
+#[test]
+fn test_delete_topic_cleans_index() {
+    let dir = tempfile::TempDir::new().unwrap();
+    std::fs::write(dir.path().join("rust.md"), "# rust").unwrap();
+    std::fs::write(dir.path().join("cooking.md"), "# cooking").unwrap();
+
+    let mut index = TopicIndex::default();
+    index.topics.insert("rust".to_string(), vec![1.0, 0.0]);
+    index.topics.insert("cooking".to_string(), vec![0.0, 1.0]);
+    index.chunks.insert(chunk_key("rust", 0), vec![1.0, 0.0]);
+    index.chunks.insert(chunk_key("rust", 1), vec![0.9, 0.1]);
+    index.chunks.insert(chunk_key("cooking", 0), vec![0.0, 1.0]);
+    std::fs::write(dir.path().join("index.json"), serde_json::to_string(&index).unwrap()).unwrap();
+
+    assert!(delete_topic_in_dir(dir.path(), "rust").unwrap());
+    assert!(!dir.path().join("rust.md").exists());
+    assert!(dir.path().join("cooking.md").exists());
+
+    let saved: TopicIndex =
+        serde_json::from_str(&std::fs::read_to_string(dir.path().join("index.json")).unwrap()).unwrap();
+    assert_eq!(saved.topics.keys().collect::<Vec<_>>(), vec!["cooking"]);
+    assert_eq!(saved.chunks.keys().collect::<Vec<_>>(), vec!["cooking#0"]);
+
+    // Deleting again is a no-op
+    assert!(!delete_topic_in_dir(dir.path(), "rust").unwrap());
+}
+
+#[test]
+fn test_assign_file_stem_disambiguates_collisions() {
+    use crate::memories::{assign_file_stem, resolve_file_stem};
+
+    let mut filenames = HashMap::new();
+    let mut names: Vec<String> = vec!["legacy topic".to_string()]; // indexed before mappings existed
+
+    let plus = assign_file_stem(&mut filenames, names.iter(), "C++").unwrap();
+    names.push("C++".to_string());
+    let star = assign_file_stem(&mut filenames, names.iter(), "C**").unwrap();
+    names.push("C**".to_string());
+    assert_eq!(plus, "C__");
+    assert_eq!(star, "C___2");
+
+    // Unmapped legacy entries still claim their sanitized stem
+    assert_eq!(assign_file_stem(&mut filenames, names.iter(), "legacy?topic").unwrap(), "legacy_topic_2");
+    // Existing names keep their file; display names resolve through the mapping
+    assert_eq!(assign_file_stem(&mut filenames, names.iter(), "C++").unwrap(), "C__");
+    assert_eq!(resolve_file_stem(&filenames, "C**").as_deref(), Some("C___2"));
+    assert_eq!(resolve_file_stem(&filenames, "legacy topic").as_deref(), Some("legacy_topic"));
+    // An unmapped name never falls back onto a stem another name owns
+    assert_eq!(resolve_file_stem(&filenames, "C--"), None);
+
+    assert!(assign_file_stem(&mut filenames, names.iter(), "   ").is_err());
+}
+
+#[test]
+fn test_colliding_insight_titles_round_trip() {
+    use crate::memories::{delete_insight_in_dir, load_insight_index_in_dir, read_insight_in_dir, store_insight_in_dir};
+
+    let dir = tempfile::TempDir::new().unwrap();
+    store_insight_in_dir(dir.path(), "C++", "Uses C++20.", vec![1.0, 0.0]).unwrap();
+    store_insight_in_dir(dir.path(), "C**", "Wildcard notes.", vec![0.0, 1.0]).unwrap();
+
+    // Neither write overwrote the other and the original titles are preserved
+    assert_eq!(read_insight_in_dir(dir.path(), "C++").unwrap(), "# C++\n\nUses C++20.");
+    assert_eq!(read_insight_in_dir(dir.path(), "C**").unwrap(), "# C**\n\nWildcard notes.");
+    let index = load_insight_index_in_dir(dir.path()).unwrap();
+    assert_eq!(index.filenames["C**"], "C___2");
+
+    assert!(delete_insight_in_dir(dir.path(), "C++").unwrap());
+    assert!(!dir.path().join("C__.md").exists());
+    assert!(read_insight_in_dir(dir.path(), "C**").is_ok());
+    assert!(!load_insight_index_in_dir(dir.path()).unwrap().filenames.contains_key("C++"));
+}
+
+#[test]
+fn test_missing_colliding_name_does_not_touch_other_entry() {
+    use crate::memories::{delete_insight_in_dir, load_insight_index_in_dir, read_insight_in_dir, store_insight_in_dir};
+
+    let dir = tempfile::TempDir::new().unwrap();
+    store_insight_in_dir(dir.path(), "C++", "Uses C++20.", vec![1.0, 0.0]).unwrap();
+
+    // "C**" sanitizes to the stem "C++" owns but was never stored
+    assert!(read_insight_in_dir(dir.path(), "C**").is_err());
+    assert!(!delete_insight_in_dir(dir.path(), "C**").unwrap());
+    assert!(dir.path().join("C__.md").exists());
+    assert_eq!(load_insight_index_in_dir(dir.path()).unwrap().filenames["C++"], "C__");
+
+    // Same for topics
+    std::fs::write(dir.path().join("C__.md"), "# C++").unwrap();
+    let mut index = TopicIndex::default();
+    index.topics.insert("C++".to_string(), vec![1.0]);
+    index.filenames.insert("C++".to_string(), "C__".to_string());
+    std::fs::write(dir.path().join("index.json"), serde_json::to_string(&index).unwrap()).unwrap();
+    assert!(crate::memories::topic_path_in_dir(dir.path(), "C**").is_err());
+    assert!(!delete_topic_in_dir(dir.path(), "C**").unwrap());
+    assert!(dir.path().join("C__.md").exists());
+}
+
+#[test]
+fn test_delete_topic_follows_name_mapping() {
+    use crate::memories::topic_path_in_dir;
+
+    let dir = tempfile::TempDir::new().unwrap();
+    std::fs::write(dir.path().join("C__.md"), "# C++").unwrap();
+    std::fs::write(dir.path().join("C___2.md"), "# C**").unwrap();
+    let mut index = TopicIndex::default();
+    for (name, stem) in [("C++", "C__"), ("C**", "C___2")] {
+        index.topics.insert(name.to_string(), vec![1.0]);
+        index.chunks.insert(chunk_key(name, 0), vec![1.0]);
+        index.filenames.insert(name.to_string(), stem.to_string());
+    }
+    std::fs::write(dir.path().join("index.json"), serde_json::to_string(&index).unwrap()).unwrap();
+
+    assert_eq!(topic_path_in_dir(dir.path(), "C**").unwrap(), dir.path().join("C___2.md"));
+    assert!(delete_topic_in_dir(dir.path(), "C**").unwrap());
+    assert!(dir.path().join("C__.md").exists());
+    assert!(!dir.path().join("C___2.md").exists());
+
+    let saved: TopicIndex =
+        serde_json::from_str(&std::fs::read_to_string(dir.path().join("index.json")).unwrap()).unwrap();
+    assert_eq!(saved.topics.keys().collect::<Vec<_>>(), vec!["C++"]);
+    assert_eq!(saved.chunks.keys().collect::<Vec<_>>(), vec!["C++#0"]);
+    assert_eq!(saved.filenames.len(), 1);
+}
+
+#[test]
+fn test_topic_access_recorded_on_selection() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut index = TopicIndex::default();
+    index.topics.insert("rust".to_string(), vec![1.0, 0.0]);
+    std::fs::write(dir.path().join("index.json"), serde_json::to_string(&index).unwrap()).unwrap();
+
+    let first = chrono::Utc::now() - chrono::Duration::days(2);
+    let second = chrono::Utc::now();
+    assert_eq!(record_topic_access_in_dir(dir.path(), "rust", first).unwrap(), 1);
+    assert_eq!(record_topic_access_in_dir(dir.path(), "rust", second).unwrap(), 2);
+    assert!(record_topic_access_in_dir(dir.path(), "missing", second).is_err());
+
+    let saved: TopicIndex =
+        serde_json::from_str(&std::fs::read_to_string(dir.path().join("index.json")).unwrap()).unwrap();
+    assert_eq!(saved.access["rust"].count, 2);
+    assert_eq!(saved.access["rust"].last_accessed, Some(second));
+    assert!(!saved.access.contains_key("missing"));
+    // Embeddings are untouched
+    assert_eq!(saved.topics["rust"], vec![1.0, 0.0]);
+}
+
+#[test]
+fn test_concurrent_topic_access_keeps_every_increment() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut index = TopicIndex::default();
+    index.topics.insert("rust".to_string(), vec![1.0, 0.0]);
+    std::fs::write(dir.path().join("index.json"), serde_json::to_string(&index).unwrap()).unwrap();
+
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                for _ in 0..10 {
+                    record_topic_access_in_dir(dir.path(), "rust", chrono::Utc::now()).unwrap();
+                }
+            });
+        }
+    });
+
+    let saved: TopicIndex =
+        serde_json::from_str(&std::fs::read_to_string(dir.path().join("index.json")).unwrap()).unwrap();
+    assert_eq!(saved.access["rust"].count, 40);
+}
+
+#[test]
+fn test_topic_stats_aggregation() {
+    let now = chrono::Utc::now();
+    let mut index = TopicIndex::default();
+    for topic in ["rust", "cooking", "travel"] {
+        index.topics.insert(topic.to_string(), vec![1.0]);
+    }
+    index.record_access("cooking", now);
+    index.record_access("rust", now - chrono::Duration::days(1));
+    index.record_access("rust", now);
+    index.record_access("rust", now);
+
+    let stats = index.stats();
+    let summary: Vec<(&str, u32)> = stats.iter().map(|s| (s.name.as_str(), s.access_count)).collect();
+    assert_eq!(summary, vec![("rust", 3), ("cooking", 1), ("travel", 0)]);
+    assert_eq!(stats[0].last_accessed, Some(now));
+    // Never-selected topics are listed so they can be pruned
+    assert_eq!(stats[2].last_accessed, None);
+
+    // Indexes written before access tracking load with empty stats
+    let legacy: TopicIndex = serde_json::from_str(r#"{"topics":{"rust":[1.0]}}"#).unwrap();
+    assert_eq!(legacy.stats()[0].access_count, 0);
+}
+
+fn insight_meta(embedding: Vec<f32>, favorite: bool) -> InsightMeta {
+    InsightMeta {
+        embedding,
+        reference_count: 0,
+        update_count: 1,
+        created_at: chrono::Utc::now(),
+        favorite,
+    }
+}
+
+#[test]
+fn test_favorites_included_regardless_of_similarity() {
+    let mut insights = InsightIndex::default();
+    insights.insights.insert("close".to_string(), insight_meta(vec![1.0, 0.0], false));
+    insights.insights.insert("unrelated fav".to_string(), insight_meta(vec![0.0, 1.0], true));
+    let mut topics = TopicIndex::default();
+    topics.topics.insert("rust".to_string(), vec![0.0, 1.0]);
+    assert_eq!(topics.toggle_favorite("rust"), Some(true));
+    assert_eq!(topics.toggle_favorite("not indexed"), None);
+
+    // Orthogonal to the query, but favorited: still selected. Non-favorites never are.
+    let favs = select_favorites(&insights, &topics, Some(&[1.0, 0.0]), None, MAX_FAVORITE_CONTEXT);
+    let names: Vec<&str> = favs.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(names, vec!["rust", "unrelated fav"]);
+    assert!(favs[0].score.abs() < 1e-5);
+    assert!(!favs[0].is_insight && favs[1].is_insight);
+
+    // Works without a query embedding, and skips the similarity match
+    let favs = select_favorites(&insights, &topics, None, Some("rust"), MAX_FAVORITE_CONTEXT);
+    assert_eq!(favs.len(), 1);
+    assert_eq!(favs[0].name, "unrelated fav");
+
+    // Toggling off removes it
+    assert_eq!(insights.toggle_favorite("unrelated fav"), Some(false));
+    assert_eq!(select_favorites(&insights, &topics, None, None, 3).len(), 1);
+}
+
+#[test]
+fn test_favorites_capped_by_relevance() {
+    let mut insights = InsightIndex::default();
+    insights.insights.insert("a".to_string(), insight_meta(vec![0.1, 1.0], true));
+    insights.insights.insert("b".to_string(), insight_meta(vec![1.0, 0.0], true));
+    insights.insights.insert("c".to_string(), insight_meta(vec![1.0, 0.5], true));
+    insights.insights.insert("d".to_string(), insight_meta(vec![0.0, 1.0], true));
+    let topics = TopicIndex::default();
+
+    let favs = select_favorites(&insights, &topics, Some(&[1.0, 0.0]), None, 2);
+    let names: Vec<&str> = favs.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(names, vec!["b", "c"]);
+
+    assert_eq!(select_favorites(&insights, &topics, None, None, MAX_FAVORITE_CONTEXT).len(), MAX_FAVORITE_CONTEXT);
+    assert!(select_favorites(&insights, &topics, None, None, 0).is_empty());
+}
+
+/// `save_memory` as `execute_tool` runs it: the real tool behind the incognito/disabled guard
+async fn guarded_save_memory(config: &AppConfig, dir: &std::path::Path, content: &str) -> String {
+    let args = serde_json::json!({"category": "fact", "content": content, "importance": 3});
+    crate::tools::run_guarded_tool(config, "save_memory", || async {
+        crate::memories::save_memory_tool_in_dir(dir, &args)
+    })
+    .await
+}
+
+#[tokio::test]
+async fn test_incognito_save_memory_leaves_memories_json_untouched() {
+    let dir = tempfile::TempDir::new().unwrap();
+    add_memory_in_dir(dir.path(), MemoryCategory::Preference, "User prefers Rust".to_string(), 4).unwrap();
+    let memories_path = dir.path().join("MEMORIES.json");
+    let before = std::fs::read_to_string(&memories_path).unwrap();
+
+    let incognito = AppConfig {
+        incognito_mode: Some(true),
+        ..Default::default()
+    };
+    let result = guarded_save_memory(&incognito, dir.path(), "User lives in Berlin").await;
+    assert!(result.starts_with("Skipped:"), "unexpected result: {}", result);
+    assert_eq!(std::fs::read_to_string(&memories_path).unwrap(), before);
+
+    // The same call outside incognito does persist
+    let normal = AppConfig::default();
+    let result = guarded_save_memory(&normal, dir.path(), "User lives in Berlin").await;
+    assert!(result.starts_with("Memory saved:"), "unexpected result: {}", result);
+    assert!(std::fs::read_to_string(&memories_path).unwrap().contains("Berlin"));
+}
+
+#[test]
+fn test_incognito_only_blocks_memory_writes() {
+    let incognito = AppConfig {
+        incognito_mode: Some(true),
+        ..Default::default()
+    };
+    for tool in crate::tools::MEMORY_WRITE_TOOLS {
+        assert!(crate::tools::incognito_skip_message(&incognito, tool).is_some());
+        assert!(crate::tools::incognito_skip_message(&AppConfig::default(), tool).is_none());
+    }
+    assert!(crate::tools::incognito_skip_message(&incognito, "read_topic_summary").is_none());
+    assert!(crate::tools::incognito_skip_message(&incognito, "web_search").is_none());
+}
+
+#[tokio::test]
+async fn test_merge_insights_replaces_originals() {
+    use crate::memories::{
+        load_insight_index_in_dir, merge_insights_in_dir, read_insight_in_dir, save_insight_index_in_dir,
+        store_insight_in_dir,
+    };
+
+    let dir = tempfile::TempDir::new().unwrap();
+    store_insight_in_dir(dir.path(), "likes dark mode", "User likes dark mode.", vec![1.0, 0.0]).unwrap();
+    store_insight_in_dir(dir.path(), "prefers dark theme", "Prefers a dark editor theme.", vec![0.9, 0.1]).unwrap();
+    store_insight_in_dir(dir.path(), "uses vim", "Edits in vim.", vec![0.0, 1.0]).unwrap();
+
+    let mut index = load_insight_index_in_dir(dir.path()).unwrap();
+    index.insights.get_mut("likes dark mode").unwrap().reference_count = 2;
+    index.insights.get_mut("prefers dark theme").unwrap().reference_count = 7;
+    index.insights.get_mut("prefers dark theme").unwrap().favorite = true;
+    save_insight_index_in_dir(dir.path(), &index).unwrap();
+
+    let titles = vec!["likes dark mode".to_string(), "prefers dark theme".to_string()];
+    let embedded = std::sync::Mutex::new(String::new());
+    merge_insights_in_dir(dir.path(), &titles, "dark mode preference", |text| {
+        *embedded.lock().unwrap() = text;
+        async { Ok(vec![0.7, 0.3]) }
+    })
+    .await
+    .unwrap();
+
+    // The merged insight holds both bodies, without the originals' headings
+    let merged = read_insight_in_dir(dir.path(), "dark mode preference").unwrap();
+    assert!(merged.starts_with("# dark mode preference\n\n"));
+    assert!(merged.contains("User likes dark mode.\n\nPrefers a dark editor theme."));
+    assert!(!merged.contains("# likes dark mode"));
+    assert!(embedded.lock().unwrap().starts_with("Insight: dark mode preference"));
+
+    // Originals are gone from disk and index; the unrelated insight is untouched
+    for title in &titles {
+        assert!(read_insight_in_dir(dir.path(), title).is_err());
+    }
+    let index = load_insight_index_in_dir(dir.path()).unwrap();
+    let mut indexed: Vec<&str> = index.insights.keys().map(|k| k.as_str()).collect();
+    indexed.sort();
+    assert_eq!(indexed, vec!["dark mode preference", "uses vim"]);
+
+    let meta = &index.insights["dark mode preference"];
+    assert_eq!(meta.embedding, vec![0.7, 0.3]);
+    assert_eq!(meta.reference_count, 7);
+    assert!(meta.favorite);
+}
+
+#[tokio::test]
+async fn test_merge_insights_missing_original_writes_nothing() {
+    use crate::memories::{load_insight_index_in_dir, merge_insights_in_dir, store_insight_in_dir};
+
+    let dir = tempfile::TempDir::new().unwrap();
+    store_insight_in_dir(dir.path(), "likes dark mode", "User likes dark mode.", vec![1.0, 0.0]).unwrap();
+
+    let titles = vec!["likes dark mode".to_string(), "missing".to_string()];
+    let result = merge_insights_in_dir(dir.path(), &titles, "merged", |_text| async {
+        Ok(vec![0.5, 0.5])
+    })
+    .await;
+    assert!(result.is_err());
+
+    let index = load_insight_index_in_dir(dir.path()).unwrap();
+    assert_eq!(index.insights.len(), 1);
+    assert!(index.insights.contains_key("likes dark mode"));
+}
+
+#[test]
+fn test_category_summary_counts_and_top_items() {
+    let mut store = MemoryStore::new();
+    // 80 chars -> (80 + 20) / 4 = 25 estimated tokens each
+    let content = |tag: &str| format!("{:<80}", tag);
+    for (tag, importance) in [("tabs", 2), ("metric", 5), ("dark", 3), ("vim", 4)] {
+        store.add(Memory::new(MemoryCategory::Preference, content(tag), importance));
+    }
+    store.add(Memory::new(MemoryCategory::Project, content("shard"), 4));
+    store.add(Memory::new(MemoryCategory::Fact, content("berlin"), 1));
+
+    let summary = store.category_summary(100, 3);
+    assert_eq!(summary.total_tokens, 150);
+    assert_eq!(summary.token_budget, 100);
+    assert_eq!(summary.headroom, -50);
+
+    let counts: Vec<(MemoryCategory, usize, usize)> = summary
+        .categories
+        .iter()
+        .map(|c| (c.category.clone(), c.count, c.tokens))
+        .collect();
+    assert_eq!(
+        counts,
+        vec![
+            (MemoryCategory::Preference, 4, 100),
+            (MemoryCategory::Project, 1, 25),
+            (MemoryCategory::Fact, 1, 25),
+            (MemoryCategory::Interaction, 0, 0),
+        ]
+    );
+
+    let top: Vec<u8> = summary.categories[0].top.iter().map(|m| m.importance).collect();
+    assert_eq!(top, vec![5, 4, 3]);
+    assert!(summary.categories[0].top[0].content.starts_with("metric"));
+    assert!(summary.categories[3].top.is_empty());
+}
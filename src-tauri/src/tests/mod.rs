@@ -8,3 +8,7 @@ mod background_tests;
 mod memories_tests;
 mod cache_tests;
 mod interactions_tests;
+mod tool_stats_tests;
+mod embedding_cache_tests;
+mod error_tests;
+mod rate_limit_tests;
@@ -1,6 +1,6 @@
 #[cfg(test)]
 mod tests {
-    use crate::prompts::get_jailbreak_prompt;
+    use crate::prompts::{get_jailbreak_prompt, render_prompt_template, render_system_prompt_template};
 
     #[test]
     fn test_get_jailbreak_prompt_gemini_flash() {
@@ -14,4 +14,111 @@ mod tests {
         let prompt = get_jailbreak_prompt("unknown-model");
         assert!(prompt.len() == 0);
     }
+
+    #[test]
+    fn test_render_prompt_template_substitutes_values() {
+        let rendered = render_prompt_template(
+            "Today is {{date}}.{{memories}}\n{{ rag }}",
+            &[
+                ("date", Some("2025-01-01")),
+                ("memories", Some(" Likes Rust.")),
+                ("rag", Some("Past chat")),
+            ],
+        );
+        assert_eq!(rendered, "Today is 2025-01-01. Likes Rust.\nPast chat");
+    }
+
+    #[test]
+    fn test_render_prompt_template_missing_and_unknown() {
+        let rendered = render_prompt_template(
+            "A{{memories}}B {{unknown}} {{rag}}C {{unclosed",
+            &[("memories", None), ("rag", Some(""))],
+        );
+        // Missing values render empty, unknown placeholders stay intact
+        assert_eq!(rendered, "AB {{unknown}} C {{unclosed");
+    }
+
+    #[test]
+    fn test_render_system_prompt_template_fills_builtins() {
+        let config = crate::config::AppConfig::default();
+        let rendered = render_system_prompt_template("Tools: {{tools}} | {{memories}}", &config, false, None, None);
+        assert!(rendered.starts_with("Tools: "));
+        assert!(rendered.contains("web_search"));
+        assert!(rendered.ends_with("| "));
+        assert!(!rendered.contains("{{"));
+    }
+
+    #[test]
+    fn test_template_tools_match_offered_tools() {
+        use crate::config::AppConfig;
+
+        let config = AppConfig {
+            disabled_tools: Some(vec!["get_weather".to_string()]),
+            ..AppConfig::default()
+        };
+        let rendered = render_system_prompt_template("{{tools}}", &config, false, None, None);
+        assert!(rendered.contains("web_search"));
+        assert!(!rendered.contains("get_weather"));
+
+        // Research mode only names its allow-list
+        let rendered = render_system_prompt_template("{{tools}}", &AppConfig::default(), true, None, None);
+        assert!(rendered.contains("search_arxiv"));
+        assert!(!rendered.contains("get_stock_price"));
+
+        let no_tools = AppConfig {
+            enable_tools: Some(false),
+            ..AppConfig::default()
+        };
+        assert_eq!(render_system_prompt_template("[{{tools}}]", &no_tools, false, None, None), "[]");
+    }
+
+    #[test]
+    fn test_date_and_time_share_the_local_clock() {
+        let before = chrono::Local::now();
+        let rendered =
+            render_system_prompt_template("{{date}} {{time}}", &crate::config::AppConfig::default(), false, None, None);
+        let after = chrono::Local::now();
+        // Either side of a minute/day rollover, the pair matches one local instant
+        let matches = |now: chrono::DateTime<chrono::Local>| rendered == now.format("%Y-%m-%d %H:%M (UTC%:z)").to_string();
+        assert!(matches(before) || matches(after), "{}", rendered);
+    }
+
+    #[test]
+    fn test_default_prompt_style_follows_verbosity() {
+        use crate::config::Verbosity;
+        use crate::prompts::{get_default_system_prompt, verbosity_instructions};
+
+        let levels = [Verbosity::Terse, Verbosity::Balanced, Verbosity::Detailed];
+        for level in levels {
+            let prompt = get_default_system_prompt(Some("MEM"), Some("RAG"), level);
+            assert!(prompt.contains(verbosity_instructions(level)), "{:?}", level);
+            for other in levels.iter().filter(|l| **l != level) {
+                assert!(!prompt.contains(verbosity_instructions(*other)), "{:?} leaked into {:?}", other, level);
+            }
+            // Tool and memory instructions don't change with verbosity
+            assert!(prompt.contains("Memory Tools:"));
+            assert!(prompt.contains("web_search has quota"));
+            assert!(prompt.contains("MEMRAG"));
+        }
+
+        assert!(verbosity_instructions(Verbosity::Terse).contains("EXTREMELY concise and even curt"));
+        assert!(verbosity_instructions(Verbosity::Detailed).contains("thorough"));
+    }
+
+    #[test]
+    fn test_verbosity_defaults_to_terse() {
+        use crate::config::{AppConfig, Verbosity};
+        use crate::prompts::{build_system_prompt, verbosity_instructions};
+
+        assert_eq!(Verbosity::default(), Verbosity::Terse);
+        let prompt = build_system_prompt(&AppConfig::default(), false, None, None);
+        assert!(prompt.contains(verbosity_instructions(Verbosity::Terse)));
+
+        let config = AppConfig {
+            verbosity: Some(Verbosity::Balanced),
+            ..AppConfig::default()
+        };
+        let prompt = build_system_prompt(&config, false, None, None);
+        assert!(prompt.contains(verbosity_instructions(Verbosity::Balanced)));
+    }
 }
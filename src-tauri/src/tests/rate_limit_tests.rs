@@ -0,0 +1,71 @@
+/**
+ * Rate Limit Tests
+ *
+ * Tests for embedding token-bucket pacing and the monthly web search quota.
+ */
+
+#[cfg(test)]
+mod tests {
+    use crate::rate_limit::{consume_web_search_quota_in_dir, web_search_quota_message, TokenBucket, WebSearchQuota};
+    use chrono::{TimeZone, Utc};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_token_bucket_paces_after_burst() {
+        // 60 rpm = 1 token/sec with a 10-token burst
+        let mut bucket = TokenBucket::new(60);
+        let start = Instant::now();
+        for _ in 0..10 {
+            assert_eq!(bucket.reserve(start), Duration::ZERO);
+        }
+        // Empty bucket: reservations queue up one second apart
+        assert_eq!(bucket.reserve(start), Duration::from_secs(1));
+        assert_eq!(bucket.reserve(start), Duration::from_secs(2));
+
+        // Five seconds later three tokens have refilled against the two owed
+        let later = start + Duration::from_secs(5);
+        assert_eq!(bucket.reserve(later), Duration::ZERO);
+        assert_eq!(bucket.reserve(later), Duration::ZERO);
+        assert_eq!(bucket.reserve(later), Duration::ZERO);
+        assert_eq!(bucket.reserve(later), Duration::from_secs(1));
+
+        // Refill is capped at the burst size
+        let idle = later + Duration::from_secs(600);
+        for _ in 0..10 {
+            assert_eq!(bucket.reserve(idle), Duration::ZERO);
+        }
+        assert!(bucket.reserve(idle) > Duration::ZERO);
+
+        let mut unlimited = TokenBucket::new(0);
+        for _ in 0..1000 {
+            assert_eq!(unlimited.reserve(start), Duration::ZERO);
+        }
+    }
+
+    #[test]
+    fn test_web_search_quota_resets_each_month() {
+        let mut quota = WebSearchQuota::default();
+        assert!(quota.try_consume("2025-01", 2));
+        assert!(quota.try_consume("2025-01", 2));
+        assert!(!quota.try_consume("2025-01", 2));
+        assert_eq!(quota.used, 2);
+
+        assert!(quota.try_consume("2025-02", 2));
+        assert_eq!(quota, WebSearchQuota { month: "2025-02".to_string(), used: 1 });
+
+        // Persisted across calls, refused once exhausted, fresh in the next month
+        let dir = tempfile::TempDir::new().unwrap();
+        let jan = Utc.with_ymd_and_hms(2025, 1, 31, 23, 0, 0).unwrap();
+        assert!(consume_web_search_quota_in_dir(dir.path(), jan, 1));
+        assert!(!consume_web_search_quota_in_dir(dir.path(), jan, 1));
+        let feb = Utc.with_ymd_and_hms(2025, 2, 1, 0, 0, 0).unwrap();
+        assert!(consume_web_search_quota_in_dir(dir.path(), feb, 1));
+    }
+
+    #[test]
+    fn test_quota_message_is_never_cached() {
+        let message = web_search_quota_message(2000);
+        assert!(message.contains("2000"));
+        assert!(!crate::cache::is_cacheable_result(&message));
+    }
+}
@@ -1,5 +1,8 @@
 
+use crate::agent::{detect_research_mode, max_turns_for};
+use crate::config::AppConfig;
 use crate::prompts::get_research_system_prompt;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 #[test]
 fn test_research_prompt_integrity() {
@@ -9,4 +12,57 @@ fn test_research_prompt_integrity() {
     assert!(prompt.contains("Execute iteratively"));
     assert!(prompt.contains("Executive summary (the only output)"));
     assert!(prompt.contains("No references, URLs, or appendices"));
+
+    // Only tools research mode offers by default are named
+    for tool in ["get_stock_price", "get_weather"] {
+        assert!(!crate::tools::DEFAULT_RESEARCH_TOOLS.contains(&tool));
+        assert!(!prompt.contains(tool), "{}", tool);
+    }
+}
+
+#[test]
+fn test_max_turns_selection() {
+    let config = AppConfig::default();
+    assert_eq!(max_turns_for(&config, true), 15);
+    assert_eq!(max_turns_for(&config, false), 5);
+
+    let config = AppConfig {
+        research_max_turns: Some(40),
+        normal_max_turns: Some(0),
+        ..AppConfig::default()
+    };
+    assert_eq!(max_turns_for(&config, true), 40);
+    // Zero would skip the turn entirely; clamp to one
+    assert_eq!(max_turns_for(&config, false), 1);
+}
+
+#[tokio::test]
+async fn test_research_detection_toggle() {
+    let calls = AtomicUsize::new(0);
+    let classify = |_query: String| {
+        calls.fetch_add(1, Ordering::SeqCst);
+        async { Ok(true) }
+    };
+
+    // Default: classifier decides
+    let config = AppConfig::default();
+    assert!(detect_research_mode(&config, Some("compare CRDT libraries"), classify).await);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    // Disabled: no classifier call
+    let config = AppConfig {
+        auto_research_detection: Some(false),
+        ..AppConfig::default()
+    };
+    assert!(!detect_research_mode(&config, Some("compare CRDT libraries"), classify).await);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    // Forced research mode skips the classifier too
+    let config = AppConfig {
+        research_mode: Some(true),
+        auto_research_detection: Some(false),
+        ..AppConfig::default()
+    };
+    assert!(detect_research_mode(&config, Some("hi"), classify).await);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
 }
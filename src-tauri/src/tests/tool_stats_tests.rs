@@ -0,0 +1,71 @@
+/**
+ * Tool Stats Tests
+ *
+ * Tests for per-tool counter increments and aggregation.
+ */
+
+#[cfg(test)]
+mod tests {
+    use crate::tool_stats::{is_error_result, ToolEvent, ToolStats};
+
+    #[test]
+    fn test_record_increments_matching_counter() {
+        let mut stats = ToolStats::default();
+        stats.record("web_search", ToolEvent::Call);
+        stats.record("web_search", ToolEvent::Call);
+        stats.record("web_search", ToolEvent::CacheHit);
+        stats.record("web_search", ToolEvent::CacheMiss);
+        stats.record("web_search", ToolEvent::Error);
+
+        let counters = &stats.tools["web_search"];
+        assert_eq!(counters.calls, 2);
+        assert_eq!(counters.cache_hits, 1);
+        assert_eq!(counters.cache_misses, 1);
+        assert_eq!(counters.errors, 1);
+    }
+
+    #[test]
+    fn test_record_keeps_tools_separate() {
+        let mut stats = ToolStats::default();
+        stats.record("web_search", ToolEvent::Call);
+        stats.record("get_weather", ToolEvent::Call);
+        stats.record("get_weather", ToolEvent::CacheHit);
+
+        assert_eq!(stats.tools.len(), 2);
+        assert_eq!(stats.tools["web_search"].cache_hits, 0);
+        assert_eq!(stats.tools["get_weather"].cache_hits, 1);
+    }
+
+    #[test]
+    fn test_totals_aggregate_across_tools() {
+        let mut stats = ToolStats::default();
+        stats.record("web_search", ToolEvent::Call);
+        stats.record("get_weather", ToolEvent::Call);
+        stats.record("get_weather", ToolEvent::Error);
+        stats.record("search_arxiv", ToolEvent::CacheMiss);
+
+        let totals = stats.totals();
+        assert_eq!(totals.calls, 2);
+        assert_eq!(totals.errors, 1);
+        assert_eq!(totals.cache_hits, 0);
+        assert_eq!(totals.cache_misses, 1);
+    }
+
+    #[test]
+    fn test_stats_roundtrip_serialization() {
+        let mut stats = ToolStats::default();
+        stats.record("web_search", ToolEvent::Call);
+
+        let json = serde_json::to_string(&stats).unwrap();
+        let restored: ToolStats = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.tools["web_search"], stats.tools["web_search"]);
+    }
+
+    #[test]
+    fn test_is_error_result() {
+        assert!(is_error_result("Error: timeout"));
+        assert!(is_error_result("Failed to save memory: disk full"));
+        assert!(is_error_result("Unknown tool: foo"));
+        assert!(!is_error_result("Weather in Paris: 20 °C"));
+    }
+}
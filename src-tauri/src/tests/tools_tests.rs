@@ -30,4 +30,98 @@ mod tests {
         assert!(params.get("properties").is_some());
         assert!(params.get("required").is_some());
     }
+
+    #[test]
+    fn test_disabled_tools_are_filtered() {
+        use crate::config::AppConfig;
+        use crate::tools::get_enabled_tools;
+
+        // Nothing disabled: same list as get_all_tools
+        let config = AppConfig {
+            allowed_file_roots: Some(vec!["/home/user/notes".to_string()]),
+            ..AppConfig::default()
+        };
+        assert_eq!(get_enabled_tools(&config).len(), get_all_tools().len());
+
+        let config = AppConfig {
+            disabled_tools: Some(vec!["web_search".to_string(), "not_a_tool".to_string()]),
+            ..config
+        };
+        let names: Vec<String> = get_enabled_tools(&config)
+            .iter()
+            .map(|t| t.function.name.clone())
+            .collect();
+        assert_eq!(names.len(), get_all_tools().len() - 1);
+        assert!(!names.contains(&"web_search".to_string()));
+        assert!(names.contains(&"get_weather".to_string()));
+        assert!(names.contains(&"search_wikipedia".to_string()));
+    }
+
+    #[test]
+    fn test_read_local_file_needs_allowed_roots() {
+        use crate::config::AppConfig;
+        use crate::tools::get_enabled_tools;
+
+        let offers_file_tool =
+            |config: &AppConfig| get_enabled_tools(config).iter().any(|t| t.function.name == "read_local_file");
+        assert!(!offers_file_tool(&AppConfig::default()));
+        assert!(!offers_file_tool(&AppConfig {
+            allowed_file_roots: Some(vec!["  ".to_string()]),
+            ..AppConfig::default()
+        }));
+        assert!(offers_file_tool(&AppConfig {
+            allowed_file_roots: Some(vec!["/home/user/notes".to_string()]),
+            ..AppConfig::default()
+        }));
+    }
+
+    #[test]
+    fn test_disabled_tool_call_is_refused() {
+        use crate::config::AppConfig;
+        use crate::tools::check_tool_enabled;
+
+        let config = AppConfig {
+            disabled_tools: Some(vec!["web_search".to_string()]),
+            ..AppConfig::default()
+        };
+        let refusal = check_tool_enabled(&config, "web_search").unwrap_err();
+        assert!(refusal.starts_with("Error:"));
+        assert!(refusal.contains("web_search"));
+        assert!(refusal.contains("disabled"));
+
+        assert!(check_tool_enabled(&config, "get_weather").is_ok());
+        assert!(check_tool_enabled(&AppConfig::default(), "web_search").is_ok());
+    }
+
+    #[test]
+    fn test_tools_filtered_by_mode() {
+        use crate::config::AppConfig;
+        use crate::tools::{get_mode_tools, DEFAULT_RESEARCH_TOOLS};
+
+        let names = |config: &AppConfig, research: bool| -> Vec<String> {
+            get_mode_tools(config, research).iter().map(|t| t.function.name.clone()).collect()
+        };
+
+        // Defaults: everything in normal mode, search/read tools in research mode
+        let config = AppConfig {
+            allowed_file_roots: Some(vec!["/home/user/notes".to_string()]),
+            ..AppConfig::default()
+        };
+        assert_eq!(names(&config, false).len(), get_all_tools().len());
+        let research = names(&config, true);
+        assert_eq!(research.len(), DEFAULT_RESEARCH_TOOLS.len());
+        assert!(research.contains(&"web_search".to_string()));
+        assert!(!research.contains(&"get_weather".to_string()));
+        assert!(!research.contains(&"save_memory".to_string()));
+
+        // Configured lists replace the defaults, and disabled_tools still applies
+        let config = AppConfig {
+            research_tools: Some(vec!["get_weather".to_string(), "web_search".to_string()]),
+            normal_tools: Some(vec!["get_time".to_string(), "translate".to_string()]),
+            disabled_tools: Some(vec!["web_search".to_string()]),
+            ..AppConfig::default()
+        };
+        assert_eq!(names(&config, true), vec!["get_weather"]);
+        assert_eq!(names(&config, false), vec!["get_time", "translate"]);
+    }
 }
@@ -0,0 +1,163 @@
+/**
+ * Tool Stats Module
+ *
+ * Tracks per-tool usage counters for observability:
+ * - calls / errors: recorded by the agent's execute_tool wrapper
+ * - cache_hits / cache_misses: recorded by the cache layer
+ *
+ * Counters live in memory and are flushed to `tool_stats.json` every
+ * FLUSH_EVERY events (and whenever stats are read or the app exits) to
+ * keep writes cheap.
+ */
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, Runtime};
+
+/// Number of recorded events between disk flushes
+const FLUSH_EVERY: usize = 10;
+
+/// Counters for a single tool
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ToolCounters {
+    pub calls: u64,
+    pub errors: u64,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+}
+
+/// Kind of event being recorded for a tool
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToolEvent {
+    Call,
+    Error,
+    CacheHit,
+    CacheMiss,
+}
+
+/// Tool stats stored on disk
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ToolStats {
+    /// Map of tool name to its counters
+    pub tools: HashMap<String, ToolCounters>,
+}
+
+impl ToolStats {
+    /// Increment the counter matching `event` for a tool
+    pub fn record(&mut self, tool_name: &str, event: ToolEvent) {
+        let counters = self.tools.entry(tool_name.to_string()).or_default();
+        match event {
+            ToolEvent::Call => counters.calls += 1,
+            ToolEvent::Error => counters.errors += 1,
+            ToolEvent::CacheHit => counters.cache_hits += 1,
+            ToolEvent::CacheMiss => counters.cache_misses += 1,
+        }
+    }
+
+    /// Sum counters across all tools
+    pub fn totals(&self) -> ToolCounters {
+        self.tools.values().fold(ToolCounters::default(), |mut acc, c| {
+            acc.calls += c.calls;
+            acc.errors += c.errors;
+            acc.cache_hits += c.cache_hits;
+            acc.cache_misses += c.cache_misses;
+            acc
+        })
+    }
+}
+
+/// Heuristic check for tool results that represent a failure
+/// Tool results are plain strings, so errors are detected by their prefix
+pub fn is_error_result(result: &str) -> bool {
+    const ERROR_PREFIXES: &[&str] = &["Error", "Failed", "Unknown tool", "Memory refresh failed"];
+    ERROR_PREFIXES.iter().any(|p| result.starts_with(p))
+}
+
+// ============================================================================
+// Persistence
+// ============================================================================
+
+struct StatsState {
+    stats: ToolStats,
+    pending_writes: usize,
+}
+
+static STATS_STATE: Mutex<Option<StatsState>> = Mutex::new(None);
+
+fn get_stats_path<R: Runtime>(app_handle: &AppHandle<R>) -> Result<PathBuf, String> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data dir: {}", e))?;
+    Ok(app_data_dir.join("tool_stats.json"))
+}
+
+fn load_stats<R: Runtime>(app_handle: &AppHandle<R>) -> ToolStats {
+    match get_stats_path(app_handle) {
+        Ok(path) if path.exists() => fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default(),
+        _ => ToolStats::default(),
+    }
+}
+
+fn save_stats<R: Runtime>(app_handle: &AppHandle<R>, stats: &ToolStats) {
+    if let Ok(path) = get_stats_path(app_handle) {
+        if let Ok(content) = serde_json::to_string_pretty(stats) {
+            if let Err(e) = fs::write(&path, content) {
+                log::warn!("[ToolStats] Failed to write stats: {}", e);
+            }
+        }
+    }
+}
+
+/// Record a tool event, flushing to disk every FLUSH_EVERY events
+pub fn record_event<R: Runtime>(app_handle: &AppHandle<R>, tool_name: &str, event: ToolEvent) {
+    let Ok(mut guard) = STATS_STATE.lock() else {
+        return;
+    };
+    let state = guard.get_or_insert_with(|| StatsState {
+        stats: load_stats(app_handle),
+        pending_writes: 0,
+    });
+
+    state.stats.record(tool_name, event);
+    state.pending_writes += 1;
+
+    if state.pending_writes >= FLUSH_EVERY {
+        save_stats(app_handle, &state.stats);
+        state.pending_writes = 0;
+    }
+}
+
+/// Write any buffered updates to disk, e.g. before the app exits
+pub fn flush_stats<R: Runtime>(app_handle: &AppHandle<R>) {
+    let Ok(mut guard) = STATS_STATE.lock() else {
+        return;
+    };
+    if let Some(state) = guard.as_mut().filter(|s| s.pending_writes > 0) {
+        save_stats(app_handle, &state.stats);
+        state.pending_writes = 0;
+    }
+}
+
+/// Get per-tool counters, flushing any pending updates first
+pub fn get_tool_stats<R: Runtime>(app_handle: &AppHandle<R>) -> HashMap<String, ToolCounters> {
+    let Ok(mut guard) = STATS_STATE.lock() else {
+        return load_stats(app_handle).tools;
+    };
+    let state = guard.get_or_insert_with(|| StatsState {
+        stats: load_stats(app_handle),
+        pending_writes: 0,
+    });
+
+    if state.pending_writes > 0 {
+        save_stats(app_handle, &state.stats);
+        state.pending_writes = 0;
+    }
+
+    state.stats.tools.clone()
+}
@@ -1,19 +1,150 @@
 use crate::agent::{FunctionDefinition, ToolDefinition};
+use crate::config::AppConfig;
 use serde_json::json;
 
+/// Whether `name` is listed in the config's `disabled_tools`
+pub fn is_tool_disabled(config: &AppConfig, name: &str) -> bool {
+    config
+        .disabled_tools
+        .as_ref()
+        .is_some_and(|disabled| disabled.iter().any(|d| d.trim() == name))
+}
+
+/// Whether the settings a tool needs are present; unusable tools aren't offered
+pub fn is_tool_configured(config: &AppConfig, name: &str) -> bool {
+    match name {
+        "read_local_file" => config
+            .allowed_file_roots
+            .as_ref()
+            .is_some_and(|roots| roots.iter().any(|root| !root.trim().is_empty())),
+        _ => true,
+    }
+}
+
+/// All tools minus those disabled in config or missing the settings they need
+pub fn get_enabled_tools(config: &AppConfig) -> Vec<ToolDefinition> {
+    get_all_tools()
+        .into_iter()
+        .filter(|t| !is_tool_disabled(config, &t.function.name) && is_tool_configured(config, &t.function.name))
+        .collect()
+}
+
+/// Tools offered in research mode when `research_tools` isn't configured
+pub const DEFAULT_RESEARCH_TOOLS: &[&str] = &[
+    "search_wikipedia",
+    "read_wikipedia_section",
+    "search_arxiv",
+    "read_arxiv_paper",
+    "web_search",
+    "search_discussions",
+    "read_feed",
+    "read_github",
+    "read_local_file",
+    "read_topic_summary",
+    "list_known_topics",
+];
+
+/// Whether the mode's allow-list (`research_tools` / `normal_tools`) includes `name`
+pub fn is_tool_in_mode(config: &AppConfig, name: &str, is_research_mode: bool) -> bool {
+    let allowed = if is_research_mode {
+        &config.research_tools
+    } else {
+        &config.normal_tools
+    };
+    match allowed {
+        Some(list) => list.iter().any(|t| t.trim() == name),
+        None if is_research_mode => DEFAULT_RESEARCH_TOOLS.contains(&name),
+        None => true,
+    }
+}
+
+/// Enabled tools narrowed to the current mode's allow-list
+pub fn get_mode_tools(config: &AppConfig, is_research_mode: bool) -> Vec<ToolDefinition> {
+    get_enabled_tools(config)
+        .into_iter()
+        .filter(|t| is_tool_in_mode(config, &t.function.name, is_research_mode))
+        .collect()
+}
+
+/// Refuse a call to a disabled tool (models occasionally call tools they weren't offered)
+pub fn check_tool_enabled(config: &AppConfig, name: &str) -> Result<(), String> {
+    if is_tool_disabled(config, name) {
+        return Err(format!(
+            "Error: The {} tool is disabled in settings. Answer without it or use another tool.",
+            name
+        ));
+    }
+    Ok(())
+}
+
+/// Tools that persist to the memories dir; no-ops while incognito is active
+pub const MEMORY_WRITE_TOOLS: &[&str] = &[
+    "save_memory",
+    "update_topic_summary",
+    "save_url_summary",
+    "refresh_memories",
+];
+
+/// Benign reply for a memory-writing tool called during an incognito session
+pub fn incognito_skip_message(config: &AppConfig, name: &str) -> Option<String> {
+    if !config.incognito_mode.unwrap_or(false) || !MEMORY_WRITE_TOOLS.contains(&name) {
+        return None;
+    }
+    Some(format!(
+        "Skipped: {} is disabled in incognito mode. Nothing was saved.",
+        name
+    ))
+}
+
+/// Run a tool only if it is enabled and allowed under the current incognito setting
+pub async fn run_guarded_tool<F, Fut>(config: &AppConfig, name: &str, run: F) -> String
+where
+    F: FnOnce() -> Fut,
+    Fut: std::future::Future<Output = String>,
+{
+    if let Err(refusal) = check_tool_enabled(config, name) {
+        log::warn!("[Tool] Model called disabled tool {}", name);
+        return refusal;
+    }
+    if let Some(skip) = incognito_skip_message(config, name) {
+        log::info!("[Tool] Skipping {} in incognito mode", name);
+        return skip;
+    }
+    run().await
+}
+
 pub fn get_all_tools() -> Vec<ToolDefinition> {
     vec![
         ToolDefinition {
             tool_type: "function".to_string(),
             function: FunctionDefinition {
                 name: "get_weather".to_string(),
-                description: "Get current weather for a location. Returns temperature, conditions, and humidity.".to_string(),
+                description: "Get current weather for a location, or a daily forecast (high/low and conditions) when days > 1.".to_string(),
                 parameters: json!({
                     "type": "object",
                     "properties": {
-                        "location": { "type": "string", "description": "City name (e.g. 'Paris', 'London') or Zip code (e.g. '94102')" },
+                        "location": { "type": ["string", "null"], "description": "City name (e.g. 'Paris', 'London'), Zip code (e.g. '94102'), or 'here' for the user's current location" },
+                        "lat": { "type": ["number", "null"], "description": "Latitude, used with lon instead of a location name" },
+                        "lon": { "type": ["number", "null"], "description": "Longitude, used with lat instead of a location name" },
+                        "days": { "type": ["integer", "null"], "description": "Forecast length in days (1-7). Omit or 1 for current conditions." },
                     },
-                    "required": ["location"],
+                    "required": ["location", "lat", "lon", "days"],
+                    "additionalProperties": false
+                }),
+                strict: Some(true),
+            },
+        },
+        ToolDefinition {
+            tool_type: "function".to_string(),
+            function: FunctionDefinition {
+                name: "get_time".to_string(),
+                description: "Get the current local time and UTC offset in a timezone. Use for 'what time is it in X' and time zone conversions.".to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "timezone": { "type": "string", "description": "IANA timezone name, e.g. 'Asia/Tokyo', 'Europe/London', 'America/New_York'" },
+                    },
+                    "required": ["timezone"],
                     "additionalProperties": false
                 }),
                 strict: Some(true),
@@ -35,15 +166,32 @@ pub fn get_all_tools() -> Vec<ToolDefinition> {
                 strict: Some(true),
             },
         },
+        ToolDefinition {
+            tool_type: "function".to_string(),
+            function: FunctionDefinition {
+                name: "read_wikipedia_section".to_string(),
+                description: "Read a specific section of a Wikipedia article as plain text, for questions the summary doesn't answer. Omit the section (null) to get the article's list of sections first.".to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "title": { "type": "string", "description": "Exact Wikipedia article title, e.g. 'Alan Turing'" },
+                        "section": { "type": ["string", "null"], "description": "Section heading (e.g. 'Early life'), anchor, or number (e.g. '2.1'). Null to list sections." },
+                    },
+                    "required": ["title", "section"],
+                    "additionalProperties": false
+                }),
+                strict: Some(true),
+            },
+        },
         ToolDefinition {
             tool_type: "function".to_string(),
             function: FunctionDefinition {
                 name: "get_stock_price".to_string(),
-                description: "Get current stock price and basic financial data for a ticker symbol. Returns price, change, and volume.".to_string(),
+                description: "Get current stock price and basic financial data for a ticker symbol or company name. Returns the resolved ticker, price, and volume.".to_string(),
                 parameters: json!({
                     "type": "object",
                     "properties": {
-                        "symbol": { "type": "string", "description": "Stock ticker symbol, e.g. AAPL, GOOGL, MSFT" },
+                        "symbol": { "type": "string", "description": "Stock ticker symbol (e.g. AAPL, GOOGL, MSFT) or company name (e.g. 'Apple')" },
                     },
                     "required": ["symbol"],
                     "additionalProperties": false
@@ -60,8 +208,10 @@ pub fn get_all_tools() -> Vec<ToolDefinition> {
                     "type": "object",
                     "properties": {
                         "query": { "type": "string", "description": "Academic search query, e.g. 'transformer attention mechanism' or 'quantum computing'" },
+                        "category": { "type": ["string", "null"], "description": "Restrict to an arXiv category, e.g. 'cs.LG', 'cs.CL', 'quant-ph'. Null for all categories." },
+                        "max_age_days": { "type": ["integer", "null"], "description": "Only papers published in the last N days, newest first. Null for any date." },
                     },
-                    "required": ["query"],
+                    "required": ["query", "category", "max_age_days"],
                     "additionalProperties": false
                 }),
                 strict: Some(true),
@@ -92,8 +242,115 @@ pub fn get_all_tools() -> Vec<ToolDefinition> {
                     "type": "object",
                     "properties": {
                         "query": { "type": "string", "description": "Search query. Be specific: include year, team name, 'current', 'latest', or 'today' for time-sensitive queries." },
+                        "site": { "type": ["string", "null"], "description": "Restrict results to one domain, e.g. 'docs.rs'. Null for no restriction." },
+                        "freshness": { "type": ["string", "null"], "enum": ["day", "week", "month", null], "description": "Only return results from the past day, week, or month. Null for any time." },
                     },
-                    "required": ["query"],
+                    "required": ["query", "site", "freshness"],
+                    "additionalProperties": false
+                }),
+                strict: Some(true),
+            },
+        },
+        ToolDefinition {
+            tool_type: "function".to_string(),
+            function: FunctionDefinition {
+                name: "search_discussions".to_string(),
+                description: "Search community discussions on Hacker News or Reddit. Best for tech opinions, tool comparisons, and real-world experiences. Returns top threads with title, URL, score, and comment count.".to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "query": { "type": "string", "description": "Search query, e.g. 'tantivy vs meilisearch' or 'rust async runtime'" },
+                        "source": {
+                            "type": "string",
+                            "enum": ["hackernews", "reddit"],
+                            "description": "Where to search: 'hackernews' for tech/startup discussions, 'reddit' for broader communities"
+                        },
+                    },
+                    "required": ["query", "source"],
+                    "additionalProperties": false
+                }),
+                strict: Some(true),
+            },
+        },
+        ToolDefinition {
+            tool_type: "function".to_string(),
+            function: FunctionDefinition {
+                name: "read_feed".to_string(),
+                description: "Read the latest items from an RSS or Atom feed. Use for monitoring news sites and blogs. Returns title, link, published date, and a short summary per item.".to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "url": { "type": "string", "description": "Feed URL, e.g. 'https://blog.rust-lang.org/feed.xml'" },
+                        "limit": { "type": ["integer", "null"], "description": "Number of latest items to return (default 5, max 20)" },
+                    },
+                    "required": ["url", "limit"],
+                    "additionalProperties": false
+                }),
+                strict: Some(true),
+            },
+        },
+        ToolDefinition {
+            tool_type: "function".to_string(),
+            function: FunctionDefinition {
+                name: "translate".to_string(),
+                description: "Translate text into another language. Also reports the detected source language. Use when the user asks for a translation or what language something is in.".to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "text": { "type": "string", "description": "Text to translate" },
+                        "target_lang": { "type": "string", "description": "Language to translate into, e.g. 'English', 'Japanese', 'es'" },
+                    },
+                    "required": ["text", "target_lang"],
+                    "additionalProperties": false
+                }),
+                strict: Some(true),
+            },
+        },
+        ToolDefinition {
+            tool_type: "function".to_string(),
+            function: FunctionDefinition {
+                name: "format_code".to_string(),
+                description: "Format a code snippet with the standard local formatter (rustfmt, black, or a JSON pretty-printer). Returns the formatted code, or the syntax error if it doesn't parse. Use to check code before presenting it.".to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "code": { "type": "string", "description": "Source code to format" },
+                        "language": { "type": "string", "enum": ["rust", "python", "json"], "description": "Language of the code" },
+                    },
+                    "required": ["code", "language"],
+                    "additionalProperties": false
+                }),
+                strict: Some(true),
+            },
+        },
+        ToolDefinition {
+            tool_type: "function".to_string(),
+            function: FunctionDefinition {
+                name: "read_github".to_string(),
+                description: "Read a file or the README from a public GitHub repository. Use when discussing a library or project to get its documentation or source.".to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "repo": { "type": "string", "description": "Repository as 'owner/name', e.g. 'tauri-apps/tauri'" },
+                        "path": { "type": "string", "description": "File path within the repo (e.g. 'src/lib.rs' or 'docs/'). Use an empty string to get the README." },
+                    },
+                    "required": ["repo", "path"],
+                    "additionalProperties": false
+                }),
+                strict: Some(true),
+            },
+        },
+        ToolDefinition {
+            tool_type: "function".to_string(),
+            function: FunctionDefinition {
+                name: "read_local_file".to_string(),
+                description: "Read a text file from the user's computer. Only works inside folders the user has allowlisted in Settings. Use when the user refers to a local file by path.".to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "path": { "type": "string", "description": "Absolute path to the file, e.g. '/Users/me/notes/todo.md'" },
+                    },
+                    "required": ["path"],
                     "additionalProperties": false
                 }),
                 strict: Some(true),
@@ -138,6 +395,23 @@ pub fn get_all_tools() -> Vec<ToolDefinition> {
                 strict: Some(true),
             },
         },
+        ToolDefinition {
+            tool_type: "function".to_string(),
+            function: FunctionDefinition {
+                name: "save_url_summary".to_string(),
+                description: "Fetch a web page, summarize it, and save the summary as a topic for later retrieval (read-it-later). Use when the user asks to save or remember an article/URL under a topic.".to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "url": { "type": "string", "description": "Full URL of the page to save (http:// or https://)" },
+                        "topic": { "type": "string", "description": "Topic name to store the summary under (e.g., 'ML_papers')." },
+                    },
+                    "required": ["url", "topic"],
+                    "additionalProperties": false
+                }),
+                strict: Some(true),
+            },
+        },
         ToolDefinition {
             tool_type: "function".to_string(),
             function: FunctionDefinition {
@@ -154,6 +428,19 @@ pub fn get_all_tools() -> Vec<ToolDefinition> {
                 strict: Some(true),
             },
         },
+        ToolDefinition {
+            tool_type: "function".to_string(),
+            function: FunctionDefinition {
+                name: "list_known_topics".to_string(),
+                description: "List the saved topic summaries by name, each with a one-line preview. Use this to discover what you already know about the user before asking them, then read_topic_summary for details.".to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {},
+                    "additionalProperties": false
+                }),
+                strict: Some(true),
+            },
+        },
         ToolDefinition {
             tool_type: "function".to_string(),
             function: FunctionDefinition {
@@ -0,0 +1,544 @@
+/**
+ * Vector index module - approximate nearest-neighbor search over interaction embeddings
+ *
+ * Implements:
+ * - HNSW graph (instant-distance) over normalized embeddings, persisted next to the JSONL logs
+ * - Pending log of vectors written since the last build, scanned linearly at query time
+ * - Staleness checks (missing file, embedding model/dims change) so callers can fall back
+ *   to the linear cosine scan
+ */
+
+use instant_distance::{Builder, HnswMap, Point, Search};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::SystemTime;
+use tauri::{AppHandle, Manager, Runtime};
+
+/// HNSW graph file (in the interactions dir)
+pub const DENSE_INDEX_FILENAME: &str = "dense_index.json";
+/// Vectors appended since the last graph build, one JSON object per line
+pub const DENSE_PENDING_FILENAME: &str = "dense_index_pending.log";
+/// Fold the pending log into the graph once it reaches this many vectors
+pub const MAX_PENDING_VECTORS: usize = 256;
+/// Fixed seed so rebuilding the same data gives the same graph
+const BUILD_SEED: u64 = 0x5348_4152_44;
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+/// Unit-length embedding; distance is 1 - cosine similarity
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingPoint(Vec<f32>);
+
+impl EmbeddingPoint {
+    pub fn new(embedding: &[f32]) -> Self {
+        let norm = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm == 0.0 {
+            return Self(embedding.to_vec());
+        }
+        Self(embedding.iter().map(|x| x / norm).collect())
+    }
+
+    fn similarity(&self, other: &Self) -> f32 {
+        self.0.iter().zip(&other.0).map(|(a, b)| a * b).sum()
+    }
+}
+
+impl Point for EmbeddingPoint {
+    fn distance(&self, other: &Self) -> f32 {
+        1.0 - self.similarity(other)
+    }
+}
+
+/// A pending-log line: a vector written after the graph was built
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PendingVector {
+    doc_id: String,
+    embedding: Vec<f32>,
+}
+
+/// HNSW index mapping embeddings to interaction doc_ids (RFC3339 timestamps)
+#[derive(Serialize, Deserialize)]
+pub struct DenseIndex {
+    /// Embedding space (`EmbeddingSettings::space_id`) the vectors came from
+    pub model: String,
+    pub dims: usize,
+    /// None when built from zero vectors
+    graph: Option<HnswMap<EmbeddingPoint, String>>,
+    /// Loaded from the pending log, not serialized with the graph
+    #[serde(skip)]
+    pending: Vec<(String, EmbeddingPoint)>,
+}
+
+impl DenseIndex {
+    /// Build from (doc_id, embedding) pairs; vectors of other dimensionality are skipped
+    pub fn build(model: &str, dims: usize, entries: Vec<(String, Vec<f32>)>) -> Self {
+        let (points, doc_ids): (Vec<EmbeddingPoint>, Vec<String>) = entries
+            .into_iter()
+            .filter(|(_, emb)| emb.len() == dims)
+            .map(|(doc_id, emb)| (EmbeddingPoint::new(&emb), doc_id))
+            .unzip();
+
+        let graph = if points.is_empty() {
+            None
+        } else {
+            Some(Builder::default().seed(BUILD_SEED).build(points, doc_ids))
+        };
+
+        Self {
+            model: model.to_string(),
+            dims,
+            graph,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Vectors in the graph plus the pending log
+    pub fn len(&self) -> usize {
+        self.graph.as_ref().map_or(0, |g| g.values.len()) + self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether `doc_id` is in the graph or the pending log
+    pub fn contains(&self, doc_id: &str) -> bool {
+        self.graph.as_ref().is_some_and(|g| g.values.iter().any(|v| v == doc_id))
+            || self.pending.iter().any(|(id, _)| id == doc_id)
+    }
+
+    /// Whether queries embedded in the `model` space at `dims` can use this index
+    pub fn is_compatible(&self, model: &str, dims: usize) -> bool {
+        self.model == model && self.dims == dims
+    }
+
+    /// Add a vector without rebuilding the graph (searched linearly until the next build)
+    pub fn push_pending(&mut self, doc_id: &str, embedding: &[f32]) {
+        if embedding.len() == self.dims {
+            self.pending.push((doc_id.to_string(), EmbeddingPoint::new(embedding)));
+        }
+    }
+
+    /// Approximate top-`limit` doc_ids by cosine similarity, best first
+    pub fn search(&self, query: &[f32], limit: usize) -> Vec<(String, f32)> {
+        if query.len() != self.dims || limit == 0 {
+            return Vec::new();
+        }
+        let query = EmbeddingPoint::new(query);
+
+        let mut results: Vec<(String, f32)> = Vec::new();
+        if let Some(graph) = &self.graph {
+            let mut search = Search::default();
+            results.extend(
+                graph
+                    .search(&query, &mut search)
+                    .take(limit)
+                    .map(|item| (item.value.clone(), 1.0 - item.distance)),
+            );
+        }
+        results.extend(
+            self.pending
+                .iter()
+                .map(|(doc_id, point)| (doc_id.clone(), query.similarity(point))),
+        );
+
+        results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        results.truncate(limit);
+        results
+    }
+}
+
+/// Exact top-`limit` by cosine similarity (reference for the HNSW results)
+pub fn brute_force_search(
+    entries: &[(String, Vec<f32>)],
+    query: &[f32],
+    limit: usize,
+) -> Vec<(String, f32)> {
+    let query = EmbeddingPoint::new(query);
+    let mut results: Vec<(String, f32)> = entries
+        .iter()
+        .filter(|(_, emb)| emb.len() == query.0.len())
+        .map(|(doc_id, emb)| (doc_id.clone(), query.similarity(&EmbeddingPoint::new(emb))))
+        .collect();
+
+    results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    results.truncate(limit);
+    results
+}
+
+// ============================================================================
+// Persistence
+// ============================================================================
+
+static DENSE_INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+/// Hold this across a rebuild or pending-log append
+fn lock_dense_index() -> MutexGuard<'static, ()> {
+    DENSE_INDEX_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Last loaded graph, reused while neither file has changed on disk
+struct CachedIndex {
+    dir: PathBuf,
+    modified: (SystemTime, Option<SystemTime>),
+    index: Arc<DenseIndex>,
+}
+
+static LOADED_INDEX: Mutex<Option<CachedIndex>> = Mutex::new(None);
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).ok()?.modified().ok()
+}
+
+fn read_pending(path: &Path) -> Vec<PendingVector> {
+    let Ok(file) = fs::File::open(path) else {
+        return Vec::new();
+    };
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect()
+}
+
+/// Load the index (graph + pending log) from `dir`, or None when it hasn't been built
+pub fn load_dense_index_from(dir: &Path) -> Option<Arc<DenseIndex>> {
+    let graph_path = dir.join(DENSE_INDEX_FILENAME);
+    let pending_path = dir.join(DENSE_PENDING_FILENAME);
+    let modified = (modified_time(&graph_path)?, modified_time(&pending_path));
+
+    let mut cached = LOADED_INDEX.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(c) = cached.as_ref() {
+        if c.dir == dir && c.modified == modified {
+            return Some(c.index.clone());
+        }
+    }
+
+    let content = fs::read_to_string(&graph_path).ok()?;
+    let mut index: DenseIndex = match serde_json::from_str(&content) {
+        Ok(index) => index,
+        Err(e) => {
+            log::warn!("Dense index corrupted, falling back to linear scan: {}", e);
+            return None;
+        }
+    };
+    for pending in read_pending(&pending_path) {
+        index.push_pending(&pending.doc_id, &pending.embedding);
+    }
+
+    let index = Arc::new(index);
+    *cached = Some(CachedIndex {
+        dir: dir.to_path_buf(),
+        modified,
+        index: index.clone(),
+    });
+    Some(index)
+}
+
+/// All (doc_id, embedding) pairs in the interaction logs from `space` at the given dimensionality
+fn collect_embeddings(dir: &Path, space: &str, dims: usize) -> Result<Vec<(String, Vec<f32>)>, String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("Failed to read interactions dir: {}", e))?;
+
+    let mut embeddings = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|s| s.to_str()) != Some("jsonl") {
+            continue;
+        }
+        let Ok(file) = fs::File::open(&path) else {
+            continue;
+        };
+        for line in BufReader::new(file).lines().map_while(Result::ok) {
+            if let Ok(entry) = serde_json::from_str::<crate::interactions::InteractionEntry>(&line) {
+                if let Some(emb) = entry.embedding_in_space(space, dims) {
+                    embeddings.push((entry.ts.to_rfc3339(), emb.clone()));
+                }
+            }
+        }
+    }
+    Ok(embeddings)
+}
+
+fn rebuild_locked(dir: &Path) -> Result<usize, String> {
+    let settings = crate::interactions::embedding_settings();
+    let dims = settings.dims as usize;
+    let space = settings.space_id();
+    let index = DenseIndex::build(&space, dims, collect_embeddings(dir, &space, dims)?);
+
+    let json = serde_json::to_string(&index)
+        .map_err(|e| format!("Failed to serialize dense index: {}", e))?;
+    crate::retrieval::write_atomic(&dir.join(DENSE_INDEX_FILENAME), json)
+        .map_err(|e| format!("Failed to write dense index: {}", e))?;
+
+    let pending_path = dir.join(DENSE_PENDING_FILENAME);
+    if pending_path.exists() {
+        fs::remove_file(&pending_path)
+            .map_err(|e| format!("Failed to clear dense index pending log: {}", e))?;
+    }
+
+    log::info!("Rebuilt dense index with {} vectors", index.len());
+    Ok(index.len())
+}
+
+/// Rebuild the graph from every embedded interaction in `dir` and clear the pending log
+pub fn rebuild_dense_index_in_dir(dir: &Path) -> Result<usize, String> {
+    let _guard = lock_dense_index();
+    rebuild_locked(dir)
+}
+
+/// Record a newly logged vector. No-op until the index has been built (searches
+/// fall back to the linear scan). Returns true once the pending log is full; the
+/// caller then hands the rebuild to `schedule_pending_fold` rather than stalling on it.
+pub fn append_to_dense_index_in_dir(dir: &Path, doc_id: &str, embedding: &[f32]) -> Result<bool, String> {
+    let _guard = lock_dense_index();
+    if !dir.join(DENSE_INDEX_FILENAME).exists() {
+        return Ok(false);
+    }
+
+    let pending_path = dir.join(DENSE_PENDING_FILENAME);
+    let line = serde_json::to_string(&PendingVector {
+        doc_id: doc_id.to_string(),
+        embedding: embedding.to_vec(),
+    })
+    .map_err(|e| format!("Failed to serialize pending vector: {}", e))?;
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&pending_path)
+        .map_err(|e| format!("Failed to open dense index pending log: {}", e))?;
+    writeln!(file, "{}", line).map_err(|e| format!("Failed to append pending vector: {}", e))?;
+    drop(file);
+
+    Ok(read_pending(&pending_path).len() >= MAX_PENDING_VECTORS)
+}
+
+/// Fold a full pending log into the graph. Returns false when there was nothing to do
+/// (e.g. another fold or a rebuild got there first).
+pub fn fold_pending_in_dir(dir: &Path) -> Result<bool, String> {
+    let _guard = lock_dense_index();
+    if read_pending(&dir.join(DENSE_PENDING_FILENAME)).len() < MAX_PENDING_VECTORS {
+        return Ok(false);
+    }
+    rebuild_locked(dir)?;
+    Ok(true)
+}
+
+/// Set while a background fold is queued or running, so a burst of appends spawns one
+static FOLD_SCHEDULED: AtomicBool = AtomicBool::new(false);
+
+/// Run `fold_pending_in_dir` on the blocking pool; the full rebuild is too slow for a turn
+pub fn schedule_pending_fold(dir: &Path) {
+    if FOLD_SCHEDULED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let dir = dir.to_path_buf();
+    tauri::async_runtime::spawn_blocking(move || {
+        let result = fold_pending_in_dir(&dir);
+        FOLD_SCHEDULED.store(false, Ordering::SeqCst);
+        if let Err(e) = result {
+            log::warn!("Failed to fold dense index pending log: {}", e);
+        }
+    });
+}
+
+/// Drop deleted interactions by rebuilding from the (already pruned) logs.
+/// Returns false without rebuilding when the index is missing or holds none of them.
+pub fn remove_from_dense_index_in_dir(dir: &Path, doc_ids: &[String]) -> Result<bool, String> {
+    let _guard = lock_dense_index();
+    let Some(index) = load_dense_index_from(dir) else {
+        return Ok(false);
+    };
+    if !doc_ids.iter().any(|id| index.contains(id)) {
+        return Ok(false);
+    }
+    rebuild_locked(dir)?;
+    Ok(true)
+}
+
+/// Approximate dense search, or None when the index is missing or was built
+/// with a different embedding model/dims (callers fall back to the linear scan)
+pub fn search_dense_index_in_dir(dir: &Path, query: &[f32], limit: usize) -> Option<Vec<(String, f32)>> {
+    let index = load_dense_index_from(dir)?;
+    let settings = crate::interactions::embedding_settings();
+    let space = settings.space_id();
+    if !index.is_compatible(&space, query.len()) || query.len() != settings.dims as usize {
+        log::debug!("[Dense] Index is stale for {} @ {} dims", space, query.len());
+        return None;
+    }
+    Some(index.search(query, limit))
+}
+
+fn get_interactions_dir<R: Runtime>(app_handle: &AppHandle<R>) -> Result<PathBuf, String> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data dir: {}", e))?;
+    Ok(app_data_dir.join("interactions"))
+}
+
+/// Rebuild the dense index from the interaction logs
+pub fn rebuild_dense_index<R: Runtime>(app_handle: &AppHandle<R>) -> Result<usize, String> {
+    let dir = get_interactions_dir(app_handle)?;
+    if !dir.exists() {
+        return Ok(0);
+    }
+    rebuild_dense_index_in_dir(&dir)
+}
+
+/// Build the index at startup if it doesn't exist yet or no longer matches the embedding settings
+pub fn ensure_dense_index<R: Runtime>(app_handle: &AppHandle<R>) -> Result<(), String> {
+    let dir = get_interactions_dir(app_handle)?;
+    if !dir.exists() {
+        return Ok(());
+    }
+    let settings = crate::interactions::embedding_settings();
+    let up_to_date = load_dense_index_from(&dir)
+        .is_some_and(|index| index.is_compatible(&settings.space_id(), settings.dims as usize));
+    if !up_to_date {
+        rebuild_dense_index_in_dir(&dir)?;
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    /// Deterministic pseudo-random vectors (xorshift) so tests don't need a rand dependency
+    fn random_vectors(count: usize, dims: usize, seed: u64) -> Vec<(String, Vec<f32>)> {
+        let mut state = seed;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % 2000) as f32 / 1000.0 - 1.0
+        };
+        (0..count)
+            .map(|i| (format!("doc_{}", i), (0..dims).map(|_| next()).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn test_hnsw_top_k_overlaps_brute_force() {
+        let entries = random_vectors(1000, 32, 42);
+        let index = DenseIndex::build("test-model", 32, entries.clone());
+        assert_eq!(index.len(), 1000);
+
+        let k = 10;
+        let queries = random_vectors(20, 32, 7);
+        let mut overlap = 0;
+        for (_, query) in &queries {
+            let exact: HashSet<String> =
+                brute_force_search(&entries, query, k).into_iter().map(|(id, _)| id).collect();
+            let approx = index.search(query, k);
+            assert_eq!(approx.len(), k);
+            overlap += approx.iter().filter(|(id, _)| exact.contains(id)).count();
+        }
+
+        let recall = overlap as f32 / (k * queries.len()) as f32;
+        assert!(recall >= 0.9, "HNSW recall@{} too low: {}", k, recall);
+    }
+
+    #[test]
+    fn test_pending_vectors_are_searchable() {
+        let mut index = DenseIndex::build("test-model", 3, vec![("a".to_string(), vec![1.0, 0.0, 0.0])]);
+        index.push_pending("b", &[0.0, 1.0, 0.0]);
+        index.push_pending("wrong_dims", &[0.0, 1.0]);
+        assert_eq!(index.len(), 2);
+
+        let results = index.search(&[0.1, 0.9, 0.0], 2);
+        assert_eq!(results[0].0, "b");
+        assert_eq!(results[1].0, "a");
+        assert!(results[0].1 > results[1].1);
+
+        // Empty graph still serves pending vectors
+        let mut empty = DenseIndex::build("test-model", 3, Vec::new());
+        assert!(empty.is_empty());
+        empty.push_pending("c", &[0.0, 0.0, 1.0]);
+        assert_eq!(empty.search(&[0.0, 0.0, 1.0], 5)[0].0, "c");
+    }
+
+    #[test]
+    fn test_missing_or_stale_index_falls_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let dims = crate::interactions::embedding_settings().dims as usize;
+        let query = vec![0.5; dims];
+
+        // Never built: no index, and appends don't create a partial one
+        assert!(search_dense_index_in_dir(dir.path(), &query, 5).is_none());
+        append_to_dense_index_in_dir(dir.path(), "2025-01-01T00:00:00+00:00", &query).unwrap();
+        assert!(!dir.path().join(DENSE_PENDING_FILENAME).exists());
+
+        // Built empty, then kept in sync by appends
+        rebuild_dense_index_in_dir(dir.path()).unwrap();
+        append_to_dense_index_in_dir(dir.path(), "2025-01-01T00:00:00+00:00", &query).unwrap();
+        let results = search_dense_index_in_dir(dir.path(), &query, 5).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, "2025-01-01T00:00:00+00:00");
+
+        // Query at another dimensionality can't use the index
+        assert!(search_dense_index_in_dir(dir.path(), &[0.5; 3], 5).is_none());
+    }
+
+    #[test]
+    fn test_rebuild_skips_vectors_from_another_embedding_space() {
+        let dir = tempfile::tempdir().unwrap();
+        let settings = crate::interactions::embedding_settings();
+        let dims = settings.dims as usize;
+        let line = |minute: u32, model: Option<&str>| {
+            serde_json::to_string(&crate::interactions::InteractionEntry {
+                ts: chrono::TimeZone::with_ymd_and_hms(&chrono::Utc, 2025, 1, 1, 0, minute, 0).unwrap(),
+                role: "user".to_string(),
+                content: format!("entry {}", minute),
+                embedding: Some(vec![0.5; dims]),
+                embedding_model: model.map(str::to_string),
+            })
+            .unwrap()
+        };
+        let space = settings.space_id();
+        let log = [line(0, Some(&space)), line(1, Some("openai:other-model")), line(2, None)].join("\n");
+        fs::write(dir.path().join("interactions-2025-01-01.jsonl"), log + "\n").unwrap();
+
+        // Same dimensionality, different model: not mixed into the index
+        let ids: Vec<String> = collect_embeddings(dir.path(), &space, dims)
+            .unwrap()
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(ids, vec!["2025-01-01T00:00:00+00:00", "2025-01-01T00:02:00+00:00"]);
+        assert_eq!(rebuild_dense_index_in_dir(dir.path()).unwrap(), 2);
+        assert!(load_dense_index_from(dir.path()).unwrap().is_compatible(&space, dims));
+        assert!(!load_dense_index_from(dir.path()).unwrap().is_compatible(&settings.model, dims));
+    }
+
+    #[test]
+    fn test_full_pending_log_is_folded_on_request() {
+        let dir = tempfile::tempdir().unwrap();
+        let dims = crate::interactions::embedding_settings().dims as usize;
+        rebuild_dense_index_in_dir(dir.path()).unwrap();
+        assert!(!fold_pending_in_dir(dir.path()).unwrap());
+
+        let due: Vec<bool> = (0..MAX_PENDING_VECTORS)
+            .map(|i| append_to_dense_index_in_dir(dir.path(), &format!("doc{}", i), &vec![0.5; dims]).unwrap())
+            .collect();
+        // Appends only report that a fold is due; the graph isn't rebuilt inline
+        assert_eq!(due.iter().filter(|&&d| d).count(), 1);
+        assert!(due[MAX_PENDING_VECTORS - 1]);
+        assert_eq!(read_pending(&dir.path().join(DENSE_PENDING_FILENAME)).len(), MAX_PENDING_VECTORS);
+
+        assert!(fold_pending_in_dir(dir.path()).unwrap());
+        assert!(!dir.path().join(DENSE_PENDING_FILENAME).exists());
+    }
+}